@@ -0,0 +1,202 @@
+// Artists endpoints - browse music libraries by artist
+//
+// Artists are MusicArtist items (see scanner::music). /Artists lists every
+// artist, including those only credited on tracks, while /Artists/AlbumArtists
+// lists the ones with albums of their own, which is what music clients show as
+// their artist list. An artist's albums and tracks are then listed through
+// /Items with albumArtistIds, artistIds or parentId.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::db::item_query::{ItemQuery, ItemSort, SortOrder};
+use crate::{models::MediaItem, scanner::music, services::auth, AppState};
+
+use super::items::{
+    authorize_user_id, batch_get_child_counts, batch_get_image_tags, batch_get_user_data,
+    media_item_to_dto, BaseItemDto, ItemsResponse,
+};
+use super::users::parse_emby_auth_header;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_artists))
+        .route("/AlbumArtists", get(get_album_artists))
+        .route("/:name", get(get_artist))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistsQuery {
+    pub parent_id: Option<String>,
+    pub search_term: Option<String>,
+    pub is_favorite: Option<bool>,
+    pub sort_order: Option<String>,
+    pub start_index: Option<i32>,
+    pub limit: Option<i32>,
+    pub user_id: Option<String>,
+}
+
+async fn require_auth(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<crate::models::User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+/// GET /Artists - all artists, album artists and track artists alike
+async fn get_artists(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ArtistsQuery>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    list_artists(&state, &user, query, false).await
+}
+
+/// GET /Artists/AlbumArtists - artists with albums of their own
+async fn get_album_artists(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ArtistsQuery>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    list_artists(&state, &user, query, true).await
+}
+
+async fn list_artists(
+    state: &AppState,
+    user: &crate::models::User,
+    query: ArtistsQuery,
+    album_artists_only: bool,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let user_id = authorize_user_id(user, query.user_id.as_deref())?;
+    let start_index = query.start_index.unwrap_or(0);
+    let limit = query.limit.unwrap_or(100).min(1000);
+
+    let access = crate::db::get_library_access(&state.db, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut item_query = ItemQuery::new()
+        .include_types(&["MusicArtist"])
+        .visible_to(&user_id);
+    if let Some(ref access) = access {
+        item_query = item_query.in_libraries(access);
+    }
+    if let Some(ref parent_id) = query.parent_id {
+        item_query = item_query.ancestor(parent_id);
+    }
+    if let Some(ref term) = query.search_term {
+        item_query = item_query.search_term(term);
+    }
+    if query.is_favorite.unwrap_or(false) {
+        item_query = item_query.favorites_of(&user_id);
+    }
+    if album_artists_only {
+        item_query = item_query.has_children();
+    }
+
+    let items = item_query
+        .clone()
+        .sort(
+            ItemSort::from_sort_by("SortName"),
+            SortOrder::from_sort_order(query.sort_order.as_deref()),
+        )
+        .limit(limit)
+        .offset(start_index)
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let total = item_query
+        .count(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ItemsResponse {
+        items: artist_dtos(state, &user_id, &items).await,
+        total_record_count: total,
+        start_index,
+    }))
+}
+
+/// GET /Artists/:name - an artist by name, in any library the user can see
+async fn get_artist(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(query): Query<ArtistsQuery>,
+) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    let user_id = authorize_user_id(&user, query.user_id.as_deref())?;
+
+    let access = crate::db::get_library_access(&state.db, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let library_ids: Vec<String> = match access {
+        Some(access) => access,
+        None => sqlx::query_scalar("SELECT id FROM libraries WHERE library_type = 'music'")
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+    };
+
+    // Artist IDs are derived from their names, so no search by name is needed
+    let mut artist = None;
+    for library_id in &library_ids {
+        artist = sqlx::query_as::<_, MediaItem>(
+            "SELECT * FROM media_items WHERE id = ? AND item_type = 'MusicArtist'
+               AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)",
+        )
+        .bind(music::artist_id(library_id, &name))
+        .bind(&user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if artist.is_some() {
+            break;
+        }
+    }
+    let items: Vec<MediaItem> = artist.into_iter().collect();
+
+    artist_dtos(&state, &user_id, &items)
+        .await
+        .pop()
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "Artist not found".to_string()))
+}
+
+/// DTOs of artist items, with their album counts, images and user data
+async fn artist_dtos(state: &AppState, user_id: &str, items: &[MediaItem]) -> Vec<BaseItemDto> {
+    let ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+    let (child_counts, image_tags, user_data) = tokio::join!(
+        batch_get_child_counts(&state.db, &ids),
+        batch_get_image_tags(&state.db, &ids),
+        batch_get_user_data(&state.db, user_id, &ids),
+    );
+    items
+        .iter()
+        .map(|item| {
+            media_item_to_dto(
+                item,
+                child_counts.get(&item.id).copied(),
+                None,
+                image_tags.get(&item.id).cloned(),
+                Some(user_data.get(&item.id).cloned().unwrap_or_default()),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,707 @@
+// Audio streaming API (direct stream or on-the-fly transcode to mp3/aac/opus) and lyrics
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, process::Stdio, sync::Arc};
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    models::MediaItem,
+    services::{auth, lyrics, mediainfo},
+    time::Ticks,
+    AppState,
+};
+
+use super::users::parse_emby_auth_header;
+use super::videos::serve_file;
+
+/// Bitrate used when the client doesn't ask for one (bits per second)
+const DEFAULT_AUDIO_BITRATE: u32 = 192_000;
+const MIN_AUDIO_BITRATE: u32 = 32_000;
+const MAX_AUDIO_BITRATE: u32 = 320_000;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:id/stream", get(stream_audio))
+        .route("/:id/stream.:container", get(stream_audio))
+        // Finamp and other music clients use the universal endpoint
+        .route("/:id/universal", get(universal_audio))
+        .route("/:id/universal.:container", get(universal_audio))
+        .route("/:id/Lyrics", get(get_lyrics))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AudioPath {
+    id: String,
+    #[serde(default)]
+    container: Option<String>,
+}
+
+/// Query parameters shared by /stream and /universal
+///
+/// Clients disagree on casing (Finamp sends PascalCase, web sends camelCase),
+/// so keys are matched case-insensitively.
+#[derive(Debug, Default)]
+struct AudioStreamQuery {
+    api_key: Option<String>,
+    static_stream: bool,
+    container: Option<String>,
+    audio_codec: Option<String>,
+    audio_bit_rate: Option<u32>,
+    max_streaming_bitrate: Option<u32>,
+    max_audio_channels: Option<u32>,
+    transcoding_container: Option<String>,
+    start_time_ticks: Option<i64>,
+}
+
+impl AudioStreamQuery {
+    fn from_params(params: HashMap<String, String>) -> Self {
+        let params: HashMap<String, String> = params
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), v))
+            .filter(|(_, v)| !v.is_empty())
+            .collect();
+
+        Self {
+            api_key: params.get("api_key").or(params.get("apikey")).cloned(),
+            static_stream: params
+                .get("static")
+                .is_some_and(|v| v.eq_ignore_ascii_case("true")),
+            container: params.get("container").cloned(),
+            audio_codec: params.get("audiocodec").cloned(),
+            audio_bit_rate: params.get("audiobitrate").and_then(|v| v.parse().ok()),
+            max_streaming_bitrate: params
+                .get("maxstreamingbitrate")
+                .and_then(|v| v.parse().ok()),
+            max_audio_channels: params.get("maxaudiochannels").and_then(|v| v.parse().ok()),
+            transcoding_container: params.get("transcodingcontainer").cloned(),
+            start_time_ticks: params.get("starttimeticks").and_then(|v| v.parse().ok()),
+        }
+    }
+}
+
+/// Output formats we can transcode to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TranscodeCodec {
+    Mp3,
+    Aac,
+    Opus,
+}
+
+impl TranscodeCodec {
+    /// Map a codec or container name from a client request
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "mp3" => Some(Self::Mp3),
+            "aac" | "m4a" | "adts" => Some(Self::Aac),
+            "opus" | "ogg" | "oga" | "webm" | "webma" => Some(Self::Opus),
+            _ => None,
+        }
+    }
+
+    /// ffmpeg encoder and muxer arguments
+    fn ffmpeg_args(self) -> [&'static str; 4] {
+        match self {
+            Self::Mp3 => ["-c:a", "libmp3lame", "-f", "mp3"],
+            Self::Aac => ["-c:a", "aac", "-f", "adts"],
+            Self::Opus => ["-c:a", "libopus", "-f", "ogg"],
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Aac => "audio/aac",
+            Self::Opus => "audio/ogg",
+        }
+    }
+}
+
+/// How a request should be served
+#[derive(Debug, PartialEq)]
+enum StreamPlan {
+    Direct,
+    Transcode { codec: TranscodeCodec, bitrate: u32 },
+}
+
+async fn require_auth(
+    state: &AppState,
+    headers: &HeaderMap,
+    query_api_key: Option<&str>,
+) -> Result<crate::models::User, (StatusCode, String)> {
+    // Music clients pass api_key in the URL since players can't set headers
+    let token = if let Some(key) = query_api_key {
+        Some(key.to_string())
+    } else {
+        parse_emby_auth_header(headers).and_then(|(_, _, _, t)| t)
+    };
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+/// Get the MIME type for an audio file based on extension
+pub(crate) fn get_audio_content_type(path: &str) -> &'static str {
+    let ext = path.rsplit('.').next().unwrap_or("").to_lowercase();
+    match ext.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" | "m4b" | "mp4" => "audio/mp4",
+        "aac" => "audio/aac",
+        "flac" => "audio/flac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "wav" => "audio/wav",
+        "webm" | "webma" => "audio/webm",
+        "wma" => "audio/x-ms-wma",
+        _ => "application/octet-stream",
+    }
+}
+
+fn file_extension(path: &str) -> String {
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+fn clamp_bitrate(bitrate: Option<u32>) -> u32 {
+    bitrate
+        .unwrap_or(DEFAULT_AUDIO_BITRATE)
+        .clamp(MIN_AUDIO_BITRATE, MAX_AUDIO_BITRATE)
+}
+
+/// Rough source bitrate from file size and runtime, used to honour MaxStreamingBitrate
+async fn estimate_bitrate(item: &MediaItem, file_path: &str) -> Option<u64> {
+    let seconds = Ticks(item.runtime_ticks?).whole_seconds();
+    if seconds <= 0 {
+        return None;
+    }
+    let size = tokio::fs::metadata(file_path).await.ok()?.len();
+    Some(size * 8 / seconds as u64)
+}
+
+/// Decide between direct play and transcoding for /Audio/:id/stream
+///
+/// Audio files are served as-is unless the client asks for a different codec or a
+/// bitrate. Video files always go through ffmpeg so only the audio track is sent.
+fn plan_stream(item: &MediaItem, file_path: &str, query: &AudioStreamQuery) -> StreamPlan {
+    let is_audio_file = item.item_type == "Audio";
+    if query.static_stream && is_audio_file {
+        return StreamPlan::Direct;
+    }
+
+    let requested = query
+        .audio_codec
+        .as_deref()
+        .or(query.container.as_deref())
+        .and_then(|c| c.split(',').find_map(TranscodeCodec::parse));
+
+    let source_matches = match requested {
+        Some(codec) => TranscodeCodec::parse(&file_extension(file_path)) == Some(codec),
+        None => true,
+    };
+
+    if is_audio_file && source_matches && query.audio_bit_rate.is_none() {
+        return StreamPlan::Direct;
+    }
+
+    StreamPlan::Transcode {
+        codec: requested.unwrap_or(TranscodeCodec::Mp3),
+        bitrate: clamp_bitrate(query.audio_bit_rate),
+    }
+}
+
+/// Decide between direct play and transcoding for /Audio/:id/universal
+///
+/// `Container` lists what the client can play, e.g. "opus,webm|opus,mp3,aac,flac"
+/// (entries may carry a "|codec" suffix). The source file is sent directly when its
+/// extension is in that list and it fits within MaxStreamingBitrate.
+fn plan_universal(
+    item: &MediaItem,
+    file_path: &str,
+    query: &AudioStreamQuery,
+    source_bitrate: Option<u64>,
+) -> StreamPlan {
+    let supported: Vec<String> = query
+        .container
+        .as_deref()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| entry.split('|').next())
+        .map(|c| c.trim().to_lowercase())
+        .filter(|c| !c.is_empty())
+        .collect();
+
+    let ext = file_extension(file_path);
+    let container_ok = supported.is_empty() || supported.contains(&ext);
+    let bitrate_ok = match (query.max_streaming_bitrate, source_bitrate) {
+        (Some(max), Some(source)) => source <= max as u64,
+        _ => true,
+    };
+
+    if item.item_type == "Audio" && (query.static_stream || (container_ok && bitrate_ok)) {
+        return StreamPlan::Direct;
+    }
+
+    // Prefer the explicit codec, then the transcoding container, then the first
+    // supported container we know how to produce
+    let codec = query
+        .audio_codec
+        .as_deref()
+        .and_then(|c| c.split(',').find_map(TranscodeCodec::parse))
+        .or_else(|| {
+            query
+                .transcoding_container
+                .as_deref()
+                .and_then(TranscodeCodec::parse)
+        })
+        .or_else(|| supported.iter().find_map(|c| TranscodeCodec::parse(c)))
+        .unwrap_or(TranscodeCodec::Aac);
+
+    let bitrate = query
+        .audio_bit_rate
+        .or(query.max_streaming_bitrate)
+        .map(|b| b.min(MAX_AUDIO_BITRATE));
+
+    StreamPlan::Transcode {
+        codec,
+        bitrate: clamp_bitrate(bitrate),
+    }
+}
+
+/// Pipe the first audio track of a file through ffmpeg
+///
+/// HEAD requests (clients probing the content type) get the headers without
+/// starting ffmpeg.
+fn transcode_response(
+    method: &Method,
+    file_path: &str,
+    codec: TranscodeCodec,
+    bitrate: u32,
+    query: &AudioStreamQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, codec.content_type())
+        .header(header::ACCEPT_RANGES, "none")
+        .header(header::CACHE_CONTROL, "no-cache");
+    if method == Method::HEAD {
+        return Ok(response.body(Body::empty()).unwrap());
+    }
+
+    let mut cmd = Command::new(mediainfo::find_ffmpeg());
+    cmd.args(["-hide_banner", "-loglevel", "error"]);
+
+    if let Some(ticks) = query.start_time_ticks.filter(|t| *t > 0) {
+        cmd.args(["-ss", &Ticks(ticks).to_ffmpeg()]);
+    }
+
+    cmd.args(["-i", file_path, "-map", "0:a:0", "-vn", "-sn", "-dn"]);
+
+    if let Some(channels) = query.max_audio_channels.filter(|c| *c > 0) {
+        cmd.args(["-ac", &channels.to_string()]);
+    }
+
+    cmd.args(codec.ffmpeg_args());
+    cmd.args(["-b:a", &bitrate.to_string(), "pipe:1"]);
+
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to run ffmpeg: {}", e),
+            )
+        })?;
+
+    let stdout = child.stdout.take().ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "ffmpeg produced no output".to_string(),
+        )
+    })?;
+
+    tracing::debug!(
+        "Transcoding audio for {} to {:?} at {} bps",
+        file_path,
+        codec,
+        bitrate
+    );
+
+    // The stream owns the child so ffmpeg is killed when the client disconnects
+    let stream = ReaderStream::new(stdout).map(move |chunk| {
+        let _ = &child;
+        chunk
+    });
+
+    Ok(response.body(Body::from_stream(stream)).unwrap())
+}
+
+async fn load_item(
+    state: &AppState,
+    id: &str,
+) -> Result<(MediaItem, String), (StatusCode, String)> {
+    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+
+    let file_path = item
+        .path
+        .clone()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item has no file path".to_string()))?;
+
+    if !tokio::fs::try_exists(&file_path).await.unwrap_or(false) {
+        return Err((StatusCode::NOT_FOUND, "File not found".to_string()));
+    }
+
+    Ok((item, file_path))
+}
+
+/// GET /Audio/:id/stream - Stream audio directly, or transcoded when a codec/bitrate is requested
+async fn stream_audio(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    Path(path_params): Path<AudioPath>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, (StatusCode, String)> {
+    let mut query = AudioStreamQuery::from_params(params);
+    require_auth(&state, &headers, query.api_key.as_deref()).await?;
+
+    // /stream.mp3 is equivalent to ?container=mp3
+    if path_params.container.is_some() {
+        query.container = path_params.container;
+    }
+
+    let (item, file_path) = load_item(&state, &path_params.id).await?;
+
+    match plan_stream(&item, &file_path, &query) {
+        StreamPlan::Direct => {
+            serve_file(&headers, &file_path, get_audio_content_type(&file_path)).await
+        }
+        StreamPlan::Transcode { codec, bitrate } => {
+            transcode_response(&method, &file_path, codec, bitrate, &query)
+        }
+    }
+}
+
+/// GET /Audio/:id/universal - Direct play if the client supports the source, otherwise transcode
+async fn universal_audio(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    Path(path_params): Path<AudioPath>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, (StatusCode, String)> {
+    let mut query = AudioStreamQuery::from_params(params);
+    require_auth(&state, &headers, query.api_key.as_deref()).await?;
+
+    if query.transcoding_container.is_none() {
+        query.transcoding_container = path_params.container;
+    }
+
+    let (item, file_path) = load_item(&state, &path_params.id).await?;
+    let source_bitrate = estimate_bitrate(&item, &file_path).await;
+
+    match plan_universal(&item, &file_path, &query, source_bitrate) {
+        StreamPlan::Direct => {
+            serve_file(&headers, &file_path, get_audio_content_type(&file_path)).await
+        }
+        StreamPlan::Transcode { codec, bitrate } => {
+            transcode_response(&method, &file_path, codec, bitrate, &query)
+        }
+    }
+}
+
+// =============================================================================
+// Lyrics
+// =============================================================================
+
+#[derive(Debug, Deserialize)]
+pub struct LyricsQuery {
+    #[serde(rename = "api_key")]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LyricDto {
+    pub metadata: LyricMetadataDto,
+    pub lyrics: Vec<LyricLineDto>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LyricMetadataDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artist: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    pub is_synced: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LyricLineDto {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<i64>,
+}
+
+impl From<lyrics::Lyrics> for LyricDto {
+    fn from(parsed: lyrics::Lyrics) -> Self {
+        Self {
+            metadata: LyricMetadataDto {
+                artist: parsed.artist,
+                album: parsed.album,
+                title: parsed.title,
+                author: parsed.author,
+                length: parsed.length_ticks,
+                by: parsed.by,
+                offset: parsed.offset_ticks,
+                is_synced: parsed.is_synced,
+            },
+            lyrics: parsed
+                .lines
+                .into_iter()
+                .map(|line| LyricLineDto {
+                    text: line.text,
+                    start: line.start_ticks,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// GET /Audio/:id/Lyrics - Get synced (LRC) or plain lyrics for an item
+///
+/// Sidecar files are re-read on every request so edits show up immediately;
+/// embedded lyrics are cached in the database to avoid probing the file again.
+async fn get_lyrics(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<LyricsQuery>,
+) -> Result<Json<LyricDto>, (StatusCode, String)> {
+    require_auth(&state, &headers, query.api_key.as_deref()).await?;
+
+    let (_item, file_path) = load_item(&state, &id).await?;
+    let file_path = std::path::Path::new(&file_path);
+
+    let stored = crate::db::get_lyrics(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let content = match stored {
+        Some((content, source))
+            if source == lyrics::LyricsSource::Embedded.as_str()
+                && lyrics::find_sidecar(file_path).is_none() =>
+        {
+            content
+        }
+        _ => {
+            let (content, source) = lyrics::discover_lyrics(file_path)
+                .await
+                .ok_or_else(|| (StatusCode::NOT_FOUND, "No lyrics found".to_string()))?;
+
+            if let Err(e) = crate::db::save_lyrics(&state.db, &id, &content, source.as_str()).await
+            {
+                tracing::warn!("Failed to store lyrics for {}: {}", id, e);
+            }
+            content
+        }
+    };
+
+    Ok(Json(lyrics::parse_lyrics(&content).into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(item_type: &str) -> MediaItem {
+        serde_json::from_value(serde_json::json!({
+            "id": "item",
+            "library_id": "lib",
+            "item_type": item_type,
+            "name": "Track",
+            "created_at": "",
+            "updated_at": "",
+        }))
+        .unwrap()
+    }
+
+    fn query(params: &[(&str, &str)]) -> AudioStreamQuery {
+        AudioStreamQuery::from_params(
+            params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_plan_stream() {
+        let audio = item("Audio");
+        // Nothing asked for, or the file's own codec: sent as-is
+        assert_eq!(
+            plan_stream(&audio, "/music/a.mp3", &query(&[])),
+            StreamPlan::Direct
+        );
+        assert_eq!(
+            plan_stream(&audio, "/music/a.m4a", &query(&[("AudioCodec", "aac")])),
+            StreamPlan::Direct
+        );
+
+        // Another codec, or a bitrate cap, means transcoding
+        assert_eq!(
+            plan_stream(&audio, "/music/a.flac", &query(&[("audioCodec", "opus")])),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Opus,
+                bitrate: DEFAULT_AUDIO_BITRATE
+            }
+        );
+        assert_eq!(
+            plan_stream(
+                &audio,
+                "/music/a.mp3",
+                &query(&[("AudioBitRate", "128000")])
+            ),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Mp3,
+                bitrate: 128_000
+            }
+        );
+        assert_eq!(
+            plan_stream(
+                &audio,
+                "/music/a.mp3",
+                &query(&[("audioBitRate", "5000000")])
+            ),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Mp3,
+                bitrate: MAX_AUDIO_BITRATE
+            }
+        );
+        // Static wins over everything for audio files
+        assert_eq!(
+            plan_stream(
+                &audio,
+                "/music/a.flac",
+                &query(&[("static", "true"), ("audioCodec", "mp3")])
+            ),
+            StreamPlan::Direct
+        );
+
+        // Video files always have their audio track extracted
+        assert_eq!(
+            plan_stream(&item("Episode"), "/tv/a.mkv", &query(&[("static", "true")])),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Mp3,
+                bitrate: DEFAULT_AUDIO_BITRATE
+            }
+        );
+    }
+
+    #[test]
+    fn test_plan_universal() {
+        let audio = item("Audio");
+        let finamp = [("Container", "opus,webm|opus,mp3,aac,m4a|aac,flac")];
+
+        // A listed container within the bitrate cap plays directly
+        assert_eq!(
+            plan_universal(&audio, "/music/a.flac", &query(&finamp), Some(900_000)),
+            StreamPlan::Direct
+        );
+        assert_eq!(
+            plan_universal(&audio, "/music/a.m4a", &query(&finamp), None),
+            StreamPlan::Direct
+        );
+
+        // Over MaxStreamingBitrate: transcoded to the first container we can produce
+        let capped = query(&[
+            ("Container", "opus,webm|opus,mp3,aac,m4a|aac,flac"),
+            ("MaxStreamingBitrate", "128000"),
+        ]);
+        assert_eq!(
+            plan_universal(&audio, "/music/a.flac", &capped, Some(900_000)),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Opus,
+                bitrate: 128_000
+            }
+        );
+        // An unknown source bitrate isn't held against the file
+        assert_eq!(
+            plan_universal(&audio, "/music/a.flac", &capped, None),
+            StreamPlan::Direct
+        );
+
+        // A container the client can't play: the transcoding container is used
+        let unsupported = query(&[
+            ("container", "mp3,aac"),
+            ("transcodingContainer", "aac"),
+            ("maxStreamingBitrate", "10000000"),
+        ]);
+        assert_eq!(
+            plan_universal(&audio, "/music/a.flac", &unsupported, Some(900_000)),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Aac,
+                bitrate: MAX_AUDIO_BITRATE
+            }
+        );
+        assert_eq!(
+            plan_universal(
+                &audio,
+                "/music/a.flac",
+                &query(&[("Container", "mp3"), ("AudioCodec", "mp3")]),
+                None
+            ),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Mp3,
+                bitrate: DEFAULT_AUDIO_BITRATE
+            }
+        );
+
+        // Without a container list audio plays directly; video falls back to AAC
+        assert_eq!(
+            plan_universal(&audio, "/music/a.wma", &query(&[]), None),
+            StreamPlan::Direct
+        );
+        assert_eq!(
+            plan_universal(&item("Movie"), "/movies/a.mkv", &query(&[]), None),
+            StreamPlan::Transcode {
+                codec: TranscodeCodec::Aac,
+                bitrate: DEFAULT_AUDIO_BITRATE
+            }
+        );
+    }
+}
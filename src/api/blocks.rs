@@ -0,0 +1,177 @@
+// Hidden items API
+// Lets users (or admins on their behalf) hide items, genres and tags from
+// browse and search results
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{db, models::User, services::auth, AppState};
+
+use super::users::parse_emby_auth_header;
+
+/// Block types accepted by the API (see the user_hidden_items view for matching)
+const BLOCK_TYPES: &[&str] = &["Item", "Genre", "Tag"];
+
+/// Routes mounted at /Users/:userId/ItemBlocks
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route(
+        "/",
+        get(get_item_blocks)
+            .post(add_item_block)
+            .delete(remove_item_block),
+    )
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemBlockDto {
+    /// Item, Genre or Tag
+    #[serde(rename = "Type")]
+    pub block_type: String,
+    /// Item ID for Item blocks, otherwise the genre or tag name
+    pub value: String,
+    /// Item name for Item blocks (read-only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_created: Option<String>,
+}
+
+async fn require_auth(state: &AppState, headers: &HeaderMap) -> Result<User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+/// Users manage their own blocks, admins manage anyone's
+async fn require_self_or_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    user_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+    if user.id != user_id && !user.is_admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot modify other user's data".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Normalize the block type casing, rejecting unknown types
+fn parse_block_type(block_type: &str) -> Result<&'static str, (StatusCode, String)> {
+    BLOCK_TYPES
+        .iter()
+        .find(|t| t.eq_ignore_ascii_case(block_type.trim()))
+        .copied()
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Type must be one of {}", BLOCK_TYPES.join(", ")),
+            )
+        })
+}
+
+/// GET /Users/{userId}/ItemBlocks - List hidden items, genres and tags
+async fn get_item_blocks(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+) -> Result<Json<Vec<ItemBlockDto>>, (StatusCode, String)> {
+    require_self_or_admin(&state, &headers, &user_id).await?;
+
+    let blocks = db::get_item_blocks(&state.db, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut result = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let name = if block.block_type == "Item" {
+            sqlx::query_scalar("SELECT name FROM media_items WHERE id = ?")
+                .bind(&block.value)
+                .fetch_optional(&state.db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        } else {
+            None
+        };
+
+        result.push(ItemBlockDto {
+            block_type: block.block_type,
+            value: block.value,
+            name,
+            date_created: Some(block.created_at),
+        });
+    }
+
+    Ok(Json(result))
+}
+
+/// POST /Users/{userId}/ItemBlocks - Hide an item, genre or tag
+async fn add_item_block(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(block): Json<ItemBlockDto>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_self_or_admin(&state, &headers, &user_id).await?;
+
+    let block_type = parse_block_type(&block.block_type)?;
+    let value = block.value.trim();
+    if value.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Value is required".to_string()));
+    }
+
+    if block_type == "Item" {
+        let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM media_items WHERE id = ?")
+            .bind(value)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if exists.is_none() {
+            return Err((StatusCode::NOT_FOUND, "Item not found".to_string()));
+        }
+    }
+
+    db::add_item_block(&state.db, &user_id, block_type, value)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("User {} now hides {} '{}'", user_id, block_type, value);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /Users/{userId}/ItemBlocks - Unhide an item, genre or tag
+async fn remove_item_block(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(block): Json<ItemBlockDto>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_self_or_admin(&state, &headers, &user_id).await?;
+
+    let block_type = parse_block_type(&block.block_type)?;
+
+    let removed = db::remove_item_block(&state.db, &user_id, block_type, block.value.trim())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, "Block not found".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
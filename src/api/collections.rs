@@ -7,11 +7,17 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::{
+    db,
+    models::MediaItem,
+    services::{auth, box_sets, server_id},
+    AppState,
+};
 
-use super::items::{BaseItemDto, ImageTags, UserItemDataDto};
+use super::items::{is_4k_resolution, is_hd_resolution, BaseItemDto, ImageTags, UserItemDataDto};
 use super::users::parse_emby_auth_header;
 
 pub fn routes() -> Router<Arc<AppState>> {
@@ -23,6 +29,10 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/:id/Items", get(get_collection_items))
         .route("/:id/Items", post(add_items_to_collection))
         .route("/:id/Items", delete(remove_items_from_collection))
+        .route(
+            "/:id/Items/:item_id/Move/:new_index",
+            post(move_collection_item),
+        )
 }
 
 #[derive(Debug, Deserialize)]
@@ -68,6 +78,67 @@ struct CollectionRow {
     name: String,
     overview: Option<String>,
     sort_name: Option<String>,
+    tmdb_collection_id: Option<String>,
+    poster_url: Option<String>,
+    backdrop_url: Option<String>,
+}
+
+/// Columns of CollectionRow
+const COLLECTION_COLUMNS: &str =
+    "id, name, overview, sort_name, tmdb_collection_id, poster_url, backdrop_url";
+
+/// The BoxSet DTO of a collection with `child_count` items
+fn collection_to_dto(col: CollectionRow, child_count: i32) -> BaseItemDto {
+    // TMDB collections (services::box_sets) show the collection's artwork
+    let image_tags = (col.poster_url.is_some() || col.backdrop_url.is_some()).then(|| ImageTags {
+        primary: col.poster_url.as_deref().map(box_sets::artwork_tag),
+        backdrop: col.backdrop_url.as_deref().map(box_sets::artwork_tag),
+        backdrop_count: usize::from(col.backdrop_url.is_some()),
+    });
+    let provider_ids = col
+        .tmdb_collection_id
+        .map(|id| HashMap::from([("Tmdb".to_string(), id)]));
+
+    BaseItemDto {
+        id: col.id,
+        name: col.name,
+        item_type: "BoxSet".to_string(),
+        server_id: server_id::get().to_string(),
+        parent_id: None,
+        overview: col.overview,
+        year: None,
+        production_year: None,
+        index_number: None,
+        index_number_end: None,
+        parent_index_number: None,
+        runtime_ticks: None,
+        community_rating: None,
+        official_rating: None,
+        path: None,
+        premiere_date: None,
+        sort_name: col.sort_name,
+        series_id: None,
+        series_name: None,
+        season_id: None,
+        season_name: None,
+        music: None,
+        live_tv: None,
+        is_folder: true,
+        child_count: Some(child_count),
+        media_type: None,
+        collection_type: Some("boxsets".to_string()),
+        user_data: UserItemDataDto::default(),
+        backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
+        image_tags,
+        provider_ids,
+        media_sources: None,
+        width: None,
+        height: None,
+        is_hd: None,
+        is_4k: None,
+        can_download: false,
+        supports_media_source_display: false,
+    }
 }
 
 async fn require_auth(
@@ -95,9 +166,10 @@ async fn get_collections(
     let start_index = query.start_index.unwrap_or(0);
     let limit = query.limit.unwrap_or(100).min(500);
 
-    let collections: Vec<CollectionRow> = sqlx::query_as(
-        "SELECT id, name, overview, sort_name FROM collections ORDER BY COALESCE(sort_name, name) LIMIT ? OFFSET ?",
-    )
+    let collections: Vec<CollectionRow> = sqlx::query_as(&format!(
+        "SELECT {} FROM collections ORDER BY COALESCE(sort_name, name) LIMIT ? OFFSET ?",
+        COLLECTION_COLUMNS
+    ))
     .bind(limit)
     .bind(start_index)
     .fetch_all(&state.db)
@@ -120,37 +192,7 @@ async fn get_collections(
                 .await
                 .unwrap_or((0,));
 
-        items.push(BaseItemDto {
-            id: col.id.clone(),
-            name: col.name,
-            item_type: "BoxSet".to_string(),
-            server_id: "jellyfin-rust-server".to_string(),
-            parent_id: None,
-            overview: col.overview,
-            year: None,
-            production_year: None,
-            index_number: None,
-            parent_index_number: None,
-            runtime_ticks: None,
-            community_rating: None,
-            path: None,
-            premiere_date: None,
-            sort_name: col.sort_name,
-            series_id: None,
-            series_name: None,
-            season_id: None,
-            season_name: None,
-            is_folder: true,
-            child_count: Some(count.0),
-            media_type: None,
-            collection_type: Some("boxsets".to_string()),
-            user_data: UserItemDataDto::default(),
-            image_tags: None,
-            provider_ids: None,
-            media_sources: None,
-            can_download: false,
-            supports_media_source_display: false,
-        });
+        items.push(collection_to_dto(col, count.0));
     }
 
     Ok(Json(CollectionsResponse {
@@ -169,7 +211,7 @@ async fn create_collection(
     let _user = require_auth(&state, &headers).await?;
 
     let collection_id = uuid::Uuid::new_v4().to_string();
-    let sort_name = query.name.to_lowercase();
+    let sort_name = crate::scanner::sort_name::sort_name(&query.name);
 
     sqlx::query("INSERT INTO collections (id, name, sort_name) VALUES (?, ?, ?)")
         .bind(&collection_id)
@@ -207,13 +249,15 @@ async fn get_collection(
 ) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
     let _user = require_auth(&state, &headers).await?;
 
-    let collection: CollectionRow =
-        sqlx::query_as("SELECT id, name, overview, sort_name FROM collections WHERE id = ?")
-            .bind(&id)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-            .ok_or_else(|| (StatusCode::NOT_FOUND, "Collection not found".to_string()))?;
+    let collection: CollectionRow = sqlx::query_as(&format!(
+        "SELECT {} FROM collections WHERE id = ?",
+        COLLECTION_COLUMNS
+    ))
+    .bind(&id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Collection not found".to_string()))?;
 
     let count: (i32,) =
         sqlx::query_as("SELECT COUNT(*) FROM collection_items WHERE collection_id = ?")
@@ -222,37 +266,7 @@ async fn get_collection(
             .await
             .unwrap_or((0,));
 
-    Ok(Json(BaseItemDto {
-        id: collection.id,
-        name: collection.name,
-        item_type: "BoxSet".to_string(),
-        server_id: "jellyfin-rust-server".to_string(),
-        parent_id: None,
-        overview: collection.overview,
-        year: None,
-        production_year: None,
-        index_number: None,
-        parent_index_number: None,
-        runtime_ticks: None,
-        community_rating: None,
-        path: None,
-        premiere_date: None,
-        sort_name: collection.sort_name,
-        series_id: None,
-        series_name: None,
-        season_id: None,
-        season_name: None,
-        is_folder: true,
-        child_count: Some(count.0),
-        media_type: None,
-        collection_type: Some("boxsets".to_string()),
-        user_data: UserItemDataDto::default(),
-        image_tags: None,
-        provider_ids: None,
-        media_sources: None,
-        can_download: false,
-        supports_media_source_display: false,
-    }))
+    Ok(Json(collection_to_dto(collection, count.0)))
 }
 
 /// DELETE /Collections/:id - Delete a collection
@@ -298,7 +312,7 @@ async fn get_collection_items(
 
     // Convert to DTOs
     let mut dtos = Vec::with_capacity(items.len());
-    for item in items {
+    for (position, item) in items.into_iter().enumerate() {
         let is_folder = matches!(
             item.item_type.as_str(),
             "Series" | "Season" | "Folder" | "CollectionFolder"
@@ -319,15 +333,18 @@ async fn get_collection_items(
             id: item.id.clone(),
             name: item.name.clone(),
             item_type: item.item_type.clone(),
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             parent_id: item.parent_id.clone(),
             overview: item.overview.clone(),
             year: item.year,
             production_year: item.year,
-            index_number: item.index_number,
-            parent_index_number: item.parent_index_number,
+            // The item's position in the collection, as used by Move
+            index_number: Some(position as i32),
+            index_number_end: None,
+            parent_index_number: item.parent_index_number_for_display(),
             runtime_ticks: item.runtime_ticks,
             community_rating: item.community_rating,
+            official_rating: item.official_rating.clone(),
             path: item.path.clone(),
             premiere_date: item.premiere_date.clone(),
             sort_name: item.sort_name.clone(),
@@ -335,14 +352,21 @@ async fn get_collection_items(
             series_name: None,
             season_id: None,
             season_name: None,
+            music: None,
+            live_tv: None,
             is_folder,
             child_count: None,
             media_type,
             collection_type: None,
             user_data,
+            backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
             image_tags,
             provider_ids: None,
             media_sources: None,
+            width: item.width,
+            height: item.height,
+            is_hd: is_hd_resolution(item.width, item.height),
+            is_4k: is_4k_resolution(item.width, item.height),
             can_download: item.path.is_some(),
             supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
         });
@@ -416,6 +440,30 @@ async fn remove_items_from_collection(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// POST /Collections/:id/Items/:item_id/Move/:new_index - Move an item to a new position
+async fn move_collection_item(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((id, item_id, new_index)): Path<(String, String, usize)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let _user = require_auth(&state, &headers).await?;
+
+    let moved = db::move_list_item(
+        &state.db,
+        db::ItemList::Collection,
+        &id,
+        &item_id,
+        new_index,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !moved {
+        return Err((StatusCode::NOT_FOUND, "Item not in collection".to_string()));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// Helper to fetch image tags for an item
 async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Option<ImageTags> {
     let images: Vec<(String,)> = sqlx::query_as("SELECT image_type FROM images WHERE item_id = ?")
@@ -432,7 +480,10 @@ async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Opti
     for (image_type,) in images {
         match image_type.as_str() {
             "Primary" => tags.primary = Some(item_id.to_string()),
-            "Backdrop" => tags.backdrop = Some(item_id.to_string()),
+            "Backdrop" => {
+                tags.backdrop = Some(item_id.to_string());
+                tags.backdrop_count += 1;
+            }
             _ => {}
         }
     }
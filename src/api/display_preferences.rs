@@ -1,22 +1,45 @@
 // Display Preferences API - Persists user display preferences per client
+//
+// Preferences are stored per user, client and preferences ID ("usersettings",
+// a library ID, ...). CustomPrefs is an open map clients use for their own
+// settings (home section layout, landing tabs, ...); every key is stored and
+// returned exactly as sent, since several clients rely on reading back values
+// nothing on the server understands. The exception is the "usersettings"
+// library order and visibility keys (services::library_views): they are
+// stored per user rather than per client, and apply to every client.
 
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
-    routing::{get, post},
+    http::{HeaderMap, StatusCode},
+    routing::get,
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
-use crate::AppState;
+use crate::{
+    models::User,
+    services::{auth, library_views},
+    AppState,
+};
+
+use super::users::parse_emby_auth_header;
+
+/// Preferences ID clients keep their user-wide settings under
+const USER_SETTINGS_ID: &str = "usersettings";
+
+/// Client used when neither the query nor the authorization header names one
+const DEFAULT_CLIENT: &str = "default";
 
 /// Routes for /DisplayPreferences
 pub fn routes() -> Router<Arc<AppState>> {
-    Router::new()
-        .route("/:display_prefs_id", get(get_display_preferences))
-        .route("/:display_prefs_id", post(update_display_preferences))
+    Router::new().route(
+        "/:display_prefs_id",
+        get(get_display_preferences)
+            .post(update_display_preferences)
+            .delete(delete_display_preferences),
+    )
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,8 +49,9 @@ pub struct DisplayPreferencesQuery {
     pub client: Option<String>,
 }
 
+/// Jellyfin's DisplayPreferencesDto; missing fields take their defaults
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", default)]
 pub struct DisplayPreferences {
     pub id: String,
     pub view_type: Option<String>,
@@ -36,7 +60,8 @@ pub struct DisplayPreferences {
     pub remember_indexing: bool,
     pub primary_image_height: i32,
     pub primary_image_width: i32,
-    pub custom_prefs: HashMap<String, String>,
+    /// Client-defined keys; null values are kept as null
+    pub custom_prefs: BTreeMap<String, Option<String>>,
     pub scroll_direction: String,
     pub show_backdrop: bool,
     pub remember_sorting: bool,
@@ -55,13 +80,13 @@ impl Default for DisplayPreferences {
             remember_indexing: false,
             primary_image_height: 250,
             primary_image_width: 250,
-            custom_prefs: HashMap::new(),
+            custom_prefs: BTreeMap::new(),
             scroll_direction: "Horizontal".to_string(),
             show_backdrop: true,
             remember_sorting: false,
             sort_order: "Ascending".to_string(),
             show_sidebar: true,
-            client: "default".to_string(),
+            client: DEFAULT_CLIENT.to_string(),
         }
     }
 }
@@ -89,7 +114,7 @@ struct DisplayPreferencesRow {
 
 impl DisplayPreferencesRow {
     fn into_dto(self) -> DisplayPreferences {
-        let custom_prefs: HashMap<String, String> = self
+        let custom_prefs: BTreeMap<String, Option<String>> = self
             .custom_prefs
             .as_ref()
             .and_then(|s| serde_json::from_str(s).ok())
@@ -116,52 +141,122 @@ impl DisplayPreferencesRow {
     }
 }
 
-/// GET /DisplayPreferences/:id
+async fn require_auth(state: &AppState, headers: &HeaderMap) -> Result<User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+/// Resolve whose preferences a request is for: the caller's own unless an
+/// admin names another user
+async fn resolve_user_id(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: &DisplayPreferencesQuery,
+) -> Result<String, (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+    match query.user_id.as_deref().filter(|id| !id.is_empty()) {
+        Some(id) if id != user.id && !user.is_admin => Err((
+            StatusCode::FORBIDDEN,
+            "Cannot access another user's display preferences".to_string(),
+        )),
+        Some(id) => Ok(id.to_string()),
+        None => Ok(user.id),
+    }
+}
+
+/// Client named by the query, or by the caller's authorization header
+fn resolve_client(headers: &HeaderMap, query: &DisplayPreferencesQuery) -> String {
+    query
+        .client
+        .clone()
+        .or_else(|| parse_emby_auth_header(headers).map(|(client, _, _, _)| client))
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| DEFAULT_CLIENT.to_string())
+}
+
+/// GET /DisplayPreferences/:id - A user's preferences for a client, or the defaults
 async fn get_display_preferences(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Path(display_prefs_id): Path<String>,
     Query(query): Query<DisplayPreferencesQuery>,
-) -> Json<DisplayPreferences> {
-    let user_id = query.user_id.as_deref().unwrap_or("default");
-    let client = query.client.as_deref().unwrap_or("default");
+) -> Result<Json<DisplayPreferences>, (StatusCode, String)> {
+    let user_id = resolve_user_id(&state, &headers, &query).await?;
+    let client = resolve_client(&headers, &query);
 
-    // Try to load from database
     let row: Option<DisplayPreferencesRow> = sqlx::query_as(
         "SELECT * FROM display_preferences WHERE id = ? AND user_id = ? AND client = ?",
     )
     .bind(&display_prefs_id)
-    .bind(user_id)
-    .bind(client)
+    .bind(&user_id)
+    .bind(&client)
     .fetch_optional(&state.db)
     .await
-    .ok()
-    .flatten();
-
-    match row {
-        Some(r) => Json(r.into_dto()),
-        None => {
-            // Return defaults
-            let prefs = DisplayPreferences {
-                id: display_prefs_id,
-                client: client.to_string(),
-                ..Default::default()
-            };
-            Json(prefs)
-        }
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut prefs = match row {
+        Some(r) => r.into_dto(),
+        None => DisplayPreferences {
+            id: display_prefs_id,
+            client,
+            ..Default::default()
+        },
+    };
+    if prefs.id == USER_SETTINGS_ID {
+        library_views::load(&state.db, &user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .write_custom_prefs(&mut prefs.custom_prefs);
     }
+    Ok(Json(prefs))
 }
 
-/// POST /DisplayPreferences/:id
+/// POST /DisplayPreferences/:id - Save a user's preferences for a client
+///
+/// CustomPrefs replaces the stored map, so keys a client drops are removed.
+/// Library order and visibility keys in "usersettings" update the user's
+/// settings for all clients; keys left out keep their value.
 async fn update_display_preferences(
     State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Path(display_prefs_id): Path<String>,
     Query(query): Query<DisplayPreferencesQuery>,
-    Json(prefs): Json<DisplayPreferences>,
+    Json(mut prefs): Json<DisplayPreferences>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let user_id = query.user_id.as_deref().unwrap_or("default");
-    let client = query.client.as_deref().unwrap_or(&prefs.client);
+    let user_id = resolve_user_id(&state, &headers, &query).await?;
+    if display_prefs_id == USER_SETTINGS_ID {
+        let mut views = library_views::load(&state.db, &user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if views.merge_custom_prefs(&prefs.custom_prefs) {
+            library_views::save(&state.db, &user_id, &views)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            super::home::invalidate_user_lists(&user_id);
+        }
+        for key in [
+            library_views::ORDERED_VIEWS_KEY,
+            library_views::HIDDEN_VIEWS_KEY,
+            library_views::LATEST_EXCLUDES_KEY,
+        ] {
+            prefs.custom_prefs.remove(key);
+        }
+    }
+    // The query names the client; the body's Client is only a fallback
+    let client = match query.client {
+        Some(ref client) if !client.is_empty() => client.clone(),
+        _ if !prefs.client.is_empty() && prefs.client != DEFAULT_CLIENT => prefs.client.clone(),
+        _ => resolve_client(&headers, &query),
+    };
 
-    let custom_prefs_json = serde_json::to_string(&prefs.custom_prefs).unwrap_or_default();
+    let custom_prefs_json = serde_json::to_string(&prefs.custom_prefs)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // Upsert the preferences
     sqlx::query(
@@ -185,8 +280,8 @@ async fn update_display_preferences(
             custom_prefs = excluded.custom_prefs"#,
     )
     .bind(&display_prefs_id)
-    .bind(user_id)
-    .bind(client)
+    .bind(&user_id)
+    .bind(&client)
     .bind(&prefs.view_type)
     .bind(&prefs.sort_by)
     .bind(&prefs.sort_order)
@@ -205,3 +300,24 @@ async fn update_display_preferences(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+/// DELETE /DisplayPreferences/:id - Reset a user's preferences for a client to the defaults
+async fn delete_display_preferences(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(display_prefs_id): Path<String>,
+    Query(query): Query<DisplayPreferencesQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user_id = resolve_user_id(&state, &headers, &query).await?;
+    let client = resolve_client(&headers, &query);
+
+    sqlx::query("DELETE FROM display_preferences WHERE id = ? AND user_id = ? AND client = ?")
+        .bind(&display_prefs_id)
+        .bind(&user_id)
+        .bind(&client)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,591 @@
+// DLNA API
+// Device description, ContentDirectory and ConnectionManager control, and
+// file streaming for DLNA clients (services::dlna announces the server).
+// Nothing here takes a token: requests are answered only while [dlna] is
+// enabled and only for clients on the local network, browsing as the
+// configured account
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{any, get, post},
+    Router,
+};
+use std::{net::SocketAddr, sync::Arc};
+
+use crate::{
+    db::{
+        self,
+        item_query::{ItemQuery, ItemSort, SortOrder},
+    },
+    models::{Library, MediaItem},
+    services::dlna::{self, DidlObject, DidlResource},
+    AppState,
+};
+
+use super::audio::get_audio_content_type;
+use super::videos::{get_content_type, serve_file};
+
+/// Object ID of the root container, whose children are the libraries
+const ROOT_ID: &str = "0";
+
+/// Item types browsed as containers
+const CONTAINER_TYPES: &[&str] = &[
+    "Series",
+    "Season",
+    "MusicArtist",
+    "MusicAlbum",
+    "BoxSet",
+    "Folder",
+];
+
+/// Routes mounted at /dlna
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/description.xml", get(get_description))
+        .route("/ContentDirectory.xml", get(get_content_directory))
+        .route("/ConnectionManager.xml", get(get_connection_manager))
+        .route("/control/ContentDirectory", post(control_content_directory))
+        .route(
+            "/control/ConnectionManager",
+            post(control_connection_manager),
+        )
+        .route("/events/:service", any(event_subscription))
+        .route("/media/:itemId", get(get_media))
+}
+
+type DlnaError = (StatusCode, String);
+
+/// Answer only while DLNA is on, and only clients on the local network
+fn require_dlna(state: &AppState, client: SocketAddr) -> Result<(), DlnaError> {
+    if !state.config.dlna.enabled {
+        return Err((StatusCode::NOT_FOUND, "DLNA is disabled".to_string()));
+    }
+    if !dlna::is_local_address(client.ip()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "DLNA is only served on the local network".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// The account DLNA clients browse as
+async fn browse_user(state: &AppState) -> Result<String, DlnaError> {
+    dlna::browse_user(&state.db, &state.config.dlna)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "No account to browse as".to_string(),
+            )
+        })
+}
+
+fn xml_response(status: StatusCode, xml: String) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "text/xml; charset=\"utf-8\"")],
+        xml,
+    )
+        .into_response()
+}
+
+/// GET /dlna/description.xml
+async fn get_description(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    Ok(xml_response(
+        StatusCode::OK,
+        dlna::device_description(&state.config.dlna.friendly_name),
+    ))
+}
+
+/// GET /dlna/ContentDirectory.xml
+async fn get_content_directory(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    Ok(xml_response(
+        StatusCode::OK,
+        dlna::content_directory_description(),
+    ))
+}
+
+/// GET /dlna/ConnectionManager.xml
+async fn get_connection_manager(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    Ok(xml_response(
+        StatusCode::OK,
+        dlna::connection_manager_description(),
+    ))
+}
+
+/// SUBSCRIBE/UNSUBSCRIBE /dlna/events/{service}
+///
+/// Some TVs refuse a server whose services can't be subscribed to. The
+/// subscription is accepted, but no events are sent: clients poll
+/// SystemUpdateID instead.
+async fn event_subscription(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    match method.as_str() {
+        "SUBSCRIBE" => {
+            let sid = headers
+                .get("SID")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("uuid:{}", uuid::Uuid::new_v4()));
+            Ok((
+                StatusCode::OK,
+                [("SID", sid), ("TIMEOUT", "Second-1800".to_string())],
+            )
+                .into_response())
+        }
+        "UNSUBSCRIBE" => Ok(StatusCode::OK.into_response()),
+        _ => Err((StatusCode::METHOD_NOT_ALLOWED, "Not allowed".to_string())),
+    }
+}
+
+/// Base URL the client reached the server at, for links in Browse results
+fn base_url(headers: &HeaderMap) -> Option<String> {
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+    Some(format!("http://{}", host))
+}
+
+fn soap_action(headers: &HeaderMap) -> String {
+    headers
+        .get("SOAPACTION")
+        .and_then(|v| v.to_str().ok())
+        .and_then(dlna::soap_action)
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// POST /dlna/control/ContentDirectory
+async fn control_content_directory(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    let body = String::from_utf8_lossy(&body);
+    let action = soap_action(&headers);
+    let service = dlna::CONTENT_DIRECTORY;
+
+    let response = match action.as_str() {
+        "Browse" => {
+            let user_id = browse_user(&state).await?;
+            let Some(base_url) = base_url(&headers) else {
+                return Err((StatusCode::BAD_REQUEST, "Missing Host".to_string()));
+            };
+            let arguments = dlna::soap_arguments(&body);
+            let argument = |name| arguments.get(name).cloned().unwrap_or_default();
+            let request = BrowseRequest {
+                object_id: argument("ObjectID"),
+                metadata: argument("BrowseFlag") == "BrowseMetadata",
+                start: argument("StartingIndex").parse().unwrap_or(0),
+                count: argument("RequestedCount").parse().unwrap_or(0),
+            };
+            match browse(&state, &user_id, &base_url, &request).await {
+                Ok(Some((objects, total))) => dlna::soap_response(
+                    service,
+                    &action,
+                    &[
+                        ("Result", dlna::didl_lite(&objects)),
+                        ("NumberReturned", objects.len().to_string()),
+                        ("TotalMatches", total.to_string()),
+                        ("UpdateID", dlna::system_update_id().to_string()),
+                    ],
+                ),
+                Ok(None) => {
+                    return Ok(xml_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        dlna::soap_fault(701, "No such object"),
+                    ))
+                }
+                Err(e) => {
+                    tracing::warn!("DLNA browse of {} failed: {}", request.object_id, e);
+                    return Ok(xml_response(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        dlna::soap_fault(501, "Action failed"),
+                    ));
+                }
+            }
+        }
+        "GetSearchCapabilities" => {
+            dlna::soap_response(service, &action, &[("SearchCaps", String::new())])
+        }
+        "GetSortCapabilities" => {
+            dlna::soap_response(service, &action, &[("SortCaps", String::new())])
+        }
+        "GetSystemUpdateID" => dlna::soap_response(
+            service,
+            &action,
+            &[("Id", dlna::system_update_id().to_string())],
+        ),
+        _ => {
+            tracing::debug!("Unsupported ContentDirectory action '{}'", action);
+            return Ok(xml_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                dlna::soap_fault(401, "Invalid Action"),
+            ));
+        }
+    };
+    Ok(xml_response(StatusCode::OK, response))
+}
+
+/// Types of the files DLNA clients are offered, for GetProtocolInfo
+const SOURCE_MIME_TYPES: &[&str] = &[
+    "video/mp4",
+    "video/x-matroska",
+    "video/webm",
+    "video/x-msvideo",
+    "video/quicktime",
+    "video/mp2t",
+    "audio/mpeg",
+    "audio/mp4",
+    "audio/flac",
+    "audio/ogg",
+    "audio/wav",
+];
+
+/// POST /dlna/control/ConnectionManager
+async fn control_connection_manager(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    let action = soap_action(&headers);
+    let service = dlna::CONNECTION_MANAGER;
+
+    let response = match action.as_str() {
+        "GetProtocolInfo" => {
+            let source = SOURCE_MIME_TYPES
+                .iter()
+                .map(|mime| format!("http-get:*:{}:*", mime))
+                .collect::<Vec<_>>()
+                .join(",");
+            dlna::soap_response(
+                service,
+                &action,
+                &[("Source", source), ("Sink", String::new())],
+            )
+        }
+        "GetCurrentConnectionIDs" => {
+            dlna::soap_response(service, &action, &[("ConnectionIDs", "0".to_string())])
+        }
+        "GetCurrentConnectionInfo" => dlna::soap_response(
+            service,
+            &action,
+            &[
+                ("RcsID", "-1".to_string()),
+                ("AVTransportID", "-1".to_string()),
+                ("ProtocolInfo", String::new()),
+                ("PeerConnectionManager", String::new()),
+                ("PeerConnectionID", "-1".to_string()),
+                ("Direction", "Output".to_string()),
+                ("Status", "OK".to_string()),
+            ],
+        ),
+        _ => {
+            return Ok(xml_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                dlna::soap_fault(401, "Invalid Action"),
+            ))
+        }
+    };
+    Ok(xml_response(StatusCode::OK, response))
+}
+
+/// A Browse request's arguments
+struct BrowseRequest {
+    object_id: String,
+    /// BrowseMetadata (the object itself) rather than BrowseDirectChildren
+    metadata: bool,
+    start: i32,
+    /// 0 for all of them
+    count: i32,
+}
+
+/// Libraries the user may see
+async fn visible_libraries(state: &AppState, user_id: &str) -> anyhow::Result<Vec<Library>> {
+    let mut libraries: Vec<Library> = sqlx::query_as("SELECT * FROM libraries ORDER BY name")
+        .fetch_all(&state.db)
+        .await?;
+    if let Some(access) = db::get_library_access(&state.db, user_id).await? {
+        libraries.retain(|lib| access.contains(&lib.id));
+    }
+    Ok(libraries)
+}
+
+/// An item the user may see
+async fn visible_item(
+    state: &AppState,
+    user_id: &str,
+    item_id: &str,
+) -> anyhow::Result<Option<MediaItem>> {
+    let item: Option<MediaItem> = sqlx::query_as(
+        "SELECT * FROM media_items WHERE id = ?
+           AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)",
+    )
+    .bind(item_id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?;
+    let Some(item) = item else {
+        return Ok(None);
+    };
+    if let Some(access) = db::get_library_access(&state.db, user_id).await? {
+        if !access.contains(&item.library_id) {
+            return Ok(None);
+        }
+    }
+    Ok(Some(item))
+}
+
+/// The objects of a Browse request and how many there are in all, or None
+/// when the object doesn't exist (or the user can't see it)
+async fn browse(
+    state: &AppState,
+    user_id: &str,
+    base_url: &str,
+    request: &BrowseRequest,
+) -> anyhow::Result<Option<(Vec<DidlObject>, usize)>> {
+    let libraries = visible_libraries(state, user_id).await?;
+
+    if request.object_id == ROOT_ID {
+        if request.metadata {
+            let root = DidlObject {
+                id: ROOT_ID.to_string(),
+                parent_id: "-1".to_string(),
+                title: state.config.dlna.friendly_name.clone(),
+                class: "object.container.storageFolder",
+                child_count: Some(libraries.len() as i32),
+                resource: None,
+                album_art_url: None,
+            };
+            return Ok(Some((vec![root], 1)));
+        }
+        let total = libraries.len();
+        let mut objects = Vec::new();
+        for library in page(libraries, request) {
+            objects.push(library_object(state, user_id, &library).await?);
+        }
+        return Ok(Some((objects, total)));
+    }
+
+    if let Some(library) = libraries.iter().find(|lib| lib.id == request.object_id) {
+        if request.metadata {
+            return Ok(Some((
+                vec![library_object(state, user_id, library).await?],
+                1,
+            )));
+        }
+        let query = ItemQuery::new()
+            .library(&library.id)
+            .top_level()
+            .visible_to(user_id)
+            .sort(ItemSort::Column("sort_name"), SortOrder::Ascending);
+        return browse_children(state, user_id, base_url, query, request).await;
+    }
+
+    let Some(item) = visible_item(state, user_id, &request.object_id).await? else {
+        return Ok(None);
+    };
+    if request.metadata {
+        let object = item_object(state, user_id, base_url, &item).await?;
+        return Ok(Some((vec![object], 1)));
+    }
+    let sort = match item.item_type.as_str() {
+        "Series" | "Season" | "MusicAlbum" => ItemSort::EpisodeOrder,
+        _ => ItemSort::Column("sort_name"),
+    };
+    let query = ItemQuery::new()
+        .parent(&item.id)
+        .visible_to(user_id)
+        .sort(sort, SortOrder::Ascending);
+    browse_children(state, user_id, base_url, query, request).await
+}
+
+/// The requested page of a list
+fn page<T>(list: Vec<T>, request: &BrowseRequest) -> impl Iterator<Item = T> {
+    let count = match request.count {
+        0 => usize::MAX,
+        count => count.max(0) as usize,
+    };
+    list.into_iter()
+        .skip(request.start.max(0) as usize)
+        .take(count)
+}
+
+async fn browse_children(
+    state: &AppState,
+    user_id: &str,
+    base_url: &str,
+    query: ItemQuery,
+    request: &BrowseRequest,
+) -> anyhow::Result<Option<(Vec<DidlObject>, usize)>> {
+    let total = query.count(&state.db).await?;
+    let count = match request.count {
+        0 => i32::MAX,
+        count => count.max(0),
+    };
+    let items = query
+        .limit(count)
+        .offset(request.start.max(0))
+        .fetch_all(&state.db)
+        .await?;
+    let mut objects = Vec::with_capacity(items.len());
+    for item in &items {
+        objects.push(item_object(state, user_id, base_url, item).await?);
+    }
+    Ok(Some((objects, total.max(0) as usize)))
+}
+
+async fn library_object(
+    state: &AppState,
+    user_id: &str,
+    library: &Library,
+) -> anyhow::Result<DidlObject> {
+    let children = ItemQuery::new()
+        .library(&library.id)
+        .top_level()
+        .visible_to(user_id)
+        .count(&state.db)
+        .await?;
+    Ok(DidlObject {
+        id: library.id.clone(),
+        parent_id: ROOT_ID.to_string(),
+        title: library.name.clone(),
+        class: "object.container.storageFolder",
+        child_count: Some(children),
+        resource: None,
+        album_art_url: None,
+    })
+}
+
+async fn item_object(
+    state: &AppState,
+    user_id: &str,
+    base_url: &str,
+    item: &MediaItem,
+) -> anyhow::Result<DidlObject> {
+    let parent_id = item
+        .parent_id
+        .clone()
+        .unwrap_or_else(|| item.library_id.clone());
+    let album_art_url = Some(format!("{}/Items/{}/Images/Primary", base_url, item.id));
+
+    if CONTAINER_TYPES.contains(&item.item_type.as_str()) {
+        let children = ItemQuery::new()
+            .parent(&item.id)
+            .visible_to(user_id)
+            .count(&state.db)
+            .await?;
+        let class = match item.item_type.as_str() {
+            "MusicAlbum" => "object.container.album.musicAlbum",
+            "MusicArtist" => "object.container.person.musicArtist",
+            _ => "object.container.storageFolder",
+        };
+        return Ok(DidlObject {
+            id: item.id.clone(),
+            parent_id,
+            title: item.name.clone(),
+            class,
+            child_count: Some(children),
+            resource: None,
+            album_art_url,
+        });
+    }
+
+    let is_audio = item.item_type == "Audio";
+    let class = match item.item_type.as_str() {
+        "Movie" => "object.item.videoItem.movie",
+        "Audio" => "object.item.audioItem.musicTrack",
+        _ => "object.item.videoItem",
+    };
+    let title = match item.index_number {
+        Some(number) if matches!(item.item_type.as_str(), "Episode" | "Audio") => {
+            format!("{}. {}", number, item.name)
+        }
+        _ => item.name.clone(),
+    };
+    let resource = match item.path {
+        Some(ref path) if item.stream_url.is_none() => {
+            let size = tokio::fs::metadata(path).await.ok().map(|m| m.len());
+            Some(DidlResource {
+                url: format!("{}/dlna/media/{}", base_url, item.id),
+                mime_type: if is_audio {
+                    get_audio_content_type(path)
+                } else {
+                    get_content_type(path)
+                },
+                size,
+                duration_ticks: item.runtime_ticks,
+                resolution: item.width.zip(item.height),
+            })
+        }
+        _ => None,
+    };
+    Ok(DidlObject {
+        id: item.id.clone(),
+        parent_id,
+        title,
+        class,
+        child_count: None,
+        resource,
+        album_art_url,
+    })
+}
+
+/// GET /dlna/media/{itemId}
+/// The item's file with Range support, and the DLNA headers TVs expect
+async fn get_media(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(item_id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, DlnaError> {
+    require_dlna(&state, client)?;
+    let user_id = browse_user(&state).await?;
+    let item = visible_item(&state, &user_id, &item_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+    let Some(path) = item.path.filter(|_| item.stream_url.is_none()) else {
+        return Err((StatusCode::NOT_FOUND, "Item has no file".to_string()));
+    };
+    let content_type = if item.item_type == "Audio" {
+        get_audio_content_type(&path)
+    } else {
+        get_content_type(&path)
+    };
+
+    let mut response = serve_file(&headers, &path, content_type).await?;
+    let response_headers = response.headers_mut();
+    response_headers.insert(
+        "transferMode.dlna.org",
+        HeaderValue::from_static("Streaming"),
+    );
+    response_headers.insert(
+        "contentFeatures.dlna.org",
+        HeaderValue::from_static(dlna::DLNA_FEATURES),
+    );
+    Ok(response)
+}
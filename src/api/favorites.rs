@@ -3,7 +3,7 @@
 
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     routing::{delete, post},
     Json, Router,
@@ -11,7 +11,11 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::AppState;
+use crate::events::{self, ServerEvent};
+use crate::{services::auth, AppState};
+
+use super::playback::BulkItemsRequest;
+use super::users::parse_emby_auth_header;
 
 /// Query parameters for favorite operations
 #[derive(Debug, Deserialize)]
@@ -39,6 +43,8 @@ pub struct UserItemDataDto {
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
+        .route("/", post(bulk_add_favorites))
+        .route("/", delete(bulk_remove_favorites))
         .route("/:itemId", post(add_favorite))
         .route("/:itemId", delete(remove_favorite))
 }
@@ -71,6 +77,11 @@ async fn add_favorite(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    events::publish(ServerEvent::UserDataChanged {
+        user_id: user_id.clone(),
+        item_ids: vec![item_id.clone()],
+    });
+
     // Get playback progress for response
     let progress: Option<(i64, bool, i32, Option<String>)> = sqlx::query_as(
         "SELECT position_ticks, played, play_count, last_played FROM playback_progress WHERE user_id = ? AND item_id = ?",
@@ -115,6 +126,11 @@ async fn remove_favorite(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    events::publish(ServerEvent::UserDataChanged {
+        user_id: user_id.clone(),
+        item_ids: vec![item_id.clone()],
+    });
+
     // Get playback progress for response
     let progress: Option<(i64, bool, i32, Option<String>)> = sqlx::query_as(
         "SELECT position_ticks, played, play_count, last_played FROM playback_progress WHERE user_id = ? AND item_id = ?",
@@ -142,6 +158,104 @@ async fn remove_favorite(
     }))
 }
 
+async fn require_auth(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<crate::models::User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+/// POST /UserFavoriteItems?userId= - Mark many items as favorites
+async fn bulk_add_favorites(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<FavoriteQuery>,
+    Json(request): Json<BulkItemsRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    bulk_set_favorite(&state, &headers, query, request, true).await
+}
+
+/// DELETE /UserFavoriteItems?userId= - Remove many items from favorites
+async fn bulk_remove_favorites(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<FavoriteQuery>,
+    Json(request): Json<BulkItemsRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    bulk_set_favorite(&state, &headers, query, request, false).await
+}
+
+async fn bulk_set_favorite(
+    state: &AppState,
+    headers: &HeaderMap,
+    query: FavoriteQuery,
+    request: BulkItemsRequest,
+    favorite: bool,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+    // Default to the caller when no userId is given
+    let user_id = query.user_id.unwrap_or_else(|| user.id.clone());
+
+    if user.id != user_id && !user.is_admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot modify other user's data".to_string(),
+        ));
+    }
+
+    let item_ids = request.validated_ids()?;
+
+    // One statement (one implicit transaction) for the whole selection;
+    // unknown item IDs and unchanged favorites are skipped
+    let mut qb: sqlx::QueryBuilder<sqlx::Sqlite> = if favorite {
+        let mut qb = sqlx::QueryBuilder::new(
+            "INSERT OR IGNORE INTO user_favorites (user_id, item_id) SELECT ",
+        );
+        qb.push_bind(&user_id);
+        qb.push(", id FROM media_items WHERE id IN (");
+        qb
+    } else {
+        let mut qb = sqlx::QueryBuilder::new("DELETE FROM user_favorites WHERE user_id = ");
+        qb.push_bind(&user_id);
+        qb.push(" AND item_id IN (");
+        qb
+    };
+    let mut separated = qb.separated(", ");
+    for id in &item_ids {
+        separated.push_bind(id);
+    }
+    separated.push_unseparated(") RETURNING item_id");
+
+    let changed: Vec<String> = qb
+        .build_query_scalar()
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::debug!(
+        "{} {} favorite(s) for user {}",
+        if favorite { "Added" } else { "Removed" },
+        changed.len(),
+        user_id
+    );
+
+    if !changed.is_empty() {
+        events::publish(ServerEvent::UserDataChanged {
+            user_id,
+            item_ids: changed,
+        });
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// Check if an item is a favorite for a user
 pub async fn is_favorite(pool: &sqlx::SqlitePool, user_id: &str, item_id: &str) -> bool {
     sqlx::query_scalar::<_, i32>("SELECT 1 FROM user_favorites WHERE user_id = ? AND item_id = ?")
@@ -9,7 +9,10 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{services::auth, AppState};
+use crate::{
+    services::{auth, server_id},
+    AppState,
+};
 
 use super::items::{BaseItemDto, ImageTags, UserItemDataDto};
 use super::users::parse_emby_auth_header;
@@ -136,15 +139,17 @@ async fn get_genres(
             id: g.id,
             name: g.name,
             item_type: "Genre".to_string(),
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             parent_id: None,
             overview: None,
             year: None,
             production_year: None,
             index_number: None,
+            index_number_end: None,
             parent_index_number: None,
             runtime_ticks: None,
             community_rating: None,
+            official_rating: None,
             path: None,
             premiere_date: None,
             sort_name: None,
@@ -152,14 +157,21 @@ async fn get_genres(
             series_name: None,
             season_id: None,
             season_name: None,
+            music: None,
+            live_tv: None,
             is_folder: true,
             child_count: Some(g.item_count),
             media_type: None,
             collection_type: None,
             user_data: UserItemDataDto::default(),
             image_tags: None,
+            backdrop_image_tags: Vec::new(),
             provider_ids: None,
             media_sources: None,
+            width: None,
+            height: None,
+            is_hd: None,
+            is_4k: None,
             can_download: false,
             supports_media_source_display: false,
         })
@@ -208,15 +220,17 @@ async fn get_genre(
         id: genre.id,
         name: genre.name,
         item_type: "Genre".to_string(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         parent_id: None,
         overview: None,
         year: None,
         production_year: None,
         index_number: None,
+        index_number_end: None,
         parent_index_number: None,
         runtime_ticks: None,
         community_rating: None,
+        official_rating: None,
         path: None,
         premiere_date: None,
         sort_name: None,
@@ -224,14 +238,21 @@ async fn get_genre(
         series_name: None,
         season_id: None,
         season_name: None,
+        music: None,
+        live_tv: None,
         is_folder: true,
         child_count: Some(count.0),
         media_type: None,
         collection_type: None,
         user_data: UserItemDataDto::default(),
         image_tags: None,
+        backdrop_image_tags: Vec::new(),
         provider_ids: None,
         media_sources: None,
+        width: None,
+        height: None,
+        is_hd: None,
+        is_4k: None,
         can_download: false,
         supports_media_source_display: false,
     }))
@@ -308,15 +329,17 @@ async fn get_studios(
             id: s.id,
             name: s.name,
             item_type: "Studio".to_string(),
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             parent_id: None,
             overview: None,
             year: None,
             production_year: None,
             index_number: None,
+            index_number_end: None,
             parent_index_number: None,
             runtime_ticks: None,
             community_rating: None,
+            official_rating: None,
             path: None,
             premiere_date: None,
             sort_name: None,
@@ -324,14 +347,21 @@ async fn get_studios(
             series_name: None,
             season_id: None,
             season_name: None,
+            music: None,
+            live_tv: None,
             is_folder: true,
             child_count: Some(s.item_count),
             media_type: None,
             collection_type: None,
             user_data: UserItemDataDto::default(),
             image_tags: None,
+            backdrop_image_tags: Vec::new(),
             provider_ids: None,
             media_sources: None,
+            width: None,
+            height: None,
+            is_hd: None,
+            is_4k: None,
             can_download: false,
             supports_media_source_display: false,
         })
@@ -378,15 +408,17 @@ async fn get_studio(
         id: studio.id,
         name: studio.name,
         item_type: "Studio".to_string(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         parent_id: None,
         overview: None,
         year: None,
         production_year: None,
         index_number: None,
+        index_number_end: None,
         parent_index_number: None,
         runtime_ticks: None,
         community_rating: None,
+        official_rating: None,
         path: None,
         premiere_date: None,
         sort_name: None,
@@ -394,14 +426,21 @@ async fn get_studio(
         series_name: None,
         season_id: None,
         season_name: None,
+        music: None,
+        live_tv: None,
         is_folder: true,
         child_count: Some(count.0),
         media_type: None,
         collection_type: None,
         user_data: UserItemDataDto::default(),
         image_tags: None,
+        backdrop_image_tags: Vec::new(),
         provider_ids: None,
         media_sources: None,
+        width: None,
+        height: None,
+        is_hd: None,
+        is_4k: None,
         can_download: false,
         supports_media_source_display: false,
     }))
@@ -1,4 +1,4 @@
-// Home screen endpoints - Latest items, Resume, NextUp
+// Home screen endpoints - Latest items, Resume, NextUp, Suggestions
 
 use axum::{
     extract::{Path, State},
@@ -6,13 +6,37 @@ use axum::{
     routing::get,
     Json, Router,
 };
-use std::sync::Arc;
-
-use crate::{models::MediaItem, services::auth, AppState};
+use sqlx::SqlitePool;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::db::item_query::{ItemQuery, ItemSort, SortOrder};
+use crate::events::{self, ServerEvent};
+use crate::services::home_cache::{self, HomeList, ListCache};
+use crate::{
+    models::MediaItem,
+    services::{auth, library_views, server_id, suggestions},
+    AppState,
+};
 
-use super::items::{get_user_item_data, BaseItemDto, ImageTags, ItemsResponse, UserItemDataDto};
+use super::items::{
+    get_user_item_data, is_4k_resolution, is_hd_resolution, music_info, BaseItemDto, ImageTags,
+    ItemsResponse, UserItemDataDto,
+};
 use super::users::parse_emby_auth_header;
 
+/// Latest, Resume and Next Up lists by user (services::home_cache)
+static HOME_CACHE: LazyLock<Mutex<ListCache<BaseItemDto>>> =
+    LazyLock::new(|| Mutex::new(ListCache::default()));
+
+/// Quiet time after a scan before the home screen lists are warmed
+const WARM_DELAY: Duration = Duration::from_secs(10);
+
+/// Users seen this recently get their lists warmed
+const ACTIVE_USER_DAYS: i64 = 30;
+
 /// Routes for /Users/:userId/Items/Latest
 pub fn user_latest_routes() -> Router<Arc<AppState>> {
     Router::new().route("/", get(get_latest_items))
@@ -23,6 +47,11 @@ pub fn resume_routes() -> Router<Arc<AppState>> {
     Router::new().route("/", get(get_resume_items))
 }
 
+/// Routes for /Users/:userId/Suggestions
+pub fn suggestion_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(get_suggestions))
+}
+
 /// Routes for /Shows/NextUp
 pub fn next_up_routes() -> Router<Arc<AppState>> {
     Router::new().route("/", get(get_next_up))
@@ -140,6 +169,32 @@ impl NextUpQuery {
     }
 }
 
+#[derive(Debug, Default)]
+pub struct SuggestionsQuery {
+    pub item_types: Vec<String>,
+    pub start_index: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+impl SuggestionsQuery {
+    fn from_uri(uri: &Uri) -> Self {
+        let params = parse_query_params(uri.query().unwrap_or(""));
+        Self {
+            // Comma-separated or repeated: type=Movie,Series or type=Movie&type=Series
+            item_types: params
+                .get("type")
+                .into_iter()
+                .flatten()
+                .flat_map(|v| v.split(','))
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            start_index: get_param_i32(&params, "startIndex"),
+            limit: get_param_i32(&params, "limit"),
+        }
+    }
+}
+
 async fn require_auth(
     state: &AppState,
     headers: &HeaderMap,
@@ -161,7 +216,7 @@ fn media_item_to_dto(
 ) -> BaseItemDto {
     let is_folder = matches!(
         item.item_type.as_str(),
-        "Series" | "Season" | "Folder" | "CollectionFolder"
+        "Series" | "Season" | "MusicAlbum" | "MusicArtist" | "Folder" | "CollectionFolder"
     );
     let media_type = match item.item_type.as_str() {
         "Episode" | "Movie" => Some("Video".to_string()),
@@ -178,6 +233,9 @@ fn media_item_to_dto(
         if let Some(ref id) = item.imdb_id {
             ids.insert("Imdb".to_string(), id.clone());
         }
+        if let Some(ref id) = item.tvdb_id {
+            ids.insert("Tvdb".to_string(), id.clone());
+        }
         if let Some(ref id) = item.anilist_id {
             ids.insert("AniList".to_string(), id.clone());
         }
@@ -198,15 +256,17 @@ fn media_item_to_dto(
         id: item.id.clone(),
         name: item.name.clone(),
         item_type: item.item_type.clone(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         parent_id: item.parent_id.clone(),
         overview: item.overview.clone(),
         year: item.year,
         production_year: item.year,
-        index_number: item.index_number,
-        parent_index_number: item.parent_index_number,
+        index_number: item.index_number_for_display(),
+        index_number_end: item.index_number_end_for_display(),
+        parent_index_number: item.parent_index_number_for_display(),
         runtime_ticks: item.runtime_ticks,
         community_rating: item.community_rating,
+        official_rating: item.official_rating.clone(),
         path: item.path.clone(),
         premiere_date: item.premiere_date.clone(),
         sort_name: item.sort_name.clone(),
@@ -217,15 +277,24 @@ fn media_item_to_dto(
         },
         series_name,
         season_id: None,
-        season_name: item.parent_index_number.map(|s| format!("Season {}", s)),
+        season_name: item
+            .parent_index_number_for_display()
+            .map(|s| format!("Season {}", s)),
+        music: music_info(item),
+        live_tv: None,
         is_folder,
         child_count: None,
         media_type,
         collection_type: None,
         user_data: UserItemDataDto::default(),
+        backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
         image_tags,
         provider_ids,
         media_sources: None,
+        width: item.width,
+        height: item.height,
+        is_hd: is_hd_resolution(item.width, item.height),
+        is_4k: is_4k_resolution(item.width, item.height),
         can_download: item.path.is_some(),
         supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
     }
@@ -247,7 +316,10 @@ async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Opti
     for (image_type,) in images {
         match image_type.as_str() {
             "Primary" => tags.primary = Some(item_id.to_string()),
-            "Backdrop" => tags.backdrop = Some(item_id.to_string()),
+            "Backdrop" => {
+                tags.backdrop = Some(item_id.to_string());
+                tags.backdrop_count += 1;
+            }
             _ => {}
         }
     }
@@ -267,32 +339,304 @@ async fn get_latest_items(
     Path(_user_id): Path<String>,
     uri: Uri,
 ) -> Result<Json<Vec<BaseItemDto>>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
     let query = LatestQuery::from_uri(&uri);
 
     let limit = query.limit.unwrap_or(16).min(100);
+    let list = HomeList::Latest(query.parent_id);
+    let result = home_list(&state.db, &user.id, list, limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Build query - get latest episodes (or movies if we had them)
-    let mut sql = String::from("SELECT * FROM media_items WHERE item_type IN ('Episode', 'Movie')");
+    // Note: Latest endpoint returns an array directly, not wrapped in ItemsResponse
+    Ok(Json(result))
+}
 
-    // Filter by library if parent_id specified
-    if let Some(ref parent_id) = query.parent_id {
-        // parent_id is the library ID - filter by library_id
-        sql.push_str(&format!(
-            " AND library_id = '{}'",
-            parent_id.replace('\'', "''")
+/// GET /Users/:userId/Suggestions - Unwatched movies and series matching the user's watch history
+/// Ranked by the genres/studios the user watches most and community rating, refreshed daily
+async fn get_suggestions(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    uri: Uri,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    if user_id != user.id && !user.is_admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot view another user's suggestions".to_string(),
         ));
     }
+    if user_id != user.id {
+        let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM users WHERE id = ?")
+            .bind(&user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if exists.is_none() {
+            return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+        }
+    }
+    let query = SuggestionsQuery::from_uri(&uri);
 
-    // Order by creation time (newest first)
-    sql.push_str(" ORDER BY created_at DESC, id DESC");
-    sql.push_str(&format!(" LIMIT {}", limit));
+    let start_index = query.start_index.unwrap_or(0).max(0);
+    let limit = query.limit.unwrap_or(20).clamp(0, 100);
 
-    let items: Vec<MediaItem> = sqlx::query_as(&sql)
-        .fetch_all(&state.db)
+    let ranking = suggestions::suggestions_for_user(&state.db, &user_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let matching: Vec<&suggestions::Suggestion> = ranking
+        .iter()
+        .filter(|s| {
+            query.item_types.is_empty()
+                || query
+                    .item_types
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(&s.item_type))
+        })
+        .collect();
+
+    let mut result = Vec::new();
+    for suggestion in matching
+        .iter()
+        .skip(start_index as usize)
+        .take(limit as usize)
+    {
+        let item: Option<MediaItem> = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+            .bind(&suggestion.item_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        // Removed since the ranking was cached
+        let Some(item) = item else {
+            continue;
+        };
+
+        let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
+        let mut dto = media_item_to_dto(&item, None, image_tags);
+        dto.user_data = get_user_item_data(&state.db, &user_id, &item.id).await;
+        result.push(dto);
+    }
+
+    Ok(Json(ItemsResponse {
+        items: result,
+        total_record_count: matching.len() as i32,
+        start_index,
+    }))
+}
+
+/// GET /UserItems/Resume
+/// Returns items that are in progress (have playback position)
+async fn get_resume_items(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    let query = ResumeQuery::from_uri(&uri);
+
+    let limit = query.limit.unwrap_or(16).min(100);
+    let result = home_list(&state.db, &user.id, HomeList::Resume, limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ItemsResponse {
+        items: result,
+        total_record_count: 0, // Not including total count per client request
+        start_index: 0,
+    }))
+}
+
+/// GET /Shows/NextUp
+/// Returns the next unwatched episode for each series the user is watching
+async fn get_next_up(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    let query = NextUpQuery::from_uri(&uri);
+
+    let limit = query.limit.unwrap_or(16).min(100);
+    let result = home_list(&state.db, &user.id, HomeList::NextUp, limit)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ItemsResponse {
+        items: result,
+        total_record_count: 0,
+        start_index: 0,
+    }))
+}
+
+/// A user's home screen list, from the cache when it holds enough items
+async fn home_list(
+    pool: &SqlitePool,
+    user_id: &str,
+    list: HomeList,
+    limit: i32,
+) -> Result<Vec<BaseItemDto>, sqlx::Error> {
+    let cached = HOME_CACHE
+        .lock()
+        .unwrap()
+        .get(user_id, &list, limit, home_cache::TTL);
+    if let Some(items) = cached {
+        return Ok(items);
+    }
+    if limit > home_cache::CACHED_LIMIT {
+        return compute_home_list(pool, user_id, &list, limit).await;
+    }
+    let mut items = cache_home_list(pool, user_id, list).await?;
+    items.truncate(limit.max(0) as usize);
+    Ok(items)
+}
+
+/// Drop a user's cached lists (their library visibility changed)
+pub(crate) fn invalidate_user_lists(user_id: &str) {
+    HOME_CACHE.lock().unwrap().invalidate(Some(user_id));
+}
+
+/// Compute and cache a user's list, returning it
+async fn cache_home_list(
+    pool: &SqlitePool,
+    user_id: &str,
+    list: HomeList,
+) -> Result<Vec<BaseItemDto>, sqlx::Error> {
+    let generation = HOME_CACHE.lock().unwrap().generation();
+    let items = compute_home_list(pool, user_id, &list, home_cache::CACHED_LIMIT).await?;
+    HOME_CACHE
+        .lock()
+        .unwrap()
+        .store(user_id, list, items.clone(), generation);
+    Ok(items)
+}
+
+/// Cache the home screen lists of users seen recently: Latest for each
+/// library, Resume and Next Up
+async fn warm_home_cache(pool: &SqlitePool) -> Result<usize, sqlx::Error> {
+    let since = (chrono::Utc::now() - chrono::Duration::days(ACTIVE_USER_DAYS)).to_rfc3339();
+    let users: Vec<String> =
+        sqlx::query_scalar("SELECT DISTINCT user_id FROM sessions WHERE last_activity > ?")
+            .bind(since)
+            .fetch_all(pool)
+            .await?;
+    let libraries: Vec<String> = sqlx::query_scalar("SELECT id FROM libraries")
+        .fetch_all(pool)
+        .await?;
+
+    for user_id in &users {
+        let lists = libraries
+            .iter()
+            .map(|id| HomeList::Latest(Some(id.clone())))
+            .chain([HomeList::Latest(None), HomeList::Resume, HomeList::NextUp]);
+        for list in lists {
+            cache_home_list(pool, user_id, list).await?;
+        }
+    }
+    Ok(users.len())
+}
+
+/// Keep the home screen cache in sync with the event bus
+///
+/// Item and image changes drop every cached list, and a user's playback and
+/// user data changes drop theirs. Once a scan has finished and events have
+/// been quiet for a moment, the lists of active users are computed again, so
+/// the first home screen after a scan doesn't wait on cold queries.
+pub async fn run_home_cache(pool: SqlitePool, cancel: CancellationToken) {
+    let mut events = events::subscribe();
+    let mut needs_warm = false;
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(WARM_DELAY), if needs_warm => {
+                needs_warm = false;
+                match warm_home_cache(&pool).await {
+                    Ok(users) => tracing::debug!("Warmed home screen cache for {} user(s)", users),
+                    Err(e) => tracing::warn!("Failed to warm home screen cache: {}", e),
+                }
+                continue;
+            }
+            event = events.recv() => event,
+        };
+
+        let mut cache = HOME_CACHE.lock().unwrap();
+        match event {
+            Ok(ServerEvent::ItemAdded { .. })
+            | Ok(ServerEvent::ItemUpdated { .. })
+            | Ok(ServerEvent::ItemRemoved { .. })
+            | Ok(ServerEvent::ImageUpdated { .. }) => cache.invalidate(None),
+            Ok(ServerEvent::PlaybackStarted { user_id, .. })
+            | Ok(ServerEvent::PlaybackStopped { user_id, .. })
+            | Ok(ServerEvent::UserDataChanged { user_id, .. }) => cache.invalidate(Some(&user_id)),
+            Ok(ServerEvent::ScanCompleted { .. }) => {
+                cache.invalidate(None);
+                needs_warm = true;
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!("Home screen cache missed {} events", skipped);
+                cache.invalidate(None);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn compute_home_list(
+    pool: &SqlitePool,
+    user_id: &str,
+    list: &HomeList,
+    limit: i32,
+) -> Result<Vec<BaseItemDto>, sqlx::Error> {
+    match list {
+        HomeList::Latest(parent_id) => {
+            latest_items(pool, user_id, parent_id.as_deref(), limit).await
+        }
+        HomeList::Resume => resume_items(pool, user_id, limit).await,
+        HomeList::NextUp => next_up_items(pool, user_id, limit).await,
+    }
+}
+
+/// Latest episodes and movies visible to a user, of one library or all
+async fn latest_items(
+    pool: &SqlitePool,
+    user_id: &str,
+    parent_id: Option<&str>,
+    limit: i32,
+) -> Result<Vec<BaseItemDto>, sqlx::Error> {
+    // Latest episodes and movies, newest first
+    let mut item_query = ItemQuery::new()
+        .include_types(&["Episode", "Movie"])
+        .visible_to(user_id)
+        .sort(ItemSort::Column("created_at"), SortOrder::Descending)
+        .sort(ItemSort::Column("id"), SortOrder::Descending)
+        .limit(limit);
+
+    // Libraries the user took out of their Latest sections have none
+    let views = library_views::load(pool, user_id).await?;
+
+    // Filter by library if parent_id specified
+    if let Some(parent_id) = parent_id {
+        if views.is_latest_excluded(parent_id) {
+            return Ok(Vec::new());
+        }
+        // parent_id is the library ID - filter by library_id
+        item_query = item_query.library(parent_id);
+    } else if !views.latest_excluded.is_empty() {
+        let libraries: Vec<String> = sqlx::query_scalar("SELECT id FROM libraries")
+            .fetch_all(pool)
+            .await?;
+        let included: Vec<String> = libraries
+            .into_iter()
+            .filter(|id| !views.is_latest_excluded(id))
+            .collect();
+        item_query = item_query.in_libraries(&included);
+    }
+
+    let items: Vec<MediaItem> = item_query.fetch_all(pool).await?;
+
     // Get series names for episodes
     let mut result = Vec::new();
     for item in items {
@@ -301,7 +645,7 @@ async fn get_latest_items(
                 let series: Option<MediaItem> =
                     sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
                         .bind(parent_id)
-                        .fetch_optional(&state.db)
+                        .fetch_optional(pool)
                         .await
                         .ok()
                         .flatten();
@@ -312,40 +656,31 @@ async fn get_latest_items(
         } else {
             None
         };
-        let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
+        let image_tags = get_image_tags_for_item(pool, &item.id).await;
         result.push(media_item_to_dto(&item, series_name, image_tags));
     }
 
-    // Note: Latest endpoint returns an array directly, not wrapped in ItemsResponse
-    Ok(Json(result))
+    Ok(result)
 }
 
-/// GET /UserItems/Resume
-/// Returns items that are in progress (have playback position)
-async fn get_resume_items(
-    State(state): State<Arc<AppState>>,
-    headers: HeaderMap,
-    uri: Uri,
-) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
-    let user = require_auth(&state, &headers).await?;
-    let query = ResumeQuery::from_uri(&uri);
-
-    let limit = query.limit.unwrap_or(16).min(100);
-
+/// Episodes and movies a user is partway through, last played first
+async fn resume_items(
+    pool: &SqlitePool,
+    user_id: &str,
+    limit: i32,
+) -> Result<Vec<BaseItemDto>, sqlx::Error> {
     // Get items with playback progress for this user
-    let items: Vec<MediaItem> = sqlx::query_as(
-        "SELECT m.* FROM media_items m
-         INNER JOIN playback_progress p ON m.id = p.item_id
-         WHERE p.user_id = ? AND p.position_ticks > 0 AND p.played = 0
-         AND m.item_type IN ('Episode', 'Movie')
-         ORDER BY p.last_played DESC
-         LIMIT ?",
-    )
-    .bind(&user.id)
-    .bind(limit)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let items: Vec<MediaItem> = ItemQuery::new()
+        .include_types(&["Episode", "Movie"])
+        .in_progress_for(user_id, true)
+        .visible_to(user_id)
+        .sort(
+            ItemSort::LastPlayed(user_id.to_string()),
+            SortOrder::Descending,
+        )
+        .limit(limit)
+        .fetch_all(pool)
+        .await?;
 
     // Get series names and playback progress for each item
     let mut result = Vec::new();
@@ -355,7 +690,7 @@ async fn get_resume_items(
                 let series: Option<MediaItem> =
                     sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
                         .bind(parent_id)
-                        .fetch_optional(&state.db)
+                        .fetch_optional(pool)
                         .await
                         .ok()
                         .flatten();
@@ -367,16 +702,16 @@ async fn get_resume_items(
             None
         };
 
-        let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
+        let image_tags = get_image_tags_for_item(pool, &item.id).await;
         let mut dto = media_item_to_dto(&item, series_name, image_tags);
 
         // Get playback progress for this item
         let progress: Option<(i64, bool)> = sqlx::query_as(
             "SELECT position_ticks, played FROM playback_progress WHERE user_id = ? AND item_id = ?",
         )
-        .bind(&user.id)
+        .bind(user_id)
         .bind(&item.id)
-        .fetch_optional(&state.db)
+        .fetch_optional(pool)
         .await
         .ok()
         .flatten();
@@ -386,9 +721,9 @@ async fn get_resume_items(
             let is_favorite = sqlx::query_scalar::<_, i32>(
                 "SELECT 1 FROM user_favorites WHERE user_id = ? AND item_id = ?",
             )
-            .bind(&user.id)
+            .bind(user_id)
             .bind(&item.id)
-            .fetch_optional(&state.db)
+            .fetch_optional(pool)
             .await
             .ok()
             .flatten()
@@ -406,54 +741,30 @@ async fn get_resume_items(
         result.push(dto);
     }
 
-    Ok(Json(ItemsResponse {
-        items: result,
-        total_record_count: 0, // Not including total count per client request
-        start_index: 0,
-    }))
+    Ok(result)
 }
 
-/// GET /Shows/NextUp
-/// Returns the next unwatched episode for each series the user is watching
-async fn get_next_up(
-    State(state): State<Arc<AppState>>,
-    headers: HeaderMap,
-    uri: Uri,
-) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
-    let user = require_auth(&state, &headers).await?;
-    let query = NextUpQuery::from_uri(&uri);
-
-    let limit = query.limit.unwrap_or(16).min(100);
-
-    // Find series where the user has watched at least one episode
-    // Then get the next unwatched episode
-    let items: Vec<MediaItem> = sqlx::query_as(
-        "SELECT m.* FROM media_items m
-         WHERE m.item_type = 'Episode'
-         AND m.parent_id IN (
-             -- Series where user has progress
-             SELECT DISTINCT m2.parent_id FROM media_items m2
-             INNER JOIN playback_progress p ON m2.id = p.item_id
-             WHERE p.user_id = ? AND m2.item_type = 'Episode'
-         )
-         AND m.id NOT IN (
-             -- Episodes already fully watched
-             SELECT item_id FROM playback_progress WHERE user_id = ? AND played = 1
-         )
-         AND m.id NOT IN (
-             -- Episodes in progress (those go to Resume)
-             SELECT item_id FROM playback_progress WHERE user_id = ? AND position_ticks > 0 AND played = 0
-         )
-         ORDER BY m.parent_id, m.parent_index_number, m.index_number
-         LIMIT ?",
-    )
-    .bind(&user.id)
-    .bind(&user.id)
-    .bind(&user.id)
-    .bind(limit)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+/// The next episode of each series a user is watching
+async fn next_up_items(
+    pool: &SqlitePool,
+    user_id: &str,
+    limit: i32,
+) -> Result<Vec<BaseItemDto>, sqlx::Error> {
+    // Find series where the user has watched at least one episode,
+    // then the episodes not yet watched (in-progress episodes go to Resume).
+    // Specials are left out, as in Jellyfin, so an OVA isn't always next
+    let items: Vec<MediaItem> = ItemQuery::new()
+        .include_types(&["Episode"])
+        .exclude_specials()
+        .series_started_by(user_id)
+        .played_by(user_id, false)
+        .in_progress_for(user_id, false)
+        .visible_to(user_id)
+        .sort(ItemSort::Column("parent_id"), SortOrder::Ascending)
+        .sort(ItemSort::EpisodeOrder, SortOrder::Ascending)
+        .limit(limit)
+        .fetch_all(pool)
+        .await?;
 
     // Deduplicate - only one episode per series (the next one to watch)
     let mut seen_series: std::collections::HashSet<String> = std::collections::HashSet::new();
@@ -471,7 +782,7 @@ async fn get_next_up(
             let series: Option<MediaItem> =
                 sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
                     .bind(parent_id)
-                    .fetch_optional(&state.db)
+                    .fetch_optional(pool)
                     .await
                     .ok()
                     .flatten();
@@ -480,7 +791,7 @@ async fn get_next_up(
             None
         };
 
-        let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
+        let image_tags = get_image_tags_for_item(pool, &item.id).await;
         result.push(media_item_to_dto(&item, series_name, image_tags));
 
         if result.len() >= limit as usize {
@@ -488,9 +799,5 @@ async fn get_next_up(
         }
     }
 
-    Ok(Json(ItemsResponse {
-        items: result,
-        total_record_count: 0,
-        start_index: 0,
-    }))
+    Ok(result)
 }
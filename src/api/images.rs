@@ -11,7 +11,15 @@ use std::sync::Arc;
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::{
+    models::MediaItem,
+    services::{
+        auth, box_sets, image_proxy,
+        image_resize::{self, OutputFormat, ResizeOptions},
+        library_images, live_tv,
+    },
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -58,46 +66,83 @@ async fn get_item_images(
 
     let mut images = Vec::new();
 
-    // Check if this is a synthetic season ID
-    let actual_item_id = if let Some(pos) = path.item_id.rfind("_season_") {
-        &path.item_id[..pos]
-    } else {
-        &path.item_id
-    };
+    let actual_item_id = &image_owner(&state, &path.item_id).await;
 
     // Query images from database
     #[derive(sqlx::FromRow)]
     struct ImageRow {
         image_type: String,
+        image_index: i64,
         path: String,
+        width: Option<i32>,
+        height: Option<i32>,
+    }
+
+    if is_library(&state, actual_item_id).await {
+        let image_dir = state.config.paths.image_cache_dir();
+        for image_type in &["Primary", "Backdrop", "Banner", "Thumb"] {
+            let Some(tag) =
+                library_images::image_tag(&state.db, &image_dir, actual_item_id, image_type).await
+            else {
+                continue;
+            };
+            images.push(ImageInfo {
+                image_type: image_type.to_string(),
+                image_index: Some(0),
+                image_tag: Some(tag),
+                path: None,
+                blur_hash: None,
+                height: None,
+                width: None,
+                size: None,
+            });
+        }
+        return Ok(Json(images));
+    }
+
+    // TMDB collections (services::box_sets) have the collection's artwork
+    for image_type in &["Primary", "Backdrop"] {
+        if let Ok(Some(url)) = box_sets::artwork_url(&state.db, actual_item_id, image_type).await {
+            images.push(ImageInfo {
+                image_type: image_type.to_string(),
+                image_index: Some(0),
+                image_tag: Some(box_sets::artwork_tag(&url)),
+                path: None,
+                blur_hash: None,
+                height: None,
+                width: None,
+                size: None,
+            });
+        }
     }
 
-    let db_images: Vec<ImageRow> =
-        sqlx::query_as("SELECT image_type, path FROM images WHERE item_id = ? ORDER BY image_type")
-            .bind(actual_item_id)
-            .fetch_all(&state.db)
+    let db_images: Vec<ImageRow> = sqlx::query_as(
+        "SELECT image_type, image_index, path, width, height FROM images
+         WHERE item_id = ? ORDER BY image_type, image_index",
+    )
+    .bind(actual_item_id)
+    .fetch_all(&state.db)
+    .await
+    .unwrap_or_default();
+
+    for row in &db_images {
+        // Get file metadata for size; dimensions were stored when downloaded
+        let size = tokio::fs::metadata(&row.path)
             .await
-            .unwrap_or_default();
-
-    for (idx, row) in db_images.iter().enumerate() {
-        // Get file metadata for size
-        let (size, width, height) = if let Ok(meta) = tokio::fs::metadata(&row.path).await {
-            (Some(meta.len() as i64), None, None) // TODO: Get actual dimensions
-        } else {
-            (None, None, None)
-        };
+            .ok()
+            .map(|meta| meta.len() as i64);
 
         // Generate a simple tag from the path hash
         let tag = format!("{:x}", md5_hash(&row.path));
 
         images.push(ImageInfo {
             image_type: row.image_type.clone(),
-            image_index: Some(idx as i32),
+            image_index: Some(row.image_index as i32),
             image_tag: Some(tag),
             path: Some(row.path.clone()),
             blur_hash: None, // TODO: Generate blur hashes
-            height,
-            width,
+            height: row.height,
+            width: row.width,
             size,
         });
     }
@@ -105,7 +150,8 @@ async fn get_item_images(
     // If no database images, try to find local images
     if images.is_empty() {
         for image_type in &["Primary", "Backdrop", "Banner", "Thumb"] {
-            if let Some(img_path) = find_image_for_item(&state, &path.item_id, image_type).await {
+            if let Some(img_path) = find_image_for_item(&state, &path.item_id, image_type, 0).await
+            {
                 let (size, width, height) = if let Ok(meta) = tokio::fs::metadata(&img_path).await {
                     (Some(meta.len() as i64), None, None)
                 } else {
@@ -147,22 +193,54 @@ fn md5_hash(input: &str) -> u64 {
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/:itemId/Images", get(get_item_images))
-        .route("/:itemId/Images/:imageType", get(get_image))
+        .route(
+            "/:itemId/Images/:imageType",
+            get(get_image).post(upload_image).delete(delete_image),
+        )
         .route("/:itemId/Images/:imageType/:index", get(get_image_indexed))
 }
 
+/// Routes mounted at /Images
+pub fn remote_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/Remote", get(get_remote_image))
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ImageQuery {
+    #[serde(alias = "maxWidth")]
     pub max_width: Option<u32>,
+    #[serde(alias = "maxHeight")]
     pub max_height: Option<u32>,
+    #[serde(alias = "width")]
     pub width: Option<u32>,
+    #[serde(alias = "height")]
     pub height: Option<u32>,
+    #[serde(alias = "quality")]
     pub quality: Option<u32>,
+    #[serde(alias = "fillWidth")]
     pub fill_width: Option<u32>,
+    #[serde(alias = "fillHeight")]
     pub fill_height: Option<u32>,
+    #[serde(alias = "format")]
+    pub format: Option<String>,
+    // The tag only busts client caches
     pub tag: Option<String>,
-    // We ignore most of these for now - just serve original images
+}
+
+impl ImageQuery {
+    fn resize_options(&self) -> ResizeOptions {
+        ResizeOptions {
+            width: self.width,
+            height: self.height,
+            max_width: self.max_width,
+            max_height: self.max_height,
+            fill_width: self.fill_width,
+            fill_height: self.fill_height,
+            quality: self.quality.map(|q| q.min(100) as u8),
+            format: self.format.as_deref().and_then(OutputFormat::parse),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -196,28 +274,127 @@ fn get_image_content_type(path: &str) -> &'static str {
     }
 }
 
+/// Whether an ID belongs to a library (CollectionFolder) rather than a media item
+async fn is_library(state: &AppState, id: &str) -> bool {
+    sqlx::query_as::<_, (String,)>("SELECT id FROM libraries WHERE id = ?")
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await
+        .ok()
+        .flatten()
+        .is_some()
+}
+
+/// The item whose images are shown for an item ID
+///
+/// Seasons without artwork of their own show their series', as do season IDs
+/// from before seasons were items ({series_id}_season_{num}).
+async fn image_owner(state: &AppState, item_id: &str) -> String {
+    if let Some(pos) = item_id.rfind("_season_") {
+        return item_id[..pos].to_string();
+    }
+    let series_id: Option<Option<String>> = sqlx::query_scalar(
+        "SELECT parent_id FROM media_items m WHERE id = ? AND item_type = 'Season'
+           AND NOT EXISTS (SELECT 1 FROM images i WHERE i.item_id = m.id)",
+    )
+    .bind(item_id)
+    .fetch_optional(&state.db)
+    .await
+    .ok()
+    .flatten();
+    series_id.flatten().unwrap_or_else(|| item_id.to_string())
+}
+
+/// Cached TMDB artwork of a collection (None for anything else)
+async fn collection_image(state: &AppState, id: &str, image_type: &str) -> Option<String> {
+    let url = box_sets::artwork_url(&state.db, id, image_type)
+        .await
+        .ok()??;
+    let url = image_proxy::validate_url(&url).ok()?;
+    match image_proxy::fetch(&state.config.paths.image_cache_dir(), &url).await {
+        Ok(path) => Some(path.to_string_lossy().to_string()),
+        Err(e) => {
+            tracing::warn!("Failed to fetch collection image {}: {:#}", url, e);
+            None
+        }
+    }
+}
+
+/// A Live TV channel logo or program image, cached locally
+///
+/// The URLs come from the admin's playlist and guide rather than a metadata
+/// provider, so they aren't limited to the provider hosts; local paths are
+/// served as they are.
+async fn live_tv_image(state: &AppState, id: &str, image_type: &str) -> Option<String> {
+    if image_type != "Primary" {
+        return None;
+    }
+    let url = live_tv::image_url(&state.db, id).await.ok()??;
+    match reqwest::Url::parse(&url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {
+            match image_proxy::fetch(&state.config.paths.image_cache_dir(), &parsed).await {
+                Ok(path) => Some(path.to_string_lossy().to_string()),
+                Err(e) => {
+                    tracing::warn!("Failed to fetch Live TV image {}: {:#}", url, e);
+                    None
+                }
+            }
+        }
+        Ok(_) => None,
+        Err(_) => tokio::fs::metadata(&url).await.is_ok().then_some(url),
+    }
+}
+
 /// Common image file patterns to search for
 const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif"];
 
 /// Search for image files near a media item
-async fn find_image_for_item(state: &AppState, item_id: &str, image_type: &str) -> Option<String> {
-    // Check if this is a synthetic season ID (format: {series_id}_season_{num})
-    // If so, use the series ID for image lookup
-    let actual_item_id = if let Some(pos) = item_id.rfind("_season_") {
-        // Extract series_id from synthetic season ID (everything before _season_)
-        &item_id[..pos]
-    } else {
-        item_id
-    };
+///
+/// `index` picks among an item's images of a type (its backdrops); on disk the
+/// later ones are named like backdrop1.jpg, fanart2.jpg.
+async fn find_image_for_item(
+    state: &AppState,
+    item_id: &str,
+    image_type: &str,
+    index: u32,
+) -> Option<String> {
+    let actual_item_id = &image_owner(state, item_id).await;
+
+    // Libraries have their own artwork: an uploaded image or a poster collage
+    if is_library(state, actual_item_id).await {
+        if index > 0 {
+            return None;
+        }
+        return library_images::library_image(
+            &state.db,
+            &state.config.paths.image_cache_dir(),
+            actual_item_id,
+            image_type,
+        )
+        .await
+        .map(|p| p.to_string_lossy().to_string());
+    }
+
+    // TMDB collections show the collection's poster and backdrop, cached locally
+    if index == 0 {
+        if let Some(path) = collection_image(state, actual_item_id, image_type).await {
+            return Some(path);
+        }
+        if let Some(path) = live_tv_image(state, actual_item_id, image_type).await {
+            return Some(path);
+        }
+    }
 
     // First check if we have an image in the database
-    let db_image: Option<(String,)> =
-        sqlx::query_as("SELECT path FROM images WHERE item_id = ? AND image_type = ?")
-            .bind(actual_item_id)
-            .bind(image_type)
-            .fetch_optional(&state.db)
-            .await
-            .ok()?;
+    let db_image: Option<(String,)> = sqlx::query_as(
+        "SELECT path FROM images WHERE item_id = ? AND image_type = ? AND image_index = ?",
+    )
+    .bind(actual_item_id)
+    .bind(image_type)
+    .bind(index)
+    .fetch_optional(&state.db)
+    .await
+    .ok()?;
 
     if let Some((path,)) = db_image {
         if tokio::fs::metadata(&path).await.is_ok() {
@@ -265,6 +442,10 @@ async fn find_image_for_item(state: &AppState, item_id: &str, image_type: &str)
 
     // Search for matching image files
     for pattern in &patterns {
+        let pattern = match index {
+            0 => pattern.clone(),
+            _ => format!("{}{}", pattern, index),
+        };
         for ext in IMAGE_EXTENSIONS {
             let filename = format!("{}.{}", pattern, ext);
             let image_path = search_dir.join(&filename);
@@ -278,7 +459,7 @@ async fn find_image_for_item(state: &AppState, item_id: &str, image_type: &str)
     if item.item_type == "Episode" {
         if let Some(ref parent_id) = item.parent_id {
             // Try to find image for parent series
-            return Box::pin(find_image_for_item(state, parent_id, image_type)).await;
+            return Box::pin(find_image_for_item(state, parent_id, image_type, index)).await;
         }
     }
 
@@ -290,7 +471,7 @@ async fn get_image(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(path): Path<ImagePath>,
-    Query(_query): Query<ImageQuery>,
+    Query(query): Query<ImageQuery>,
 ) -> Result<Response, (StatusCode, String)> {
     // Images don't require auth in Jellyfin by default
     // But we'll check if there's a token and validate it if present
@@ -298,31 +479,188 @@ async fn get_image(
         let _ = auth::validate_session(&state.db, &token).await;
     }
 
-    let image_path = find_image_for_item(&state, &path.item_id, &path.image_type)
+    let image_path = find_image_for_item(&state, &path.item_id, &path.image_type, 0)
         .await
         .ok_or_else(|| (StatusCode::NOT_FOUND, "Image not found".to_string()))?;
 
-    serve_image_file(&image_path).await
+    serve_resized_image(&state, &image_path, &query).await
 }
 
-/// GET /Items/:itemId/Images/:imageType/:index
+/// GET /Items/:itemId/Images/:imageType/:index - One of an item's images of a type (backdrops)
 async fn get_image_indexed(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(path): Path<ImagePathIndexed>,
     Query(query): Query<ImageQuery>,
 ) -> Result<Response, (StatusCode, String)> {
-    // For now, ignore index and return the primary image
-    get_image(
-        State(state),
-        headers,
-        Path(ImagePath {
-            item_id: path.item_id,
-            image_type: path.image_type,
-        }),
-        Query(query),
+    if let Some((_, _, _, Some(token))) = parse_emby_auth_header(&headers) {
+        let _ = auth::validate_session(&state.db, &token).await;
+    }
+
+    let image_path = find_image_for_item(&state, &path.item_id, &path.image_type, path.index)
+        .await
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Image not found".to_string()))?;
+
+    serve_resized_image(&state, &image_path, &query).await
+}
+
+async fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    let user = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    Ok(())
+}
+
+/// POST /Items/:itemId/Images/:imageType - Set a library's image (admin only)
+/// The body is the image, base64-encoded like Jellyfin clients send it, or raw bytes
+async fn upload_image(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(path): Path<ImagePath>,
+    body: axum::body::Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    if !is_library(&state, &path.item_id).await {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Image uploads are only supported for libraries".to_string(),
+        ));
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("image/jpeg");
+    let extension = match content_type.split(';').next().unwrap_or("").trim() {
+        "image/png" => "png",
+        "image/webp" => "webp",
+        "image/gif" => "gif",
+        "image/jpeg" | "image/jpg" => "jpg",
+        other => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("Unsupported image type: {}", other),
+            ))
+        }
+    };
+
+    use base64::Engine;
+    let text: Vec<u8> = body
+        .iter()
+        .copied()
+        .filter(|b| !b.is_ascii_whitespace())
+        .collect();
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(&text)
+        .unwrap_or_else(|_| body.to_vec());
+    if data.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Empty image".to_string()));
+    }
+
+    library_images::save_user_image(
+        &state.config.paths.image_cache_dir(),
+        &path.item_id,
+        &path.image_type,
+        extension,
+        &data,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("Set {} image for library {}", path.image_type, path.item_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /Items/:itemId/Images/:imageType - Remove a library's uploaded image (admin only)
+async fn delete_image(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(path): Path<ImagePath>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    if !is_library(&state, &path.item_id).await {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Image deletion is only supported for libraries".to_string(),
+        ));
+    }
+
+    let removed = library_images::delete_user_image(
+        &state.config.paths.image_cache_dir(),
+        &path.item_id,
+        &path.image_type,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err((StatusCode::NOT_FOUND, "Image not found".to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoteImageQuery {
+    #[serde(alias = "imageUrl")]
+    pub url: Option<String>,
+}
+
+/// GET /Images/Remote?url= - Proxy a metadata provider image (e.g. a RemoteImages result)
+async fn get_remote_image(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RemoteImageQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    // Like local images this needs no auth; the host allow-list keeps it from
+    // being an open proxy
+    let url = query
+        .url
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "url is required".to_string()))?;
+    let url =
+        image_proxy::validate_url(&url).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let path = image_proxy::fetch(&state.config.paths.image_cache_dir(), &url)
+        .await
+        .map_err(|e| {
+            tracing::debug!("Remote image {} failed: {:#}", url, e);
+            (StatusCode::BAD_GATEWAY, format!("{:#}", e))
+        })?;
+    serve_image_file(&path.to_string_lossy()).await
+}
+
+/// Serve an image in the size and format the query asks for (services::image_resize)
+///
+/// Images that can't be resized (unreadable or unsupported) are served as they are.
+async fn serve_resized_image(
+    state: &AppState,
+    path: &str,
+    query: &ImageQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let source = std::path::Path::new(path);
+    match image_resize::resize(
+        &state.config.paths.image_cache_dir(),
+        source,
+        &query.resize_options(),
     )
     .await
+    {
+        Ok(resized) => serve_image_file(&resized.to_string_lossy()).await,
+        Err(e) => {
+            tracing::debug!("Cannot resize image {}: {:#}", path, e);
+            serve_image_file(path).await
+        }
+    }
 }
 
 /// Serve an image file
@@ -0,0 +1,245 @@
+// Registration invites API
+// Admins create expiring invite codes; anyone holding one can create an
+// account with the invite's library access and is signed in right away
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    models::User,
+    services::{auth, invites},
+    AppState,
+};
+
+use super::users::{login, parse_emby_auth_header, AuthenticationResult};
+
+/// Default invite lifetime: one week
+const DEFAULT_LIFETIME_HOURS: i64 = 7 * 24;
+
+/// Routes mounted at /Users/Invites
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_invites).post(create_invite))
+        .route("/:code", get(get_invite).delete(revoke_invite))
+        .route("/:code/Redeem", post(redeem_invite))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreateInviteRequest {
+    /// How long the invite is valid (default a week, at most 30 days)
+    pub expires_in_hours: Option<i64>,
+    /// How many accounts may be created with it (default 1)
+    pub max_uses: Option<i64>,
+    /// Library access of created accounts (default all libraries)
+    pub enable_all_folders: Option<bool>,
+    #[serde(default)]
+    pub enabled_folders: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RedeemInviteRequest {
+    pub name: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct InviteDto {
+    pub code: String,
+    pub created_by: String,
+    pub date_created: String,
+    pub expiration_date: String,
+    pub max_uses: i64,
+    pub use_count: i64,
+    pub enable_all_folders: bool,
+    pub enabled_folders: Vec<String>,
+    pub is_valid: bool,
+}
+
+/// What anyone holding a code may learn about its invite
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublicInviteDto {
+    pub code: String,
+    pub expiration_date: String,
+    pub is_valid: bool,
+}
+
+impl From<invites::Invite> for InviteDto {
+    fn from(invite: invites::Invite) -> Self {
+        Self {
+            is_valid: invite.is_usable(chrono::Utc::now()),
+            enabled_folders: invite.library_access().unwrap_or_default(),
+            code: invite.code,
+            created_by: invite.created_by,
+            date_created: invite.created_at,
+            expiration_date: invite.expires_at,
+            max_uses: invite.max_uses,
+            use_count: invite.use_count,
+            enable_all_folders: invite.enable_all_folders,
+        }
+    }
+}
+
+async fn require_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    let user = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+
+    Ok(user)
+}
+
+/// GET /Users/Invites - List invites, newest first (admin)
+async fn list_invites(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<InviteDto>>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let invites = invites::list(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(invites.into_iter().map(InviteDto::from).collect()))
+}
+
+/// POST /Users/Invites - Create an invite (admin)
+async fn create_invite(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateInviteRequest>,
+) -> Result<Json<InviteDto>, (StatusCode, String)> {
+    let admin = require_admin(&state, &headers).await?;
+
+    let library_access = if req.enable_all_folders.unwrap_or(true) {
+        None
+    } else {
+        for library_id in &req.enabled_folders {
+            let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM libraries WHERE id = ?")
+                .bind(library_id)
+                .fetch_optional(&state.db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            if exists.is_none() {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown library {}", library_id),
+                ));
+            }
+        }
+        Some(req.enabled_folders)
+    };
+
+    let invite = invites::create(
+        &state.db,
+        &admin.id,
+        req.expires_in_hours.unwrap_or(DEFAULT_LIFETIME_HOURS),
+        req.max_uses.unwrap_or(1),
+        library_access.as_deref(),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "Admin {} created an invite for {} account(s), valid until {}",
+        admin.id,
+        invite.max_uses,
+        invite.expires_at
+    );
+    Ok(Json(invite.into()))
+}
+
+/// GET /Users/Invites/:code - Check an invite before registering (public)
+async fn get_invite(
+    State(state): State<Arc<AppState>>,
+    Path(code): Path<String>,
+) -> Result<Json<PublicInviteDto>, (StatusCode, String)> {
+    let invite = invites::get(&state.db, &code)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Invite not found".to_string()))?;
+
+    Ok(Json(PublicInviteDto {
+        is_valid: invite.is_usable(chrono::Utc::now()),
+        code: invite.code,
+        expiration_date: invite.expires_at,
+    }))
+}
+
+/// DELETE /Users/Invites/:code - Revoke an invite (admin)
+async fn revoke_invite(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let removed = invites::revoke(&state.db, &code)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !removed {
+        return Err((StatusCode::NOT_FOUND, "Invite not found".to_string()));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /Users/Invites/:code/Redeem - Create an account with an invite and
+/// sign in (public)
+async fn redeem_invite(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(code): Path<String>,
+    Json(req): Json<RedeemInviteRequest>,
+) -> Result<Json<AuthenticationResult>, (StatusCode, String)> {
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required".to_string()));
+    }
+    if req.password.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Password is required".to_string()));
+    }
+
+    let taken: Option<(String,)> =
+        sqlx::query_as("SELECT id FROM users WHERE LOWER(name) = LOWER(?)")
+            .bind(name)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if taken.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "A user with that name already exists".to_string(),
+        ));
+    }
+
+    invites::redeem(&state.db, &code, name, &req.password)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| {
+            (
+                StatusCode::GONE,
+                "Invite not found, expired or used up".to_string(),
+            )
+        })?;
+
+    login(&state, &headers, name, &req.password).await.map(Json)
+}
@@ -12,9 +12,21 @@ use std::sync::Arc;
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
-use crate::{models::MediaItem, services::auth, services::mediainfo, AppState};
+use crate::db::item_query::{ItemQuery, ItemSort, SortOrder};
+use crate::events::{self, ServerEvent};
+use crate::scanner::music;
+use crate::services::{
+    box_sets, episode_order, external_streams, library_images, library_views, parental_controls,
+    season_mapping, server_id, tmdb::TmdbClient,
+};
+use crate::{models::Library, models::MediaItem, services::auth, services::mediainfo, AppState};
+
+pub use crate::db::item_query::{is_4k_resolution, is_hd_resolution};
 
-use super::playbackinfo::{MediaSourceInfo, MediaStreamInfo};
+use super::playbackinfo::{
+    apply_stream_url, external_stream_info, version_name, video_stream_info, MediaSourceInfo,
+    MediaStreamInfo,
+};
 
 fn parse_query_params(query: &str) -> std::collections::HashMap<String, Vec<String>> {
     let mut params: std::collections::HashMap<String, Vec<String>> =
@@ -53,7 +65,10 @@ fn get_param_bool(
 
 /// Build MediaSourceInfo for a media item (used for single item requests)
 /// This provides video/audio/subtitle stream info to clients like Fladder
-async fn build_media_source_for_item(item: &MediaItem) -> Option<MediaSourceInfo> {
+async fn build_media_source_for_item(
+    pool: &sqlx::SqlitePool,
+    item: &MediaItem,
+) -> Option<MediaSourceInfo> {
     let file_path = item.path.as_ref()?;
 
     // Get file size
@@ -72,44 +87,7 @@ async fn build_media_source_for_item(item: &MediaItem) -> Option<MediaSourceInfo
 
     // Add video stream
     if media_info.video_codec.is_some() {
-        media_streams.push(MediaStreamInfo {
-            stream_type: "Video".to_string(),
-            codec: media_info.video_codec.clone(),
-            index: 0,
-            is_default: true,
-            is_forced: false,
-            is_external: false,
-            width: media_info.width,
-            height: media_info.height,
-            bit_rate: media_info.bitrate.map(|b| b as i64),
-            aspect_ratio: media_info
-                .width
-                .zip(media_info.height)
-                .map(|(w, h)| format!("{}:{}", w, h)),
-            average_frame_rate: None,
-            real_frame_rate: None,
-            video_range: Some("SDR".to_string()),
-            video_range_type: Some("SDR".to_string()),
-            pixel_format: None,
-            level: None,
-            profile: None,
-            channels: None,
-            sample_rate: None,
-            channel_layout: None,
-            language: None,
-            title: None,
-            display_title: media_info.video_codec.as_ref().map(|c| {
-                if let (Some(w), Some(h)) = (media_info.width, media_info.height) {
-                    format!("{} - {}x{}", c.to_uppercase(), w, h)
-                } else {
-                    c.to_uppercase()
-                }
-            }),
-            delivery_method: None,
-            delivery_url: None,
-            is_text_subtitle_stream: None,
-            supports_external_stream: None,
-        });
+        media_streams.push(video_stream_info(&media_info));
     }
 
     // Add audio streams
@@ -135,14 +113,17 @@ async fn build_media_source_for_item(item: &MediaItem) -> Option<MediaSourceInfo
             aspect_ratio: None,
             average_frame_rate: None,
             real_frame_rate: None,
+            is_interlaced: None,
             video_range: None,
             video_range_type: None,
             pixel_format: None,
             level: None,
             profile: None,
+            bit_depth: None,
             channels: audio.channels,
             sample_rate: audio.sample_rate,
             channel_layout,
+            is_commentary: Some(audio.is_commentary),
             language: audio.language.clone(),
             title: audio.title.clone(),
             display_title: Some(audio.display_title()),
@@ -175,14 +156,17 @@ async fn build_media_source_for_item(item: &MediaItem) -> Option<MediaSourceInfo
             aspect_ratio: None,
             average_frame_rate: None,
             real_frame_rate: None,
+            is_interlaced: None,
             video_range: None,
             video_range_type: None,
             pixel_format: None,
             level: None,
             profile: None,
+            bit_depth: None,
             channels: None,
             sample_rate: None,
             channel_layout: None,
+            is_commentary: None,
             language: sub.language.clone(),
             title: sub.title.clone(),
             display_title: Some(sub.display_title()),
@@ -204,10 +188,19 @@ async fn build_media_source_for_item(item: &MediaItem) -> Option<MediaSourceInfo
         });
     }
 
+    // Subtitle and audio files beside the video
+    if item.stream_url.is_none() {
+        for file in
+            external_streams::for_item(pool, &item.id, std::path::Path::new(file_path)).await
+        {
+            media_streams.push(external_stream_info(&item.id, &file));
+        }
+    }
+
     // Determine container from path
     let container = file_path.rsplit('.').next().map(|s| s.to_lowercase());
 
-    Some(MediaSourceInfo {
+    let mut source = MediaSourceInfo {
         id: item.id.clone(),
         name: item.name.clone(),
         path: item.path.clone(),
@@ -228,13 +221,17 @@ async fn build_media_source_for_item(item: &MediaItem) -> Option<MediaSourceInfo
         requires_looping: false,
         supports_probing: true,
         media_streams,
+        default_audio_stream_index: mediainfo::default_audio_index(&media_info.audio_streams),
         direct_stream_url: Some(format!("/Videos/{}/stream", item.id)),
         transcoding_url: None,
         transcoding_sub_protocol: None,
         transcoding_container: None,
-    })
+        transcode_reasons: Vec::new(),
+    };
+    apply_stream_url(&mut source, item);
+    Some(source)
 }
-use super::users::parse_emby_auth_header;
+use super::users::{parse_emby_auth_header, require_api_key_or_admin};
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -244,8 +241,10 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/Filters2", get(get_item_filters2))
         .route("/:id", get(get_item))
         .route("/:id", axum::routing::delete(delete_item))
+        .route("/:id/Ancestors", get(get_item_ancestors))
         .route("/:id/Similar", get(get_similar_items))
         .route("/:id/Refresh", axum::routing::post(refresh_item))
+        .route("/:id/Rescan", axum::routing::post(rescan_item))
         .route("/:id/Download", get(download_item))
         .route("/:id/RemoteImages", get(get_remote_images))
         .route(
@@ -254,6 +253,10 @@ pub fn routes() -> Router<Arc<AppState>> {
         )
         .route("/:id/ExternalIdInfos", get(get_external_id_infos))
         .route("/:id/MetadataEditor", get(get_metadata_editor))
+        .route(
+            "/:id/EpisodeOrdering",
+            axum::routing::post(set_episode_ordering),
+        )
         .route(
             "/RemoteSearch/Series",
             axum::routing::post(remote_search_series),
@@ -374,7 +377,7 @@ pub struct QueryFilters {
     pub years: Vec<i32>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 #[serde(rename_all = "PascalCase")]
 pub struct NameGuidPair {
     pub name: String,
@@ -396,13 +399,34 @@ pub struct FiltersQuery {
     pub recursive: Option<bool>,
 }
 
+/// Ratings of the items a user can see, of one library or all, lowest first
+async fn official_ratings(
+    pool: &sqlx::SqlitePool,
+    user_id: &str,
+    library_id: Option<&str>,
+) -> Vec<String> {
+    sqlx::query_scalar(
+        "SELECT official_rating FROM media_items
+         WHERE official_rating IS NOT NULL
+           AND (?1 IS NULL OR library_id = ?1)
+           AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?2)
+         GROUP BY official_rating
+         ORDER BY MIN(parental_rating) IS NULL, MIN(parental_rating), official_rating",
+    )
+    .bind(library_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default()
+}
+
 /// GET /Items/Filters - Get filter values (legacy format)
 async fn get_item_filters(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Query(query): Query<FiltersQuery>,
 ) -> Result<Json<QueryFiltersLegacy>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
 
     // Get distinct genres
     let genres: Vec<(String,)> = if let Some(ref parent_id) = query.parent_id {
@@ -446,8 +470,8 @@ async fn get_item_filters(
 
     Ok(Json(QueryFiltersLegacy {
         genres: genres.into_iter().map(|(g,)| g).collect(),
-        tags: vec![],             // We don't have tags yet
-        official_ratings: vec![], // We don't have ratings yet
+        tags: vec![], // We don't have tags yet
+        official_ratings: official_ratings(&state.db, &user.id, query.parent_id.as_deref()).await,
         years: years.into_iter().map(|(y,)| y).collect(),
     }))
 }
@@ -458,7 +482,7 @@ async fn get_item_filters2(
     headers: HeaderMap,
     Query(query): Query<FiltersQuery>,
 ) -> Result<Json<QueryFilters>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
 
     // Get genres with IDs
     let genres: Vec<(String, String)> = if let Some(ref parent_id) = query.parent_id {
@@ -506,7 +530,7 @@ async fn get_item_filters2(
             .map(|(name, id)| NameGuidPair { name, id })
             .collect(),
         tags: vec![],
-        official_ratings: vec![],
+        official_ratings: official_ratings(&state.db, &user.id, query.parent_id.as_deref()).await,
         years: years.into_iter().map(|(y,)| y).collect(),
     }))
 }
@@ -596,6 +620,27 @@ async fn delete_item(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    // Delete stored lyrics
+    sqlx::query("DELETE FROM lyrics WHERE item_id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Delete blocks pointing at this item
+    sqlx::query("DELETE FROM user_item_blocks WHERE block_type = 'Item' AND value = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Delete season mappings (series)
+    sqlx::query("DELETE FROM series_season_mappings WHERE item_id = ?")
+        .bind(&id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     // Delete collection items
     sqlx::query("DELETE FROM collection_items WHERE item_id = ?")
         .bind(&id)
@@ -618,6 +663,9 @@ async fn delete_item(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     tracing::info!("Item {} deleted by admin {}", id, user.id);
+    events::publish(ServerEvent::ItemRemoved {
+        item_id: id.clone(),
+    });
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -645,6 +693,14 @@ pub struct GetItemsQuery {
     pub genres: Option<Vec<String>>,
     pub genre_ids: Option<Vec<String>>,
     pub media_types: Option<Vec<String>>,
+    pub is_dubbed: Option<bool>,
+    pub is_dual_audio: Option<bool>,
+    pub audio_languages: Option<Vec<String>>,
+    pub is_hd: Option<bool>,
+    pub is_4k: Option<bool>,
+    pub artist_ids: Option<Vec<String>>,
+    pub album_artist_ids: Option<Vec<String>>,
+    pub album_ids: Option<Vec<String>>,
 }
 
 impl GetItemsQuery {
@@ -667,6 +723,14 @@ impl GetItemsQuery {
             genres: params.get("genres").cloned(),
             genre_ids: params.get("genreIds").cloned(),
             media_types: params.get("mediaTypes").cloned(),
+            is_dubbed: get_param_bool(&params, "isDubbed"),
+            is_dual_audio: get_param_bool(&params, "isDualAudio"),
+            audio_languages: params.get("audioLanguages").cloned(),
+            is_hd: get_param_bool(&params, "isHd"),
+            is_4k: get_param_bool(&params, "is4K"),
+            artist_ids: params.get("artistIds").cloned(),
+            album_artist_ids: params.get("albumArtistIds").cloned(),
+            album_ids: params.get("albumIds").cloned(),
         }
     }
 }
@@ -703,15 +767,34 @@ pub struct BaseItemDto {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub index_number: Option<i32>,
 
+    /// Last episode of a multi-episode file ("S01E01-E03")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_number_end: Option<i32>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub parent_index_number: Option<i32>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub runtime_ticks: Option<i64>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+
+    #[serde(rename = "IsHD", skip_serializing_if = "Option::is_none")]
+    pub is_hd: Option<bool>,
+
+    #[serde(rename = "Is4K", skip_serializing_if = "Option::is_none")]
+    pub is_4k: Option<bool>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub community_rating: Option<f64>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub official_rating: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
 
@@ -733,6 +816,14 @@ pub struct BaseItemDto {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub season_name: Option<String>,
 
+    /// Album and artists, for music items
+    #[serde(flatten)]
+    pub music: Option<MusicInfo>,
+
+    /// Channel and guide fields, for Live TV items (services::live_tv)
+    #[serde(flatten)]
+    pub live_tv: Option<LiveTvInfo>,
+
     pub is_folder: bool,
 
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -749,6 +840,10 @@ pub struct BaseItemDto {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_tags: Option<ImageTags>,
 
+    /// Tags of the item's backdrops, which clients request by index to rotate them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub backdrop_image_tags: Vec<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub provider_ids: Option<ProviderIds>,
 
@@ -759,6 +854,79 @@ pub struct BaseItemDto {
     pub supports_media_source_display: bool,
 }
 
+/// Album and artist fields of Audio and MusicAlbum items
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct MusicInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub album_id: Option<String>,
+    pub album_artist: String,
+    pub album_artists: Vec<NameGuidPair>,
+    pub artists: Vec<String>,
+    pub artist_items: Vec<NameGuidPair>,
+}
+
+/// Fields of TvChannel and Program items
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct LiveTvInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_number: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_program: Option<Box<BaseItemDto>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_primary_image_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode_title: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub genres: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_movie: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_series: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_news: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_kids: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_sports: Option<bool>,
+}
+
+/// Music fields of an item, with artist IDs derived from their names
+pub(crate) fn music_info(item: &MediaItem) -> Option<MusicInfo> {
+    if !matches!(item.item_type.as_str(), "Audio" | "MusicAlbum") {
+        return None;
+    }
+    let album_artist = item.album_artist.clone()?;
+    let artist_pair = |name: &String| NameGuidPair {
+        name: name.clone(),
+        id: music::artist_id(&item.library_id, name),
+    };
+    let artists = music::split_names(item.artists.as_deref().unwrap_or(&album_artist));
+    Some(MusicInfo {
+        album: item.album.clone(),
+        album_id: item.album.as_ref().and(item.parent_id.clone()),
+        album_artists: vec![artist_pair(&album_artist)],
+        artist_items: artists.iter().map(artist_pair).collect(),
+        artists,
+        album_artist,
+    })
+}
+
 #[derive(Debug, Serialize, Clone, Default)]
 #[serde(rename_all = "PascalCase")]
 pub struct UserItemDataDto {
@@ -777,6 +945,18 @@ pub struct ImageTags {
     pub primary: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub backdrop: Option<String>,
+    /// Backdrops the item has; BackdropImageTags lists one tag for each
+    #[serde(skip)]
+    pub backdrop_count: usize,
+}
+
+impl ImageTags {
+    /// An item's BackdropImageTags, one per backdrop in index order
+    pub fn backdrop_image_tags(tags: &Option<ImageTags>) -> Vec<String> {
+        tags.as_ref()
+            .and_then(|t| Some(vec![t.backdrop.clone()?; t.backdrop_count.max(1)]))
+            .unwrap_or_default()
+    }
 }
 
 /// Provider IDs map (e.g., Tmdb, Imdb, AniList, Mal)
@@ -798,7 +978,10 @@ async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Opti
     for (image_type,) in images {
         match image_type.as_str() {
             "Primary" => tags.primary = Some(item_id.to_string()),
-            "Backdrop" => tags.backdrop = Some(item_id.to_string()),
+            "Backdrop" => {
+                tags.backdrop = Some(item_id.to_string());
+                tags.backdrop_count += 1;
+            }
             _ => {}
         }
     }
@@ -870,8 +1053,20 @@ pub async fn get_user_item_data(
 
 use std::collections::{HashMap, HashSet};
 
+/// Child count of a folder item: a series' seasons, a season's episodes
+pub(crate) async fn get_child_count(pool: &sqlx::SqlitePool, id: &str) -> i32 {
+    batch_get_child_counts(pool, &[id])
+        .await
+        .get(id)
+        .copied()
+        .unwrap_or(0)
+}
+
 /// Batch fetch child counts for multiple parent IDs
-async fn batch_get_child_counts(
+///
+/// Episodes count towards their season, and only towards their series when
+/// they have none.
+pub(crate) async fn batch_get_child_counts(
     pool: &sqlx::SqlitePool,
     parent_ids: &[&str],
 ) -> HashMap<String, i32> {
@@ -880,14 +1075,16 @@ async fn batch_get_child_counts(
     }
 
     // Build query with placeholders
-    let placeholders: Vec<&str> = parent_ids.iter().map(|_| "?").collect();
+    let placeholders = vec!["?"; parent_ids.len()].join(",");
     let query = format!(
-        "SELECT parent_id, COUNT(*) as cnt FROM media_items WHERE parent_id IN ({}) GROUP BY parent_id",
-        placeholders.join(",")
+        "SELECT COALESCE(season_id, parent_id), COUNT(*) as cnt FROM media_items
+         WHERE (parent_id IN ({0}) OR season_id IN ({0})) AND version_of IS NULL
+         GROUP BY COALESCE(season_id, parent_id)",
+        placeholders
     );
 
     let mut query_builder = sqlx::query_as::<_, (String, i32)>(&query);
-    for id in parent_ids {
+    for id in parent_ids.iter().chain(parent_ids) {
         query_builder = query_builder.bind(*id);
     }
 
@@ -900,7 +1097,7 @@ async fn batch_get_child_counts(
 }
 
 /// Batch fetch parent items (for getting series names for episodes)
-async fn batch_get_parent_names(
+pub(crate) async fn batch_get_parent_names(
     pool: &sqlx::SqlitePool,
     parent_ids: &[&str],
 ) -> HashMap<String, String> {
@@ -927,11 +1124,12 @@ async fn batch_get_parent_names(
         .collect()
 }
 
-/// Batch fetch image tags for multiple items
-async fn batch_get_image_tags(
+/// Batch fetch the image types (Primary, Backdrop, Thumb, ...) stored for multiple
+/// items, with how many images of each type they have
+async fn batch_get_image_types(
     pool: &sqlx::SqlitePool,
     item_ids: &[&str],
-) -> HashMap<String, ImageTags> {
+) -> HashMap<String, HashMap<String, usize>> {
     if item_ids.is_empty() {
         return HashMap::new();
     }
@@ -949,20 +1147,39 @@ async fn batch_get_image_tags(
 
     let rows = query_builder.fetch_all(pool).await.unwrap_or_default();
 
-    let mut result: HashMap<String, ImageTags> = HashMap::new();
+    let mut result: HashMap<String, HashMap<String, usize>> = HashMap::new();
     for (item_id, image_type) in rows {
-        let tags = result.entry(item_id.clone()).or_default();
-        match image_type.as_str() {
-            "Primary" => tags.primary = Some(item_id),
-            "Backdrop" => tags.backdrop = Some(item_id.clone()),
-            _ => {}
-        }
+        *result
+            .entry(item_id)
+            .or_default()
+            .entry(image_type)
+            .or_default() += 1;
     }
     result
 }
 
+/// Batch fetch image tags for multiple items
+pub(crate) async fn batch_get_image_tags(
+    pool: &sqlx::SqlitePool,
+    item_ids: &[&str],
+) -> HashMap<String, ImageTags> {
+    batch_get_image_types(pool, item_ids)
+        .await
+        .into_iter()
+        .map(|(item_id, types)| {
+            let tag = |image_type: &str| types.contains_key(image_type).then(|| item_id.clone());
+            let tags = ImageTags {
+                primary: tag("Primary"),
+                backdrop: tag("Backdrop"),
+                backdrop_count: types.get("Backdrop").copied().unwrap_or(0),
+            };
+            (item_id, tags)
+        })
+        .collect()
+}
+
 /// Batch fetch user data (playback progress + favorites) for multiple items
-async fn batch_get_user_data(
+pub(crate) async fn batch_get_user_data(
     pool: &sqlx::SqlitePool,
     user_id: &str,
     item_ids: &[&str],
@@ -1030,7 +1247,7 @@ async fn batch_get_user_data(
     result
 }
 
-fn media_item_to_dto(
+pub(crate) fn media_item_to_dto(
     item: &MediaItem,
     child_count: Option<i32>,
     series_name: Option<String>,
@@ -1039,7 +1256,7 @@ fn media_item_to_dto(
 ) -> BaseItemDto {
     let is_folder = matches!(
         item.item_type.as_str(),
-        "Series" | "Season" | "Folder" | "CollectionFolder"
+        "Series" | "Season" | "MusicAlbum" | "MusicArtist" | "Folder" | "CollectionFolder"
     );
     let media_type = match item.item_type.as_str() {
         "Episode" | "Movie" => Some("Video".to_string()),
@@ -1056,6 +1273,9 @@ fn media_item_to_dto(
         if let Some(ref id) = item.imdb_id {
             ids.insert("Imdb".to_string(), id.clone());
         }
+        if let Some(ref id) = item.tvdb_id {
+            ids.insert("Tvdb".to_string(), id.clone());
+        }
         if let Some(ref id) = item.anilist_id {
             ids.insert("AniList".to_string(), id.clone());
         }
@@ -1079,15 +1299,17 @@ fn media_item_to_dto(
         id: item.id.clone(),
         name: item.name.clone(),
         item_type: item.item_type.clone(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         parent_id: item.parent_id.clone(),
         overview: item.overview.clone(),
         year: item.year,
         production_year: item.year,
-        index_number: item.index_number,
-        parent_index_number: item.parent_index_number,
+        index_number: item.index_number_for_display(),
+        index_number_end: item.index_number_end_for_display(),
+        parent_index_number: item.parent_index_number_for_display(),
         runtime_ticks: item.runtime_ticks,
         community_rating: item.community_rating,
+        official_rating: item.official_rating.clone(),
         path: item.path.clone(),
         premiere_date: item.premiere_date.clone(),
         sort_name: item.sort_name.clone(),
@@ -1098,33 +1320,29 @@ fn media_item_to_dto(
             None
         },
         series_name,
-        // Generate synthetic season_id for episodes: {series_id}_season_{season_number}
-        season_id: if item.item_type == "Episode" {
-            if let (Some(ref series_id), Some(season_num)) =
-                (&item.parent_id, item.parent_index_number)
-            {
-                Some(format!("{}_season_{}", series_id, season_num))
-            } else {
-                None
-            }
-        } else {
-            None
-        },
-        season_name: item.parent_index_number.map(|s| {
+        season_id: item.season_id.clone(),
+        season_name: item.parent_index_number_for_display().map(|s| {
             if s == 0 {
                 "Specials".to_string()
             } else {
                 format!("Season {}", s)
             }
         }),
+        music: music_info(item),
+        live_tv: None,
         is_folder,
         child_count,
         media_type,
         collection_type: None,
         user_data: user_data.unwrap_or_default(),
+        backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
         image_tags,
         provider_ids,
         media_sources: None, // Populated separately for single item requests
+        width: item.width,
+        height: item.height,
+        is_hd: is_hd_resolution(item.width, item.height),
+        is_4k: is_4k_resolution(item.width, item.height),
         can_download: item.path.is_some(),
         supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
     }
@@ -1137,8 +1355,32 @@ async fn get_items(
 ) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
     let query = GetItemsQuery::from_uri(&uri);
-    let user_id = query.user_id.as_deref().unwrap_or(&user.id);
+    let user_id = authorize_user_id(&user, query.user_id.as_deref())?;
+    list_items(&state, &user_id, query).await
+}
+
+/// The user whose view of the library is listed: the requested user if the
+/// caller may act as them (themselves, or anyone for admins), else the caller
+pub(crate) fn authorize_user_id(
+    user: &crate::models::User,
+    requested: Option<&str>,
+) -> Result<String, (StatusCode, String)> {
+    match requested.filter(|id| !id.is_empty()) {
+        Some(id) if id != user.id && !user.is_admin => Err((
+            StatusCode::FORBIDDEN,
+            "Cannot view other user's items".to_string(),
+        )),
+        Some(id) => Ok(id.to_string()),
+        None => Ok(user.id.clone()),
+    }
+}
 
+/// List items as seen by a user: their hidden items, favorites and user data
+async fn list_items(
+    state: &AppState,
+    user_id: &str,
+    query: GetItemsQuery,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
     let start_index = query.start_index.unwrap_or(0);
     let limit = query.limit.unwrap_or(100).min(1000);
 
@@ -1150,160 +1392,188 @@ async fn get_items(
             .map(|v| v.iter().any(|s| s.eq_ignore_ascii_case("IsFavorite")))
             .unwrap_or(false);
 
-    // Parse item types once for reuse
-    let include_types: Option<Vec<String>> = query.include_item_types.map(|vec| {
-        vec.iter()
-            .flat_map(|s| s.split(','))
-            .map(|s| s.trim().to_string())
-            .collect()
-    });
-
-    // Determine sort column (whitelist to prevent injection)
-    let sort_by_vec = query.sort_by.unwrap_or_default();
-    let sort_by = sort_by_vec
-        .first()
-        .map(|s| s.as_str())
-        .unwrap_or("SortName");
-    let order_col = match sort_by {
-        "DateCreated" => "created_at",
-        "PremiereDate" => "premiere_date",
-        "IndexNumber" => "index_number",
-        "CommunityRating" => "community_rating",
-        "Name" => "name",
-        "DateLastContentAdded" => "updated_at",
-        _ => "sort_name",
-    };
-    let sort_order_vec = query.sort_order.unwrap_or_default();
-    let sort_order = if sort_order_vec.first().map(|s| s.as_str()) == Some("Descending") {
-        "DESC"
-    } else {
-        "ASC"
-    };
-
-    // Build main query using QueryBuilder for safe parameter binding
-    let mut qb: sqlx::QueryBuilder<sqlx::Sqlite> =
-        sqlx::QueryBuilder::new("SELECT * FROM media_items WHERE 1=1");
+    // Every sort key applies in turn ("ParentIndexNumber,IndexNumber" for an
+    // album's tracks); keys without an order of their own take the last one
+    let mut sort_by: Vec<String> = query
+        .sort_by
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|s| s.split(','))
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    let mut sort_orders: Vec<String> = query
+        .sort_order
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|s| s.split(','))
+        .map(|s| s.trim().to_string())
+        .collect();
 
-    // Filter by parent
-    if let Some(ref parent_id) = query.parent_id {
-        if query.recursive.unwrap_or(false) {
-            qb.push(" AND (library_id = ")
-                .push_bind(parent_id.clone())
-                .push(" OR parent_id = ")
-                .push_bind(parent_id.clone())
-                .push(")");
-        } else {
-            qb.push(" AND parent_id = ").push_bind(parent_id.clone());
+    // Browsing a library without an order uses the library's own (config
+    // `libraries.sort_by`)
+    if sort_by.is_empty() {
+        if let Some(ref parent_id) = query.parent_id {
+            let default: Option<(Option<String>, Option<String>)> =
+                sqlx::query_as("SELECT sort_by, sort_order FROM libraries WHERE id = ?")
+                    .bind(parent_id)
+                    .fetch_optional(&state.db)
+                    .await
+                    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            if let Some((Some(default_sort_by), default_sort_order)) = default {
+                sort_by = default_sort_by
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect();
+                if sort_orders.is_empty() {
+                    sort_orders.extend(default_sort_order);
+                }
+            }
         }
-    } else if !query.recursive.unwrap_or(false) {
-        qb.push(" AND parent_id IS NULL");
     }
 
-    // Filter by item types using tuple binding
-    if let Some(ref types) = include_types {
-        qb.push(" AND item_type IN (");
-        let mut separated = qb.separated(", ");
-        for t in types {
-            separated.push_bind(t.to_string());
+    // Libraries are not media items; list them as CollectionFolders when those are asked for
+    let wants_libraries = query.include_item_types.as_ref().is_some_and(|types| {
+        types
+            .iter()
+            .flat_map(|t| t.split(','))
+            .any(|t| t.trim().eq_ignore_ascii_case("CollectionFolder"))
+    });
+    if wants_libraries && query.parent_id.is_none() {
+        let mut libraries: Vec<Library> = sqlx::query_as("SELECT * FROM libraries ORDER BY name")
+            .fetch_all(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let access = crate::db::get_library_access(&state.db, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if let Some(access) = access {
+            libraries.retain(|lib| access.contains(&lib.id));
         }
-        separated.push_unseparated(")");
-    }
-
-    // Search term - case insensitive search
-    if let Some(ref term) = query.search_term {
-        let search_pattern = format!("%{}%", term.to_lowercase());
-        qb.push(" AND (LOWER(name) LIKE ")
-            .push_bind(search_pattern.clone())
-            .push(" OR LOWER(COALESCE(overview, '')) LIKE ")
-            .push_bind(search_pattern)
-            .push(")");
+        library_views::load(&state.db, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .sort(&mut libraries, |lib| &lib.id);
+        let total = libraries.len() as i32;
+        let mut dtos = Vec::new();
+        for lib in libraries
+            .iter()
+            .skip(start_index.max(0) as usize)
+            .take(limit.max(0) as usize)
+        {
+            dtos.push(library_to_dto(state, lib).await);
+        }
+        return Ok(Json(ItemsResponse {
+            items: dtos,
+            total_record_count: total,
+            start_index,
+        }));
     }
 
-    // Filter by favorites using subquery with bound parameter
-    if is_favorite {
-        qb.push(" AND id IN (SELECT item_id FROM user_favorites WHERE user_id = ")
-            .push_bind(user_id.to_string())
-            .push(")");
-    }
-
-    // Sort and pagination (column names are whitelisted, not user input)
-    qb.push(" ORDER BY ")
-        .push(order_col)
-        .push(" ")
-        .push(sort_order)
-        .push(" LIMIT ")
-        .push_bind(limit)
-        .push(" OFFSET ")
-        .push_bind(start_index);
-
-    // Execute main query
-    let items: Vec<MediaItem> = qb
-        .build_query_as()
-        .fetch_all(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    // Build count query with same filters
-    let mut count_qb: sqlx::QueryBuilder<sqlx::Sqlite> =
-        sqlx::QueryBuilder::new("SELECT COUNT(*) FROM media_items WHERE 1=1");
+    let mut item_query = ItemQuery::new()
+        .language_hints(
+            query.is_dubbed,
+            query.is_dual_audio,
+            query.audio_languages.as_deref().unwrap_or_default(),
+        )
+        .resolution(query.is_hd, query.is_4k)
+        .visible_to(user_id);
 
+    // Filter by parent; playlists and collections list their entries, in
+    // their own order unless another is asked for (clients play them this way)
     if let Some(ref parent_id) = query.parent_id {
-        if query.recursive.unwrap_or(false) {
-            count_qb
-                .push(" AND (library_id = ")
-                .push_bind(parent_id.clone())
-                .push(" OR parent_id = ")
-                .push_bind(parent_id.clone())
-                .push(")");
-        } else {
-            count_qb
-                .push(" AND parent_id = ")
-                .push_bind(parent_id.clone());
-        }
+        let list = crate::db::find_item_list(&state.db, parent_id, user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        item_query = match list {
+            Some(list) if sort_by.is_empty() => item_query.in_list(list, parent_id).sort(
+                ItemSort::ListOrder(list, parent_id.clone()),
+                SortOrder::Ascending,
+            ),
+            Some(list) => item_query.in_list(list, parent_id),
+            None if query.recursive.unwrap_or(false) => item_query.ancestor(parent_id),
+            None => item_query.parent(parent_id),
+        };
     } else if !query.recursive.unwrap_or(false) {
-        count_qb.push(" AND parent_id IS NULL");
+        item_query = item_query.top_level();
     }
 
-    if let Some(ref types) = include_types {
-        count_qb.push(" AND item_type IN (");
-        let mut separated = count_qb.separated(", ");
-        for t in types {
-            separated.push_bind(t.to_string());
-        }
-        separated.push_unseparated(")");
+    if let Some(ref types) = query.include_item_types {
+        item_query = item_query.include_types(types);
     }
 
-    if let Some(ref term) = query.search_term {
-        let search_pattern = format!("%{}%", term.to_lowercase());
-        count_qb
-            .push(" AND (LOWER(name) LIKE ")
-            .push_bind(search_pattern.clone())
-            .push(" OR LOWER(COALESCE(overview, '')) LIKE ")
-            .push_bind(search_pattern)
-            .push(")");
+    if let Some(ref ids) = query.artist_ids {
+        item_query = item_query.artists(ids);
+    }
+    if let Some(ref ids) = query.album_artist_ids {
+        item_query = item_query.album_artists(ids);
+    }
+    if let Some(ref ids) = query.album_ids {
+        item_query = item_query.albums(ids);
     }
 
     if is_favorite {
-        count_qb
-            .push(" AND id IN (SELECT item_id FROM user_favorites WHERE user_id = ")
-            .push_bind(user_id.to_string())
-            .push(")");
+        item_query = item_query.favorites_of(user_id);
     }
 
-    let total: (i32,) = count_qb
-        .build_query_as()
-        .fetch_one(&state.db)
+    // Search terms go through the FTS index; LIKE covers terms it can't use
+    // (single letters) and a broken index
+    let search_term = query
+        .search_term
+        .as_deref()
+        .filter(|t| !t.trim().is_empty());
+    let fts_query = search_term.map(prepare_fts_filter).unwrap_or_default();
+    let page = if fts_query.is_empty() {
+        if let Some(term) = search_term {
+            item_query = item_query.search_term(term);
+        }
+        fetch_sorted_page(
+            &state.db,
+            &item_query,
+            &sort_by,
+            &sort_orders,
+            limit,
+            start_index,
+        )
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    } else {
+        let fts_page = fetch_sorted_page(
+            &state.db,
+            &item_query.clone().fts_match(&fts_query),
+            &sort_by,
+            &sort_orders,
+            limit,
+            start_index,
+        )
+        .await;
+        match fts_page {
+            Ok(page) => Ok(page),
+            Err(e) => {
+                tracing::warn!("FTS search failed, falling back to LIKE: {}", e);
+                item_query = item_query.search_term(search_term.unwrap_or_default());
+                fetch_sorted_page(
+                    &state.db,
+                    &item_query,
+                    &sort_by,
+                    &sort_orders,
+                    limit,
+                    start_index,
+                )
+                .await
+            }
+        }
+    };
+    let (items, total) = page.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     // Batch fetch all related data to avoid N+1 queries
     // Collect IDs for batch queries
     let item_ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
 
-    // Items that need child counts (Series/Season)
+    // Items that need child counts (Series/Season, albums and artists)
     let folder_ids: Vec<&str> = items
         .iter()
-        .filter(|i| matches!(i.item_type.as_str(), "Series" | "Season"))
+        .filter(|i| has_child_count(&i.item_type))
         .map(|i| i.id.as_str())
         .collect();
 
@@ -1325,7 +1595,7 @@ async fn get_items(
     // Convert to DTOs using batched data
     let mut dtos = Vec::with_capacity(items.len());
     for item in &items {
-        let child_count = if matches!(item.item_type.as_str(), "Series" | "Season") {
+        let child_count = if has_child_count(&item.item_type) {
             child_counts.get(&item.id).copied()
         } else {
             None
@@ -1353,109 +1623,129 @@ async fn get_items(
 
     Ok(Json(ItemsResponse {
         items: dtos,
-        total_record_count: total.0,
+        total_record_count: total,
         start_index,
     }))
 }
 
+/// Whether an item type is shown with its number of children
+fn has_child_count(item_type: &str) -> bool {
+    matches!(
+        item_type,
+        "Series" | "Season" | "MusicAlbum" | "MusicArtist"
+    )
+}
+
+/// Build the CollectionFolder DTO for a library, as listed by /UserViews
+pub(crate) async fn library_to_dto(state: &AppState, lib: &Library) -> BaseItemDto {
+    let child_count: (i32,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM media_items WHERE library_id = ? AND parent_id IS NULL",
+    )
+    .bind(&lib.id)
+    .fetch_one(&state.db)
+    .await
+    .unwrap_or((0,));
+
+    let primary = library_images::image_tag(
+        &state.db,
+        &state.config.paths.image_cache_dir(),
+        &lib.id,
+        "Primary",
+    )
+    .await;
+    let backdrop = library_images::image_tag(
+        &state.db,
+        &state.config.paths.image_cache_dir(),
+        &lib.id,
+        "Backdrop",
+    )
+    .await;
+    let image_tags = if primary.is_some() || backdrop.is_some() {
+        Some(ImageTags {
+            primary,
+            backdrop,
+            ..Default::default()
+        })
+    } else {
+        None
+    };
+
+    BaseItemDto {
+        id: lib.id.clone(),
+        name: lib.name.clone(),
+        item_type: "CollectionFolder".to_string(),
+        server_id: server_id::get().to_string(),
+        parent_id: None,
+        overview: None,
+        year: None,
+        production_year: None,
+        index_number: None,
+        index_number_end: None,
+        parent_index_number: None,
+        runtime_ticks: None,
+        community_rating: None,
+        official_rating: None,
+        path: Some(lib.path.clone()),
+        premiere_date: None,
+        sort_name: Some(lib.name.clone()),
+        series_id: None,
+        series_name: None,
+        season_id: None,
+        season_name: None,
+        music: None,
+        live_tv: None,
+        is_folder: true,
+        child_count: Some(child_count.0),
+        media_type: None,
+        collection_type: Some(super::views::collection_type_for(&lib.library_type)),
+        user_data: UserItemDataDto::default(),
+        backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
+        image_tags,
+        provider_ids: None,
+        media_sources: None,
+        width: None,
+        height: None,
+        is_hd: None,
+        is_4k: None,
+        can_download: false,
+        supports_media_source_display: false,
+    }
+}
+
 async fn get_item(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
 ) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
+    let id = resolve_legacy_season(&state, id).await?;
 
-    // Check if this is a synthetic season ID (format: {series_id}_season_{num})
-    if let Some(pos) = id.rfind("_season_") {
-        let series_id = &id[..pos];
-        let season_num: i32 = id[pos + 8..].parse().unwrap_or(1);
-
-        // Get the series
-        let series: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
-            .bind(series_id)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-            .ok_or_else(|| (StatusCode::NOT_FOUND, "Series not found".to_string()))?;
-
-        // Count episodes in this season
-        let episode_count: (i32,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM media_items
-             WHERE parent_id = ? AND item_type = 'Episode' AND COALESCE(parent_index_number, 1) = ?",
-        )
-        .bind(series_id)
-        .bind(season_num)
-        .fetch_one(&state.db)
+    // Libraries are CollectionFolder items
+    let library: Option<Library> = sqlx::query_as("SELECT * FROM libraries WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&state.db)
         .await
-        .unwrap_or((0,));
-
-        // Get image tags from series
-        let image_tags = get_image_tags_for_item(&state.db, series_id).await;
-
-        // Season name
-        let season_name = if season_num == 0 {
-            "Specials".to_string()
-        } else {
-            format!("Season {}", season_num)
-        };
-
-        let sort_name = if season_num == 0 {
-            "Season 999".to_string()
-        } else {
-            format!("Season {:03}", season_num)
-        };
-
-        // Build synthetic season DTO
-        let dto = BaseItemDto {
-            id: id.clone(),
-            name: season_name,
-            item_type: "Season".to_string(),
-            server_id: "jellyfin-rust-server".to_string(),
-            parent_id: Some(series_id.to_string()),
-            overview: None,
-            year: series.year,
-            production_year: series.year,
-            index_number: Some(season_num),
-            parent_index_number: None,
-            runtime_ticks: None,
-            community_rating: None,
-            path: None,
-            premiere_date: None,
-            sort_name: Some(sort_name),
-            series_id: Some(series_id.to_string()),
-            series_name: Some(series.name.clone()),
-            season_id: None,
-            season_name: None,
-            is_folder: true,
-            child_count: Some(episode_count.0),
-            media_type: None,
-            collection_type: None,
-            user_data: UserItemDataDto::default(),
-            image_tags,
-            provider_ids: None,
-            media_sources: None,
-            can_download: false,
-            supports_media_source_display: false,
-        };
-
-        return Ok(Json(dto));
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(lib) = library {
+        return Ok(Json(library_to_dto(&state, &lib).await));
     }
 
-    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+    let item: Option<MediaItem> = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
         .bind(&id)
         .fetch_optional(&state.db)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(item) = item else {
+        // Live TV channels and programs
+        return match super::live_tv::find_item(&state, &id).await? {
+            Some(dto) => Ok(Json(dto)),
+            None => Err((StatusCode::NOT_FOUND, "Item not found".to_string())),
+        };
+    };
 
     // Get child count for folders
-    let child_count = if matches!(item.item_type.as_str(), "Series" | "Season") {
-        let count: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM media_items WHERE parent_id = ?")
-            .bind(&item.id)
-            .fetch_one(&state.db)
-            .await
-            .unwrap_or((0,));
-        Some(count.0)
+    let child_count = if has_child_count(&item.item_type) {
+        Some(get_child_count(&state.db, &item.id).await)
     } else {
         None
     };
@@ -1488,12 +1778,177 @@ async fn get_item(
 
     // For video items, populate media_sources with stream info (fixes "null null" badge in Fladder)
     if matches!(item.item_type.as_str(), "Episode" | "Movie") {
-        if let Some(media_source) = build_media_source_for_item(&item).await {
-            dto.media_sources = Some(vec![media_source]);
+        if let Some(media_source) = build_media_source_for_item(&state.db, &item).await {
+            let mut sources = vec![media_source];
+            // Other files of the same episode, so clients can offer a version picker
+            let versions: Vec<MediaItem> = sqlx::query_as(
+                "SELECT * FROM media_items WHERE version_of = ? AND duplicate_state = ? ORDER BY height DESC, path",
+            )
+            .bind(&item.id)
+            .bind(crate::scanner::duplicates::STATE_VERSION)
+            .fetch_all(&state.db)
+            .await
+            .unwrap_or_default();
+            for version in &versions {
+                if let Some(source) = build_media_source_for_item(&state.db, version).await {
+                    sources.push(source);
+                }
+            }
+            if sources.len() > 1 {
+                for source in &mut sources {
+                    source.name = version_name(source);
+                }
+            }
+            dto.media_sources = Some(sources);
+        }
+    }
+
+    Ok(Json(dto))
+}
+
+/// The season item for a season ID from before seasons were items, or the ID itself
+async fn resolve_legacy_season(
+    state: &AppState,
+    id: String,
+) -> Result<String, (StatusCode, String)> {
+    let season_id = crate::scanner::seasons::find_legacy(&state.db, &id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(season_id.unwrap_or(id))
+}
+
+/// GET /Items/{id}/Ancestors - Parent chain of an item for breadcrumbs, nearest first
+/// (Episode -> Season -> Series -> library CollectionFolder)
+async fn get_item_ancestors(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<BaseItemDto>>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+
+    let id = resolve_legacy_season(&state, id).await?;
+    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+
+    // Episodes sit directly under their series; their season comes first
+    let mut parent_id = match item.item_type.as_str() {
+        "Episode" => item.season_id.clone().or_else(|| item.parent_id.clone()),
+        _ => item.parent_id.clone(),
+    };
+
+    let mut ancestors = Vec::new();
+    // Guards against parent_id cycles in a damaged database
+    let mut depth = 0;
+    while let Some(current_id) = parent_id.take() {
+        depth += 1;
+        if depth > 16 {
+            break;
+        }
+        let Some(parent): Option<MediaItem> =
+            sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+                .bind(&current_id)
+                .fetch_optional(&state.db)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        else {
+            break;
+        };
+
+        let child_count = if has_child_count(&parent.item_type) {
+            Some(get_child_count(&state.db, &parent.id).await)
+        } else {
+            None
+        };
+        let image_tags = get_image_tags_for_item(&state.db, &parent.id).await;
+        let user_data = get_user_item_data(&state.db, &user.id, &parent.id).await;
+        ancestors.push(media_item_to_dto(
+            &parent,
+            child_count,
+            None,
+            image_tags,
+            Some(user_data),
+        ));
+        parent_id = parent.parent_id.clone();
+    }
+
+    // The owning library, as the CollectionFolder returned by /UserViews
+    let library: Option<Library> = sqlx::query_as("SELECT * FROM libraries WHERE id = ?")
+        .bind(&item.library_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(lib) = library {
+        ancestors.push(library_to_dto(&state, &lib).await);
+    }
+
+    Ok(Json(ancestors))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ItemByPathQuery {
+    pub path: String,
+    #[serde(alias = "api_key", rename = "apiKey")]
+    pub api_key: Option<String>,
+}
+
+/// GET /Library/ItemByPath?path= - Look up the media item for an absolute file or folder path
+/// For external tools reconciling their state with the server (admin session or API key)
+pub async fn get_item_by_path(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ItemByPathQuery>,
+) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
+    require_api_key_or_admin(&state, &headers, query.api_key.as_deref()).await?;
+
+    // Folder paths may be stored with or without a trailing separator
+    let trimmed = query.path.trim_end_matches(['/', '\\']);
+
+    let item: MediaItem =
+        sqlx::query_as("SELECT * FROM media_items WHERE path = ? OR path = ? LIMIT 1")
+            .bind(&query.path)
+            .bind(trimmed)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(|| (StatusCode::NOT_FOUND, "No item with that path".to_string()))?;
+
+    let child_count = if has_child_count(&item.item_type) {
+        Some(get_child_count(&state.db, &item.id).await)
+    } else {
+        None
+    };
+
+    let series_name = if item.item_type == "Episode" {
+        match item.parent_id {
+            Some(ref parent_id) => {
+                sqlx::query_as::<_, (String,)>("SELECT name FROM media_items WHERE id = ?")
+                    .bind(parent_id)
+                    .fetch_optional(&state.db)
+                    .await
+                    .ok()
+                    .flatten()
+                    .map(|(name,)| name)
+            }
+            None => None,
         }
-    }
+    } else {
+        None
+    };
 
-    Ok(Json(dto))
+    let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
+
+    // No user context here, so no user data
+    Ok(Json(media_item_to_dto(
+        &item,
+        child_count,
+        series_name,
+        image_tags,
+        None,
+    )))
 }
 
 async fn get_similar_items(
@@ -1550,6 +2005,7 @@ async fn get_similar_items(
         WHERE ig.genre_id IN (SELECT value FROM json_each(?))
           AND m.id != ?
           AND m.item_type = ?
+          AND m.id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)
         GROUP BY m.id
         ORDER BY shared_genres DESC, m.community_rating DESC NULLS LAST
         LIMIT 12
@@ -1558,6 +2014,7 @@ async fn get_similar_items(
     .bind(serde_json::to_string(&genre_ids).unwrap_or_default())
     .bind(&id)
     .bind(&source.item_type)
+    .bind(&user.id)
     .fetch_all(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -1567,16 +2024,6 @@ async fn get_similar_items(
     // Convert to DTOs
     let mut dtos = Vec::with_capacity(similar_items.len());
     for item in similar_items {
-        let is_folder = matches!(
-            item.item_type.as_str(),
-            "Series" | "Season" | "Folder" | "CollectionFolder"
-        );
-        let media_type = match item.item_type.as_str() {
-            "Episode" | "Movie" => Some("Video".to_string()),
-            "Audio" => Some("Audio".to_string()),
-            _ => None,
-        };
-
         let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
         let user_data = get_user_item_data(&state.db, &user.id, &item.id).await;
 
@@ -1584,15 +2031,17 @@ async fn get_similar_items(
             id: item.id.clone(),
             name: item.name.clone(),
             item_type: item.item_type.clone(),
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             parent_id: item.parent_id.clone(),
             overview: item.overview.clone(),
             year: item.year,
             production_year: item.year,
-            index_number: item.index_number,
-            parent_index_number: item.parent_index_number,
+            index_number: item.index_number_for_display(),
+            index_number_end: item.index_number_end_for_display(),
+            parent_index_number: item.parent_index_number_for_display(),
             runtime_ticks: item.runtime_ticks,
             community_rating: item.community_rating,
+            official_rating: item.official_rating.clone(),
             path: item.path.clone(),
             premiere_date: item.premiere_date.clone(),
             sort_name: item.sort_name.clone(),
@@ -1600,14 +2049,21 @@ async fn get_similar_items(
             series_name: None,
             season_id: None,
             season_name: None,
-            is_folder,
+            music: None,
+            live_tv: None,
+            is_folder: is_folder_type(&item.item_type),
             child_count: None,
-            media_type,
+            media_type: media_type_of(&item.item_type),
             collection_type: None,
             user_data,
+            backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
             image_tags,
             provider_ids: None,
             media_sources: None,
+            width: item.width,
+            height: item.height,
+            is_hd: is_hd_resolution(item.width, item.height),
+            is_4k: is_4k_resolution(item.width, item.height),
             can_download: item.path.is_some(),
             supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
         });
@@ -1621,15 +2077,17 @@ async fn get_similar_items(
 }
 
 // User-specific item endpoints (called as /Users/{userId}/Items)
+/// GET /Users/{userId}/Items - Items as seen by the user in the path (a
+/// userId in the query string is ignored)
 pub async fn get_user_items(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
-    Path(_user_id): Path<String>,
+    Path(user_id): Path<String>,
     uri: Uri,
 ) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
-    // Delegate to regular get_items - user-specific data (playback progress)
-    // would be merged in a full implementation
-    get_items(State(state), headers, uri).await
+    let user = require_auth(&state, &headers).await?;
+    let user_id = authorize_user_id(&user, Some(&user_id))?;
+    list_items(&state, &user_id, GetItemsQuery::from_uri(&uri)).await
 }
 
 pub async fn get_user_item(
@@ -1645,6 +2103,8 @@ pub async fn get_user_item(
 #[serde(rename_all = "camelCase")]
 pub struct SearchHintsQuery {
     pub search_term: Option<String>,
+    /// Only search within this library, series or season
+    pub parent_id: Option<String>,
     pub limit: Option<i32>,
     pub include_item_types: Option<String>,
     pub exclude_item_types: Option<String>,
@@ -1656,6 +2116,9 @@ pub struct SearchHintsQuery {
     pub is_sports: Option<bool>,
     #[serde(rename = "userId")]
     pub user_id: Option<String>,
+    /// Only ID, name, type and year, without image or series lookups (for
+    /// clients that search on every keystroke)
+    pub fast_mode: Option<bool>,
 }
 
 #[derive(Debug, Serialize)]
@@ -1728,7 +2191,7 @@ async fn search_hints(
     headers: HeaderMap,
     Query(query): Query<SearchHintsQuery>,
 ) -> Result<Json<SearchHintsResponse>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
 
     let search_term = match query.search_term {
         Some(ref term) if !term.is_empty() => term.clone(),
@@ -1742,49 +2205,67 @@ async fn search_hints(
 
     let limit = query.limit.unwrap_or(20).min(100);
 
+    let access = crate::db::get_library_access(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let filters = search_filters(&query, &user.id, access.as_deref());
+
+    if query.fast_mode == Some(true) {
+        return fast_search_hints(&state.db, &search_term, filters, limit)
+            .await
+            .map(Json);
+    }
+
     // Try FTS search first, fall back to LIKE if FTS fails
-    let items: Vec<MediaItem> = match search_with_fts(&state.db, &search_term, &query, limit).await
-    {
-        Ok(items) => items,
-        Err(_) => {
-            // Fallback to LIKE search
-            search_with_like(&state.db, &search_term, &query, limit).await?
-        }
+    let items: Vec<MediaItem> =
+        match search_with_fts(&state.db, &search_term, filters.clone(), limit).await {
+            Ok(items) => items,
+            Err(_) => {
+                // Fallback to LIKE search
+                search_with_like(&state.db, &search_term, filters, limit).await?
+            }
+        };
+
+    // Series of episodes, for their names and as the source of images episodes lack
+    let series_ids: Vec<&str> = items
+        .iter()
+        .filter(|i| i.item_type == "Episode")
+        .filter_map(|i| i.parent_id.as_deref())
+        .collect();
+    let image_owner_ids: Vec<&str> = items
+        .iter()
+        .map(|i| i.id.as_str())
+        .chain(series_ids.iter().copied())
+        .collect();
+    let (series_names, image_types) = tokio::join!(
+        batch_get_parent_names(&state.db, &series_ids),
+        batch_get_image_types(&state.db, &image_owner_ids),
+    );
+    // Item that has an image of a type: the item itself, or else its series.
+    // Image tags are the owning item's ID, as everywhere else.
+    let image_owner = |item_id: &String, series_id: Option<&String>, image_type: &str| {
+        std::iter::once(item_id)
+            .chain(series_id)
+            .find(|id| {
+                image_types
+                    .get(*id)
+                    .is_some_and(|t| t.contains_key(image_type))
+            })
+            .cloned()
     };
 
     // Convert to search hints
     let mut hints = Vec::with_capacity(items.len());
     for item in &items {
-        // Get series name for episodes
-        let (series_name, series_id) = if item.item_type == "Episode" {
-            if let Some(ref parent_id) = item.parent_id {
-                let parent: Option<MediaItem> =
-                    sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
-                        .bind(parent_id)
-                        .fetch_optional(&state.db)
-                        .await
-                        .ok()
-                        .flatten();
-                (
-                    parent.as_ref().map(|p| p.name.clone()),
-                    Some(parent_id.clone()),
-                )
-            } else {
-                (None, None)
-            }
-        } else {
-            (None, None)
-        };
+        let series_id = item
+            .parent_id
+            .as_ref()
+            .filter(|_| item.item_type == "Episode");
+        let series_name = series_id.and_then(|id| series_names.get(id).cloned());
 
-        let is_folder = matches!(
-            item.item_type.as_str(),
-            "Series" | "Season" | "Folder" | "CollectionFolder"
-        );
-        let media_type = match item.item_type.as_str() {
-            "Episode" | "Movie" => Some("Video".to_string()),
-            "Audio" => Some("Audio".to_string()),
-            _ => None,
-        };
+        let primary_image_tag = image_owner(&item.id, None, "Primary");
+        let thumb_image_item_id = image_owner(&item.id, series_id, "Thumb");
+        let backdrop_image_item_id = image_owner(&item.id, series_id, "Backdrop");
 
         hints.push(SearchHint {
             id: item.id.clone(),
@@ -1792,18 +2273,18 @@ async fn search_hints(
             item_type: item.item_type.clone(),
             year: item.year,
             production_year: item.year,
-            index_number: item.index_number,
-            parent_index_number: item.parent_index_number,
-            primary_image_tag: Some("default".to_string()), // Placeholder
-            thumb_image_tag: None,
-            thumb_image_item_id: None,
-            backdrop_image_tag: None,
-            backdrop_image_item_id: None,
+            index_number: item.index_number_for_display(),
+            parent_index_number: item.parent_index_number_for_display(),
+            primary_image_tag,
+            thumb_image_tag: thumb_image_item_id.clone(),
+            thumb_image_item_id,
+            backdrop_image_tag: backdrop_image_item_id.clone(),
+            backdrop_image_item_id,
             series_name,
-            series_id,
+            series_id: series_id.cloned(),
             runtime_ticks: item.runtime_ticks,
-            media_type,
-            is_folder,
+            media_type: media_type_of(&item.item_type),
+            is_folder: is_folder_type(&item.item_type),
             run_time_ticks: item.runtime_ticks,
             channel_id: None,
             channel_name: None,
@@ -1816,6 +2297,91 @@ async fn search_hints(
     }))
 }
 
+/// Search hints straight from the FTS or name index, with nothing looked up
+/// per item
+async fn fast_search_hints(
+    pool: &sqlx::SqlitePool,
+    search_term: &str,
+    filters: ItemQuery,
+    limit: i32,
+) -> Result<SearchHintsResponse, (StatusCode, String)> {
+    let fts_query = prepare_fts_query(search_term);
+    let fts_items = if fts_query.is_empty() {
+        None
+    } else {
+        filters
+            .clone()
+            .fts_match(&fts_query)
+            .sort(ItemSort::SearchRank, SortOrder::Ascending)
+            .limit(limit)
+            .fetch_hints(pool)
+            .await
+            .ok()
+    };
+    // One-letter terms and FTS errors fall back to a name match
+    let items = match fts_items {
+        Some(items) => items,
+        None => filters
+            .name_term(search_term)
+            .sort(
+                ItemSort::NameRelevance(search_term.to_string()),
+                SortOrder::Ascending,
+            )
+            .sort(ItemSort::Column("name"), SortOrder::Ascending)
+            .limit(limit)
+            .fetch_hints(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+    };
+
+    let hints: Vec<SearchHint> = items
+        .into_iter()
+        .map(|item| SearchHint {
+            media_type: media_type_of(&item.item_type),
+            is_folder: is_folder_type(&item.item_type),
+            id: item.id,
+            name: item.name,
+            item_type: item.item_type,
+            year: item.year,
+            production_year: item.year,
+            index_number: None,
+            parent_index_number: None,
+            primary_image_tag: None,
+            thumb_image_tag: None,
+            thumb_image_item_id: None,
+            backdrop_image_tag: None,
+            backdrop_image_item_id: None,
+            series_name: None,
+            series_id: None,
+            runtime_ticks: None,
+            run_time_ticks: None,
+            channel_id: None,
+            channel_name: None,
+        })
+        .collect();
+    Ok(SearchHintsResponse {
+        total_record_count: hints.len() as i32,
+        search_hints: hints,
+    })
+}
+
+/// Whether items of a type are browsed into rather than played
+fn is_folder_type(item_type: &str) -> bool {
+    matches!(
+        item_type,
+        "Series" | "Season" | "MusicAlbum" | "MusicArtist" | "Folder" | "CollectionFolder"
+    )
+}
+
+/// MediaType of playable item types
+fn media_type_of(item_type: &str) -> Option<String> {
+    match item_type {
+        "Episode" | "Movie" => Some("Video".to_string()),
+        "Audio" => Some("Audio".to_string()),
+        _ => None,
+    }
+}
+
 // ============================================================================
 // Search helper functions
 // ============================================================================
@@ -1824,7 +2390,7 @@ async fn search_hints(
 async fn search_with_fts(
     pool: &sqlx::SqlitePool,
     search_term: &str,
-    query: &SearchHintsQuery,
+    filters: ItemQuery,
     limit: i32,
 ) -> Result<Vec<MediaItem>, sqlx::Error> {
     // Prepare FTS query
@@ -1834,119 +2400,109 @@ async fn search_with_fts(
         return Ok(vec![]);
     }
 
-    // Parse item type filters
-    let include_types: Option<Vec<&str>> = query
-        .include_item_types
-        .as_ref()
-        .map(|t| t.split(',').map(|s| s.trim()).collect());
-    let exclude_types: Option<Vec<&str>> = query
-        .exclude_item_types
-        .as_ref()
-        .map(|t| t.split(',').map(|s| s.trim()).collect());
-
-    // Build query with QueryBuilder for safe parameter binding
-    let mut qb: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
-        r#"SELECT m.*
-        FROM media_items m
-        JOIN media_items_fts f ON m.rowid = f.rowid
-        WHERE media_items_fts MATCH "#,
-    );
-
-    qb.push_bind(fts_query);
+    filters
+        .fts_match(&fts_query)
+        .sort(ItemSort::SearchRank, SortOrder::Ascending)
+        .limit(limit)
+        .fetch_all(pool)
+        .await
+}
 
-    // Include type filter
-    if let Some(ref types) = include_types {
-        qb.push(" AND m.item_type IN (");
-        let mut separated = qb.separated(", ");
-        for t in types {
-            separated.push_bind(t.to_string());
-        }
-        separated.push_unseparated(")");
+/// One page of a query's items, in the requested order, with the total count
+///
+/// Keys without their own sort order use the last one given; no keys sorts by
+/// name.
+async fn fetch_sorted_page(
+    pool: &sqlx::SqlitePool,
+    item_query: &ItemQuery,
+    sort_by: &[String],
+    sort_orders: &[String],
+    limit: i32,
+    start_index: i32,
+) -> Result<(Vec<MediaItem>, i32), sqlx::Error> {
+    // Sort columns are whitelisted by ItemSort
+    let mut sorted_query = item_query.clone();
+    if sort_by.is_empty() {
+        let order = SortOrder::from_sort_order(sort_orders.first().map(|s| s.as_str()));
+        sorted_query = sorted_query.sort(ItemSort::from_sort_by("SortName"), order);
     }
-
-    // Exclude type filter
-    if let Some(ref types) = exclude_types {
-        qb.push(" AND m.item_type NOT IN (");
-        let mut separated = qb.separated(", ");
-        for t in types {
-            separated.push_bind(t.to_string());
-        }
-        separated.push_unseparated(")");
+    for (i, key) in sort_by.iter().enumerate() {
+        let order = sort_orders.get(i).or(sort_orders.last());
+        sorted_query = sorted_query.sort(
+            ItemSort::from_sort_by(key),
+            SortOrder::from_sort_order(order.map(|s| s.as_str())),
+        );
     }
+    let items = sorted_query
+        .limit(limit)
+        .offset(start_index)
+        .fetch_all(pool)
+        .await?;
 
-    qb.push(" ORDER BY bm25(media_items_fts) LIMIT ")
-        .push_bind(limit);
+    // Count query with the same filters
+    let total = item_query.count(pool).await?;
+    Ok((items, total))
+}
 
-    qb.build_query_as().fetch_all(pool).await
+/// Item type, parent and per-user filters shared by both search paths
+///
+/// `access` is the user's libraries when they don't have access to all of them.
+/// Hidden items are excluded either way; filtering on the libraries directly
+/// keeps restricted users' searches from scanning the rest of the database.
+fn search_filters(query: &SearchHintsQuery, user_id: &str, access: Option<&[String]>) -> ItemQuery {
+    let mut item_query = ItemQuery::new().visible_to(user_id);
+    if let Some(ref parent_id) = query.parent_id {
+        item_query = item_query.ancestor(parent_id);
+    }
+    if let Some(library_ids) = access {
+        item_query = item_query.in_libraries(library_ids);
+    }
+    if let Some(ref types) = query.include_item_types {
+        item_query = item_query.include_types(&[types]);
+    }
+    if let Some(ref types) = query.exclude_item_types {
+        item_query = item_query.exclude_types(&[types]);
+    }
+    item_query
 }
 
 /// Fallback search using LIKE (slower but always works)
 async fn search_with_like(
     pool: &sqlx::SqlitePool,
     search_term: &str,
-    query: &SearchHintsQuery,
+    filters: ItemQuery,
     limit: i32,
 ) -> Result<Vec<MediaItem>, (StatusCode, String)> {
-    let search_lower = search_term.to_lowercase();
-    let search_pattern = format!("%{}%", search_lower);
-    let prefix_pattern = format!("{}%", search_lower);
-
-    // Parse item type filters
-    let include_types: Option<Vec<&str>> = query
-        .include_item_types
-        .as_ref()
-        .map(|t| t.split(',').map(|s| s.trim()).collect());
-    let exclude_types: Option<Vec<&str>> = query
-        .exclude_item_types
-        .as_ref()
-        .map(|t| t.split(',').map(|s| s.trim()).collect());
-
-    // Build query with QueryBuilder
-    let mut qb: sqlx::QueryBuilder<sqlx::Sqlite> =
-        sqlx::QueryBuilder::new("SELECT * FROM media_items WHERE (LOWER(name) LIKE ");
-
-    qb.push_bind(search_pattern.clone())
-        .push(" OR LOWER(COALESCE(overview, '')) LIKE ")
-        .push_bind(search_pattern)
-        .push(")");
-
-    // Include type filter
-    if let Some(ref types) = include_types {
-        qb.push(" AND item_type IN (");
-        let mut separated = qb.separated(", ");
-        for t in types {
-            separated.push_bind(t.to_string());
-        }
-        separated.push_unseparated(")");
-    }
-
-    // Exclude type filter
-    if let Some(ref types) = exclude_types {
-        qb.push(" AND item_type NOT IN (");
-        let mut separated = qb.separated(", ");
-        for t in types {
-            separated.push_bind(t.to_string());
-        }
-        separated.push_unseparated(")");
-    }
-
     // Order by relevance: exact matches first, then prefix matches, then contains
-    qb.push(" ORDER BY CASE WHEN LOWER(name) = ")
-        .push_bind(search_lower.clone())
-        .push(" THEN 0 WHEN LOWER(name) LIKE ")
-        .push_bind(prefix_pattern)
-        .push(" THEN 1 ELSE 2 END, name LIMIT ")
-        .push_bind(limit);
-
-    qb.build_query_as()
+    filters
+        .search_term(search_term)
+        .sort(
+            ItemSort::NameRelevance(search_term.to_string()),
+            SortOrder::Ascending,
+        )
+        .sort(ItemSort::Column("name"), SortOrder::Ascending)
+        .limit(limit)
         .fetch_all(pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
 
+/// Prepare an /Items search term for FTS5: every word must start a word in the
+/// name, sort name or overview
+///
+/// An empty result means the index can't be used for the term.
+fn prepare_fts_filter(term: &str) -> String {
+    fts_terms(term).join(" ")
+}
+
 /// Prepare a user query for FTS5
 fn prepare_fts_query(query: &str) -> String {
-    let terms: Vec<String> = query
+    fts_terms(query).join(" OR ")
+}
+
+/// Quoted prefix terms for the words of a query, leaving out one-letter words
+fn fts_terms(query: &str) -> Vec<String> {
+    query
         .split_whitespace()
         .filter(|s| !s.is_empty() && s.len() >= 2)
         .map(|s| {
@@ -1954,9 +2510,7 @@ fn prepare_fts_query(query: &str) -> String {
             let escaped = s.replace(['"', '\'', '*'], "").replace('-', " ");
             format!("\"{}\"*", escaped)
         })
-        .collect();
-
-    terms.join(" OR ")
+        .collect()
 }
 
 // =============================================================================
@@ -2149,6 +2703,74 @@ async fn refresh_item(
     Ok(StatusCode::NO_CONTENT)
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RescanResponse {
+    pub run_time_ticks: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub size: i64,
+    pub thumbnail_queued: bool,
+}
+
+/// POST /Items/:id/Rescan - Probe an item's file again after it was replaced in place (admin only)
+async fn rescan_item(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<RescanResponse>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+
+    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+
+    let path = match item.path.as_deref() {
+        Some(path) if item.stream_url.is_none() => std::path::Path::new(path),
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Item has no local file to rescan".to_string(),
+            ))
+        }
+    };
+    if !tokio::fs::try_exists(path).await.unwrap_or(false) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("File not found: {}", path.display()),
+        ));
+    }
+
+    let image_cache_dir = state.config.paths.cache_dir.join("images");
+    let result =
+        crate::scanner::rescan_item(&state.db, &item.id, &item.item_type, path, &image_cache_dir)
+            .await
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{:#}", e)))?;
+    tracing::info!(
+        "Rescanned {} '{}': {:?} ticks, {:?}x{:?}, {} bytes",
+        item.item_type,
+        item.name,
+        result.runtime_ticks,
+        result.width,
+        result.height,
+        result.file_size
+    );
+
+    Ok(Json(RescanResponse {
+        run_time_ticks: result.runtime_ticks,
+        width: result.width,
+        height: result.height,
+        size: result.file_size as i64,
+        thumbnail_queued: result.thumbnail_queued,
+    }))
+}
+
 /// Internal function to refresh metadata for an item
 async fn refresh_item_metadata(
     db: &sqlx::SqlitePool,
@@ -2164,7 +2786,10 @@ async fn refresh_item_metadata(
     use crate::services::metadata::MetadataService;
 
     let cache_dir = config.paths.cache_dir.join("images");
-    let metadata_service = MetadataService::from_env(cache_dir, None);
+    let settings = crate::scanner::library_settings::get(&item.library_id);
+    let metadata_service = MetadataService::from_env(cache_dir, settings.anime_db(None))
+        .with_language(settings.metadata_language.as_deref())
+        .with_fresh_lookups();
 
     tracing::info!(
         "Refreshing metadata for {} '{}' (replace_all={})",
@@ -2257,6 +2882,10 @@ async fn refresh_item_metadata(
                     .await?;
                 }
 
+                if let Some(ref rating) = meta.official_rating {
+                    parental_controls::set_item_rating(db, &item.id, rating).await?;
+                }
+
                 // Queue images
                 if replace_images {
                     // Delete existing images first
@@ -2272,6 +2901,7 @@ async fn refresh_item_metadata(
                 if let Some(ref url) = meta.backdrop_url {
                     crate::db::queue_image(db, &item.id, "Backdrop", url).await?;
                 }
+                crate::db::queue_extra_backdrops(db, &item.id, &meta.extra_backdrop_urls).await?;
 
                 // Update genres
                 if let Some(ref genres) = meta.genres {
@@ -2324,6 +2954,26 @@ async fn refresh_item_metadata(
                     }
                 }
 
+                // New episodes (or changed provider seasons) need mappings and the
+                // selected ordering re-applied
+                let tmdb = tmdb_client(config);
+                if let Err(e) = season_mapping::auto_map_series(db, tmdb.as_ref(), &item.id).await {
+                    tracing::warn!(
+                        "Failed to update season mappings for '{}': {}",
+                        item.name,
+                        e
+                    );
+                }
+                if let Err(e) =
+                    episode_order::reapply_episode_ordering(db, tmdb.as_ref(), &item.id).await
+                {
+                    tracing::warn!(
+                        "Failed to re-apply episode ordering for '{}': {}",
+                        item.name,
+                        e
+                    );
+                }
+
                 tracing::info!("Successfully refreshed metadata for series '{}'", item.name);
             } else {
                 tracing::warn!("No metadata found for series '{}'", item.name);
@@ -2387,6 +3037,10 @@ async fn refresh_item_metadata(
                     .await?;
                 }
 
+                if let Some(ref rating) = meta.official_rating {
+                    parental_controls::set_item_rating(db, &item.id, rating).await?;
+                }
+
                 // Queue images
                 if replace_images {
                     sqlx::query("DELETE FROM images WHERE item_id = ?")
@@ -2401,6 +3055,7 @@ async fn refresh_item_metadata(
                 if let Some(ref url) = meta.backdrop_url {
                     crate::db::queue_image(db, &item.id, "Backdrop", url).await?;
                 }
+                crate::db::queue_extra_backdrops(db, &item.id, &meta.extra_backdrop_urls).await?;
 
                 // Update genres
                 if let Some(ref genres) = meta.genres {
@@ -2417,6 +3072,9 @@ async fn refresh_item_metadata(
                     }
                 }
 
+                // Franchise BoxSet from the movie's TMDB collection
+                box_sets::record_movie(db, &item.id, meta.collection.as_ref()).await?;
+
                 tracing::info!("Successfully refreshed metadata for movie '{}'", item.name);
             } else {
                 tracing::warn!("No metadata found for movie '{}'", item.name);
@@ -2424,9 +3082,25 @@ async fn refresh_item_metadata(
         }
         _ => {
             tracing::debug!("Refresh not supported for item type: {}", item.item_type);
+            return Ok(());
         }
     }
 
+    // Provider IDs may have changed; look up the ones still missing
+    if let Err(e) =
+        crate::services::provider_ids::cross_populate_item(db, &metadata_service, &item.id).await
+    {
+        tracing::warn!(
+            "Failed to cross-populate provider IDs for {}: {}",
+            item.id,
+            e
+        );
+    }
+
+    events::publish(ServerEvent::ItemUpdated {
+        item_id: item.id.clone(),
+    });
+
     Ok(())
 }
 
@@ -2927,6 +3601,15 @@ async fn get_external_id_infos(
         url_format_string: Some("https://www.imdb.com/title/{0}".to_string()),
     });
 
+    if item.item_type != "Movie" {
+        infos.push(ExternalIdInfo {
+            name: "TheTVDB".to_string(),
+            key: "Tvdb".to_string(),
+            id_type: "Series".to_string(),
+            url_format_string: Some("https://thetvdb.com/dereferrer/series/{0}".to_string()),
+        });
+    }
+
     infos.push(ExternalIdInfo {
         name: "TheMovieDb".to_string(),
         key: "Tmdb".to_string(),
@@ -3011,6 +3694,11 @@ pub struct MetadataEditorInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_type: Option<String>,
     pub content_type_options: Vec<NameValuePair>,
+    /// Selected TMDB episode group for series ("" or absent means aired order)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub episode_ordering: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub episode_ordering_options: Vec<NameValuePair>,
 }
 
 /// GET /Items/:id/MetadataEditor - Get metadata editor configuration
@@ -3025,53 +3713,17 @@ async fn get_metadata_editor(
     let external_ids_result =
         get_external_id_infos(State(state.clone()), headers.clone(), Path(id.clone())).await?;
 
+    let (episode_ordering, episode_ordering_options) =
+        get_episode_ordering_options(&state, &id).await?;
+
     let info = MetadataEditorInfo {
-        parental_rating_options: vec![
-            ParentalRating {
-                name: "G".to_string(),
-                value: 1,
-            },
-            ParentalRating {
-                name: "PG".to_string(),
-                value: 5,
-            },
-            ParentalRating {
-                name: "PG-13".to_string(),
-                value: 7,
-            },
-            ParentalRating {
-                name: "R".to_string(),
-                value: 9,
-            },
-            ParentalRating {
-                name: "NC-17".to_string(),
-                value: 10,
-            },
-            ParentalRating {
-                name: "TV-Y".to_string(),
-                value: 1,
-            },
-            ParentalRating {
-                name: "TV-Y7".to_string(),
-                value: 3,
-            },
-            ParentalRating {
-                name: "TV-G".to_string(),
-                value: 1,
-            },
-            ParentalRating {
-                name: "TV-PG".to_string(),
-                value: 5,
-            },
-            ParentalRating {
-                name: "TV-14".to_string(),
-                value: 7,
-            },
-            ParentalRating {
-                name: "TV-MA".to_string(),
-                value: 9,
-            },
-        ],
+        parental_rating_options: parental_controls::RATINGS
+            .iter()
+            .map(|(name, value)| ParentalRating {
+                name: name.to_string(),
+                value: *value,
+            })
+            .collect(),
         countries: vec![
             CountryInfo {
                 name: "United States".to_string(),
@@ -3117,16 +3769,133 @@ async fn get_metadata_editor(
                 name: "TV Shows".to_string(),
                 value: "tvshows".to_string(),
             },
+            NameValuePair {
+                name: "Music".to_string(),
+                value: "music".to_string(),
+            },
             NameValuePair {
                 name: "Mixed Content".to_string(),
                 value: "mixed".to_string(),
             },
         ],
+        episode_ordering,
+        episode_ordering_options,
     };
 
     Ok(Json(info))
 }
 
+/// TMDB client from the configured API key (None if TMDB is not configured)
+pub fn tmdb_client(config: &crate::config::AppConfig) -> Option<TmdbClient> {
+    config
+        .tmdb_api_key
+        .clone()
+        .map(|key| TmdbClient::new(key, config.paths.cache_dir.join("images")))
+}
+
+/// Current ordering and available TMDB episode groups of a series
+///
+/// Lookup failures only hide the options, the rest of the editor still works.
+async fn get_episode_ordering_options(
+    state: &AppState,
+    id: &str,
+) -> Result<(Option<String>, Vec<NameValuePair>), (StatusCode, String)> {
+    let series: Option<(Option<String>, Option<String>)> = sqlx::query_as(
+        "SELECT tmdb_id, episode_group_id FROM media_items WHERE id = ? AND item_type = 'Series'",
+    )
+    .bind(id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let Some((tmdb_id, episode_group_id)) = series else {
+        return Ok((None, Vec::new()));
+    };
+
+    let mut options = vec![NameValuePair {
+        name: "Aired order".to_string(),
+        value: String::new(),
+    }];
+
+    let tv_id = tmdb_id.and_then(|t| t.parse::<i64>().ok());
+    if let (Some(tmdb), Some(tv_id)) = (tmdb_client(&state.config), tv_id) {
+        match tmdb.get_episode_groups(tv_id).await {
+            Ok(groups) => options.extend(groups.into_iter().map(|g| {
+                let mut name = match g.type_name() {
+                    Some(kind) => format!("{}: {}", kind, g.name),
+                    None => g.name.clone(),
+                };
+                if let Some(count) = g.episode_count {
+                    name.push_str(&format!(" ({} episodes)", count));
+                }
+                NameValuePair { name, value: g.id }
+            })),
+            Err(e) => tracing::warn!("Failed to fetch episode groups for {}: {}", id, e),
+        }
+    }
+
+    Ok((Some(episode_group_id.unwrap_or_default()), options))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EpisodeOrderingBody {
+    /// TMDB episode group ID, or null/"" for aired order
+    pub episode_group_id: Option<String>,
+}
+
+/// POST /Items/:id/EpisodeOrdering - Select an alternate episode ordering for a series (admin only)
+async fn set_episode_ordering(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(body): Json<EpisodeOrderingBody>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+
+    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+
+    if item.item_type != "Series" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Episode orderings only apply to series".to_string(),
+        ));
+    }
+
+    let group_id = body.episode_group_id.as_deref().filter(|g| !g.is_empty());
+    let tmdb = tmdb_client(&state.config);
+    if group_id.is_some() {
+        if tmdb.is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "TMDB is not configured".to_string(),
+            ));
+        }
+        if item.tmdb_id.is_none() {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Series has no TMDB ID, identify it first".to_string(),
+            ));
+        }
+    }
+
+    episode_order::set_episode_ordering(&state.db, tmdb.as_ref(), &id, group_id)
+        .await
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    events::publish(ServerEvent::ItemUpdated { item_id: id });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 // =============================================================================
 // Remote Search - Search for series/movies to identify
 // =============================================================================
@@ -3476,5 +4245,7 @@ async fn apply_remote_search(
         body.search_provider_name.as_deref().unwrap_or("unknown")
     );
 
+    events::publish(ServerEvent::ItemUpdated { item_id: id });
+
     Ok(StatusCode::NO_CONTENT)
 }
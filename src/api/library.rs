@@ -1,14 +1,28 @@
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
     routing::{delete, get, post},
     Json, Router,
 };
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use uuid::Uuid;
 
-use crate::{models::Library, scanner, services::auth, AppState};
+use crate::{
+    events::{self, ServerEvent},
+    models::Library,
+    scanner::{
+        self,
+        library_settings::{self, LibrarySettings},
+    },
+    services::{
+        auth, image_refresh, mediainfo, playback_stats, progress, scheduled_tasks, thumbnails,
+    },
+    time::Ticks,
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -31,10 +45,14 @@ pub struct VirtualFolderInfo {
     pub item_id: String,
     pub primary_image_item_id: Option<String>,
     pub refresh_status: String,
+    /// Whether the library's folder is missing (its items are kept until it's back)
+    pub is_offline: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offline_since: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "PascalCase")]
+#[serde(rename_all = "PascalCase", default)]
 pub struct LibraryOptions {
     pub enable_photos: bool,
     pub enable_realtime_monitor: bool,
@@ -48,6 +66,27 @@ pub struct LibraryOptions {
     pub automatic_refresh_interval_days: i32,
     pub metadata_savers: Vec<String>,
     pub type_options: Vec<TypeOptions>,
+    /// Language of names and overviews from TMDB ("ja", "pt-BR"); empty for the default
+    pub preferred_metadata_language: Option<String>,
+    // Overrides of server settings for this library: null keeps the server's,
+    // and a field left out of an update keeps the library's current value
+    // (Jellyfin's own clients don't know them)
+    #[serde(deserialize_with = "present")]
+    pub enable_anime_db: Option<Option<bool>>,
+    #[serde(deserialize_with = "present")]
+    pub fetch_episode_metadata: Option<Option<bool>>,
+    /// Read runtime and resolution of video files with ffprobe while scanning
+    #[serde(deserialize_with = "present")]
+    pub extract_media_info: Option<Option<bool>>,
+}
+
+/// Tell a field set to null (Some(None)) from one left out (None)
+pub(super) fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -74,6 +113,58 @@ impl Default for LibraryOptions {
             automatic_refresh_interval_days: 0,
             metadata_savers: vec![],
             type_options: vec![],
+            preferred_metadata_language: None,
+            enable_anime_db: None,
+            fetch_episode_metadata: None,
+            extract_media_info: None,
+        }
+    }
+}
+
+impl LibraryOptions {
+    /// Options showing a library's stored settings
+    fn from_settings(settings: LibrarySettings) -> Self {
+        Self {
+            preferred_metadata_language: Some(settings.metadata_language.unwrap_or_default()),
+            enable_anime_db: Some(settings.anime_db_enabled),
+            fetch_episode_metadata: Some(settings.fetch_episode_metadata),
+            extract_media_info: Some(settings.extract_media_info),
+            ..Default::default()
+        }
+    }
+
+    /// A library's `current` settings with these options applied, or why
+    /// they can't be stored
+    fn apply_to(&self, current: LibrarySettings) -> Result<LibrarySettings, (StatusCode, String)> {
+        let Some(language) = self.preferred_metadata_language.as_deref() else {
+            return Ok(self.apply_overrides(current));
+        };
+        let language = language.trim();
+        if !language.is_empty() && !library_settings::is_valid_language(language) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Invalid PreferredMetadataLanguage '{}' (expected a code like \"ja\" or \"pt-BR\")",
+                    language
+                ),
+            ));
+        }
+        Ok(self.apply_overrides(LibrarySettings {
+            metadata_language: (!language.is_empty()).then(|| language.to_string()),
+            ..current
+        }))
+    }
+
+    fn apply_overrides(&self, current: LibrarySettings) -> LibrarySettings {
+        LibrarySettings {
+            anime_db_enabled: self.enable_anime_db.unwrap_or(current.anime_db_enabled),
+            fetch_episode_metadata: self
+                .fetch_episode_metadata
+                .unwrap_or(current.fetch_episode_metadata),
+            extract_media_info: self
+                .extract_media_info
+                .unwrap_or(current.extract_media_info),
+            ..current
         }
     }
 }
@@ -135,10 +226,12 @@ async fn get_virtual_folders(
             name: lib.name,
             locations: vec![lib.path],
             collection_type: Some(lib.library_type),
-            library_options: LibraryOptions::default(),
+            library_options: LibraryOptions::from_settings(library_settings::get(&lib.id)),
             item_id: lib.id,
             primary_image_item_id: None,
             refresh_status: "Idle".to_string(),
+            is_offline: lib.offline_since.is_some(),
+            offline_since: lib.offline_since,
         })
         .collect();
 
@@ -153,13 +246,30 @@ async fn add_virtual_folder(
 ) -> Result<StatusCode, (StatusCode, String)> {
     require_admin(&state, &headers).await?;
 
-    let id = Uuid::new_v4().to_string();
     let collection_type = query
         .collection_type
         .unwrap_or_else(|| "movies".to_string());
 
     // Get path from query params or use a default
     let path = query.paths.unwrap_or_default();
+    let id = Library::stable_id(&path, &collection_type);
+    let settings = body
+        .as_ref()
+        .and_then(|Json(body)| body.library_options.as_ref())
+        .map(|options| options.apply_to(LibrarySettings::default()))
+        .transpose()?;
+
+    let existing: Option<(String,)> = sqlx::query_as("SELECT id FROM libraries WHERE id = ?")
+        .bind(&id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if existing.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            "A library for this folder already exists".to_string(),
+        ));
+    }
 
     sqlx::query("INSERT INTO libraries (id, name, path, library_type) VALUES (?, ?, ?, ?)")
         .bind(&id)
@@ -172,6 +282,13 @@ async fn add_virtual_folder(
 
     tracing::info!("Created library '{}' at path '{}'", query.name, path);
 
+    // Stored before the first scan so it uses them
+    if let Some(settings) = settings {
+        library_settings::save(&state.db, &id, &settings)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
     // Trigger a library scan for the newly added library
     let should_refresh = query.refresh_library.unwrap_or(true);
     if should_refresh && !path.is_empty() {
@@ -223,22 +340,32 @@ async fn remove_virtual_folder(
 ) -> Result<StatusCode, (StatusCode, String)> {
     require_admin(&state, &headers).await?;
 
-    tracing::debug!("Deleting library with name: '{}'", query.name);
-
-    let result = sqlx::query("DELETE FROM libraries WHERE name = ?")
+    let library_id: String = sqlx::query_scalar("SELECT id FROM libraries WHERE name = ?")
         .bind(&query.name)
-        .execute(&state.db)
+        .fetch_optional(&state.db)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, "Library not found".to_string()))?;
 
-    tracing::debug!("Rows affected: {}", result.rows_affected());
+    let report = crate::db::delete_library(
+        &state.db,
+        &library_id,
+        &state.config.paths.image_cache_dir(),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Library not found".to_string()))?;
 
-    if result.rows_affected() == 0 {
-        return Err((StatusCode::NOT_FOUND, "Library not found".to_string()));
+    tracing::info!(
+        "Deleted library '{}': {} items, {} cached image folders",
+        query.name,
+        report.item_ids.len(),
+        report.image_dirs_removed
+    );
+    for item_id in report.item_ids {
+        events::publish(ServerEvent::ItemRemoved { item_id });
     }
 
-    tracing::info!("Deleted library '{}'", query.name);
-
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -249,21 +376,38 @@ pub struct UpdateLibraryOptionsRequest {
     pub library_options: LibraryOptions,
 }
 
+/// POST /Library/VirtualFolders/LibraryOptions - Store a library's metadata
+/// language and its overrides of the server's scanner settings (admin)
 async fn update_library_options(
-    State(_state): State<Arc<AppState>>,
+    State(state): State<Arc<AppState>>,
     headers: HeaderMap,
-    Json(_req): Json<UpdateLibraryOptionsRequest>,
+    Json(req): Json<UpdateLibraryOptionsRequest>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    // For now, just validate auth and return success
-    // Library options aren't stored in DB yet
-    let (_, _, _, token) = parse_emby_auth_header(&headers)
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+    require_admin(&state, &headers).await?;
 
-    let _token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+    let name: String = sqlx::query_scalar("SELECT name FROM libraries WHERE id = ?")
+        .bind(&req.id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Library not found".to_string()))?;
+
+    let settings = req
+        .library_options
+        .apply_to(library_settings::get(&req.id))?;
+    library_settings::save(&state.db, &req.id, &settings)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    tracing::info!("Updated the options of library '{}': {:?}", name, settings);
 
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// POST /Library/VirtualFolders/Refresh - Reconcile every library with its files (admin)
+///
+/// Unlike POST /Library/Refresh, changed files are probed again in place
+/// before new files are scanned; runs in the background, outside the task
+/// scheduler.
 async fn refresh_library(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -297,3 +441,985 @@ async fn refresh_library(
 
     Ok(StatusCode::NO_CONTENT)
 }
+
+// =============================================================================
+// On-demand scans
+// =============================================================================
+
+/// POST /Library/Refresh - Scan every library for new and removed files now (admin)
+///
+/// Starts the "Scan Media Library" scheduled task, so its progress shows under
+/// /ScheduledTasks and it can be cancelled there; 409 while it is running.
+/// Changed files aren't probed again; POST /Library/VirtualFolders/Refresh does that.
+pub async fn refresh_all_libraries(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let task = scheduled_tasks::find("library-scan").ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "No library scan task".to_string(),
+        )
+    })?;
+    if !scheduled_tasks::start(&state.db, &state.config, task, None) {
+        return Err((
+            StatusCode::CONFLICT,
+            "A library scan is already running".to_string(),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanLibraryQuery {
+    /// quick (new and removed files, the default), full (every file and its
+    /// metadata) or metadata (items without metadata)
+    #[serde(rename = "type")]
+    pub scan_type: Option<String>,
+}
+
+/// POST /Library/:libraryId/Scan - Scan one library now (admin; 409 while it is being scanned)
+///
+/// The scan runs in the background; it shows under /System/Status while it
+/// runs and in /Library/ScanHistory once it's done.
+pub async fn scan_library(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(library_id): Path<String>,
+    Query(query): Query<ScanLibraryQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let scan_type = query.scan_type.as_deref().unwrap_or("quick").to_lowercase();
+    if !matches!(scan_type.as_str(), "quick" | "full" | "metadata") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown scan type {} (quick, full or metadata)", scan_type),
+        ));
+    }
+
+    let library: Library = sqlx::query_as("SELECT * FROM libraries WHERE id = ?")
+        .bind(&library_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Library not found".to_string()))?;
+    if scan_type == "metadata" && library.library_type == "music" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Music libraries have no metadata scan".to_string(),
+        ));
+    }
+
+    let request = progress::request_scan(&library.id).ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            format!("Library '{}' is already being scanned", library.name),
+        )
+    })?;
+
+    tracing::info!(
+        "Scan ({}) requested for library '{}' ({})",
+        scan_type,
+        library.name,
+        library.id
+    );
+    let pool = state.db.clone();
+    let config = state.config.clone();
+    tokio::spawn(async move {
+        let _request = request;
+        let cache_dir = config.paths.cache_dir.clone();
+        let result = match scan_type.as_str() {
+            "quick" => scanner::quick_scan_library(
+                &pool,
+                &library.id,
+                &library.path,
+                &library.library_type,
+                cache_dir,
+            )
+            .await
+            .map(|r| format!("{} added, {} removed", r.files_added, r.files_removed)),
+            "full" => scanner::scan_library_with_cache_dir(
+                &pool,
+                &library.id,
+                &library.path,
+                &library.library_type,
+                cache_dir,
+                Some(config.anime_db_enabled),
+                Some(config.fetch_episode_metadata),
+            )
+            .await
+            .map(|r| {
+                format!(
+                    "{} series, {} episodes, {} movies, {} tracks added",
+                    r.series_added, r.episodes_added, r.movies_added, r.tracks_added
+                )
+            }),
+            _ => scanner::scan_missing_metadata(
+                &pool,
+                &library.id,
+                cache_dir,
+                Some(config.anime_db_enabled),
+            )
+            .await
+            .map(|r| {
+                format!(
+                    "{}/{} series, {}/{} movies updated",
+                    r.series_updated, r.series_scanned, r.movies_updated, r.movies_scanned
+                )
+            }),
+        };
+        match result {
+            Ok(summary) => tracing::info!(
+                "Scan ({}) of '{}' finished: {}",
+                scan_type,
+                library.name,
+                summary
+            ),
+            Err(e) => tracing::error!("Scan ({}) of '{}' failed: {}", scan_type, library.name, e),
+        }
+    });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// =============================================================================
+// Library export
+// =============================================================================
+
+const EXPORT_CSV_HEADER: &str = "Id,Type,SeriesName,SeasonNumber,EpisodeNumber,Name,Year,Path,Container,VideoCodec,AudioCodecs,Resolution,RuntimeMinutes,PlayCount,PlayedByUsers,TmdbId,ImdbId,AniListId,MalId,AniDbId,KitsuId\n";
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportLibraryQuery {
+    pub format: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ExportRow {
+    id: String,
+    item_type: String,
+    name: String,
+    series_name: Option<String>,
+    parent_index_number: Option<i32>,
+    index_number: Option<i32>,
+    year: Option<i32>,
+    path: Option<String>,
+    runtime_ticks: Option<i64>,
+    tmdb_id: Option<String>,
+    imdb_id: Option<String>,
+    anilist_id: Option<String>,
+    mal_id: Option<String>,
+    anidb_id: Option<String>,
+    kitsu_id: Option<String>,
+    play_count: i64,
+    played_users: i64,
+    /// The file's cached ffprobe output, if it has been probed
+    probe_output: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExportEntry {
+    id: String,
+    #[serde(rename = "Type")]
+    item_type: String,
+    series_name: Option<String>,
+    season_number: Option<i32>,
+    episode_number: Option<i32>,
+    name: String,
+    year: Option<i32>,
+    path: Option<String>,
+    container: Option<String>,
+    video_codec: Option<String>,
+    audio_codecs: Vec<String>,
+    resolution: Option<String>,
+    runtime_minutes: Option<i64>,
+    play_count: i64,
+    played_by_users: i64,
+    tmdb_id: Option<String>,
+    imdb_id: Option<String>,
+    #[serde(rename = "AniListId")]
+    anilist_id: Option<String>,
+    mal_id: Option<String>,
+    #[serde(rename = "AniDbId")]
+    anidb_id: Option<String>,
+    kitsu_id: Option<String>,
+}
+
+impl ExportEntry {
+    fn from_row(row: ExportRow) -> Self {
+        // Codecs come from the probe cache; files never probed leave them empty
+        let media_info = row
+            .probe_output
+            .as_deref()
+            .and_then(|output| mediainfo::parse_probe_output(output).ok())
+            .unwrap_or_default();
+
+        Self {
+            id: row.id,
+            item_type: row.item_type,
+            series_name: row.series_name,
+            season_number: row.parent_index_number,
+            episode_number: row.index_number,
+            name: row.name,
+            year: row.year,
+            path: row.path,
+            container: media_info.container,
+            video_codec: media_info.video_codec,
+            audio_codecs: media_info
+                .audio_streams
+                .into_iter()
+                .map(|s| s.codec)
+                .collect(),
+            resolution: media_info
+                .width
+                .zip(media_info.height)
+                .map(|(w, h)| format!("{}x{}", w, h)),
+            runtime_minutes: row
+                .runtime_ticks
+                .or(media_info.duration_ticks)
+                .map(|ticks| Ticks(ticks).whole_minutes()),
+            play_count: row.play_count,
+            played_by_users: row.played_users,
+            tmdb_id: row.tmdb_id,
+            imdb_id: row.imdb_id,
+            anilist_id: row.anilist_id,
+            mal_id: row.mal_id,
+            anidb_id: row.anidb_id,
+            kitsu_id: row.kitsu_id,
+        }
+    }
+
+    fn to_csv_line(&self) -> String {
+        let opt = |v: &Option<String>| csv_field(v.as_deref().unwrap_or(""));
+        let num = |v: Option<i32>| v.map(|n| n.to_string()).unwrap_or_default();
+
+        let fields = [
+            csv_field(&self.id),
+            csv_field(&self.item_type),
+            opt(&self.series_name),
+            num(self.season_number),
+            num(self.episode_number),
+            csv_field(&self.name),
+            num(self.year),
+            opt(&self.path),
+            opt(&self.container),
+            opt(&self.video_codec),
+            csv_field(&self.audio_codecs.join("|")),
+            opt(&self.resolution),
+            self.runtime_minutes
+                .map(|n| n.to_string())
+                .unwrap_or_default(),
+            self.play_count.to_string(),
+            self.played_by_users.to_string(),
+            opt(&self.tmdb_id),
+            opt(&self.imdb_id),
+            opt(&self.anilist_id),
+            opt(&self.mal_id),
+            opt(&self.anidb_id),
+            opt(&self.kitsu_id),
+        ];
+
+        let mut line = fields.join(",");
+        line.push('\n');
+        line
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// GET /Library/:id/Export - Download a full listing of a library's movies and
+/// episodes as CSV or JSON
+///
+/// Music isn't listed: the columns (series, season and episode, video codec,
+/// resolution, movie database IDs) describe video files.
+pub async fn export_library(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(library_id): Path<String>,
+    Query(query): Query<ExportLibraryQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let format = query
+        .format
+        .as_deref()
+        .unwrap_or("csv")
+        .to_ascii_lowercase();
+    if format != "csv" && format != "json" {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Unsupported export format '{}', expected csv or json",
+                format
+            ),
+        ));
+    }
+
+    let library: Option<(String,)> = sqlx::query_as("SELECT name FROM libraries WHERE id = ?")
+        .bind(&library_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (library_name,) =
+        library.ok_or_else(|| (StatusCode::NOT_FOUND, "Library not found".to_string()))?;
+
+    tracing::info!("Exporting library '{}' as {}", library_name, format);
+
+    // Rows are read while the body is sent, so a large library is never held
+    // in memory; the query runs in its own task because its stream borrows
+    // the pool
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<Result<ExportRow, sqlx::Error>>(64);
+    let pool = state.db.clone();
+    tokio::spawn(async move {
+        // A cached probe is only used while the file still has the size and
+        // modification time the last refresh recorded; items without recorded
+        // ones (never refreshed) take it by path
+        let mut rows = sqlx::query_as(
+            r#"
+            SELECT m.id, m.item_type, m.name, p.name AS series_name,
+                   m.parent_index_number, m.index_number, m.year, m.path, m.runtime_ticks,
+                   m.tmdb_id, m.imdb_id, m.anilist_id, m.mal_id, m.anidb_id, m.kitsu_id,
+                   COALESCE(SUM(pp.play_count), 0) AS play_count,
+                   COUNT(CASE WHEN pp.played = 1 THEN 1 END) AS played_users,
+                   pc.output AS probe_output
+            FROM media_items m
+            LEFT JOIN media_items p ON p.id = m.parent_id
+            LEFT JOIN playback_progress pp ON pp.item_id = m.id
+            LEFT JOIN probe_cache pc ON pc.path = m.path
+                AND (m.file_size IS NULL OR m.file_modified IS NULL
+                     OR (pc.file_size = m.file_size AND pc.file_modified = m.file_modified))
+            WHERE m.library_id = ? AND m.item_type IN ('Movie', 'Episode')
+            GROUP BY m.id
+            ORDER BY COALESCE(p.sort_name, p.name, m.sort_name, m.name),
+                     m.parent_index_number, m.index_number
+            "#,
+        )
+        .bind(&library_id)
+        .fetch(&pool);
+        while let Some(row) = rows.next().await {
+            // The client went away
+            if tx.send(row).await.is_err() {
+                break;
+            }
+        }
+    });
+    let rows = stream::poll_fn(move |cx| rx.poll_recv(cx));
+
+    let is_json = format == "json";
+    let entries = rows.enumerate().map(move |(i, row)| {
+        // Headers are already sent, so a failed read can only cut the download short
+        let entry = ExportEntry::from_row(row.map_err(|e| {
+            tracing::warn!("Library export failed: {}", e);
+            std::io::Error::other(e)
+        })?);
+        let chunk = if is_json {
+            let separator = if i == 0 { "\n" } else { ",\n" };
+            format!(
+                "{}{}",
+                separator,
+                serde_json::to_string(&entry).unwrap_or_default()
+            )
+        } else {
+            entry.to_csv_line()
+        };
+        Ok::<_, std::io::Error>(chunk)
+    });
+
+    let (header, footer, content_type) = if is_json {
+        ("[", "\n]\n", "application/json")
+    } else {
+        (EXPORT_CSV_HEADER, "", "text/csv; charset=utf-8")
+    };
+
+    let body = stream::once(async move { Ok(header.to_string()) })
+        .chain(entries)
+        .chain(stream::once(async move { Ok(footer.to_string()) }));
+
+    let filename: String = library_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, content_type)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}.{}\"", filename, format),
+        )
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from_stream(body))
+        .unwrap())
+}
+
+// =============================================================================
+// Image refresh
+// =============================================================================
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshImagesQuery {
+    /// Only refresh images downloaded more than this many days ago
+    pub older_than_days: Option<u32>,
+}
+
+/// POST /Library/:libraryId/RefreshImages - Download every series and movie
+/// poster and backdrop in a library again from the providers (admin)
+pub async fn refresh_library_images(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(library_id): Path<String>,
+    Query(query): Query<RefreshImagesQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM libraries WHERE id = ?")
+        .bind(&library_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if exists.is_none() {
+        return Err((StatusCode::NOT_FOUND, "Library not found".to_string()));
+    }
+    if image_refresh::is_running() {
+        return Err((
+            StatusCode::CONFLICT,
+            "An image refresh is already running".to_string(),
+        ));
+    }
+
+    let pool = state.db.clone();
+    let image_cache_dir = state.config.paths.cache_dir.join("images");
+    tokio::spawn(async move {
+        match image_refresh::refresh_images(
+            &pool,
+            image_cache_dir,
+            Some(&library_id),
+            query.older_than_days,
+        )
+        .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => tracing::info!("Image refresh already running, skipped"),
+            Err(e) => tracing::error!("Image refresh of library {} failed: {}", library_id, e),
+        }
+    });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// =============================================================================
+// Scan history
+// =============================================================================
+
+pub fn scan_history_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_scan_history))
+        .route("/:scanId", get(get_scan_details))
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScanHistoryDto {
+    pub id: String,
+    pub library_id: String,
+    pub library_name: String,
+    pub scan_type: String,
+    pub status: String,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub duration_ms: Option<i64>,
+    pub items_added: i64,
+    pub items_removed: i64,
+    pub items_updated: i64,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScanHistoryResponse {
+    pub items: Vec<ScanHistoryDto>,
+    pub total_record_count: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScanChangeDto {
+    pub change_type: String,
+    pub item_id: String,
+    pub item_type: String,
+    pub name: String,
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScanDetailsDto {
+    #[serde(flatten)]
+    pub scan: ScanHistoryDto,
+    pub changes: Vec<ScanChangeDto>,
+    /// Changes matching the filter that were recorded (the first 1000 per scan)
+    pub total_change_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanHistoryQuery {
+    pub library_id: Option<String>,
+    pub start_index: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanDetailsQuery {
+    /// Added, Removed or Updated
+    pub change_type: Option<String>,
+    pub start_index: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// GET /Library/ScanHistory - Recent scan runs, newest first
+async fn get_scan_history(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ScanHistoryQuery>,
+) -> Result<Json<ScanHistoryResponse>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let (total,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM scan_history WHERE (?1 IS NULL OR library_id = ?1)")
+            .bind(query.library_id.as_deref())
+            .fetch_one(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let items: Vec<ScanHistoryDto> = sqlx::query_as(
+        "SELECT * FROM scan_history WHERE (?1 IS NULL OR library_id = ?1)
+         ORDER BY started_at DESC LIMIT ?2 OFFSET ?3",
+    )
+    .bind(query.library_id.as_deref())
+    .bind(query.limit.unwrap_or(50).max(0))
+    .bind(query.start_index.unwrap_or(0).max(0))
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ScanHistoryResponse {
+        items,
+        total_record_count: total,
+    }))
+}
+
+/// GET /Library/ScanHistory/:scanId - A scan run with the items it added, removed or updated
+async fn get_scan_details(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(scan_id): Path<String>,
+    Query(query): Query<ScanDetailsQuery>,
+) -> Result<Json<ScanDetailsDto>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let scan: ScanHistoryDto = sqlx::query_as("SELECT * FROM scan_history WHERE id = ?")
+        .bind(&scan_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Scan not found".to_string()))?;
+
+    let (total,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM scan_history_items
+         WHERE scan_id = ?1 AND (?2 IS NULL OR change_type = ?2 COLLATE NOCASE)",
+    )
+    .bind(&scan_id)
+    .bind(query.change_type.as_deref())
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let changes: Vec<ScanChangeDto> = sqlx::query_as(
+        "SELECT change_type, item_id, item_type, name, path FROM scan_history_items
+         WHERE scan_id = ?1 AND (?2 IS NULL OR change_type = ?2 COLLATE NOCASE)
+         ORDER BY rowid LIMIT ?3 OFFSET ?4",
+    )
+    .bind(&scan_id)
+    .bind(query.change_type.as_deref())
+    .bind(query.limit.unwrap_or(200).max(0))
+    .bind(query.start_index.unwrap_or(0).max(0))
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ScanDetailsDto {
+        scan,
+        changes,
+        total_change_count: total,
+    }))
+}
+
+// =============================================================================
+// Duplicate episodes
+// =============================================================================
+
+pub fn duplicate_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(get_duplicates))
+}
+
+/// A file that is another copy of an episode, next to the copy that is shown
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct DuplicateEpisodeDto {
+    pub id: String,
+    pub library_id: String,
+    pub series_id: Option<String>,
+    pub series_name: Option<String>,
+    pub parent_index_number: Option<i32>,
+    pub index_number: Option<i32>,
+    pub path: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Version (offered as an alternate source) or Review (hidden)
+    pub state: String,
+    pub primary_id: String,
+    pub primary_path: Option<String>,
+    pub primary_width: Option<i32>,
+    pub primary_height: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DuplicateEpisodesResponse {
+    pub items: Vec<DuplicateEpisodeDto>,
+    pub total_record_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatesQuery {
+    pub library_id: Option<String>,
+    /// Version or Review
+    pub state: Option<String>,
+    pub start_index: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+const DUPLICATES_FROM: &str = "FROM media_items d
+    JOIN media_items p ON p.id = d.version_of
+    LEFT JOIN media_items s ON s.id = d.parent_id
+    WHERE (?1 IS NULL OR d.library_id = ?1) AND (?2 IS NULL OR d.duplicate_state = ?2 COLLATE NOCASE)";
+
+/// GET /Library/Duplicates - Episode files hidden behind a better copy of the same episode
+async fn get_duplicates(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<DuplicatesQuery>,
+) -> Result<Json<DuplicateEpisodesResponse>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let (total,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) {}", DUPLICATES_FROM))
+        .bind(query.library_id.as_deref())
+        .bind(query.state.as_deref())
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let items: Vec<DuplicateEpisodeDto> = sqlx::query_as(&format!(
+        "SELECT d.id, d.library_id, d.parent_id AS series_id, s.name AS series_name,
+                d.parent_index_number, d.index_number, d.path, d.width, d.height,
+                d.duplicate_state AS state, p.id AS primary_id, p.path AS primary_path,
+                p.width AS primary_width, p.height AS primary_height
+         {}
+         ORDER BY s.sort_name, s.name, d.parent_index_number, d.index_number, d.path
+         LIMIT ?3 OFFSET ?4",
+        DUPLICATES_FROM
+    ))
+    .bind(query.library_id.as_deref())
+    .bind(query.state.as_deref())
+    .bind(query.limit.unwrap_or(100).max(0))
+    .bind(query.start_index.unwrap_or(0).max(0))
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(DuplicateEpisodesResponse {
+        items,
+        total_record_count: total,
+    }))
+}
+
+// =============================================================================
+// Playback analytics
+// =============================================================================
+
+pub fn playback_stats_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(get_playback_stats))
+        .route("/:itemId", get(get_item_playback_stats))
+}
+
+/// Playback rollups of one item (services::playback_stats)
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaybackStatsDto {
+    pub item_id: String,
+    pub name: String,
+    pub item_type: String,
+    pub library_id: String,
+    pub series_name: Option<String>,
+    pub runtime_ticks: Option<i64>,
+    pub play_count: i64,
+    /// Playbacks that got far enough to mark the item played
+    pub completed_count: i64,
+    /// Average percentage watched, over playbacks where it is known
+    pub average_completion: Option<f64>,
+    /// Start of the minute where unfinished playbacks most often stopped
+    pub top_drop_off_ticks: Option<i64>,
+    pub last_played_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaybackStatsResponse {
+    pub items: Vec<PlaybackStatsDto>,
+    pub total_record_count: i64,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct DropOffDto {
+    pub position_ticks: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ItemPlaybackStatsDto {
+    #[serde(flatten)]
+    pub stats: PlaybackStatsDto,
+    /// Most common drop-off minutes, most frequent first
+    pub drop_offs: Vec<DropOffDto>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackStatsQuery {
+    pub library_id: Option<String>,
+    pub item_type: Option<String>,
+    /// Only items played at least this often
+    pub min_plays: Option<i64>,
+    /// PlayCount (most played first, default), Completion (least finished
+    /// first) or LastPlayed (most recent first)
+    pub sort_by: Option<String>,
+    pub start_index: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Drop-off minutes listed per item
+const MAX_DROP_OFFS: i64 = 10;
+
+fn playback_stats_select() -> String {
+    format!(
+        "SELECT ps.item_id, m.name, m.item_type, m.library_id, s.name AS series_name,
+                m.runtime_ticks, ps.play_count, ps.completed_count,
+                CASE WHEN ps.completion_samples > 0
+                     THEN ps.completion_sum / ps.completion_samples END AS average_completion,
+                (SELECT d.minute FROM playback_drop_offs d WHERE d.item_id = ps.item_id
+                 ORDER BY d.count DESC, d.minute LIMIT 1) * {} AS top_drop_off_ticks,
+                ps.last_played_at
+         FROM playback_stats ps
+         JOIN media_items m ON m.id = ps.item_id
+         LEFT JOIN media_items s ON s.id = m.parent_id AND m.item_type = 'Episode'",
+        playback_stats::DROP_OFF_BUCKET_TICKS
+    )
+}
+
+const PLAYBACK_STATS_FILTER: &str = "WHERE (?1 IS NULL OR m.library_id = ?1)
+    AND (?2 IS NULL OR m.item_type = ?2 COLLATE NOCASE) AND ps.play_count >= ?3";
+
+/// GET /Library/PlaybackStats - Per-item play counts, average completion and main drop-off point
+async fn get_playback_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<PlaybackStatsQuery>,
+) -> Result<Json<PlaybackStatsResponse>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let order_by = match query.sort_by.as_deref() {
+        Some(s) if s.eq_ignore_ascii_case("Completion") => {
+            "average_completion IS NULL, average_completion, ps.play_count DESC"
+        }
+        Some(s) if s.eq_ignore_ascii_case("LastPlayed") => "ps.last_played_at DESC",
+        _ => "ps.play_count DESC, m.name",
+    };
+    let min_plays = query.min_plays.unwrap_or(1);
+
+    let (total,): (i64,) = sqlx::query_as(&format!(
+        "SELECT COUNT(*) FROM playback_stats ps JOIN media_items m ON m.id = ps.item_id {}",
+        PLAYBACK_STATS_FILTER
+    ))
+    .bind(query.library_id.as_deref())
+    .bind(query.item_type.as_deref())
+    .bind(min_plays)
+    .fetch_one(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let items: Vec<PlaybackStatsDto> = sqlx::query_as(&format!(
+        "{} {} ORDER BY {} LIMIT ?4 OFFSET ?5",
+        playback_stats_select(),
+        PLAYBACK_STATS_FILTER,
+        order_by
+    ))
+    .bind(query.library_id.as_deref())
+    .bind(query.item_type.as_deref())
+    .bind(min_plays)
+    .bind(query.limit.unwrap_or(100).max(0))
+    .bind(query.start_index.unwrap_or(0).max(0))
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(PlaybackStatsResponse {
+        items,
+        total_record_count: total,
+    }))
+}
+
+/// GET /Library/PlaybackStats/:itemId - An item's playback stats with its most common drop-off points
+async fn get_item_playback_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(item_id): Path<String>,
+) -> Result<Json<ItemPlaybackStatsDto>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let stats: PlaybackStatsDto =
+        sqlx::query_as(&format!("{} WHERE ps.item_id = ?", playback_stats_select()))
+            .bind(&item_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or_else(|| {
+                (
+                    StatusCode::NOT_FOUND,
+                    "No playback stats for this item".to_string(),
+                )
+            })?;
+
+    let drop_offs: Vec<DropOffDto> = sqlx::query_as(
+        "SELECT minute * ? AS position_ticks, count FROM playback_drop_offs
+         WHERE item_id = ? ORDER BY count DESC, minute LIMIT ?",
+    )
+    .bind(playback_stats::DROP_OFF_BUCKET_TICKS)
+    .bind(&item_id)
+    .bind(MAX_DROP_OFFS)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(ItemPlaybackStatsDto { stats, drop_offs }))
+}
+
+// =============================================================================
+// Thumbnail generation
+// =============================================================================
+
+pub fn thumbnail_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(get_thumbnail_progress))
+}
+
+/// GET /Library/Thumbnails - Progress of the background thumbnail workers
+async fn get_thumbnail_progress(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<thumbnails::Progress>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    thumbnails::progress(&state.db, &state.config.thumbnails)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export_row(path: &str, probe_output: Option<&str>) -> ExportRow {
+        ExportRow {
+            id: "abc".to_string(),
+            item_type: "Episode".to_string(),
+            name: "Pilot, \"Part 1\"".to_string(),
+            series_name: Some("Show\nName".to_string()),
+            parent_index_number: Some(1),
+            index_number: Some(2),
+            year: None,
+            path: Some(path.to_string()),
+            runtime_ticks: None,
+            tmdb_id: Some("1399".to_string()),
+            imdb_id: None,
+            anilist_id: None,
+            mal_id: None,
+            anidb_id: None,
+            kitsu_id: Some("7".to_string()),
+            play_count: 3,
+            played_users: 1,
+            probe_output: probe_output.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_export_csv_columns_and_escaping() {
+        let probe = r#"{
+            "format": {"duration": "1500", "format_name": "matroska,webm"},
+            "streams": [
+                {"index": 0, "codec_type": "video", "codec_name": "hevc", "width": 1920, "height": 1080},
+                {"index": 1, "codec_type": "audio", "codec_name": "eac3", "channels": 6},
+                {"index": 2, "codec_type": "audio", "codec_name": "aac", "channels": 2}
+            ]
+        }"#;
+        let line =
+            ExportEntry::from_row(export_row("/tv/Show/S01E02.mkv", Some(probe))).to_csv_line();
+        assert_eq!(
+            line,
+            "abc,Episode,\"Show\nName\",1,2,\"Pilot, \"\"Part 1\"\"\",,/tv/Show/S01E02.mkv,\
+             \"matroska,webm\",hevc,eac3|aac,1920x1080,25,3,1,1399,,,,,7\n"
+        );
+
+        // One field per header column, in the header's order
+        let header: Vec<&str> = EXPORT_CSV_HEADER.trim_end().split(',').collect();
+        let mut unprobed = export_row("/tv/Show/S01E03.mkv", None);
+        unprobed.name = "Pilot".to_string();
+        unprobed.series_name = Some("Show".to_string());
+        let line = ExportEntry::from_row(unprobed).to_csv_line();
+        let fields: Vec<&str> = line.trim_end().split(',').collect();
+        assert_eq!(fields.len(), header.len());
+        let column = |name| fields[header.iter().position(|h| *h == name).unwrap()];
+        assert_eq!(column("Path"), "/tv/Show/S01E03.mkv");
+        assert_eq!(column("PlayCount"), "3");
+        assert_eq!(column("KitsuId"), "7");
+        // Files that were never probed leave the codec columns empty
+        for name in [
+            "Container",
+            "VideoCodec",
+            "AudioCodecs",
+            "Resolution",
+            "RuntimeMinutes",
+        ] {
+            assert_eq!(column(name), "", "{}", name);
+        }
+    }
+}
@@ -0,0 +1,534 @@
+// Live TV endpoints - channels and guide from the IPTV tuner (services::live_tv)
+//
+// Channels and programs are TvChannel and Program items; recordings, timers and
+// series timers are listed empty, since the tuner can't record.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{
+    services::{
+        auth,
+        client_capabilities::split_list,
+        live_tv::{self, Channel, Program, ProgramFilter, ProgramKind},
+        server_id,
+    },
+    time::TICKS_PER_SECOND,
+    AppState,
+};
+
+use super::items::{BaseItemDto, ImageTags, ItemsResponse, LiveTvInfo, UserItemDataDto};
+use super::users::parse_emby_auth_header;
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/Info", get(get_info))
+        .route("/GuideInfo", get(get_guide_info))
+        .route("/Channels", get(get_channels))
+        .route("/Channels/:id", get(get_channel))
+        .route("/Programs", get(get_programs))
+        .route("/Programs", post(post_programs))
+        .route("/Programs/Recommended", get(get_recommended_programs))
+        .route("/Programs/:id", get(get_program))
+        .route("/Recordings", get(get_empty_list))
+        .route("/Recordings/Folders", get(get_empty_list))
+        .route("/Recordings/Series", get(get_empty_list))
+        .route("/Timers", get(get_empty_list))
+        .route("/SeriesTimers", get(get_empty_list))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChannelsQuery {
+    #[serde(alias = "startIndex")]
+    pub start_index: Option<i32>,
+    #[serde(alias = "limit")]
+    pub limit: Option<i32>,
+    /// Include the program airing now (default: true)
+    #[serde(alias = "addCurrentProgram")]
+    pub add_current_program: Option<bool>,
+}
+
+/// Channel IDs as a comma-separated query value or a JSON array
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ChannelIds {
+    List(Vec<String>),
+    Joined(String),
+}
+
+/// Filters of GET /LiveTv/Programs (query) and POST /LiveTv/Programs (GetProgramsDto)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProgramsQuery {
+    #[serde(alias = "channelIds")]
+    pub channel_ids: Option<ChannelIds>,
+    #[serde(alias = "minStartDate")]
+    pub min_start_date: Option<String>,
+    #[serde(alias = "maxStartDate")]
+    pub max_start_date: Option<String>,
+    #[serde(alias = "minEndDate")]
+    pub min_end_date: Option<String>,
+    #[serde(alias = "maxEndDate")]
+    pub max_end_date: Option<String>,
+    #[serde(alias = "isAiring")]
+    pub is_airing: Option<bool>,
+    #[serde(alias = "hasAired")]
+    pub has_aired: Option<bool>,
+    #[serde(alias = "isMovie")]
+    pub is_movie: Option<bool>,
+    #[serde(alias = "isSeries")]
+    pub is_series: Option<bool>,
+    #[serde(alias = "isNews")]
+    pub is_news: Option<bool>,
+    #[serde(alias = "isKids")]
+    pub is_kids: Option<bool>,
+    #[serde(alias = "isSports")]
+    pub is_sports: Option<bool>,
+    #[serde(alias = "startIndex")]
+    pub start_index: Option<i32>,
+    #[serde(alias = "limit")]
+    pub limit: Option<i32>,
+}
+
+impl ProgramsQuery {
+    fn filter(&self) -> Result<ProgramFilter, (StatusCode, String)> {
+        let date = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(|v| {
+                    live_tv::parse_guide_time(v)
+                        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Invalid date: {}", v)))
+                })
+                .transpose()
+        };
+        let channel_ids = match &self.channel_ids {
+            Some(ChannelIds::List(ids)) => ids.clone(),
+            Some(ChannelIds::Joined(ids)) => split_list(Some(ids)),
+            None => Vec::new(),
+        };
+        // A series is anything that isn't a movie
+        let kinds = [
+            (
+                ProgramKind::Movie,
+                self.is_movie.or(self.is_series.map(|s| !s)),
+            ),
+            (ProgramKind::News, self.is_news),
+            (ProgramKind::Kids, self.is_kids),
+            (ProgramKind::Sports, self.is_sports),
+        ]
+        .into_iter()
+        .filter_map(|(kind, wanted)| Some((kind, wanted?)))
+        .collect();
+
+        Ok(ProgramFilter {
+            channel_ids,
+            min_start_date: date(&self.min_start_date)?,
+            max_start_date: date(&self.max_start_date)?,
+            min_end_date: date(&self.min_end_date)?,
+            max_end_date: date(&self.max_end_date)?,
+            is_airing: self.is_airing,
+            has_aired: self.has_aired,
+            kinds,
+        })
+    }
+}
+
+/// A tuner in GET /LiveTv/Info (LiveTvServiceInfo)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LiveTvServiceInfo {
+    pub name: String,
+    pub home_page_url: String,
+    /// Ok or Unavailable
+    pub status: String,
+    pub status_message: String,
+    pub version: String,
+    pub has_update_available: bool,
+    pub is_visible: bool,
+    pub tuners: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LiveTvInfoResponse {
+    pub services: Vec<LiveTvServiceInfo>,
+    pub is_enabled: bool,
+    pub enabled_users: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GuideInfoResponse {
+    pub start_date: String,
+    pub end_date: String,
+}
+
+async fn require_auth(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<crate::models::User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+/// A TvChannel or Program item
+fn live_tv_item(
+    id: String,
+    name: String,
+    item_type: &str,
+    image_url: Option<&str>,
+    info: LiveTvInfo,
+) -> BaseItemDto {
+    BaseItemDto {
+        id,
+        sort_name: Some(name.clone()),
+        name,
+        item_type: item_type.to_string(),
+        server_id: server_id::get().to_string(),
+        parent_id: None,
+        overview: None,
+        year: None,
+        production_year: None,
+        index_number: None,
+        index_number_end: None,
+        parent_index_number: None,
+        runtime_ticks: None,
+        community_rating: None,
+        official_rating: None,
+        path: None,
+        premiere_date: None,
+        series_id: None,
+        series_name: None,
+        season_id: None,
+        season_name: None,
+        music: None,
+        live_tv: Some(info),
+        is_folder: false,
+        child_count: None,
+        media_type: Some("Video".to_string()),
+        collection_type: None,
+        user_data: UserItemDataDto::default(),
+        image_tags: image_url.map(|url| ImageTags {
+            primary: Some(live_tv::image_tag(url)),
+            backdrop: None,
+            backdrop_count: 0,
+        }),
+        backdrop_image_tags: Vec::new(),
+        provider_ids: None,
+        media_sources: None,
+        width: None,
+        height: None,
+        is_hd: None,
+        is_4k: None,
+        can_download: false,
+        supports_media_source_display: false,
+    }
+}
+
+/// The TvChannel item of a channel, with the program airing on it now
+pub(crate) fn channel_to_dto(channel: &Channel, current: Option<&Program>) -> BaseItemDto {
+    live_tv_item(
+        channel.id.clone(),
+        channel.name.clone(),
+        "TvChannel",
+        channel.logo_url.as_deref(),
+        LiveTvInfo {
+            channel_type: Some("TV".to_string()),
+            number: channel.number.clone(),
+            channel_number: channel.number.clone(),
+            current_program: current.map(|p| Box::new(program_to_dto(p, Some(channel)))),
+            ..Default::default()
+        },
+    )
+}
+
+/// The Program item of a guide entry
+pub(crate) fn program_to_dto(program: &Program, channel: Option<&Channel>) -> BaseItemDto {
+    let category = program.category.as_deref();
+    let is = |kind: ProgramKind| Some(kind.matches(category));
+    let is_movie = ProgramKind::Movie.matches(category);
+    let mut dto = live_tv_item(
+        program.id.clone(),
+        program.title.clone(),
+        "Program",
+        program.image_url.as_deref(),
+        LiveTvInfo {
+            channel_id: Some(program.channel_id.clone()),
+            channel_name: channel.map(|c| c.name.clone()),
+            channel_primary_image_tag: channel
+                .and_then(|c| c.logo_url.as_deref())
+                .map(live_tv::image_tag),
+            start_date: Some(program.start_date.clone()),
+            end_date: Some(program.end_date.clone()),
+            episode_title: program.episode_title.clone(),
+            genres: program.category.iter().cloned().collect(),
+            is_movie: Some(is_movie),
+            is_series: Some(!is_movie),
+            is_news: is(ProgramKind::News),
+            is_kids: is(ProgramKind::Kids),
+            is_sports: is(ProgramKind::Sports),
+            ..Default::default()
+        },
+    );
+    dto.overview = program.overview.clone();
+    dto.runtime_ticks = program_seconds(program).map(|s| s * TICKS_PER_SECOND);
+    dto
+}
+
+fn program_seconds(program: &Program) -> Option<i64> {
+    let time = |t: &str| DateTime::parse_from_rfc3339(t).ok();
+    Some((time(&program.end_date)? - time(&program.start_date)?).num_seconds())
+}
+
+fn internal_error(e: anyhow::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// GET /LiveTv/Info - The tuner and whether Live TV is set up
+async fn get_info(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<LiveTvInfoResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+
+    let is_enabled = state.config.live_tv.is_enabled();
+    let has_channels = live_tv::has_channels(&state.db)
+        .await
+        .map_err(internal_error)?;
+    let services = if is_enabled {
+        vec![LiveTvServiceInfo {
+            name: "M3U Tuner".to_string(),
+            home_page_url: String::new(),
+            status: if has_channels { "Ok" } else { "Unavailable" }.to_string(),
+            status_message: if has_channels {
+                String::new()
+            } else {
+                "No channels yet; run the Refresh Guide task".to_string()
+            },
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            has_update_available: false,
+            is_visible: true,
+            tuners: Vec::new(),
+        }]
+    } else {
+        Vec::new()
+    };
+
+    Ok(Json(LiveTvInfoResponse {
+        services,
+        is_enabled,
+        enabled_users: Vec::new(),
+    }))
+}
+
+/// GET /LiveTv/GuideInfo - The time span the guide covers
+async fn get_guide_info(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<GuideInfoResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+
+    let now = live_tv::guide_time(Utc::now());
+    let last_end: Option<String> = sqlx::query_scalar("SELECT MAX(end_date) FROM programs")
+        .fetch_one(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(GuideInfoResponse {
+        end_date: last_end.filter(|end| *end > now).unwrap_or(now.clone()),
+        start_date: now,
+    }))
+}
+
+/// GET /LiveTv/Channels - Channels in playlist order, with what's on now
+async fn get_channels(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ChannelsQuery>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+
+    let channels = live_tv::list_channels(&state.db)
+        .await
+        .map_err(internal_error)?;
+    let current = if query.add_current_program != Some(false) {
+        live_tv::current_programs(&state.db)
+            .await
+            .map_err(internal_error)?
+    } else {
+        HashMap::new()
+    };
+
+    let start_index = query.start_index.unwrap_or(0).max(0);
+    let items = channels
+        .iter()
+        .skip(start_index as usize)
+        .take(query.limit.map_or(usize::MAX, |l| l.max(0) as usize))
+        .map(|channel| channel_to_dto(channel, current.get(&channel.id)))
+        .collect();
+
+    Ok(Json(ItemsResponse {
+        items,
+        total_record_count: channels.len() as i32,
+        start_index,
+    }))
+}
+
+/// GET /LiveTv/Channels/{id}
+async fn get_channel(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+
+    let channel = live_tv::find_channel(&state.db, &id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Channel not found".to_string()))?;
+    let current = live_tv::current_programs(&state.db)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(channel_to_dto(&channel, current.get(&channel.id))))
+}
+
+/// The TvChannel or Program item with this ID, for GET /Items/{id}
+pub(crate) async fn find_item(
+    state: &AppState,
+    id: &str,
+) -> Result<Option<BaseItemDto>, (StatusCode, String)> {
+    if let Some(channel) = live_tv::find_channel(&state.db, id)
+        .await
+        .map_err(internal_error)?
+    {
+        let current = live_tv::current_programs(&state.db)
+            .await
+            .map_err(internal_error)?;
+        return Ok(Some(channel_to_dto(&channel, current.get(&channel.id))));
+    }
+    let Some(program) = live_tv::find_program(&state.db, id)
+        .await
+        .map_err(internal_error)?
+    else {
+        return Ok(None);
+    };
+    let channel = live_tv::find_channel(&state.db, &program.channel_id)
+        .await
+        .map_err(internal_error)?;
+    Ok(Some(program_to_dto(&program, channel.as_ref())))
+}
+
+/// Programs matching a filter, as Program items
+async fn list_programs(
+    state: &AppState,
+    filter: &ProgramFilter,
+    start_index: Option<i32>,
+    limit: Option<i32>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    let start_index = start_index.unwrap_or(0).max(0);
+    let (programs, total) = live_tv::list_programs(
+        &state.db,
+        filter,
+        start_index as i64,
+        limit.map(|l| l.max(0) as i64),
+    )
+    .await
+    .map_err(internal_error)?;
+    let channels: HashMap<String, Channel> = live_tv::list_channels(&state.db)
+        .await
+        .map_err(internal_error)?
+        .into_iter()
+        .map(|c| (c.id.clone(), c))
+        .collect();
+
+    Ok(Json(ItemsResponse {
+        items: programs
+            .iter()
+            .map(|p| program_to_dto(p, channels.get(&p.channel_id)))
+            .collect(),
+        total_record_count: total as i32,
+        start_index,
+    }))
+}
+
+/// GET /LiveTv/Programs - Guide entries by channel and time
+async fn get_programs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ProgramsQuery>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+    list_programs(&state, &query.filter()?, query.start_index, query.limit).await
+}
+
+/// POST /LiveTv/Programs - The same, with the filters in the body (the guide
+/// asks for many channels at once)
+async fn post_programs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    body: Option<Json<ProgramsQuery>>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+    let query = body.map(|Json(body)| body).unwrap_or_default();
+    list_programs(&state, &query.filter()?, query.start_index, query.limit).await
+}
+
+/// GET /LiveTv/Programs/Recommended - Programs yet to end, soonest first
+async fn get_recommended_programs(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ProgramsQuery>,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+    let mut filter = query.filter()?;
+    filter.has_aired = filter.has_aired.or(Some(false));
+    list_programs(&state, &filter, query.start_index, query.limit).await
+}
+
+/// GET /LiveTv/Programs/{id}
+async fn get_program(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+
+    let program = live_tv::find_program(&state.db, &id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Program not found".to_string()))?;
+    let channel = live_tv::find_channel(&state.db, &program.channel_id)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(program_to_dto(&program, channel.as_ref())))
+}
+
+/// GET /LiveTv/Recordings, /Timers, ... - Nothing: the tuner can't record
+async fn get_empty_list(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
+    require_auth(&state, &headers).await?;
+    Ok(Json(ItemsResponse {
+        items: Vec::new(),
+        total_record_count: 0,
+        start_index: 0,
+    }))
+}
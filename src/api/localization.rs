@@ -7,7 +7,10 @@ use axum::{
 use serde::Serialize;
 use std::sync::Arc;
 
-use crate::{services::auth, AppState};
+use crate::{
+    services::{auth, localization, parental_controls},
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -24,8 +27,14 @@ pub fn routes() -> Router<Arc<AppState>> {
 pub struct CultureDto {
     pub name: String,
     pub display_name: String,
+    // PascalCase alone would write "Iso"
+    #[serde(rename = "TwoLetterISOLanguageName")]
     pub two_letter_iso_language_name: String,
+    #[serde(rename = "ThreeLetterISOLanguageName")]
     pub three_letter_iso_language_name: String,
+    /// Bibliographic and terminology codes ("ger", "deu")
+    #[serde(rename = "ThreeLetterISOLanguageNames")]
+    pub three_letter_iso_language_names: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -71,31 +80,22 @@ async fn get_cultures(
 ) -> Result<Json<Vec<CultureDto>>, (StatusCode, String)> {
     let _user = require_auth(&state, &headers).await?;
 
-    let cultures = [
-        ("en-US", "English (United States)", "en", "eng"),
-        ("en-GB", "English (United Kingdom)", "en", "eng"),
-        ("ja-JP", "Japanese (Japan)", "ja", "jpn"),
-        ("zh-CN", "Chinese (Simplified)", "zh", "zho"),
-        ("zh-TW", "Chinese (Traditional)", "zh", "zho"),
-        ("ko-KR", "Korean (Korea)", "ko", "kor"),
-        ("de-DE", "German (Germany)", "de", "deu"),
-        ("fr-FR", "French (France)", "fr", "fra"),
-        ("es-ES", "Spanish (Spain)", "es", "spa"),
-        ("pt-BR", "Portuguese (Brazil)", "pt", "por"),
-        ("it-IT", "Italian (Italy)", "it", "ita"),
-        ("ru-RU", "Russian (Russia)", "ru", "rus"),
-        ("nl-NL", "Dutch (Netherlands)", "nl", "nld"),
-        ("pl-PL", "Polish (Poland)", "pl", "pol"),
-        ("sv-SE", "Swedish (Sweden)", "sv", "swe"),
-    ]
-    .into_iter()
-    .map(|(name, display, iso2, iso3)| CultureDto {
-        name: name.to_string(),
-        display_name: display.to_string(),
-        two_letter_iso_language_name: iso2.to_string(),
-        three_letter_iso_language_name: iso3.to_string(),
-    })
-    .collect();
+    // The codes stream languages are normalized to, so clients' language
+    // preferences match them
+    let cultures = localization::LANGUAGES
+        .iter()
+        .filter(|lang| !lang.two_letter.is_empty())
+        .map(|lang| CultureDto {
+            name: lang.name.to_string(),
+            display_name: lang.name.to_string(),
+            two_letter_iso_language_name: lang.two_letter.to_string(),
+            three_letter_iso_language_name: lang.three_letter.to_string(),
+            three_letter_iso_language_names: std::iter::once(lang.three_letter)
+                .chain(lang.terminology)
+                .map(str::to_string)
+                .collect(),
+        })
+        .collect();
 
     Ok(Json(cultures))
 }
@@ -146,25 +146,13 @@ async fn get_parental_ratings(
 ) -> Result<Json<Vec<ParentalRatingDto>>, (StatusCode, String)> {
     let _user = require_auth(&state, &headers).await?;
 
-    let ratings = [
-        ("G", 0),
-        ("PG", 10),
-        ("PG-13", 13),
-        ("R", 17),
-        ("NC-17", 18),
-        ("TV-Y", 0),
-        ("TV-Y7", 7),
-        ("TV-G", 0),
-        ("TV-PG", 10),
-        ("TV-14", 14),
-        ("TV-MA", 17),
-    ]
-    .into_iter()
-    .map(|(name, value)| ParentalRatingDto {
-        name: name.to_string(),
-        value,
-    })
-    .collect();
+    let ratings = parental_controls::RATINGS
+        .iter()
+        .map(|(name, value)| ParentalRatingDto {
+            name: name.to_string(),
+            value: *value,
+        })
+        .collect();
 
     Ok(Json(ratings))
 }
@@ -1,17 +1,26 @@
-use axum::Router;
+use axum::{
+    http::{Method, StatusCode, Uri},
+    Router,
+};
 use std::sync::Arc;
 
 use crate::AppState;
 
+mod artists;
+mod audio;
+mod blocks;
 mod branding;
 mod collections;
 mod display_preferences;
+mod dlna;
 mod favorites;
 pub mod filters;
 mod home;
 mod images;
+mod invites;
 mod items;
 mod library;
+mod live_tv;
 mod localization;
 mod movies;
 mod persons;
@@ -20,6 +29,7 @@ mod playbackinfo;
 mod playlists;
 pub mod segments;
 pub mod sessions;
+mod share;
 mod shows;
 mod stubs;
 mod subtitles;
@@ -28,22 +38,45 @@ mod tasks;
 mod users;
 mod videos;
 mod views;
+mod watch_import;
+mod webhooks;
+
+pub use home::run_home_cache;
+pub use users::impersonate;
+
+/// Fallback for requests no route matches
+///
+/// Paths that match a route with other methods never get here: axum answers
+/// those with 405 and an Allow header, and serves HEAD from the GET handler
+/// (without the body). Logged so probes for unimplemented endpoints show up.
+pub async fn not_found(method: Method, uri: Uri) -> (StatusCode, &'static str) {
+    tracing::debug!("No route for {} {}", method, uri.path());
+    (StatusCode::NOT_FOUND, "Not found")
+}
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .nest("/System", system::routes())
         .nest("/Branding", branding::routes())
         .nest("/Users", users::routes())
+        .nest("/Users/Invites", invites::routes()) // Invite codes for self-registration
         .nest("/Library/VirtualFolders", library::routes())
+        .nest("/Library/ScanHistory", library::scan_history_routes()) // What each scan changed
+        .nest("/Library/Duplicates", library::duplicate_routes()) // Episodes found in several files
+        .nest("/Library/PlaybackStats", library::playback_stats_routes()) // Per-item completion and drop-off
+        .nest("/Library/Thumbnails", library::thumbnail_routes()) // Background thumbnail progress
         .nest("/Items", items::routes())
         .nest("/Items", images::routes()) // Image routes under /Items/:id/Images
+        .nest("/Images", images::remote_routes()) // Provider image proxy
         .nest("/Items", playbackinfo::routes()) // PlaybackInfo under /Items/:id/PlaybackInfo
         .nest("/Items", subtitles::search_routes()) // Subtitle search under /Items/:id/RemoteSearch/Subtitles
         .nest("/Search", items::search_routes()) // Search hints
         .nest("/Videos", videos::routes())
         .nest("/Videos", subtitles::routes()) // Subtitle routes under /Videos/:id/:id/Subtitles
+        .nest("/Audio", audio::routes()) // Audio-only streaming (direct or mp3/aac/opus transcode)
         .nest("/Sessions", sessions::routes()) // Active session management
         .nest("/Sessions", playback::routes()) // Playback reporting (Playing, Progress, Stopped)
+        .nest("/socket", sessions::socket_routes()) // Client WebSocket (remote control, messages)
         .nest("/Shows", shows::routes()) // Shows endpoints (Seasons, Episodes)
         .nest("/Shows/NextUp", home::next_up_routes()) // NextUp endpoint
         .nest("/Movies", movies::routes()) // Movie recommendations
@@ -55,8 +88,15 @@ pub fn routes() -> Router<Arc<AppState>> {
         .nest("/Collections", collections::routes()) // Collections API
         .nest("/Playlists", playlists::routes()) // Playlists API
         .nest("/Persons", persons::routes()) // Cast/actors API
+        .nest("/LiveTv", live_tv::routes()) // IPTV channels and guide
+        .nest("/Artists", artists::routes()) // Music artists
         .nest("/Localization", localization::routes()) // Cultures/languages API
         .nest("/MediaSegments", segments::routes()) // Media segments (intro/outro skip)
+        .nest("/Episode", segments::intro_skipper_routes()) // Intro Skipper plugin API shape
+        .nest("/Webhooks", webhooks::routes()) // Sonarr/Radarr import notifications
+        .nest("/ShareLinks", share::routes()) // Manage share links
+        .nest("/Share", share::public_routes()) // Shared item player and stream (no account)
+        .nest("/dlna", dlna::routes()) // DLNA clients (no account, local network only)
         // Jellyfin clients also query /Users/{userId}/Items
         .route(
             "/Users/:userId/Items",
@@ -66,12 +106,47 @@ pub fn routes() -> Router<Arc<AppState>> {
             "/Users/:userId/Items/:itemId",
             axum::routing::get(items::get_user_item),
         )
+        // Item lookup by file path for external tools
+        .route(
+            "/Library/ItemByPath",
+            axum::routing::get(items::get_item_by_path),
+        )
+        // Library inventory export (CSV/JSON download)
+        .route(
+            "/Library/:libraryId/Export",
+            axum::routing::get(library::export_library),
+        )
+        // On-demand scans of every library or one library
+        .route(
+            "/Library/Refresh",
+            axum::routing::post(library::refresh_all_libraries),
+        )
+        .route(
+            "/Library/:libraryId/Scan",
+            axum::routing::post(library::scan_library),
+        )
+        // Re-download provider artwork for a library
+        .route(
+            "/Library/:libraryId/RefreshImages",
+            axum::routing::post(library::refresh_library_images),
+        )
+        // Share a movie or episode through an expiring link
+        .route(
+            "/Items/:itemId/Share",
+            axum::routing::post(share::create_share_link),
+        )
         // User latest items for home screen
         .nest("/Users/:userId/Items/Latest", home::user_latest_routes())
+        // Personalized suggestions from watch history
+        .nest("/Users/:userId/Suggestions", home::suggestion_routes())
         // User images
         .nest("/Users/:userId/Images", users::user_image_routes())
         // User played items (mark as played/unplayed)
         .nest("/Users/:userId/PlayedItems", playback::user_played_routes())
+        // Per-user hidden items, genres and tags
+        .nest("/Users/:userId/ItemBlocks", blocks::routes())
+        // Watch state import from Plex/Kodi
+        .nest("/Users/:userId/WatchStateImport", watch_import::routes())
         // User favorites
         .nest("/UserFavoriteItems", favorites::routes())
         // Genres and Studios endpoints
@@ -9,9 +9,13 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::{
+    models::MediaItem,
+    services::{auth, server_id},
+    AppState,
+};
 
-use super::items::{BaseItemDto, ImageTags, UserItemDataDto};
+use super::items::{is_4k_resolution, is_hd_resolution, BaseItemDto, ImageTags, UserItemDataDto};
 use super::users::parse_emby_auth_header;
 
 pub fn routes() -> Router<Arc<AppState>> {
@@ -106,6 +110,7 @@ async fn get_recommendations(
              INNER JOIN item_genres ig ON m.id = ig.item_id
              INNER JOIN genres g ON ig.genre_id = g.id
              WHERE m.item_type = 'Movie' AND m.id != ? AND g.name IN ({})
+             AND m.id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)
              ORDER BY m.community_rating DESC NULLS LAST
              LIMIT ?",
             placeholders.join(",")
@@ -115,7 +120,7 @@ async fn get_recommendations(
         for genre in &genre_names {
             query_builder = query_builder.bind(genre);
         }
-        query_builder = query_builder.bind(item_limit);
+        query_builder = query_builder.bind(&user.id).bind(item_limit);
 
         let similar: Vec<MediaItem> = query_builder.fetch_all(&state.db).await.unwrap_or_default();
 
@@ -176,6 +181,7 @@ async fn get_recommendations(
                  INNER JOIN genres g ON ig.genre_id = g.id
                  WHERE m.item_type = 'Movie' AND m.id != ? AND g.name IN ({})
                  AND m.id NOT IN (SELECT item_id FROM playback_progress WHERE user_id = ? AND played = 1)
+                 AND m.id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)
                  ORDER BY m.community_rating DESC NULLS LAST
                  LIMIT ?",
                 placeholders.join(",")
@@ -185,7 +191,7 @@ async fn get_recommendations(
             for genre in &genre_names {
                 query_builder = query_builder.bind(genre);
             }
-            query_builder = query_builder.bind(&user.id).bind(item_limit);
+            query_builder = query_builder.bind(&user.id).bind(&user.id).bind(item_limit);
 
             let similar: Vec<MediaItem> =
                 query_builder.fetch_all(&state.db).await.unwrap_or_default();
@@ -227,11 +233,13 @@ async fn get_recommendations(
                  INNER JOIN item_genres ig ON m.id = ig.item_id
                  WHERE ig.genre_id = ? AND m.item_type = 'Movie'
                  AND m.id NOT IN (SELECT item_id FROM playback_progress WHERE user_id = ? AND played = 1)
+                 AND m.id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)
                  ORDER BY m.community_rating DESC NULLS LAST
                  LIMIT ?",
             )
             .bind(&genre_id)
             .bind(&user.id)
+            .bind(&user.id)
             .bind(item_limit)
             .fetch_all(&state.db)
             .await
@@ -277,15 +285,17 @@ async fn convert_to_dtos(state: &AppState, items: &[MediaItem], user_id: &str) -
             id: item.id.clone(),
             name: item.name.clone(),
             item_type: item.item_type.clone(),
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             parent_id: item.parent_id.clone(),
             overview: item.overview.clone(),
             year: item.year,
             production_year: item.year,
-            index_number: item.index_number,
-            parent_index_number: item.parent_index_number,
+            index_number: item.index_number_for_display(),
+            index_number_end: item.index_number_end_for_display(),
+            parent_index_number: item.parent_index_number_for_display(),
             runtime_ticks: item.runtime_ticks,
             community_rating: item.community_rating,
+            official_rating: item.official_rating.clone(),
             path: item.path.clone(),
             premiere_date: item.premiere_date.clone(),
             sort_name: item.sort_name.clone(),
@@ -293,14 +303,21 @@ async fn convert_to_dtos(state: &AppState, items: &[MediaItem], user_id: &str) -
             series_name: None,
             season_id: None,
             season_name: None,
+            music: None,
+            live_tv: None,
             is_folder,
             child_count: None,
             media_type,
             collection_type: None,
             user_data,
+            backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
             image_tags,
             provider_ids: None,
             media_sources: None,
+            width: item.width,
+            height: item.height,
+            is_hd: is_hd_resolution(item.width, item.height),
+            is_4k: is_4k_resolution(item.width, item.height),
             can_download: item.path.is_some(),
             supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
         });
@@ -325,7 +342,10 @@ async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Opti
     for (image_type,) in images {
         match image_type.as_str() {
             "Primary" => tags.primary = Some(item_id.to_string()),
-            "Backdrop" => tags.backdrop = Some(item_id.to_string()),
+            "Backdrop" => {
+                tags.backdrop = Some(item_id.to_string());
+                tags.backdrop_count += 1;
+            }
             _ => {}
         }
     }
@@ -11,7 +11,10 @@ use std::sync::Arc;
 use tokio::fs::File;
 use tokio_util::io::ReaderStream;
 
-use crate::{services::auth, AppState};
+use crate::{
+    services::{auth, server_id},
+    AppState,
+};
 
 use super::items::UserItemDataDto;
 use super::users::parse_emby_auth_header;
@@ -191,7 +194,7 @@ fn person_row_to_dto(row: PersonRow) -> PersonDto {
         id: row.id.clone(),
         name: row.name,
         item_type: "Person".to_string(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         role: row.role,
         primary_image_tag: if has_image {
             Some(row.id.clone())
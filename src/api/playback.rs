@@ -7,10 +7,14 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{services::auth, AppState};
+use crate::events::{self, ServerEvent};
+use crate::{
+    services::{auth, playstate},
+    AppState,
+};
 
 use super::sessions;
-use super::users::parse_emby_auth_header;
+use super::users::{parse_client_version, parse_emby_auth_header};
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -22,6 +26,8 @@ pub fn routes() -> Router<Arc<AppState>> {
 
 pub fn user_played_routes() -> Router<Arc<AppState>> {
     Router::new()
+        .route("/", post(bulk_mark_played))
+        .route("/", delete(bulk_mark_unplayed))
         .route("/:itemId", post(mark_played))
         .route("/:itemId", delete(mark_unplayed))
 }
@@ -133,7 +139,16 @@ async fn on_playback_start(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Update active session
+    // Update active session (touched first to record the client version)
+    let _ = sessions::touch_session(
+        &state.db,
+        &user.id,
+        &device_id,
+        &device_name,
+        &client,
+        parse_client_version(&headers).as_deref(),
+    )
+    .await;
     let _ = sessions::update_session_playback(
         &state.db,
         &user.id,
@@ -145,6 +160,12 @@ async fn on_playback_start(
     )
     .await;
 
+    events::publish(ServerEvent::PlaybackStarted {
+        user_id: user.id,
+        item_id: info.item_id,
+        device_id,
+    });
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -164,7 +185,6 @@ async fn on_playback_progress(
         None,
     ));
 
-    let now = chrono::Utc::now().to_rfc3339();
     let is_paused = info.is_paused.unwrap_or(false);
 
     tracing::debug!(
@@ -174,29 +194,15 @@ async fn on_playback_progress(
         info.position_ticks
     );
 
-    // Update position
-    sqlx::query(
-        r#"
-        INSERT INTO playback_progress (user_id, item_id, position_ticks, last_played)
-        VALUES (?, ?, ?, ?)
-        ON CONFLICT (user_id, item_id) DO UPDATE SET
-            position_ticks = excluded.position_ticks,
-            last_played = excluded.last_played
-        "#,
-    )
-    .bind(&user.id)
-    .bind(&info.item_id)
-    .bind(info.position_ticks)
-    .bind(&now)
-    .execute(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    // Written with the next flush (services::playstate)
+    playstate::record_progress(&user.id, &info.item_id, info.position_ticks);
 
     // Update active session
     let _ = sessions::update_session_progress(
         &state.db,
         &user.id,
         &device_id,
+        &info.item_id,
         info.position_ticks,
         is_paused,
     )
@@ -221,8 +227,6 @@ async fn on_playback_stopped(
         None,
     ));
 
-    let now = chrono::Utc::now().to_rfc3339();
-
     tracing::info!(
         "Playback stopped: user={}, item={}, position={}",
         user.id,
@@ -230,22 +234,49 @@ async fn on_playback_stopped(
         info.position_ticks
     );
 
-    // Get the media item to check runtime for auto-marking as played
-    let runtime: Option<(Option<i64>,)> =
-        sqlx::query_as("SELECT runtime_ticks FROM media_items WHERE id = ?")
-            .bind(&info.item_id)
-            .fetch_optional(&state.db)
+    let should_mark_played =
+        finalize_playback(&state.db, &user.id, &info.item_id, info.position_ticks)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Check if we should mark as played (watched > 90% of content)
+    // Clear session playback state
+    let _ = sessions::clear_session_playback(&state.db, &user.id, &device_id).await;
+
+    events::publish(ServerEvent::PlaybackStopped {
+        user_id: user.id,
+        item_id: info.item_id,
+        position_ticks: info.position_ticks,
+        played: should_mark_played,
+    });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Record the final position of a playback, marking the item played (and
+/// counting the play) when more than 90% was watched; returns whether it was
+/// marked played
+pub(crate) async fn finalize_playback(
+    pool: &sqlx::SqlitePool,
+    user_id: &str,
+    item_id: &str,
+    position_ticks: i64,
+) -> anyhow::Result<bool> {
+    let now = chrono::Utc::now().to_rfc3339();
+    // This write is newer than any position still waiting to be flushed
+    playstate::discard(user_id, item_id);
+
+    let runtime: Option<(Option<i64>,)> =
+        sqlx::query_as("SELECT runtime_ticks FROM media_items WHERE id = ?")
+            .bind(item_id)
+            .fetch_optional(pool)
+            .await?;
+
     let should_mark_played = if let Some((Some(runtime_ticks),)) = runtime {
-        runtime_ticks > 0 && info.position_ticks > (runtime_ticks * 90 / 100)
+        runtime_ticks > 0 && position_ticks > (runtime_ticks * 90 / 100)
     } else {
         false
     };
 
-    // Update progress
     sqlx::query(
         r#"
         INSERT INTO playback_progress (user_id, item_id, position_ticks, played, play_count, last_played)
@@ -257,21 +288,17 @@ async fn on_playback_stopped(
             last_played = excluded.last_played
         "#,
     )
-    .bind(&user.id)
-    .bind(&info.item_id)
-    .bind(if should_mark_played { 0 } else { info.position_ticks }) // Reset to 0 if played
+    .bind(user_id)
+    .bind(item_id)
+    .bind(if should_mark_played { 0 } else { position_ticks }) // Reset to 0 if played
     .bind(should_mark_played)
     .bind(&now)
     .bind(should_mark_played)
     .bind(should_mark_played)
-    .execute(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    .execute(pool)
+    .await?;
 
-    // Clear session playback state
-    let _ = sessions::clear_session_playback(&state.db, &user.id, &device_id).await;
-
-    Ok(StatusCode::NO_CONTENT)
+    Ok(should_mark_played)
 }
 
 /// POST /Sessions/Logout - End the current session
@@ -281,10 +308,8 @@ async fn logout(
 ) -> Result<StatusCode, (StatusCode, String)> {
     // Try to get the token and delete the session
     if let Some((_, _, _, Some(token))) = parse_emby_auth_header(&headers) {
-        // Delete the session from database
-        sqlx::query("DELETE FROM sessions WHERE token = ?")
-            .bind(&token)
-            .execute(&state.db)
+        // Delete the session from database (and the auth cache)
+        auth::revoke_session(&state.db, &token)
             .await
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -331,6 +356,11 @@ async fn mark_played(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    events::publish(ServerEvent::UserDataChanged {
+        user_id: user_id.clone(),
+        item_ids: vec![item_id.clone()],
+    });
+
     // Return updated user data
     let progress = get_user_item_data(&state, &user_id, &item_id).await?;
     Ok(Json(progress))
@@ -369,11 +399,157 @@ async fn mark_unplayed(
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    events::publish(ServerEvent::UserDataChanged {
+        user_id: user_id.clone(),
+        item_ids: vec![item_id.clone()],
+    });
+
     // Return updated user data
     let progress = get_user_item_data(&state, &user_id, &item_id).await?;
     Ok(Json(progress))
 }
 
+/// Maximum number of items accepted by a single bulk request
+pub const MAX_BULK_ITEMS: usize = 500;
+
+/// Request body for bulk played/favorite changes (client multi-select)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BulkItemsRequest {
+    pub item_ids: Vec<String>,
+}
+
+impl BulkItemsRequest {
+    /// De-duplicated item IDs, rejecting empty or oversized requests
+    pub fn validated_ids(self) -> Result<Vec<String>, (StatusCode, String)> {
+        let mut ids = self.item_ids;
+        ids.sort();
+        ids.dedup();
+        ids.retain(|id| !id.is_empty());
+
+        if ids.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "No item IDs given".to_string()));
+        }
+        if ids.len() > MAX_BULK_ITEMS {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("At most {} items per request", MAX_BULK_ITEMS),
+            ));
+        }
+        Ok(ids)
+    }
+}
+
+/// Apply a played/unplayed change to many items in one statement
+///
+/// Unknown item IDs are skipped. Returns the IDs that were updated and
+/// publishes a single UserDataChanged event for all of them.
+async fn set_played_bulk(
+    state: &AppState,
+    user_id: &str,
+    item_ids: &[String],
+    played: bool,
+) -> Result<Vec<String>, (StatusCode, String)> {
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let mut qb: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(
+        "INSERT INTO playback_progress (user_id, item_id, position_ticks, played, play_count, last_played) SELECT ",
+    );
+    qb.push_bind(user_id);
+    if played {
+        qb.push(", id, 0, 1, 1, ");
+        qb.push_bind(&now);
+    } else {
+        qb.push(", id, 0, 0, 0, NULL");
+    }
+    qb.push(" FROM media_items WHERE id IN (");
+    let mut separated = qb.separated(", ");
+    for id in item_ids {
+        separated.push_bind(id);
+    }
+    separated.push_unseparated(")");
+    if played {
+        qb.push(
+            " ON CONFLICT (user_id, item_id) DO UPDATE SET \
+             position_ticks = 0, played = 1, play_count = play_count + 1, \
+             last_played = excluded.last_played",
+        );
+    } else {
+        qb.push(
+            " ON CONFLICT (user_id, item_id) DO UPDATE SET \
+             position_ticks = 0, played = 0, play_count = 0",
+        );
+    }
+    qb.push(" RETURNING item_id");
+
+    // A single statement runs in one implicit transaction: all items change or none do
+    let updated: Vec<String> = qb
+        .build_query_scalar()
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if !updated.is_empty() {
+        events::publish(ServerEvent::UserDataChanged {
+            user_id: user_id.to_string(),
+            item_ids: updated.clone(),
+        });
+    }
+
+    Ok(updated)
+}
+
+/// POST /Users/{userId}/PlayedItems - Mark many items as played
+async fn bulk_mark_played(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(request): Json<BulkItemsRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    bulk_set_played(&state, &headers, &user_id, request, true).await
+}
+
+/// DELETE /Users/{userId}/PlayedItems - Mark many items as unplayed
+async fn bulk_mark_unplayed(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(request): Json<BulkItemsRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    bulk_set_played(&state, &headers, &user_id, request, false).await
+}
+
+async fn bulk_set_played(
+    state: &AppState,
+    headers: &HeaderMap,
+    user_id: &str,
+    request: BulkItemsRequest,
+    played: bool,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+
+    // Verify the user is modifying their own data
+    if user.id != user_id && !user.is_admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot modify other user's data".to_string(),
+        ));
+    }
+
+    let item_ids = request.validated_ids()?;
+    let updated = set_played_bulk(state, user_id, &item_ids, played).await?;
+
+    tracing::debug!(
+        "Marked {} of {} item(s) {} for user {}",
+        updated.len(),
+        item_ids.len(),
+        if played { "played" } else { "unplayed" },
+        user_id
+    );
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// Helper to get user item data
 async fn get_user_item_data(
     state: &AppState,
@@ -7,11 +7,23 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqlitePool;
 use std::sync::Arc;
 
 use crate::{
     models::MediaItem,
-    services::{auth, mediainfo},
+    scanner::duplicates,
+    services::{
+        auth, client_capabilities,
+        device_profile::{
+            self, AudioProperties, PlayMethod, PlaybackLimits, SourceProperties, VideoProperties,
+        },
+        external_streams::{self, ExternalFile, RemuxContainer},
+        live_tv::{self, Channel},
+        mediainfo::{self, AudioStream, MediaInfo, SubtitleStream},
+        strm,
+    },
     AppState,
 };
 
@@ -23,7 +35,7 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/:id/PlaybackInfo", post(get_playback_info))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PlaybackInfoQuery {
     pub user_id: Option<String>,
@@ -42,6 +54,39 @@ pub struct PlaybackInfoQuery {
     pub allow_audio_stream_copy: Option<bool>,
 }
 
+impl PlaybackInfoQuery {
+    /// Take the options the query string left out from the request body
+    fn fill_from(&mut self, body: PlaybackInfoQuery) {
+        self.user_id = self.user_id.take().or(body.user_id);
+        self.max_streaming_bitrate = self.max_streaming_bitrate.or(body.max_streaming_bitrate);
+        self.start_time_ticks = self.start_time_ticks.or(body.start_time_ticks);
+        self.audio_stream_index = self.audio_stream_index.or(body.audio_stream_index);
+        self.subtitle_stream_index = self.subtitle_stream_index.or(body.subtitle_stream_index);
+        self.max_audio_channels = self.max_audio_channels.or(body.max_audio_channels);
+        self.media_source_id = self.media_source_id.take().or(body.media_source_id);
+        self.live_stream_id = self.live_stream_id.take().or(body.live_stream_id);
+        self.auto_open_live_stream = self.auto_open_live_stream.or(body.auto_open_live_stream);
+        self.enable_direct_play = self.enable_direct_play.or(body.enable_direct_play);
+        self.enable_direct_stream = self.enable_direct_stream.or(body.enable_direct_stream);
+        self.enable_transcoding = self.enable_transcoding.or(body.enable_transcoding);
+        self.allow_video_stream_copy = self
+            .allow_video_stream_copy
+            .or(body.allow_video_stream_copy);
+        self.allow_audio_stream_copy = self
+            .allow_audio_stream_copy
+            .or(body.allow_audio_stream_copy);
+    }
+}
+
+/// Body of POST /Items/:id/PlaybackInfo (Jellyfin's PlaybackInfoDto)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaybackInfoBody {
+    #[serde(flatten)]
+    pub options: PlaybackInfoQuery,
+    pub device_profile: Option<Value>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PlaybackInfoResponse {
@@ -76,17 +121,22 @@ pub struct MediaSourceInfo {
     pub supports_probing: bool,
 
     pub media_streams: Vec<MediaStreamInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_audio_stream_index: Option<i32>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub direct_stream_url: Option<String>,
 
-    // Transcoding info (we don't support but clients may expect these)
+    // Transcoding info, only set when external files are remuxed into the stream
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoding_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoding_sub_protocol: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub transcoding_container: Option<String>,
+    /// Why the source isn't direct played (Jellyfin's TranscodeReason names)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub transcode_reasons: Vec<&'static str>,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -115,6 +165,8 @@ pub struct MediaStreamInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub real_frame_rate: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_interlaced: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub video_range: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub video_range_type: Option<String>,
@@ -124,6 +176,8 @@ pub struct MediaStreamInfo {
     pub level: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bit_depth: Option<u32>,
 
     // Audio specific
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -132,6 +186,9 @@ pub struct MediaStreamInfo {
     pub sample_rate: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub channel_layout: Option<String>,
+    /// Commentary track, so clients can list it apart from the programme audio
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_commentary: Option<bool>,
 
     // Subtitle specific
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -168,9 +225,23 @@ async fn get_playback_info(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(item_id): Path<String>,
-    Query(_query): Query<PlaybackInfoQuery>,
+    Query(mut query): Query<PlaybackInfoQuery>,
+    body: Option<Json<PlaybackInfoBody>>,
 ) -> Result<Json<PlaybackInfoResponse>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
+    let Json(body) = body.unwrap_or_default();
+    query.fill_from(body.options);
+
+    // Live TV channels have one source: the tuner's stream
+    let channel = live_tv::find_channel(&state.db, &item_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(channel) = channel {
+        return Ok(Json(PlaybackInfoResponse {
+            media_sources: vec![channel_media_source(&channel)],
+            play_session_id: uuid::Uuid::new_v4().to_string().replace("-", ""),
+        }));
+    }
 
     // Get the media item
     let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
@@ -180,6 +251,102 @@ async fn get_playback_info(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
 
+    let mut media_sources = vec![build_media_source(&state.db, &item).await?];
+
+    // Other files of the same episode, offered as alternate versions
+    let versions: Vec<MediaItem> = sqlx::query_as(
+        "SELECT * FROM media_items WHERE version_of = ? AND duplicate_state = ? ORDER BY height DESC, path",
+    )
+    .bind(&item.id)
+    .bind(duplicates::STATE_VERSION)
+    .fetch_all(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    for version in &versions {
+        media_sources.push(build_media_source(&state.db, version).await?);
+    }
+    if media_sources.len() > 1 {
+        for source in &mut media_sources {
+            source.name = version_name(source);
+        }
+    }
+
+    // The profile posted with the request, or the one the device registered
+    let (device_id, token) = parse_emby_auth_header(&headers)
+        .map(|(_, _, device_id, token)| (Some(device_id), token))
+        .unwrap_or_default();
+    let device_id = device_id.filter(|id| !id.is_empty());
+    let profile = match (body.device_profile.filter(|p| p.is_object()), &device_id) {
+        (Some(profile), _) => Some(profile),
+        (None, Some(device_id)) => {
+            client_capabilities::device_profile(&state.db, &user.id, device_id).await
+        }
+        (None, None) => None,
+    };
+    let media_type = if item.item_type == "Audio" {
+        "Audio"
+    } else {
+        "Video"
+    };
+    let limits = PlaybackLimits {
+        max_bitrate: query
+            .max_streaming_bitrate
+            .filter(|b| *b > 0)
+            .map(|b| b.min(u32::MAX as i64) as u32),
+        max_audio_channels: query.max_audio_channels,
+        enable_direct_play: query.enable_direct_play != Some(false),
+        enable_direct_stream: query.enable_direct_stream != Some(false),
+    };
+
+    // Generate a play session ID
+    let play_session_id = uuid::Uuid::new_v4().to_string().replace("-", "");
+
+    for source in &mut media_sources {
+        let decision = device_profile::decide(
+            profile.as_ref(),
+            &source_properties(source, media_type, &query),
+            &limits,
+        );
+        source.supports_direct_play = decision.method == PlayMethod::DirectPlay;
+        source.transcode_reasons = decision.reasons.clone();
+
+        apply_external_remux(source, &item.id, &query, profile.as_ref(), token.as_deref());
+        if media_type == "Audio" {
+            continue;
+        }
+        if let Some(container) = decision.remux_container {
+            apply_direct_stream(source, &item.id, &query, container, token.as_deref());
+        } else if decision.method == PlayMethod::Transcode && state.config.transcoding.enabled {
+            apply_transcoding(
+                source,
+                &item.id,
+                &query,
+                profile.as_ref(),
+                &play_session_id,
+                device_id.as_deref(),
+                token.as_deref(),
+            );
+        }
+    }
+
+    // A specific version was asked for
+    if let Some(ref source_id) = query.media_source_id {
+        if media_sources.iter().any(|s| &s.id == source_id) {
+            media_sources.retain(|s| &s.id == source_id);
+        }
+    }
+
+    Ok(Json(PlaybackInfoResponse {
+        media_sources,
+        play_session_id,
+    }))
+}
+
+/// Media source for an item's file, with stream details from ffprobe
+async fn build_media_source(
+    pool: &SqlitePool,
+    item: &MediaItem,
+) -> Result<MediaSourceInfo, (StatusCode, String)> {
     // Get the file path
     let file_path = item
         .path
@@ -203,144 +370,33 @@ async fn get_playback_info(
     // Add video stream
     if let Some(ref info) = media_info {
         if info.video_codec.is_some() {
-            media_streams.push(MediaStreamInfo {
-                stream_type: "Video".to_string(),
-                codec: info.video_codec.clone(),
-                index: 0,
-                is_default: true,
-                is_forced: false,
-                is_external: false,
-                width: info.width,
-                height: info.height,
-                bit_rate: info.bitrate.map(|b| b as i64),
-                aspect_ratio: info
-                    .width
-                    .zip(info.height)
-                    .map(|(w, h)| format!("{}:{}", w, h)),
-                average_frame_rate: None,
-                real_frame_rate: None,
-                video_range: Some("SDR".to_string()), // Default, could be detected
-                video_range_type: Some("SDR".to_string()),
-                pixel_format: None,
-                level: None,
-                profile: None,
-                channels: None,
-                sample_rate: None,
-                channel_layout: None,
-                language: None,
-                title: None,
-                display_title: info.video_codec.as_ref().map(|c| {
-                    if let (Some(w), Some(h)) = (info.width, info.height) {
-                        format!("{} - {}x{}", c.to_uppercase(), w, h)
-                    } else {
-                        c.to_uppercase()
-                    }
-                }),
-                delivery_method: None,
-                delivery_url: None,
-                is_text_subtitle_stream: None,
-                supports_external_stream: None,
-            });
+            media_streams.push(video_stream_info(info));
         }
 
         // Add audio streams (supports multiple tracks)
         for audio in &info.audio_streams {
-            let channel_layout = audio.channels.map(|ch| match ch {
-                1 => "mono".to_string(),
-                2 => "stereo".to_string(),
-                6 => "5.1".to_string(),
-                8 => "7.1".to_string(),
-                _ => format!("{} channels", ch),
-            });
-
-            media_streams.push(MediaStreamInfo {
-                stream_type: "Audio".to_string(),
-                codec: Some(audio.codec.clone()),
-                index: audio.index,
-                is_default: audio.is_default,
-                is_forced: false,
-                is_external: false,
-                width: None,
-                height: None,
-                bit_rate: None,
-                aspect_ratio: None,
-                average_frame_rate: None,
-                real_frame_rate: None,
-                video_range: None,
-                video_range_type: None,
-                pixel_format: None,
-                level: None,
-                profile: None,
-                channels: audio.channels,
-                sample_rate: audio.sample_rate,
-                channel_layout,
-                language: audio.language.clone(),
-                title: audio.title.clone(),
-                display_title: Some(audio.display_title()),
-                delivery_method: None,
-                delivery_url: None,
-                is_text_subtitle_stream: None,
-                supports_external_stream: None,
-            });
+            media_streams.push(audio_stream_info(audio, false));
         }
 
         // Add subtitle streams
         for sub in &info.subtitle_streams {
-            let is_text = sub.is_text_based();
-            // Use native format extension for the delivery URL
-            let format_ext = match sub.codec.as_str() {
-                "ass" | "ssa" => "ass",
-                "subrip" | "srt" => "srt",
-                "webvtt" | "vtt" => "vtt",
-                _ => "srt", // fallback
-            };
-            media_streams.push(MediaStreamInfo {
-                stream_type: "Subtitle".to_string(),
-                codec: Some(sub.codec.clone()),
-                index: sub.index,
-                is_default: sub.is_default,
-                is_forced: sub.is_forced,
-                is_external: false,
-                width: None,
-                height: None,
-                bit_rate: None,
-                aspect_ratio: None,
-                average_frame_rate: None,
-                real_frame_rate: None,
-                video_range: None,
-                video_range_type: None,
-                pixel_format: None,
-                level: None,
-                profile: None,
-                channels: None,
-                sample_rate: None,
-                channel_layout: None,
-                language: sub.language.clone(),
-                title: sub.title.clone(),
-                display_title: Some(sub.display_title()),
-                delivery_method: if is_text {
-                    Some("External".to_string())
-                } else {
-                    Some("Embed".to_string()) // Bitmap subs need to be embedded/burned
-                },
-                delivery_url: if is_text {
-                    Some(format!(
-                        "/Videos/{}/{}/Subtitles/{}/0/Stream.{}",
-                        item.id, item.id, sub.index, format_ext
-                    ))
-                } else {
-                    None
-                },
-                is_text_subtitle_stream: Some(is_text),
-                supports_external_stream: Some(is_text),
-            });
+            media_streams.push(subtitle_stream_info(&item.id, sub, false));
+        }
+    }
+
+    // Subtitle and audio files beside the video
+    if item.stream_url.is_none() {
+        for file in
+            external_streams::for_item(pool, &item.id, std::path::Path::new(file_path)).await
+        {
+            media_streams.push(external_stream_info(&item.id, &file));
         }
     }
 
     // Determine container from path
     let container = file_path.rsplit('.').next().map(|s| s.to_lowercase());
 
-    let media_source = MediaSourceInfo {
+    let mut source = MediaSourceInfo {
         id: item.id.clone(),
         name: item.name.clone(),
         path: item.path.clone(),
@@ -356,7 +412,7 @@ async fn get_playback_info(
         source_type: "Default".to_string(),
         is_remote: false,
         read_at_native_framerate: false,
-        supports_transcoding: false, // We don't support transcoding
+        supports_transcoding: false, // Set when a remux or HLS transcode is offered
         supports_direct_stream: true,
         supports_direct_play: true,
         is_infinite_stream: false,
@@ -365,17 +421,495 @@ async fn get_playback_info(
         requires_looping: false,
         supports_probing: true,
         media_streams,
+        default_audio_stream_index: media_info
+            .as_ref()
+            .and_then(|i| mediainfo::default_audio_index(&i.audio_streams)),
         direct_stream_url: Some(format!("/Videos/{}/stream", item.id)),
         transcoding_url: None,
         transcoding_sub_protocol: None,
         transcoding_container: None,
+        transcode_reasons: Vec::new(),
     };
+    apply_stream_url(&mut source, item);
+    Ok(source)
+}
 
-    // Generate a play session ID
-    let play_session_id = uuid::Uuid::new_v4().to_string().replace("-", "");
+/// Remux selected external files into the stream when the client can't load
+/// them separately
+///
+/// External audio always needs it; an external subtitle only when the device
+/// profile doesn't list its format for external delivery. The source then
+/// offers a copy-only Matroska remux as its transcoding URL instead of direct
+/// play.
+fn apply_external_remux(
+    source: &mut MediaSourceInfo,
+    item_id: &str,
+    query: &PlaybackInfoQuery,
+    profile: Option<&Value>,
+    token: Option<&str>,
+) {
+    if source.is_remote {
+        return;
+    }
+    let selected = |stream_type: &str, index: Option<i32>| {
+        let index = index?;
+        source
+            .media_streams
+            .iter()
+            .position(|s| s.stream_type == stream_type && s.is_external && s.index == index)
+    };
+    let audio = selected("Audio", query.audio_stream_index);
+    let subtitle = selected("Subtitle", query.subtitle_stream_index).filter(|&i| {
+        let format = subtitle_format(source.media_streams[i].codec.as_deref().unwrap_or(""));
+        profile.and_then(|p| client_capabilities::can_load_external_subtitle(p, format))
+            == Some(false)
+    });
+    if audio.is_none() && subtitle.is_none() {
+        return;
+    }
 
-    Ok(Json(PlaybackInfoResponse {
-        media_sources: vec![media_source],
-        play_session_id,
-    }))
+    let mut url = format!(
+        "/Videos/{}/remux.mkv?MediaSourceId={}",
+        item_id,
+        urlencoding::encode(&source.id)
+    );
+    if let Some(i) = audio {
+        url.push_str(&format!(
+            "&AudioStreamIndex={}",
+            source.media_streams[i].index
+        ));
+    }
+    if let Some(i) = subtitle {
+        let stream = &mut source.media_streams[i];
+        stream.delivery_method = Some("Embed".to_string());
+        stream.delivery_url = None;
+        url.push_str(&format!("&SubtitleStreamIndex={}", stream.index));
+    }
+    if let Some(ticks) = query.start_time_ticks.filter(|t| *t > 0) {
+        url.push_str(&format!("&StartTimeTicks={}", ticks));
+    }
+    if let Some(token) = token {
+        url.push_str(&format!("&api_key={}", urlencoding::encode(token)));
+    }
+
+    source.supports_direct_play = false;
+    source.supports_direct_stream = false;
+    source.supports_transcoding = true;
+    source.transcoding_url = Some(url);
+    source.transcoding_sub_protocol = Some("http".to_string());
+    source.transcoding_container = Some("mkv".to_string());
+}
+
+/// Copy a source's streams into a container the client plays, for sources
+/// whose codecs it plays but not their container
+///
+/// Sources already remuxed for external files and remote streams are left alone.
+fn apply_direct_stream(
+    source: &mut MediaSourceInfo,
+    item_id: &str,
+    query: &PlaybackInfoQuery,
+    container: RemuxContainer,
+    token: Option<&str>,
+) {
+    if source.is_remote || source.transcoding_url.is_some() {
+        return;
+    }
+    let mut url = format!(
+        "/Videos/{}/remux.{}?MediaSourceId={}",
+        item_id,
+        container.extension(),
+        urlencoding::encode(&source.id)
+    );
+    if let Some(index) = query.audio_stream_index.filter(|i| *i >= 0) {
+        url.push_str(&format!("&AudioStreamIndex={}", index));
+    }
+    if let Some(ticks) = query.start_time_ticks.filter(|t| *t > 0) {
+        url.push_str(&format!("&StartTimeTicks={}", ticks));
+    }
+    if let Some(token) = token {
+        url.push_str(&format!("&api_key={}", urlencoding::encode(token)));
+    }
+
+    source.supports_direct_play = false;
+    source.supports_direct_stream = false;
+    source.supports_transcoding = true;
+    source.transcoding_url = Some(url);
+    source.transcoding_sub_protocol = Some("http".to_string());
+    source.transcoding_container = Some(container.extension().to_string());
+}
+
+/// What the device profile negotiation judges a source by: its container,
+/// video stream and the audio track it would play with
+fn source_properties(
+    source: &MediaSourceInfo,
+    media_type: &'static str,
+    query: &PlaybackInfoQuery,
+) -> SourceProperties {
+    let streams = &source.media_streams;
+    let video = streams.iter().find(|s| s.stream_type == "Video");
+    let audio = query
+        .audio_stream_index
+        .and_then(|index| {
+            streams
+                .iter()
+                .find(|s| s.stream_type == "Audio" && s.index == index)
+        })
+        .or_else(|| {
+            source.default_audio_stream_index.and_then(|index| {
+                streams
+                    .iter()
+                    .find(|s| s.stream_type == "Audio" && s.index == index)
+            })
+        })
+        .or_else(|| streams.iter().find(|s| s.stream_type == "Audio"));
+
+    SourceProperties {
+        media_type,
+        container: source.container.clone(),
+        bitrate: source.bitrate,
+        video: video.map(|v| VideoProperties {
+            codec: v.codec.clone(),
+            width: v.width,
+            height: v.height,
+            bitrate: v.bit_rate,
+            level: v.level,
+            profile: v.profile.clone(),
+            bit_depth: v.bit_depth,
+            frame_rate: v.real_frame_rate.or(v.average_frame_rate),
+            is_interlaced: v.is_interlaced,
+        }),
+        audio: audio.map(|a| AudioProperties {
+            codec: a.codec.clone(),
+            channels: a.channels,
+            sample_rate: a.sample_rate,
+            bitrate: a.bit_rate,
+        }),
+    }
+}
+
+/// Offer an HLS transcode of a source the device profile negotiation decided
+/// to transcode
+///
+/// Renditions stay within the client's limit (MaxStreamingBitrate from the
+/// query or the device profile). Sources already remuxed for external files,
+/// remote streams and clients whose profile lists no HLS transcoding profile
+/// are left alone.
+fn apply_transcoding(
+    source: &mut MediaSourceInfo,
+    item_id: &str,
+    query: &PlaybackInfoQuery,
+    profile: Option<&Value>,
+    play_session_id: &str,
+    device_id: Option<&str>,
+    token: Option<&str>,
+) {
+    if query.enable_transcoding == Some(false)
+        || source.is_remote
+        || source.transcoding_url.is_some()
+        || profile.and_then(|p| client_capabilities::can_play_hls(p, "Video")) == Some(false)
+    {
+        return;
+    }
+    let max_bitrate = query
+        .max_streaming_bitrate
+        .filter(|b| *b > 0)
+        .map(|b| b.min(u32::MAX as i64) as u32)
+        .or_else(|| profile.and_then(client_capabilities::max_streaming_bitrate));
+
+    let mut url = format!(
+        "/Videos/{}/master.m3u8?MediaSourceId={}&PlaySessionId={}",
+        item_id,
+        urlencoding::encode(&source.id),
+        play_session_id
+    );
+    if let Some(device_id) = device_id {
+        url.push_str(&format!("&DeviceId={}", urlencoding::encode(device_id)));
+    }
+    if let Some(bitrate) = max_bitrate {
+        url.push_str(&format!("&MaxStreamingBitrate={}", bitrate));
+    }
+    if let Some(index) = query.audio_stream_index.filter(|i| *i >= 0) {
+        url.push_str(&format!("&AudioStreamIndex={}", index));
+    }
+    if let Some(token) = token {
+        url.push_str(&format!("&api_key={}", urlencoding::encode(token)));
+    }
+
+    source.supports_direct_play = false;
+    source.supports_direct_stream = false;
+    source.supports_transcoding = true;
+    source.transcoding_url = Some(url);
+    source.transcoding_sub_protocol = Some("hls".to_string());
+    source.transcoding_container = Some("ts".to_string());
+}
+
+/// Media stream for a file's video track
+pub(crate) fn video_stream_info(info: &MediaInfo) -> MediaStreamInfo {
+    MediaStreamInfo {
+        stream_type: "Video".to_string(),
+        codec: info.video_codec.clone(),
+        index: 0,
+        is_default: true,
+        is_forced: false,
+        is_external: false,
+        width: info.width,
+        height: info.height,
+        bit_rate: info.bitrate.map(|b| b as i64),
+        aspect_ratio: info.aspect_ratio.clone(),
+        average_frame_rate: info.average_frame_rate,
+        real_frame_rate: info.real_frame_rate,
+        is_interlaced: Some(info.is_interlaced),
+        video_range: Some("SDR".to_string()), // Default, could be detected
+        video_range_type: Some("SDR".to_string()),
+        pixel_format: info.pixel_format.clone(),
+        level: info.video_level,
+        profile: info.video_profile.clone(),
+        bit_depth: info.video_bit_depth,
+        channels: None,
+        sample_rate: None,
+        channel_layout: None,
+        is_commentary: None,
+        language: None,
+        title: None,
+        display_title: info.video_codec.as_ref().map(|c| {
+            let mut title = c.to_uppercase();
+            if let (Some(w), Some(h)) = (info.width, info.height) {
+                title = format!("{} - {}x{}", title, w, h);
+                if info.is_interlaced {
+                    title.push('i');
+                }
+            }
+            title
+        }),
+        delivery_method: None,
+        delivery_url: None,
+        is_text_subtitle_stream: None,
+        supports_external_stream: None,
+    }
+}
+
+/// Media stream for a subtitle or audio file beside the video
+pub(crate) fn external_stream_info(item_id: &str, file: &ExternalFile) -> MediaStreamInfo {
+    if file.is_audio {
+        audio_stream_info(&file.audio_stream(), true)
+    } else {
+        subtitle_stream_info(item_id, &file.subtitle_stream(), true)
+    }
+}
+
+/// Media stream for an embedded or external audio track
+fn audio_stream_info(audio: &AudioStream, is_external: bool) -> MediaStreamInfo {
+    let channel_layout = audio.channels.map(|ch| match ch {
+        1 => "mono".to_string(),
+        2 => "stereo".to_string(),
+        6 => "5.1".to_string(),
+        8 => "7.1".to_string(),
+        _ => format!("{} channels", ch),
+    });
+
+    let mut display_title = audio.display_title();
+    if is_external {
+        display_title.push_str(" - External");
+    }
+
+    MediaStreamInfo {
+        stream_type: "Audio".to_string(),
+        codec: Some(audio.codec.clone()),
+        index: audio.index,
+        is_default: audio.is_default,
+        is_forced: false,
+        is_external,
+        width: None,
+        height: None,
+        bit_rate: None,
+        aspect_ratio: None,
+        average_frame_rate: None,
+        real_frame_rate: None,
+        is_interlaced: None,
+        video_range: None,
+        video_range_type: None,
+        pixel_format: None,
+        level: None,
+        profile: None,
+        bit_depth: None,
+        channels: audio.channels,
+        sample_rate: audio.sample_rate,
+        channel_layout,
+        is_commentary: Some(audio.is_commentary),
+        language: audio.language.clone(),
+        title: audio.title.clone(),
+        display_title: Some(display_title),
+        delivery_method: None,
+        delivery_url: None,
+        is_text_subtitle_stream: None,
+        supports_external_stream: None,
+    }
+}
+
+/// Subtitle format of a codec, as used in delivery URLs and device profiles
+fn subtitle_format(codec: &str) -> &'static str {
+    match codec {
+        "ass" | "ssa" => "ass",
+        "subrip" | "srt" => "srt",
+        "webvtt" | "vtt" => "vtt",
+        _ => "srt", // fallback
+    }
+}
+
+/// Media stream for an embedded or external subtitle
+fn subtitle_stream_info(item_id: &str, sub: &SubtitleStream, is_external: bool) -> MediaStreamInfo {
+    let is_text = sub.is_text_based();
+    let mut display_title = sub.display_title();
+    if is_external {
+        display_title.push_str(" - External");
+    }
+
+    MediaStreamInfo {
+        stream_type: "Subtitle".to_string(),
+        codec: Some(sub.codec.clone()),
+        index: sub.index,
+        is_default: sub.is_default,
+        is_forced: sub.is_forced,
+        is_external,
+        width: None,
+        height: None,
+        bit_rate: None,
+        aspect_ratio: None,
+        average_frame_rate: None,
+        real_frame_rate: None,
+        is_interlaced: None,
+        video_range: None,
+        video_range_type: None,
+        pixel_format: None,
+        level: None,
+        profile: None,
+        bit_depth: None,
+        channels: None,
+        sample_rate: None,
+        channel_layout: None,
+        is_commentary: None,
+        language: sub.language.clone(),
+        title: sub.title.clone(),
+        display_title: Some(display_title),
+        delivery_method: if is_text {
+            Some("External".to_string())
+        } else {
+            Some("Embed".to_string()) // Bitmap subs need to be embedded/burned
+        },
+        delivery_url: if is_text {
+            // Use native format extension for the delivery URL
+            Some(format!(
+                "/Videos/{}/{}/Subtitles/{}/0/Stream.{}",
+                item_id,
+                item_id,
+                sub.index,
+                subtitle_format(&sub.codec)
+            ))
+        } else {
+            None
+        },
+        is_text_subtitle_stream: Some(is_text),
+        supports_external_stream: Some(is_text),
+    }
+}
+
+/// Point a media source at the remote URL of an item added from a .strm file
+///
+/// Clients play the URL directly; /Videos/{id}/stream redirects to it for
+/// clients that only use the direct stream URL.
+pub(crate) fn apply_stream_url(source: &mut MediaSourceInfo, item: &MediaItem) {
+    let Some(url) = item
+        .stream_url
+        .as_deref()
+        .and_then(|u| reqwest::Url::parse(u).ok())
+    else {
+        return;
+    };
+    source.protocol = strm::protocol(&url).unwrap_or("Http").to_string();
+    source.container = std::path::Path::new(url.path())
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    source.path = Some(url.to_string());
+    source.size = None;
+    source.is_remote = true;
+    source.supports_probing = false;
+}
+
+/// Media source of a Live TV channel: its stream URL, played as it is
+///
+/// Streams without a recognisable extension are usually MPEG-TS; HLS streams
+/// are offered as such so clients use their HLS player.
+fn channel_media_source(channel: &Channel) -> MediaSourceInfo {
+    let url = reqwest::Url::parse(&channel.stream_url).ok();
+    let container = url
+        .as_ref()
+        .and_then(|url| {
+            std::path::Path::new(url.path())
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase())
+        })
+        .map(|ext| {
+            if ext == "m3u8" {
+                "hls".to_string()
+            } else {
+                ext
+            }
+        })
+        .unwrap_or_else(|| "ts".to_string());
+
+    MediaSourceInfo {
+        id: channel.id.clone(),
+        name: channel.name.clone(),
+        path: Some(channel.stream_url.clone()),
+        protocol: url
+            .as_ref()
+            .and_then(strm::protocol)
+            .unwrap_or("Http")
+            .to_string(),
+        container: Some(container),
+        size: None,
+        bitrate: None,
+        runtime_ticks: None,
+        source_type: "Default".to_string(),
+        is_remote: true,
+        read_at_native_framerate: false,
+        supports_transcoding: false,
+        supports_direct_stream: true,
+        supports_direct_play: true,
+        is_infinite_stream: true,
+        requires_opening: false,
+        requires_closing: false,
+        requires_looping: false,
+        supports_probing: false,
+        media_streams: Vec::new(),
+        default_audio_stream_index: None,
+        direct_stream_url: Some(format!("/Videos/{}/stream", channel.id)),
+        transcoding_url: None,
+        transcoding_sub_protocol: None,
+        transcoding_container: None,
+        transcode_reasons: Vec::new(),
+    }
+}
+
+/// Label a version by its resolution and container (e.g. "1080p MKV"), or by
+/// its file name when the resolution is unknown
+pub(crate) fn version_name(source: &MediaSourceInfo) -> String {
+    let height = source
+        .media_streams
+        .iter()
+        .find(|s| s.stream_type == "Video")
+        .and_then(|s| s.height);
+    let file_name = source
+        .path
+        .as_deref()
+        .and_then(|p| std::path::Path::new(p).file_stem())
+        .map(|s| s.to_string_lossy().into_owned());
+    match (height, file_name) {
+        (Some(height), _) => match source.container {
+            Some(ref container) => format!("{}p {}", height, container.to_uppercase()),
+            None => format!("{}p", height),
+        },
+        (None, Some(file_name)) => file_name,
+        (None, None) => source.name.clone(),
+    }
 }
@@ -7,9 +7,20 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::{
+    db::{
+        self,
+        item_query::{ItemQuery, ItemSort, SortOrder},
+        ItemList,
+    },
+    services::{auth, client_capabilities::split_list, server_id},
+    AppState,
+};
 
-use super::items::{BaseItemDto, ImageTags, UserItemDataDto};
+use super::items::{
+    authorize_user_id, batch_get_image_tags, batch_get_parent_names, batch_get_user_data,
+    media_item_to_dto, BaseItemDto, UserItemDataDto,
+};
 use super::users::parse_emby_auth_header;
 
 pub fn routes() -> Router<Arc<AppState>> {
@@ -17,10 +28,15 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/", get(get_playlists))
         .route("/", post(create_playlist))
         .route("/:id", get(get_playlist))
+        .route("/:id", post(update_playlist))
         .route("/:id", delete(delete_playlist))
         .route("/:id/Items", get(get_playlist_items))
         .route("/:id/Items", post(add_items_to_playlist))
         .route("/:id/Items", delete(remove_items_from_playlist))
+        .route(
+            "/:id/Items/:entry_id/Move/:new_index",
+            post(move_playlist_item),
+        )
 }
 
 #[derive(Debug, Deserialize)]
@@ -31,21 +47,65 @@ pub struct PlaylistsQuery {
     pub limit: Option<i32>,
 }
 
-#[derive(Debug, Deserialize)]
+/// Playlist to create, from the query string (older clients) or the body
+#[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct CreatePlaylistRequest {
-    pub name: String,
+    #[serde(alias = "name")]
+    pub name: Option<String>,
+    #[serde(alias = "ids")]
     pub ids: Option<String>,
+    #[serde(alias = "userId")]
+    pub user_id: Option<String>,
+    #[serde(alias = "mediaType")]
+    pub media_type: Option<String>,
+}
+
+/// Body of POST /Playlists (CreatePlaylistDto)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreatePlaylistBody {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub ids: Vec<String>,
     pub user_id: Option<String>,
     pub media_type: Option<String>,
 }
 
+/// Body of POST /Playlists/{id}: a new name and/or the complete new contents
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdatePlaylistBody {
+    pub name: Option<String>,
+    pub ids: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PlaylistItemsQuery {
+    #[serde(alias = "ids")]
     pub ids: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaylistEntriesQuery {
+    #[serde(alias = "userId")]
+    pub user_id: Option<String>,
+    #[serde(alias = "startIndex")]
+    pub start_index: Option<i32>,
+    #[serde(alias = "limit")]
+    pub limit: Option<i32>,
+}
+
+/// Entries to remove, by PlaylistItemId (EntryIds) or item ID (Ids)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RemovePlaylistItemsQuery {
+    pub entry_ids: Option<String>,
+    pub ids: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct PlaylistCreatedResponse {
@@ -60,6 +120,26 @@ pub struct PlaylistsResponse {
     pub start_index: i32,
 }
 
+/// A playlist entry: the item, numbered by its position, with the ID clients
+/// move and remove it by
+///
+/// An item is in a playlist at most once, so the entry ID is the item's ID.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaylistEntryDto {
+    #[serde(flatten)]
+    pub item: BaseItemDto,
+    pub playlist_item_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlaylistItemsResponse {
+    pub items: Vec<PlaylistEntryDto>,
+    pub total_record_count: i32,
+    pub start_index: i32,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 struct PlaylistRow {
     id: String,
@@ -83,6 +163,101 @@ async fn require_auth(
         .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
 }
 
+/// The user's playlist with this ID
+async fn find_playlist(
+    state: &AppState,
+    id: &str,
+    user_id: &str,
+) -> Result<PlaylistRow, (StatusCode, String)> {
+    sqlx::query_as(
+        "SELECT id, name, user_id, media_type, sort_name FROM playlists WHERE id = ? AND user_id = ?",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Playlist not found".to_string()))
+}
+
+/// The Playlist DTO of a playlist with `child_count` entries
+fn playlist_to_dto(playlist: PlaylistRow, child_count: i32) -> BaseItemDto {
+    BaseItemDto {
+        id: playlist.id,
+        name: playlist.name,
+        item_type: "Playlist".to_string(),
+        server_id: server_id::get().to_string(),
+        parent_id: None,
+        overview: None,
+        year: None,
+        production_year: None,
+        index_number: None,
+        index_number_end: None,
+        parent_index_number: None,
+        runtime_ticks: None,
+        community_rating: None,
+        official_rating: None,
+        path: None,
+        premiere_date: None,
+        sort_name: playlist.sort_name,
+        series_id: None,
+        series_name: None,
+        season_id: None,
+        season_name: None,
+        music: None,
+        live_tv: None,
+        is_folder: true,
+        child_count: Some(child_count),
+        media_type: playlist.media_type,
+        collection_type: None,
+        user_data: UserItemDataDto::default(),
+        image_tags: None,
+        backdrop_image_tags: Vec::new(),
+        provider_ids: None,
+        media_sources: None,
+        width: None,
+        height: None,
+        is_hd: None,
+        is_4k: None,
+        can_download: false,
+        supports_media_source_display: false,
+    }
+}
+
+/// The items added for IDs put in a playlist: series, seasons and albums add
+/// their episodes or tracks in order, IDs that aren't items are dropped
+async fn playable_items(pool: &sqlx::SqlitePool, ids: &[String]) -> sqlx::Result<Vec<String>> {
+    const ORDER: &str = "ORDER BY COALESCE(display_parent_index_number, parent_index_number), \
+                         COALESCE(display_index_number, index_number), sort_name";
+    let mut items = Vec::new();
+    for id in ids {
+        let item_type: Option<String> =
+            sqlx::query_scalar("SELECT item_type FROM media_items WHERE id = ?")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?;
+        let children = match item_type.as_deref() {
+            None => continue,
+            Some("Series") => "item_type = 'Episode' AND parent_id = ?",
+            Some("Season") => "item_type = 'Episode' AND season_id = ?",
+            Some("MusicAlbum") => "item_type = 'Audio' AND parent_id = ?",
+            Some(_) => {
+                items.push(id.clone());
+                continue;
+            }
+        };
+        let children: Vec<String> = sqlx::query_scalar(&format!(
+            "SELECT id FROM media_items WHERE {} AND version_of IS NULL {}",
+            children, ORDER
+        ))
+        .bind(id)
+        .fetch_all(pool)
+        .await?;
+        items.extend(children);
+    }
+    Ok(items)
+}
+
 async fn get_playlists(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
@@ -118,37 +293,7 @@ async fn get_playlists(
                 .await
                 .unwrap_or((0,));
 
-        items.push(BaseItemDto {
-            id: pl.id.clone(),
-            name: pl.name,
-            item_type: "Playlist".to_string(),
-            server_id: "jellyfin-rust-server".to_string(),
-            parent_id: None,
-            overview: None,
-            year: None,
-            production_year: None,
-            index_number: None,
-            parent_index_number: None,
-            runtime_ticks: None,
-            community_rating: None,
-            path: None,
-            premiere_date: None,
-            sort_name: pl.sort_name,
-            series_id: None,
-            series_name: None,
-            season_id: None,
-            season_name: None,
-            is_folder: true,
-            child_count: Some(count.0),
-            media_type: pl.media_type,
-            collection_type: None,
-            user_data: UserItemDataDto::default(),
-            image_tags: None,
-            provider_ids: None,
-            media_sources: None,
-            can_download: false,
-            supports_media_source_display: false,
-        });
+        items.push(playlist_to_dto(pl, count.0));
     }
 
     Ok(Json(PlaylistsResponse {
@@ -162,39 +307,56 @@ async fn create_playlist(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Query(query): Query<CreatePlaylistRequest>,
+    body: Option<Json<CreatePlaylistBody>>,
 ) -> Result<Json<PlaylistCreatedResponse>, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+
+    let name = body
+        .name
+        .or(query.name)
+        .filter(|name| !name.trim().is_empty())
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Name is required".to_string()))?;
+    let user_id = authorize_user_id(&user, body.user_id.or(query.user_id).as_deref())?;
+    let mut ids = body.ids;
+    ids.extend(split_list(query.ids.as_deref()));
+    let items = playable_items(&state.db, &ids)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Without a media type the first item decides (Video or Audio)
+    let media_type = match body.media_type.or(query.media_type) {
+        Some(media_type) => Some(media_type),
+        None => match items.first() {
+            Some(first) => sqlx::query_scalar(
+                "SELECT CASE item_type WHEN 'Audio' THEN 'Audio' ELSE 'Video' END FROM media_items WHERE id = ?",
+            )
+            .bind(first)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+            None => None,
+        },
+    };
 
     let playlist_id = uuid::Uuid::new_v4().to_string();
-    let sort_name = query.name.to_lowercase();
+    let sort_name = name.to_lowercase();
 
     sqlx::query(
         "INSERT INTO playlists (id, name, user_id, media_type, sort_name) VALUES (?, ?, ?, ?, ?)",
     )
     .bind(&playlist_id)
-    .bind(&query.name)
-    .bind(&user.id)
-    .bind(&query.media_type)
+    .bind(&name)
+    .bind(&user_id)
+    .bind(&media_type)
     .bind(&sort_name)
     .execute(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    if let Some(ref ids) = query.ids {
-        for (i, item_id) in ids.split(',').enumerate() {
-            let item_id = item_id.trim();
-            if !item_id.is_empty() {
-                let _ = sqlx::query(
-                    "INSERT OR IGNORE INTO playlist_items (playlist_id, item_id, sort_order) VALUES (?, ?, ?)",
-                )
-                .bind(&playlist_id)
-                .bind(item_id)
-                .bind(i as i32)
-                .execute(&state.db)
-                .await;
-            }
-        }
-    }
+    db::append_list_items(&state.db, ItemList::Playlist, &playlist_id, &items)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     Ok(Json(PlaylistCreatedResponse { id: playlist_id }))
 }
@@ -205,16 +367,7 @@ async fn get_playlist(
     Path(id): Path<String>,
 ) -> Result<Json<BaseItemDto>, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
-
-    let playlist: PlaylistRow = sqlx::query_as(
-        "SELECT id, name, user_id, media_type, sort_name FROM playlists WHERE id = ? AND user_id = ?",
-    )
-    .bind(&id)
-    .bind(&user.id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or_else(|| (StatusCode::NOT_FOUND, "Playlist not found".to_string()))?;
+    let playlist = find_playlist(&state, &id, &user.id).await?;
 
     let count: (i32,) = sqlx::query_as("SELECT COUNT(*) FROM playlist_items WHERE playlist_id = ?")
         .bind(&id)
@@ -222,37 +375,44 @@ async fn get_playlist(
         .await
         .unwrap_or((0,));
 
-    Ok(Json(BaseItemDto {
-        id: playlist.id,
-        name: playlist.name,
-        item_type: "Playlist".to_string(),
-        server_id: "jellyfin-rust-server".to_string(),
-        parent_id: None,
-        overview: None,
-        year: None,
-        production_year: None,
-        index_number: None,
-        parent_index_number: None,
-        runtime_ticks: None,
-        community_rating: None,
-        path: None,
-        premiere_date: None,
-        sort_name: playlist.sort_name,
-        series_id: None,
-        series_name: None,
-        season_id: None,
-        season_name: None,
-        is_folder: true,
-        child_count: Some(count.0),
-        media_type: playlist.media_type,
-        collection_type: None,
-        user_data: UserItemDataDto::default(),
-        image_tags: None,
-        provider_ids: None,
-        media_sources: None,
-        can_download: false,
-        supports_media_source_display: false,
-    }))
+    Ok(Json(playlist_to_dto(playlist, count.0)))
+}
+
+/// POST /Playlists/:id - Rename a playlist and/or replace its items
+async fn update_playlist(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(body): Json<UpdatePlaylistBody>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    find_playlist(&state, &id, &user.id).await?;
+
+    if let Some(name) = body.name.filter(|name| !name.trim().is_empty()) {
+        sqlx::query("UPDATE playlists SET name = ?, sort_name = ? WHERE id = ?")
+            .bind(&name)
+            .bind(name.to_lowercase())
+            .bind(&id)
+            .execute(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    if let Some(ids) = body.ids {
+        let items = playable_items(&state.db, &ids)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        sqlx::query("DELETE FROM playlist_items WHERE playlist_id = ?")
+            .bind(&id)
+            .execute(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        db::append_list_items(&state.db, ItemList::Playlist, &id, &items)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
 }
 
 async fn delete_playlist(
@@ -276,87 +436,75 @@ async fn get_playlist_items(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
-) -> Result<Json<PlaylistsResponse>, (StatusCode, String)> {
+    Query(query): Query<PlaylistEntriesQuery>,
+) -> Result<Json<PlaylistItemsResponse>, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
+    let user_id = authorize_user_id(&user, query.user_id.as_deref())?;
+    find_playlist(&state, &id, &user_id).await?;
+
+    let start_index = query.start_index.unwrap_or(0).max(0);
+    let entries = ItemQuery::new()
+        .in_list(ItemList::Playlist, &id)
+        .visible_to(&user_id);
+    let page = entries
+        .clone()
+        .sort(
+            ItemSort::ListOrder(ItemList::Playlist, id.clone()),
+            SortOrder::Ascending,
+        )
+        .sort(ItemSort::from_sort_by("SortName"), SortOrder::Ascending)
+        // SQLite reads LIMIT -1 as no limit; the offset needs a LIMIT
+        .limit(query.limit.map_or(-1, |limit| limit.max(0)))
+        .offset(start_index);
+    let items = page
+        .fetch_all(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let total = entries
+        .count(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Verify user owns this playlist
-    let _playlist: PlaylistRow = sqlx::query_as(
-        "SELECT id, name, user_id, media_type, sort_name FROM playlists WHERE id = ? AND user_id = ?",
-    )
-    .bind(&id)
-    .bind(&user.id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-    .ok_or_else(|| (StatusCode::NOT_FOUND, "Playlist not found".to_string()))?;
-
-    let items: Vec<MediaItem> = sqlx::query_as(
-        r#"
-        SELECT m.* FROM media_items m
-        JOIN playlist_items pi ON m.id = pi.item_id
-        WHERE pi.playlist_id = ?
-        ORDER BY pi.sort_order, m.sort_name
-        "#,
-    )
-    .bind(&id)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    let total = items.len() as i32;
-
-    let mut dtos = Vec::with_capacity(items.len());
-    for item in items {
-        let is_folder = matches!(
-            item.item_type.as_str(),
-            "Series" | "Season" | "Folder" | "CollectionFolder"
-        );
-        let media_type = match item.item_type.as_str() {
-            "Episode" | "Movie" => Some("Video".to_string()),
-            "Audio" => Some("Audio".to_string()),
-            _ => None,
-        };
-
-        let image_tags = get_image_tags_for_item(&state.db, &item.id).await;
-        let user_data = get_user_item_data(&state.db, &user.id, &item.id).await;
-
-        dtos.push(BaseItemDto {
-            id: item.id.clone(),
-            name: item.name.clone(),
-            item_type: item.item_type.clone(),
-            server_id: "jellyfin-rust-server".to_string(),
-            parent_id: item.parent_id.clone(),
-            overview: item.overview.clone(),
-            year: item.year,
-            production_year: item.year,
-            index_number: item.index_number,
-            parent_index_number: item.parent_index_number,
-            runtime_ticks: item.runtime_ticks,
-            community_rating: item.community_rating,
-            path: item.path.clone(),
-            premiere_date: item.premiere_date.clone(),
-            sort_name: item.sort_name.clone(),
-            series_id: None,
-            series_name: None,
-            season_id: None,
-            season_name: None,
-            is_folder,
-            child_count: None,
-            media_type,
-            collection_type: None,
-            user_data,
-            image_tags,
-            provider_ids: None,
-            media_sources: None,
-            can_download: item.path.is_some(),
-            supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
-        });
-    }
+    let item_ids: Vec<&str> = items.iter().map(|i| i.id.as_str()).collect();
+    let series_ids: Vec<&str> = items
+        .iter()
+        .filter(|i| i.item_type == "Episode")
+        .filter_map(|i| i.parent_id.as_deref())
+        .collect();
+    let (series_names, image_tags, user_data) = tokio::join!(
+        batch_get_parent_names(&state.db, &series_ids),
+        batch_get_image_tags(&state.db, &item_ids),
+        batch_get_user_data(&state.db, &user_id, &item_ids),
+    );
+
+    let dtos = items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let series_name = (item.item_type == "Episode")
+                .then(|| item.parent_id.as_ref().and_then(|id| series_names.get(id)))
+                .flatten()
+                .cloned();
+            let mut dto = media_item_to_dto(
+                item,
+                None,
+                series_name,
+                image_tags.get(&item.id).cloned(),
+                user_data.get(&item.id).cloned(),
+            );
+            // The entry's position, as used by Move
+            dto.index_number = Some(start_index + i as i32);
+            PlaylistEntryDto {
+                playlist_item_id: item.id.clone(),
+                item: dto,
+            }
+        })
+        .collect();
 
-    Ok(Json(PlaylistsResponse {
+    Ok(Json(PlaylistItemsResponse {
         items: dtos,
         total_record_count: total,
-        start_index: 0,
+        start_index,
     }))
 }
 
@@ -367,39 +515,14 @@ async fn add_items_to_playlist(
     Query(query): Query<PlaylistItemsQuery>,
 ) -> Result<StatusCode, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
+    find_playlist(&state, &id, &user.id).await?;
 
-    // Verify user owns this playlist
-    let _: (String,) = sqlx::query_as("SELECT id FROM playlists WHERE id = ? AND user_id = ?")
-        .bind(&id)
-        .bind(&user.id)
-        .fetch_optional(&state.db)
+    let items = playable_items(&state.db, &split_list(Some(&query.ids)))
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Playlist not found".to_string()))?;
-
-    let max_order: (i32,) = sqlx::query_as(
-        "SELECT COALESCE(MAX(sort_order), 0) FROM playlist_items WHERE playlist_id = ?",
-    )
-    .bind(&id)
-    .fetch_one(&state.db)
-    .await
-    .unwrap_or((0,));
-
-    let mut order = max_order.0;
-    for item_id in query.ids.split(',') {
-        let item_id = item_id.trim();
-        if !item_id.is_empty() {
-            order += 1;
-            let _ = sqlx::query(
-                "INSERT OR IGNORE INTO playlist_items (playlist_id, item_id, sort_order) VALUES (?, ?, ?)",
-            )
-            .bind(&id)
-            .bind(item_id)
-            .bind(order)
-            .execute(&state.db)
-            .await;
-        }
-    }
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    db::append_list_items(&state.db, ItemList::Playlist, &id, &items)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     Ok(StatusCode::NO_CONTENT)
 }
@@ -408,20 +531,14 @@ async fn remove_items_from_playlist(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(id): Path<String>,
-    Query(query): Query<PlaylistItemsQuery>,
+    Query(query): Query<RemovePlaylistItemsQuery>,
 ) -> Result<StatusCode, (StatusCode, String)> {
     let user = require_auth(&state, &headers).await?;
 
-    // Verify user owns this playlist
-    let _: (String,) = sqlx::query_as("SELECT id FROM playlists WHERE id = ? AND user_id = ?")
-        .bind(&id)
-        .bind(&user.id)
-        .fetch_optional(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Playlist not found".to_string()))?;
+    find_playlist(&state, &id, &user.id).await?;
 
-    for item_id in query.ids.split(',') {
+    let ids = query.entry_ids.or(query.ids).unwrap_or_default();
+    for item_id in ids.split(',') {
         let item_id = item_id.trim();
         if !item_id.is_empty() {
             let _ = sqlx::query("DELETE FROM playlist_items WHERE playlist_id = ? AND item_id = ?")
@@ -435,66 +552,24 @@ async fn remove_items_from_playlist(
     Ok(StatusCode::NO_CONTENT)
 }
 
-async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Option<ImageTags> {
-    let images: Vec<(String,)> = sqlx::query_as("SELECT image_type FROM images WHERE item_id = ?")
-        .bind(item_id)
-        .fetch_all(pool)
-        .await
-        .unwrap_or_default();
-
-    if images.is_empty() {
-        return None;
-    }
+async fn move_playlist_item(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((id, entry_id, new_index)): Path<(String, String, usize)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
 
-    let mut tags = ImageTags::default();
-    for (image_type,) in images {
-        match image_type.as_str() {
-            "Primary" => tags.primary = Some(item_id.to_string()),
-            "Backdrop" => tags.backdrop = Some(item_id.to_string()),
-            _ => {}
-        }
-    }
+    find_playlist(&state, &id, &user.id).await?;
 
-    if tags.primary.is_some() || tags.backdrop.is_some() {
-        Some(tags)
-    } else {
-        None
+    let moved = db::move_list_item(&state.db, ItemList::Playlist, &id, &entry_id, new_index)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !moved {
+        return Err((
+            StatusCode::NOT_FOUND,
+            "Playlist entry not found".to_string(),
+        ));
     }
-}
-
-async fn get_user_item_data(
-    pool: &sqlx::SqlitePool,
-    user_id: &str,
-    item_id: &str,
-) -> UserItemDataDto {
-    let progress: Option<(i64, bool, i32, Option<String>)> = sqlx::query_as(
-        "SELECT position_ticks, played, play_count, last_played FROM playback_progress WHERE user_id = ? AND item_id = ?",
-    )
-    .bind(user_id)
-    .bind(item_id)
-    .fetch_optional(pool)
-    .await
-    .ok()
-    .flatten();
 
-    let (position_ticks, played, play_count, last_played) = progress.unwrap_or((0, false, 0, None));
-
-    let is_favorite = sqlx::query_scalar::<_, i32>(
-        "SELECT 1 FROM user_favorites WHERE user_id = ? AND item_id = ?",
-    )
-    .bind(user_id)
-    .bind(item_id)
-    .fetch_optional(pool)
-    .await
-    .ok()
-    .flatten()
-    .is_some();
-
-    UserItemDataDto {
-        playback_position_ticks: position_ticks,
-        play_count,
-        is_favorite,
-        played,
-        last_played_date: last_played,
-    }
+    Ok(StatusCode::NO_CONTENT)
 }
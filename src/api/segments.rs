@@ -9,7 +9,7 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{services::auth, AppState};
+use crate::{services::auth, time::Ticks, AppState};
 
 use super::users::parse_emby_auth_header;
 
@@ -20,6 +20,24 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/:itemId/:segmentId", delete(delete_segment))
 }
 
+/// Intro Skipper plugin endpoints, for clients that skip intros through the
+/// plugin rather than MediaSegments (enabled by `server.intro_skipper_api`)
+pub fn intro_skipper_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:itemId/IntroTimestamps", get(get_intro_timestamps))
+        .route("/:itemId/IntroTimestamps/v1", get(get_intro_timestamps))
+        .route(
+            "/:itemId/IntroSkipperSegments",
+            get(get_intro_skipper_segments),
+        )
+}
+
+/// Seconds before an intro that the Intro Skipper plugin shows its skip prompt
+const SHOW_PROMPT_BEFORE_SECONDS: f64 = 5.0;
+
+/// Seconds into an intro that the Intro Skipper plugin hides its skip prompt
+const HIDE_PROMPT_AFTER_SECONDS: f64 = 10.0;
+
 /// Segment types as defined by Jellyfin
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum MediaSegmentType {
@@ -85,6 +103,50 @@ pub struct CreateSegmentRequest {
     pub end_ticks: i64,
 }
 
+/// A segment in the Intro Skipper plugin's shape (times in seconds)
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IntroTimestampsDto {
+    pub episode_id: String,
+    pub valid: bool,
+    pub intro_start: f64,
+    pub intro_end: f64,
+    pub show_skip_prompt_at: f64,
+    pub hide_skip_prompt_at: f64,
+}
+
+impl IntroTimestampsDto {
+    fn new(item_id: &str, (start_ticks, end_ticks): (i64, i64)) -> Self {
+        let start = Ticks(start_ticks).as_secs_f64();
+        let end = Ticks(end_ticks).as_secs_f64();
+        Self {
+            episode_id: item_id.to_string(),
+            valid: end > start,
+            intro_start: start,
+            intro_end: end,
+            show_skip_prompt_at: (start - SHOW_PROMPT_BEFORE_SECONDS).max(0.0),
+            hide_skip_prompt_at: (start + HIDE_PROMPT_AFTER_SECONDS).min(end),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IntroTimestampsQuery {
+    /// "Introduction" (default) or "Credits"
+    #[serde(alias = "mode")]
+    pub mode: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct IntroSkipperSegmentsDto {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub introduction: Option<IntroTimestampsDto>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credits: Option<IntroTimestampsDto>,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 struct SegmentRow {
     id: String,
@@ -225,6 +287,68 @@ async fn delete_segment(
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Answer Intro Skipper requests only when the compatibility API is enabled,
+/// so clients see the plugin as missing otherwise
+fn require_intro_skipper_api(state: &AppState) -> Result<(), (StatusCode, String)> {
+    if state.config.intro_skipper_api {
+        Ok(())
+    } else {
+        Err((StatusCode::NOT_FOUND, "Not found".to_string()))
+    }
+}
+
+/// GET /Episode/:itemId/IntroTimestamps - An episode's intro (or credits with
+/// Mode=Credits) in the Intro Skipper plugin's shape
+async fn get_intro_timestamps(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(item_id): Path<String>,
+    Query(query): Query<IntroTimestampsQuery>,
+) -> Result<Json<IntroTimestampsDto>, (StatusCode, String)> {
+    require_intro_skipper_api(&state)?;
+    let _user = require_auth(&state, &headers).await?;
+
+    let segment = match query.mode.as_deref() {
+        None | Some("Introduction") => get_intro(&state.db, &item_id).await,
+        Some("Credits") => get_outro(&state.db, &item_id).await,
+        Some(mode) => {
+            return Err((StatusCode::BAD_REQUEST, format!("Invalid mode: {}", mode)));
+        }
+    };
+
+    segment
+        .map(|segment| Json(IntroTimestampsDto::new(&item_id, segment)))
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                "No segment for this episode".to_string(),
+            )
+        })
+}
+
+/// GET /Episode/:itemId/IntroSkipperSegments - An episode's intro and credits
+/// in the Intro Skipper plugin's shape
+async fn get_intro_skipper_segments(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(item_id): Path<String>,
+) -> Result<Json<IntroSkipperSegmentsDto>, (StatusCode, String)> {
+    require_intro_skipper_api(&state)?;
+    let _user = require_auth(&state, &headers).await?;
+
+    let introduction = get_intro(&state.db, &item_id)
+        .await
+        .map(|segment| IntroTimestampsDto::new(&item_id, segment));
+    let credits = get_outro(&state.db, &item_id)
+        .await
+        .map(|segment| IntroTimestampsDto::new(&item_id, segment));
+
+    Ok(Json(IntroSkipperSegmentsDto {
+        introduction,
+        credits,
+    }))
+}
+
 // ============================================================================
 // Helper functions for importing segments from external sources
 // ============================================================================
@@ -252,9 +376,8 @@ pub async fn import_edl_file(
                 _ => "Intro", // Default to intro for other types
             };
 
-            // Convert seconds to ticks (10,000,000 ticks per second)
-            let start_ticks = (start_secs * 10_000_000.0) as i64;
-            let end_ticks = (end_secs * 10_000_000.0) as i64;
+            let start_ticks = Ticks::from_secs_f64(start_secs).0;
+            let end_ticks = Ticks::from_secs_f64(end_secs).0;
 
             if end_ticks > start_ticks {
                 let segment_id = uuid::Uuid::new_v4().to_string();
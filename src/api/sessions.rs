@@ -1,25 +1,54 @@
 // Sessions API - Active playback session tracking for multi-device support
 
 use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
     extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
+    response::Response,
     routing::{get, post},
     Json, Router,
 };
+use futures::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-
-use crate::{models::MediaItem, services::auth, AppState};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    events::{self, LibraryChanges},
+    models::MediaItem,
+    services::{
+        auth,
+        client_capabilities::{self, ClientCapabilities},
+        server_id,
+    },
+    AppState,
+};
 
-use super::items::{BaseItemDto, ImageTags, UserItemDataDto};
-use super::users::parse_emby_auth_header;
+use super::items::{
+    batch_get_user_data, is_4k_resolution, is_hd_resolution, BaseItemDto, UserItemDataDto,
+};
+use super::users::{parse_client_version, parse_emby_auth_header};
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/", get(get_sessions))
+        .route("/Capabilities", post(post_capabilities))
+        .route("/Capabilities/Full", post(post_full_capabilities))
         .route("/:sessionId/Playing/:command", post(send_playback_command))
         .route("/:sessionId/System/:command", post(send_system_command))
         .route("/:sessionId/Message", post(send_message))
+        .route("/:sessionId/Command", post(send_general_command))
+        .route("/:sessionId/Command/:command", post(send_named_command))
+        .route("/:sessionId/Logout", post(logout_session))
+}
+
+/// Routes mounted at /socket
+pub fn socket_routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", get(open_socket))
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,6 +70,8 @@ pub struct SessionInfo {
     pub device_id: String,
     pub device_type: Option<String>,
     pub application_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_icon_url: Option<String>,
     pub last_activity_date: String,
     pub is_active: bool,
     pub supports_remote_control: bool,
@@ -83,6 +114,16 @@ pub struct MessageBody {
     pub timeout_ms: Option<i64>,
 }
 
+/// Jellyfin's GeneralCommand body (DisplayMessage, GoHome, SetVolume, ...)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GeneralCommandBody {
+    pub name: String,
+    pub controlling_user_id: Option<String>,
+    #[serde(default)]
+    pub arguments: HashMap<String, String>,
+}
+
 #[derive(Debug, sqlx::FromRow)]
 struct SessionRow {
     id: String,
@@ -91,6 +132,8 @@ struct SessionRow {
     device_name: String,
     client: String,
     client_version: Option<String>,
+    device_type: Option<String>,
+    app_icon_url: Option<String>,
     now_playing_item_id: Option<String>,
     now_playing_position_ticks: Option<i64>,
     is_paused: i32,
@@ -99,6 +142,9 @@ struct SessionRow {
     play_method: Option<String>,
     play_state: Option<String>,
     last_activity: String,
+    playable_media_types: Option<String>,
+    supported_commands: Option<String>,
+    supports_media_control: bool,
 }
 
 async fn require_auth(
@@ -130,8 +176,9 @@ async fn get_sessions(
 
     let mut sql = String::from(
         "SELECT id, user_id, device_id, device_name, client, client_version, \
-         now_playing_item_id, now_playing_position_ticks, is_paused, is_muted, \
-         volume_level, play_method, play_state, last_activity \
+         device_type, app_icon_url, now_playing_item_id, now_playing_position_ticks, is_paused, is_muted, \
+         volume_level, play_method, play_state, last_activity, \
+         playable_media_types, supported_commands, supports_media_control \
          FROM active_sessions WHERE last_activity > ?",
     );
 
@@ -196,34 +243,121 @@ async fn get_sessions(
             None
         };
 
+        let connected = is_connected(&session.id);
+        // Sessions that never registered capabilities get the defaults
+        let registered = session.supported_commands.is_some();
+        let (playable_media_types, supported_commands) = if registered {
+            (
+                client_capabilities::split_list(session.playable_media_types.as_deref()),
+                client_capabilities::split_list(session.supported_commands.as_deref()),
+            )
+        } else {
+            (
+                to_strings(client_capabilities::DEFAULT_MEDIA_TYPES),
+                to_strings(client_capabilities::DEFAULT_COMMANDS),
+            )
+        };
+        let supports_media_control = connected && (!registered || session.supports_media_control);
         result.push(SessionInfo {
             id: session.id,
             user_id: session.user_id,
             user_name,
-            client: session.client.clone(),
+            device_type: Some(session.device_type.unwrap_or_else(|| {
+                client_capabilities::device_type(&session.client, &session.device_name).to_string()
+            })),
+            client: session.client,
             device_name: session.device_name,
             device_id: session.device_id,
-            device_type: Some(detect_device_type(&session.client)),
             application_version: session.client_version,
+            app_icon_url: session.app_icon_url,
             last_activity_date: session.last_activity,
             is_active: true,
-            supports_remote_control: true,
-            supports_media_control: true,
+            supports_remote_control: connected && (!registered || !supported_commands.is_empty()),
+            supports_media_control,
             now_playing_item,
             play_state,
-            playable_media_types: vec!["Video".to_string(), "Audio".to_string()],
-            supported_commands: vec![
-                "PlayState".to_string(),
-                "Seek".to_string(),
-                "PlayNext".to_string(),
-                "PlayLast".to_string(),
-            ],
+            playable_media_types,
+            supported_commands,
         });
     }
 
     Ok(Json(result))
 }
 
+/// POST /Sessions/Capabilities - Register the calling client's capabilities from query parameters
+async fn post_capabilities(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(params): Query<Vec<(String, String)>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    // Lists may be comma-separated or repeated, and clients differ in key case
+    let mut caps = ClientCapabilities::default();
+    for (key, value) in params {
+        match key.to_ascii_lowercase().as_str() {
+            "playablemediatypes" => caps
+                .playable_media_types
+                .extend(client_capabilities::split_list(Some(&value))),
+            "supportedcommands" => caps
+                .supported_commands
+                .extend(client_capabilities::split_list(Some(&value))),
+            "supportsmediacontrol" => {
+                caps.supports_media_control = value.eq_ignore_ascii_case("true")
+            }
+            "supportspersistentidentifier" => {
+                caps.supports_persistent_identifier = value.eq_ignore_ascii_case("true")
+            }
+            _ => {}
+        }
+    }
+    register_capabilities(&state, &headers, caps).await
+}
+
+/// POST /Sessions/Capabilities/Full - Register the calling client's capabilities, including its device profile
+async fn post_full_capabilities(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Json(caps): Json<ClientCapabilities>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    register_capabilities(&state, &headers, caps).await
+}
+
+async fn register_capabilities(
+    state: &AppState,
+    headers: &HeaderMap,
+    caps: ClientCapabilities,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+    let (client, device_name, device_id, _) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+    if device_id.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Missing DeviceId".to_string()));
+    }
+
+    let version = parse_client_version(headers);
+    let session_id = touch_session(
+        &state.db,
+        &user.id,
+        &device_id,
+        &device_name,
+        &client,
+        version.as_deref(),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    client_capabilities::store(&state.db, &session_id, &caps)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::debug!(
+        "Session {} registered capabilities: media types {:?}, commands {:?}, media control {}",
+        session_id,
+        caps.playable_media_types,
+        caps.supported_commands,
+        caps.supports_media_control
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// POST /Sessions/:sessionId/Playing/:command - Send playback command
 async fn send_playback_command(
     State(state): State<Arc<AppState>>,
@@ -231,19 +365,9 @@ async fn send_playback_command(
     Path((session_id, command)): Path<(String, String)>,
     body: Option<Json<PlaybackCommandBody>>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
-
-    // Verify session exists
-    let session_exists: Option<(String,)> =
-        sqlx::query_as("SELECT id FROM active_sessions WHERE id = ?")
-            .bind(&session_id)
-            .fetch_optional(&state.db)
-            .await
-            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
-
-    if session_exists.is_none() {
-        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
-    }
+    let (user, session) = require_session_control(&state, &headers, &session_id).await?;
+    session.check_media_control()?;
+    let body = body.map(|Json(body)| body);
 
     // Handle different commands
     match command.to_lowercase().as_str() {
@@ -275,7 +399,7 @@ async fn send_playback_command(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
         }
         "seek" => {
-            if let Some(Json(body)) = body {
+            if let Some(ref body) = body {
                 if let Some(ticks) = body.seek_position_ticks {
                     sqlx::query(
                         "UPDATE active_sessions SET now_playing_position_ticks = ? WHERE id = ?",
@@ -302,9 +426,27 @@ async fn send_playback_command(
                 .await
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
         }
-        _ => {
-            tracing::debug!("Unhandled playback command: {}", command);
-        }
+        _ => {}
+    }
+
+    let delivered = send_to_session(
+        &session_id,
+        "Playstate",
+        serde_json::json!({
+            "Command": command,
+            "SeekPositionTicks": body.as_ref().and_then(|b| b.seek_position_ticks),
+            "ControllingUserId": body
+                .as_ref()
+                .and_then(|b| b.controlling_user_id.clone())
+                .unwrap_or(user.id),
+        }),
+    );
+    if delivered == 0 {
+        tracing::debug!(
+            "Playback command {} for session {} not delivered: client not connected",
+            command,
+            session_id
+        );
     }
 
     Ok(StatusCode::NO_CONTENT)
@@ -316,41 +458,563 @@ async fn send_system_command(
     headers: HeaderMap,
     Path((session_id, command)): Path<(String, String)>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let (user, session) = require_session_control(&state, &headers, &session_id).await?;
+    session.check_command(&command)?;
 
-    tracing::debug!(
-        "System command {} for session {}: {}",
-        command,
-        session_id,
-        command
-    );
-
-    // System commands are typically client-side (GoHome, GoToSettings, etc.)
-    // We just acknowledge them
+    // System commands (GoHome, GoToSettings, ...) are carried out by the client
+    deliver_general_command(&session_id, &command, &user.id, HashMap::new());
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// POST /Sessions/:sessionId/Message - Send message to session
+/// POST /Sessions/:sessionId/Message - Display a message on the client
 async fn send_message(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(session_id): Path<String>,
     Json(body): Json<MessageBody>,
 ) -> Result<StatusCode, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let (user, session) = require_session_control(&state, &headers, &session_id).await?;
+    session.check_command("DisplayMessage")?;
 
-    tracing::debug!(
-        "Message to session {}: {} - {}",
-        session_id,
-        body.header.as_deref().unwrap_or("Message"),
-        body.text
+    let mut arguments = HashMap::new();
+    arguments.insert(
+        "Header".to_string(),
+        body.header.unwrap_or_else(|| "Message".to_string()),
     );
+    arguments.insert("Text".to_string(), body.text);
+    if let Some(timeout) = body.timeout_ms {
+        arguments.insert("TimeoutMs".to_string(), timeout.to_string());
+    }
 
-    // In a real implementation, this would push to a WebSocket connection
-    // For now, we just acknowledge
+    deliver_general_command(&session_id, "DisplayMessage", &user.id, arguments);
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// POST /Sessions/:sessionId/Command - Send a general command to the client
+async fn send_general_command(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+    Json(body): Json<GeneralCommandBody>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (user, session) = require_session_control(&state, &headers, &session_id).await?;
+    session.check_command(&body.name)?;
+
+    let controlling_user_id = body.controlling_user_id.unwrap_or(user.id);
+    deliver_general_command(
+        &session_id,
+        &body.name,
+        &controlling_user_id,
+        body.arguments,
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /Sessions/:sessionId/Command/:command - Send a general command without arguments
+async fn send_named_command(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path((session_id, command)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (user, session) = require_session_control(&state, &headers, &session_id).await?;
+    session.check_command(&command)?;
+
+    deliver_general_command(&session_id, &command, &user.id, HashMap::new());
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /Sessions/:sessionId/Logout - Sign a device out and revoke its tokens (admin only)
+async fn logout_session(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(session_id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (user, session) = require_session_control(&state, &headers, &session_id).await?;
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+
+    let revoked = auth::revoke_device_sessions(&state.db, &session.user_id, &session.device_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query("DELETE FROM active_sessions WHERE id = ?")
+        .bind(&session_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // The client notices the revoked token on its next request; closing the
+    // socket makes it reconnect (and fail) right away
+    let closed = close_session_sockets(&session_id);
+
+    tracing::info!(
+        "Admin {} logged out session {} ({} token(s) revoked, {} socket(s) closed)",
+        user.name,
+        session_id,
+        revoked,
+        closed
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct ControlledSession {
+    user_id: String,
+    device_id: String,
+    supported_commands: Option<String>,
+    supports_media_control: bool,
+}
+
+impl ControlledSession {
+    /// Refuse commands the client registered it doesn't handle
+    fn check_command(&self, name: &str) -> Result<(), (StatusCode, String)> {
+        let Some(ref commands) = self.supported_commands else {
+            return Ok(());
+        };
+        if client_capabilities::split_list(Some(commands))
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case(name))
+        {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::BAD_REQUEST,
+                format!("Client does not support the {} command", name),
+            ))
+        }
+    }
+
+    /// Refuse playstate commands when the client registered it doesn't take them
+    fn check_media_control(&self) -> Result<(), (StatusCode, String)> {
+        if self.supported_commands.is_some() && !self.supports_media_control {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Client does not support media control".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Authorize sending a command to a session: admins may control any session,
+/// users only their own devices
+async fn require_session_control(
+    state: &AppState,
+    headers: &HeaderMap,
+    session_id: &str,
+) -> Result<(crate::models::User, ControlledSession), (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+
+    let session: ControlledSession = sqlx::query_as(
+        "SELECT user_id, device_id, supported_commands, supports_media_control
+             FROM active_sessions WHERE id = ?",
+    )
+    .bind(session_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Session not found".to_string()))?;
+
+    if !user.is_admin && session.user_id != user.id {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot control another user's session".to_string(),
+        ));
+    }
+    Ok((user, session))
+}
+
+fn deliver_general_command(
+    session_id: &str,
+    name: &str,
+    controlling_user_id: &str,
+    arguments: HashMap<String, String>,
+) {
+    let delivered = send_to_session(
+        session_id,
+        "GeneralCommand",
+        serde_json::json!({
+            "Name": name,
+            "ControllingUserId": controlling_user_id,
+            "Arguments": arguments,
+        }),
+    );
+    if delivered == 0 {
+        tracing::debug!(
+            "Command {} for session {} not delivered: client not connected",
+            name,
+            session_id
+        );
+    }
+}
+
+// ============================================================================
+// Client WebSocket connections
+// ============================================================================
+
+/// Message pushed to a client, in Jellyfin's WebSocket envelope
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct OutboundMessage<'a> {
+    message_type: &'a str,
+    message_id: String,
+    data: serde_json::Value,
+}
+
+/// Work for the task writing to one socket
+enum SocketCommand {
+    Send(String),
+    Close,
+}
+
+struct SocketHandle {
+    connection_id: u64,
+    user_id: String,
+    sender: mpsc::UnboundedSender<SocketCommand>,
+}
+
+/// Open sockets by session ID; a device may have several (e.g. browser tabs)
+static SOCKETS: LazyLock<Mutex<HashMap<String, Vec<SocketHandle>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(1);
+
+fn sockets() -> std::sync::MutexGuard<'static, HashMap<String, Vec<SocketHandle>>> {
+    SOCKETS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Whether a session has a socket open, i.e. can be remote controlled
+pub fn is_connected(session_id: &str) -> bool {
+    sockets().get(session_id).is_some_and(|s| !s.is_empty())
+}
+
+fn encode_message(message_type: &str, data: serde_json::Value) -> Option<String> {
+    serde_json::to_string(&OutboundMessage {
+        message_type,
+        message_id: uuid::Uuid::new_v4().simple().to_string(),
+        data,
+    })
+    .ok()
+}
+
+/// Push a message to every socket of a session, returning how many received it
+pub fn send_to_session(session_id: &str, message_type: &str, data: serde_json::Value) -> usize {
+    let Some(text) = encode_message(message_type, data) else {
+        return 0;
+    };
+
+    sockets().get(session_id).map_or(0, |handles| {
+        handles
+            .iter()
+            .filter(|h| h.sender.send(SocketCommand::Send(text.clone())).is_ok())
+            .count()
+    })
+}
+
+/// Push a message to every socket of a user's sessions, returning how many received it
+pub fn send_to_user(user_id: &str, message_type: &str, data: serde_json::Value) -> usize {
+    let Some(text) = encode_message(message_type, data) else {
+        return 0;
+    };
+
+    sockets()
+        .values()
+        .flatten()
+        .filter(|h| h.user_id == user_id)
+        .filter(|h| h.sender.send(SocketCommand::Send(text.clone())).is_ok())
+        .count()
+}
+
+fn user_has_socket(user_id: &str) -> bool {
+    sockets().values().flatten().any(|h| h.user_id == user_id)
+}
+
+/// Push a message to every open socket, returning how many received it
+pub fn send_to_all(message_type: &str, data: serde_json::Value) -> usize {
+    let Some(text) = encode_message(message_type, data) else {
+        return 0;
+    };
+
+    sockets()
+        .values()
+        .flatten()
+        .filter(|h| h.sender.send(SocketCommand::Send(text.clone())).is_ok())
+        .count()
+}
+
+/// Keep-alive interval requested from clients when their socket opens
+const SOCKET_KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// Largest message accepted from a client; clients only send small JSON messages
+const MAX_SOCKET_MESSAGE_SIZE: usize = 1024 * 1024;
+
+/// How long item changes are collected before a LibraryChanged message is sent
+const LIBRARY_CHANGE_DELAY: Duration = Duration::from_secs(2);
+
+/// Tell connected clients which items were added, updated or removed
+///
+/// Clients refetch the listed items, so home screens pick up posters from the
+/// background image downloader without a manual refresh. Changes are batched
+/// for a moment so a scan or a run of downloads sends a few messages, not one
+/// per item.
+pub async fn run_library_notifier(cancel: CancellationToken) {
+    let mut events = events::subscribe();
+    let mut changes = LibraryChanges::default();
+    // Counted from the first change of a batch, so a steady stream of changes
+    // still gets sent
+    let mut flush_at = tokio::time::Instant::now();
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep_until(flush_at), if !changes.is_empty() => {
+                let batch = std::mem::take(&mut changes);
+                if let Ok(data) = serde_json::to_value(&batch) {
+                    let sent = send_to_all("LibraryChanged", data);
+                    tracing::debug!(
+                        "Sent LibraryChanged ({} added, {} updated, {} removed) to {} socket(s)",
+                        batch.items_added.len(),
+                        batch.items_updated.len(),
+                        batch.items_removed.len(),
+                        sent
+                    );
+                }
+                continue;
+            }
+            event = events.recv() => event,
+        };
+
+        match event {
+            Ok(event) => {
+                let was_empty = changes.is_empty();
+                if changes.record(&event) && was_empty {
+                    flush_at = tokio::time::Instant::now() + LIBRARY_CHANGE_DELAY;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!("Library notifier missed {} events", skipped)
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Tell a user's connected clients about changed favorites and played state
+///
+/// Sent as Jellyfin's UserDataChanged message, so a title marked played on one
+/// device updates on the user's other devices.
+pub async fn run_user_data_notifier(pool: sqlx::SqlitePool, cancel: CancellationToken) {
+    let mut events = events::subscribe();
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => event,
+        };
+
+        let (user_id, item_ids) = match event {
+            Ok(events::ServerEvent::UserDataChanged { user_id, item_ids }) => (user_id, item_ids),
+            Ok(_) => continue,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!("User data notifier missed {} events", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        if !user_has_socket(&user_id) {
+            continue;
+        }
+
+        let ids: Vec<&str> = item_ids.iter().map(|id| id.as_str()).collect();
+        let mut user_data = batch_get_user_data(&pool, &user_id, &ids).await;
+        let user_data_list: Vec<serde_json::Value> = item_ids
+            .iter()
+            .filter_map(|id| {
+                let mut data = serde_json::to_value(user_data.remove(id)?).ok()?;
+                data["ItemId"] = id.clone().into();
+                data["Key"] = id.clone().into();
+                Some(data)
+            })
+            .collect();
+
+        let sent = send_to_user(
+            &user_id,
+            "UserDataChanged",
+            serde_json::json!({
+                "UserId": user_id,
+                "UserDataList": user_data_list,
+            }),
+        );
+        tracing::debug!(
+            "Sent UserDataChanged ({} item(s)) to {} socket(s)",
+            user_data_list.len(),
+            sent
+        );
+    }
+}
+
+fn close_session_sockets(session_id: &str) -> usize {
+    sockets().get(session_id).map_or(0, |handles| {
+        handles
+            .iter()
+            .filter(|h| h.sender.send(SocketCommand::Close).is_ok())
+            .count()
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SocketQuery {
+    #[serde(alias = "ApiKey", alias = "apiKey")]
+    pub api_key: Option<String>,
+    #[serde(alias = "DeviceId")]
+    #[serde(rename = "deviceId")]
+    pub device_id: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TokenDevice {
+    user_id: String,
+    device_id: String,
+    device_name: String,
+    client: String,
+}
+
+/// GET /socket - WebSocket for server-pushed messages (remote control, notifications)
+async fn open_socket(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SocketQuery>,
+    upgrade: Option<WebSocketUpgrade>,
+) -> Result<Response, (StatusCode, String)> {
+    let token = query
+        .api_key
+        .or_else(|| parse_emby_auth_header(&headers).and_then(|(_, _, _, token)| token))
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+    let user = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    let device: TokenDevice = sqlx::query_as(
+        "SELECT user_id, device_id, device_name, client FROM sessions WHERE token = ?",
+    )
+    .bind(&token)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Invalid session".to_string()))?;
+    let device_id = query.device_id.unwrap_or(device.device_id);
+
+    let upgrade = upgrade.ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "Expected a WebSocket upgrade".to_string(),
+        )
+    })?;
+
+    touch_session(
+        &state.db,
+        &device.user_id,
+        &device_id,
+        &device.device_name,
+        &device.client,
+        None,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let session_id = format!("{}_{}", user.id, device_id);
+    Ok(upgrade
+        .max_message_size(MAX_SOCKET_MESSAGE_SIZE)
+        .on_upgrade(move |socket| run_socket(state, user.id, session_id, device_id, socket)))
+}
+
+async fn run_socket(
+    state: Arc<AppState>,
+    user_id: String,
+    session_id: String,
+    device_id: String,
+    socket: WebSocket,
+) {
+    let (mut write_half, mut read_half) = socket.split();
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let connection_id = NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed);
+    sockets()
+        .entry(session_id.clone())
+        .or_default()
+        .push(SocketHandle {
+            connection_id,
+            user_id,
+            sender: sender.clone(),
+        });
+    tracing::debug!("WebSocket connected for session {}", session_id);
+
+    // Clients send KeepAlive at half this interval
+    if let Some(text) = encode_message(
+        "ForceKeepAlive",
+        serde_json::json!(SOCKET_KEEP_ALIVE.as_secs()),
+    ) {
+        let _ = sender.send(SocketCommand::Send(text));
+    }
+
+    // Writes happen on their own task so reads are never cancelled mid-frame
+    let writer = tokio::spawn(async move {
+        while let Some(command) = receiver.recv().await {
+            let result = match command {
+                SocketCommand::Send(text) => write_half.send(Message::Text(text)).await,
+                SocketCommand::Close => {
+                    let _ = write_half.close().await;
+                    break;
+                }
+            };
+            if result.is_err() {
+                break;
+            }
+        }
+    });
+
+    // Pings and the client's close are answered by the WebSocket itself; after
+    // a close the stream ends once the reply is sent
+    while let Some(message) = read_half.next().await {
+        match message {
+            Ok(Message::Text(text)) => {
+                // Any client message (KeepAlive, SessionsStart, ...) shows the device is still there
+                let _ = sqlx::query("UPDATE active_sessions SET last_activity = ? WHERE id = ?")
+                    .bind(chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string())
+                    .bind(&session_id)
+                    .execute(&state.db)
+                    .await;
+                if text.contains("\"KeepAlive\"") {
+                    let _ = sender.send(SocketCommand::Send(
+                        serde_json::json!({"MessageType": "KeepAlive"}).to_string(),
+                    ));
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                tracing::debug!("WebSocket for session {} failed: {}", session_id, e);
+                break;
+            }
+        }
+    }
+
+    {
+        let mut sockets = sockets();
+        if let Some(handles) = sockets.get_mut(&session_id) {
+            handles.retain(|h| h.connection_id != connection_id);
+            if handles.is_empty() {
+                sockets.remove(&session_id);
+            }
+        }
+    }
+    drop(sender);
+    let _ = writer.await;
+    tracing::debug!(
+        "WebSocket closed for session {} (device {})",
+        session_id,
+        device_id
+    );
+}
+
 // ============================================================================
 // Session management helpers
 // ============================================================================
@@ -371,14 +1035,17 @@ pub async fn update_session_playback(
     sqlx::query(
         r#"
         INSERT INTO active_sessions (id, user_id, device_id, device_name, client, 
-            now_playing_item_id, now_playing_position_ticks, play_state, last_activity)
-        VALUES (?, ?, ?, ?, ?, ?, ?, 'playing', ?)
+            device_type, now_playing_item_id, now_playing_position_ticks, play_state,
+            last_activity, playback_reported_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, 'playing', ?, ?)
         ON CONFLICT(user_id, device_id) DO UPDATE SET
+            device_type = excluded.device_type,
             now_playing_item_id = excluded.now_playing_item_id,
             now_playing_position_ticks = excluded.now_playing_position_ticks,
             play_state = 'playing',
             is_paused = 0,
-            last_activity = excluded.last_activity
+            last_activity = excluded.last_activity,
+            playback_reported_at = excluded.playback_reported_at
         "#,
     )
     .bind(&session_id)
@@ -386,9 +1053,49 @@ pub async fn update_session_playback(
     .bind(device_id)
     .bind(device_name)
     .bind(client)
+    .bind(client_capabilities::device_type(client, device_name))
     .bind(item_id)
     .bind(position_ticks)
     .bind(&now)
+    .bind(&now)
+    .execute(pool)
+    .await?;
+
+    Ok(session_id)
+}
+
+/// Create the session of a connected client that is not playing anything yet,
+/// or refresh its activity time
+pub async fn touch_session(
+    pool: &sqlx::SqlitePool,
+    user_id: &str,
+    device_id: &str,
+    device_name: &str,
+    client: &str,
+    client_version: Option<&str>,
+) -> anyhow::Result<String> {
+    let session_id = format!("{}_{}", user_id, device_id);
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    sqlx::query(
+        r#"
+        INSERT INTO active_sessions (id, user_id, device_id, device_name, client,
+            client_version, device_type, last_activity)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        ON CONFLICT(user_id, device_id) DO UPDATE SET
+            client_version = COALESCE(excluded.client_version, client_version),
+            device_type = excluded.device_type,
+            last_activity = excluded.last_activity
+        "#,
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .bind(device_id)
+    .bind(device_name)
+    .bind(client)
+    .bind(client_version)
+    .bind(client_capabilities::device_type(client, device_name))
+    .bind(&now)
     .execute(pool)
     .await?;
 
@@ -396,10 +1103,14 @@ pub async fn update_session_playback(
 }
 
 /// Update session progress
+///
+/// Also restores the playing item, in case the session was finalized as dead
+/// while the client was only slow to report.
 pub async fn update_session_progress(
     pool: &sqlx::SqlitePool,
     user_id: &str,
     device_id: &str,
+    item_id: &str,
     position_ticks: i64,
     is_paused: bool,
 ) -> anyhow::Result<()> {
@@ -409,14 +1120,17 @@ pub async fn update_session_progress(
     sqlx::query(
         r#"
         UPDATE active_sessions 
-        SET now_playing_position_ticks = ?, is_paused = ?, play_state = ?, last_activity = ?
+        SET now_playing_item_id = ?, now_playing_position_ticks = ?, is_paused = ?,
+            play_state = ?, last_activity = ?, playback_reported_at = ?
         WHERE user_id = ? AND device_id = ?
         "#,
     )
+    .bind(item_id)
     .bind(position_ticks)
     .bind(is_paused as i32)
     .bind(play_state)
     .bind(&now)
+    .bind(&now)
     .bind(user_id)
     .bind(device_id)
     .execute(pool)
@@ -450,6 +1164,67 @@ pub async fn clear_session_playback(
     Ok(())
 }
 
+#[derive(Debug, sqlx::FromRow)]
+struct DeadPlayback {
+    id: String,
+    user_id: String,
+    device_id: String,
+    now_playing_item_id: String,
+    now_playing_position_ticks: i64,
+}
+
+/// Finalize playback of sessions that haven't reported progress for the given
+/// number of seconds (e.g. the client crashed mid-playback)
+///
+/// The last reported position is recorded as if the client had sent a stop
+/// report, so watched items are still marked played, and the session is marked
+/// stopped. Returns how many sessions were finalized.
+pub async fn finalize_dead_sessions(
+    pool: &sqlx::SqlitePool,
+    timeout_secs: i64,
+) -> anyhow::Result<usize> {
+    let cutoff = chrono::Utc::now() - chrono::Duration::seconds(timeout_secs);
+    let cutoff_str = cutoff.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let dead: Vec<DeadPlayback> = sqlx::query_as(
+        "SELECT id, user_id, device_id, now_playing_item_id,
+                COALESCE(now_playing_position_ticks, 0) AS now_playing_position_ticks
+         FROM active_sessions
+         WHERE now_playing_item_id IS NOT NULL AND play_state IN ('playing', 'paused')
+           AND COALESCE(playback_reported_at, last_activity) < ?",
+    )
+    .bind(&cutoff_str)
+    .fetch_all(pool)
+    .await?;
+
+    for session in &dead {
+        let played = super::playback::finalize_playback(
+            pool,
+            &session.user_id,
+            &session.now_playing_item_id,
+            session.now_playing_position_ticks,
+        )
+        .await?;
+        clear_session_playback(pool, &session.user_id, &session.device_id).await?;
+
+        tracing::info!(
+            "Finalized playback of {} on dead session {} at position {}{}",
+            session.now_playing_item_id,
+            session.id,
+            session.now_playing_position_ticks,
+            if played { " (marked played)" } else { "" }
+        );
+        events::publish(events::ServerEvent::PlaybackStopped {
+            user_id: session.user_id.clone(),
+            item_id: session.now_playing_item_id.clone(),
+            position_ticks: session.now_playing_position_ticks,
+            played,
+        });
+    }
+
+    Ok(dead.len())
+}
+
 /// Clean up stale sessions (older than given seconds)
 pub async fn cleanup_stale_sessions(
     pool: &sqlx::SqlitePool,
@@ -470,29 +1245,8 @@ pub async fn cleanup_stale_sessions(
 // Helper functions
 // ============================================================================
 
-fn detect_device_type(client: &str) -> String {
-    let client_lower = client.to_lowercase();
-    if client_lower.contains("android") {
-        "Android".to_string()
-    } else if client_lower.contains("ios")
-        || client_lower.contains("iphone")
-        || client_lower.contains("ipad")
-    {
-        "iOS".to_string()
-    } else if client_lower.contains("web") || client_lower.contains("browser") {
-        "Web".to_string()
-    } else if client_lower.contains("roku") {
-        "Roku".to_string()
-    } else if client_lower.contains("tv")
-        || client_lower.contains("firetv")
-        || client_lower.contains("androidtv")
-    {
-        "TV".to_string()
-    } else if client_lower.contains("fladder") {
-        "Mobile".to_string()
-    } else {
-        "Unknown".to_string()
-    }
+fn to_strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
 }
 
 async fn batch_get_user_names(
@@ -562,15 +1316,17 @@ async fn batch_get_items(
                     id: item.id.clone(),
                     name: item.name.clone(),
                     item_type: item.item_type.clone(),
-                    server_id: "jellyfin-rust-server".to_string(),
+                    server_id: server_id::get().to_string(),
                     parent_id: item.parent_id.clone(),
                     overview: item.overview.clone(),
                     year: item.year,
                     production_year: item.year,
-                    index_number: item.index_number,
-                    parent_index_number: item.parent_index_number,
+                    index_number: item.index_number_for_display(),
+                    index_number_end: item.index_number_end_for_display(),
+                    parent_index_number: item.parent_index_number_for_display(),
                     runtime_ticks: item.runtime_ticks,
                     community_rating: item.community_rating,
+                    official_rating: item.official_rating.clone(),
                     path: item.path.clone(),
                     premiere_date: item.premiere_date.clone(),
                     sort_name: item.sort_name.clone(),
@@ -578,14 +1334,21 @@ async fn batch_get_items(
                     series_name: None,
                     season_id: None,
                     season_name: None,
+                    music: None,
+                    live_tv: None,
                     is_folder,
                     child_count: None,
                     media_type,
                     collection_type: None,
                     user_data: UserItemDataDto::default(),
                     image_tags: None,
+                    backdrop_image_tags: Vec::new(),
                     provider_ids: None,
                     media_sources: None,
+                    width: item.width,
+                    height: item.height,
+                    is_hd: is_hd_resolution(item.width, item.height),
+                    is_4k: is_4k_resolution(item.width, item.height),
                     can_download: item.path.is_some(),
                     supports_media_source_display: item.item_type == "Episode"
                         || item.item_type == "Movie",
@@ -0,0 +1,385 @@
+// Share links API
+// Users create expiring links that let anyone stream one movie or episode
+// without an account; the server's [sharing] policy limits who may share,
+// for how long, and at what bitrate
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{Html, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{process::Stdio, sync::Arc};
+use tokio::process::Command;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    models::{MediaItem, User},
+    services::{auth, mediainfo, share_links},
+    AppState,
+};
+
+use super::users::parse_emby_auth_header;
+use super::videos::{get_content_type, serve_file};
+
+/// Item types that can be shared
+const SHAREABLE_TYPES: &[&str] = &["Movie", "Episode", "Video"];
+
+/// Routes mounted at /ShareLinks
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/", get(list_share_links))
+        .route("/:token", delete(revoke_share_link))
+}
+
+/// Public routes mounted at /Share, authorized by the link's token alone
+pub fn public_routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/:token", get(share_page))
+        .route("/:token/stream", get(share_stream))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreateShareLinkRequest {
+    /// How long the link is valid (default and maximum set by the policy)
+    pub expires_in_hours: Option<u64>,
+    /// Bitrate cap in bits/s; sources above it are transcoded
+    pub max_bitrate: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListShareLinksQuery {
+    /// Admins see everyone's links unless they pick a user
+    pub user_id: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ShareLinkDto {
+    pub token: String,
+    /// Path of the player page to hand out, relative to the server address
+    pub url: String,
+    pub item_id: String,
+    pub item_name: Option<String>,
+    pub user_id: String,
+    pub date_created: String,
+    pub expiration_date: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bitrate: Option<i64>,
+    pub view_count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_viewed_date: Option<String>,
+}
+
+impl ShareLinkDto {
+    fn new(link: share_links::ShareLink, item_name: Option<String>) -> Self {
+        Self {
+            url: format!("/Share/{}", link.token),
+            token: link.token,
+            item_id: link.item_id,
+            item_name,
+            user_id: link.user_id,
+            date_created: link.created_at,
+            expiration_date: link.expires_at,
+            max_bitrate: link.max_bitrate,
+            view_count: link.view_count,
+            last_viewed_date: link.last_viewed_at,
+        }
+    }
+}
+
+async fn require_auth(state: &AppState, headers: &HeaderMap) -> Result<User, (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
+}
+
+fn sharing_disabled() -> (StatusCode, String) {
+    (
+        StatusCode::FORBIDDEN,
+        "Sharing is disabled on this server".to_string(),
+    )
+}
+
+/// POST /Items/:itemId/Share - Create a share link for a movie or episode
+pub async fn create_share_link(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(item_id): Path<String>,
+    Json(req): Json<CreateShareLinkRequest>,
+) -> Result<Json<ShareLinkDto>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    let policy = &state.config.sharing;
+    if !policy.enabled {
+        return Err(sharing_disabled());
+    }
+    if !policy.users_can_share && !user.is_admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only administrators can share items".to_string(),
+        ));
+    }
+
+    // Items the user can't see can't be shared either
+    let item: MediaItem = sqlx::query_as(
+        "SELECT * FROM media_items WHERE id = ?
+           AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)",
+    )
+    .bind(&item_id)
+    .bind(&user.id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+
+    if !SHAREABLE_TYPES.contains(&item.item_type.as_str()) || item.path.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only movies and episodes can be shared".to_string(),
+        ));
+    }
+    // The remote URL would be handed out as is
+    if item.stream_url.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Remote streams can't be shared".to_string(),
+        ));
+    }
+
+    let link = share_links::create(
+        &state.db,
+        policy,
+        &item.id,
+        &user.id,
+        req.expires_in_hours.unwrap_or(policy.max_lifetime_hours),
+        req.max_bitrate,
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "User {} shared {} until {}",
+        user.id,
+        item.id,
+        link.expires_at
+    );
+    Ok(Json(ShareLinkDto::new(link, Some(item.name))))
+}
+
+/// GET /ShareLinks?userId= - List share links (users their own, admins everyone's)
+async fn list_share_links(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ListShareLinksQuery>,
+) -> Result<Json<Vec<ShareLinkDto>>, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+    let user_id = if user.is_admin {
+        query.user_id.filter(|id| !id.is_empty())
+    } else {
+        Some(user.id)
+    };
+
+    let links = share_links::list(&state.db, user_id.as_deref())
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut result = Vec::with_capacity(links.len());
+    for link in links {
+        let name: Option<(String,)> = sqlx::query_as("SELECT name FROM media_items WHERE id = ?")
+            .bind(&link.item_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        result.push(ShareLinkDto::new(link, name.map(|(n,)| n)));
+    }
+    Ok(Json(result))
+}
+
+/// DELETE /ShareLinks/:token - Revoke a share link (its owner or an admin)
+async fn revoke_share_link(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let user = require_auth(&state, &headers).await?;
+
+    let link = share_links::get(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Share link not found".to_string()))?;
+    if link.user_id != user.id && !user.is_admin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot revoke other user's share links".to_string(),
+        ));
+    }
+
+    share_links::revoke(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    tracing::info!("User {} revoked share link for {}", user.id, link.item_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The item behind a token, if the link is valid and sharing is enabled
+async fn shared_item(
+    state: &AppState,
+    token: &str,
+) -> Result<(share_links::ShareLink, MediaItem), (StatusCode, String)> {
+    if !state.config.sharing.enabled {
+        return Err(sharing_disabled());
+    }
+    let link = share_links::get(&state.db, token)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .filter(|link| !link.is_expired(chrono::Utc::now()))
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                "This link has expired or been revoked".to_string(),
+            )
+        })?;
+    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+        .bind(&link.item_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+    Ok((link, item))
+}
+
+/// Escape text for HTML
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// GET /Share/:token - Player page for a shared item (public)
+async fn share_page(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Html<String>, (StatusCode, String)> {
+    let (link, item) = shared_item(&state, &token).await?;
+
+    let title = match (
+        item.item_type.as_str(),
+        item.parent_index_number,
+        item.index_number,
+    ) {
+        ("Episode", Some(season), Some(episode)) => {
+            format!("S{:02}E{:02} - {}", season, episode, item.name)
+        }
+        _ => item.name.clone(),
+    };
+    let title = escape_html(&title);
+    let overview = escape_html(item.overview.as_deref().unwrap_or_default());
+    Ok(Html(format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{title}</title>\
+         <style>body{{margin:0;background:#101010;color:#eee;font-family:sans-serif}}\
+         video{{width:100%;max-height:80vh;background:#000}}main{{padding:0 1em}}</style>\
+         </head><body><video controls autoplay src=\"/Share/{token}/stream\"></video>\
+         <main><h1>{title}</h1><p>{overview}</p><p><small>Available until {expires}</small></p>\
+         </main></body></html>",
+        token = link.token,
+        expires = escape_html(&link.expires_at),
+    )))
+}
+
+/// GET /Share/:token/stream - Stream a shared item (public)
+///
+/// Served as is with Range support, or transcoded when the link or the policy
+/// caps the bitrate below the source's.
+async fn share_stream(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    Path(token): Path<String>,
+) -> Result<Response, (StatusCode, String)> {
+    let (link, item) = shared_item(&state, &token).await?;
+    let file_path = item
+        .path
+        .as_deref()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item has no file path".to_string()))?;
+
+    // Count a view once per playback rather than for every range request
+    let is_first_request = headers
+        .get(header::RANGE)
+        .and_then(|r| r.to_str().ok())
+        .is_none_or(|r| r.starts_with("bytes=0-"));
+    if method == Method::GET && is_first_request {
+        if let Err(e) = share_links::record_view(&state.db, &link.token).await {
+            tracing::warn!("Failed to record share link view: {}", e);
+        }
+    }
+
+    let cap = share_links::effective_bitrate(link.max_bitrate, &state.config.sharing);
+    let source_bitrate = match cap {
+        Some(_) => mediainfo::extract_media_info_async(std::path::Path::new(file_path))
+            .await
+            .ok()
+            .and_then(|info| info.bitrate),
+        None => None,
+    };
+    let bitrate = match (cap, source_bitrate) {
+        // Unknown source bitrates are transcoded to be safe
+        (Some(cap), source) if source.is_none_or(|b| b > cap as u64) => cap,
+        _ => return serve_file(&headers, file_path, get_content_type(file_path)).await,
+    };
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "video/mp4")
+        .header(header::ACCEPT_RANGES, "none")
+        .header(header::CACHE_CONTROL, "no-cache");
+    if method == Method::HEAD {
+        return Ok(response.body(Body::empty()).unwrap());
+    }
+
+    let args = share_links::transcode_args(std::path::Path::new(file_path), bitrate);
+    let mut child = Command::new(mediainfo::find_ffmpeg())
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to run ffmpeg: {}", e),
+            )
+        })?;
+
+    let stdout = child.stdout.take().ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "ffmpeg produced no output".to_string(),
+        )
+    })?;
+
+    tracing::debug!("Transcoding shared item {} at {} bps", item.id, bitrate);
+
+    // The stream owns the child so ffmpeg is killed when the viewer disconnects
+    let stream = ReaderStream::new(stdout).map(move |chunk| {
+        let _ = &child;
+        chunk
+    });
+
+    Ok(response.body(Body::from_stream(stream)).unwrap())
+}
@@ -7,17 +7,32 @@ use axum::{
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
 use std::sync::Arc;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::events::{self, ServerEvent};
+use crate::scanner::seasons;
+use crate::services::season_mapping::{self, SeasonMapping, SuggestedMapping};
+use crate::{
+    models::MediaItem,
+    services::{auth, server_id},
+    AppState,
+};
 
-use super::items::{BaseItemDto, ImageTags, ItemsResponse, UserItemDataDto};
+use super::items::{
+    batch_get_child_counts, batch_get_image_tags, batch_get_user_data, is_4k_resolution,
+    is_hd_resolution, tmdb_client, BaseItemDto, ImageTags, ItemsResponse, UserItemDataDto,
+};
 use super::users::parse_emby_auth_header;
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
         .route("/:seriesId/Seasons", get(get_seasons))
         .route("/:seriesId/Episodes", get(get_episodes))
+        .route(
+            "/:seriesId/SeasonMappings",
+            get(get_season_mappings).post(set_season_mappings),
+        )
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,6 +97,9 @@ fn media_item_to_dto(
         if let Some(ref id) = item.imdb_id {
             ids.insert("Imdb".to_string(), id.clone());
         }
+        if let Some(ref id) = item.tvdb_id {
+            ids.insert("Tvdb".to_string(), id.clone());
+        }
         if let Some(ref id) = item.anilist_id {
             ids.insert("AniList".to_string(), id.clone());
         }
@@ -102,15 +120,17 @@ fn media_item_to_dto(
         id: item.id.clone(),
         name: item.name.clone(),
         item_type: item.item_type.clone(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         parent_id: item.parent_id.clone(),
         overview: item.overview.clone(),
         year: item.year,
         production_year: item.year,
-        index_number: item.index_number,
-        parent_index_number: item.parent_index_number,
+        index_number: item.index_number_for_display(),
+        index_number_end: item.index_number_end_for_display(),
+        parent_index_number: item.parent_index_number_for_display(),
         runtime_ticks: item.runtime_ticks,
         community_rating: item.community_rating,
+        official_rating: item.official_rating.clone(),
         path: item.path.clone(),
         premiere_date: item.premiere_date.clone(),
         sort_name: item.sort_name.clone(),
@@ -120,133 +140,84 @@ fn media_item_to_dto(
             None
         },
         series_name,
-        season_id: if item.item_type == "Episode" {
-            // Generate synthetic season_id: {series_id}_season_{season_number}
-            if let (Some(ref series_id), Some(season_num)) =
-                (&item.parent_id, item.parent_index_number)
-            {
-                Some(format!("{}_season_{}", series_id, season_num))
-            } else {
-                None
-            }
+        season_id: item.season_id.clone(),
+        season_name: if item.item_type == "Episode" {
+            item.parent_index_number_for_display()
+                .map(seasons::season_name)
         } else {
             None
         },
-        season_name: item.parent_index_number.map(|s| {
-            if s == 0 {
-                "Specials".to_string()
-            } else {
-                format!("Season {}", s)
-            }
-        }),
+        music: None,
+        live_tv: None,
         is_folder,
         child_count: None,
         media_type,
         collection_type: None,
         user_data: UserItemDataDto::default(),
+        backdrop_image_tags: ImageTags::backdrop_image_tags(&image_tags),
         image_tags,
         provider_ids,
         media_sources: None,
+        width: item.width,
+        height: item.height,
+        is_hd: is_hd_resolution(item.width, item.height),
+        is_4k: is_4k_resolution(item.width, item.height),
         can_download: item.path.is_some(),
         supports_media_source_display: item.item_type == "Episode" || item.item_type == "Movie",
     }
 }
 
 /// GET /Shows/:seriesId/Seasons
-/// Returns seasons for a series. Since we don't have explicit Season items,
-/// we'll synthesize them from episodes' parent_index_number (season number).
+/// Returns the seasons of a series, specials first
 async fn get_seasons(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(series_id): Path<String>,
-    Query(_query): Query<SeasonsQuery>,
+    Query(query): Query<SeasonsQuery>,
 ) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
+    let user_id = query.user_id.as_deref().unwrap_or(&user.id);
 
-    // Get the series
-    let series: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
-        .bind(&series_id)
-        .fetch_optional(&state.db)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Series not found".to_string()))?;
+    let series = visible_series(&state, &series_id, &user.id).await?;
 
-    // Get series image tags to use for seasons (fallback)
-    let series_image_tags = get_image_tags_for_item(&state.db, &series_id).await;
-
-    // Get distinct season numbers from episodes
-    // Use COALESCE to handle NULL as season 1 in the query itself
-    let season_numbers: Vec<(i32,)> = sqlx::query_as(
-        "SELECT DISTINCT COALESCE(parent_index_number, 1) as season_num FROM media_items 
-         WHERE parent_id = ? AND item_type = 'Episode' 
-         ORDER BY season_num",
+    let seasons: Vec<MediaItem> = sqlx::query_as(
+        "SELECT * FROM media_items WHERE parent_id = ? AND item_type = 'Season'
+         AND (?2 IS NULL OR (index_number = 0) = ?2)
+         AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?3)
+         ORDER BY index_number",
     )
     .bind(&series_id)
+    .bind(query.is_special_season)
+    .bind(&user.id)
     .fetch_all(&state.db)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Create synthetic Season items
-    let mut items = Vec::new();
-    for (season_num,) in season_numbers {
-        // Count episodes in this season
-        let episode_count: (i32,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM media_items 
-             WHERE parent_id = ? AND item_type = 'Episode' AND COALESCE(parent_index_number, 1) = ?",
-        )
-        .bind(&series_id)
-        .bind(season_num)
-        .fetch_one(&state.db)
-        .await
-        .unwrap_or((0,));
+    let season_ids: Vec<&str> = seasons.iter().map(|s| s.id.as_str()).collect();
+    let (child_counts, mut image_tags, mut user_data) = tokio::join!(
+        batch_get_child_counts(&state.db, &season_ids),
+        batch_get_image_tags(&state.db, &season_ids),
+        batch_get_user_data(&state.db, user_id, &season_ids),
+    );
 
-        // Season name: Season 0 = "Specials", otherwise "Season X"
-        let season_name = if season_num == 0 {
-            "Specials".to_string()
-        } else {
-            format!("Season {}", season_num)
-        };
+    // Seasons without artwork of their own show the series'
+    let series_image_tags = get_image_tags_for_item(&state.db, &series_id).await;
 
-        // Sort name: Specials should sort last (use 999), otherwise by number
-        let sort_name = if season_num == 0 {
-            "Season 999".to_string() // Specials sort last
-        } else {
-            format!("Season {:03}", season_num)
-        };
-
-        items.push(BaseItemDto {
-            id: format!("{}_season_{}", series_id, season_num),
-            name: season_name,
-            item_type: "Season".to_string(),
-            server_id: "jellyfin-rust-server".to_string(),
-            parent_id: Some(series_id.clone()),
-            overview: None,
-            year: series.year,
-            production_year: series.year,
-            index_number: Some(season_num),
-            parent_index_number: None,
-            runtime_ticks: None,
-            community_rating: None,
-            path: None,
-            premiere_date: None,
-            sort_name: Some(sort_name),
-            series_id: Some(series_id.clone()),
-            series_name: Some(series.name.clone()),
-            season_id: None,
-            season_name: None,
-            is_folder: true,
-            child_count: Some(episode_count.0),
-            media_type: None,
-            collection_type: None,
-            user_data: UserItemDataDto::default(),
-            // Use series images as fallback for season images
-            image_tags: series_image_tags.clone(),
-            provider_ids: None,
-            media_sources: None,
-            can_download: false,
-            supports_media_source_display: false,
-        });
-    }
+    let items: Vec<BaseItemDto> = seasons
+        .iter()
+        .map(|season| {
+            let tags = image_tags
+                .remove(&season.id)
+                .or_else(|| series_image_tags.clone());
+            let mut dto = media_item_to_dto(season, Some(series.name.clone()), tags);
+            dto.series_id = Some(series_id.clone());
+            dto.child_count = Some(child_counts.get(&season.id).copied().unwrap_or(0));
+            if let Some(data) = user_data.remove(&season.id) {
+                dto.user_data = data;
+            }
+            dto
+        })
+        .collect();
 
     let total = items.len() as i32;
 
@@ -257,86 +228,146 @@ async fn get_seasons(
     }))
 }
 
+/// A series, unless it's hidden from the user (blocked, or above their parental limit)
+async fn visible_series(
+    state: &AppState,
+    series_id: &str,
+    user_id: &str,
+) -> Result<MediaItem, (StatusCode, String)> {
+    sqlx::query_as(
+        "SELECT * FROM media_items WHERE id = ?
+         AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)",
+    )
+    .bind(series_id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(|| (StatusCode::NOT_FOUND, "Series not found".to_string()))
+}
+
+/// Season number an episode is listed under, as used for filtering and sorting
+const EPISODE_SEASON: &str = "COALESCE(display_parent_index_number, parent_index_number, -1)";
+
+/// Episode number within the season, as used for sorting
+const EPISODE_NUMBER: &str = "COALESCE(display_index_number, index_number, -1)";
+
 /// GET /Shows/:seriesId/Episodes
 /// Returns episodes for a series, optionally filtered by season
+///
+/// Episodes are ordered by season and episode number. StartItemId skips the
+/// episodes before that one; StartIndex and Limit page through the rest, with
+/// no limit unless one is given.
 async fn get_episodes(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Path(series_id): Path<String>,
     Query(query): Query<EpisodesQuery>,
 ) -> Result<Json<ItemsResponse>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
+    let user_id = query.user_id.as_deref().unwrap_or(&user.id);
 
     // Get the series for its name
-    let series: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
+    let series = visible_series(&state, &series_id, &user.id).await?;
+
+    let start_index = query.start_index.unwrap_or(0).max(0);
+    let limit = query.limit.filter(|l| *l >= 0).unwrap_or(-1);
+
+    // Season and episode numbers of the first episode to list
+    let start_item: Option<(i32, i32, String)> = match query.start_item_id {
+        Some(ref start_id) => sqlx::query_as(&format!(
+            "SELECT {}, {}, id FROM media_items WHERE id = ? AND parent_id = ?",
+            EPISODE_SEASON, EPISODE_NUMBER
+        ))
+        .bind(start_id)
         .bind(&series_id)
         .fetch_optional(&state.db)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Series not found".to_string()))?;
-
-    let start_index = query.start_index.unwrap_or(0);
-    let limit = query.limit.unwrap_or(1000).min(1000);
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+        None => None,
+    };
 
-    // Build query for episodes
-    let mut sql =
-        String::from("SELECT * FROM media_items WHERE parent_id = ? AND item_type = 'Episode'");
+    // Season IDs from before seasons were items are looked up by number
+    let season_id = match query.season_id {
+        Some(ref id) => seasons::find_legacy(&state.db, id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .or_else(|| Some(id.clone())),
+        None => None,
+    };
+    let season = query.season.filter(|_| season_id.is_none());
 
-    // Filter by season number if specified
-    if let Some(season_num) = query.season {
-        sql.push_str(&format!(" AND parent_index_number = {}", season_num));
+    let mut filter = String::from(
+        "FROM media_items WHERE parent_id = ? AND item_type = 'Episode' AND version_of IS NULL
+         AND id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ?)",
+    );
+    if season_id.is_some() {
+        filter.push_str(" AND season_id = ?");
+    } else if season.is_some() {
+        filter.push_str(&format!(" AND {} = ?", EPISODE_SEASON));
     }
-
-    // Or filter by synthetic season_id
-    if let Some(ref season_id) = query.season_id {
-        // Parse season number from synthetic ID like "seriesid_season_1"
-        if let Some(num_str) = season_id.rsplit('_').next() {
-            if let Ok(season_num) = num_str.parse::<i32>() {
-                sql.push_str(&format!(" AND parent_index_number = {}", season_num));
-            }
-        }
+    if start_item.is_some() {
+        filter.push_str(&format!(
+            " AND ({}, {}, id) >= (?, ?, ?)",
+            EPISODE_SEASON, EPISODE_NUMBER
+        ));
     }
+    let sql = format!(
+        "SELECT * {} ORDER BY {}, {}, id LIMIT ? OFFSET ?",
+        filter, EPISODE_SEASON, EPISODE_NUMBER
+    );
+    let count_sql = format!("SELECT COUNT(*) {}", filter);
+    let bind_filter = |sql| {
+        let mut q = sqlx::query(sql)
+            .bind(series_id.clone())
+            .bind(user.id.clone());
+        if let Some(ref season_id) = season_id {
+            q = q.bind(season_id.clone());
+        } else if let Some(season) = season {
+            q = q.bind(season);
+        }
+        if let Some((season, episode, ref id)) = start_item {
+            q = q.bind(season).bind(episode).bind(id.clone());
+        }
+        q
+    };
 
-    sql.push_str(" ORDER BY parent_index_number, index_number");
-    sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, start_index));
-
-    let episodes: Vec<MediaItem> = sqlx::query_as(&sql)
-        .bind(&series_id)
+    let episodes: Vec<MediaItem> = bind_filter(&sql)
+        .bind(limit)
+        .bind(start_index)
         .fetch_all(&state.db)
         .await
+        .and_then(|rows| rows.iter().map(MediaItem::from_row).collect())
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Count total
-    let mut count_sql = String::from(
-        "SELECT COUNT(*) FROM media_items WHERE parent_id = ? AND item_type = 'Episode'",
-    );
-    if let Some(season_num) = query.season {
-        count_sql.push_str(&format!(" AND parent_index_number = {}", season_num));
-    }
-    if let Some(ref season_id) = query.season_id {
-        if let Some(num_str) = season_id.rsplit('_').next() {
-            if let Ok(season_num) = num_str.parse::<i32>() {
-                count_sql.push_str(&format!(" AND parent_index_number = {}", season_num));
-            }
-        }
-    }
-
-    let total: (i32,) = sqlx::query_as(&count_sql)
-        .bind(&series_id)
+    let total: i32 = bind_filter(&count_sql)
         .fetch_one(&state.db)
         .await
-        .unwrap_or((0,));
+        .and_then(|row| row.try_get(0))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    // Build items with image tags
-    let mut items = Vec::with_capacity(episodes.len());
-    for ep in &episodes {
-        let image_tags = get_image_tags_for_item(&state.db, &ep.id).await;
-        items.push(media_item_to_dto(ep, Some(series.name.clone()), image_tags));
-    }
+    // Images and user data for the whole page at once
+    let episode_ids: Vec<&str> = episodes.iter().map(|ep| ep.id.as_str()).collect();
+    let (mut image_tags, mut user_data) = tokio::join!(
+        batch_get_image_tags(&state.db, &episode_ids),
+        batch_get_user_data(&state.db, user_id, &episode_ids),
+    );
+
+    let items = episodes
+        .iter()
+        .map(|ep| {
+            let mut dto =
+                media_item_to_dto(ep, Some(series.name.clone()), image_tags.remove(&ep.id));
+            if let Some(data) = user_data.remove(&ep.id) {
+                dto.user_data = data;
+            }
+            dto
+        })
+        .collect();
 
     Ok(Json(ItemsResponse {
         items,
-        total_record_count: total.0,
+        total_record_count: total,
         start_index,
     }))
 }
@@ -357,7 +388,10 @@ async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Opti
     for (image_type,) in images {
         match image_type.as_str() {
             "Primary" => tags.primary = Some(item_id.to_string()),
-            "Backdrop" => tags.backdrop = Some(item_id.to_string()),
+            "Backdrop" => {
+                tags.backdrop = Some(item_id.to_string());
+                tags.backdrop_count += 1;
+            }
             _ => {}
         }
     }
@@ -368,3 +402,128 @@ async fn get_image_tags_for_item(pool: &sqlx::SqlitePool, item_id: &str) -> Opti
         None
     }
 }
+
+// =============================================================================
+// Season mappings (split-cour anime)
+// =============================================================================
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SeasonMappingsResponse {
+    pub mappings: Vec<SeasonMapping>,
+    /// Manual (set by an admin) or Auto (applied from high-confidence suggestions)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Rules proposed from TMDB season episode counts, with confidence
+    pub suggestions: Vec<SuggestedMapping>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SetSeasonMappingsBody {
+    /// Replaces all rules; an empty list returns the series to automatic mapping
+    pub mappings: Vec<SeasonMapping>,
+}
+
+async fn require_admin_series(
+    state: &AppState,
+    headers: &HeaderMap,
+    series_id: &str,
+) -> Result<(), (StatusCode, String)> {
+    let user = require_auth(state, headers).await?;
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+
+    let item_type: Option<String> =
+        sqlx::query_scalar("SELECT item_type FROM media_items WHERE id = ?")
+            .bind(series_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    match item_type.as_deref() {
+        Some("Series") => Ok(()),
+        Some(_) => Err((StatusCode::BAD_REQUEST, "Item is not a series".to_string())),
+        None => Err((StatusCode::NOT_FOUND, "Series not found".to_string())),
+    }
+}
+
+/// GET /Shows/:seriesId/SeasonMappings - Current season mappings and suggestions (admin only)
+async fn get_season_mappings(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(series_id): Path<String>,
+) -> Result<Json<SeasonMappingsResponse>, (StatusCode, String)> {
+    require_admin_series(&state, &headers, &series_id).await?;
+
+    let mappings = season_mapping::get_mappings(&state.db, &series_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let source: Option<String> =
+        sqlx::query_scalar("SELECT source FROM series_season_mappings WHERE item_id = ? LIMIT 1")
+            .bind(&series_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Suggestions need TMDB; without it the editor still shows the stored rules
+    let suggestions = match tmdb_client(&state.config) {
+        Some(tmdb) => season_mapping::suggest_for_series(&state.db, &tmdb, &series_id)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to suggest season mappings for {}: {}", series_id, e);
+                Vec::new()
+            }),
+        None => Vec::new(),
+    };
+
+    Ok(Json(SeasonMappingsResponse {
+        mappings,
+        source,
+        suggestions,
+    }))
+}
+
+/// POST /Shows/:seriesId/SeasonMappings - Replace season mappings and renumber episodes (admin only)
+async fn set_season_mappings(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(series_id): Path<String>,
+    Json(body): Json<SetSeasonMappingsBody>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin_series(&state, &headers, &series_id).await?;
+
+    for m in &body.mappings {
+        if m.disk_season < 0
+            || m.provider_season < 0
+            || m.first_episode < 1
+            || m.last_episode.is_some_and(|last| last < m.first_episode)
+            || m.first_episode - m.episode_offset < 1
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Invalid mapping for season {} from episode {}",
+                    m.disk_season, m.first_episode
+                ),
+            ));
+        }
+    }
+
+    season_mapping::set_mappings(&state.db, &series_id, &body.mappings, "Manual")
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let renumbered = season_mapping::apply_season_mappings(&state.db, &series_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "Season mappings of series {} updated ({} rules, {} episodes renumbered)",
+        series_id,
+        body.mappings.len(),
+        renumbered
+    );
+    events::publish(ServerEvent::ItemUpdated { item_id: series_id });
+
+    Ok(StatusCode::NO_CONTENT)
+}
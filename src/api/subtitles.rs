@@ -13,7 +13,12 @@ use std::{path::PathBuf, process::Stdio, sync::Arc};
 use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::{
+    models::MediaItem,
+    services::{auth, external_streams},
+    time::Ticks,
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -153,8 +158,34 @@ async fn get_subtitle_inner(
         .as_ref()
         .ok_or_else(|| (StatusCode::NOT_FOUND, "Item has no file path".to_string()))?;
 
-    // Convert start_ticks to seconds (1 tick = 100 nanoseconds)
-    let start_seconds = start_ticks as f64 / 10_000_000.0;
+    let start = Ticks(start_ticks);
+
+    // Subtitle files beside the video are converted from the file itself, and
+    // not cached since the files can change
+    let external = if index >= external_streams::FIRST_INDEX {
+        let file = external_streams::find_index(
+            &state.db,
+            &item.id,
+            std::path::Path::new(file_path),
+            index,
+        )
+        .await
+        .filter(|f| !f.is_audio)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Subtitle not found".to_string()))?;
+        Some(file.path)
+    } else {
+        None
+    };
+    if let Some(ref path) = external {
+        // Served as is when the client asks for the file's own format
+        if start_ticks == 0
+            && path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case(&format))
+        {
+            return serve_subtitle_file(path, &format).await;
+        }
+    }
 
     // Check cache first (include start_ticks in cache key if non-zero)
     let cache_dir = get_subtitle_cache_dir(&item_id);
@@ -165,7 +196,7 @@ async fn get_subtitle_inner(
     };
 
     // Check cache (use async to avoid blocking)
-    if tokio::fs::try_exists(&cache_file).await.unwrap_or(false) {
+    if external.is_none() && tokio::fs::try_exists(&cache_file).await.unwrap_or(false) {
         tracing::debug!("Serving cached subtitle: {:?}", cache_file);
         return serve_subtitle_file(&cache_file, &format).await;
     }
@@ -176,7 +207,7 @@ async fn get_subtitle_inner(
         index,
         file_path,
         format,
-        start_seconds
+        start.as_secs_f64()
     );
 
     // Create cache directory
@@ -195,17 +226,24 @@ async fn get_subtitle_inner(
     // Build ffmpeg command
     // If start_ticks > 0, we need to offset the subtitle timestamps
     let mut cmd = Command::new(find_ffmpeg());
-    cmd.args(["-i", file_path]);
+    match external {
+        Some(ref path) => cmd.arg("-i").arg(path),
+        None => cmd.args(["-i", file_path]),
+    };
 
     // Add timestamp offset if seeking
     if start_ticks > 0 {
         // Use -ss after -i for subtitle streams to properly offset
-        cmd.args(["-ss", &format!("{:.3}", start_seconds)]);
+        cmd.args(["-ss", &start.to_ffmpeg()]);
     }
 
     cmd.args([
         "-map",
-        &format!("0:{}", index),
+        &if external.is_some() {
+            "0:0".to_string()
+        } else {
+            format!("0:{}", index)
+        },
         "-c:s",
         output_codec,
         "-f",
@@ -238,8 +276,10 @@ async fn get_subtitle_inner(
     let subtitle_data = output.stdout;
 
     // Cache the result
-    if let Err(e) = tokio::fs::write(&cache_file, &subtitle_data).await {
-        tracing::warn!("Failed to cache subtitle: {}", e);
+    if external.is_none() {
+        if let Err(e) = tokio::fs::write(&cache_file, &subtitle_data).await {
+            tracing::warn!("Failed to cache subtitle: {}", e);
+        }
     }
 
     // Serve the subtitle
@@ -1,13 +1,21 @@
 use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
+    body::Body,
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use tokio_util::io::ReaderStream;
 
-use crate::{services::auth, AppState};
+use crate::{
+    db::{self, query_stats},
+    logging,
+    services::{auth, progress, scheduled_tasks, server_id, updates},
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -17,6 +25,17 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/Info/Public", get(get_public_system_info))
         .route("/Info/Storage", get(get_storage_info))
         .route("/Configuration", get(get_configuration))
+        .route("/Logs", get(get_log_files))
+        .route("/Logs/Log", get(get_log_file))
+        .route(
+            "/QueryStats",
+            get(get_query_stats).delete(reset_query_stats),
+        )
+        .route("/Search/Rebuild", post(rebuild_search_index))
+        .route("/Status", get(get_server_status))
+        .route("/Updates", get(get_update_status))
+        .route("/Updates/Check", post(check_for_updates))
+        .route("/Updates/Install", post(install_update))
         .route("/Restart", post(restart_server))
         .route("/Shutdown", post(shutdown_server))
         .route("/Ping", get(ping))
@@ -68,10 +87,10 @@ async fn get_system_info() -> Json<SystemInfo> {
     Json(SystemInfo {
         server_name: "Jellyfin Rust".to_string(),
         version: "10.11.5".to_string(), // Mimic Jellyfin version for client compat
-        id: "jellyfin-rust-server".to_string(),
+        id: server_id::get().to_string(),
         operating_system: std::env::consts::OS.to_string(),
         has_pending_restart: false,
-        has_update_available: false,
+        has_update_available: updates::update_available(),
     })
 }
 
@@ -79,7 +98,7 @@ async fn get_public_system_info() -> Json<PublicSystemInfo> {
     Json(PublicSystemInfo {
         server_name: "Jellyfin Rust".to_string(),
         version: "10.11.5".to_string(),
-        id: "jellyfin-rust-server".to_string(),
+        id: server_id::get().to_string(),
         local_address: "http://localhost:8096".to_string(),
         startup_wizard_completed: true,
     })
@@ -212,20 +231,39 @@ async fn get_storage_info(
         }
     }
 
+    let log_folder = if state.config.logging.file_enabled {
+        get_folder_storage(&state.config.paths.log_dir()).await
+    } else {
+        None
+    };
+
     Ok(Json(SystemStorageDto {
         program_data_folder: data_folder,
         cache_folder,
-        log_folder: None,
+        log_folder,
         libraries: library_storage,
     }))
 }
 
 /// Helper to require admin authentication
 async fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
-    let (_, _, _, token) = parse_emby_auth_header(headers)
-        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+    require_admin_with_key(state, headers, None).await
+}
 
-    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+/// Admin check that also accepts an api_key query parameter (for download links)
+async fn require_admin_with_key(
+    state: &AppState,
+    headers: &HeaderMap,
+    query_api_key: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let token = match query_api_key {
+        Some(key) => key.to_string(),
+        None => {
+            let (_, _, _, token) = parse_emby_auth_header(headers)
+                .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+            token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?
+        }
+    };
 
     let user = auth::validate_session(&state.db, &token)
         .await
@@ -238,6 +276,381 @@ async fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (Sta
     Ok(())
 }
 
+// =============================================================================
+// Query statistics
+// =============================================================================
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct QueryTimingDto {
+    pub name: String,
+    pub count: u64,
+    pub slow_count: u64,
+    pub total_ms: f64,
+    pub average_ms: f64,
+    pub max_ms: f64,
+}
+
+impl QueryTimingDto {
+    fn new(name: String, timing: query_stats::QueryTiming) -> Self {
+        let total_ms = timing.total.as_secs_f64() * 1000.0;
+        Self {
+            name,
+            count: timing.count,
+            slow_count: timing.slow_count,
+            total_ms,
+            average_ms: total_ms / timing.count.max(1) as f64,
+            max_ms: timing.max.as_secs_f64() * 1000.0,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct QueryStatsDto {
+    pub enabled: bool,
+    pub slow_query_threshold_ms: u64,
+    pub routes: Vec<QueryTimingDto>,
+    pub statements: Vec<QueryTimingDto>,
+    pub recent_slow_queries: Vec<query_stats::SlowQuery>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryStatsQuery {
+    /// Entries per list (default 50)
+    pub limit: Option<usize>,
+}
+
+/// GET /System/QueryStats - Database time per route and statement, and recent slow queries
+async fn get_query_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<QueryStatsQuery>,
+) -> Result<Json<QueryStatsDto>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let limit = query.limit.unwrap_or(50);
+    let to_dtos = |timings: Vec<(String, query_stats::QueryTiming)>| {
+        timings
+            .into_iter()
+            .take(limit)
+            .map(|(name, timing)| QueryTimingDto::new(name, timing))
+            .collect()
+    };
+
+    Ok(Json(QueryStatsDto {
+        enabled: state.config.logging.query_stats,
+        slow_query_threshold_ms: state.config.logging.slow_query_ms,
+        routes: to_dtos(query_stats::route_timings()),
+        statements: to_dtos(query_stats::statement_timings()),
+        recent_slow_queries: query_stats::recent_slow_queries(),
+    }))
+}
+
+/// DELETE /System/QueryStats - Clear collected query statistics
+async fn reset_query_stats(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+    query_stats::reset();
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// =============================================================================
+// Search index
+// =============================================================================
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRebuildQuery {
+    /// Only compare the index with the library instead of rebuilding it
+    #[serde(default)]
+    pub verify: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SearchIndexStatusDto {
+    pub media_item_count: i64,
+    /// Null (like the counts below) when the index can't be read
+    pub index_row_count: Option<i64>,
+    /// Items search can't find
+    pub missing_rows: Option<i64>,
+    /// Index entries for items that were removed
+    pub stale_rows: Option<i64>,
+    pub in_sync: Option<bool>,
+    pub rebuild_running: bool,
+}
+
+/// POST /System/Search/Rebuild - Rebuild the search index, or with `verify=true`
+/// report how it differs from the library (admin)
+///
+/// The rebuild runs as the "Rebuild Search Index" scheduled task, so its
+/// progress shows under /ScheduledTasks; 409 while it is running.
+async fn rebuild_search_index(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<SearchRebuildQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let task = scheduled_tasks::find("search-index").ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "No search index task".to_string(),
+        )
+    })?;
+
+    if query.verify {
+        let status = db::verify_fts_index(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Ok(Json(SearchIndexStatusDto {
+            media_item_count: status.media_item_count,
+            index_row_count: status.fts_row_count,
+            missing_rows: status.missing_rows,
+            stale_rows: status.stale_rows,
+            in_sync: status.in_sync(),
+            rebuild_running: scheduled_tasks::is_running(task.id),
+        })
+        .into_response());
+    }
+
+    if !scheduled_tasks::start(&state.db, &state.config, task, None) {
+        return Err((
+            StatusCode::CONFLICT,
+            "The search index is already being rebuilt".to_string(),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+// =============================================================================
+// Log files
+// =============================================================================
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LogFileDto {
+    pub date_created: String,
+    pub date_modified: String,
+    pub size: u64,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogFileQuery {
+    pub name: String,
+    #[serde(rename = "api_key")]
+    pub api_key: Option<String>,
+}
+
+/// GET /System/Logs - List log files, newest first
+async fn get_log_files(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<LogFileDto>>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let log_dir = state.config.paths.log_dir();
+    let files = tokio::task::spawn_blocking(move || logging::list_log_files(&log_dir))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .unwrap_or_default();
+
+    let mut result = Vec::with_capacity(files.len());
+    for path in files {
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let to_rfc3339 = |time: std::io::Result<std::time::SystemTime>| {
+            time.map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+                .unwrap_or_default()
+        };
+        let modified = to_rfc3339(metadata.modified());
+        // Not every filesystem records creation time
+        let created = metadata
+            .created()
+            .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339())
+            .unwrap_or_else(|_| modified.clone());
+
+        result.push(LogFileDto {
+            date_created: created,
+            date_modified: modified,
+            size: metadata.len(),
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        });
+    }
+
+    Ok(Json(result))
+}
+
+/// GET /System/Logs/Log?name= - Download a log file as plain text
+async fn get_log_file(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<LogFileQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    require_admin_with_key(&state, &headers, query.api_key.as_deref()).await?;
+
+    // Only serve our own log files, never arbitrary paths
+    if !logging::is_log_file_name(&query.name) {
+        return Err((StatusCode::BAD_REQUEST, "Invalid log file name".to_string()));
+    }
+
+    let path = state.config.paths.log_dir().join(&query.name);
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "Log file not found".to_string()))?;
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename=\"{}\"", query.name),
+        )
+        .body(Body::from_stream(ReaderStream::new(file)))
+        .unwrap())
+}
+
+// =============================================================================
+// Dashboard status
+// =============================================================================
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct QueueStatus {
+    pub images_pending: i64,
+    /// Gave up after three attempts
+    pub images_failed: i64,
+    pub thumbnails_pending: i64,
+    pub thumbnails_failed: i64,
+    pub unmatched_series: i64,
+    /// Unmatched series that the missing metadata task will try again
+    pub unmatched_series_retrying: i64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ServerStatus {
+    pub queues: QueueStatus,
+    pub scans: Vec<ScanStatus>,
+    pub scheduled_tasks: Vec<scheduled_tasks::TaskInfo>,
+    pub background_tasks: Vec<progress::BackgroundTask>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScanStatus {
+    pub library_name: Option<String>,
+    #[serde(flatten)]
+    pub progress: progress::ScanProgress,
+}
+
+/// GET /System/Status - Queue backlogs, running scans and tasks, and background loop health (admin)
+async fn get_server_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<ServerStatus>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+    let internal = |e: sqlx::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+
+    let queues: QueueStatus = sqlx::query_as(
+        "SELECT
+            (SELECT COUNT(*) FROM image_queue WHERE status = 'pending') AS images_pending,
+            (SELECT COUNT(*) FROM image_queue WHERE status = 'failed') AS images_failed,
+            (SELECT COUNT(*) FROM thumbnail_queue WHERE status = 'pending') AS thumbnails_pending,
+            (SELECT COUNT(*) FROM thumbnail_queue WHERE status = 'failed') AS thumbnails_failed,
+            (SELECT COUNT(*) FROM unmatched_series) AS unmatched_series,
+            (SELECT COUNT(*) FROM unmatched_series WHERE attempt_count < 3) AS unmatched_series_retrying",
+    )
+    .fetch_one(&state.db)
+    .await
+    .map_err(internal)?;
+
+    let library_names: std::collections::HashMap<String, String> =
+        sqlx::query_as("SELECT id, name FROM libraries")
+            .fetch_all(&state.db)
+            .await
+            .map_err(internal)?
+            .into_iter()
+            .collect();
+    let scans = progress::scans()
+        .into_iter()
+        .map(|scan| ScanStatus {
+            library_name: library_names.get(&scan.library_id).cloned(),
+            progress: scan,
+        })
+        .collect();
+
+    let scheduled_tasks = scheduled_tasks::list(&state.db, &state.config)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .into_iter()
+        .filter(|task| task.state != "Idle")
+        .collect();
+
+    Ok(Json(ServerStatus {
+        queues,
+        scans,
+        scheduled_tasks,
+        background_tasks: progress::background_tasks(),
+    }))
+}
+
+// =============================================================================
+// Updates
+// =============================================================================
+
+/// GET /System/Updates - What the last release check found
+async fn get_update_status(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<updates::UpdateStatus>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+    Ok(Json(updates::status(&state.config.updates)))
+}
+
+/// POST /System/Updates/Check - Check the release feed now
+async fn check_for_updates(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<Json<updates::UpdateStatus>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+    updates::check(&state.config.updates)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))
+}
+
+/// POST /System/Updates/Install - Install the newer release and restart
+async fn install_update(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    tracing::info!("Update installation requested by admin");
+    let installed = updates::install(&state.config.updates).await.map_err(|e| {
+        let status = match e {
+            updates::InstallError::Unavailable(_) => StatusCode::FORBIDDEN,
+            updates::InstallError::NothingToInstall(_) => StatusCode::CONFLICT,
+            updates::InstallError::Failed(_) => StatusCode::BAD_GATEWAY,
+        };
+        (status, e.to_string())
+    })?;
+
+    tracing::info!("Restarting into version {}", installed.version);
+    updates::restart_into(installed.executable);
+    Ok(StatusCode::NO_CONTENT)
+}
+
 /// POST /System/Restart - Restart the server
 ///
 /// This sends a 204 response and then triggers a process restart.
@@ -1,13 +1,30 @@
 use axum::{
-    extract::{Path, State},
-    http::{HeaderMap, StatusCode},
+    extract::{Path, Query, Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
     routing::{delete, get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 
-use crate::{services::auth, AppState};
+use crate::{
+    events::{self, ServerEvent},
+    models::User,
+    services::{auth, library_views, parental_controls, server_id},
+    AppState,
+};
+
+/// Header an admin sets to make a request as another user (ID or name)
+const IMPERSONATE_HEADER: &str = "X-Jellyfin-Impersonate-User";
+
+/// Query parameter alternative to IMPERSONATE_HEADER, for links opened in a browser
+const IMPERSONATE_QUERY: &str = "impersonateUserId";
+
+/// Response header naming the user an impersonated request ran as
+const IMPERSONATING_HEADER: &str = "X-Jellyfin-Impersonating";
 
 pub fn routes() -> Router<Arc<AppState>> {
     Router::new()
@@ -17,7 +34,11 @@ pub fn routes() -> Router<Arc<AppState>> {
         .route("/Me", get(get_current_user))
         .route("/New", post(create_user))
         .route("/:userId", get(get_user_by_id))
+        .route("/:userId", post(update_user))
         .route("/:userId", delete(delete_user))
+        .route("/:userId/Password", post(update_password))
+        .route("/:userId/Policy", post(update_policy))
+        .route("/:userId/Configuration", post(update_configuration))
 }
 
 /// User image routes - mounted at /Users/:userId/Images
@@ -61,10 +82,20 @@ pub struct UserPolicy {
     pub is_hidden: bool,
     pub is_disabled: bool,
     pub enable_all_folders: bool,
+    /// Libraries the user may see when EnableAllFolders is off
+    pub enabled_folders: Vec<String>,
+    /// Highest parental rating score shown (services::parental_controls);
+    /// null for no limit
+    pub max_parental_rating: Option<i32>,
+    /// Genre and studio names hidden from the user
+    pub blocked_tags: Vec<String>,
     pub enable_audio_playback_transcoding: bool,
     pub enable_video_playback_transcoding: bool,
     pub enable_playback_remuxing: bool,
     pub enable_media_conversion: bool,
+    /// Live TV channels and guide (services::live_tv)
+    pub enable_live_tv_access: bool,
+    pub enable_live_tv_management: bool,
     pub authentication_provider_id: String,
     pub password_reset_provider_id: String,
 }
@@ -80,6 +111,12 @@ pub struct UserConfiguration {
     pub hide_played_in_latest: bool,
     pub remember_audio_selections: bool,
     pub remember_subtitle_selections: bool,
+    /// Libraries in the user's order (services::library_views)
+    pub ordered_views: Vec<String>,
+    /// Libraries hidden from the user's views
+    pub my_media_excludes: Vec<String>,
+    /// Libraries left out of the user's Latest sections
+    pub latest_items_excludes: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -100,10 +137,15 @@ impl Default for UserPolicy {
             is_hidden: false,
             is_disabled: false,
             enable_all_folders: true,
+            enabled_folders: Vec::new(),
+            max_parental_rating: None,
+            blocked_tags: Vec::new(),
             enable_audio_playback_transcoding: false,
             enable_video_playback_transcoding: false,
             enable_playback_remuxing: true,
             enable_media_conversion: false,
+            enable_live_tv_access: true,
+            enable_live_tv_management: false,
             authentication_provider_id:
                 "Jellyfin.Server.Implementations.Users.DefaultAuthenticationProvider".to_string(),
             password_reset_provider_id:
@@ -123,56 +165,311 @@ impl Default for UserConfiguration {
             hide_played_in_latest: true,
             remember_audio_selections: true,
             remember_subtitle_selections: true,
+            ordered_views: Vec::new(),
+            my_media_excludes: Vec::new(),
+            latest_items_excludes: Vec::new(),
         }
     }
 }
 
-/// Parse the X-Emby-Authorization header
-/// Format: MediaBrowser Client="...", Device="...", DeviceId="...", Version="...", Token="..."
-pub fn parse_emby_auth_header(
-    headers: &HeaderMap,
-) -> Option<(String, String, String, Option<String>)> {
+/// A user's policy, with their library access and parental controls
+async fn user_policy(state: &AppState, user: &User) -> Result<UserPolicy, (StatusCode, String)> {
+    let access = crate::db::get_library_access(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let is_disabled = auth::is_disabled(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let parental = parental_controls::load(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(UserPolicy {
+        is_administrator: user.is_admin,
+        is_disabled,
+        enable_all_folders: access.is_none(),
+        enabled_folders: access.unwrap_or_default(),
+        max_parental_rating: parental.max_parental_rating,
+        blocked_tags: parental.blocked_tags,
+        enable_live_tv_management: user.is_admin,
+        ..Default::default()
+    })
+}
+
+/// A user's configuration, with their library order and visibility
+async fn user_configuration(
+    state: &AppState,
+    user: &User,
+) -> Result<UserConfiguration, (StatusCode, String)> {
+    let views = library_views::load(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(UserConfiguration {
+        ordered_views: views.ordered,
+        my_media_excludes: views.hidden,
+        latest_items_excludes: views.latest_excluded,
+        ..Default::default()
+    })
+}
+
+/// Key/value pairs of the X-Emby-Authorization (or Authorization) header
+fn auth_header_params(headers: &HeaderMap) -> Option<impl Iterator<Item = (&str, &str)>> {
     let auth_header = headers
         .get("X-Emby-Authorization")
         .or_else(|| headers.get("Authorization"))?
         .to_str()
         .ok()?;
 
-    let mut client = String::new();
-    let mut device = String::new();
-    let mut device_id = String::new();
-    let mut token = None;
-
     // Remove "MediaBrowser " or "Emby " prefix
     let params = auth_header
         .strip_prefix("MediaBrowser ")
         .or_else(|| auth_header.strip_prefix("Emby "))
         .unwrap_or(auth_header);
 
-    for part in params.split(',') {
-        let part = part.trim();
-        if let Some((key, value)) = part.split_once('=') {
-            let value = value.trim_matches('"');
-            match key.trim() {
-                "Client" => client = value.to_string(),
-                "Device" => device = value.to_string(),
-                "DeviceId" => device_id = value.to_string(),
-                "Token" => token = Some(value.to_string()),
-                _ => {}
-            }
+    Some(params.split(',').filter_map(|part| {
+        let (key, value) = part.trim().split_once('=')?;
+        Some((key.trim(), value.trim_matches('"')))
+    }))
+}
+
+/// Parse the X-Emby-Authorization header
+/// Format: MediaBrowser Client="...", Device="...", DeviceId="...", Version="...", Token="..."
+pub fn parse_emby_auth_header(
+    headers: &HeaderMap,
+) -> Option<(String, String, String, Option<String>)> {
+    let mut client = String::new();
+    let mut device = String::new();
+    let mut device_id = String::new();
+    let mut token = None;
+
+    for (key, value) in auth_header_params(headers)? {
+        match key {
+            "Client" => client = value.to_string(),
+            "Device" => device = value.to_string(),
+            "DeviceId" => device_id = value.to_string(),
+            "Token" => token = Some(value.to_string()),
+            _ => {}
         }
     }
 
     Some((client, device, device_id, token))
 }
 
+/// The client version (`Version`) from the X-Emby-Authorization header
+pub fn parse_client_version(headers: &HeaderMap) -> Option<String> {
+    auth_header_params(headers)?
+        .find(|(key, _)| *key == "Version")
+        .map(|(_, value)| value.to_string())
+        .filter(|version| !version.is_empty())
+}
+
+/// Extract a static API key from the X-Api-Key header or an apiKey/api_key query value
+/// Used by external tools (webhooks, automation) that can't hold a session token
+pub fn parse_api_key(headers: &HeaderMap, query_key: Option<&str>) -> Option<String> {
+    headers
+        .get("X-Api-Key")
+        .and_then(|v| v.to_str().ok())
+        .or(query_key)
+        .filter(|k| !k.is_empty())
+        .map(|k| k.to_string())
+}
+
+/// Authorize external tools: accepts either the configured API key or an admin session
+pub async fn require_api_key_or_admin(
+    state: &AppState,
+    headers: &HeaderMap,
+    query_key: Option<&str>,
+) -> Result<(), (StatusCode, String)> {
+    let provided = parse_api_key(headers, query_key);
+    if auth::verify_api_key(state.config.api_key.as_deref(), provided.as_deref()) {
+        return Ok(());
+    }
+
+    let token = parse_emby_auth_header(headers)
+        .and_then(|(_, _, _, token)| token)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Invalid API key".to_string()))?;
+
+    let user = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+
+    Ok(())
+}
+
+/// Middleware running admin requests as another user ("read as user")
+///
+/// Lets an admin see exactly what a user sees (home screen, hidden items,
+/// permissions) by sending IMPERSONATE_HEADER or IMPERSONATE_QUERY with their
+/// own token. The token is swapped for a server-side session of the target
+/// user, so every handler applies that user's permissions; nothing is granted
+/// beyond what the user already has. Only GET and HEAD requests may be
+/// impersonated, and each one is published as a UserImpersonated event for
+/// the activity log.
+pub async fn impersonate(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    // Parsed leniently: a malformed query is the handler's problem, not ours
+    let query = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .map(|q| q.0)
+        .unwrap_or_default();
+    let target = request
+        .headers()
+        .get(IMPERSONATE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .or(query.get(IMPERSONATE_QUERY).map(String::as_str))
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string);
+    let Some(target) = target else {
+        return next.run(request).await;
+    };
+
+    let target = match start_impersonation(&state, &mut request, &target).await {
+        Ok(target) => target,
+        Err(e) => return e.into_response(),
+    };
+
+    let mut response = next.run(request).await;
+    if let Ok(name) = HeaderValue::from_str(&target.name) {
+        response.headers_mut().insert(IMPERSONATING_HEADER, name);
+    }
+    response
+}
+
+/// Check an impersonation request and swap its token; returns the target user
+async fn start_impersonation(
+    state: &AppState,
+    request: &mut Request,
+    target: &str,
+) -> Result<User, (StatusCode, String)> {
+    // Handlers reading an api_key parameter prefer it over the header, so a
+    // query token is the one checked here, and both are swapped below
+    let query_token = Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|q| {
+            q.0.into_iter()
+                .find(|(name, value)| is_api_key_param(name) && !value.is_empty())
+        })
+        .map(|(_, value)| value);
+    let header_token = parse_emby_auth_header(request.headers()).and_then(|(_, _, _, token)| token);
+    let token = query_token
+        .clone()
+        .or(header_token.clone())
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+    let admin = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+    if !admin.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    if request.method() != Method::GET && request.method() != Method::HEAD {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Impersonated requests are read-only".to_string(),
+        ));
+    }
+
+    let user: User = sqlx::query_as("SELECT * FROM users WHERE id = ? OR name = ?")
+        .bind(target)
+        .bind(target)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    let session_token = auth::impersonation_session(&state.db, &admin, &token, &user)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let headers = request.headers_mut();
+    if header_token.is_some() {
+        let name = if headers.contains_key("X-Emby-Authorization") {
+            "X-Emby-Authorization"
+        } else {
+            "Authorization"
+        };
+        let header = headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        let header = HeaderValue::from_str(&auth::replace_header_token(header, &session_token))
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+        headers.insert(name, header);
+    }
+    headers.remove(IMPERSONATE_HEADER);
+    if query_token.is_some() {
+        *request.uri_mut() = replace_query_token(request.uri(), &session_token)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+
+    events::publish(ServerEvent::UserImpersonated {
+        admin_user_id: admin.id,
+        user_id: user.id.clone(),
+        method: request.method().to_string(),
+        path: request.uri().path().to_string(),
+    });
+
+    Ok(user)
+}
+
+/// Whether a query parameter carries an access token (`api_key`, `ApiKey`, ...)
+fn is_api_key_param(name: &str) -> bool {
+    name.eq_ignore_ascii_case("api_key") || name.eq_ignore_ascii_case("apikey")
+}
+
+/// `uri` with every access token parameter set to `token` and IMPERSONATE_QUERY
+/// dropped; other parameters are kept exactly as sent
+fn replace_query_token(uri: &Uri, token: &str) -> Result<Uri, axum::http::Error> {
+    let Some(query) = uri.query() else {
+        return Ok(uri.clone());
+    };
+    let pairs: Vec<String> = query
+        .split('&')
+        .filter_map(|pair| {
+            let name = pair.split('=').next().unwrap_or_default();
+            if name == IMPERSONATE_QUERY {
+                None
+            } else if is_api_key_param(name) {
+                Some(format!("{}={}", name, token))
+            } else {
+                Some(pair.to_string())
+            }
+        })
+        .collect();
+    let path_and_query = if pairs.is_empty() {
+        uri.path().to_string()
+    } else {
+        format!("{}?{}", uri.path(), pairs.join("&"))
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse()?);
+    Ok(Uri::from_parts(parts)?)
+}
+
 async fn authenticate_by_name(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
     Json(req): Json<AuthenticateRequest>,
 ) -> Result<Json<AuthenticationResult>, (StatusCode, String)> {
+    login(&state, &headers, &req.username, &req.pw)
+        .await
+        .map(Json)
+}
+
+/// Sign in and open a session for the requesting device
+pub(crate) async fn login(
+    state: &AppState,
+    headers: &HeaderMap,
+    username: &str,
+    password: &str,
+) -> Result<AuthenticationResult, (StatusCode, String)> {
     let (client, device_name, device_id, _) =
-        parse_emby_auth_header(&headers).unwrap_or_else(|| {
+        parse_emby_auth_header(headers).unwrap_or_else(|| {
             (
                 "Unknown".to_string(),
                 "Unknown".to_string(),
@@ -183,8 +480,8 @@ async fn authenticate_by_name(
 
     let (user, session) = auth::authenticate(
         &state.db,
-        &req.username,
-        &req.pw,
+        username,
+        password,
         &device_id,
         &device_name,
         &client,
@@ -195,15 +492,12 @@ async fn authenticate_by_name(
     let user_dto = UserDto {
         id: user.id.clone(),
         name: user.name.clone(),
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         has_password: true,
         has_configured_password: true,
         enable_auto_login: false,
-        policy: UserPolicy {
-            is_administrator: user.is_admin,
-            ..Default::default()
-        },
-        configuration: UserConfiguration::default(),
+        policy: user_policy(state, &user).await?,
+        configuration: user_configuration(state, &user).await?,
     };
 
     let session_info = SessionInfo {
@@ -215,12 +509,12 @@ async fn authenticate_by_name(
         device_id: session.device_id,
     };
 
-    Ok(Json(AuthenticationResult {
+    Ok(AuthenticationResult {
         user: user_dto,
         session_info,
         access_token: session.token,
-        server_id: "jellyfin-rust-server".to_string(),
-    }))
+        server_id: server_id::get().to_string(),
+    })
 }
 
 async fn get_users(
@@ -242,22 +536,21 @@ async fn get_users(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let user_dtos: Vec<UserDto> = users
-        .into_iter()
-        .map(|u| UserDto {
+    let mut user_dtos = Vec::with_capacity(users.len());
+    for u in users {
+        let policy = user_policy(&state, &u).await?;
+        let configuration = user_configuration(&state, &u).await?;
+        user_dtos.push(UserDto {
             id: u.id,
             name: u.name,
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             has_password: true,
             has_configured_password: true,
             enable_auto_login: false,
-            policy: UserPolicy {
-                is_administrator: u.is_admin,
-                ..Default::default()
-            },
-            configuration: UserConfiguration::default(),
-        })
-        .collect();
+            policy,
+            configuration,
+        });
+    }
 
     Ok(Json(user_dtos))
 }
@@ -305,18 +598,17 @@ async fn get_current_user(
         .await
         .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
 
+    let policy = user_policy(&state, &user).await?;
+    let configuration = user_configuration(&state, &user).await?;
     Ok(Json(UserDto {
         id: user.id,
         name: user.name,
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         has_password: true,
         has_configured_password: true,
         enable_auto_login: false,
-        policy: UserPolicy {
-            is_administrator: user.is_admin,
-            ..Default::default()
-        },
-        configuration: UserConfiguration::default(),
+        policy,
+        configuration,
     }))
 }
 
@@ -352,18 +644,17 @@ async fn get_user_by_id(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))?;
 
+    let policy = user_policy(&state, &user).await?;
+    let configuration = user_configuration(&state, &user).await?;
     Ok(Json(UserDto {
         id: user.id,
         name: user.name,
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         has_password: true,
         has_configured_password: true,
         enable_auto_login: false,
-        policy: UserPolicy {
-            is_administrator: user.is_admin,
-            ..Default::default()
-        },
-        configuration: UserConfiguration::default(),
+        policy,
+        configuration,
     }))
 }
 
@@ -408,9 +699,7 @@ async fn delete_user(
     }
 
     // Delete user's sessions first
-    sqlx::query("DELETE FROM sessions WHERE user_id = ?")
-        .bind(&user_id)
-        .execute(&state.db)
+    auth::revoke_all_user_sessions(&state.db, &user_id)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -527,14 +816,315 @@ async fn create_user(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     tracing::info!("User '{}' created by admin {}", req.name, current_user.id);
+    events::publish(ServerEvent::UserCreated {
+        user_id: user_id.clone(),
+        user_name: req.name.clone(),
+    });
 
     Ok(Json(CreateUserResponse {
         id: user_id,
         name: req.name,
-        server_id: "jellyfin-rust-server".to_string(),
+        server_id: server_id::get().to_string(),
         has_password: !password.is_empty(),
         has_configured_password: !password.is_empty(),
         policy: UserPolicy::default(),
         configuration: UserConfiguration::default(),
     }))
 }
+
+/// The signed-in user and their token
+async fn require_auth(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<(User, String), (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    let user = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+    Ok((user, token))
+}
+
+async fn find_user(state: &AppState, user_id: &str) -> Result<User, (StatusCode, String)> {
+    sqlx::query_as("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "User not found".to_string()))
+}
+
+/// Request body for updating a user (clients send the whole UserDto; only Name is applied)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateUserRequest {
+    pub name: Option<String>,
+}
+
+/// POST /Users/:userId - Rename a user (admin, or the user themselves)
+async fn update_user(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(req): Json<UpdateUserRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (current_user, _) = require_auth(&state, &headers).await?;
+    if !current_user.is_admin && current_user.id != user_id {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    let user = find_user(&state, &user_id).await?;
+
+    let Some(name) = req.name.map(|n| n.trim().to_string()) else {
+        return Ok(StatusCode::NO_CONTENT);
+    };
+    if name.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Name is required".to_string()));
+    }
+    if name == user.name {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let taken: Option<(String,)> =
+        sqlx::query_as("SELECT id FROM users WHERE LOWER(name) = LOWER(?) AND id != ?")
+            .bind(&name)
+            .bind(&user_id)
+            .fetch_optional(&state.db)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if taken.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "A user with that name already exists".to_string(),
+        ));
+    }
+
+    sqlx::query("UPDATE users SET name = ? WHERE id = ?")
+        .bind(&name)
+        .bind(&user_id)
+        .execute(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    auth::invalidate_cached_user(&user_id);
+
+    tracing::info!(
+        "User '{}' renamed to '{}' by {}",
+        user.name,
+        name,
+        current_user.name
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Request body for changing a password
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdatePasswordRequest {
+    #[serde(alias = "CurrentPassword")]
+    pub current_pw: Option<String>,
+    pub new_pw: Option<String>,
+    /// Clear the password instead of setting one
+    #[serde(default)]
+    pub reset_password: bool,
+}
+
+/// POST /Users/:userId/Password - Change or reset a password
+///
+/// Users changing their own password must give the current one; admins can set
+/// anyone else's without it. The user's other sessions are signed out.
+async fn update_password(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(req): Json<UpdatePasswordRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (current_user, token) = require_auth(&state, &headers).await?;
+    let own_account = current_user.id == user_id;
+    if !current_user.is_admin && !own_account {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    let user = find_user(&state, &user_id).await?;
+
+    if own_account {
+        let current_pw = req.current_pw.as_deref().unwrap_or_default();
+        let valid = auth::verify_password(current_pw, &user.password_hash)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if !valid {
+            return Err((
+                StatusCode::FORBIDDEN,
+                "Current password is incorrect".to_string(),
+            ));
+        }
+    }
+
+    let new_pw = if req.reset_password {
+        String::new()
+    } else {
+        req.new_pw.ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "NewPw or ResetPassword is required".to_string(),
+            )
+        })?
+    };
+    auth::change_password(
+        &state.db,
+        &user_id,
+        &new_pw,
+        own_account.then_some(token.as_str()),
+    )
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "Password of '{}' {} by {}",
+        user.name,
+        if req.reset_password {
+            "reset"
+        } else {
+            "changed"
+        },
+        current_user.name
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Request body for updating a user's configuration (clients send the whole
+/// UserConfiguration; only the library order and visibility are applied)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateConfigurationRequest {
+    pub ordered_views: Option<Vec<String>>,
+    pub my_media_excludes: Option<Vec<String>>,
+    pub latest_items_excludes: Option<Vec<String>>,
+}
+
+/// POST /Users/:userId/Configuration - Set a user's library order and visibility (admin, or the user themselves)
+async fn update_configuration(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(req): Json<UpdateConfigurationRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (current_user, _) = require_auth(&state, &headers).await?;
+    if !current_user.is_admin && current_user.id != user_id {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    find_user(&state, &user_id).await?;
+
+    let mut views = library_views::load(&state.db, &user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(ordered) = req.ordered_views {
+        views.ordered = ordered;
+    }
+    if let Some(hidden) = req.my_media_excludes {
+        views.hidden = hidden;
+    }
+    if let Some(latest_excluded) = req.latest_items_excludes {
+        views.latest_excluded = latest_excluded;
+    }
+    library_views::save(&state.db, &user_id, &views)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    super::home::invalidate_user_lists(&user_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Request body for updating a user's policy (fields left out keep their value)
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdatePolicyRequest {
+    pub is_administrator: Option<bool>,
+    pub is_disabled: Option<bool>,
+    pub enable_all_folders: Option<bool>,
+    pub enabled_folders: Option<Vec<String>>,
+    /// Null removes the limit
+    #[serde(default, deserialize_with = "super::library::present")]
+    pub max_parental_rating: Option<Option<i32>>,
+    pub blocked_tags: Option<Vec<String>>,
+}
+
+/// POST /Users/:userId/Policy - Set a user's administrator rights, disabled state, library access and parental controls (admin)
+async fn update_policy(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(req): Json<UpdatePolicyRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let (current_user, _) = require_auth(&state, &headers).await?;
+    if !current_user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    let user = find_user(&state, &user_id).await?;
+
+    // An admin can't lock themselves out
+    if current_user.id == user_id
+        && (req.is_administrator == Some(false) || req.is_disabled == Some(true))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Cannot remove your own administrator rights or disable your own account".to_string(),
+        ));
+    }
+
+    let mut tx = state
+        .db
+        .begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(is_admin) = req.is_administrator {
+        sqlx::query("UPDATE users SET is_admin = ? WHERE id = ?")
+            .bind(is_admin)
+            .bind(&user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+    if req.enable_all_folders.is_some() || req.enabled_folders.is_some() {
+        let current = crate::db::get_library_access(&state.db, &user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let all = req.enable_all_folders.unwrap_or(current.is_none());
+        let folders = req.enabled_folders.clone().or(current).unwrap_or_default();
+        crate::db::set_library_access(&mut tx, &user_id, (!all).then_some(folders.as_slice()))
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+    if req.max_parental_rating.is_some() || req.blocked_tags.is_some() {
+        let current = parental_controls::load(&state.db, &user_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let parental = parental_controls::ParentalPolicy {
+            max_parental_rating: req
+                .max_parental_rating
+                .unwrap_or(current.max_parental_rating),
+            blocked_tags: req.blocked_tags.clone().unwrap_or(current.blocked_tags),
+        };
+        parental_controls::save(&mut tx, &user_id, &parental)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if let Some(disabled) = req.is_disabled {
+        auth::set_disabled(&state.db, &user_id, disabled)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+    auth::invalidate_cached_user(&user_id);
+    super::home::invalidate_user_lists(&user_id);
+
+    tracing::info!(
+        "Policy of '{}' updated by {}: {:?}",
+        user.name,
+        current_user.name,
+        req
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -1,18 +1,30 @@
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, HeaderValue, StatusCode},
-    response::Response,
+    extract::{Path, Query, RawQuery, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     routing::get,
     Router,
 };
+use futures::StreamExt;
 use serde::Deserialize;
-use std::sync::Arc;
+use std::{process::Stdio, sync::Arc};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::process::Command;
 use tokio_util::io::ReaderStream;
 
-use crate::{models::MediaItem, services::auth, AppState};
+use crate::{
+    models::MediaItem,
+    services::{
+        auth,
+        external_streams::{self, RemuxContainer},
+        live_tv, mediainfo,
+        transcoding::{self, Rendition, TranscodeSpec},
+    },
+    time::Ticks,
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -23,6 +35,16 @@ pub fn routes() -> Router<Arc<AppState>> {
         // Jellyfin clients also use these endpoints
         .route("/:id/original", get(stream_video))
         .route("/:id/original.:container", get(stream_video))
+        // Copy-only remux with external subtitle/audio files, or into a
+        // container the client plays
+        .route("/:id/remux.:container", get(remux_video))
+        // HLS transcoding for clients that can't direct play the file
+        .route("/:id/master.m3u8", get(get_master_playlist))
+        .route("/:id/hls1/:rendition/:file", get(get_hls_file))
+        .route(
+            "/ActiveEncodings",
+            axum::routing::delete(stop_active_encodings),
+        )
         // Trickplay endpoints (seek preview thumbnails)
         .route(
             "/:id/Trickplay/:width/tiles.m3u8",
@@ -46,6 +68,43 @@ pub struct StreamQuery {
     // We ignore most of these since we only do direct play
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RemuxQuery {
+    pub media_source_id: Option<String>,
+    pub audio_stream_index: Option<i32>,
+    pub subtitle_stream_index: Option<i32>,
+    pub start_time_ticks: Option<i64>,
+    #[serde(rename = "api_key")]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct HlsQuery {
+    pub media_source_id: Option<String>,
+    pub play_session_id: Option<String>,
+    pub device_id: Option<String>,
+    pub max_streaming_bitrate: Option<u32>,
+    pub audio_stream_index: Option<i32>,
+    #[serde(rename = "api_key")]
+    pub api_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HlsPath {
+    id: String,
+    rendition: String,
+    file: String, // "main.m3u8" or e.g. "12.ts"
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveEncodingsQuery {
+    pub device_id: Option<String>,
+    pub play_session_id: Option<String>,
+}
+
 async fn require_auth(
     state: &AppState,
     headers: &HeaderMap,
@@ -67,7 +126,7 @@ async fn require_auth(
 }
 
 /// Get the MIME type for a video file based on extension
-fn get_content_type(path: &str) -> &'static str {
+pub(crate) fn get_content_type(path: &str) -> &'static str {
     let ext = path.rsplit('.').next().unwrap_or("").to_lowercase();
     match ext.as_str() {
         "mp4" | "m4v" => "video/mp4",
@@ -85,44 +144,100 @@ fn get_content_type(path: &str) -> &'static str {
     }
 }
 
-/// Parse HTTP Range header (e.g., "bytes=0-1023" or "bytes=1024-")
-fn parse_range_header(range_header: Option<&HeaderValue>, file_size: u64) -> Option<(u64, u64)> {
-    let range_str = range_header?.to_str().ok()?;
+/// What to send for a request's Range header (RFC 7233)
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// No usable Range header: the whole file (200)
+    Full,
+    /// Inclusive first and last byte (206)
+    Partial(u64, u64),
+    /// The range starts past the end of the file (416)
+    Unsatisfiable,
+}
 
-    if !range_str.starts_with("bytes=") {
-        return None;
+/// Parse a single byte range ("bytes=0-1023", "bytes=1024-" or "bytes=-500")
+///
+/// Malformed headers, other units and multiple ranges are ignored, which the
+/// RFC allows, so the whole file is served.
+fn parse_range_header(range_header: Option<&HeaderValue>, file_size: u64) -> ByteRange {
+    let Some(range) = range_header
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().strip_prefix("bytes="))
+    else {
+        return ByteRange::Full;
+    };
+    if range.contains(',') {
+        return ByteRange::Full;
     }
-
-    let range = range_str.trim_start_matches("bytes=");
-    let parts: Vec<&str> = range.split('-').collect();
-
-    if parts.len() != 2 {
-        return None;
+    let Some((first, last)) = range.trim().split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (first, last) = (first.trim(), last.trim());
+
+    if first.is_empty() {
+        // Suffix range: "-500" means the last 500 bytes
+        return match last.parse::<u64>() {
+            Ok(0) => ByteRange::Unsatisfiable,
+            Ok(_) if file_size == 0 => ByteRange::Unsatisfiable,
+            Ok(length) => ByteRange::Partial(file_size.saturating_sub(length), file_size - 1),
+            Err(_) => ByteRange::Full,
+        };
     }
 
-    let start: u64 = if parts[0].is_empty() {
-        // Suffix range: "-500" means last 500 bytes
-        let suffix_len: u64 = parts[1].parse().ok()?;
-        file_size.saturating_sub(suffix_len)
-    } else {
-        parts[0].parse().ok()?
+    let Ok(start) = first.parse::<u64>() else {
+        return ByteRange::Full;
     };
-
-    let end: u64 = if parts[1].is_empty() {
-        file_size - 1
+    let end = if last.is_empty() {
+        None
     } else {
-        parts[1].parse().ok()?
+        match last.parse::<u64>() {
+            Ok(end) if end >= start => Some(end),
+            _ => return ByteRange::Full,
+        }
     };
-
-    // Validate range
-    if start > end || start >= file_size {
-        return None;
+    if start >= file_size {
+        return ByteRange::Unsatisfiable;
     }
+    // The last byte may be past the end of the file
+    let end = end.map_or(file_size - 1, |end| end.min(file_size - 1));
+    ByteRange::Partial(start, end)
+}
+
+/// Validators for a file, so a client resuming a download with If-Range gets
+/// the rest of the same file rather than part of a replaced one
+struct FileValidators {
+    etag: String,
+    last_modified: Option<String>,
+}
 
-    // Clamp end to file size
-    let end = end.min(file_size - 1);
+impl FileValidators {
+    fn new(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata
+            .modified()
+            .ok()
+            .map(chrono::DateTime::<chrono::Utc>::from);
+        Self {
+            etag: format!(
+                "\"{:x}-{:x}\"",
+                metadata.len(),
+                modified.map_or(0, |m| m.timestamp())
+            ),
+            last_modified: modified.map(|m| m.format("%a, %d %b %Y %H:%M:%S GMT").to_string()),
+        }
+    }
 
-    Some((start, end))
+    /// Whether an If-Range header still matches the file (weak tags never do)
+    fn if_range_matches(&self, if_range: &HeaderValue) -> bool {
+        let Ok(value) = if_range.to_str() else {
+            return false;
+        };
+        let value = value.trim();
+        if value.starts_with('"') {
+            value == self.etag
+        } else {
+            !value.starts_with("W/") && self.last_modified.as_deref() == Some(value)
+        }
+    }
 }
 
 async fn stream_video(
@@ -133,13 +248,15 @@ async fn stream_video(
 ) -> Result<Response, (StatusCode, String)> {
     let _user = require_auth(&state, &headers, query.api_key.as_deref()).await?;
 
-    // Get the media item
-    let item: MediaItem = sqlx::query_as("SELECT * FROM media_items WHERE id = ?")
-        .bind(&path_params.id)
-        .fetch_optional(&state.db)
+    // Live TV channels are played from the tuner's stream
+    let channel = live_tv::find_channel(&state.db, &path_params.id)
         .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))?;
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(channel) = channel {
+        return Ok(Redirect::temporary(&channel.stream_url).into_response());
+    }
+
+    let item = load_source(&state, &path_params.id, query.media_source_id.as_deref()).await?;
 
     // Get the file path
     let file_path = item
@@ -147,6 +264,334 @@ async fn stream_video(
         .as_ref()
         .ok_or_else(|| (StatusCode::NOT_FOUND, "Item has no file path".to_string()))?;
 
+    // Items from .strm files are played from their remote URL
+    if let Some(ref url) = item.stream_url {
+        return Ok(Redirect::temporary(url).into_response());
+    }
+
+    serve_file(&headers, file_path, get_content_type(file_path)).await
+}
+
+/// The media item, or the alternate version of it the client picked
+async fn load_source(
+    state: &AppState,
+    id: &str,
+    media_source_id: Option<&str>,
+) -> Result<MediaItem, (StatusCode, String)> {
+    let source_id = media_source_id.filter(|id| !id.is_empty()).unwrap_or(id);
+    sqlx::query_as("SELECT * FROM media_items WHERE id = ? AND (id = ? OR version_of = ?)")
+        .bind(source_id)
+        .bind(id)
+        .bind(id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item not found".to_string()))
+}
+
+/// GET /Videos/:id/remux.mkv - Video remuxed with external subtitle/audio files
+/// GET /Videos/:id/remux.{mp4,ts,webm} - Video copied into another container
+///
+/// PlaybackInfo hands this out as the transcoding URL when a selected external
+/// file can't be delivered separately, or when the client plays the file's
+/// codecs but not its container. Streams are copied, not transcoded.
+async fn remux_video(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    Path((id, container)): Path<(String, String)>,
+    Query(query): Query<RemuxQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let _user = require_auth(&state, &headers, query.api_key.as_deref()).await?;
+    let container = RemuxContainer::from_name(&container)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Unknown container".to_string()))?;
+    let item = load_source(&state, &id, query.media_source_id.as_deref()).await?;
+
+    let file_path = item
+        .path
+        .as_deref()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item has no file path".to_string()))?;
+    if item.stream_url.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Remote streams can't be remuxed".to_string(),
+        ));
+    }
+    let file_path = std::path::Path::new(file_path);
+
+    // Only external files are muxed in; embedded streams are in the video already
+    let (pool, item_id) = (&state.db, item.id.as_str());
+    let external = |index: Option<i32>, is_audio: bool| async move {
+        match index.filter(|i| *i >= external_streams::FIRST_INDEX) {
+            Some(index) => external_streams::find_index(pool, item_id, file_path, index)
+                .await
+                .filter(|f| f.is_audio == is_audio)
+                .map(|f| Some(f.path))
+                .ok_or_else(|| {
+                    (
+                        StatusCode::NOT_FOUND,
+                        format!("No external stream {}", index),
+                    )
+                }),
+            None => Ok(None),
+        }
+    };
+    let audio = external(query.audio_stream_index, true).await?;
+    let subtitle = external(query.subtitle_stream_index, false).await?;
+    if subtitle.is_some() && container != RemuxContainer::Mkv {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "External subtitles can only be remuxed into Matroska".to_string(),
+        ));
+    }
+    let audio_stream_index = query
+        .audio_stream_index
+        .filter(|i| (0..external_streams::FIRST_INDEX).contains(i));
+
+    let response = Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, container.content_type())
+        .header(header::ACCEPT_RANGES, "none")
+        .header(header::CACHE_CONTROL, "no-cache");
+    if method == Method::HEAD {
+        return Ok(response.body(Body::empty()).unwrap());
+    }
+
+    let args = external_streams::remux_args(
+        file_path,
+        audio.as_deref(),
+        audio_stream_index,
+        subtitle.as_deref(),
+        query.start_time_ticks.map(Ticks),
+        container,
+    );
+    let mut child = Command::new(mediainfo::find_ffmpeg())
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to run ffmpeg: {}", e),
+            )
+        })?;
+
+    let stdout = child.stdout.take().ok_or_else(|| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "ffmpeg produced no output".to_string(),
+        )
+    })?;
+
+    tracing::debug!(
+        "Remuxing {} into {} with external audio {:?} and subtitle {:?}",
+        file_path.display(),
+        container.extension(),
+        audio,
+        subtitle
+    );
+
+    // The stream owns the child so ffmpeg is killed when the client disconnects
+    let stream = ReaderStream::new(stdout).map(move |chunk| {
+        let _ = &child;
+        chunk
+    });
+
+    Ok(response.body(Body::from_stream(stream)).unwrap())
+}
+
+/// A source that can be transcoded: a local file, with transcoding enabled
+async fn load_transcode_source(
+    state: &AppState,
+    id: &str,
+    query: &HlsQuery,
+) -> Result<(MediaItem, String), (StatusCode, String)> {
+    if !state.config.transcoding.enabled {
+        return Err((StatusCode::FORBIDDEN, "Transcoding is disabled".to_string()));
+    }
+    let item = load_source(state, id, query.media_source_id.as_deref()).await?;
+    if item.stream_url.is_some() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Remote streams can't be transcoded".to_string(),
+        ));
+    }
+    let file_path = item
+        .path
+        .clone()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Item has no file path".to_string()))?;
+    Ok((item, file_path))
+}
+
+fn playlist_response(playlist: String) -> Response {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, "application/vnd.apple.mpegurl")
+        .header(header::CACHE_CONTROL, "no-cache")
+        .body(Body::from(playlist))
+        .unwrap()
+}
+
+/// GET /Videos/:id/master.m3u8 - HLS master playlist of transcoded renditions
+///
+/// PlaybackInfo hands this out as the transcoding URL for sources the client
+/// can't direct play. Renditions go up to the source's height and stay within
+/// MaxStreamingBitrate.
+async fn get_master_playlist(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Query(query): Query<HlsQuery>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let _user = require_auth(&state, &headers, query.api_key.as_deref()).await?;
+    let (item, _) = load_transcode_source(&state, &id, &query).await?;
+
+    let height = item.height.and_then(|h| u32::try_from(h).ok());
+    let width = item.width.and_then(|w| u32::try_from(w).ok());
+    let renditions = transcoding::ladder(height, query.max_streaming_bitrate);
+    let playlist = transcoding::master_playlist(
+        &renditions,
+        width.zip(height),
+        raw_query.as_deref().unwrap_or_default(),
+    );
+    Ok(playlist_response(playlist))
+}
+
+/// GET /Videos/:id/hls1/:rendition/main.m3u8 - Segment playlist of a rendition
+/// GET /Videos/:id/hls1/:rendition/:n.ts - Transcoded segment
+///
+/// Segments are transcoded as they're requested; the first request for a
+/// session starts ffmpeg and waits for its segment.
+async fn get_hls_file(
+    State(state): State<Arc<AppState>>,
+    method: Method,
+    headers: HeaderMap,
+    Path(path): Path<HlsPath>,
+    Query(query): Query<HlsQuery>,
+    RawQuery(raw_query): RawQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let user = require_auth(&state, &headers, query.api_key.as_deref()).await?;
+    let (item, file_path) = load_transcode_source(&state, &path.id, &query).await?;
+    let rendition = Rendition::parse(&path.rendition)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Unknown rendition".to_string()))?;
+    let config = &state.config.transcoding;
+    let file_path = std::path::Path::new(&file_path);
+
+    if path.file == "main.m3u8" {
+        let duration = match item.runtime_ticks.filter(|t| *t > 0) {
+            Some(ticks) => Ticks(ticks),
+            None => mediainfo::extract_media_info_async(file_path)
+                .await
+                .ok()
+                .and_then(|info| info.duration_ticks)
+                .map(Ticks)
+                .ok_or_else(|| {
+                    (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Unknown duration".to_string(),
+                    )
+                })?,
+        };
+        let playlist = transcoding::media_playlist(
+            duration,
+            config.segment_seconds.max(1),
+            raw_query.as_deref().unwrap_or_default(),
+        );
+        return Ok(playlist_response(playlist));
+    }
+
+    let segment: u32 = path
+        .file
+        .strip_suffix(".ts")
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Unknown file".to_string()))?;
+
+    // HEAD requests don't start a transcode
+    if method == Method::HEAD {
+        return Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "video/mp2t")
+            .body(Body::empty())
+            .unwrap());
+    }
+
+    // Only external audio files are added as a second input
+    let (audio_file, audio_stream_index) = match query.audio_stream_index {
+        Some(index) if index >= external_streams::FIRST_INDEX => {
+            let file = external_streams::find_index(&state.db, &item.id, file_path, index)
+                .await
+                .filter(|f| f.is_audio)
+                .ok_or_else(|| {
+                    (
+                        StatusCode::NOT_FOUND,
+                        format!("No external stream {}", index),
+                    )
+                })?;
+            (Some(file.path), None)
+        }
+        index => (None, index.filter(|i| *i >= 0)),
+    };
+    let spec = TranscodeSpec {
+        input: file_path.to_path_buf(),
+        audio_file,
+        audio_stream_index,
+        rendition,
+    };
+
+    // One transcode per play session (or device, for clients that send none)
+    let session = query
+        .play_session_id
+        .clone()
+        .or_else(|| query.device_id.clone())
+        .unwrap_or_else(|| format!("user-{}", user.id));
+    let segment_path = transcoding::segment(
+        config,
+        &state.config.paths.transcode_dir(),
+        &session,
+        query.device_id.as_deref(),
+        &spec,
+        segment,
+    )
+    .await
+    .map_err(|e| {
+        tracing::warn!("Transcoding {} failed: {}", file_path.display(), e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?
+    .ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Too many transcodes running".to_string(),
+        )
+    })?;
+
+    serve_file(&headers, &segment_path.to_string_lossy(), "video/mp2t").await
+}
+
+/// DELETE /Videos/ActiveEncodings - Stop the transcode of a play session or device
+async fn stop_active_encodings(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<ActiveEncodingsQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let _user = require_auth(&state, &headers, None).await?;
+    let stopped =
+        transcoding::stop(query.play_session_id.as_deref(), query.device_id.as_deref()).await;
+    if stopped > 0 {
+        tracing::debug!("Stopped {} transcode(s)", stopped);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Serve a file from disk, honouring Range and If-Range for seeking and resuming
+pub async fn serve_file(
+    headers: &HeaderMap,
+    file_path: &str,
+    content_type: &'static str,
+) -> Result<Response, (StatusCode, String)> {
     // Open the file
     let file = File::open(file_path)
         .await
@@ -160,13 +605,24 @@ async fn stream_video(
     })?;
 
     let file_size = metadata.len();
-    let content_type = get_content_type(file_path);
+    let validators = FileValidators::new(&metadata);
+
+    // A range only applies while If-Range still matches the file
+    let range = match headers.get(header::IF_RANGE) {
+        Some(if_range) if !validators.if_range_matches(if_range) => ByteRange::Full,
+        _ => parse_range_header(headers.get(header::RANGE), file_size),
+    };
 
-    // Check for Range header
-    let range = parse_range_header(headers.get(header::RANGE), file_size);
+    let mut response = Response::builder()
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, &validators.etag)
+        .header(header::CACHE_CONTROL, "no-cache");
+    if let Some(ref last_modified) = validators.last_modified {
+        response = response.header(header::LAST_MODIFIED, last_modified);
+    }
 
     match range {
-        Some((start, end)) => {
+        ByteRange::Partial(start, end) => {
             // Partial content response (206)
             let length = end - start + 1;
 
@@ -194,7 +650,7 @@ async fn stream_video(
             let stream = ReaderStream::new(limited);
             let body = Body::from_stream(stream);
 
-            Ok(Response::builder()
+            Ok(response
                 .status(StatusCode::PARTIAL_CONTENT)
                 .header(header::CONTENT_TYPE, content_type)
                 .header(header::CONTENT_LENGTH, length)
@@ -202,24 +658,33 @@ async fn stream_video(
                     header::CONTENT_RANGE,
                     format!("bytes {}-{}/{}", start, end, file_size),
                 )
-                .header(header::ACCEPT_RANGES, "bytes")
-                .header(header::CACHE_CONTROL, "no-cache")
                 .body(body)
                 .unwrap())
         }
-        None => {
+        ByteRange::Unsatisfiable => {
+            tracing::debug!(
+                "Unsatisfiable range {:?} for {} ({} bytes)",
+                headers.get(header::RANGE),
+                file_path,
+                file_size
+            );
+            Ok(response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", file_size))
+                .body(Body::empty())
+                .unwrap())
+        }
+        ByteRange::Full => {
             // Full content response (200)
             tracing::debug!("Serving full file {} ({} bytes)", file_path, file_size);
 
             let stream = ReaderStream::new(file);
             let body = Body::from_stream(stream);
 
-            Ok(Response::builder()
+            Ok(response
                 .status(StatusCode::OK)
                 .header(header::CONTENT_TYPE, content_type)
                 .header(header::CONTENT_LENGTH, file_size)
-                .header(header::ACCEPT_RANGES, "bytes")
-                .header(header::CACHE_CONTROL, "no-cache")
                 .body(body)
                 .unwrap())
         }
@@ -299,3 +764,61 @@ async fn get_trickplay_tile(
         "Trickplay not available for this item".to_string(),
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(header: &str, file_size: u64) -> ByteRange {
+        parse_range_header(Some(&HeaderValue::from_str(header).unwrap()), file_size)
+    }
+
+    #[test]
+    fn test_parse_range_header() {
+        assert_eq!(parse_range_header(None, 1000), ByteRange::Full);
+        assert_eq!(range("bytes=0-99", 1000), ByteRange::Partial(0, 99));
+        // Open-ended and suffix ranges
+        assert_eq!(range("bytes=900-", 1000), ByteRange::Partial(900, 999));
+        assert_eq!(range("bytes=-100", 1000), ByteRange::Partial(900, 999));
+        assert_eq!(range("bytes=-5000", 1000), ByteRange::Partial(0, 999));
+        // The last byte is clamped to the end of the file
+        assert_eq!(range("bytes=500-5000", 1000), ByteRange::Partial(500, 999));
+
+        assert_eq!(range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=2000-3000", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(range("bytes=-10", 0), ByteRange::Unsatisfiable);
+
+        // Multiple ranges, other units and malformed values get the whole file
+        assert_eq!(range("bytes=0-9,20-29", 1000), ByteRange::Full);
+        assert_eq!(range("items=0-9", 1000), ByteRange::Full);
+        assert_eq!(range("bytes=9-0", 1000), ByteRange::Full);
+        assert_eq!(range("bytes=abc", 1000), ByteRange::Full);
+        assert_eq!(range("bytes=a-9", 1000), ByteRange::Full);
+        assert_eq!(range("bytes=-x", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn test_if_range_matches() {
+        let validators = FileValidators {
+            etag: "\"3e8-5f5e100\"".to_string(),
+            last_modified: Some("Sat, 03 Mar 1973 09:46:40 GMT".to_string()),
+        };
+        let matches =
+            |value: &'static str| validators.if_range_matches(&HeaderValue::from_static(value));
+
+        assert!(matches("\"3e8-5f5e100\""));
+        assert!(matches("Sat, 03 Mar 1973 09:46:40 GMT"));
+        // A changed file no longer matches, and weak tags never do
+        assert!(!matches("\"3e8-5f5e101\""));
+        assert!(!matches("Sun, 04 Mar 1973 09:46:40 GMT"));
+        assert!(!matches("W/\"3e8-5f5e100\""));
+
+        let unknown_date = FileValidators {
+            last_modified: None,
+            ..validators
+        };
+        assert!(!unknown_date
+            .if_range_matches(&HeaderValue::from_static("Sat, 03 Mar 1973 09:46:40 GMT")));
+    }
+}
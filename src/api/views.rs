@@ -9,7 +9,11 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
-use crate::{models::Library, services::auth, AppState};
+use crate::{
+    models::Library,
+    services::{auth, library_images, library_views, live_tv, server_id},
+    AppState,
+};
 
 use super::users::parse_emby_auth_header;
 
@@ -94,20 +98,45 @@ async fn require_auth(
         .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))
 }
 
+/// Map a library type to the collection type clients expect on its CollectionFolder
+pub(crate) fn collection_type_for(library_type: &str) -> String {
+    match library_type {
+        "tvshows" | "TvShows" => "tvshows",
+        "movies" | "Movies" => "movies",
+        "music" | "Music" => "music",
+        _ => "mixed",
+    }
+    .to_string()
+}
+
 /// GET /UserViews
-/// Returns the library views (sections) for the home screen
+/// Returns the library views (sections) for the home screen, in the user's
+/// order and without the libraries they hid (unless includeHidden is set)
 async fn get_user_views(
     State(state): State<Arc<AppState>>,
     headers: HeaderMap,
-    Query(_query): Query<UserViewsQuery>,
+    Query(query): Query<UserViewsQuery>,
 ) -> Result<Json<UserViewsResponse>, (StatusCode, String)> {
-    let _user = require_auth(&state, &headers).await?;
+    let user = require_auth(&state, &headers).await?;
 
-    // Get all libraries
-    let libraries: Vec<Library> = sqlx::query_as("SELECT * FROM libraries ORDER BY name")
+    // Get the libraries the user may see
+    let mut libraries: Vec<Library> = sqlx::query_as("SELECT * FROM libraries ORDER BY name")
         .fetch_all(&state.db)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let access = crate::db::get_library_access(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Some(access) = access {
+        libraries.retain(|lib| access.contains(&lib.id));
+    }
+    let views = library_views::load(&state.db, &user.id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if query.include_hidden != Some(true) {
+        libraries.retain(|lib| !views.is_hidden(&lib.id));
+    }
+    views.sort(&mut libraries, |lib| &lib.id);
 
     let mut items = Vec::new();
 
@@ -122,19 +151,14 @@ async fn get_user_views(
         .unwrap_or((0,));
 
         // Map library type to collection type
-        let collection_type = match lib.library_type.as_str() {
-            "tvshows" | "TvShows" => Some("tvshows".to_string()),
-            "movies" | "Movies" => Some("movies".to_string()),
-            "music" | "Music" => Some("music".to_string()),
-            _ => Some("mixed".to_string()),
-        };
+        let collection_type = Some(collection_type_for(&lib.library_type));
 
         items.push(UserViewDto {
             id: lib.id.clone(),
             name: lib.name.clone(),
             item_type: "CollectionFolder".to_string(),
             collection_type,
-            server_id: "jellyfin-rust-server".to_string(),
+            server_id: server_id::get().to_string(),
             is_folder: true,
             etag: None,
             date_created: Some(lib.created_at.clone()),
@@ -147,7 +171,43 @@ async fn get_user_views(
             child_count: Some(child_count.0),
             display_preferences_id: lib.id.clone(),
             primary_image_aspect_ratio: None,
-            image_tags: Some(ImageTagsView::default()),
+            image_tags: Some(ImageTagsView {
+                primary: library_images::image_tag(
+                    &state.db,
+                    &state.config.paths.image_cache_dir(),
+                    &lib.id,
+                    "Primary",
+                )
+                .await,
+            }),
+        });
+    }
+
+    // Live TV follows the libraries once the tuner has channels
+    let has_channels = live_tv::has_channels(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if has_channels {
+        let id = live_tv::view_id();
+        items.push(UserViewDto {
+            id: id.clone(),
+            name: "Live TV".to_string(),
+            item_type: "UserView".to_string(),
+            collection_type: Some("livetv".to_string()),
+            server_id: server_id::get().to_string(),
+            is_folder: true,
+            etag: None,
+            date_created: None,
+            can_delete: false,
+            can_download: false,
+            sort_name: Some("Live TV".to_string()),
+            external_urls: None,
+            path: None,
+            enable_media_source_display: false,
+            child_count: None,
+            display_preferences_id: id,
+            primary_image_aspect_ratio: None,
+            image_tags: None,
         });
     }
 
@@ -0,0 +1,125 @@
+// Watch state import API
+// Lets admins bring a user's played/resume state and favorites over from Plex or Kodi
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::events::{self, ServerEvent};
+use crate::services::watch_import::{
+    self, ImportOptions, ImportSummary, PathMapping, WatchStateSource,
+};
+use crate::{services::auth, AppState};
+
+use super::users::parse_emby_auth_header;
+
+/// Routes mounted at /Users/:userId/WatchStateImport
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new().route("/", post(import_watch_state))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WatchStateImportRequest {
+    /// Plex library database, Kodi MyVideos database, or Kodi videodb.xml/favourites.xml
+    /// (a path on the server)
+    pub path: String,
+    /// Plex or Kodi; detected from the file when omitted
+    #[serde(default)]
+    pub source: Option<WatchStateSource>,
+    /// Plex account whose state is imported (1 is the server owner)
+    #[serde(default)]
+    pub plex_account_id: Option<i64>,
+    /// Prefix rewrites from the other server's paths to ours
+    #[serde(default)]
+    pub path_mappings: Vec<PathMapping>,
+    /// Ratings (0-10) at or above this become favorites, null to skip
+    #[serde(default = "default_favorite_min_rating")]
+    pub favorite_min_rating: Option<f64>,
+    /// Report what would be imported without writing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_favorite_min_rating() -> Option<f64> {
+    Some(10.0)
+}
+
+async fn require_admin(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let (_, _, _, token) = parse_emby_auth_header(headers)
+        .ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing auth header".to_string()))?;
+
+    let token = token.ok_or_else(|| (StatusCode::UNAUTHORIZED, "Missing token".to_string()))?;
+
+    let user = auth::validate_session(&state.db, &token)
+        .await
+        .map_err(|e| (StatusCode::UNAUTHORIZED, e.to_string()))?;
+
+    if !user.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin required".to_string()));
+    }
+    Ok(())
+}
+
+/// POST /Users/{userId}/WatchStateImport - Import played/resume state and favorites from Plex or Kodi (admin only)
+async fn import_watch_state(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    Json(request): Json<WatchStateImportRequest>,
+) -> Result<Json<ImportSummary>, (StatusCode, String)> {
+    require_admin(&state, &headers).await?;
+
+    let user_exists: Option<(String,)> = sqlx::query_as("SELECT id FROM users WHERE id = ?")
+        .bind(&user_id)
+        .fetch_optional(&state.db)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if user_exists.is_none() {
+        return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+    }
+
+    let file = std::path::Path::new(&request.path);
+    if !file.is_file() {
+        return Err((StatusCode::BAD_REQUEST, "File not found".to_string()));
+    }
+
+    let records = watch_import::read_watch_records(file, request.source, request.plex_account_id)
+        .await
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{:#}", e)))?;
+
+    let options = ImportOptions {
+        path_mappings: request.path_mappings,
+        favorite_min_rating: request.favorite_min_rating,
+        dry_run: request.dry_run,
+    };
+    let summary = watch_import::import_watch_state(&state.db, &user_id, &records, &options)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!(
+        "Watch state import for user {}{}: {} entries, {} matched ({} played, {} in progress, {} favorites), {} unmatched",
+        user_id,
+        if request.dry_run { " (dry run)" } else { "" },
+        summary.total,
+        summary.matched,
+        summary.played,
+        summary.in_progress,
+        summary.favorites,
+        summary.unmatched
+    );
+
+    if !summary.changed_item_ids.is_empty() {
+        events::publish(ServerEvent::UserDataChanged {
+            user_id,
+            item_ids: summary.changed_item_ids.clone(),
+        });
+    }
+
+    Ok(Json(summary))
+}
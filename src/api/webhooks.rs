@@ -0,0 +1,200 @@
+// Webhooks API
+// Import notifications from Sonarr/Radarr trigger a targeted scan of the imported path
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::post,
+    Json, Router,
+};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::{Arc, LazyLock};
+
+use crate::{scanner, AppState};
+
+use super::users::require_api_key_or_admin;
+
+/// Serializes webhook scans so a season pack import doesn't race itself
+/// into duplicate series/episodes
+static WEBHOOK_SCAN_LOCK: LazyLock<tokio::sync::Mutex<()>> =
+    LazyLock::new(|| tokio::sync::Mutex::new(()));
+
+pub fn routes() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/Sonarr", post(sonarr_webhook))
+        .route("/Radarr", post(radarr_webhook))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookQuery {
+    #[serde(alias = "api_key", rename = "apiKey")]
+    pub api_key: Option<String>,
+}
+
+/// File reference as sent by Sonarr/Radarr
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookFile {
+    pub path: Option<String>,
+    pub relative_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SonarrSeries {
+    pub path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SonarrPayload {
+    pub event_type: String,
+    pub series: Option<SonarrSeries>,
+    pub episode_file: Option<WebhookFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RadarrMovie {
+    pub folder_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RadarrPayload {
+    pub event_type: String,
+    pub movie: Option<RadarrMovie>,
+    pub movie_file: Option<WebhookFile>,
+}
+
+/// Resolve the path to scan: the imported file if given, otherwise the item folder
+fn resolve_target(folder: Option<&str>, file: Option<&WebhookFile>) -> Option<PathBuf> {
+    if let Some(file) = file {
+        if let Some(ref path) = file.path {
+            return Some(PathBuf::from(path));
+        }
+        if let (Some(folder), Some(ref relative)) = (folder, &file.relative_path) {
+            return Some(PathBuf::from(folder).join(relative));
+        }
+    }
+    folder.map(PathBuf::from)
+}
+
+/// Whether an event type changes files on disk and warrants a scan
+fn is_scan_event(event_type: &str) -> bool {
+    matches!(
+        event_type,
+        "Download"
+            | "Rename"
+            | "EpisodeFileDelete"
+            | "SeriesDelete"
+            | "MovieFileDelete"
+            | "MovieDelete"
+    )
+}
+
+/// Kick off a targeted scan in the background so the webhook returns immediately
+fn spawn_targeted_scan(state: &AppState, target: PathBuf, source: &'static str) {
+    let pool = state.db.clone();
+    let cache_dir = state.config.paths.cache_dir.clone();
+    let anime_db_enabled = state.config.anime_db_enabled;
+    let fetch_episode_metadata = state.config.fetch_episode_metadata;
+
+    tokio::spawn(async move {
+        let _guard = WEBHOOK_SCAN_LOCK.lock().await;
+        if let Err(e) = scanner::scan_path(
+            &pool,
+            &target,
+            cache_dir,
+            Some(anime_db_enabled),
+            fetch_episode_metadata,
+        )
+        .await
+        {
+            tracing::warn!(
+                "{} webhook scan of '{}' failed: {}",
+                source,
+                target.display(),
+                e
+            );
+        }
+    });
+}
+
+/// POST /Webhooks/Sonarr - Sonarr "On Import"/"On Rename"/delete notifications
+async fn sonarr_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<WebhookQuery>,
+    Json(payload): Json<SonarrPayload>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_api_key_or_admin(&state, &headers, query.api_key.as_deref()).await?;
+
+    if payload.event_type == "Test" {
+        tracing::info!("Received Sonarr test webhook");
+        return Ok(StatusCode::OK);
+    }
+
+    if !is_scan_event(&payload.event_type) {
+        tracing::debug!("Ignoring Sonarr webhook event: {}", payload.event_type);
+        return Ok(StatusCode::OK);
+    }
+
+    let folder = payload.series.as_ref().and_then(|s| s.path.as_deref());
+    let target = resolve_target(folder, payload.episode_file.as_ref()).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "Webhook payload has no path".to_string(),
+        )
+    })?;
+
+    tracing::info!(
+        "Sonarr {} event for '{}', scanning",
+        payload.event_type,
+        target.display()
+    );
+    spawn_targeted_scan(&state, target, "Sonarr");
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// POST /Webhooks/Radarr - Radarr "On Import"/"On Rename"/delete notifications
+async fn radarr_webhook(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    Query(query): Query<WebhookQuery>,
+    Json(payload): Json<RadarrPayload>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    require_api_key_or_admin(&state, &headers, query.api_key.as_deref()).await?;
+
+    if payload.event_type == "Test" {
+        tracing::info!("Received Radarr test webhook");
+        return Ok(StatusCode::OK);
+    }
+
+    if !is_scan_event(&payload.event_type) {
+        tracing::debug!("Ignoring Radarr webhook event: {}", payload.event_type);
+        return Ok(StatusCode::OK);
+    }
+
+    let folder = payload
+        .movie
+        .as_ref()
+        .and_then(|m| m.folder_path.as_deref());
+    let target = resolve_target(folder, payload.movie_file.as_ref()).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            "Webhook payload has no path".to_string(),
+        )
+    })?;
+
+    tracing::info!(
+        "Radarr {} event for '{}', scanning",
+        payload.event_type,
+        target.display()
+    );
+    spawn_targeted_scan(&state, target, "Radarr");
+
+    Ok(StatusCode::ACCEPTED)
+}
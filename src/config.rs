@@ -1,7 +1,7 @@
 // Configuration module for jellyfin-rust
 // Handles XDG-compliant directory paths and TOML configuration file
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 const APP_NAME: &str = "jellyfin-rust";
@@ -26,8 +26,32 @@ pub struct ConfigFile {
     /// Scanner/library refresh configuration
     pub scanner: ScannerConfig,
 
+    /// Log file configuration
+    pub logging: LoggingConfig,
+
+    /// Share link policy
+    pub sharing: SharingConfig,
+
+    /// HLS transcoding for clients that can't direct play a file
+    pub transcoding: TranscodingConfig,
+
+    /// Background thumbnail generation
+    pub thumbnails: ThumbnailConfig,
+
+    /// Checking for and installing new releases
+    pub updates: UpdateConfig,
+
+    /// DLNA media server for TVs and players without a Jellyfin app
+    pub dlna: DlnaConfig,
+
+    /// IPTV channels and their guide
+    pub live_tv: LiveTvConfig,
+
     /// Media libraries to auto-create on startup
     pub libraries: Vec<LibraryConfig>,
+
+    /// HTTP callbacks for server events
+    pub webhooks: Vec<WebhookConfig>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -38,6 +62,18 @@ pub struct ServerConfig {
 
     /// Bind address (default: 0.0.0.0)
     pub bind_address: String,
+
+    /// Static API key for external tools such as Sonarr/Radarr webhooks (optional)
+    /// Accepted via the X-Api-Key header or apiKey query parameter
+    pub api_key: Option<String>,
+
+    /// Minutes without a progress report before a playing session is treated as
+    /// stopped and its progress finalized (default: 10, 0 = never)
+    pub playback_timeout_minutes: u64,
+
+    /// Serve the Intro Skipper plugin's /Episode/{id}/IntroTimestamps endpoints
+    /// from media segments, for clients that don't use MediaSegments (default: false)
+    pub intro_skipper_api: bool,
 }
 
 impl Default for ServerConfig {
@@ -45,6 +81,9 @@ impl Default for ServerConfig {
         Self {
             port: 8096,
             bind_address: "0.0.0.0".to_string(),
+            api_key: None,
+            playback_timeout_minutes: 10,
+            intro_skipper_api: false,
         }
     }
 }
@@ -75,6 +114,38 @@ pub struct MetadataConfig {
     /// When disabled, episodes only get basic info (name, season/episode number)
     /// Disabling reduces API calls significantly for large libraries
     pub fetch_episode_metadata: bool,
+
+    /// Which of a provider's posters and backdrops to use
+    pub artwork: ArtworkConfig,
+}
+
+/// Choosing among the images a provider offers (services::metadata::select_image)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ArtworkConfig {
+    /// Image languages in order of preference, as two-letter codes (default: ["en"])
+    /// Textless images rank after these for posters and before them for backdrops
+    pub languages: Vec<String>,
+
+    /// Votes an image needs before its rating counts (default: 3)
+    pub min_vote_count: i64,
+
+    /// Images narrower than this are only used when nothing wider is available (default: 1000)
+    pub min_width: u32,
+
+    /// Backdrops kept per series or movie, for clients that rotate them (default: 5)
+    pub max_backdrops: usize,
+}
+
+impl Default for ArtworkConfig {
+    fn default() -> Self {
+        Self {
+            languages: vec!["en".to_string()],
+            min_vote_count: 3,
+            min_width: 1000,
+            max_backdrops: 5,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -96,9 +167,27 @@ pub struct LibraryConfig {
     /// Path to the media folder
     pub path: PathBuf,
 
-    /// Library type: "tvshows" or "movies"
+    /// Library type: "tvshows", "movies" or "music"
     #[serde(rename = "type")]
     pub library_type: String,
+
+    /// Glob patterns of files and folders to leave out of this library, in
+    /// addition to `scanner.exclude` (e.g. "**/sample*")
+    #[serde(default)]
+    pub exclude: Vec<String>,
+
+    /// How the library's items are listed when a client doesn't ask for an
+    /// order: a SortBy value such as "DateCreated" (default: SortName)
+    #[serde(default)]
+    pub sort_by: Option<String>,
+
+    /// "Ascending" or "Descending" (default: Ascending)
+    #[serde(default)]
+    pub sort_order: Option<String>,
+
+    /// Watch this library's folders for changes, overriding `scanner.watch`
+    #[serde(default)]
+    pub watch: Option<bool>,
 }
 
 /// Scanner/library refresh configuration
@@ -130,6 +219,61 @@ pub struct ScannerConfig {
 
     /// Whether to automatically retry failed thumbnail generations (default: true)
     pub retry_failed_thumbnails: bool,
+
+    /// Interval in hours to retry metadata lookup for unmatched series (default: 24, 0 to disable)
+    /// Series that failed to match are retried up to 3 times before being given up on
+    pub unmatched_retry_interval_hours: u64,
+
+    /// Run the database consistency check in the background after startup (default: false)
+    /// It can also be run any time as the "Check Database Consistency" scheduled task
+    pub consistency_check_on_startup: bool,
+
+    /// Let the consistency check repair what it finds (default: false, report only)
+    /// Removes orphaned rows and stale image entries, and rebuilds an out-of-sync search index
+    pub consistency_repair: bool,
+
+    /// What to do when several files are the same episode (default: "versions")
+    /// "versions" offers the other files as alternate versions of the best one;
+    /// "prefer_quality" hides them and lists them for review (GET /Library/Duplicates)
+    pub duplicate_episodes: DuplicateEpisodeMode,
+
+    /// Glob patterns of files and folders to leave out of every library,
+    /// relative to the library folder (see scanner::exclude)
+    /// Default: Synology/QNAP metadata folders and partial downloads
+    pub exclude: Vec<String>,
+
+    /// Video files smaller than this are skipped as stubs (default: 1, 0 to disable)
+    pub min_file_size_mb: u64,
+
+    /// Video files shorter than this are skipped as samples (default: 40, 0 to disable)
+    /// Files whose duration ffprobe can't read are kept
+    pub min_duration_seconds: u64,
+
+    /// Languages whose leading articles are moved to the end of sort names, so
+    /// "The Matrix" sorts as "matrix, the" (see scanner::sort_name)
+    /// Known: en, de, fr, es, it, nl, pt (default: ["en"])
+    pub ignore_articles: Vec<String>,
+
+    /// Further words to treat as leading articles (default: empty)
+    pub extra_articles: Vec<String>,
+
+    /// Watch library folders and add or remove items as files appear or
+    /// disappear, without waiting for a quick scan (default: false)
+    pub watch: bool,
+
+    /// Seconds a changed path must be quiet before it is handled (default: 10)
+    pub watch_debounce_seconds: u64,
+}
+
+/// Handling of files that map to the same series, season and episode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateEpisodeMode {
+    /// Stack the files as versions of one episode, the best one first
+    #[default]
+    Versions,
+    /// Keep only the highest-quality file and flag the others for review
+    PreferQuality,
 }
 
 impl Default for ScannerConfig {
@@ -168,6 +312,298 @@ impl Default for ScannerConfig {
             ],
             missing_thumbnail_check_minutes: 60,
             retry_failed_thumbnails: true,
+            unmatched_retry_interval_hours: 24,
+            consistency_check_on_startup: false,
+            consistency_repair: false,
+            duplicate_episodes: DuplicateEpisodeMode::default(),
+            exclude: vec![
+                "@eaDir".to_string(),
+                ".@__thumb".to_string(),
+                "*.part".to_string(),
+                "*.!qB".to_string(),
+            ],
+            min_file_size_mb: 1,
+            min_duration_seconds: 40,
+            ignore_articles: vec!["en".to_string()],
+            extra_articles: Vec::new(),
+            watch: false,
+            watch_debounce_seconds: 10,
+        }
+    }
+}
+
+/// Log file configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Write logs to files in <data_dir>/logs in addition to stdout (default: true)
+    pub file_enabled: bool,
+
+    /// Start a new log file once the current one reaches this size (default: 10 MB)
+    /// Files are also rotated at midnight UTC
+    pub max_file_size_mb: u64,
+
+    /// Number of log files to keep; older files are deleted (default: 10)
+    pub max_files: usize,
+
+    /// Log database statements slower than this, with the route that issued
+    /// them (default: 500 ms, 0 disables)
+    pub slow_query_ms: u64,
+
+    /// Time every database statement for GET /System/QueryStats (default: true)
+    pub query_stats: bool,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file_enabled: true,
+            max_file_size_mb: 10,
+            max_files: 10,
+            slow_query_ms: 500,
+            query_stats: true,
+        }
+    }
+}
+
+/// Share link policy (links that let anyone stream one item without an account)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SharingConfig {
+    /// Allow share links at all; existing links stop working when off (default: true)
+    pub enabled: bool,
+
+    /// Let non-admin users create share links (default: true)
+    pub users_can_share: bool,
+
+    /// Longest a share link may stay valid (default: 168 hours)
+    pub max_lifetime_hours: u64,
+
+    /// Cap in bits/s on every shared stream; sources above it are transcoded
+    /// (default: 0, no cap)
+    pub max_bitrate: u32,
+}
+
+impl Default for SharingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            users_can_share: true,
+            max_lifetime_hours: 7 * 24,
+            max_bitrate: 0,
+        }
+    }
+}
+
+/// HLS transcoding configuration
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TranscodingConfig {
+    /// Offer transcoding to clients that can't direct play a file (default: true)
+    pub enabled: bool,
+
+    /// Transcodes running at once; more are refused until one ends
+    /// (default: 2, 0 = no limit)
+    pub max_sessions: usize,
+
+    /// x264 preset: faster presets use less CPU for larger files (default: veryfast)
+    pub preset: String,
+
+    /// Length of HLS segments in seconds (default: 6)
+    pub segment_seconds: u32,
+}
+
+impl Default for TranscodingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_sessions: 2,
+            preset: "veryfast".to_string(),
+            segment_seconds: 6,
+        }
+    }
+}
+
+/// Background thumbnail generation (services::thumbnails)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ThumbnailConfig {
+    /// Thumbnails extracted at once (default: 0, half the CPU cores, at most 4)
+    pub workers: usize,
+
+    /// Hardware decoding for ffmpeg: "none", "vaapi", "nvenc" or "qsv"
+    /// (default: none); files the hardware can't decode fall back to software
+    pub hwaccel: HwAccel,
+
+    /// Render node used by VAAPI (default: /dev/dri/renderD128)
+    pub vaapi_device: String,
+
+    /// Where thumbnails are written: "cache" (default) or "media", beside the
+    /// video (`<name>-thumb.jpg`, `<name>-poster.jpg` for movies) in folders
+    /// the server can write to, and in the cache elsewhere
+    pub location: ThumbnailLocation,
+
+    /// Take the first detailed frame in the two minutes after the usual
+    /// position instead of whatever is there, skipping black frames, fades and
+    /// credits (default: false; decodes much more of each video)
+    pub scene_detection: bool,
+}
+
+/// Where generated thumbnails are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailLocation {
+    #[default]
+    Cache,
+    Media,
+}
+
+/// Hardware decoder used for thumbnail extraction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HwAccel {
+    #[default]
+    None,
+    Vaapi,
+    Nvenc,
+    Qsv,
+}
+
+impl Default for ThumbnailConfig {
+    fn default() -> Self {
+        Self {
+            workers: 0,
+            hwaccel: HwAccel::default(),
+            vaapi_device: "/dev/dri/renderD128".to_string(),
+            location: ThumbnailLocation::default(),
+            scene_detection: false,
+        }
+    }
+}
+
+/// Checking the release feed for new versions (services::updates)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct UpdateConfig {
+    /// Check for new releases in the background (default: true)
+    pub check: bool,
+
+    /// Hours between checks (default: 24)
+    pub check_interval_hours: u64,
+
+    /// Latest-release endpoint of the GitHub releases API (default: this project's)
+    pub feed_url: String,
+
+    /// Let admins install a new release with POST /System/Updates/Install
+    /// (default: false); the binary is replaced and the server restarted
+    pub allow_self_update: bool,
+}
+
+impl Default for UpdateConfig {
+    fn default() -> Self {
+        Self {
+            check: true,
+            check_interval_hours: 24,
+            feed_url: "https://api.github.com/repos/imaviso/jellyfin-rust/releases/latest"
+                .to_string(),
+            allow_self_update: false,
+        }
+    }
+}
+
+/// DLNA/UPnP media server (services::dlna)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct DlnaConfig {
+    /// Announce the server on the local network and serve its libraries to
+    /// DLNA clients (default: false)
+    pub enabled: bool,
+
+    /// Name TVs and players list the server under (default: Jellyfin Rust)
+    pub friendly_name: String,
+
+    /// Account whose library access and blocks apply to DLNA clients, which
+    /// can't sign in (default: the first admin)
+    pub user: Option<String>,
+
+    /// Seconds between announcements (default: 900)
+    pub announce_interval_seconds: u64,
+}
+
+impl Default for DlnaConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            friendly_name: "Jellyfin Rust".to_string(),
+            user: None,
+            announce_interval_seconds: 900,
+        }
+    }
+}
+
+/// IPTV channels from an M3U tuner and their XMLTV guide (services::live_tv)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LiveTvConfig {
+    /// M3U playlist of channels, as a URL or a local path (default: none, Live TV off)
+    pub m3u: Option<String>,
+
+    /// XMLTV guide, as a URL or a local path, optionally gzipped
+    /// (default: the playlist's url-tvg, if any)
+    pub xmltv: Option<String>,
+
+    /// Hours between guide refreshes (default: 12)
+    pub guide_refresh_hours: u64,
+}
+
+impl LiveTvConfig {
+    /// Whether a tuner is configured
+    pub fn is_enabled(&self) -> bool {
+        self.m3u.as_deref().is_some_and(|m3u| !m3u.is_empty())
+    }
+}
+
+impl Default for LiveTvConfig {
+    fn default() -> Self {
+        Self {
+            m3u: None,
+            xmltv: None,
+            guide_refresh_hours: 12,
+        }
+    }
+}
+
+/// An HTTP endpoint notified of server events (Discord, ntfy, Home Assistant, ...)
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// URL the events are POSTed to
+    pub url: String,
+
+    /// Events to send: ItemAdded, ScanCompleted, PlaybackStarted,
+    /// PlaybackStopped, UserCreated, UpdateAvailable (default: empty, all of them)
+    pub events: Vec<String>,
+
+    /// Request body with {{Placeholder}} values filled in (default: the event
+    /// and its values as a JSON object)
+    pub template: Option<String>,
+
+    /// Content-Type of the request; values filled into JSON templates are
+    /// escaped (default: application/json)
+    pub content_type: String,
+
+    /// Extra request headers (authorization, ntfy's Title and Tags, ...)
+    pub headers: std::collections::HashMap<String, String>,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            events: Vec::new(),
+            template: None,
+            content_type: "application/json".to_string(),
+            headers: std::collections::HashMap::new(),
         }
     }
 }
@@ -296,11 +732,21 @@ impl AppPaths {
         format!("sqlite:{}?mode=rwc", self.database_path().display())
     }
 
+    /// Get the log file directory
+    pub fn log_dir(&self) -> PathBuf {
+        self.data_dir.join("logs")
+    }
+
     /// Get the image cache directory
     pub fn image_cache_dir(&self) -> PathBuf {
         self.cache_dir.join("images")
     }
 
+    /// Get the directory for HLS segments of running transcodes
+    pub fn transcode_dir(&self) -> PathBuf {
+        self.cache_dir.join("transcodes")
+    }
+
     /// Get the anime database cache path
     pub fn anime_db_cache_dir(&self) -> PathBuf {
         self.cache_dir.clone()
@@ -347,6 +793,15 @@ pub struct AppConfig {
     /// Bind address
     pub bind_address: String,
 
+    /// Static API key for external tools (optional)
+    pub api_key: Option<String>,
+
+    /// Minutes without a progress report before playback is finalized (0 = never)
+    pub playback_timeout_minutes: u64,
+
+    /// Whether the Intro Skipper plugin endpoints are served
+    pub intro_skipper_api: bool,
+
     /// TMDB API key (optional)
     pub tmdb_api_key: Option<String>,
 
@@ -356,6 +811,9 @@ pub struct AppConfig {
     /// Whether to fetch per-episode metadata
     pub fetch_episode_metadata: bool,
 
+    /// Choosing among provider images
+    pub artwork: ArtworkConfig,
+
     /// Path to ffmpeg binary
     pub ffmpeg_path: Option<PathBuf>,
 
@@ -367,6 +825,30 @@ pub struct AppConfig {
 
     /// Scanner configuration
     pub scanner: ScannerConfig,
+
+    /// Log file configuration
+    pub logging: LoggingConfig,
+
+    /// Share link policy
+    pub sharing: SharingConfig,
+
+    /// HLS transcoding configuration
+    pub transcoding: TranscodingConfig,
+
+    /// Background thumbnail generation
+    pub thumbnails: ThumbnailConfig,
+
+    /// Release checks and self-update
+    pub updates: UpdateConfig,
+
+    /// DLNA media server
+    pub dlna: DlnaConfig,
+
+    /// IPTV tuner and guide
+    pub live_tv: LiveTvConfig,
+
+    /// HTTP callbacks for server events
+    pub webhooks: Vec<WebhookConfig>,
 }
 
 impl AppConfig {
@@ -404,13 +886,36 @@ impl AppConfig {
             paths,
             port: Self::env_port().unwrap_or(8096),
             bind_address: Self::env_bind_address().unwrap_or_else(|| "0.0.0.0".to_string()),
+            api_key: std::env::var("JELLYFIN_RUST_API_KEY").ok(),
+            playback_timeout_minutes: ServerConfig::default().playback_timeout_minutes,
+            intro_skipper_api: ServerConfig::default().intro_skipper_api,
             tmdb_api_key: std::env::var("TMDB_API_KEY").ok(),
             anime_db_enabled: Self::env_anime_db_enabled(),
             fetch_episode_metadata: Self::env_fetch_episode_metadata(),
+            artwork: ArtworkConfig::default(),
             ffmpeg_path: std::env::var("FFMPEG_PATH").ok().map(PathBuf::from),
             ffprobe_path: std::env::var("FFPROBE_PATH").ok().map(PathBuf::from),
             libraries: Vec::new(),
             scanner: ScannerConfig::default(),
+            logging: LoggingConfig {
+                slow_query_ms: Self::env_slow_query_ms()
+                    .unwrap_or(LoggingConfig::default().slow_query_ms),
+                ..LoggingConfig::default()
+            },
+            sharing: SharingConfig::default(),
+            transcoding: TranscodingConfig::default(),
+            thumbnails: ThumbnailConfig::default(),
+            updates: UpdateConfig::default(),
+            dlna: DlnaConfig {
+                enabled: Self::env_dlna_enabled().unwrap_or(false),
+                ..DlnaConfig::default()
+            },
+            live_tv: LiveTvConfig {
+                m3u: std::env::var("JELLYFIN_RUST_M3U").ok(),
+                xmltv: std::env::var("JELLYFIN_RUST_XMLTV").ok(),
+                ..LiveTvConfig::default()
+            },
+            webhooks: Vec::new(),
         }
     }
 
@@ -469,7 +974,7 @@ impl AppConfig {
     }
 
     /// Build configuration from config file with environment overrides
-    fn build(config_file: ConfigFile) -> Self {
+    pub fn build(config_file: ConfigFile) -> Self {
         let paths = AppPaths::new(&config_file.paths);
 
         // Port: env > config > default
@@ -479,6 +984,11 @@ impl AppConfig {
         let bind_address =
             Self::env_bind_address().unwrap_or_else(|| config_file.server.bind_address.clone());
 
+        // Static API key: env > config
+        let api_key = std::env::var("JELLYFIN_RUST_API_KEY")
+            .ok()
+            .or(config_file.server.api_key);
+
         // TMDB API key: env > config
         let tmdb_api_key = std::env::var("TMDB_API_KEY")
             .ok()
@@ -510,17 +1020,50 @@ impl AppConfig {
             .map(PathBuf::from)
             .or(config_file.tools.ffprobe_path);
 
+        // DLNA enabled: env > config
+        let mut dlna = config_file.dlna;
+        if let Some(enabled) = Self::env_dlna_enabled() {
+            dlna.enabled = enabled;
+        }
+
+        // Live TV sources: env > config
+        let mut live_tv = config_file.live_tv;
+        if let Ok(m3u) = std::env::var("JELLYFIN_RUST_M3U") {
+            live_tv.m3u = Some(m3u);
+        }
+        if let Ok(xmltv) = std::env::var("JELLYFIN_RUST_XMLTV") {
+            live_tv.xmltv = Some(xmltv);
+        }
+
+        // Slow query threshold: env > config
+        let mut logging = config_file.logging;
+        if let Some(ms) = Self::env_slow_query_ms() {
+            logging.slow_query_ms = ms;
+        }
+
         Self {
             paths,
             port,
             bind_address,
+            api_key,
+            playback_timeout_minutes: config_file.server.playback_timeout_minutes,
+            intro_skipper_api: config_file.server.intro_skipper_api,
             tmdb_api_key,
             anime_db_enabled,
             fetch_episode_metadata,
+            artwork: config_file.metadata.artwork,
             ffmpeg_path,
             ffprobe_path,
             libraries: config_file.libraries,
             scanner: config_file.scanner,
+            logging,
+            sharing: config_file.sharing,
+            transcoding: config_file.transcoding,
+            thumbnails: config_file.thumbnails,
+            updates: config_file.updates,
+            dlna,
+            live_tv,
+            webhooks: config_file.webhooks,
         }
     }
 
@@ -534,6 +1077,18 @@ impl AppConfig {
         std::env::var("JELLYFIN_RUST_BIND_ADDRESS").ok()
     }
 
+    fn env_slow_query_ms() -> Option<u64> {
+        std::env::var("JELLYFIN_RUST_SLOW_QUERY_MS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+    }
+
+    fn env_dlna_enabled() -> Option<bool> {
+        std::env::var("JELLYFIN_RUST_DLNA")
+            .ok()
+            .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
+    }
+
     fn env_anime_db_enabled() -> bool {
         std::env::var("ENABLE_ANIME_DB")
             .map(|v| v.eq_ignore_ascii_case("true") || v == "1")
@@ -575,6 +1130,16 @@ impl AppConfig {
             tracing::debug!("Episode metadata fetching: disabled (reduces API calls)");
         }
 
+        if self.logging.file_enabled {
+            tracing::info!("Log files: {}", self.paths.log_dir().display());
+        }
+
+        if self.logging.slow_query_ms > 0 {
+            tracing::debug!("Slow query threshold: {} ms", self.logging.slow_query_ms);
+        } else {
+            tracing::debug!("Slow query log: disabled");
+        }
+
         if let Some(ref path) = self.ffmpeg_path {
             tracing::debug!("FFmpeg: {}", path.display());
         }
@@ -0,0 +1,782 @@
+// Shared item query builder
+//
+// Browse, count, search, Latest, Resume and NextUp all select media items with
+// overlapping filters. ItemQuery collects the filters once and renders them into
+// a sqlx QueryBuilder, so a new filter (tags, ratings, permissions) is added here
+// and every endpoint picks it up. The outer table is always aliased as "m".
+
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use super::ItemList;
+use crate::models::MediaItem;
+
+/// HD badge threshold (720p); either dimension counts so letterboxed/cropped encodes still qualify
+pub const HD_MIN_WIDTH: i32 = 1280;
+pub const HD_MIN_HEIGHT: i32 = 720;
+/// 4K badge threshold (2160p, with slack for cropped encodes like 3840x1600)
+pub const UHD_MIN_WIDTH: i32 = 3800;
+pub const UHD_MIN_HEIGHT: i32 = 2100;
+
+/// Whether a video resolution counts as HD (None when resolution is unknown)
+pub fn is_hd_resolution(width: Option<i32>, height: Option<i32>) -> Option<bool> {
+    if width.is_none() && height.is_none() {
+        return None;
+    }
+    Some(width.unwrap_or(0) >= HD_MIN_WIDTH || height.unwrap_or(0) >= HD_MIN_HEIGHT)
+}
+
+/// Whether a video resolution counts as 4K (None when resolution is unknown)
+pub fn is_4k_resolution(width: Option<i32>, height: Option<i32>) -> Option<bool> {
+    if width.is_none() && height.is_none() {
+        return None;
+    }
+    Some(width.unwrap_or(0) >= UHD_MIN_WIDTH || height.unwrap_or(0) >= UHD_MIN_HEIGHT)
+}
+
+/// How results are ordered
+#[derive(Debug, Clone)]
+pub enum ItemSort {
+    /// A media_items column (whitelisted, never user input)
+    Column(&'static str),
+    /// FTS5 relevance; only meaningful together with `fts_match`
+    SearchRank,
+    /// Exact name matches first, then prefix matches, then the rest
+    NameRelevance(String),
+    /// When the user last played the item
+    LastPlayed(String),
+    /// Season then episode, following the series' alternate ordering if one is selected
+    EpisodeOrder,
+    /// Position in a playlist or collection (with `in_list`)
+    ListOrder(ItemList, String),
+}
+
+impl ItemSort {
+    /// Map a Jellyfin SortBy value to a column, defaulting to SortName
+    pub fn from_sort_by(sort_by: &str) -> Self {
+        Self::Column(match sort_by {
+            "DateCreated" => "created_at",
+            "PremiereDate" => "premiere_date",
+            "IndexNumber" => "index_number",
+            "ParentIndexNumber" => "parent_index_number",
+            "CommunityRating" => "community_rating",
+            "Name" => "name",
+            "DateLastContentAdded" => "updated_at",
+            _ => "sort_name",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    /// Parse a Jellyfin SortOrder value (anything but "Descending" is ascending)
+    pub fn from_sort_order(sort_order: Option<&str>) -> Self {
+        if sort_order == Some("Descending") {
+            Self::Descending
+        } else {
+            Self::Ascending
+        }
+    }
+
+    fn as_sql(self) -> &'static str {
+        match self {
+            Self::Ascending => "ASC",
+            Self::Descending => "DESC",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ParentFilter {
+    /// Direct children of an item
+    Parent(String),
+    /// Anything in a library, or children of an item when the ID isn't a library
+    Ancestor(String),
+    /// Top-level items only
+    TopLevel,
+    /// Entries of a playlist or collection
+    List(ItemList, String),
+}
+
+/// The columns of an item a search hint needs
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ItemHint {
+    pub id: String,
+    pub name: String,
+    pub item_type: String,
+    pub year: Option<i32>,
+}
+
+/// Filters, sorting, user scoping and pagination for a media_items query
+#[derive(Debug, Clone, Default)]
+pub struct ItemQuery {
+    parent: Option<ParentFilter>,
+    library_id: Option<String>,
+    libraries: Option<Vec<String>>,
+    include_types: Vec<String>,
+    exclude_types: Vec<String>,
+    search_term: Option<String>,
+    names_only: bool,
+    artist_ids: Vec<String>,
+    album_artist_ids: Vec<String>,
+    album_ids: Vec<String>,
+    has_children: bool,
+    exclude_specials: bool,
+    fts_match: Option<String>,
+    is_dubbed: Option<bool>,
+    is_dual_audio: Option<bool>,
+    audio_languages: Vec<String>,
+    is_hd: Option<bool>,
+    is_4k: Option<bool>,
+    favorites_of: Option<String>,
+    visible_to: Option<String>,
+    /// (user, played)
+    played: Option<(String, bool)>,
+    /// (user, in progress)
+    in_progress: Option<(String, bool)>,
+    series_started_by: Option<String>,
+    sort: Vec<(ItemSort, SortOrder)>,
+    limit: Option<i32>,
+    offset: Option<i32>,
+}
+
+impl ItemQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Direct children of `parent_id`: a series' seasons, a season's episodes
+    pub fn parent(mut self, parent_id: &str) -> Self {
+        self.parent = Some(ParentFilter::Parent(parent_id.to_string()));
+        self
+    }
+
+    /// Items in library `id`, or children of item `id` (recursive browse)
+    pub fn ancestor(mut self, id: &str) -> Self {
+        self.parent = Some(ParentFilter::Ancestor(id.to_string()));
+        self
+    }
+
+    /// Only items without a parent (series, movies)
+    pub fn top_level(mut self) -> Self {
+        self.parent = Some(ParentFilter::TopLevel);
+        self
+    }
+
+    /// Entries of a playlist or collection (sort by `ItemSort::ListOrder` to keep their order)
+    pub fn in_list(mut self, list: ItemList, list_id: &str) -> Self {
+        self.parent = Some(ParentFilter::List(list, list_id.to_string()));
+        self
+    }
+
+    pub fn library(mut self, library_id: &str) -> Self {
+        self.library_id = Some(library_id.to_string());
+        self
+    }
+
+    /// Only items of the listed libraries (the ones a user has access to)
+    pub fn in_libraries(mut self, library_ids: &[String]) -> Self {
+        self.libraries = Some(library_ids.to_vec());
+        self
+    }
+
+    pub fn include_types<S: AsRef<str>>(mut self, types: &[S]) -> Self {
+        self.include_types = split_list(types);
+        self
+    }
+
+    pub fn exclude_types<S: AsRef<str>>(mut self, types: &[S]) -> Self {
+        self.exclude_types = split_list(types);
+        self
+    }
+
+    /// Case-insensitive substring match on name and overview
+    pub fn search_term(mut self, term: &str) -> Self {
+        self.search_term = Some(term.to_lowercase());
+        self
+    }
+
+    /// Case-insensitive substring match on the name alone, which the search
+    /// hint index covers
+    pub fn name_term(mut self, term: &str) -> Self {
+        self.search_term = Some(term.to_lowercase());
+        self.names_only = true;
+        self
+    }
+
+    /// Only tracks and albums by any of the listed MusicArtist items
+    pub fn artists<S: AsRef<str>>(mut self, artist_ids: &[S]) -> Self {
+        self.artist_ids = split_list(artist_ids);
+        self
+    }
+
+    /// Only tracks and albums whose album artist is one of the listed MusicArtist items
+    pub fn album_artists<S: AsRef<str>>(mut self, artist_ids: &[S]) -> Self {
+        self.album_artist_ids = split_list(artist_ids);
+        self
+    }
+
+    /// Only tracks of the listed albums
+    pub fn albums<S: AsRef<str>>(mut self, album_ids: &[S]) -> Self {
+        self.album_ids = split_list(album_ids);
+        self
+    }
+
+    /// Only items with children (artists with albums of their own)
+    pub fn has_children(mut self) -> Self {
+        self.has_children = true;
+        self
+    }
+
+    /// Leave out specials (episodes listed under season 0)
+    pub fn exclude_specials(mut self) -> Self {
+        self.exclude_specials = true;
+        self
+    }
+
+    /// Full-text match against media_items_fts (an already prepared FTS5 query)
+    pub fn fts_match(mut self, fts_query: &str) -> Self {
+        self.fts_match = Some(fts_query.to_string());
+        self
+    }
+
+    /// Dub/language hint filters (isDubbed, isDualAudio, audioLanguages)
+    pub fn language_hints<S: AsRef<str>>(
+        mut self,
+        is_dubbed: Option<bool>,
+        is_dual_audio: Option<bool>,
+        audio_languages: &[S],
+    ) -> Self {
+        self.is_dubbed = is_dubbed;
+        self.is_dual_audio = is_dual_audio;
+        self.audio_languages = split_list(audio_languages)
+            .into_iter()
+            .map(|s| {
+                crate::scanner::language_code(&s)
+                    .map(|c| c.to_string())
+                    .unwrap_or_else(|| s.to_lowercase())
+            })
+            .collect();
+        self
+    }
+
+    /// Resolution filters (isHd, is4K)
+    pub fn resolution(mut self, is_hd: Option<bool>, is_4k: Option<bool>) -> Self {
+        self.is_hd = is_hd;
+        self.is_4k = is_4k;
+        self
+    }
+
+    /// Only the user's favorites
+    pub fn favorites_of(mut self, user_id: &str) -> Self {
+        self.favorites_of = Some(user_id.to_string());
+        self
+    }
+
+    /// Hide items the user has blocked (items, genres, tags)
+    pub fn visible_to(mut self, user_id: &str) -> Self {
+        self.visible_to = Some(user_id.to_string());
+        self
+    }
+
+    /// Only items the user has (or hasn't) marked played
+    pub fn played_by(mut self, user_id: &str, played: bool) -> Self {
+        self.played = Some((user_id.to_string(), played));
+        self
+    }
+
+    /// Only items the user has (or hasn't) partially watched
+    pub fn in_progress_for(mut self, user_id: &str, in_progress: bool) -> Self {
+        self.in_progress = Some((user_id.to_string(), in_progress));
+        self
+    }
+
+    /// Only episodes of series the user has started watching
+    pub fn series_started_by(mut self, user_id: &str) -> Self {
+        self.series_started_by = Some(user_id.to_string());
+        self
+    }
+
+    /// Add a sort key (keys apply in the order they were added)
+    pub fn sort(mut self, sort: ItemSort, order: SortOrder) -> Self {
+        self.sort.push((sort, order));
+        self
+    }
+
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: i32) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// SELECT m.* with filters, sorting and pagination
+    pub fn build(&self) -> QueryBuilder<'static, Sqlite> {
+        self.build_select("m.*")
+    }
+
+    /// Like build, selecting only the given columns
+    fn build_select(&self, columns: &str) -> QueryBuilder<'static, Sqlite> {
+        let mut qb = QueryBuilder::new(format!("SELECT {} ", columns));
+        self.push_from_where(&mut qb);
+        self.push_order(&mut qb);
+        if let Some(limit) = self.limit {
+            qb.push(" LIMIT ").push_bind(limit);
+            if let Some(offset) = self.offset {
+                qb.push(" OFFSET ").push_bind(offset);
+            }
+        }
+        qb
+    }
+
+    /// SELECT COUNT(*) with the same filters, ignoring sorting and pagination
+    pub fn build_count(&self) -> QueryBuilder<'static, Sqlite> {
+        let mut qb = QueryBuilder::new("SELECT COUNT(*) ");
+        self.push_from_where(&mut qb);
+        qb
+    }
+
+    pub async fn fetch_all(&self, pool: &SqlitePool) -> Result<Vec<MediaItem>, sqlx::Error> {
+        self.build().build_query_as().fetch_all(pool).await
+    }
+
+    /// ID, name, type and year of each item, for type-ahead search
+    pub async fn fetch_hints(&self, pool: &SqlitePool) -> Result<Vec<ItemHint>, sqlx::Error> {
+        self.build_select("m.id, m.name, m.item_type, m.year")
+            .build_query_as()
+            .fetch_all(pool)
+            .await
+    }
+
+    pub async fn count(&self, pool: &SqlitePool) -> Result<i32, sqlx::Error> {
+        self.build_count()
+            .build_query_scalar()
+            .fetch_one(pool)
+            .await
+    }
+
+    fn push_from_where(&self, qb: &mut QueryBuilder<'static, Sqlite>) {
+        qb.push("FROM media_items m");
+        if let Some(ref fts_query) = self.fts_match {
+            qb.push(" JOIN media_items_fts f ON m.rowid = f.rowid WHERE media_items_fts MATCH ")
+                .push_bind(fts_query.clone());
+        } else {
+            qb.push(" WHERE 1=1");
+        }
+        // Other copies of an episode are reached through the primary's media sources
+        qb.push(" AND m.version_of IS NULL");
+
+        match self.parent {
+            Some(ParentFilter::Parent(ref id)) => {
+                // Episodes are listed under their season rather than their series
+                qb.push(" AND (m.season_id = ")
+                    .push_bind(id.clone())
+                    .push(" OR (m.parent_id = ")
+                    .push_bind(id.clone())
+                    .push(" AND m.season_id IS NULL))");
+            }
+            Some(ParentFilter::Ancestor(ref id)) => {
+                qb.push(" AND (m.library_id = ")
+                    .push_bind(id.clone())
+                    .push(" OR m.parent_id = ")
+                    .push_bind(id.clone())
+                    .push(" OR m.season_id = ")
+                    .push_bind(id.clone())
+                    .push(")");
+            }
+            Some(ParentFilter::TopLevel) => {
+                qb.push(" AND m.parent_id IS NULL");
+            }
+            Some(ParentFilter::List(list, ref id)) => {
+                let (table, column) = list.table();
+                qb.push(format!(
+                    " AND m.id IN (SELECT item_id FROM {} WHERE {} = ",
+                    table, column
+                ))
+                .push_bind(id.clone())
+                .push(")");
+            }
+            None => {}
+        }
+
+        if let Some(ref library_id) = self.library_id {
+            qb.push(" AND m.library_id = ")
+                .push_bind(library_id.clone());
+        }
+
+        if let Some(ref library_ids) = self.libraries {
+            if library_ids.is_empty() {
+                qb.push(" AND 0");
+            } else {
+                qb.push(" AND m.library_id IN (");
+                let mut separated = qb.separated(", ");
+                for id in library_ids {
+                    separated.push_bind(id.clone());
+                }
+                separated.push_unseparated(")");
+            }
+        }
+
+        for (types, negate) in [(&self.include_types, ""), (&self.exclude_types, " NOT")] {
+            if types.is_empty() {
+                continue;
+            }
+            qb.push(format!(" AND m.item_type{} IN (", negate));
+            let mut separated = qb.separated(", ");
+            for t in types {
+                separated.push_bind(t.clone());
+            }
+            separated.push_unseparated(")");
+        }
+
+        if let Some(ref term) = self.search_term {
+            let pattern = format!("%{}%", term);
+            if self.names_only {
+                qb.push(" AND LOWER(m.name) LIKE ").push_bind(pattern);
+            } else {
+                qb.push(" AND (LOWER(m.name) LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" OR LOWER(COALESCE(m.overview, '')) LIKE ")
+                    .push_bind(pattern)
+                    .push(")");
+            }
+        }
+
+        self.push_music_filters(qb);
+        self.push_language_filters(qb);
+        self.push_resolution_filters(qb);
+        self.push_user_filters(qb);
+    }
+
+    /// Artists are matched by name, since that's what tracks and albums store
+    fn push_music_filters(&self, qb: &mut QueryBuilder<'static, Sqlite>) {
+        let artist_filters = [
+            (
+                &self.artist_ids,
+                "instr(';' || LOWER(COALESCE(m.artists, m.album_artist)) || ';', ';' || LOWER(a.name) || ';') > 0",
+            ),
+            (&self.album_artist_ids, "LOWER(a.name) = LOWER(m.album_artist)"),
+        ];
+        for (ids, condition) in artist_filters {
+            if ids.is_empty() {
+                continue;
+            }
+            qb.push(format!(
+                " AND EXISTS (SELECT 1 FROM media_items a WHERE a.item_type = 'MusicArtist' \
+                 AND a.library_id = m.library_id AND {} AND a.id IN (",
+                condition
+            ));
+            let mut separated = qb.separated(", ");
+            for id in ids {
+                separated.push_bind(id.clone());
+            }
+            separated.push_unseparated("))");
+        }
+
+        if !self.album_ids.is_empty() {
+            qb.push(" AND m.parent_id IN (");
+            let mut separated = qb.separated(", ");
+            for id in &self.album_ids {
+                separated.push_bind(id.clone());
+            }
+            separated.push_unseparated(")");
+        }
+
+        if self.has_children {
+            qb.push(" AND EXISTS (SELECT 1 FROM media_items c WHERE c.parent_id = m.id)");
+        }
+
+        if self.exclude_specials {
+            qb.push(" AND COALESCE(m.display_parent_index_number, m.parent_index_number, 1) != 0");
+        }
+    }
+
+    /// Hints are stored on files, so a series matches when any of its episodes match
+    fn push_language_filters(&self, qb: &mut QueryBuilder<'static, Sqlite>) {
+        let flags = [
+            ("is_dubbed", self.is_dubbed),
+            ("is_dual_audio", self.is_dual_audio),
+        ];
+        for (column, wanted) in flags {
+            match wanted {
+                Some(true) => {
+                    qb.push(format!(
+                        " AND (m.{col} = 1 OR m.id IN (SELECT parent_id FROM media_items WHERE {col} = 1))",
+                        col = column
+                    ));
+                }
+                Some(false) => {
+                    qb.push(format!(
+                        " AND m.{col} = 0 AND m.id NOT IN (SELECT parent_id FROM media_items WHERE {col} = 1 AND parent_id IS NOT NULL)",
+                        col = column
+                    ));
+                }
+                None => {}
+            }
+        }
+
+        if !self.audio_languages.is_empty() {
+            qb.push(" AND (");
+            let mut separated = qb.separated(" OR ");
+            for code in &self.audio_languages {
+                let pattern = format!("%,{},%", code);
+                separated
+                    .push("(',' || COALESCE(m.audio_languages, '') || ',') LIKE ")
+                    .push_bind_unseparated(pattern.clone())
+                    .push("m.id IN (SELECT parent_id FROM media_items WHERE (',' || COALESCE(audio_languages, '') || ',') LIKE ")
+                    .push_bind_unseparated(pattern)
+                    .push_unseparated(")");
+            }
+            separated.push_unseparated(")");
+        }
+    }
+
+    /// Like language hints, a series matches when any of its episodes match
+    fn push_resolution_filters(&self, qb: &mut QueryBuilder<'static, Sqlite>) {
+        let filters = [
+            (HD_MIN_WIDTH, HD_MIN_HEIGHT, self.is_hd),
+            (UHD_MIN_WIDTH, UHD_MIN_HEIGHT, self.is_4k),
+        ];
+        for (min_width, min_height, wanted) in filters {
+            let condition = |prefix: &str| {
+                format!(
+                    "({p}width >= {} OR {p}height >= {})",
+                    min_width,
+                    min_height,
+                    p = prefix
+                )
+            };
+            match wanted {
+                Some(true) => {
+                    qb.push(format!(
+                        " AND ({} OR m.id IN (SELECT parent_id FROM media_items WHERE {}))",
+                        condition("m."),
+                        condition("")
+                    ));
+                }
+                Some(false) => {
+                    qb.push(format!(
+                        " AND NOT COALESCE({}, 0) AND m.id NOT IN (SELECT parent_id FROM media_items WHERE {} AND parent_id IS NOT NULL)",
+                        condition("m."),
+                        condition("")
+                    ));
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn push_user_filters(&self, qb: &mut QueryBuilder<'static, Sqlite>) {
+        if let Some(ref user_id) = self.favorites_of {
+            qb.push(" AND m.id IN (SELECT item_id FROM user_favorites WHERE user_id = ")
+                .push_bind(user_id.clone())
+                .push(")");
+        }
+
+        if let Some(ref user_id) = self.visible_to {
+            qb.push(" AND m.id NOT IN (SELECT item_id FROM user_hidden_items WHERE user_id = ")
+                .push_bind(user_id.clone())
+                .push(")");
+        }
+
+        if let Some((ref user_id, played)) = self.played {
+            qb.push(if played {
+                " AND m.id IN"
+            } else {
+                " AND m.id NOT IN"
+            })
+            .push(" (SELECT item_id FROM playback_progress WHERE played = 1 AND user_id = ")
+            .push_bind(user_id.clone())
+            .push(")");
+        }
+
+        if let Some((ref user_id, in_progress)) = self.in_progress {
+            qb.push(if in_progress { " AND m.id IN" } else { " AND m.id NOT IN" })
+                .push(" (SELECT item_id FROM playback_progress WHERE position_ticks > 0 AND played = 0 AND user_id = ")
+                .push_bind(user_id.clone())
+                .push(")");
+        }
+
+        if let Some(ref user_id) = self.series_started_by {
+            qb.push(
+                " AND m.parent_id IN (SELECT DISTINCT m2.parent_id FROM media_items m2 \
+                 INNER JOIN playback_progress p ON m2.id = p.item_id \
+                 WHERE m2.item_type = 'Episode' AND p.user_id = ",
+            )
+            .push_bind(user_id.clone())
+            .push(")");
+        }
+    }
+
+    fn push_order(&self, qb: &mut QueryBuilder<'static, Sqlite>) {
+        if self.sort.is_empty() {
+            return;
+        }
+
+        qb.push(" ORDER BY ");
+        for (i, (sort, order)) in self.sort.iter().enumerate() {
+            if i > 0 {
+                qb.push(", ");
+            }
+            match sort {
+                // Display names sort ignoring case and diacritics, like
+                // sort names (the FOLD collation is registered in main)
+                ItemSort::Column("name") => {
+                    qb.push("m.name COLLATE FOLD");
+                }
+                ItemSort::Column(column) => {
+                    qb.push(format!("m.{}", column));
+                }
+                ItemSort::SearchRank => {
+                    qb.push("bm25(media_items_fts)");
+                }
+                ItemSort::NameRelevance(term) => {
+                    let term = term.to_lowercase();
+                    qb.push("CASE WHEN LOWER(m.name) = ")
+                        .push_bind(term.clone())
+                        .push(" THEN 0 WHEN LOWER(m.name) LIKE ")
+                        .push_bind(format!("{}%", term))
+                        .push(" THEN 1 ELSE 2 END");
+                }
+                ItemSort::EpisodeOrder => {
+                    let order = order.as_sql();
+                    qb.push(format!(
+                        "COALESCE(m.display_parent_index_number, m.parent_index_number) {}, \
+                         COALESCE(m.display_index_number, m.index_number)",
+                        order
+                    ));
+                }
+                ItemSort::ListOrder(list, id) => {
+                    let (table, column) = list.table();
+                    qb.push(format!(
+                        "(SELECT sort_order FROM {} WHERE item_id = m.id AND {} = ",
+                        table, column
+                    ))
+                    .push_bind(id.clone())
+                    .push(")");
+                }
+                ItemSort::LastPlayed(user_id) => {
+                    qb.push("(SELECT last_played FROM playback_progress WHERE item_id = m.id AND user_id = ")
+                        .push_bind(user_id.clone())
+                        .push(")");
+                }
+            }
+            qb.push(" ").push(order.as_sql());
+        }
+    }
+}
+
+/// Flatten values that may themselves be comma-separated ("Movie,Series")
+fn split_list<S: AsRef<str>>(values: &[S]) -> Vec<String> {
+    values
+        .iter()
+        .flat_map(|s| s.as_ref().split(','))
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_combines_filters_and_pagination() {
+        let query = ItemQuery::new()
+            .ancestor("lib")
+            .include_types(&["Movie,Series"])
+            .favorites_of("user")
+            .visible_to("user")
+            .sort(ItemSort::from_sort_by("DateCreated"), SortOrder::Descending)
+            .limit(10)
+            .offset(20);
+
+        let sql = query.build().into_sql();
+        assert!(sql.starts_with("SELECT m.* FROM media_items m WHERE 1=1"));
+        assert!(sql.contains("(m.library_id = ? OR m.parent_id = ? OR m.season_id = ?)"));
+        assert!(sql.contains("m.item_type IN (?, ?)"));
+        assert!(sql.contains("user_favorites"));
+        assert!(sql.contains("user_hidden_items"));
+        assert!(sql.ends_with("ORDER BY m.created_at DESC LIMIT ? OFFSET ?"));
+
+        // Counts share the filters but not the ordering or pagination
+        let count_sql = query.build_count().into_sql();
+        assert!(count_sql.starts_with("SELECT COUNT(*) FROM media_items m"));
+        assert!(count_sql.contains("user_hidden_items"));
+        assert!(!count_sql.contains("ORDER BY"));
+        assert!(!count_sql.contains("LIMIT"));
+    }
+
+    #[test]
+    fn test_fts_query_joins_search_table() {
+        let sql = ItemQuery::new()
+            .fts_match("\"naruto\"*")
+            .ancestor("lib")
+            .in_libraries(&["lib".to_string(), "other".to_string()])
+            .exclude_types(&["Episode"])
+            .sort(ItemSort::SearchRank, SortOrder::Ascending)
+            .build()
+            .into_sql();
+
+        assert!(sql
+            .contains("JOIN media_items_fts f ON m.rowid = f.rowid WHERE media_items_fts MATCH ?"));
+        assert!(sql.contains("m.library_id IN (?, ?)"));
+        assert!(sql.contains("m.item_type NOT IN (?)"));
+        assert!(sql.ends_with("ORDER BY bm25(media_items_fts) ASC"));
+    }
+
+    #[test]
+    fn test_list_entries_in_list_order() {
+        let sql = ItemQuery::new()
+            .in_list(ItemList::Playlist, "pl")
+            .sort(
+                ItemSort::ListOrder(ItemList::Playlist, "pl".to_string()),
+                SortOrder::Ascending,
+            )
+            .build()
+            .into_sql();
+
+        assert!(sql.contains("m.id IN (SELECT item_id FROM playlist_items WHERE playlist_id = ?)"));
+        assert!(sql.ends_with(
+            "ORDER BY (SELECT sort_order FROM playlist_items WHERE item_id = m.id AND playlist_id = ?) ASC"
+        ));
+        assert!(!sql.contains("m.parent_id"));
+    }
+
+    #[test]
+    fn test_music_filters_match_artists_by_name() {
+        let sql = ItemQuery::new()
+            .include_types(&["MusicAlbum"])
+            .album_artists(&["a1,a2"])
+            .albums(&["album"])
+            .has_children()
+            .build()
+            .into_sql();
+
+        assert!(sql.contains("LOWER(a.name) = LOWER(m.album_artist) AND a.id IN (?, ?))"));
+        assert!(sql.contains("m.parent_id IN (?)"));
+        assert!(sql.contains("EXISTS (SELECT 1 FROM media_items c WHERE c.parent_id = m.id)"));
+        assert!(!sql.contains("instr("));
+    }
+
+    #[test]
+    fn test_hint_columns_and_name_only_match() {
+        let sql = ItemQuery::new()
+            .name_term("Naruto")
+            .include_types(&["Series"])
+            .build_select("m.id, m.name, m.item_type, m.year")
+            .into_sql();
+
+        assert!(sql.starts_with("SELECT m.id, m.name, m.item_type, m.year FROM media_items m"));
+        assert!(sql.contains("AND LOWER(m.name) LIKE ?"));
+        assert!(!sql.contains("overview"));
+    }
+}
@@ -1,5 +1,10 @@
 use anyhow::{Context, Result};
 use sqlx::SqlitePool;
+use std::path::Path;
+
+pub mod item_query;
+pub mod query_stats;
+pub mod schema;
 
 /// Configure SQLite PRAGMAs for a single connection
 ///
@@ -33,6 +38,9 @@ pub async fn configure_connection(pool: &SqlitePool) -> Result<()> {
 }
 
 pub async fn migrate(pool: &SqlitePool) -> Result<()> {
+    // Stop before touching a database a newer, incompatible version has migrated
+    schema::check(pool).await?;
+
     sqlx::query(
         r#"
         CREATE TABLE IF NOT EXISTS users (
@@ -288,14 +296,408 @@ pub async fn migrate(pool: &SqlitePool) -> Result<()> {
             sort_order INTEGER NOT NULL DEFAULT 0,
             PRIMARY KEY (item_id, person_id, role)
         );
+
+        -- Lyrics found beside or embedded in audio files
+        CREATE TABLE IF NOT EXISTS lyrics (
+            item_id TEXT PRIMARY KEY REFERENCES media_items(id) ON DELETE CASCADE,
+            content TEXT NOT NULL,       -- Raw LRC or plain text
+            source TEXT NOT NULL,        -- sidecar, embedded
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- Items, genres and tags a user has hidden from browse and search
+        CREATE TABLE IF NOT EXISTS user_item_blocks (
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            block_type TEXT NOT NULL,    -- Item, Genre, Tag
+            value TEXT NOT NULL,         -- Item ID, or genre/tag name
+            created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (user_id, block_type, value)
+        );
+
+        -- Libraries a user may see when users.enable_all_folders is off
+        CREATE TABLE IF NOT EXISTS user_library_access (
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            library_id TEXT NOT NULL REFERENCES libraries(id) ON DELETE CASCADE,
+            PRIMARY KEY (user_id, library_id)
+        );
+
+        -- A user's library order and visibility on their home screen (services::library_views)
+        CREATE TABLE IF NOT EXISTS user_library_views (
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            library_id TEXT NOT NULL REFERENCES libraries(id) ON DELETE CASCADE,
+            sort_index INTEGER,
+            hidden INTEGER NOT NULL DEFAULT 0,
+            hide_latest INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (user_id, library_id)
+        );
+
+        -- Invite codes that let someone create their own account (services::invites)
+        CREATE TABLE IF NOT EXISTS user_invites (
+            code TEXT PRIMARY KEY,
+            created_by TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            max_uses INTEGER NOT NULL DEFAULT 1,
+            use_count INTEGER NOT NULL DEFAULT 0,
+            -- Library access given to accounts created with the invite
+            enable_all_folders INTEGER NOT NULL DEFAULT 1,
+            enabled_folders TEXT         -- Comma-separated library IDs
+        );
+
+        -- Expiring links that stream one item without an account (services::share_links)
+        CREATE TABLE IF NOT EXISTS share_links (
+            token TEXT PRIMARY KEY,
+            item_id TEXT NOT NULL REFERENCES media_items(id) ON DELETE CASCADE,
+            user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            created_at TEXT NOT NULL,
+            expires_at TEXT NOT NULL,
+            max_bitrate INTEGER,         -- bits/s; sources above it are transcoded
+            view_count INTEGER NOT NULL DEFAULT 0,
+            last_viewed_at TEXT
+        );
+
+        -- Per-series rules mapping disk season/episode numbers to provider seasons
+        CREATE TABLE IF NOT EXISTS series_season_mappings (
+            item_id TEXT NOT NULL REFERENCES media_items(id) ON DELETE CASCADE,  -- Series
+            disk_season INTEGER NOT NULL,
+            first_episode INTEGER NOT NULL,
+            last_episode INTEGER,        -- NULL = to the end of the disk season
+            provider_season INTEGER NOT NULL,
+            episode_offset INTEGER NOT NULL DEFAULT 0,
+            source TEXT NOT NULL DEFAULT 'Manual',  -- Manual, Auto
+            PRIMARY KEY (item_id, disk_season, first_episode)
+        );
+
+        -- One row per library scan run, with what it changed (scanner::history)
+        CREATE TABLE IF NOT EXISTS scan_history (
+            id TEXT PRIMARY KEY,
+            library_id TEXT NOT NULL,    -- No FK: history outlives deleted libraries
+            library_name TEXT NOT NULL,
+            scan_type TEXT NOT NULL,     -- Full, Quick, Targeted, Refresh
+            status TEXT NOT NULL,        -- Running, Completed, Failed, Interrupted
+            started_at TEXT NOT NULL,
+            finished_at TEXT,
+            duration_ms INTEGER,
+            items_added INTEGER NOT NULL DEFAULT 0,
+            items_removed INTEGER NOT NULL DEFAULT 0,
+            items_updated INTEGER NOT NULL DEFAULT 0,
+            error TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS scan_history_items (
+            scan_id TEXT NOT NULL REFERENCES scan_history(id) ON DELETE CASCADE,
+            change_type TEXT NOT NULL,   -- Added, Removed, Updated
+            item_id TEXT NOT NULL,       -- Not a FK: removed items are gone
+            item_type TEXT NOT NULL,
+            name TEXT NOT NULL,
+            path TEXT
+        );
+
+        -- Per-item playback rollups, updated as playbacks finish (services::playback_stats)
+        CREATE TABLE IF NOT EXISTS playback_stats (
+            item_id TEXT PRIMARY KEY REFERENCES media_items(id) ON DELETE CASCADE,
+            play_count INTEGER NOT NULL DEFAULT 0,
+            completed_count INTEGER NOT NULL DEFAULT 0,     -- Playbacks that marked the item played
+            completion_sum REAL NOT NULL DEFAULT 0,         -- Sum of completion percentages
+            completion_samples INTEGER NOT NULL DEFAULT 0,  -- Playbacks with a known completion
+            last_played_at TEXT
+        );
+
+        -- Where unfinished playbacks were stopped, in one-minute buckets
+        CREATE TABLE IF NOT EXISTS playback_drop_offs (
+            item_id TEXT NOT NULL REFERENCES media_items(id) ON DELETE CASCADE,
+            minute INTEGER NOT NULL,
+            count INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (item_id, minute)
+        );
+
+        -- Triggers changed through the API and each task's last run (services::scheduled_tasks)
+        CREATE TABLE IF NOT EXISTS scheduled_tasks (
+            id TEXT PRIMARY KEY,
+            triggers TEXT,               -- JSON; NULL keeps the defaults from the config
+            last_status TEXT,            -- Completed, Failed, Cancelled, Aborted
+            last_started_at TEXT,
+            last_finished_at TEXT,
+            last_error TEXT
+        );
+
+        -- The ServerId clients know this server by (services::server_id)
+        CREATE TABLE IF NOT EXISTS server_info (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            server_id TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        -- ffprobe output by file, reused while the size and mtime match (services::mediainfo)
+        CREATE TABLE IF NOT EXISTS probe_cache (
+            path TEXT PRIMARY KEY,
+            file_size INTEGER NOT NULL,
+            file_modified INTEGER NOT NULL,  -- Unix seconds
+            output TEXT NOT NULL,            -- ffprobe's JSON
+            probed_at TEXT NOT NULL
+        );
+
+        -- Provider lookups that found a match, reused by later scans (services::metadata)
+        CREATE TABLE IF NOT EXISTS metadata_cache (
+            provider TEXT NOT NULL,
+            query TEXT NOT NULL,
+            response TEXT NOT NULL,          -- the lookup's result as JSON
+            fetched_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            PRIMARY KEY (provider, query)
+        );
+
+        -- Subtitle and audio files beside a video, found when it's scanned (services::external_streams)
+        CREATE TABLE IF NOT EXISTS external_streams (
+            item_id TEXT NOT NULL REFERENCES media_items(id) ON DELETE CASCADE,
+            stream_index INTEGER NOT NULL,   -- From external_streams::FIRST_INDEX, in file name order
+            path TEXT NOT NULL,
+            is_audio INTEGER NOT NULL DEFAULT 0,
+            codec TEXT,
+            language TEXT,                   -- ISO 639-2/B, parsed from the file name
+            title TEXT,
+            is_default INTEGER NOT NULL DEFAULT 0,
+            is_forced INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (item_id, stream_index)
+        );
+
+        -- Live TV channels from the M3U tuner, replaced on each guide refresh (services::live_tv)
+        CREATE TABLE IF NOT EXISTS channels (
+            id TEXT PRIMARY KEY,             -- Stable across refreshes: from tvg-id, else the stream URL
+            guide_id TEXT,                   -- tvg-id, matched against XMLTV channel ids
+            name TEXT NOT NULL,
+            number TEXT,                     -- tvg-chno, else the position in the playlist
+            group_title TEXT,
+            logo_url TEXT,
+            stream_url TEXT NOT NULL,
+            sort_index INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- XMLTV guide entries that haven't ended yet (services::live_tv)
+        CREATE TABLE IF NOT EXISTS programs (
+            id TEXT PRIMARY KEY,
+            channel_id TEXT NOT NULL REFERENCES channels(id) ON DELETE CASCADE,
+            title TEXT NOT NULL,
+            episode_title TEXT,
+            overview TEXT,
+            category TEXT,
+            image_url TEXT,
+            start_date TEXT NOT NULL,        -- RFC 3339, UTC
+            end_date TEXT NOT NULL
+        );
+
+        -- Parental controls an admin set for a user (services::parental_controls)
+        CREATE TABLE IF NOT EXISTS user_policies (
+            user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+            max_parental_rating INTEGER,     -- Highest media_items.parental_rating shown; NULL for no limit
+            blocked_tags TEXT NOT NULL DEFAULT '[]', -- JSON array of genre and studio names
+            updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
         "#,
     )
     .execute(pool)
     .await?;
 
+    // Columns added after the initial schema, for databases created by older versions
+    add_missing_columns(pool).await?;
+
     // Create indexes in separate statements for better error handling
     create_indexes(pool).await?;
 
+    create_views(pool).await?;
+
+    schema::record(pool).await?;
+
+    Ok(())
+}
+
+/// Columns added to existing tables after their initial release: (table, column, definition)
+/// CREATE TABLE IF NOT EXISTS leaves existing tables untouched, so new columns go here
+/// instead of into the CREATE TABLE statements above.
+/// Any schema change also bumps schema::SCHEMA_VERSION.
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    // Audio language hints parsed from file/folder names ("Dual Audio", "ENG DUB")
+    ("media_items", "audio_languages", "TEXT"), // Comma-separated ISO 639-2 codes
+    ("media_items", "is_dubbed", "INTEGER NOT NULL DEFAULT 0"),
+    ("media_items", "is_dual_audio", "INTEGER NOT NULL DEFAULT 0"),
+    // Video resolution from ffprobe, used for HD/4K badges and filters
+    ("media_items", "width", "INTEGER"),
+    ("media_items", "height", "INTEGER"),
+    // Alternate episode ordering (TMDB episode group) selected for a series,
+    // and the resulting per-episode numbers shown instead of the aired ones
+    ("media_items", "episode_group_id", "TEXT"),
+    ("media_items", "display_parent_index_number", "INTEGER"),
+    ("media_items", "display_index_number", "INTEGER"),
+    // IDs resolved from other providers' cross-references (services::provider_ids)
+    ("media_items", "tvdb_id", "TEXT"),
+    ("media_items", "provider_ids_checked_at", "TEXT"),
+    // Copies of an episode in another file (scanner::duplicates): the primary
+    // episode's ID, and whether the copy is a playable Version or under Review
+    ("media_items", "version_of", "TEXT"),
+    ("media_items", "duplicate_state", "TEXT"),
+    ("media_items", "stream_url", "TEXT"), // Remote URL of a .strm item
+    // Season item of an episode (scanner::seasons)
+    (
+        "media_items",
+        "season_id",
+        "TEXT REFERENCES media_items(id) ON DELETE SET NULL",
+    ),
+    // Album and artist names of music items (scanner::music); artists are
+    // semicolon-separated
+    ("media_items", "album", "TEXT"),
+    ("media_items", "album_artist", "TEXT"),
+    ("media_items", "artists", "TEXT"),
+    // When a library's root folder was found missing (scanner::availability);
+    // NULL while it's online
+    ("libraries", "offline_since", "TEXT"),
+    // Order of the library's items when clients don't ask for one (config
+    // `libraries.sort_by`); NULL means SortName
+    ("libraries", "sort_by", "TEXT"),
+    ("libraries", "sort_order", "TEXT"),
+    // Capabilities registered by the client (services::client_capabilities);
+    // NULL supported_commands means the client never registered any
+    ("active_sessions", "playable_media_types", "TEXT"), // Comma-separated
+    ("active_sessions", "supported_commands", "TEXT"),   // Comma-separated
+    (
+        "active_sessions",
+        "supports_media_control",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    (
+        "active_sessions",
+        "supports_persistent_identifier",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    ("active_sessions", "device_profile", "TEXT"), // DeviceProfile JSON
+    ("active_sessions", "capabilities_updated_at", "TEXT"),
+    ("active_sessions", "playback_reported_at", "TEXT"), // Last playback start/progress report
+    // Roku, TV, iOS... from the client and device name (services::client_capabilities::device_type)
+    ("active_sessions", "device_type", "TEXT"),
+    // Admin who opened a read-as-user session (services::auth::impersonation_session)
+    ("sessions", "impersonated_by", "TEXT"),
+    // When a provider image was downloaded, and queued re-downloads that
+    // replace the cached file (services::image_refresh)
+    ("images", "downloaded_at", "TEXT"),
+    (
+        "image_queue",
+        "replace_existing",
+        "INTEGER NOT NULL DEFAULT 0",
+    ),
+    // Off limits a user to the libraries in user_library_access
+    ("users", "enable_all_folders", "INTEGER NOT NULL DEFAULT 1"),
+    // Accounts an admin disabled can't sign in (services::auth::set_disabled)
+    ("users", "is_disabled", "INTEGER NOT NULL DEFAULT 0"),
+    // Pixel size of a downloaded image (services::image_validation)
+    ("images", "width", "INTEGER"),
+    ("images", "height", "INTEGER"),
+    // Position among an item's images of one type (db::split_image_index);
+    // series and movies can have several backdrops
+    ("images", "image_index", "INTEGER NOT NULL DEFAULT 0"),
+    // Last episode of a multi-episode file (scanner::parse_episode_filename)
+    ("media_items", "index_number_end", "INTEGER"),
+    // Size and modification time (unix seconds) of the file when a refresh
+    // last saw it; a change gets the item probed again (scanner::reconcile)
+    ("media_items", "file_size", "INTEGER"),
+    ("media_items", "file_modified", "INTEGER"),
+    // Per-library overrides of the server's metadata and scanner settings
+    // (scanner::library_settings); NULL keeps the server setting
+    ("libraries", "anime_db_enabled", "INTEGER"),
+    ("libraries", "fetch_episode_metadata", "INTEGER"),
+    ("libraries", "metadata_language", "TEXT"),
+    ("libraries", "extract_media_info", "INTEGER"),
+    // TMDB collection a movie belongs to, and the collection created for it
+    // with its provider artwork (services::box_sets)
+    ("media_items", "tmdb_collection_id", "TEXT"),
+    ("collections", "tmdb_collection_id", "TEXT"),
+    ("collections", "poster_url", "TEXT"),
+    ("collections", "backdrop_url", "TEXT"),
+    // Age rating from the provider and its score in years (services::parental_controls)
+    ("media_items", "official_rating", "TEXT"),
+    ("media_items", "parental_rating", "INTEGER"),
+];
+
+/// Every item hidden from a user, with blocks expanded to the items they cover
+///
+/// A blocked item also hides everything below it, at any depth: a hidden series
+/// hides its seasons and episodes, a hidden artist its albums and their tracks.
+/// Genre blocks match genre names; Tag blocks match genre or studio names,
+/// which are the only tag-like metadata stored. Matching ignores case. Items of
+/// libraries a user has no access to are hidden as well, as are items rated
+/// above a user's parental limit and items with tags their policy blocks
+/// (services::parental_controls).
+const USER_HIDDEN_ITEMS_VIEW: &str = r#"
+CREATE VIEW user_hidden_items AS
+WITH RECURSIVE blocked_roots(user_id, item_id) AS (
+    SELECT user_id, value FROM user_item_blocks WHERE block_type = 'Item'
+    UNION
+    SELECT b.user_id, ig.item_id FROM user_item_blocks b
+    JOIN genres g ON g.name = b.value COLLATE NOCASE
+    JOIN item_genres ig ON ig.genre_id = g.id
+    WHERE b.block_type IN ('Genre', 'Tag')
+    UNION
+    SELECT b.user_id, ist.item_id FROM user_item_blocks b
+    JOIN studios st ON st.name = b.value COLLATE NOCASE
+    JOIN item_studios ist ON ist.studio_id = st.id
+    WHERE b.block_type = 'Tag'
+    UNION
+    SELECT p.user_id, ig.item_id FROM user_policies p, json_each(p.blocked_tags) t
+    JOIN genres g ON g.name = t.value COLLATE NOCASE
+    JOIN item_genres ig ON ig.genre_id = g.id
+    UNION
+    SELECT p.user_id, ist.item_id FROM user_policies p, json_each(p.blocked_tags) t
+    JOIN studios st ON st.name = t.value COLLATE NOCASE
+    JOIN item_studios ist ON ist.studio_id = st.id
+    UNION
+    SELECT p.user_id, m.id FROM user_policies p
+    JOIN media_items m ON m.parental_rating > p.max_parental_rating
+),
+blocked(user_id, item_id) AS (
+    SELECT user_id, item_id FROM blocked_roots
+    UNION
+    SELECT b.user_id, m.id FROM blocked b
+    JOIN media_items m ON m.parent_id = b.item_id OR m.season_id = b.item_id
+)
+SELECT user_id, item_id FROM blocked
+UNION
+SELECT u.id, m.id FROM users u JOIN media_items m
+WHERE u.enable_all_folders = 0
+  AND m.library_id NOT IN (SELECT a.library_id FROM user_library_access a WHERE a.user_id = u.id)
+"#;
+
+/// (Re)create views so their definitions always match this version
+async fn create_views(pool: &SqlitePool) -> Result<()> {
+    sqlx::query("DROP VIEW IF EXISTS user_hidden_items")
+        .execute(pool)
+        .await?;
+    sqlx::query(USER_HIDDEN_ITEMS_VIEW)
+        .execute(pool)
+        .await
+        .context("Failed to create user_hidden_items view")?;
+    Ok(())
+}
+
+/// Add any columns from ADDED_COLUMNS that don't exist yet
+async fn add_missing_columns(pool: &SqlitePool) -> Result<()> {
+    for (table, column, definition) in ADDED_COLUMNS {
+        let existing: Vec<(String,)> =
+            sqlx::query_as(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                .fetch_all(pool)
+                .await?;
+
+        if existing.iter().any(|(name,)| name == column) {
+            continue;
+        }
+
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to add column {}.{}", table, column))?;
+
+        tracing::info!("Added column {}.{}", table, column);
+    }
+
     Ok(())
 }
 
@@ -318,15 +720,31 @@ async fn create_indexes(pool: &SqlitePool) -> Result<()> {
         // Composite: library + type (common filter combination)
         "CREATE INDEX IF NOT EXISTS idx_media_items_library_type ON media_items(library_id, item_type)",
 
+        // Alternate copies of an episode (scanner::duplicates)
+        "CREATE INDEX IF NOT EXISTS idx_media_items_version_of ON media_items(version_of) WHERE version_of IS NOT NULL",
+
+        // Episodes of a season (scanner::seasons)
+        "CREATE INDEX IF NOT EXISTS idx_media_items_season ON media_items(season_id) WHERE season_id IS NOT NULL",
+        "CREATE INDEX IF NOT EXISTS idx_media_items_album_artist ON media_items(album_artist) WHERE album_artist IS NOT NULL",
+
         // Sort by name
         "CREATE INDEX IF NOT EXISTS idx_media_items_sort_name ON media_items(sort_name)",
 
+        // Fast search hints: name matches read only this index, not the rows
+        "CREATE INDEX IF NOT EXISTS idx_media_items_search_hints ON media_items(name, item_type, year, id, library_id, parent_id, season_id, version_of)",
+
+        // Dub-only views
+        "CREATE INDEX IF NOT EXISTS idx_media_items_dubbed ON media_items(is_dubbed) WHERE is_dubbed = 1",
+
         // Sort by year
         "CREATE INDEX IF NOT EXISTS idx_media_items_year ON media_items(year)",
 
         // Sort by community rating
         "CREATE INDEX IF NOT EXISTS idx_media_items_rating ON media_items(community_rating)",
 
+        // Items above a user's parental limit (user_hidden_items)
+        "CREATE INDEX IF NOT EXISTS idx_media_items_parental_rating ON media_items(parental_rating)",
+
         // Sort by date added (created_at)
         "CREATE INDEX IF NOT EXISTS idx_media_items_created ON media_items(created_at)",
 
@@ -397,6 +815,10 @@ async fn create_indexes(pool: &SqlitePool) -> Result<()> {
         // Find items by studio
         "CREATE INDEX IF NOT EXISTS idx_item_studios_studio ON item_studios(studio_id)",
 
+        // Hidden items per user
+        "CREATE INDEX IF NOT EXISTS idx_user_item_blocks_user ON user_item_blocks(user_id, block_type)",
+        "CREATE INDEX IF NOT EXISTS idx_share_links_user ON share_links(user_id)",
+
         // =========================================
         // Libraries indexes
         // =========================================
@@ -414,6 +836,10 @@ async fn create_indexes(pool: &SqlitePool) -> Result<()> {
         // Find collections containing an item
         "CREATE INDEX IF NOT EXISTS idx_collection_items_item ON collection_items(item_id)",
 
+        // One collection per TMDB collection; movies of a TMDB collection
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_collections_tmdb ON collections(tmdb_collection_id)",
+        "CREATE INDEX IF NOT EXISTS idx_media_items_tmdb_collection ON media_items(tmdb_collection_id) WHERE tmdb_collection_id IS NOT NULL",
+
         // =========================================
         // Media segments indexes
         // =========================================
@@ -475,6 +901,25 @@ async fn create_indexes(pool: &SqlitePool) -> Result<()> {
 
         // Find items featuring a person
         "CREATE INDEX IF NOT EXISTS idx_item_persons_person ON item_persons(person_id)",
+
+        // =========================================
+        // Scan history indexes
+        // =========================================
+
+        // List recent scans, optionally for one library
+        "CREATE INDEX IF NOT EXISTS idx_scan_history_started ON scan_history(started_at)",
+        "CREATE INDEX IF NOT EXISTS idx_scan_history_library ON scan_history(library_id, started_at)",
+
+        // Changes made by a scan
+        "CREATE INDEX IF NOT EXISTS idx_scan_history_items_scan ON scan_history_items(scan_id)",
+
+        // =========================================
+        // Live TV indexes
+        // =========================================
+
+        // A channel's guide, skipping programs that have ended
+        "CREATE INDEX IF NOT EXISTS idx_programs_channel ON programs(channel_id, end_date)",
+        "CREATE INDEX IF NOT EXISTS idx_programs_start ON programs(start_date)",
     ];
 
     for index_sql in indexes {
@@ -534,11 +979,71 @@ pub async fn queue_image(
     Ok(())
 }
 
+/// The queue's name for an item's `index`th image of a type ("Backdrop", "Backdrop1", ...)
+///
+/// Each name is queued and cached on its own, and the downloader stores it as
+/// the base type with its index.
+pub fn indexed_image_type(image_type: &str, index: usize) -> String {
+    if index == 0 {
+        image_type.to_string()
+    } else {
+        format!("{}{}", image_type, index)
+    }
+}
+
+/// The type and index of a queued image ("Backdrop2" is Backdrop 2)
+pub fn split_image_index(image_type: &str) -> (&str, i64) {
+    let base = image_type.trim_end_matches(|c: char| c.is_ascii_digit());
+    match image_type[base.len()..].parse() {
+        Ok(index) if !base.is_empty() => (base, index),
+        _ => (image_type, 0),
+    }
+}
+
+/// Queue the backdrops after an item's first one, as Backdrop1, Backdrop2, ...
+pub async fn queue_extra_backdrops(
+    pool: &SqlitePool,
+    item_id: &str,
+    urls: &[String],
+) -> Result<()> {
+    for (i, url) in urls.iter().enumerate() {
+        queue_image(pool, item_id, &indexed_image_type("Backdrop", i + 1), url).await?;
+    }
+    Ok(())
+}
+
+/// Queue a provider image to be downloaded again, replacing the cached file
+pub async fn queue_image_refresh(
+    pool: &SqlitePool,
+    item_id: &str,
+    image_type: &str,
+    url: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO image_queue (item_id, image_type, url, status, replace_existing)
+        VALUES (?, ?, ?, 'pending', 1)
+        ON CONFLICT(item_id, image_type) DO UPDATE SET
+            url = excluded.url,
+            status = 'pending',
+            attempts = 0,
+            replace_existing = 1
+        "#,
+    )
+    .bind(item_id)
+    .bind(image_type)
+    .bind(url)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
 /// Get pending images from the queue (batch)
 pub async fn get_pending_images(pool: &SqlitePool, limit: i32) -> Result<Vec<PendingImage>> {
     let rows: Vec<PendingImage> = sqlx::query_as(
         r#"
-        SELECT id, item_id, image_type, url, attempts
+        SELECT id, item_id, image_type, url, attempts, replace_existing
         FROM image_queue
         WHERE status = 'pending' AND attempts < 3
         ORDER BY id ASC
@@ -592,11 +1097,17 @@ pub struct PendingImage {
     pub image_type: String,
     pub url: String,
     pub attempts: i32,
+    /// Replace the cached file instead of reusing it
+    pub replace_existing: bool,
 }
 
 /// Queue a video file for thumbnail generation
 /// If already queued (even if failed), reset to pending for retry
 pub async fn queue_thumbnail(pool: &SqlitePool, item_id: &str, video_path: &str) -> Result<()> {
+    // Remote streams (.strm) have no local video to grab frames from
+    if crate::services::strm::is_strm_file(std::path::Path::new(video_path)) {
+        return Ok(());
+    }
     sqlx::query(
         r#"
         INSERT INTO thumbnail_queue (item_id, video_path, status, attempts)
@@ -615,6 +1126,141 @@ pub async fn queue_thumbnail(pool: &SqlitePool, item_id: &str, video_path: &str)
     Ok(())
 }
 
+/// Get stored lyrics for an item: (content, source)
+pub async fn get_lyrics(pool: &SqlitePool, item_id: &str) -> Result<Option<(String, String)>> {
+    let row = sqlx::query_as("SELECT content, source FROM lyrics WHERE item_id = ?")
+        .bind(item_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+/// Store lyrics for an item, replacing any previous copy
+pub async fn save_lyrics(
+    pool: &SqlitePool,
+    item_id: &str,
+    content: &str,
+    source: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO lyrics (item_id, content, source, updated_at)
+        VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+        ON CONFLICT(item_id) DO UPDATE SET
+            content = excluded.content,
+            source = excluded.source,
+            updated_at = CURRENT_TIMESTAMP
+        "#,
+    )
+    .bind(item_id)
+    .bind(content)
+    .bind(source)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// A user's hidden item, genre or tag
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ItemBlock {
+    pub block_type: String,
+    pub value: String,
+    pub created_at: String,
+}
+
+/// Get a user's blocks, oldest first
+pub async fn get_item_blocks(pool: &SqlitePool, user_id: &str) -> Result<Vec<ItemBlock>> {
+    let rows = sqlx::query_as(
+        "SELECT block_type, value, created_at FROM user_item_blocks WHERE user_id = ? ORDER BY created_at, block_type, value",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+/// Add a block (no-op if it already exists)
+pub async fn add_item_block(
+    pool: &SqlitePool,
+    user_id: &str,
+    block_type: &str,
+    value: &str,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT OR IGNORE INTO user_item_blocks (user_id, block_type, value) VALUES (?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(block_type)
+    .bind(value)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Remove a block, returning whether it existed
+pub async fn remove_item_block(
+    pool: &SqlitePool,
+    user_id: &str,
+    block_type: &str,
+    value: &str,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "DELETE FROM user_item_blocks WHERE user_id = ? AND block_type = ? AND value = ? COLLATE NOCASE",
+    )
+    .bind(user_id)
+    .bind(block_type)
+    .bind(value)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Libraries a user may see, or None when they may see all of them
+pub async fn get_library_access(pool: &SqlitePool, user_id: &str) -> Result<Option<Vec<String>>> {
+    let all: Option<(bool,)> = sqlx::query_as("SELECT enable_all_folders FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    if all.is_none_or(|(all,)| all) {
+        return Ok(None);
+    }
+    let rows: Vec<(String,)> = sqlx::query_as(
+        "SELECT library_id FROM user_library_access WHERE user_id = ? ORDER BY library_id",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(Some(rows.into_iter().map(|(id,)| id).collect()))
+}
+
+/// Give a user access to all libraries (None) or only to the listed ones
+pub async fn set_library_access(
+    conn: &mut sqlx::SqliteConnection,
+    user_id: &str,
+    library_ids: Option<&[String]>,
+) -> Result<()> {
+    sqlx::query("UPDATE users SET enable_all_folders = ? WHERE id = ?")
+        .bind(library_ids.is_none())
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM user_library_access WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    for library_id in library_ids.unwrap_or_default() {
+        sqlx::query(
+            "INSERT OR IGNORE INTO user_library_access (user_id, library_id)
+             SELECT ?, id FROM libraries WHERE id = ?",
+        )
+        .bind(user_id)
+        .bind(library_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+    Ok(())
+}
+
 /// Check if an item has a thumbnail image
 pub async fn has_thumbnail(pool: &SqlitePool, item_id: &str) -> Result<bool> {
     let row: Option<(i64,)> =
@@ -789,6 +1435,138 @@ pub async fn rebuild_fts_index(pool: &SqlitePool) -> Result<()> {
     Ok(())
 }
 
+/// Items indexed per batch of a tracked rebuild
+const FTS_REBUILD_BATCH: i64 = 2000;
+
+/// Rebuild the FTS index in batches of items, calling `progress(done, total)`
+/// after each so a task can report how far it got
+///
+/// Search finds only the items indexed so far until it finishes, which is why
+/// the startup and event-driven rebuilds use the single statement above.
+pub async fn rebuild_fts_index_with_progress(
+    pool: &SqlitePool,
+    mut progress: impl FnMut(usize, usize),
+) -> Result<()> {
+    tracing::info!("Rebuilding full-text search index in batches...");
+
+    // The 'delete-all' command also works when the shadow tables disagree
+    // with media_items, which a plain DELETE would trip over
+    if sqlx::query("INSERT INTO media_items_fts(media_items_fts) VALUES('delete-all')")
+        .execute(pool)
+        .await
+        .is_err()
+    {
+        // Corrupted beyond that: the one-statement rebuild recreates the table
+        rebuild_fts_index(pool).await?;
+        progress(1, 1);
+        return Ok(());
+    }
+
+    let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM media_items")
+        .fetch_one(pool)
+        .await?;
+    let total = total as usize;
+    let mut done = 0;
+    let mut last_rowid = 0i64;
+    progress(0, total);
+
+    loop {
+        let (batch_end, count): (Option<i64>, i64) = sqlx::query_as(
+            "SELECT MAX(rowid), COUNT(*) FROM
+             (SELECT rowid FROM media_items WHERE rowid > ? ORDER BY rowid LIMIT ?)",
+        )
+        .bind(last_rowid)
+        .bind(FTS_REBUILD_BATCH)
+        .fetch_one(pool)
+        .await?;
+        let Some(batch_end) = batch_end else {
+            break;
+        };
+
+        sqlx::query(
+            r#"
+            INSERT INTO media_items_fts(rowid, name, overview, sort_name)
+            SELECT rowid, name, COALESCE(overview, ''), COALESCE(sort_name, name)
+            FROM media_items WHERE rowid > ? AND rowid <= ?
+            "#,
+        )
+        .bind(last_rowid)
+        .bind(batch_end)
+        .execute(pool)
+        .await?;
+
+        last_rowid = batch_end;
+        done += count as usize;
+        progress(done.min(total), total);
+    }
+
+    tracing::info!("Full-text search index rebuilt ({} items)", done);
+    Ok(())
+}
+
+/// How the FTS index compares with media_items
+///
+/// The index counts are None when the index can't be read.
+#[derive(Debug, Default, Clone)]
+pub struct FtsIndexStatus {
+    /// Number of rows in media_items
+    pub media_item_count: i64,
+    /// Number of documents in the FTS index
+    pub fts_row_count: Option<i64>,
+    /// Media items without a document in the index
+    pub missing_rows: Option<i64>,
+    /// Documents whose media item no longer exists
+    pub stale_rows: Option<i64>,
+}
+
+impl FtsIndexStatus {
+    /// Whether search sees exactly the items in media_items (None when unknown)
+    pub fn in_sync(&self) -> Option<bool> {
+        Some(
+            self.fts_row_count? == self.media_item_count
+                && self.missing_rows? == 0
+                && self.stale_rows? == 0,
+        )
+    }
+}
+
+/// Compare the FTS index with media_items without changing either
+///
+/// The docsize shadow table holds one row per indexed document, keyed by the
+/// rowid of the item it was built from.
+pub async fn verify_fts_index(pool: &SqlitePool) -> Result<FtsIndexStatus> {
+    let (media_item_count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM media_items")
+        .fetch_one(pool)
+        .await?;
+
+    let counts: Result<(i64, i64, i64), _> = sqlx::query_as(
+        "SELECT
+            (SELECT COUNT(*) FROM media_items_fts_docsize),
+            (SELECT COUNT(*) FROM media_items
+             WHERE rowid NOT IN (SELECT id FROM media_items_fts_docsize)),
+            (SELECT COUNT(*) FROM media_items_fts_docsize
+             WHERE id NOT IN (SELECT rowid FROM media_items))",
+    )
+    .fetch_one(pool)
+    .await;
+
+    Ok(match counts {
+        Ok((fts_row_count, missing_rows, stale_rows)) => FtsIndexStatus {
+            media_item_count,
+            fts_row_count: Some(fts_row_count),
+            missing_rows: Some(missing_rows),
+            stale_rows: Some(stale_rows),
+        },
+        Err(e) => {
+            tracing::warn!("FTS index can't be read: {}", e);
+            FtsIndexStatus {
+                media_item_count,
+                ..Default::default()
+            }
+        }
+    })
+}
+
 /// Update FTS index for a single item (use after individual inserts/updates)
 pub async fn update_fts_item(pool: &SqlitePool, item_id: &str) -> Result<()> {
     // Get the rowid for this item
@@ -863,3 +1641,641 @@ fn prepare_fts_query(query: &str) -> String {
 
     terms.join(" OR ")
 }
+
+// ============================================================================
+// Consistency check
+// ============================================================================
+
+/// Tables whose `item_id` column must point at an existing media item
+const ITEM_REFERENCE_TABLES: &[&str] = &[
+    "images",
+    "image_queue",
+    "thumbnail_queue",
+    "playback_progress",
+    "user_favorites",
+    "item_genres",
+    "item_studios",
+    "item_persons",
+    "collection_items",
+    "playlist_items",
+    "media_segments",
+    "lyrics",
+    "series_season_mappings",
+    "external_streams",
+];
+
+/// Result of a database consistency check
+#[derive(Debug, Default)]
+pub struct ConsistencyReport {
+    /// Whether what was found was also repaired
+    pub repaired: bool,
+    /// Rows referencing a deleted media item, per table
+    pub orphaned_rows: Vec<(&'static str, u64)>,
+    /// Image rows whose file no longer exists on disk
+    pub missing_image_files: u64,
+    /// Media items whose parent no longer exists (reported, not repaired)
+    pub dangling_children: i64,
+    /// Media items whose library no longer exists (reported, not repaired)
+    pub dangling_library_items: i64,
+    /// How the FTS index compared with media_items before any repair
+    pub fts: FtsIndexStatus,
+    /// Whether the FTS index was rebuilt because it was out of sync
+    pub fts_rebuilt: bool,
+}
+
+impl ConsistencyReport {
+    /// Number of problems that can be repaired (whether or not they were)
+    pub fn repairable(&self) -> u64 {
+        self.orphaned_rows.iter().map(|(_, n)| n).sum::<u64>()
+            + self.missing_image_files
+            + u64::from(self.fts.in_sync() == Some(false))
+    }
+
+    /// Whether any discrepancy was found
+    pub fn has_issues(&self) -> bool {
+        self.repairable() > 0 || self.dangling_children > 0 || self.dangling_library_items > 0
+    }
+}
+
+/// Validate referential integrity, repairing what can be repaired safely when
+/// `repair` is set and only counting it otherwise
+///
+/// Foreign keys are enforced on our connections, but databases that were written
+/// by older versions or touched by external tools can still accumulate orphans.
+/// Repairs: orphaned item references, image rows pointing at missing files,
+/// and an FTS index that is out of sync with media_items. An FTS index that
+/// can't be read is reported as unknown and left alone.
+pub async fn check_consistency(pool: &SqlitePool, repair: bool) -> Result<ConsistencyReport> {
+    let mut report = ConsistencyReport {
+        repaired: repair,
+        ..Default::default()
+    };
+
+    for table in ITEM_REFERENCE_TABLES {
+        let filter = "item_id NOT IN (SELECT id FROM media_items)";
+        let count = if repair {
+            sqlx::query(&format!("DELETE FROM {} WHERE {}", table, filter))
+                .execute(pool)
+                .await
+                .with_context(|| format!("Failed to clean orphaned rows in {}", table))?
+                .rows_affected()
+        } else {
+            let count: i64 =
+                sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE {}", table, filter))
+                    .fetch_one(pool)
+                    .await
+                    .with_context(|| format!("Failed to count orphaned rows in {}", table))?;
+            count as u64
+        };
+
+        if count > 0 {
+            tracing::warn!(
+                "{} {} orphaned rows in {}",
+                if repair { "Removed" } else { "Found" },
+                count,
+                table
+            );
+            report.orphaned_rows.push((table, count));
+        }
+    }
+
+    // Image rows whose file was deleted out from under us (artwork beside the
+    // media of an offline library is only unreachable)
+    let images: Vec<(String, String)> = sqlx::query_as(
+        "SELECT i.id, i.path FROM images i
+         LEFT JOIN media_items m ON m.id = i.item_id
+         LEFT JOIN libraries l ON l.id = m.library_id
+         WHERE l.offline_since IS NULL",
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for (id, path) in images {
+        if tokio::fs::try_exists(&path).await.unwrap_or(true) {
+            continue;
+        }
+        if repair {
+            sqlx::query("DELETE FROM images WHERE id = ?")
+                .bind(&id)
+                .execute(pool)
+                .await?;
+        }
+        report.missing_image_files += 1;
+    }
+
+    if report.missing_image_files > 0 {
+        tracing::warn!(
+            "{} {} image rows pointing at missing files",
+            if repair { "Removed" } else { "Found" },
+            report.missing_image_files
+        );
+    }
+
+    // Structural problems in media_items itself are only reported - deleting
+    // items could throw away watch history the user still cares about
+    let (dangling_children,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM media_items WHERE parent_id IS NOT NULL AND parent_id NOT IN (SELECT id FROM media_items)",
+    )
+    .fetch_one(pool)
+    .await?;
+    report.dangling_children = dangling_children;
+
+    let (dangling_library_items,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM media_items WHERE library_id NOT IN (SELECT id FROM libraries)",
+    )
+    .fetch_one(pool)
+    .await?;
+    report.dangling_library_items = dangling_library_items;
+
+    if dangling_children > 0 {
+        tracing::warn!(
+            "{} media items reference a parent that no longer exists",
+            dangling_children
+        );
+    }
+    if dangling_library_items > 0 {
+        tracing::warn!(
+            "{} media items reference a library that no longer exists",
+            dangling_library_items
+        );
+    }
+
+    report.fts = verify_fts_index(pool).await?;
+    match report.fts.in_sync() {
+        Some(true) => {}
+        Some(false) => {
+            let fts = &report.fts;
+            tracing::warn!(
+                "FTS index out of sync ({} indexed, {} media items, {} missing, {} stale){}",
+                fts.fts_row_count.unwrap_or_default(),
+                fts.media_item_count,
+                fts.missing_rows.unwrap_or_default(),
+                fts.stale_rows.unwrap_or_default(),
+                if repair { ", rebuilding" } else { "" }
+            );
+            if repair {
+                rebuild_fts_index(pool).await?;
+                report.fts_rebuilt = true;
+            }
+        }
+        None => tracing::warn!(
+            "FTS index couldn't be checked; run the Rebuild Search Index task if search misses items"
+        ),
+    }
+
+    Ok(report)
+}
+
+/// What deleting a library removed
+#[derive(Debug, Default)]
+pub struct LibraryDeleteReport {
+    /// IDs of the media items that were deleted
+    pub item_ids: Vec<String>,
+    /// Cached image folders removed from disk
+    pub image_dirs_removed: usize,
+}
+
+/// Delete a library with everything stored for it
+///
+/// Items, their images, queue entries, unmatched tracking and FTS rows go in one
+/// transaction, so a failure leaves the library as it was. Cached artwork under
+/// the image cache is removed afterwards; artwork beside the media is left alone.
+/// Returns None if there is no such library.
+pub async fn delete_library(
+    pool: &SqlitePool,
+    library_id: &str,
+    image_cache_dir: &Path,
+) -> Result<Option<LibraryDeleteReport>> {
+    let mut tx = pool.begin().await?;
+
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM libraries WHERE id = ?)")
+        .bind(library_id)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !exists {
+        return Ok(None);
+    }
+
+    // The FTS index is external-content, so its rows must go while the items
+    // they were built from still exist
+    sqlx::query(
+        "DELETE FROM media_items_fts WHERE rowid IN
+         (SELECT rowid FROM media_items WHERE library_id = ?)",
+    )
+    .bind(library_id)
+    .execute(&mut *tx)
+    .await
+    .context("Failed to remove library items from the search index")?;
+
+    // Cascades would cover these, but not on databases written without foreign keys
+    for table in ["image_queue", "thumbnail_queue", "images"] {
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE item_id IN (SELECT id FROM media_items WHERE library_id = ?)",
+            table
+        ))
+        .bind(library_id)
+        .execute(&mut *tx)
+        .await
+        .with_context(|| format!("Failed to clear {} for the library", table))?;
+    }
+    for table in [
+        "unmatched_series",
+        "user_library_access",
+        "user_library_views",
+    ] {
+        sqlx::query(&format!("DELETE FROM {} WHERE library_id = ?", table))
+            .bind(library_id)
+            .execute(&mut *tx)
+            .await
+            .with_context(|| format!("Failed to clear {} for the library", table))?;
+    }
+
+    // Collected first: RETURNING would miss children removed by the parent_id cascade
+    let item_ids: Vec<String> =
+        sqlx::query_scalar("SELECT id FROM media_items WHERE library_id = ?")
+            .bind(library_id)
+            .fetch_all(&mut *tx)
+            .await?;
+    sqlx::query("DELETE FROM media_items WHERE library_id = ?")
+        .bind(library_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to delete library items")?;
+
+    sqlx::query("DELETE FROM libraries WHERE id = ?")
+        .bind(library_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    // Cached artwork is stored per item ({image_cache}/{item_id}/) and per
+    // library ({image_cache}/libraries/{library_id}/)
+    let mut report = LibraryDeleteReport {
+        item_ids,
+        image_dirs_removed: 0,
+    };
+    let dirs = report
+        .item_ids
+        .iter()
+        .map(|id| image_cache_dir.join(id))
+        .chain(std::iter::once(
+            image_cache_dir.join("libraries").join(library_id),
+        ));
+    for dir in dirs {
+        match tokio::fs::remove_dir_all(&dir).await {
+            Ok(()) => report.image_dirs_removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to remove {}: {}", dir.display(), e),
+        }
+    }
+
+    Ok(Some(report))
+}
+
+/// Ordered lists whose items clients can rearrange
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ItemList {
+    Playlist,
+    Collection,
+}
+
+impl ItemList {
+    /// Entry table and the column holding the list's ID
+    fn table(self) -> (&'static str, &'static str) {
+        match self {
+            ItemList::Playlist => ("playlist_items", "playlist_id"),
+            ItemList::Collection => ("collection_items", "collection_id"),
+        }
+    }
+}
+
+/// Whether an ID is one of the user's playlists or a collection
+pub async fn find_item_list(
+    pool: &SqlitePool,
+    id: &str,
+    user_id: &str,
+) -> Result<Option<ItemList>> {
+    let is_playlist: Option<i32> =
+        sqlx::query_scalar("SELECT 1 FROM playlists WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+    if is_playlist.is_some() {
+        return Ok(Some(ItemList::Playlist));
+    }
+    let is_collection: Option<i32> = sqlx::query_scalar("SELECT 1 FROM collections WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(is_collection.map(|_| ItemList::Collection))
+}
+
+/// Append items to a playlist or collection, in the given order
+///
+/// Items already in the list and IDs that aren't media items are skipped.
+/// Returns how many items were added.
+pub async fn append_list_items(
+    pool: &SqlitePool,
+    list: ItemList,
+    list_id: &str,
+    item_ids: &[String],
+) -> Result<u64> {
+    let (table, column) = list.table();
+    let mut tx = pool.begin().await?;
+    let mut added = 0;
+    for item_id in item_ids {
+        added += sqlx::query(&format!(
+            "INSERT OR IGNORE INTO {table} ({column}, item_id, sort_order)
+             SELECT ?, id, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM {table} WHERE {column} = ?)
+             FROM media_items WHERE id = ?",
+            table = table,
+            column = column
+        ))
+        .bind(list_id)
+        .bind(list_id)
+        .bind(item_id)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    tx.commit().await?;
+    Ok(added)
+}
+
+/// Move an item of a playlist or collection to a 0-based position
+///
+/// Entries are renumbered 0..n in their listed order, so a position read from
+/// the list is the one to send back. Positions past the end move the item
+/// last. Returns false if the item isn't in the list.
+pub async fn move_list_item(
+    pool: &SqlitePool,
+    list: ItemList,
+    list_id: &str,
+    item_id: &str,
+    new_index: usize,
+) -> Result<bool> {
+    let (table, column) = list.table();
+    let mut tx = pool.begin().await?;
+
+    // Same order the list is served in
+    let mut item_ids: Vec<String> = sqlx::query_scalar(&format!(
+        "SELECT e.item_id FROM {} e JOIN media_items m ON m.id = e.item_id
+         WHERE e.{} = ? ORDER BY e.sort_order, m.sort_name",
+        table, column
+    ))
+    .bind(list_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let Some(current) = item_ids.iter().position(|id| id == item_id) else {
+        return Ok(false);
+    };
+    let moved = item_ids.remove(current);
+    item_ids.insert(new_index.min(item_ids.len()), moved);
+
+    for (position, id) in item_ids.iter().enumerate() {
+        sqlx::query(&format!(
+            "UPDATE {} SET sort_order = ? WHERE {} = ? AND item_id = ?",
+            table, column
+        ))
+        .bind(position as i64)
+        .bind(list_id)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    /// A migrated in-memory database
+    async fn test_pool() -> SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        migrate(&pool).await.unwrap();
+        pool
+    }
+
+    async fn execute_all(pool: &SqlitePool, statements: &[&str]) {
+        for sql in statements {
+            sqlx::query(sql).execute(pool).await.unwrap();
+        }
+    }
+
+    /// A migrated in-memory database without foreign keys, so orphans can be seeded
+    async fn orphan_pool() -> SqlitePool {
+        let pool = test_pool().await;
+        sqlx::query("PRAGMA foreign_keys = OFF")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let existing = env!("CARGO_MANIFEST_DIR");
+        for sql in [
+            "INSERT INTO libraries (id, name, path, library_type) VALUES ('lib', 'TV', '/tv', 'tvshows')".to_string(),
+            "INSERT INTO media_items (id, library_id, item_type, name) VALUES ('kept', 'lib', 'Series', 'Show')".to_string(),
+            "INSERT INTO users (id, name, password_hash) VALUES ('user', 'admin', 'x')".to_string(),
+            "INSERT INTO playback_progress (user_id, item_id) VALUES ('user', 'kept'), ('user', 'gone')".to_string(),
+            "INSERT INTO user_favorites (user_id, item_id) VALUES ('user', 'gone')".to_string(),
+            "INSERT INTO images (id, item_id, image_type, path) VALUES ('missing-file', 'kept', 'Primary', '/nonexistent/poster.jpg')".to_string(),
+            format!("INSERT INTO images (id, item_id, image_type, path) VALUES ('orphan', 'gone', 'Primary', '{}')", existing),
+        ] {
+            sqlx::query(&sql).execute(&pool).await.unwrap();
+        }
+        pool
+    }
+
+    async fn count(pool: &SqlitePool, table: &str) -> i64 {
+        sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table))
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_blocks_hide_everything_below() {
+        let pool = test_pool().await;
+        execute_all(
+            &pool,
+            &[
+                "INSERT INTO users (id, name, password_hash) VALUES ('kid', 'kid', 'x')",
+                "INSERT INTO libraries (id, name, path, library_type) VALUES ('lib', 'Music', '/music', 'music')",
+                "INSERT INTO media_items (id, library_id, parent_id, item_type, name) VALUES
+                    ('artist', 'lib', NULL, 'MusicArtist', 'Artist'),
+                    ('album', 'lib', 'artist', 'MusicAlbum', 'Album'),
+                    ('track1', 'lib', 'album', 'Audio', 'Track 1'),
+                    ('track2', 'lib', 'album', 'Audio', 'Track 2'),
+                    ('other', 'lib', NULL, 'MusicArtist', 'Other'),
+                    ('other-album', 'lib', 'other', 'MusicAlbum', 'Other Album')",
+                "INSERT INTO genres (id, name) VALUES ('g', 'Metal')",
+                "INSERT INTO item_genres (item_id, genre_id) VALUES ('artist', 'g')",
+            ],
+        )
+        .await;
+        let hidden = || async {
+            let mut ids: Vec<String> =
+                sqlx::query_scalar("SELECT item_id FROM user_hidden_items WHERE user_id = 'kid'")
+                    .fetch_all(&pool)
+                    .await
+                    .unwrap();
+            ids.sort();
+            ids
+        };
+        let everything_below = ["album", "artist", "track1", "track2"];
+
+        execute_all(
+            &pool,
+            &["INSERT INTO user_item_blocks (user_id, block_type, value) VALUES ('kid', 'Item', 'artist')"],
+        )
+        .await;
+        assert_eq!(hidden().await, everything_below);
+
+        // A genre block on the artist reaches its tracks the same way
+        execute_all(
+            &pool,
+            &[
+                "DELETE FROM user_item_blocks",
+                "INSERT INTO user_item_blocks (user_id, block_type, value) VALUES ('kid', 'Genre', 'metal')",
+            ],
+        )
+        .await;
+        assert_eq!(hidden().await, everything_below);
+    }
+
+    #[tokio::test]
+    async fn test_consistency_check_reports_then_repairs() {
+        let pool = orphan_pool().await;
+
+        // Report only: everything is found and nothing is touched
+        let report = check_consistency(&pool, false).await.unwrap();
+        assert!(!report.repaired);
+        let mut orphaned = report.orphaned_rows.clone();
+        orphaned.sort();
+        assert_eq!(
+            orphaned,
+            [
+                ("images", 1),
+                ("playback_progress", 1),
+                ("user_favorites", 1)
+            ]
+        );
+        assert_eq!(report.missing_image_files, 1);
+        assert_eq!(report.fts.in_sync(), Some(false));
+        assert!(!report.fts_rebuilt);
+        // Three orphans, the missing image and the search index
+        assert_eq!(report.repairable(), 5);
+        assert_eq!(count(&pool, "playback_progress").await, 2);
+        assert_eq!(count(&pool, "user_favorites").await, 1);
+        assert_eq!(count(&pool, "images").await, 2);
+
+        // Repair: the orphans and the missing image go, the rest stays
+        let report = check_consistency(&pool, true).await.unwrap();
+        assert!(report.repaired);
+        assert!(report.fts_rebuilt);
+        let progress: Vec<String> = sqlx::query_scalar("SELECT item_id FROM playback_progress")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(progress, ["kept"]);
+        assert_eq!(count(&pool, "user_favorites").await, 0);
+        assert_eq!(count(&pool, "images").await, 0);
+        assert_eq!(count(&pool, "media_items").await, 1);
+        assert_eq!(verify_fts_index(&pool).await.unwrap().in_sync(), Some(true));
+
+        assert!(!check_consistency(&pool, true).await.unwrap().has_issues());
+    }
+
+    #[tokio::test]
+    async fn test_delete_library_removes_everything_stored_for_it() {
+        let pool = test_pool().await;
+        execute_all(
+            &pool,
+            &[
+                "INSERT INTO users (id, name, password_hash) VALUES ('user', 'admin', 'x')",
+                "INSERT INTO libraries (id, name, path, library_type) VALUES
+                    ('tv', 'TV', '/tv', 'tvshows'), ('movies', 'Movies', '/movies', 'movies')",
+                "INSERT INTO media_items (id, library_id, parent_id, item_type, name) VALUES
+                    ('show', 'tv', NULL, 'Series', 'Show'),
+                    ('episode', 'tv', 'show', 'Episode', 'Pilot'),
+                    ('movie', 'movies', NULL, 'Movie', 'Film')",
+                "INSERT INTO images (id, item_id, image_type, path) VALUES
+                    ('show-poster', 'show', 'Primary', '/tv/Show/poster.jpg'),
+                    ('movie-poster', 'movie', 'Primary', '/movies/Film/poster.jpg')",
+                "INSERT INTO image_queue (item_id, image_type, url) VALUES
+                    ('show', 'Backdrop', 'http://x/b.jpg'), ('movie', 'Backdrop', 'http://x/m.jpg')",
+                "INSERT INTO thumbnail_queue (item_id, video_path) VALUES
+                    ('episode', '/tv/Show/pilot.mkv'), ('movie', '/movies/Film/film.mkv')",
+                "INSERT INTO unmatched_series (id, library_id, series_id, folder_name) VALUES ('u', 'tv', 'show', 'Show')",
+                "INSERT INTO user_library_access (user_id, library_id) VALUES ('user', 'tv'), ('user', 'movies')",
+                "INSERT INTO user_library_views (user_id, library_id, sort_index) VALUES ('user', 'tv', 0), ('user', 'movies', 1)",
+            ],
+        )
+        .await;
+        rebuild_fts_index(&pool).await.unwrap();
+
+        let cache =
+            std::env::temp_dir().join(format!("jellyfin-rust-images-{}", uuid::Uuid::new_v4()));
+        for dir in ["show", "episode", "movie", "libraries/tv"] {
+            std::fs::create_dir_all(cache.join(dir)).unwrap();
+        }
+
+        let report = delete_library(&pool, "tv", &cache).await.unwrap().unwrap();
+        let mut deleted = report.item_ids.clone();
+        deleted.sort();
+        assert_eq!(deleted, ["episode", "show"]);
+        assert_eq!(report.image_dirs_removed, 3);
+        assert!(!cache.join("episode").exists());
+        assert!(!cache.join("libraries/tv").exists());
+        assert!(cache.join("movie").exists());
+
+        // Only the other library's rows are left
+        let libraries: Vec<String> = sqlx::query_scalar("SELECT id FROM libraries")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(libraries, ["movies"]);
+        for table in [
+            "media_items",
+            "images",
+            "image_queue",
+            "thumbnail_queue",
+            "user_library_access",
+            "user_library_views",
+        ] {
+            assert_eq!(count(&pool, table).await, 1, "{}", table);
+        }
+        assert_eq!(count(&pool, "unmatched_series").await, 0);
+        let fts = verify_fts_index(&pool).await.unwrap();
+        assert_eq!(fts.fts_row_count, Some(1));
+        assert_eq!(fts.in_sync(), Some(true));
+
+        assert!(delete_library(&pool, "tv", &cache).await.unwrap().is_none());
+        std::fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_search_index_is_unknown() {
+        let pool = orphan_pool().await;
+        sqlx::query("DROP TABLE media_items_fts")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let report = check_consistency(&pool, true).await.unwrap();
+        assert_eq!(report.fts.fts_row_count, None);
+        assert_eq!(report.fts.in_sync(), None);
+        assert!(!report.fts_rebuilt);
+        // The orphans and the missing image, not the index
+        assert_eq!(report.repairable(), 4);
+    }
+}
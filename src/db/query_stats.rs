@@ -0,0 +1,315 @@
+// Query timing statistics and slow-query log
+//
+// sqlx reports every statement it finishes as a `sqlx::query` tracing event
+// with its elapsed time: at TRACE normally, at WARN once it exceeds the slow
+// threshold (both configured on the connect options in main.rs). The layer
+// below aggregates those events per route and per statement. Requests run
+// inside a `route` span carrying their matched path; sqlx's connection worker
+// re-enters the caller's span, so statements are attributed to the route that
+// issued them. Statements outside a request (scans, background tasks) are
+// grouped under BACKGROUND_ROUTE.
+
+use axum::{extract::MatchedPath, extract::Request, middleware::Next, response::Response};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Instrument, Level, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Target sqlx logs statements under
+const SQLX_TARGET: &str = "sqlx::query";
+
+/// Name of the span that tags a request with its route
+const ROUTE_SPAN: &str = "route";
+
+/// Route recorded for statements issued outside a request
+pub const BACKGROUND_ROUTE: &str = "(background)";
+
+/// Distinct statements tracked; further ones are only counted per route
+const MAX_STATEMENTS: usize = 1000;
+
+/// Slow statements kept for the admin endpoint
+const MAX_RECENT_SLOW: usize = 50;
+
+/// Longest statement text kept for a slow query
+const MAX_STATEMENT_LEN: usize = 4000;
+
+/// Aggregated timings for a group of statements
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryTiming {
+    pub count: u64,
+    pub slow_count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl QueryTiming {
+    fn add(&mut self, elapsed: Duration, slow: bool) {
+        self.count += 1;
+        self.total += elapsed;
+        self.max = self.max.max(elapsed);
+        if slow {
+            self.slow_count += 1;
+        }
+    }
+}
+
+/// A statement that exceeded the slow-query threshold
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SlowQuery {
+    pub route: String,
+    pub statement: String,
+    pub duration_ms: f64,
+    pub rows_returned: u64,
+    pub date: String,
+}
+
+#[derive(Default)]
+struct Stats {
+    routes: HashMap<String, QueryTiming>,
+    statements: HashMap<String, QueryTiming>,
+    recent_slow: VecDeque<SlowQuery>,
+}
+
+static STATS: LazyLock<Mutex<Stats>> = LazyLock::new(|| Mutex::new(Stats::default()));
+
+fn stats() -> std::sync::MutexGuard<'static, Stats> {
+    STATS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Per-route timings, slowest total first
+pub fn route_timings() -> Vec<(String, QueryTiming)> {
+    sorted(&stats().routes)
+}
+
+/// Per-statement timings (keyed by sqlx's statement summary), slowest total first
+pub fn statement_timings() -> Vec<(String, QueryTiming)> {
+    sorted(&stats().statements)
+}
+
+/// Most recent slow statements, newest first
+pub fn recent_slow_queries() -> Vec<SlowQuery> {
+    stats().recent_slow.iter().rev().cloned().collect()
+}
+
+/// Forget everything recorded so far
+pub fn reset() {
+    *stats() = Stats::default();
+}
+
+fn sorted(map: &HashMap<String, QueryTiming>) -> Vec<(String, QueryTiming)> {
+    let mut list: Vec<(String, QueryTiming)> =
+        map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+    list.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+    list
+}
+
+fn record(route: &str, query: &QueryEvent, slow: bool) {
+    let elapsed = Duration::from_secs_f64(query.elapsed_secs.max(0.0));
+    let mut stats = stats();
+
+    stats
+        .routes
+        .entry(route.to_string())
+        .or_default()
+        .add(elapsed, slow);
+
+    let summary = query.summary.as_str();
+    if let Some(timing) = stats.statements.get_mut(summary) {
+        timing.add(elapsed, slow);
+    } else if stats.statements.len() < MAX_STATEMENTS {
+        let mut timing = QueryTiming::default();
+        timing.add(elapsed, slow);
+        stats.statements.insert(summary.to_string(), timing);
+    }
+
+    if slow {
+        // sqlx leaves the full statement empty when the summary already is one
+        let statement = if query.statement.trim().is_empty() {
+            query.summary.clone()
+        } else {
+            query.statement.trim().to_string()
+        };
+        if stats.recent_slow.len() == MAX_RECENT_SLOW {
+            stats.recent_slow.pop_front();
+        }
+        stats.recent_slow.push_back(SlowQuery {
+            route: route.to_string(),
+            statement: statement.chars().take(MAX_STATEMENT_LEN).collect(),
+            duration_ms: elapsed.as_secs_f64() * 1000.0,
+            rows_returned: query.rows_returned,
+            date: chrono::Utc::now().to_rfc3339(),
+        });
+    }
+}
+
+/// Middleware running each request inside a span naming its matched route
+///
+/// Must be added with `route_layer` so the matched path is known.
+pub async fn route_span(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    // WARN so the route still prefixes slow statement warnings under RUST_LOG=warn
+    let span = tracing::warn_span!(ROUTE_SPAN, path = %route);
+    next.run(request).instrument(span).await
+}
+
+/// Route stored in a `route` span's extensions
+struct RouteName(String);
+
+#[derive(Default)]
+struct RouteVisitor(Option<String>);
+
+impl Visit for RouteVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "path" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "path" {
+            // Display values arrive here; Debug would add quotes
+            self.0 = Some(format!("{:?}", value).trim_matches('"').to_string());
+        }
+    }
+}
+
+/// Fields of a sqlx statement event
+#[derive(Default)]
+struct QueryEvent {
+    summary: String,
+    statement: String,
+    elapsed_secs: f64,
+    rows_returned: u64,
+}
+
+impl Visit for QueryEvent {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "summary" => self.summary = value.to_string(),
+            "db.statement" => self.statement = value.to_string(),
+            _ => {}
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "elapsed_secs" {
+            self.elapsed_secs = value;
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if field.name() == "rows_returned" {
+            self.rows_returned = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Tracing layer collecting statement timings
+pub struct QueryStatsLayer;
+
+impl<S> Layer<S> for QueryStatsLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: Context<'_, S>,
+    ) {
+        if attrs.metadata().name() != ROUTE_SPAN {
+            return;
+        }
+        let mut visitor = RouteVisitor::default();
+        attrs.record(&mut visitor);
+        if let (Some(route), Some(span)) = (visitor.0, ctx.span(id)) {
+            span.extensions_mut().insert(RouteName(route));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if event.metadata().target() != SQLX_TARGET {
+            return;
+        }
+        let mut query = QueryEvent::default();
+        event.record(&mut query);
+
+        let route = ctx.event_scope(event).and_then(|scope| {
+            scope
+                .from_root()
+                .find_map(|span| span.extensions().get::<RouteName>().map(|r| r.0.clone()))
+        });
+        // sqlx only logs at WARN (or above) for statements over the threshold
+        let slow = *event.metadata().level() <= Level::WARN;
+        record(route.as_deref().unwrap_or(BACKGROUND_ROUTE), &query, slow);
+    }
+}
+
+/// The statistics layer, filtered to sqlx statements and route spans
+pub fn layer<S>() -> impl Layer<S>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let filter: Targets = Targets::new()
+        .with_target(SQLX_TARGET, Level::TRACE)
+        .with_target(module_path!(), Level::WARN);
+    QueryStatsLayer.with_filter(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_statements_are_attributed_to_their_route() {
+        let subscriber = tracing_subscriber::registry().with(layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::warn_span!(ROUTE_SPAN, path = %"/Items/:id");
+            let _guard = span.enter();
+            tracing::trace!(
+                target: SQLX_TARGET,
+                summary = "SELECT * FROM test_fast",
+                elapsed_secs = 0.002,
+                rows_returned = 1u64,
+            );
+            tracing::warn!(
+                target: SQLX_TARGET,
+                summary = "SELECT * FROM test_slow",
+                elapsed_secs = 1.5,
+                rows_returned = 7u64,
+                "slow statement: execution time exceeded alert threshold"
+            );
+        });
+
+        let routes = route_timings();
+        let (_, timing) = routes.iter().find(|(r, _)| r == "/Items/:id").unwrap();
+        assert_eq!(timing.count, 2);
+        assert_eq!(timing.slow_count, 1);
+        assert_eq!(timing.max, Duration::from_secs_f64(1.5));
+
+        let slow = recent_slow_queries();
+        let query = slow
+            .iter()
+            .find(|q| q.statement == "SELECT * FROM test_slow")
+            .unwrap();
+        assert_eq!(query.route, "/Items/:id");
+        assert_eq!(query.rows_returned, 7);
+        assert!(!slow
+            .iter()
+            .any(|q| q.statement == "SELECT * FROM test_fast"));
+    }
+}
@@ -0,0 +1,190 @@
+// Schema version tracking and downgrade protection
+//
+// The schema is migrated in place on every start (CREATE TABLE IF NOT EXISTS,
+// ADDED_COLUMNS, create_indexes), which only ever moves forward. An older
+// binary started against a database a newer one has migrated could misread
+// columns it doesn't know about, so the schema_info row records the schema
+// version, the oldest app version that can still open the database, and the
+// app version that last opened it. Startup refuses to continue when this
+// build is older than that minimum.
+
+use anyhow::{bail, Result};
+use sqlx::SqlitePool;
+
+use crate::version::Version;
+
+/// Schema version this build migrates to
+///
+/// Bump it with any schema change (new table, ADDED_COLUMNS entry, view).
+pub const SCHEMA_VERSION: i64 = 24;
+
+/// Oldest app version that can open a database at SCHEMA_VERSION
+///
+/// Additive changes (new tables, nullable or defaulted columns) keep it, since
+/// older builds simply don't read them. Raise it to the current release when a
+/// change would make older builds misbehave (a column they write with the wrong
+/// meaning, data moved to another table).
+///
+/// 0.2.0: seasons are stored as Season items that episodes point at with
+/// season_id; older builds list them as unknown children of their series.
+pub const MIN_COMPATIBLE_APP_VERSION: &str = "0.2.0";
+
+/// This build's version
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+struct SchemaInfo {
+    schema_version: i64,
+    min_app_version: String,
+    app_version: String,
+}
+
+/// Refuse databases this build is too old for
+fn check_compatible(stored: &SchemaInfo, app_version: &str) -> Result<()> {
+    let (Some(minimum), Some(current)) = (
+        Version::parse(&stored.min_app_version),
+        Version::parse(app_version),
+    ) else {
+        bail!(
+            "Database records an unreadable minimum version '{}'",
+            stored.min_app_version
+        );
+    };
+    // Pre-releases count as their release: a 0.2.0-rc build opens databases
+    // that need 0.2.0, since it already has that release's schema
+    if current.numbers() < minimum.numbers() {
+        bail!(
+            "This database was upgraded to schema version {} by jellyfin-rust {} and needs \
+             jellyfin-rust {} or newer, but this is version {}. Run a newer build, or restore \
+             a backup of the database taken before the upgrade.",
+            stored.schema_version,
+            stored.app_version,
+            stored.min_app_version,
+            app_version
+        );
+    }
+    Ok(())
+}
+
+/// What to record after a successful start, or None to leave the row alone
+///
+/// A newer (compatible) schema keeps its own record, so starting an older
+/// build never lowers the version or the minimum.
+fn next_info(stored: Option<&SchemaInfo>) -> Option<SchemaInfo> {
+    match stored {
+        Some(info) if info.schema_version > SCHEMA_VERSION => None,
+        Some(info) if info.schema_version == SCHEMA_VERSION && info.app_version == APP_VERSION => {
+            None
+        }
+        _ => Some(SchemaInfo {
+            schema_version: SCHEMA_VERSION,
+            min_app_version: MIN_COMPATIBLE_APP_VERSION.to_string(),
+            app_version: APP_VERSION.to_string(),
+        }),
+    }
+}
+
+/// Check the database can be opened by this build, before anything is migrated
+pub async fn check(pool: &SqlitePool) -> Result<()> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS schema_info (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            schema_version INTEGER NOT NULL,
+            min_app_version TEXT NOT NULL,
+            app_version TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        )",
+    )
+    .execute(pool)
+    .await?;
+
+    if let Some(stored) = stored_info(pool).await? {
+        check_compatible(&stored, APP_VERSION)?;
+        if stored.schema_version > SCHEMA_VERSION {
+            tracing::warn!(
+                "Database schema version {} (from jellyfin-rust {}) is newer than this build's {}; \
+                 it is marked compatible, but features of the newer version won't be available",
+                stored.schema_version,
+                stored.app_version,
+                SCHEMA_VERSION
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Record this build's schema version once migrations have run
+pub async fn record(pool: &SqlitePool) -> Result<()> {
+    let stored = stored_info(pool).await?;
+    let Some(info) = next_info(stored.as_ref()) else {
+        return Ok(());
+    };
+
+    sqlx::query(
+        "INSERT INTO schema_info (id, schema_version, min_app_version, app_version, updated_at)
+         VALUES (1, ?, ?, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET
+            schema_version = excluded.schema_version,
+            min_app_version = excluded.min_app_version,
+            app_version = excluded.app_version,
+            updated_at = excluded.updated_at",
+    )
+    .bind(info.schema_version)
+    .bind(&info.min_app_version)
+    .bind(&info.app_version)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+
+    match stored {
+        Some(old) if old.schema_version < info.schema_version => tracing::info!(
+            "Database schema upgraded from version {} to {}",
+            old.schema_version,
+            info.schema_version
+        ),
+        _ => {}
+    }
+    Ok(())
+}
+
+async fn stored_info(pool: &SqlitePool) -> Result<Option<SchemaInfo>> {
+    Ok(sqlx::query_as(
+        "SELECT schema_version, min_app_version, app_version FROM schema_info WHERE id = 1",
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(schema_version: i64, min_app_version: &str) -> SchemaInfo {
+        SchemaInfo {
+            schema_version,
+            min_app_version: min_app_version.to_string(),
+            app_version: "9.9.9".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_downgrade_protection() {
+        // A newer schema that older builds can still read
+        assert!(check_compatible(&info(SCHEMA_VERSION + 1, "0.1.0"), "0.2.0").is_ok());
+        // One that needs a newer build (0.10 sorts after 0.9)
+        let err = check_compatible(&info(SCHEMA_VERSION + 1, "0.10.0"), "0.9.5").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("needs jellyfin-rust 0.10.0 or newer"));
+        assert!(check_compatible(&info(SCHEMA_VERSION, "0.2.0"), "0.2.0-rc1").is_ok());
+        assert!(check_compatible(&info(SCHEMA_VERSION, "garbage"), "0.2.0").is_err());
+
+        // Older builds never overwrite a newer schema's record
+        assert_eq!(next_info(Some(&info(SCHEMA_VERSION + 1, "0.1.0"))), None);
+        assert_eq!(
+            next_info(Some(&info(SCHEMA_VERSION - 1, "0.0.1"))).map(|i| i.schema_version),
+            Some(SCHEMA_VERSION)
+        );
+        assert!(next_info(None).is_some());
+    }
+}
@@ -0,0 +1,391 @@
+// Server-wide event bus
+//
+// Modules publish what happened (an item was added, playback started, a scan
+// finished) without knowing who cares. Cross-cutting features - search index
+// updates, notifications, activity logging - subscribe instead of being called
+// directly from the scanner and API handlers.
+
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::BTreeSet;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use crate::db;
+use crate::services::playback_stats;
+
+/// Events buffered per subscriber before slow consumers start lagging
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Quiet period after removals before the search index is rebuilt
+const FTS_REBUILD_DELAY: Duration = Duration::from_secs(5);
+
+static EVENT_BUS: LazyLock<broadcast::Sender<ServerEvent>> =
+    LazyLock::new(|| broadcast::channel(EVENT_BUS_CAPACITY).0);
+
+/// Something that happened on the server
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "Type", rename_all = "PascalCase")]
+pub enum ServerEvent {
+    /// A media item was inserted by a scan
+    #[serde(rename_all = "PascalCase")]
+    ItemAdded {
+        item_id: String,
+        item_type: String,
+        library_id: String,
+    },
+    /// An item's metadata changed (refresh, edit)
+    #[serde(rename_all = "PascalCase")]
+    ItemUpdated { item_id: String },
+    /// An item gained or replaced an image (downloaded poster, generated thumbnail)
+    #[serde(rename_all = "PascalCase")]
+    ImageUpdated { item_id: String, image_type: String },
+    /// A media item was deleted (file removed or deleted via the API)
+    #[serde(rename_all = "PascalCase")]
+    ItemRemoved { item_id: String },
+    #[serde(rename_all = "PascalCase")]
+    PlaybackStarted {
+        user_id: String,
+        item_id: String,
+        device_id: String,
+    },
+    #[serde(rename_all = "PascalCase")]
+    PlaybackStopped {
+        user_id: String,
+        item_id: String,
+        position_ticks: i64,
+        played: bool,
+    },
+    /// Favorite or played state changed for one or more items (bulk edits publish once)
+    #[serde(rename_all = "PascalCase")]
+    UserDataChanged {
+        user_id: String,
+        item_ids: Vec<String>,
+    },
+    /// An admin made a request as another user (audit trail)
+    #[serde(rename_all = "PascalCase")]
+    UserImpersonated {
+        admin_user_id: String,
+        user_id: String,
+        method: String,
+        path: String,
+    },
+    /// An account was created (by an admin or with an invite)
+    #[serde(rename_all = "PascalCase")]
+    UserCreated { user_id: String, user_name: String },
+    /// A full, quick, or targeted scan finished
+    #[serde(rename_all = "PascalCase")]
+    ScanCompleted {
+        library_id: String,
+        items_added: i32,
+        items_removed: i32,
+    },
+    /// The release feed has a newer version than the one running
+    #[serde(rename_all = "PascalCase")]
+    UpdateAvailable { version: String, url: String },
+}
+
+/// Publish an event to all subscribers (no-op if nobody is listening)
+pub fn publish(event: ServerEvent) {
+    let _ = EVENT_BUS.send(event);
+}
+
+/// Subscribe to events published from now on
+pub fn subscribe() -> broadcast::Receiver<ServerEvent> {
+    EVENT_BUS.subscribe()
+}
+
+/// Item changes collected for one `LibraryChanged` WebSocket message
+///
+/// Serializes as Jellyfin's LibraryUpdateInfo. An item only appears in one
+/// list: updates to an item added in the same batch are part of the add, and an
+/// item added and removed again is left out entirely.
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct LibraryChanges {
+    pub folders_added_to: BTreeSet<String>,
+    pub folders_removed_from: BTreeSet<String>,
+    pub items_added: BTreeSet<String>,
+    pub items_removed: BTreeSet<String>,
+    pub items_updated: BTreeSet<String>,
+    pub collection_folders: BTreeSet<String>,
+}
+
+impl LibraryChanges {
+    /// Add an event to the batch; returns false for events that aren't item changes
+    pub fn record(&mut self, event: &ServerEvent) -> bool {
+        match event {
+            ServerEvent::ItemAdded {
+                item_id,
+                library_id,
+                ..
+            } => {
+                self.items_added.insert(item_id.clone());
+                self.items_updated.remove(item_id);
+                self.folders_added_to.insert(library_id.clone());
+                self.collection_folders.insert(library_id.clone());
+            }
+            ServerEvent::ItemUpdated { item_id } | ServerEvent::ImageUpdated { item_id, .. } => {
+                if !self.items_added.contains(item_id) && !self.items_removed.contains(item_id) {
+                    self.items_updated.insert(item_id.clone());
+                }
+            }
+            ServerEvent::ItemRemoved { item_id } => {
+                self.items_updated.remove(item_id);
+                if !self.items_added.remove(item_id) {
+                    self.items_removed.insert(item_id.clone());
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items_added.is_empty()
+            && self.items_removed.is_empty()
+            && self.items_updated.is_empty()
+    }
+}
+
+/// Keep the full-text search index in sync with item changes
+///
+/// Removed items can't be looked up once deleted, so removals (and lagged
+/// receivers) schedule a full rebuild once events have been quiet for a moment.
+/// This batches the many removals of a scan into a single rebuild.
+pub async fn run_fts_updater(pool: SqlitePool, cancel: CancellationToken) {
+    let mut events = subscribe();
+    let mut needs_rebuild = false;
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(FTS_REBUILD_DELAY), if needs_rebuild => {
+                if let Err(e) = db::rebuild_fts_index(&pool).await {
+                    tracing::error!("Failed to rebuild FTS index: {}", e);
+                }
+                needs_rebuild = false;
+                continue;
+            }
+            event = events.recv() => event,
+        };
+
+        match event {
+            Ok(ServerEvent::ItemAdded { item_id, .. })
+            | Ok(ServerEvent::ItemUpdated { item_id }) => {
+                if let Err(e) = db::update_fts_item(&pool, &item_id).await {
+                    tracing::warn!("Failed to update search index for {}: {}", item_id, e);
+                }
+            }
+            Ok(ServerEvent::ItemRemoved { .. }) => needs_rebuild = true,
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!(
+                    "Search index updater missed {} events, scheduling rebuild",
+                    skipped
+                );
+                needs_rebuild = true;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Fold finished playbacks into the per-item playback analytics
+pub async fn run_playback_stats(pool: SqlitePool, cancel: CancellationToken) {
+    let mut events = subscribe();
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => event,
+        };
+
+        match event {
+            Ok(ServerEvent::PlaybackStopped {
+                item_id,
+                position_ticks,
+                played,
+                ..
+            }) => {
+                if let Err(e) =
+                    playback_stats::record_playback(&pool, &item_id, position_ticks, played).await
+                {
+                    tracing::warn!("Failed to record playback stats for {}: {}", item_id, e);
+                }
+            }
+            Ok(_) => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!("Playback stats missed {} events", skipped)
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// Trace every event at debug level (RUST_LOG=jellyfin_rust::events=debug)
+pub async fn run_activity_log(cancel: CancellationToken) {
+    let mut events = subscribe();
+
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => event,
+        };
+
+        match event {
+            Ok(ServerEvent::ItemAdded {
+                item_id, item_type, ..
+            }) => tracing::debug!("Activity: {} {} added", item_type, item_id),
+            Ok(ServerEvent::ItemUpdated { item_id }) => {
+                tracing::debug!("Activity: item {} updated", item_id)
+            }
+            Ok(ServerEvent::ImageUpdated {
+                item_id,
+                image_type,
+            }) => tracing::debug!("Activity: {} image of {} updated", image_type, item_id),
+            Ok(ServerEvent::ItemRemoved { item_id }) => {
+                tracing::debug!("Activity: item {} removed", item_id)
+            }
+            Ok(ServerEvent::PlaybackStarted {
+                user_id,
+                item_id,
+                device_id,
+            }) => tracing::debug!(
+                "Activity: user {} started playing {} on {}",
+                user_id,
+                item_id,
+                device_id
+            ),
+            Ok(ServerEvent::PlaybackStopped {
+                user_id,
+                item_id,
+                played,
+                ..
+            }) => tracing::debug!(
+                "Activity: user {} stopped {}{}",
+                user_id,
+                item_id,
+                if played { " (marked played)" } else { "" }
+            ),
+            Ok(ServerEvent::UserDataChanged { user_id, item_ids }) => tracing::debug!(
+                "Activity: user {} changed data of {} item(s)",
+                user_id,
+                item_ids.len()
+            ),
+            // An audit trail, so logged at info rather than debug
+            Ok(ServerEvent::UserImpersonated {
+                admin_user_id,
+                user_id,
+                method,
+                path,
+            }) => tracing::info!(
+                "Activity: admin {} requested {} {} as user {}",
+                admin_user_id,
+                method,
+                path,
+                user_id
+            ),
+            Ok(ServerEvent::UserCreated { user_id, user_name }) => {
+                tracing::debug!("Activity: user {} ({}) created", user_name, user_id)
+            }
+            Ok(ServerEvent::ScanCompleted {
+                library_id,
+                items_added,
+                items_removed,
+            }) => tracing::debug!(
+                "Activity: scan of library {} finished ({} added, {} removed)",
+                library_id,
+                items_added,
+                items_removed
+            ),
+            // Meant for admins, so logged at info like the audit trail
+            Ok(ServerEvent::UpdateAvailable { version, url }) => {
+                tracing::info!("Activity: version {} is available ({})", version, url)
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::debug!("Activity log missed {} events", skipped)
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers() {
+        let mut first = subscribe();
+        let mut second = subscribe();
+
+        publish(ServerEvent::ItemRemoved {
+            item_id: "test-item".to_string(),
+        });
+
+        for rx in [&mut first, &mut second] {
+            // Other tests may publish concurrently, so look for ours specifically
+            loop {
+                match rx.recv().await.unwrap() {
+                    ServerEvent::ItemRemoved { item_id } if item_id == "test-item" => break,
+                    _ => continue,
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_library_changes_batching() {
+        let mut changes = LibraryChanges::default();
+        let added = |id: &str| ServerEvent::ItemAdded {
+            item_id: id.to_string(),
+            item_type: "Episode".to_string(),
+            library_id: "lib".to_string(),
+        };
+        let updated = |id: &str| ServerEvent::ImageUpdated {
+            item_id: id.to_string(),
+            image_type: "Primary".to_string(),
+        };
+        let removed = |id: &str| ServerEvent::ItemRemoved {
+            item_id: id.to_string(),
+        };
+
+        assert!(changes.record(&added("new")));
+        assert!(changes.record(&updated("new")));
+        assert!(changes.record(&updated("poster")));
+        assert!(changes.record(&updated("poster")));
+        assert!(changes.record(&added("temp")));
+        assert!(changes.record(&removed("temp")));
+        assert!(changes.record(&removed("old")));
+        // Removed items aren't reported as updated too
+        assert!(changes.record(&ServerEvent::ItemUpdated {
+            item_id: "old".to_string()
+        }));
+        assert!(!changes.record(&ServerEvent::UserDataChanged {
+            user_id: "u".to_string(),
+            item_ids: vec!["poster".to_string()],
+        }));
+
+        let json = serde_json::to_value(&changes).unwrap();
+        assert_eq!(json["ItemsAdded"], serde_json::json!(["new"]));
+        assert_eq!(json["ItemsUpdated"], serde_json::json!(["poster"]));
+        assert_eq!(json["ItemsRemoved"], serde_json::json!(["old"]));
+        assert_eq!(json["CollectionFolders"], serde_json::json!(["lib"]));
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = ServerEvent::ScanCompleted {
+            library_id: "lib".to_string(),
+            items_added: 2,
+            items_removed: 1,
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["Type"], "ScanCompleted");
+        assert_eq!(json["LibraryId"], "lib");
+        assert_eq!(json["ItemsAdded"], 2);
+    }
+}
@@ -0,0 +1,236 @@
+// Logging setup: stdout plus optional rotating log files
+//
+// The subscriber is installed before the config is loaded (so config warnings
+// are not lost); file output is switched on afterwards through a reload handle.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing_subscriber::{
+    fmt::{self, format::DefaultFields, format::Format, MakeWriter},
+    layer::SubscriberExt,
+    reload,
+    util::SubscriberInitExt,
+    EnvFilter, Layer, Registry,
+};
+
+use crate::db::query_stats;
+
+/// Log files are named "<prefix>_<YYYYMMDD>_<HHMMSS>.log" so names sort chronologically
+pub const LOG_FILE_PREFIX: &str = "jellyfin-rust";
+pub const LOG_FILE_EXTENSION: &str = "log";
+
+type FileLayer = fmt::Layer<Registry, DefaultFields, Format, RollingFileWriter>;
+
+/// Handle used to attach the file layer once the log directory is known
+pub type FileLogHandle = reload::Handle<Option<FileLayer>, Registry>;
+
+/// Install the global subscriber (stdout only until file output is enabled)
+pub fn init() -> FileLogHandle {
+    let (file_layer, handle) = reload::Layer::new(None);
+
+    // Filters are per output so the query statistics layer still receives
+    // sqlx's per-statement events when RUST_LOG hides them
+    tracing_subscriber::registry()
+        .with(file_layer.with_filter(env_filter()))
+        .with(fmt::layer().with_filter(env_filter()))
+        .with(query_stats::layer())
+        .init();
+
+    handle
+}
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "jellyfin_rust=debug,tower_http=debug,sqlx::query=warn".into())
+}
+
+/// Start writing logs to rotating files in `dir`
+pub fn enable_file_output(
+    handle: &FileLogHandle,
+    dir: PathBuf,
+    max_file_size_mb: u64,
+    max_files: usize,
+) -> Result<()> {
+    let writer = RollingFileWriter::new(dir, max_file_size_mb * 1024 * 1024, max_files)?;
+    let layer = fmt::layer().with_ansi(false).with_writer(writer);
+    handle
+        .reload(Some(layer))
+        .context("Failed to attach log file output")
+}
+
+/// Whether a file name looks like one of our log files (used to validate downloads)
+pub fn is_log_file_name(name: &str) -> bool {
+    !name.contains(['/', '\\'])
+        && name.starts_with(LOG_FILE_PREFIX)
+        && Path::new(name).extension().and_then(|e| e.to_str()) == Some(LOG_FILE_EXTENSION)
+}
+
+/// List log files in a directory, newest first
+pub fn list_log_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(is_log_file_name)
+        })
+        .collect();
+    files.sort();
+    files.reverse();
+    Ok(files)
+}
+
+struct RollingState {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: Option<File>,
+    /// Day (YYYYMMDD, UTC) the current file was opened
+    day: String,
+    size: u64,
+}
+
+impl RollingState {
+    fn open_new_file(&mut self) -> io::Result<()> {
+        let now = chrono::Utc::now();
+        let mut path = self.dir.join(format!(
+            "{}_{}.{}",
+            LOG_FILE_PREFIX,
+            now.format("%Y%m%d_%H%M%S"),
+            LOG_FILE_EXTENSION
+        ));
+        // Size-based rotation can happen twice within a second
+        let mut suffix = 1;
+        while path.exists() {
+            path = self.dir.join(format!(
+                "{}_{}_{}.{}",
+                LOG_FILE_PREFIX,
+                now.format("%Y%m%d_%H%M%S"),
+                suffix,
+                LOG_FILE_EXTENSION
+            ));
+            suffix += 1;
+        }
+
+        self.file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+        self.day = now.format("%Y%m%d").to_string();
+        self.size = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete the oldest files beyond max_files
+    fn prune(&self) {
+        if let Ok(files) = list_log_files(&self.dir) {
+            for old in files.into_iter().skip(self.max_files.max(1)) {
+                let _ = fs::remove_file(old);
+            }
+        }
+    }
+
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        self.file.is_none()
+            || (self.size > 0 && self.size + incoming as u64 > self.max_bytes)
+            || chrono::Utc::now().format("%Y%m%d").to_string() != self.day
+    }
+}
+
+/// File writer that rotates daily and when the current file exceeds a size limit
+#[derive(Clone)]
+pub struct RollingFileWriter {
+    state: Arc<Mutex<RollingState>>,
+}
+
+impl RollingFileWriter {
+    pub fn new(dir: PathBuf, max_bytes: u64, max_files: usize) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+
+        let mut state = RollingState {
+            dir,
+            max_bytes: max_bytes.max(1024),
+            max_files,
+            file: None,
+            day: String::new(),
+            size: 0,
+        };
+        state.open_new_file().context("Failed to open log file")?;
+
+        Ok(Self {
+            state: Arc::new(Mutex::new(state)),
+        })
+    }
+}
+
+pub struct RollingFileGuard<'a>(MutexGuard<'a, RollingState>);
+
+impl Write for RollingFileGuard<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let state = &mut *self.0;
+        if state.needs_rotation(buf.len()) {
+            state.open_new_file()?;
+        }
+        let written = match state.file.as_mut() {
+            Some(file) => file.write(buf)?,
+            None => buf.len(),
+        };
+        state.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.file.as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for RollingFileWriter {
+    type Writer = RollingFileGuard<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        // A panic while logging must not take logging down with it
+        RollingFileGuard(self.state.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_log_file_name() {
+        assert!(is_log_file_name("jellyfin-rust_20261015_063949.log"));
+        assert!(is_log_file_name("jellyfin-rust_20261015_063949_1.log"));
+        assert!(!is_log_file_name("../jellyfin-rust_x.log"));
+        assert!(!is_log_file_name("jellyfin.db"));
+        assert!(!is_log_file_name("jellyfin-rust_20261015.txt"));
+    }
+
+    #[test]
+    fn test_rotation_by_size_and_pruning() {
+        let dir = std::env::temp_dir().join(format!("jellyfin-rust-logs-{}", uuid::Uuid::new_v4()));
+        let writer = RollingFileWriter::new(dir.clone(), 1024, 2).unwrap();
+
+        let line = vec![b'x'; 600];
+        for _ in 0..4 {
+            writer.make_writer().write_all(&line).unwrap();
+        }
+
+        // 4 writes of 600 bytes with a 1 KB limit need 4 files, only 2 are kept
+        let files = list_log_files(&dir).unwrap();
+        assert_eq!(files.len(), 2);
+        for file in &files {
+            assert!(fs::metadata(file).unwrap().len() <= 1024);
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
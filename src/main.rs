@@ -1,21 +1,27 @@
 use anyhow::Result;
-use axum::{routing::get, Router};
+use axum::{extract::Request, routing::get, Router, ServiceExt};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::ConnectOptions;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::time::Duration;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
+use tower::Layer;
 use tower_http::cors::CorsLayer;
+use tower_http::normalize_path::{NormalizePath, NormalizePathLayer};
 use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 mod api;
 mod config;
 mod db;
+mod events;
+mod logging;
 mod models;
 mod scanner;
 mod services;
+mod time;
+mod version;
 
 use config::AppConfig;
 
@@ -41,7 +47,7 @@ impl BackgroundTasks {
     where
         F: std::future::Future<Output = ()> + Send + 'static,
     {
-        let handle = tokio::spawn(future);
+        let handle = tokio::spawn(services::progress::supervise(name, future));
         self.handles.push((name, handle));
     }
 
@@ -70,16 +76,34 @@ pub struct AppState {
     pub config: AppConfig,
 }
 
+// Root handler
+async fn root_handler() -> &'static str {
+    "Jellyfin Rust Server"
+}
+
+/// The server's routes with the layers every request goes through
+fn app(state: std::sync::Arc<AppState>) -> NormalizePath<Router> {
+    let app = Router::new()
+        .route("/", get(root_handler).head(root_handler))
+        .route("/health", get(|| async { "OK" }))
+        .nest("/", api::routes())
+        .fallback(api::not_found)
+        .route_layer(axum::middleware::from_fn(db::query_stats::route_span))
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            api::impersonate,
+        ))
+        .layer(CorsLayer::permissive())
+        .layer(TraceLayer::new_for_http())
+        .with_state(state);
+    // Trailing slashes are trimmed before routing, so "/Items/" finds "/Items"
+    NormalizePathLayer::trim_trailing_slash().layer(app)
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "jellyfin_rust=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Initialize tracing (file output is attached once config is loaded)
+    let log_handle = logging::init();
 
     // Load .env file if present
     dotenvy::dotenv().ok();
@@ -88,6 +112,17 @@ async fn main() -> Result<()> {
 
     config.paths.ensure_dirs().await?;
 
+    if config.logging.file_enabled {
+        if let Err(e) = logging::enable_file_output(
+            &log_handle,
+            config.paths.log_dir(),
+            config.logging.max_file_size_mb,
+            config.logging.max_files,
+        ) {
+            tracing::warn!("File logging disabled: {:#}", e);
+        }
+    }
+
     config.log_config();
 
     // Database setup with optimized connection pool
@@ -104,8 +139,25 @@ async fn main() -> Result<()> {
         .page_size(8192)
         // Enable foreign key enforcement
         .foreign_keys(true)
+        // Case- and diacritic-insensitive ordering by name
+        .collation("FOLD", scanner::sort_name::compare)
         // Busy timeout for concurrent access (5 seconds)
-        .busy_timeout(Duration::from_secs(5));
+        .busy_timeout(Duration::from_secs(5))
+        // Statement timings feed db::query_stats: every statement at TRACE
+        // (when enabled), slow ones at WARN with their route
+        .log_statements(if config.logging.query_stats {
+            log::LevelFilter::Trace
+        } else {
+            log::LevelFilter::Off
+        })
+        .log_slow_statements(
+            if config.logging.slow_query_ms > 0 {
+                log::LevelFilter::Warn
+            } else {
+                log::LevelFilter::Off
+            },
+            Duration::from_millis(config.logging.slow_query_ms),
+        );
 
     let pool = SqlitePoolOptions::new()
         .max_connections(10)
@@ -133,6 +185,38 @@ async fn main() -> Result<()> {
     tracing::info!("SQLite configured: WAL mode, 32MB cache, 64MB mmap (per connection)");
 
     db::migrate(&pool).await?;
+    services::server_id::load(&pool).await?;
+    if let Err(e) = services::mediainfo::init_probe_cache(&pool).await {
+        tracing::warn!("Probe cache unavailable: {}", e);
+    }
+    if let Err(e) = services::metadata::init_response_cache(&pool).await {
+        tracing::warn!("Metadata response cache unavailable: {}", e);
+    }
+
+    // Scans can't survive a restart
+    match scanner::history::mark_interrupted(&pool).await {
+        Ok(0) => {}
+        Ok(count) => tracing::warn!("{} library scans were interrupted by a restart", count),
+        Err(e) => tracing::warn!("Failed to update scan history: {}", e),
+    }
+
+    // Seasons of episodes scanned before seasons were items
+    match scanner::seasons::sync_library(&pool, None).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Created seasons for {} series", count),
+        Err(e) => tracing::warn!("Failed to create seasons: {}", e),
+    }
+
+    if let Err(e) = scanner::library_settings::load(&pool).await {
+        tracing::warn!("Failed to load library settings: {}", e);
+    }
+
+    // Libraries whose folders aren't mounted keep their items until they're back
+    match scanner::availability::check_all(&pool).await {
+        Ok(0) => {}
+        Ok(count) => tracing::warn!("{} libraries are offline", count),
+        Err(e) => tracing::warn!("Failed to check library folders: {}", e),
+    }
 
     // Create default admin user if no users exist
     let user_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
@@ -159,18 +243,40 @@ async fn main() -> Result<()> {
         );
     }
 
+    scanner::duplicates::set_mode(config.scanner.duplicate_episodes);
+    let library_excludes: Vec<_> = config
+        .libraries
+        .iter()
+        .map(|lib| (lib.path.clone(), lib.exclude.clone()))
+        .collect();
+    scanner::exclude::set_patterns(&config.scanner.exclude, &library_excludes);
+    scanner::samples::set_thresholds(
+        config.scanner.min_file_size_mb,
+        config.scanner.min_duration_seconds,
+    );
+    services::metadata::set_artwork_preferences(config.artwork.clone());
+    scanner::sort_name::set_articles(
+        &config.scanner.ignore_articles,
+        &config.scanner.extra_articles,
+    );
+
+    // Sort names follow the article settings and items renamed since the last start
+    match scanner::sort_name::refresh_all(&pool).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Updated the sort names of {} items", count),
+        Err(e) => tracing::warn!("Failed to update sort names: {}", e),
+    }
+
     // Detect CPU cores and calculate optimal batch sizes for background tasks
     let cpu_cores = std::thread::available_parallelism()
         .map(|p| p.get())
         .unwrap_or(4);
 
-    let thumbnail_batch_size = (cpu_cores * 2).clamp(10, 40) as i32;
     let image_batch_size = (cpu_cores * 3).clamp(15, 60) as i32;
 
     tracing::info!(
-        "Detected {} CPU cores: thumbnail batch={}, image batch={}",
+        "Detected {} CPU cores: image batch={}",
         cpu_cores,
-        thumbnail_batch_size,
         image_batch_size
     );
 
@@ -178,6 +284,63 @@ async fn main() -> Result<()> {
     let mut bg_tasks = BackgroundTasks::new();
     let shutdown_token = bg_tasks.token();
 
+    // Event bus consumers (started first so they see events from the initial scan)
+    bg_tasks.spawn(
+        "fts-updater",
+        events::run_fts_updater(pool.clone(), shutdown_token.clone()),
+    );
+    bg_tasks.spawn(
+        "activity-log",
+        events::run_activity_log(shutdown_token.clone()),
+    );
+    bg_tasks.spawn(
+        "playback-stats",
+        events::run_playback_stats(pool.clone(), shutdown_token.clone()),
+    );
+    bg_tasks.spawn(
+        "webhooks",
+        services::webhooks::run(
+            pool.clone(),
+            config.webhooks.clone(),
+            shutdown_token.clone(),
+        ),
+    );
+    bg_tasks.spawn(
+        "library-notifier",
+        api::sessions::run_library_notifier(shutdown_token.clone()),
+    );
+    bg_tasks.spawn(
+        "playstate-flusher",
+        services::playstate::run_flusher(pool.clone(), shutdown_token.clone()),
+    );
+    bg_tasks.spawn(
+        "home-cache",
+        api::run_home_cache(pool.clone(), shutdown_token.clone()),
+    );
+    bg_tasks.spawn(
+        "user-data-notifier",
+        api::sessions::run_user_data_notifier(pool.clone(), shutdown_token.clone()),
+    );
+
+    // Announce the server to DLNA clients on the local network
+    if config.dlna.enabled {
+        bg_tasks.spawn(
+            "dlna",
+            services::dlna::run(
+                config.dlna.clone(),
+                config.port,
+                config.bind_address.clone(),
+                shutdown_token.clone(),
+            ),
+        );
+    }
+
+    // Stop transcodes nobody is watching anymore
+    bg_tasks.spawn(
+        "transcode-cleanup",
+        services::transcoding::run_cleanup(config.paths.transcode_dir(), shutdown_token.clone()),
+    );
+
     // Spawn background task for library auto-creation and scanning
     // (This is a one-time task, doesn't need cancellation)
     if !config.libraries.is_empty() {
@@ -208,7 +371,7 @@ async fn main() -> Result<()> {
 
                 if existing.is_none() {
                     let lib_type = lib.library_type.to_lowercase();
-                    if lib_type != "tvshows" && lib_type != "movies" {
+                    if !matches!(lib_type.as_str(), "tvshows" | "movies" | "music") {
                         tracing::warn!(
                             "Skipping library '{}': invalid type '{}'",
                             lib.name,
@@ -227,7 +390,10 @@ async fn main() -> Result<()> {
                         continue;
                     }
 
-                    let library_id = uuid::Uuid::new_v4().to_string();
+                    let library_id = models::Library::stable_id(
+                        lib.path.to_str().unwrap_or_default(),
+                        &lib_type,
+                    );
                     tracing::info!(
                         "Creating library '{}' ({}) at {}",
                         lib.name,
@@ -275,6 +441,17 @@ async fn main() -> Result<()> {
                         }
                     }
                 }
+
+                if let Err(e) =
+                    sqlx::query("UPDATE libraries SET sort_by = ?, sort_order = ? WHERE path = ?")
+                        .bind(lib.sort_by.as_deref())
+                        .bind(lib.sort_order.as_deref())
+                        .bind(lib.path.to_str().unwrap_or_default())
+                        .execute(&bg_pool)
+                        .await
+                {
+                    tracing::warn!("Failed to set the sort order of '{}': {}", lib.name, e);
+                }
             }
 
             tracing::info!("Background: Library initialization complete");
@@ -294,77 +471,22 @@ async fn main() -> Result<()> {
         });
     }
 
-    // Spawn periodic scanner task with cancellation support
-    if config.scanner.enabled {
+    // Spawn periodic full refresh task with cancellation support
+    // (quick scans are the "Scan Media Library" scheduled task)
+    if config.scanner.enabled && config.scanner.full_scan_interval_hours > 0 {
         let scanner_pool = pool.clone();
-        let scanner_config = config.clone();
+        let full_interval = Duration::from_secs(config.scanner.full_scan_interval_hours * 3600);
         let cancel = shutdown_token.clone();
         bg_tasks.spawn("periodic-scanner", async move {
-            tokio::time::sleep(Duration::from_secs(5)).await;
-
-            let quick_interval =
-                Duration::from_secs(scanner_config.scanner.quick_scan_interval_minutes * 60);
-            let full_interval =
-                Duration::from_secs(scanner_config.scanner.full_scan_interval_hours * 3600);
-
-            let mut last_quick_scan = std::time::Instant::now();
-            let mut last_full_scan = std::time::Instant::now();
-
-            if scanner_config.scanner.scan_on_startup {
-                tracing::info!("Running startup quick scan...");
-                match scanner::quick_scan_all_libraries(
-                    &scanner_pool,
-                    scanner_config.paths.cache_dir.clone(),
-                )
-                .await
-                {
-                    Ok(result) if result.files_added > 0 || result.files_removed > 0 => {
-                        tracing::info!(
-                            "Startup quick scan: {} added, {} removed",
-                            result.files_added,
-                            result.files_removed
-                        );
-                    }
-                    Err(e) => tracing::error!("Startup quick scan failed: {}", e),
-                    _ => {}
-                }
-            }
-
-            let check_interval = Duration::from_secs(60);
             loop {
                 tokio::select! {
                     _ = cancel.cancelled() => {
                         tracing::debug!("Scanner task received shutdown signal");
                         break;
                     }
-                    _ = tokio::time::sleep(check_interval) => {
-                        let now = std::time::Instant::now();
-
-                        if scanner_config.scanner.quick_scan_interval_minutes > 0
-                            && now.duration_since(last_quick_scan) >= quick_interval
-                        {
-                            if let Ok(result) = scanner::quick_scan_all_libraries(
-                                &scanner_pool,
-                                scanner_config.paths.cache_dir.clone(),
-                            ).await {
-                                if result.files_added > 0 || result.files_removed > 0 {
-                                    tracing::info!(
-                                        "Quick scan: {} added, {} removed",
-                                        result.files_added, result.files_removed
-                                    );
-                                }
-                            }
-                            last_quick_scan = now;
-                        }
-
-                        if scanner_config.scanner.full_scan_interval_hours > 0
-                            && now.duration_since(last_full_scan) >= full_interval
-                        {
-                            if let Err(e) = scanner::refresh_all_libraries(&scanner_pool).await {
-                                tracing::error!("Full scan failed: {}", e);
-                            }
-                            last_full_scan = now;
-                            last_quick_scan = now;
+                    _ = tokio::time::sleep(full_interval) => {
+                        if let Err(e) = scanner::refresh_all_libraries(&scanner_pool).await {
+                            tracing::error!("Full scan failed: {}", e);
                         }
                     }
                 }
@@ -372,6 +494,18 @@ async fn main() -> Result<()> {
         });
     }
 
+    // Spawn the scheduled task runner (library scans, missing metadata and
+    // thumbnails, database optimization, session cleanup)
+    {
+        let task_pool = pool.clone();
+        let task_config = config.clone();
+        let cancel = shutdown_token.clone();
+        bg_tasks.spawn("task-scheduler", async move {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            services::scheduled_tasks::run(task_pool, task_config, cancel).await;
+        });
+    }
+
     // Spawn background image downloader task with cancellation
     {
         let image_pool = pool.clone();
@@ -401,21 +535,45 @@ async fn main() -> Result<()> {
                         for image in pending {
                             if cancel.is_cancelled() { break; }
 
-                            if let Ok(path) = metadata_service
-                                .download_image_to_cache(&image.url, &image.item_id, &image.image_type)
-                                .await
-                            {
+                            let downloaded = if image.replace_existing {
+                                metadata_service
+                                    .redownload_image_to_cache(&image.url, &image.item_id, &image.image_type)
+                                    .await
+                            } else {
+                                metadata_service
+                                    .download_image_to_cache(&image.url, &image.item_id, &image.image_type)
+                                    .await
+                            };
+                            if let Ok(path) = downloaded {
+                                // "Backdrop2" is stored as the item's Backdrop 2
+                                let (image_type, image_index) = db::split_image_index(&image.image_type);
+                                // A download replaces the item's old row of that type and index
+                                let _ = sqlx::query("DELETE FROM images WHERE item_id = ? AND image_type = ? AND image_index = ?")
+                                    .bind(&image.item_id)
+                                    .bind(image_type)
+                                    .bind(image_index)
+                                    .execute(&image_pool)
+                                    .await;
                                 let image_id = uuid::Uuid::new_v4().to_string();
+                                let size = services::image_validation::inspect_file(&path).await.ok();
                                 let _ = sqlx::query(
-                                    "INSERT OR REPLACE INTO images (id, item_id, image_type, path) VALUES (?, ?, ?, ?)",
+                                    "INSERT OR REPLACE INTO images (id, item_id, image_type, image_index, path, downloaded_at, width, height) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
                                 )
                                 .bind(&image_id)
                                 .bind(&image.item_id)
-                                .bind(&image.image_type)
+                                .bind(image_type)
+                                .bind(image_index)
                                 .bind(path.to_str().unwrap_or_default())
+                                .bind(chrono::Utc::now().to_rfc3339())
+                                .bind(size.map(|s| s.width))
+                                .bind(size.map(|s| s.height))
                                 .execute(&image_pool)
                                 .await;
                                 let _ = db::mark_image_downloaded(&image_pool, image.id).await;
+                                events::publish(events::ServerEvent::ImageUpdated {
+                                    item_id: image.item_id,
+                                    image_type: image_type.to_string(),
+                                });
                             } else {
                                 let _ = db::mark_image_failed(&image_pool, image.id).await;
                             }
@@ -430,165 +588,62 @@ async fn main() -> Result<()> {
         });
     }
 
-    // Spawn background thumbnail generator with cancellation
-    {
-        let thumb_pool = pool.clone();
-        let thumb_config = config.clone();
+    // Spawn release update checker
+    if config.updates.check {
+        let update_config = config.updates.clone();
         let cancel = shutdown_token.clone();
-        bg_tasks.spawn("thumbnail-generator", async move {
-            tokio::time::sleep(Duration::from_secs(15)).await;
-            let image_cache_dir = thumb_config.paths.cache_dir.join("images");
-            tracing::info!("Background thumbnail generator started");
-
-            loop {
-                if cancel.is_cancelled() {
-                    tracing::debug!("Thumbnail generator received shutdown signal");
-                    break;
-                }
-
-                match db::get_pending_thumbnails(&thumb_pool, thumbnail_batch_size).await {
-                    Ok(pending) if !pending.is_empty() => {
-                        for thumb in pending {
-                            if cancel.is_cancelled() { break; }
-
-                            let video_path = std::path::Path::new(&thumb.video_path);
-                            let timestamp = services::mediainfo::extract_media_info_async(video_path)
-                                .await
-                                .ok()
-                                .and_then(|i| i.duration_seconds)
-                                .map(services::mediainfo::calculate_thumbnail_timestamp)
-                                .unwrap_or(30.0);
-
-                            let item_dir = image_cache_dir.join(&thumb.item_id);
-                            let output_path = item_dir.join("Primary.jpg");
+        bg_tasks.spawn("update-checker", async move {
+            services::updates::run(update_config, cancel).await;
+        });
+    }
 
-                            if let Ok(()) = services::mediainfo::extract_thumbnail_async(
-                                video_path, &output_path, timestamp, Some(480),
-                            ).await {
-                                let image_id = uuid::Uuid::new_v4().to_string();
-                                let _ = sqlx::query(
-                                    "INSERT OR REPLACE INTO images (id, item_id, image_type, path) VALUES (?, ?, ?, ?)",
-                                )
-                                .bind(&image_id)
-                                .bind(&thumb.item_id)
-                                .bind("Primary")
-                                .bind(output_path.to_str().unwrap_or_default())
-                                .execute(&thumb_pool)
-                                .await;
-                                let _ = db::mark_thumbnail_complete(&thumb_pool, thumb.id).await;
-                            } else {
-                                let _ = db::mark_thumbnail_failed(&thumb_pool, thumb.id).await;
-                            }
-                            tokio::time::sleep(Duration::from_millis(200)).await;
-                        }
-                    }
-                    _ => {
-                        tokio::time::sleep(Duration::from_secs(10)).await;
-                    }
-                }
-            }
+    // Spawn library folder watcher when any library is watched
+    if config.scanner.watch || config.libraries.iter().any(|lib| lib.watch == Some(true)) {
+        let watch_pool = pool.clone();
+        let watch_config = config.clone();
+        let cancel = shutdown_token.clone();
+        bg_tasks.spawn("library-watcher", async move {
+            // Let library-init register configured libraries first
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            scanner::watcher::run(watch_pool, watch_config, cancel).await;
         });
     }
 
-    // Spawn session cleanup task with cancellation
+    // Spawn background thumbnail workers with cancellation
     {
-        let session_pool = pool.clone();
+        let thumb_pool = pool.clone();
+        let image_cache_dir = config.paths.image_cache_dir();
+        let thumb_config = config.thumbnails.clone();
         let cancel = shutdown_token.clone();
-        bg_tasks.spawn("session-cleanup", async move {
-            tokio::time::sleep(Duration::from_secs(30)).await;
-            tracing::info!("Session cleanup task started");
-
-            loop {
-                tokio::select! {
-                    _ = cancel.cancelled() => {
-                        tracing::debug!("Session cleanup received shutdown signal");
-                        break;
-                    }
-                    _ = tokio::time::sleep(Duration::from_secs(300)) => {
-                        if let Ok(removed) = services::auth::cleanup_expired_sessions(&session_pool).await {
-                            if removed > 0 {
-                                tracing::info!("Cleaned up {} expired sessions", removed);
-                            }
-                        }
-                        if let Ok(removed) = api::sessions::cleanup_stale_sessions(&session_pool, 3600).await {
-                            if removed > 0 {
-                                tracing::info!("Cleaned up {} stale active sessions", removed);
-                            }
-                        }
-                    }
-                }
-            }
+        bg_tasks.spawn("thumbnail-generator", async move {
+            tokio::time::sleep(Duration::from_secs(15)).await;
+            services::thumbnails::run(thumb_pool, image_cache_dir, thumb_config, cancel).await;
         });
     }
 
-    // Spawn missing thumbnail checker task (configurable interval)
-    if config.scanner.missing_thumbnail_check_minutes > 0 {
-        let thumb_check_pool = pool.clone();
-        let thumb_check_config = config.clone();
+    // Spawn dead playback reaper (finalizes sessions that stopped reporting progress)
+    if config.playback_timeout_minutes > 0 {
+        let reaper_pool = pool.clone();
         let cancel = shutdown_token.clone();
-        let interval_secs = config.scanner.missing_thumbnail_check_minutes * 60;
-
-        bg_tasks.spawn("missing-thumbnail-checker", async move {
-            // Wait 2 minutes before first check (let initial scan complete)
-            tokio::time::sleep(Duration::from_secs(120)).await;
-            tracing::info!(
-                "Missing thumbnail checker started (interval: {} minutes)",
-                thumb_check_config.scanner.missing_thumbnail_check_minutes
-            );
-
+        let timeout_secs = (config.playback_timeout_minutes * 60) as i64;
+        bg_tasks.spawn("playback-reaper", async move {
             loop {
                 tokio::select! {
                     _ = cancel.cancelled() => {
-                        tracing::debug!("Missing thumbnail checker received shutdown signal");
+                        tracing::debug!("Playback reaper received shutdown signal");
                         break;
                     }
-                    _ = tokio::time::sleep(Duration::from_secs(interval_secs)) => {
-                        // Queue any items missing thumbnails
-                        match db::queue_missing_thumbnails(&thumb_check_pool).await {
-                            Ok(count) if count > 0 => {
-                                tracing::info!("Queued {} missing thumbnails for generation", count);
-                            }
-                            Ok(_) => {
-                                tracing::debug!("No missing thumbnails found");
-                            }
-                            Err(e) => {
-                                tracing::warn!("Failed to check for missing thumbnails: {}", e);
-                            }
-                        }
-
-                        // Also reset any failed thumbnails for retry (if enabled)
-                        if thumb_check_config.scanner.retry_failed_thumbnails {
-                            match db::reset_failed_thumbnails(&thumb_check_pool).await {
-                                Ok(count) if count > 0 => {
-                                    tracing::info!("Reset {} failed thumbnails for retry", count);
-                                }
-                                Ok(_) => {}
-                                Err(e) => {
-                                    tracing::warn!("Failed to reset failed thumbnails: {}", e);
-                                }
-                            }
+                    _ = tokio::time::sleep(Duration::from_secs(60)) => {
+                        if let Err(e) = api::sessions::finalize_dead_sessions(&reaper_pool, timeout_secs).await {
+                            tracing::warn!("Failed to finalize dead playback sessions: {}", e);
                         }
                     }
                 }
             }
         });
-    } else {
-        tracing::info!("Missing thumbnail checker disabled (interval set to 0)");
     }
 
-    // Root handler
-    async fn root_handler() -> &'static str {
-        "Jellyfin Rust Server"
-    }
-
-    // Build router
-    let app = Router::new()
-        .route("/", get(root_handler).head(root_handler))
-        .route("/health", get(|| async { "OK" }))
-        .nest("/", api::routes())
-        .layer(CorsLayer::permissive())
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
+    let app = app(state);
 
     let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
     tracing::info!("Starting server on {}", addr);
@@ -620,9 +675,13 @@ async fn main() -> Result<()> {
 
     // Start server with graceful shutdown
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal)
-        .await?;
+    // Client addresses are kept for handlers that only answer the local network (api::dlna)
+    axum::serve(
+        listener,
+        ServiceExt::<Request>::into_make_service_with_connect_info::<SocketAddr>(app),
+    )
+    .with_graceful_shutdown(shutdown_signal)
+    .await?;
 
     // After server stops, gracefully shutdown background tasks
     bg_tasks.shutdown().await;
@@ -630,3 +689,376 @@ async fn main() -> Result<()> {
     tracing::info!("Server shutdown complete");
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{to_bytes, Body};
+    use axum::http::{header, Method, StatusCode};
+    use tower::ServiceExt as _;
+
+    async fn test_pool() -> sqlx::SqlitePool {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        db::migrate(&pool).await.unwrap();
+        pool
+    }
+
+    fn test_app(pool: sqlx::SqlitePool) -> NormalizePath<Router> {
+        app(std::sync::Arc::new(AppState {
+            db: pool,
+            config: AppConfig::build(config::ConfigFile::default()),
+        }))
+    }
+
+    async fn send_request(
+        pool: sqlx::SqlitePool,
+        request: Request<Body>,
+    ) -> (StatusCode, header::HeaderMap, Vec<u8>) {
+        let response = test_app(pool).oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.unwrap();
+        (parts.status, parts.headers, body.to_vec())
+    }
+
+    async fn send(method: Method, uri: &str) -> (StatusCode, header::HeaderMap, Vec<u8>) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        send_request(test_pool().await, request).await
+    }
+
+    /// Create a user with password "pw" and sign them in from `device_id`
+    async fn sign_in(pool: &sqlx::SqlitePool, name: &str, device_id: &str) -> String {
+        let (_, session) = services::auth::authenticate(pool, name, "pw", device_id, "Dev", "t")
+            .await
+            .unwrap();
+        session.token
+    }
+
+    /// A request with `token` in the auth header and an optional JSON body
+    fn authed(method: Method, uri: &str, token: &str, body: Option<&str>) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(
+                "X-Emby-Authorization",
+                format!(
+                    r#"MediaBrowser Client="t", DeviceId="dev", Token="{}""#,
+                    token
+                ),
+            )
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(body.map(|b| Body::from(b.to_string())).unwrap_or_default())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wrong_method_is_405_with_allow() {
+        let (status, headers, _) = send(Method::POST, "/health").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+        let allow = headers[header::ALLOW].to_str().unwrap();
+        assert!(allow.contains("GET"), "{}", allow);
+        assert!(allow.contains("HEAD"), "{}", allow);
+    }
+
+    #[tokio::test]
+    async fn test_head_is_served_without_body() {
+        let (status, headers, body) = send(Method::GET, "/health").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, b"OK");
+
+        let (status, head_headers, body) = send(Method::HEAD, "/health").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            head_headers[header::CONTENT_TYPE],
+            headers[header::CONTENT_TYPE]
+        );
+        assert_eq!(head_headers[header::CONTENT_LENGTH], "2");
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_trailing_slash_and_unknown_paths() {
+        // Without a token the route answers 401; a missed route would be 404
+        let (status, _, _) = send(Method::GET, "/Items").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (status, _, _) = send(Method::GET, "/Items/").await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _, body) = send(Method::GET, "/Nowhere/To/Be/Found").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body, b"Not found");
+    }
+
+    #[tokio::test]
+    async fn test_impersonation_swaps_query_and_header_tokens() {
+        let pool = test_pool().await;
+        services::auth::create_user(&pool, "admin", "pw", true)
+            .await
+            .unwrap();
+        services::auth::create_user(&pool, "viewer", "pw", false)
+            .await
+            .unwrap();
+        let (_, session) = services::auth::authenticate(&pool, "admin", "pw", "dev", "Dev", "t")
+            .await
+            .unwrap();
+        let auth_header = format!(
+            r#"MediaBrowser Client="t", DeviceId="dev", Token="{}""#,
+            session.token
+        );
+        // The log download prefers an api_key parameter over the header and
+        // needs an admin: a bad name is 400 for the admin, 403 for anyone else
+        let uri = format!("/System/Logs/Log?name=x&api_key={}", session.token);
+
+        let request = Request::builder().uri(&uri).body(Body::empty()).unwrap();
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // A link carrying only the query key
+        let request = Request::builder()
+            .uri(format!("{}&impersonateUserId=viewer", uri))
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(headers["X-Jellyfin-Impersonating"], "viewer");
+
+        // Header and query key together: neither may keep the admin's rights
+        let request = Request::builder()
+            .uri(&uri)
+            .header("X-Emby-Authorization", &auth_header)
+            .header("X-Jellyfin-Impersonate-User", "viewer")
+            .body(Body::empty())
+            .unwrap();
+        let (status, headers, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(headers["X-Jellyfin-Impersonating"], "viewer");
+    }
+
+    #[tokio::test]
+    async fn test_password_changes() {
+        let pool = test_pool().await;
+        let alice = services::auth::create_user(&pool, "alice", "pw", false)
+            .await
+            .unwrap();
+        services::auth::create_user(&pool, "bob", "pw", false)
+            .await
+            .unwrap();
+        let (alice_here, alice_elsewhere) = (
+            sign_in(&pool, "alice", "phone").await,
+            sign_in(&pool, "alice", "tv").await,
+        );
+        let bob = sign_in(&pool, "bob", "phone").await;
+        let uri = format!("/Users/{}/Password", alice.id);
+
+        let body = r#"{"CurrentPw":"pw","NewPw":"new"}"#;
+        let request = authed(Method::POST, &uri, &bob, Some(body));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let wrong = r#"{"CurrentPw":"nope","NewPw":"new"}"#;
+        let request = authed(Method::POST, &uri, &alice_here, Some(wrong));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let request = authed(Method::POST, &uri, &alice_here, Some(body));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // Only the session that made the change stays signed in
+        let request = authed(Method::GET, "/Users/Me", &alice_here, None);
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::OK);
+        let request = authed(Method::GET, "/Users/Me", &alice_elsewhere, None);
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        assert!(
+            services::auth::authenticate(&pool, "alice", "pw", "tv", "Dev", "t")
+                .await
+                .is_err()
+        );
+        services::auth::authenticate(&pool, "alice", "new", "tv", "Dev", "t")
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_user_and_policy_updates() {
+        let pool = test_pool().await;
+        let admin = services::auth::create_user(&pool, "admin", "pw", true)
+            .await
+            .unwrap();
+        let alice = services::auth::create_user(&pool, "alice", "pw", false)
+            .await
+            .unwrap();
+        services::auth::create_user(&pool, "bob", "pw", false)
+            .await
+            .unwrap();
+        let admin_token = sign_in(&pool, "admin", "phone").await;
+        let alice_token = sign_in(&pool, "alice", "phone").await;
+        let bob_token = sign_in(&pool, "bob", "phone").await;
+
+        // Renaming: only the user themselves or an admin, and names stay unique
+        let uri = format!("/Users/{}", alice.id);
+        let request = authed(Method::POST, &uri, &bob_token, Some(r#"{"Name":"eve"}"#));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let request = authed(Method::POST, &uri, &alice_token, Some(r#"{"Name":"BOB"}"#));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let request = authed(Method::POST, &uri, &alice_token, Some(r#"{"Name":"ally"}"#));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        // An admin can't demote or disable themselves
+        let own_policy = format!("/Users/{}/Policy", admin.id);
+        for body in [r#"{"IsAdministrator":false}"#, r#"{"IsDisabled":true}"#] {
+            let request = authed(Method::POST, &own_policy, &admin_token, Some(body));
+            let (status, _, _) = send_request(pool.clone(), request).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{}", body);
+        }
+
+        let policy = format!("/Users/{}/Policy", alice.id);
+        let disable = r#"{"IsDisabled":true}"#;
+        let request = authed(Method::POST, &policy, &bob_token, Some(disable));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // Disabling signs the user out and keeps them from signing back in
+        let request = authed(Method::POST, &policy, &admin_token, Some(disable));
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let request = authed(Method::GET, "/Users/Me", &alice_token, None);
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let request = authed(
+            Method::POST,
+            "/Users/AuthenticateByName",
+            "",
+            Some(r#"{"Username":"ally","Pw":"pw"}"#),
+        );
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        services::auth::set_disabled(&pool, &alice.id, false)
+            .await
+            .unwrap();
+        sign_in(&pool, "ally", "phone").await;
+    }
+
+    #[tokio::test]
+    async fn test_items_of_another_user() {
+        let pool = test_pool().await;
+        services::auth::create_user(&pool, "admin", "pw", true)
+            .await
+            .unwrap();
+        let alice = services::auth::create_user(&pool, "alice", "pw", false)
+            .await
+            .unwrap();
+        services::auth::create_user(&pool, "bob", "pw", false)
+            .await
+            .unwrap();
+        for sql in [
+            "INSERT INTO libraries (id, name, path, library_type) VALUES ('lib', 'Movies', '/movies', 'movies')",
+            "INSERT INTO media_items (id, library_id, item_type, name) VALUES ('movie', 'lib', 'Movie', 'Film')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+        // Alice's view differs from everyone else's
+        sqlx::query(
+            "INSERT INTO user_item_blocks (user_id, block_type, value) VALUES (?, 'Item', 'movie')",
+        )
+        .bind(&alice.id)
+        .execute(&pool)
+        .await
+        .unwrap();
+        let admin_token = sign_in(&pool, "admin", "phone").await;
+        let alice_token = sign_in(&pool, "alice", "phone").await;
+        let bob_token = sign_in(&pool, "bob", "phone").await;
+
+        let total = |pool: sqlx::SqlitePool, uri: String, token: String| async move {
+            let request = authed(Method::GET, &uri, &token, None);
+            let (status, _, body) = send_request(pool, request).await;
+            assert_eq!(status, StatusCode::OK, "{}", uri);
+            let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+            body["TotalRecordCount"].as_i64().unwrap()
+        };
+        let alice_items = format!("/Users/{}/Items?Recursive=true", alice.id);
+
+        // Other users' items are for admins only, and show that user's view
+        let request = authed(Method::GET, &alice_items, &bob_token, None);
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let request = authed(
+            Method::GET,
+            &format!("/Items?userId={}", alice.id),
+            &bob_token,
+            None,
+        );
+        let (status, _, _) = send_request(pool.clone(), request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            total(pool.clone(), alice_items, admin_token.clone()).await,
+            0
+        );
+        let items = "/Items?Recursive=true".to_string();
+        assert_eq!(total(pool.clone(), items, admin_token).await, 1);
+
+        // An empty userId means the caller
+        let items = "/Items?Recursive=true&userId=".to_string();
+        assert_eq!(total(pool.clone(), items.clone(), alice_token).await, 0);
+        assert_eq!(total(pool.clone(), items, bob_token).await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_library_export_uses_current_probes_only() {
+        let pool = test_pool().await;
+        services::auth::create_user(&pool, "admin", "pw", true)
+            .await
+            .unwrap();
+        let probe = r#"{"format":{"format_name":"matroska"},"streams":[]}"#;
+        for sql in [
+            "INSERT INTO libraries (id, name, path, library_type) VALUES ('lib', 'Movies', '/movies', 'movies')".to_string(),
+            "INSERT INTO media_items (id, library_id, item_type, name, path, file_size, file_modified) VALUES
+                ('current', 'lib', 'Movie', 'A', '/movies/a.mkv', 100, 1000),
+                ('stale', 'lib', 'Movie', 'B', '/movies/b.mkv', 200, 2000),
+                ('unrecorded', 'lib', 'Movie', 'C', '/movies/c.mkv', NULL, NULL),
+                ('song', 'lib', 'Audio', 'D', '/movies/d.flac', NULL, NULL)".to_string(),
+            format!(
+                "INSERT INTO probe_cache (path, file_size, file_modified, output, probed_at) VALUES
+                    ('/movies/a.mkv', 100, 1000, '{0}', ''),
+                    ('/movies/b.mkv', 150, 1500, '{0}', ''),
+                    ('/movies/c.mkv', 300, 3000, '{0}', ''),
+                    ('/movies/d.flac', 400, 4000, '{0}', '')",
+                probe
+            ),
+        ] {
+            sqlx::query(&sql).execute(&pool).await.unwrap();
+        }
+        let token = sign_in(&pool, "admin", "phone").await;
+
+        let request = authed(Method::GET, "/Library/lib/Export?format=json", &token, None);
+        let (status, _, body) = send_request(pool, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let entries: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+        let containers: Vec<(&str, Option<&str>)> = entries
+            .iter()
+            .map(|e| (e["Id"].as_str().unwrap(), e["Container"].as_str()))
+            .collect();
+        assert_eq!(
+            containers,
+            [
+                ("current", Some("matroska")),
+                ("stale", None),
+                ("unrecorded", Some("matroska"))
+            ]
+        );
+    }
+}
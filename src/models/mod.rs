@@ -31,6 +31,18 @@ pub struct Library {
     pub path: String,
     pub library_type: String,
     pub created_at: String,
+    /// Set while the library's root folder is unavailable (scanner::availability)
+    #[sqlx(default)]
+    pub offline_since: Option<String>,
+}
+
+impl Library {
+    /// Deterministic ID for a library folder, so recreating a library (or the database)
+    /// keeps the ID clients stored for its view
+    pub fn stable_id(path: &str, library_type: &str) -> String {
+        let key = format!("library:{}:{}", library_type.to_lowercase(), path);
+        Uuid::new_v3(&Uuid::NAMESPACE_URL, key.as_bytes()).to_string()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,11 +71,57 @@ pub struct MediaItem {
     pub mal_id: Option<String>,
     pub anidb_id: Option<String>,
     pub kitsu_id: Option<String>,
+    #[sqlx(default)]
+    pub tvdb_id: Option<String>,
     pub sort_name: Option<String>,
     pub index_number: Option<i32>,
     pub parent_index_number: Option<i32>,
+    /// Last episode of a file holding several ("S01E01-E03")
+    #[sqlx(default)]
+    pub index_number_end: Option<i32>,
     pub created_at: String,
     pub updated_at: String,
+    /// Video resolution (Movies/Episodes only, from ffprobe)
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Episode numbers from the series' alternate ordering, if one is selected
+    pub display_parent_index_number: Option<i32>,
+    pub display_index_number: Option<i32>,
+    /// Remote URL for items added from a .strm file
+    #[sqlx(default)]
+    pub stream_url: Option<String>,
+    /// Season item of an episode
+    #[sqlx(default)]
+    pub season_id: Option<String>,
+    /// Album and artists of music items; `artists` is semicolon-separated
+    #[sqlx(default)]
+    pub album: Option<String>,
+    #[sqlx(default)]
+    pub album_artist: Option<String>,
+    #[sqlx(default)]
+    pub artists: Option<String>,
+    /// Age rating from the provider ("PG-13", "TV-MA")
+    #[sqlx(default)]
+    pub official_rating: Option<String>,
+}
+
+impl MediaItem {
+    /// Episode number to show: the alternate ordering's if set, otherwise the aired one
+    pub fn index_number_for_display(&self) -> Option<i32> {
+        self.display_index_number.or(self.index_number)
+    }
+
+    /// Last episode number of a multi-episode file, shifted like the first
+    pub fn index_number_end_for_display(&self) -> Option<i32> {
+        let offset = self.index_number_for_display()? - self.index_number?;
+        Some(self.index_number_end? + offset)
+    }
+
+    /// Season number to show: the alternate ordering's if set, otherwise the aired one
+    pub fn parent_index_number_for_display(&self) -> Option<i32> {
+        self.display_parent_index_number
+            .or(self.parent_index_number)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
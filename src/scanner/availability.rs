@@ -0,0 +1,107 @@
+// Library availability
+//
+// A library on a network share disappears whenever the mount drops, and every
+// file in it then looks deleted: a quick scan would remove all of its items,
+// along with their played state, favorites and downloaded artwork. Before a
+// scan touches a library its root folder is checked, and a library whose root
+// is missing, unreadable, or empty while items are stored for it (an unmounted
+// mount point is usually an empty folder) is marked offline
+// (`libraries.offline_since`) and left alone. The next check that finds the
+// root again clears the mark and scans resume as usual.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::path::Path;
+use tokio::fs;
+
+/// Whether a library's root folder can be scanned
+///
+/// An empty root only counts as available when the library has no items yet.
+pub async fn root_available(path: &Path, has_items: bool) -> bool {
+    let mut entries = match fs::read_dir(path).await {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    if !has_items {
+        return true;
+    }
+    matches!(entries.next_entry().await, Ok(Some(_)))
+}
+
+/// Check a library's root and record whether it's offline; returns whether
+/// the library is online
+pub async fn check_library(pool: &SqlitePool, library_id: &str, path: &str) -> Result<bool> {
+    let has_items: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM media_items WHERE library_id = ? AND path IS NOT NULL)",
+    )
+    .bind(library_id)
+    .fetch_one(pool)
+    .await?;
+    let online = root_available(Path::new(path), has_items).await;
+
+    if online {
+        let cleared = sqlx::query(
+            "UPDATE libraries SET offline_since = NULL WHERE id = ? AND offline_since IS NOT NULL",
+        )
+        .bind(library_id)
+        .execute(pool)
+        .await?;
+        if cleared.rows_affected() > 0 {
+            tracing::info!("Library '{}' is back online at {}", library_id, path);
+        }
+    } else {
+        let marked = sqlx::query(
+            "UPDATE libraries SET offline_since = CURRENT_TIMESTAMP
+             WHERE id = ? AND offline_since IS NULL",
+        )
+        .bind(library_id)
+        .execute(pool)
+        .await?;
+        if marked.rows_affected() > 0 {
+            tracing::warn!(
+                "Library '{}' is offline: {} is missing or empty; its items are kept until it's back",
+                library_id,
+                path
+            );
+        }
+    }
+    Ok(online)
+}
+
+/// Check every library (at startup); returns the number that are offline
+pub async fn check_all(pool: &SqlitePool) -> Result<usize> {
+    let libraries: Vec<(String, String)> = sqlx::query_as("SELECT id, path FROM libraries")
+        .fetch_all(pool)
+        .await?;
+    let mut offline = 0;
+    for (library_id, path) in &libraries {
+        if !check_library(pool, library_id, path).await? {
+            offline += 1;
+        }
+    }
+    Ok(offline)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_empty_root_is_offline_only_with_items() {
+        let dir = std::env::temp_dir().join(format!(
+            "jellyfin-rust-availability-{}",
+            uuid::Uuid::new_v4()
+        ));
+        fs::create_dir_all(&dir).await.unwrap();
+
+        // An empty folder is a new library, or an unmounted share
+        assert!(root_available(&dir, false).await);
+        assert!(!root_available(&dir, true).await);
+
+        fs::write(dir.join("movie.mkv"), b"").await.unwrap();
+        assert!(root_available(&dir, true).await);
+
+        fs::remove_dir_all(&dir).await.unwrap();
+        assert!(!root_available(&dir, false).await);
+    }
+}
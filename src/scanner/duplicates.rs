@@ -0,0 +1,259 @@
+// Duplicate episode handling
+//
+// Two files can map to the same series, season and episode (a 720p and a 1080p
+// release, or a re-download next to the original). Each file keeps its own
+// media_items row, since the scanner and file removal work per path, but after
+// every scan the copies are grouped and the best file (highest resolution, then
+// largest) becomes the primary episode. The others point at it through
+// `version_of` and are left out of listings. Depending on the configured mode
+// they are either offered as alternate media sources of the primary
+// ("versions") or only flagged for an admin to review ("prefer_quality").
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::config::DuplicateEpisodeMode;
+
+/// `duplicate_state` of a copy offered as an alternate media source
+pub const STATE_VERSION: &str = "Version";
+
+/// `duplicate_state` of a lower-quality copy flagged for review
+pub const STATE_REVIEW: &str = "Review";
+
+static MODE: OnceLock<DuplicateEpisodeMode> = OnceLock::new();
+
+/// Set how duplicate episodes are handled (from the scanner config)
+pub fn set_mode(mode: DuplicateEpisodeMode) {
+    let _ = MODE.set(mode);
+}
+
+fn mode() -> DuplicateEpisodeMode {
+    MODE.get().copied().unwrap_or_default()
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct EpisodeFile {
+    id: String,
+    parent_id: Option<String>,
+    parent_index_number: Option<i32>,
+    index_number: Option<i32>,
+    /// A multi-episode file only duplicates another covering the same range
+    index_number_end: Option<i32>,
+    path: Option<String>,
+    width: Option<i32>,
+    height: Option<i32>,
+    version_of: Option<String>,
+    duplicate_state: Option<String>,
+    #[sqlx(skip)]
+    size: u64,
+}
+
+impl EpisodeFile {
+    /// Ordering key: better files compare greater
+    fn quality(&self) -> (i64, u64) {
+        let pixels = self.width.unwrap_or(0) as i64 * self.height.unwrap_or(0) as i64;
+        (pixels, self.size)
+    }
+}
+
+/// A change to an episode's duplicate columns
+#[derive(Debug, PartialEq)]
+struct Assignment {
+    id: String,
+    version_of: Option<String>,
+    duplicate_state: Option<&'static str>,
+    /// The episode was shown before and now isn't; its user data moves to the new primary
+    demoted: bool,
+}
+
+/// Work out which episodes are copies of which, returning only the changes
+fn plan(episodes: Vec<EpisodeFile>, state: &'static str) -> Vec<Assignment> {
+    let mut groups: HashMap<(String, i32, i32, Option<i32>), Vec<EpisodeFile>> = HashMap::new();
+    for episode in episodes {
+        match (
+            &episode.parent_id,
+            episode.parent_index_number,
+            episode.index_number,
+        ) {
+            (Some(series), Some(season), Some(number)) => groups
+                .entry((series.clone(), season, number, episode.index_number_end))
+                .or_default()
+                .push(episode),
+            // Without full numbering there is nothing to match on; undo any old flag
+            _ if episode.version_of.is_some() => groups
+                .entry((episode.id.clone(), -1, -1, None))
+                .or_default()
+                .push(episode),
+            _ => {}
+        }
+    }
+
+    let mut changes = Vec::new();
+    for mut files in groups.into_values() {
+        // Best first; ties go to the existing primary, then to the path, so the
+        // choice doesn't flip between scans
+        files.sort_by(|a, b| {
+            b.quality()
+                .cmp(&a.quality())
+                .then_with(|| a.version_of.is_some().cmp(&b.version_of.is_some()))
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        let primary = files[0].id.clone();
+
+        for (i, file) in files.into_iter().enumerate() {
+            let (version_of, duplicate_state) = if i == 0 {
+                (None, None)
+            } else {
+                (Some(primary.clone()), Some(state))
+            };
+            if file.version_of == version_of && file.duplicate_state.as_deref() == duplicate_state {
+                continue;
+            }
+            changes.push(Assignment {
+                demoted: file.version_of.is_none() && version_of.is_some(),
+                id: file.id,
+                version_of,
+                duplicate_state,
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.id.cmp(&b.id));
+    changes
+}
+
+/// Group a library's duplicate episodes behind their best copy; returns how
+/// many episodes changed
+pub async fn resolve_library(pool: &SqlitePool, library_id: &str) -> Result<usize> {
+    // Only episodes that share numbering with another one (or were flagged
+    // before) can change, which keeps this cheap for libraries without copies
+    let mut episodes: Vec<EpisodeFile> = sqlx::query_as(
+        "SELECT m.id, m.parent_id, m.parent_index_number, m.index_number, m.index_number_end, m.path,
+                m.width, m.height, m.version_of, m.duplicate_state
+         FROM media_items m
+         WHERE m.library_id = ? AND m.item_type = 'Episode'
+           AND (m.version_of IS NOT NULL OR EXISTS (
+               SELECT 1 FROM media_items o
+               WHERE o.parent_id = m.parent_id AND o.item_type = 'Episode' AND o.id != m.id
+                 AND o.parent_index_number = m.parent_index_number
+                 AND o.index_number = m.index_number
+                 AND o.index_number_end IS m.index_number_end))",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+
+    if episodes.is_empty() {
+        return Ok(0);
+    }
+    for episode in &mut episodes {
+        if let Some(ref path) = episode.path {
+            episode.size = tokio::fs::metadata(path)
+                .await
+                .map(|m| m.len())
+                .unwrap_or(0);
+        }
+    }
+
+    let state = match mode() {
+        DuplicateEpisodeMode::Versions => STATE_VERSION,
+        DuplicateEpisodeMode::PreferQuality => STATE_REVIEW,
+    };
+    let changes = plan(episodes, state);
+
+    let mut tx = pool.begin().await?;
+    for change in &changes {
+        sqlx::query("UPDATE media_items SET version_of = ?, duplicate_state = ? WHERE id = ?")
+            .bind(&change.version_of)
+            .bind(change.duplicate_state)
+            .bind(&change.id)
+            .execute(&mut *tx)
+            .await?;
+
+        // Keep watch state and favorites when a better copy takes over
+        if let (true, Some(primary)) = (change.demoted, &change.version_of) {
+            for table in ["playback_progress", "user_favorites"] {
+                sqlx::query(&format!(
+                    "UPDATE OR IGNORE {} SET item_id = ? WHERE item_id = ?",
+                    table
+                ))
+                .bind(primary)
+                .bind(&change.id)
+                .execute(&mut *tx)
+                .await?;
+            }
+            tracing::info!(
+                "Episode {} superseded by better copy {} ({})",
+                change.id,
+                primary,
+                change.duplicate_state.unwrap_or_default()
+            );
+        }
+    }
+    tx.commit().await?;
+
+    Ok(changes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(id: &str, episode: i32, height: i32, version_of: Option<&str>) -> EpisodeFile {
+        EpisodeFile {
+            id: id.to_string(),
+            parent_id: Some("series".to_string()),
+            parent_index_number: Some(1),
+            index_number: Some(episode),
+            index_number_end: None,
+            path: Some(format!("/tv/{}.mkv", id)),
+            width: Some(height * 16 / 9),
+            height: Some(height),
+            version_of: version_of.map(str::to_string),
+            duplicate_state: version_of.map(|_| STATE_VERSION.to_string()),
+            size: 1000,
+        }
+    }
+
+    #[test]
+    fn test_plan_prefers_highest_resolution() {
+        let changes = plan(
+            vec![
+                // E1: 720p was primary until a 1080p copy arrived
+                file("e1-720", 1, 720, None),
+                file("e1-1080", 1, 1080, None),
+                // E2: already resolved, nothing to do
+                file("e2-1080", 2, 1080, None),
+                file("e2-480", 2, 480, Some("e2-1080")),
+                // E3: the primary was deleted, so the leftover copy is shown again
+                file("e3-720", 3, 720, Some("gone")),
+            ],
+            STATE_REVIEW,
+        );
+
+        assert_eq!(
+            changes,
+            vec![
+                Assignment {
+                    id: "e1-720".to_string(),
+                    version_of: Some("e1-1080".to_string()),
+                    duplicate_state: Some(STATE_REVIEW),
+                    demoted: true,
+                },
+                Assignment {
+                    id: "e2-480".to_string(),
+                    version_of: Some("e2-1080".to_string()),
+                    duplicate_state: Some(STATE_REVIEW),
+                    demoted: false,
+                },
+                Assignment {
+                    id: "e3-720".to_string(),
+                    version_of: None,
+                    duplicate_state: None,
+                    demoted: false,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,177 @@
+// Scan exclusion globs
+//
+// Besides `.ignore` files, folders and files can be kept out of libraries with
+// glob patterns: `scanner.exclude` applies to every library and a configured
+// library's `exclude` adds its own. Patterns match the path relative to the
+// library folder, case-insensitively, with `*` and `?` staying inside one path
+// component and `**` crossing any number of them. A pattern without a `/`
+// matches a file or folder name at any depth, like a .gitignore line, and an
+// excluded folder excludes everything under it. Libraries added through the
+// API aren't in the config, so their paths are matched from the filesystem
+// root, which only makes a difference for patterns that contain a folder.
+
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+#[derive(Debug, Default)]
+struct ExcludeRules {
+    /// Patterns for every library
+    global: Vec<Regex>,
+    /// Library folder and its own patterns
+    libraries: Vec<(PathBuf, Vec<Regex>)>,
+}
+
+static RULES: OnceLock<ExcludeRules> = OnceLock::new();
+
+/// Set the exclusion patterns from the scanner and library configs
+///
+/// Invalid patterns are logged and skipped.
+pub fn set_patterns(global: &[String], libraries: &[(PathBuf, Vec<String>)]) {
+    let compile_all = |patterns: &[String]| -> Vec<Regex> {
+        patterns
+            .iter()
+            .filter_map(|pattern| match glob_to_regex(pattern) {
+                Some(re) => Some(re),
+                None => {
+                    tracing::warn!("Ignoring invalid scan exclusion pattern '{}'", pattern);
+                    None
+                }
+            })
+            .collect()
+    };
+    let rules = ExcludeRules {
+        global: compile_all(global),
+        libraries: libraries
+            .iter()
+            .filter(|(_, patterns)| !patterns.is_empty())
+            .map(|(root, patterns)| (root.clone(), compile_all(patterns)))
+            .collect(),
+    };
+    let _ = RULES.set(rules);
+}
+
+/// Translate a glob into an anchored, case-insensitive regex
+pub fn glob_to_regex(pattern: &str) -> Option<Regex> {
+    let pattern = pattern.trim().trim_start_matches("./");
+    if pattern.is_empty() {
+        return None;
+    }
+    // Name patterns match at any depth
+    let pattern = if pattern.trim_end_matches('/').contains('/') {
+        pattern.trim_start_matches('/').to_string()
+    } else {
+        format!("**/{}", pattern)
+    };
+    let pattern = pattern.trim_end_matches('/');
+
+    let mut re = String::from("(?i)^");
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                if at_start && chars.get(i + 2) == Some(&'/') {
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else if i > 0 && chars[i - 1] == '/' && i + 2 == chars.len() {
+                    // "dir/**": the folder and everything under it
+                    re.pop();
+                    re.push_str("(?:/.*)?");
+                    i += 2;
+                } else {
+                    re.push_str(".*");
+                    i += 2;
+                }
+            }
+            '*' => {
+                re.push_str("[^/]*");
+                i += 1;
+            }
+            '?' => {
+                re.push_str("[^/]");
+                i += 1;
+            }
+            '[' => {
+                let end = chars[i + 1..].iter().position(|&c| c == ']')? + i + 1;
+                let mut class: String = chars[i + 1..end].iter().collect();
+                if let Some(rest) = class.strip_prefix('!') {
+                    class = format!("^{}", rest);
+                }
+                re.push('[');
+                re.push_str(&class.replace('\\', "\\\\"));
+                re.push(']');
+                i = end + 1;
+            }
+            c => {
+                re.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    // Anything under an excluded folder is excluded too
+    re.push_str("(?:/.*)?$");
+    Regex::new(&re).ok()
+}
+
+/// Whether a file or folder is excluded from its library by a pattern
+pub fn is_excluded(path: &Path) -> bool {
+    let Some(rules) = RULES.get() else {
+        return false;
+    };
+
+    // Relative to the most specific configured library holding the path
+    let library = rules
+        .libraries
+        .iter()
+        .filter(|(root, _)| path.starts_with(root))
+        .max_by_key(|(root, _)| root.as_os_str().len());
+    let relative = match library {
+        Some((root, _)) => path.strip_prefix(root).unwrap_or(path),
+        None => path,
+    };
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let relative = relative.trim_start_matches('/');
+
+    let library_patterns = library.map(|(_, patterns)| patterns.as_slice());
+    rules
+        .global
+        .iter()
+        .chain(library_patterns.unwrap_or_default())
+        .any(|re| re.is_match(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_patterns() {
+        let matches = |pattern: &str, path: &str| glob_to_regex(pattern).unwrap().is_match(path);
+
+        // Names match at any depth, case-insensitively
+        assert!(matches("**/sample*", "Show/Season 1/Sample.mkv"));
+        assert!(matches("sample*", "Sample-episode.mkv"));
+        assert!(matches("*.part", "Show/Episode 01.mkv.part"));
+        assert!(!matches("*.part", "Show/Episode 01.mkv"));
+
+        // Folders exclude their contents
+        assert!(matches("**/@eaDir/**", "Show/@eaDir"));
+        assert!(matches(
+            "**/@eaDir/**",
+            "Show/@eaDir/Episode 01.mkv/SYNOVIDEO_VIDEO_SCREENSHOT.jpg"
+        ));
+        assert!(matches("@eaDir", "@eaDir/thumb.jpg"));
+
+        // Patterns with a folder are anchored at the library folder
+        assert!(matches("Show/Season ?", "Show/Season 2/Episode.mkv"));
+        assert!(!matches("Show/Season ?", "Other/Show/Season 2"));
+        assert!(!matches("Season [!0-9]*", "Show/Season 1"));
+        assert!(matches("Season [!0-9]*", "Show/Season X"));
+
+        // * stays inside one path component
+        assert!(!matches("Show/*.mkv", "Show/Season 1/Episode.mkv"));
+        assert!(matches("Show/**.mkv", "Show/Season 1/Episode.mkv"));
+    }
+}
@@ -0,0 +1,262 @@
+// Scan history
+//
+// Every scan run is recorded in scan_history together with what it changed, so
+// admins can see which items a scheduled scan added or removed. Changes are
+// found by diffing the library's items before and after the run rather than by
+// instrumenting each code path that inserts or deletes: items are keyed by
+// file path (by ID for folders such as series), so an item that was deleted and
+// re-created for the same file counts as updated, not removed and added.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::time::Instant;
+use uuid::Uuid;
+
+/// Changed items recorded per scan; the counts always cover every change
+const MAX_RECORDED_CHANGES: usize = 1000;
+
+/// Scans kept; older runs are deleted when a scan finishes
+const MAX_SCANS_KEPT: i64 = 200;
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct ItemSnapshot {
+    id: String,
+    item_type: String,
+    name: String,
+    path: Option<String>,
+    updated_at: String,
+}
+
+impl ItemSnapshot {
+    fn key(&self) -> String {
+        self.path.clone().unwrap_or_else(|| self.id.clone())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Change {
+    Added,
+    Removed,
+    Updated,
+}
+
+impl Change {
+    fn as_str(self) -> &'static str {
+        match self {
+            Change::Added => "Added",
+            Change::Removed => "Removed",
+            Change::Updated => "Updated",
+        }
+    }
+}
+
+/// Differences between two snapshots, sorted by item type and name
+fn diff<'a>(
+    before: &'a HashMap<String, ItemSnapshot>,
+    after: &'a HashMap<String, ItemSnapshot>,
+) -> Vec<(Change, &'a ItemSnapshot)> {
+    let mut changes: Vec<(Change, &ItemSnapshot)> = Vec::new();
+    for (key, item) in after {
+        match before.get(key) {
+            None => changes.push((Change::Added, item)),
+            Some(old) if old.id != item.id || old.updated_at != item.updated_at => {
+                changes.push((Change::Updated, item))
+            }
+            Some(_) => {}
+        }
+    }
+    for (key, item) in before {
+        if !after.contains_key(key) {
+            changes.push((Change::Removed, item));
+        }
+    }
+    changes.sort_by(|a, b| {
+        (a.1.item_type.as_str(), a.1.name.as_str(), a.1.key()).cmp(&(
+            b.1.item_type.as_str(),
+            b.1.name.as_str(),
+            b.1.key(),
+        ))
+    });
+    changes
+}
+
+async fn snapshot(pool: &SqlitePool, library_id: &str) -> Result<HashMap<String, ItemSnapshot>> {
+    let items: Vec<ItemSnapshot> = sqlx::query_as(
+        "SELECT id, item_type, name, path, updated_at FROM media_items WHERE library_id = ?",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(items.into_iter().map(|item| (item.key(), item)).collect())
+}
+
+/// A scan in progress; call `finish` with the scan's outcome
+pub struct ScanRun {
+    id: String,
+    library_id: String,
+    started: Instant,
+    before: HashMap<String, ItemSnapshot>,
+}
+
+impl ScanRun {
+    /// Record the start of a scan (Full, Quick, Targeted or Refresh) of a library
+    ///
+    /// Returns None (after logging why) if the run can't be recorded; the scan
+    /// should go ahead regardless.
+    pub async fn start(pool: &SqlitePool, library_id: &str, scan_type: &str) -> Option<Self> {
+        match Self::begin(pool, library_id, scan_type).await {
+            Ok(run) => Some(run),
+            Err(e) => {
+                tracing::warn!("Failed to record start of scan: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn begin(pool: &SqlitePool, library_id: &str, scan_type: &str) -> Result<Self> {
+        let before = snapshot(pool, library_id).await?;
+        let id = Uuid::new_v4().to_string();
+        sqlx::query(
+            "INSERT INTO scan_history (id, library_id, library_name, scan_type, status, started_at)
+             SELECT ?, id, name, ?, 'Running', ? FROM libraries WHERE id = ?",
+        )
+        .bind(&id)
+        .bind(scan_type)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(library_id)
+        .execute(pool)
+        .await?;
+
+        Ok(Self {
+            id,
+            library_id: library_id.to_string(),
+            started: Instant::now(),
+            before,
+        })
+    }
+
+    /// Record the scan's result and the items it changed
+    ///
+    /// Failures are logged rather than returned so they never mask the scan's
+    /// own result.
+    pub async fn finish<T>(self, pool: &SqlitePool, outcome: &Result<T>) {
+        if let Err(e) = self.record(pool, outcome.as_ref().err()).await {
+            tracing::warn!("Failed to record scan history: {}", e);
+        }
+    }
+
+    async fn record(&self, pool: &SqlitePool, error: Option<&anyhow::Error>) -> Result<()> {
+        let after = snapshot(pool, &self.library_id).await?;
+        let changes = diff(&self.before, &after);
+        let count = |change: Change| changes.iter().filter(|(c, _)| *c == change).count() as i64;
+
+        let mut tx = pool.begin().await?;
+        sqlx::query(
+            "UPDATE scan_history SET status = ?, finished_at = ?, duration_ms = ?,
+                items_added = ?, items_removed = ?, items_updated = ?, error = ?
+             WHERE id = ?",
+        )
+        .bind(if error.is_some() {
+            "Failed"
+        } else {
+            "Completed"
+        })
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(self.started.elapsed().as_millis() as i64)
+        .bind(count(Change::Added))
+        .bind(count(Change::Removed))
+        .bind(count(Change::Updated))
+        .bind(error.map(|e| format!("{:#}", e)))
+        .bind(&self.id)
+        .execute(&mut *tx)
+        .await?;
+
+        for (change, item) in changes.iter().take(MAX_RECORDED_CHANGES) {
+            sqlx::query(
+                "INSERT INTO scan_history_items (scan_id, change_type, item_id, item_type, name, path)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&self.id)
+            .bind(change.as_str())
+            .bind(&item.id)
+            .bind(&item.item_type)
+            .bind(&item.name)
+            .bind(&item.path)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "DELETE FROM scan_history WHERE id NOT IN
+             (SELECT id FROM scan_history ORDER BY started_at DESC LIMIT ?)",
+        )
+        .bind(MAX_SCANS_KEPT)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Mark scans left running by a previous process as interrupted
+pub async fn mark_interrupted(pool: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query(
+        "UPDATE scan_history SET status = 'Interrupted', finished_at = ? WHERE status = 'Running'",
+    )
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str, path: Option<&str>, updated_at: &str) -> ItemSnapshot {
+        ItemSnapshot {
+            id: id.to_string(),
+            item_type: "Episode".to_string(),
+            name: id.to_string(),
+            path: path.map(str::to_string),
+            updated_at: updated_at.to_string(),
+        }
+    }
+
+    fn by_key(items: Vec<ItemSnapshot>) -> HashMap<String, ItemSnapshot> {
+        items.into_iter().map(|i| (i.key(), i)).collect()
+    }
+
+    #[test]
+    fn test_diff_matches_items_by_path() {
+        let before = by_key(vec![
+            item("a", Some("/tv/a.mkv"), "1"),
+            item("b", Some("/tv/b.mkv"), "1"),
+            item("c", Some("/tv/c.mkv"), "1"),
+            item("series", None, "1"),
+        ]);
+        let after = by_key(vec![
+            item("a", Some("/tv/a.mkv"), "1"),
+            // Re-created for the same file: updated, not removed and added
+            item("b2", Some("/tv/b.mkv"), "2"),
+            item("d", Some("/tv/d.mkv"), "2"),
+            item("series", None, "2"),
+        ]);
+
+        let changes: Vec<(Change, &str)> = diff(&before, &after)
+            .into_iter()
+            .map(|(c, i)| (c, i.id.as_str()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (Change::Updated, "b2"),
+                (Change::Removed, "c"),
+                (Change::Added, "d"),
+                (Change::Updated, "series"),
+            ]
+        );
+    }
+}
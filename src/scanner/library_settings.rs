@@ -0,0 +1,130 @@
+// Per-library settings
+//
+// A library can override the server's metadata and scanner settings: whether
+// the anime offline database is used, whether episode metadata is fetched,
+// which language metadata is requested in, and whether video files are probed
+// with ffprobe. The overrides are columns of the libraries table, where NULL
+// means the server setting applies, and are edited through the LibraryOptions
+// of /Library/VirtualFolders. A copy is kept in memory so the scanner can ask
+// about a library deep inside a scan without a query per file.
+
+use anyhow::Result;
+use regex::Regex;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+static SETTINGS: LazyLock<Mutex<HashMap<String, LibrarySettings>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static RE_LANGUAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-z]{2}(?:-[A-Z]{2})?$").unwrap());
+
+/// A library's overrides; None keeps the server setting
+#[derive(Debug, Clone, Default, PartialEq, sqlx::FromRow)]
+pub struct LibrarySettings {
+    pub anime_db_enabled: Option<bool>,
+    pub fetch_episode_metadata: Option<bool>,
+    /// ISO 639-1 code, optionally with a region ("ja", "pt-BR")
+    pub metadata_language: Option<String>,
+    pub extract_media_info: Option<bool>,
+}
+
+impl LibrarySettings {
+    /// Whether to use the anime offline database, given the server setting
+    pub fn anime_db(&self, server: Option<bool>) -> Option<bool> {
+        self.anime_db_enabled.or(server)
+    }
+
+    /// Whether to fetch episode metadata, given the server setting
+    pub fn episode_metadata(&self, server: bool) -> bool {
+        self.fetch_episode_metadata.unwrap_or(server)
+    }
+}
+
+/// Whether a metadata language looks like "en" or "pt-BR"
+pub fn is_valid_language(language: &str) -> bool {
+    RE_LANGUAGE.is_match(language)
+}
+
+/// Load every library's settings (at startup)
+pub async fn load(pool: &SqlitePool) -> Result<()> {
+    let rows: Vec<SettingsRow> = sqlx::query_as(
+        "SELECT id, anime_db_enabled, fetch_episode_metadata, metadata_language, extract_media_info
+         FROM libraries",
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut settings = SETTINGS.lock().unwrap();
+    settings.clear();
+    for row in rows {
+        settings.insert(row.id, row.settings);
+    }
+    Ok(())
+}
+
+#[derive(sqlx::FromRow)]
+struct SettingsRow {
+    id: String,
+    #[sqlx(flatten)]
+    settings: LibrarySettings,
+}
+
+/// A library's settings
+pub fn get(library_id: &str) -> LibrarySettings {
+    SETTINGS
+        .lock()
+        .unwrap()
+        .get(library_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Store a library's settings
+pub async fn save(pool: &SqlitePool, library_id: &str, settings: &LibrarySettings) -> Result<()> {
+    sqlx::query(
+        "UPDATE libraries SET anime_db_enabled = ?, fetch_episode_metadata = ?,
+         metadata_language = ?, extract_media_info = ? WHERE id = ?",
+    )
+    .bind(settings.anime_db_enabled)
+    .bind(settings.fetch_episode_metadata)
+    .bind(&settings.metadata_language)
+    .bind(settings.extract_media_info)
+    .bind(library_id)
+    .execute(pool)
+    .await?;
+    SETTINGS
+        .lock()
+        .unwrap()
+        .insert(library_id.to_string(), settings.clone());
+    Ok(())
+}
+
+/// Whether the scanner reads a library's video files with ffprobe
+pub fn probes_media(library_id: &str) -> bool {
+    get(library_id).extract_media_info.unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_fall_back_to_server_settings() {
+        let inherit = LibrarySettings::default();
+        assert_eq!(inherit.anime_db(Some(true)), Some(true));
+        assert!(!inherit.episode_metadata(false));
+
+        let custom = LibrarySettings {
+            anime_db_enabled: Some(false),
+            fetch_episode_metadata: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(custom.anime_db(Some(true)), Some(false));
+        assert!(custom.episode_metadata(false));
+
+        assert!(probes_media("library-settings-test"));
+        assert!(is_valid_language("ja") && is_valid_language("pt-BR"));
+        assert!(!is_valid_language("japanese") && !is_valid_language("EN"));
+    }
+}
@@ -3,7 +3,7 @@ use futures::{stream, StreamExt};
 use regex::Regex;
 use sqlx::SqlitePool;
 use std::collections::HashSet;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::sync::LazyLock;
 use tokio::fs;
 use uuid::Uuid;
@@ -12,8 +12,29 @@ use crate::api::filters::{
     get_or_create_genre, get_or_create_person, get_or_create_studio, link_item_genre,
     link_item_person, link_item_studio,
 };
+use crate::events::{self, ServerEvent};
+use crate::services::box_sets;
+use crate::services::external_streams;
 use crate::services::mediainfo;
 use crate::services::metadata::{MetadataService, UnifiedMetadata};
+use crate::services::parental_controls;
+use crate::services::progress;
+use crate::services::season_mapping;
+use crate::services::strm;
+
+pub mod availability;
+pub mod duplicates;
+pub mod exclude;
+pub mod history;
+pub mod library_settings;
+pub mod music;
+pub mod reconcile;
+pub mod samples;
+pub mod seasons;
+pub mod sort_name;
+pub mod watcher;
+
+use history::ScanRun;
 
 /// Concurrency limit for parallel operations (metadata fetch, ffprobe, etc.)
 const SCAN_CONCURRENCY: usize = 4;
@@ -41,6 +62,8 @@ struct EpisodeMediaInfo {
     path: PathBuf,
     parsed: ParsedEpisode,
     runtime_ticks: Option<i64>,
+    width: Option<i32>,
+    height: Option<i32>,
 }
 
 /// Collected media info for a movie (after parallel ffprobe)
@@ -49,6 +72,8 @@ struct MovieMediaInfo {
     path: PathBuf,
     parsed: ParsedMovie,
     runtime_ticks: Option<i64>,
+    width: Option<i32>,
+    height: Option<i32>,
 }
 
 /// Recursively collect all video files in a directory, with symlink loop protection
@@ -80,9 +105,14 @@ async fn collect_video_files(path: &Path, visited: &mut HashSet<PathBuf>) -> Res
 
     while let Some(entry) = entries.next_entry().await? {
         let entry_path = entry.path();
+        if exclude::is_excluded(&entry_path) {
+            continue;
+        }
 
         if entry_path.is_file() && is_video_file(&entry_path) {
-            files.push(entry_path);
+            if !samples::too_small(&entry_path).await {
+                files.push(entry_path);
+            }
         } else if entry_path.is_dir() {
             let folder_name = entry_path
                 .file_name()
@@ -108,23 +138,38 @@ async fn collect_video_files(path: &Path, visited: &mut HashSet<PathBuf>) -> Res
     Ok(files)
 }
 
+/// Media info of a video file, unless its library has ffprobe turned off
+async fn probe_video(library_id: &str, path: &Path) -> Result<mediainfo::MediaInfo> {
+    if !library_settings::probes_media(library_id) {
+        anyhow::bail!("media info extraction is off for this library");
+    }
+    mediainfo::extract_media_info_async(path).await
+}
+
 /// Extract media info for multiple files in parallel
 async fn parallel_extract_media_info(
+    library_id: &str,
     files: Vec<(PathBuf, ParsedEpisode)>,
 ) -> Vec<EpisodeMediaInfo> {
     stream::iter(files)
         .map(|(path, parsed)| async move {
-            let runtime_ticks = match mediainfo::extract_media_info_async(&path).await {
-                Ok(info) => info.duration_ticks,
+            let (runtime_ticks, width, height) = match probe_video(library_id, &path).await {
+                Ok(info) => (
+                    info.duration_ticks,
+                    info.width.map(|w| w as i32),
+                    info.height.map(|h| h as i32),
+                ),
                 Err(e) => {
                     tracing::debug!("Failed to extract media info for {:?}: {}", path, e);
-                    None
+                    (None, None, None)
                 }
             };
             EpisodeMediaInfo {
                 path,
                 parsed,
                 runtime_ticks,
+                width,
+                height,
             }
         })
         .buffer_unordered(SCAN_CONCURRENCY)
@@ -133,20 +178,29 @@ async fn parallel_extract_media_info(
 }
 
 /// Extract media info for multiple movie files in parallel
-async fn parallel_extract_movie_info(files: Vec<(PathBuf, ParsedMovie)>) -> Vec<MovieMediaInfo> {
+async fn parallel_extract_movie_info(
+    library_id: &str,
+    files: Vec<(PathBuf, ParsedMovie)>,
+) -> Vec<MovieMediaInfo> {
     stream::iter(files)
         .map(|(path, parsed)| async move {
-            let runtime_ticks = match mediainfo::extract_media_info_async(&path).await {
-                Ok(info) => info.duration_ticks,
+            let (runtime_ticks, width, height) = match probe_video(library_id, &path).await {
+                Ok(info) => (
+                    info.duration_ticks,
+                    info.width.map(|w| w as i32),
+                    info.height.map(|h| h as i32),
+                ),
                 Err(e) => {
                     tracing::debug!("Failed to extract media info for {:?}: {}", path, e);
-                    None
+                    (None, None, None)
                 }
             };
             MovieMediaInfo {
                 path,
                 parsed,
                 runtime_ticks,
+                width,
+                height,
             }
         })
         .buffer_unordered(SCAN_CONCURRENCY)
@@ -177,13 +231,41 @@ fn get_video_extensions() -> &'static [String] {
         .map(|v| v.as_slice())
         .unwrap_or(&[])
 }
-static RE_SEASON_EP: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"[Ss](\d{1,2})[Ee](\d{1,3})").unwrap());
+static RE_SEASON_EP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[Ss](\d{1,2})[Ee](\d{1,3})((?:-?[Ee]\d{1,3})+|-\d{1,3}\b)?").unwrap()
+});
 static RE_ALT_EP: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"(?:^|[\s\-])[Ee]?(\d{1,2})[Ee](\d{1,3})(?:\s|[\[\(]|$)").unwrap()
 });
+/// "Show - 01-02": no spaces around the dash, so "Show 2 - 05" stays one episode
+static RE_ANIME_RANGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"[\s\-]+[Ee]?(\d{1,3})-[Ee]?(\d{1,3})(?:\s*[\[\(]|$)").unwrap());
 static RE_ANIME_EP: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"[\s\-]+[Ee]?(\d{1,3})(?:\s*[\[\(]|$)").unwrap());
+static RE_SEASON_FOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?:season|series|staffel|saison|temporada)[\s._-]*(\d{1,3})(?:\D|$)|^s(\d{1,2})$",
+    )
+    .unwrap()
+});
+static RE_SPECIALS_FOLDER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)^(?:specials?|season[\s._-]*specials?|ovas?|oads?)$").unwrap()
+});
+/// "Show - Specials" or "Show - OVA" next to the show's own folder
+static RE_SHOW_FOLDER_SPECIALS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?P<base>.+?)\s+-\s+(?:specials?|ovas?|oads?)$").unwrap());
+/// Anime special numbering: "Show - SP01", "Show OVA 2", "Show - Special"
+static RE_SPECIAL_EP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)[\s\-_.]+(?:sp|ova|oad|special)(?:[\s._]?(\d{1,3}))?(?:\s*[\[\(]|$)").unwrap()
+});
+static RE_SHOW_FOLDER_SEASON: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)^(?P<base>.+?)[\s._:-]+(?:s(?P<s>\d{1,2})|season[\s._]*(?P<season>\d{1,2})|(?P<ordinal>\d{1,2})(?:st|nd|rd|th)[\s._]+season|(?P<word>second|third|fourth|fifth|sixth|seventh|eighth|ninth|tenth)[\s._]+season|part[\s._]*(?P<part>\d{1,2}))(?P<rest>[\s._\[\(-].*)?$",
+    )
+    .unwrap()
+});
+static RE_SEASON_RANGE_REST: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^\s*-\s*s?\d").unwrap());
 static RE_GROUP_TAG: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^\[.*?\]\s*[\-]?\s*").unwrap());
 static RE_RELEASE_INFO: LazyLock<Regex> = LazyLock::new(|| {
@@ -209,6 +291,14 @@ static RE_BRACKETED_INFO: LazyLock<Regex> =
 static RE_PAREN_RELEASE_INFO: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new(r"\s*\((?:BD|DVD|BluRay|BDRip|WEB|HDTV|V\d+|\d{3,4}p)[^\)]*\)\s*").unwrap()
 });
+static RE_DUAL_AUDIO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:dual|multi)[\s._-]*(?:audio|lang(?:uage)?s?)\b").unwrap()
+});
+static RE_LANGUAGE_AUDIO: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(eng(?:lish)?|jap(?:anese)?|jpn|spa(?:nish)?|esp|ger(?:man)?|deu|fre(?:nch)?|fra|ita(?:lian)?|por(?:tuguese)?|kor(?:ean)?|chi(?:nese)?|rus(?:sian)?|hin(?:di)?)[\s._-]*(dub(?:bed)?|audio)\b").unwrap()
+});
+static RE_DUBBED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:dub|dubbed|dubs)\b").unwrap());
 static RE_MOVIE_YEAR: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"^(.+?)[\s\.\-]*[\(\[]?(\d{4})[\)\]]?\s*$").unwrap());
 
@@ -218,6 +308,11 @@ pub fn is_video_file(path: &Path) -> bool {
         None => return false,
     };
 
+    // Remote streams are always picked up, whatever the configured extensions
+    if ext == "strm" {
+        return true;
+    }
+
     // Check configured extensions first
     let configured = get_video_extensions();
     if !configured.is_empty() {
@@ -233,6 +328,20 @@ pub struct ParsedEpisode {
     pub show_name: String,
     pub season: i32,
     pub episode: i32,
+    /// Last episode of a file holding several ("S01E01E02", "S01E01-E03", "Show - 01-02")
+    pub episode_end: Option<i32>,
+    /// Season taken from a "Season NN"/"Specials" folder because the filename had none
+    pub season_from_folder: bool,
+}
+
+impl ParsedEpisode {
+    /// Name used until metadata provides one ("Episode 5", "Episode 1-2")
+    pub fn default_name(&self) -> String {
+        match self.episode_end {
+            Some(end) => format!("Episode {}-{}", self.episode, end),
+            None => format!("Episode {}", self.episode),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -247,7 +356,100 @@ pub struct ParsedMovie {
 /// - "[Group] Show Name - E05 [quality].mkv" (anime style)
 /// - "Show Name - 05.mkv" (simple numbered)
 /// - "Show.Name.S01E01.mkv" (dot-separated)
+/// - "Show Name - SP01.mkv", "Show Name OVA 2.mkv" (specials, season 0)
 pub fn parse_episode_filename(filename: &str) -> Option<ParsedEpisode> {
+    parse_episode_name(filename).map(|(parsed, _)| parsed)
+}
+
+/// Parse episode info from a file path, using the season folder when the filename has no season
+///
+/// Anime is often named only "Show - 05.mkv" inside "Season 2/", which the
+/// filename alone would put in season 1. Explicit SxxEyy/1x05 numbering wins.
+pub fn parse_episode_path(path: &Path) -> Option<ParsedEpisode> {
+    let filename = path.file_name()?.to_str()?;
+    let (mut parsed, has_season) = parse_episode_name(filename)?;
+
+    if !has_season {
+        // "Season 2/" inside the show folder, or a show folder like "Show 2nd Season/"
+        let folder_season = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .and_then(|n| {
+                parse_season_folder(n).or_else(|| infer_season_from_folder_name(n).map(|i| i.1))
+            });
+        if let Some(season) = folder_season {
+            parsed.season = season;
+            parsed.season_from_folder = true;
+        }
+    }
+
+    Some(parsed)
+}
+
+/// Season number of a "Season 01"/"S2"/"Specials" folder (specials are season 0)
+pub fn parse_season_folder(folder_name: &str) -> Option<i32> {
+    let name = folder_name.trim();
+    if RE_SPECIALS_FOLDER.is_match(name) {
+        return Some(0);
+    }
+    let caps = RE_SEASON_FOLDER.captures(name)?;
+    caps.get(1).or_else(|| caps.get(2))?.as_str().parse().ok()
+}
+
+/// Split a show folder name like "Show 2nd Season" or "Show S2" into the base show name and season
+///
+/// Recognizes "S2", "Season 2", "2nd Season", "Second Season" and "Part 2"
+/// after the show name, and "Show - Specials"/"Show - OVA" as season 0. Multi-season packs ("S01-S03") are not a single season.
+pub fn infer_season_from_folder_name(folder_name: &str) -> Option<(String, i32)> {
+    if let Some(caps) = RE_SHOW_FOLDER_SPECIALS.captures(folder_name.trim()) {
+        return Some((caps.name("base")?.as_str().trim().to_string(), 0));
+    }
+
+    let caps = RE_SHOW_FOLDER_SEASON.captures(folder_name.trim())?;
+
+    if let Some(rest) = caps.name("rest") {
+        if RE_SEASON_RANGE_REST.is_match(rest.as_str()) {
+            return None;
+        }
+    }
+
+    let season = if let Some(word) = caps.name("word") {
+        match word.as_str().to_lowercase().as_str() {
+            "second" => 2,
+            "third" => 3,
+            "fourth" => 4,
+            "fifth" => 5,
+            "sixth" => 6,
+            "seventh" => 7,
+            "eighth" => 8,
+            "ninth" => 9,
+            _ => 10,
+        }
+    } else {
+        ["s", "season", "ordinal", "part"]
+            .iter()
+            .find_map(|group| caps.name(group))?
+            .as_str()
+            .parse()
+            .ok()?
+    };
+
+    let base = caps.name("base")?.as_str().trim().to_string();
+    Some((base, season))
+}
+
+/// Most episodes one file is taken to hold; a larger "range" is something
+/// else, like "S01E01-720"
+const MAX_EPISODES_PER_FILE: i32 = 10;
+
+/// End of an episode range, if it really is one
+fn episode_range_end(first: i32, last: i32) -> Option<i32> {
+    (last > first && last - first < MAX_EPISODES_PER_FILE).then_some(last)
+}
+
+/// Parse a filename, also reporting whether it contained a season number
+fn parse_episode_name(filename: &str) -> Option<(ParsedEpisode, bool)> {
     let name = filename
         .rsplit_once('.')
         .map(|(name, _)| name)
@@ -256,14 +458,24 @@ pub fn parse_episode_filename(filename: &str) -> Option<ParsedEpisode> {
     if let Some(caps) = RE_SEASON_EP.captures(name) {
         let season: i32 = caps.get(1)?.as_str().parse().ok()?;
         let episode: i32 = caps.get(2)?.as_str().parse().ok()?;
+        // The last number of "E02E03" or "-E03"
+        let episode_end = caps.get(3).and_then(|m| {
+            let last = m.as_str().rsplit(|c: char| !c.is_ascii_digit()).next()?;
+            episode_range_end(episode, last.parse().ok()?)
+        });
 
         let show_name = extract_show_name(name, caps.get(0)?.start());
 
-        return Some(ParsedEpisode {
-            show_name,
-            season,
-            episode,
-        });
+        return Some((
+            ParsedEpisode {
+                show_name,
+                season,
+                episode,
+                episode_end,
+                season_from_folder: false,
+            },
+            true,
+        ));
     }
 
     if let Some(caps) = RE_ALT_EP.captures(name) {
@@ -273,12 +485,56 @@ pub fn parse_episode_filename(filename: &str) -> Option<ParsedEpisode> {
         ) {
             if (1..=20).contains(&season) && (1..=999).contains(&episode) {
                 let show_name = extract_show_name(name, caps.get(0)?.start());
-                return Some(ParsedEpisode {
+                return Some((
+                    ParsedEpisode {
+                        show_name,
+                        season,
+                        episode,
+                        episode_end: None,
+                        season_from_folder: false,
+                    },
+                    true,
+                ));
+            }
+        }
+    }
+
+    // Specials named without a season: "SP01" is S00E01
+    if let Some(caps) = RE_SPECIAL_EP.captures(name) {
+        let episode: i32 = match caps.get(1) {
+            Some(m) => m.as_str().parse().ok()?,
+            None => 1,
+        };
+        let show_name = extract_show_name(name, caps.get(0)?.start());
+        if episode >= 1 && !show_name.is_empty() {
+            return Some((
+                ParsedEpisode {
                     show_name,
-                    season,
+                    season: 0,
                     episode,
-                });
-            }
+                    episode_end: None,
+                    season_from_folder: false,
+                },
+                true,
+            ));
+        }
+    }
+
+    if let Some(caps) = RE_ANIME_RANGE.captures(name) {
+        let episode: i32 = caps.get(1)?.as_str().parse().ok()?;
+        let end: i32 = caps.get(2)?.as_str().parse().ok()?;
+        if let Some(episode_end) = episode_range_end(episode, end).filter(|_| episode >= 1) {
+            let show_name = extract_show_name(name, caps.get(0)?.start());
+            return Some((
+                ParsedEpisode {
+                    show_name,
+                    season: 1,
+                    episode,
+                    episode_end: Some(episode_end),
+                    season_from_folder: false,
+                },
+                false,
+            ));
         }
     }
 
@@ -286,11 +542,16 @@ pub fn parse_episode_filename(filename: &str) -> Option<ParsedEpisode> {
         let episode: i32 = caps.get(1)?.as_str().parse().ok()?;
         if (1..=999).contains(&episode) {
             let show_name = extract_show_name(name, caps.get(0)?.start());
-            return Some(ParsedEpisode {
-                show_name,
-                season: 1,
-                episode,
-            });
+            return Some((
+                ParsedEpisode {
+                    show_name,
+                    season: 1,
+                    episode,
+                    episode_end: None,
+                    season_from_folder: false,
+                },
+                false,
+            ));
         }
     }
 
@@ -340,6 +601,178 @@ pub fn parse_movie_filename(filename: &str) -> ParsedMovie {
     }
 }
 
+/// Audio language hints parsed from file and folder names
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LanguageHints {
+    pub is_dubbed: bool,
+    pub is_dual_audio: bool,
+    /// ISO 639-2 codes of languages mentioned alongside "dub"/"audio"
+    pub languages: Vec<&'static str>,
+}
+
+impl LanguageHints {
+    pub fn is_empty(&self) -> bool {
+        !self.is_dubbed && !self.is_dual_audio && self.languages.is_empty()
+    }
+
+    fn merge(&mut self, other: LanguageHints) {
+        self.is_dubbed |= other.is_dubbed;
+        self.is_dual_audio |= other.is_dual_audio;
+        for lang in other.languages {
+            if !self.languages.contains(&lang) {
+                self.languages.push(lang);
+            }
+        }
+    }
+}
+
+/// Map a language word from a release name to its ISO 639-2 code
+pub fn language_code(word: &str) -> Option<&'static str> {
+    let word = word.to_lowercase();
+    let code = match word.get(..3)? {
+        "eng" => "eng",
+        "jap" | "jpn" => "jpn",
+        "spa" | "esp" => "spa",
+        "ger" | "deu" => "ger",
+        "fre" | "fra" => "fre",
+        "ita" => "ita",
+        "por" => "por",
+        "kor" => "kor",
+        "chi" => "chi",
+        "rus" => "rus",
+        "hin" => "hin",
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Parse dub/language hints from a file or folder name
+/// e.g., "[Group] Show - 01 [Dual Audio]" -> dual audio
+///       "Show S01E01 ENG DUB" -> dubbed, eng
+pub fn parse_language_hints(name: &str) -> LanguageHints {
+    let mut hints = LanguageHints {
+        is_dual_audio: RE_DUAL_AUDIO.is_match(name),
+        is_dubbed: RE_DUBBED.is_match(name),
+        languages: Vec::new(),
+    };
+
+    for caps in RE_LANGUAGE_AUDIO.captures_iter(name) {
+        if let Some(code) = caps.get(1).and_then(|m| language_code(m.as_str())) {
+            if !hints.languages.contains(&code) {
+                hints.languages.push(code);
+            }
+        }
+    }
+
+    hints
+}
+
+/// Language hints for a media file, combining its filename with its two parent folders
+/// (season folder and show/movie folder)
+pub fn language_hints_for_path(path: &Path) -> LanguageHints {
+    let mut hints = LanguageHints::default();
+    for component in path.iter().rev().take(3) {
+        if let Some(name) = component.to_str() {
+            hints.merge(parse_language_hints(name));
+        }
+    }
+    hints
+}
+
+/// Announce a newly inserted item on the event bus
+fn publish_item_added(item_id: &str, item_type: &str, library_id: &str) {
+    events::publish(ServerEvent::ItemAdded {
+        item_id: item_id.to_string(),
+        item_type: item_type.to_string(),
+        library_id: library_id.to_string(),
+    });
+}
+
+/// Store parsed language hints on an item (no-op when nothing was found)
+async fn store_language_hints(
+    pool: &SqlitePool,
+    item_id: &str,
+    hints: &LanguageHints,
+) -> Result<()> {
+    if hints.is_empty() {
+        return Ok(());
+    }
+
+    let languages = (!hints.languages.is_empty()).then(|| hints.languages.join(","));
+
+    sqlx::query(
+        "UPDATE media_items SET is_dubbed = ?, is_dual_audio = ?, audio_languages = ? WHERE id = ?",
+    )
+    .bind(hints.is_dubbed)
+    .bind(hints.is_dual_audio)
+    .bind(languages)
+    .bind(item_id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Store the remote URL of an item added from a .strm file (no-op for other files)
+///
+/// Also called for existing items, so edits to a .strm file apply on the next scan.
+async fn store_stream_url(pool: &SqlitePool, item_id: &str, file_path: &str) {
+    let path = Path::new(file_path);
+    if !strm::is_strm_file(path) {
+        return;
+    }
+    let url = match strm::read_url(path).await {
+        Ok(url) => Some(url),
+        Err(e) => {
+            tracing::warn!("Unplayable .strm file: {:#}", e);
+            None
+        }
+    };
+    if let Err(e) = sqlx::query("UPDATE media_items SET stream_url = ? WHERE id = ?")
+        .bind(url)
+        .bind(item_id)
+        .execute(pool)
+        .await
+    {
+        tracing::warn!("Failed to store stream URL for {}: {}", file_path, e);
+    }
+}
+
+/// Store the subtitle and audio files beside an item's video (none for .strm files)
+///
+/// Also called for existing items, so files added or removed beside a video
+/// apply on the next scan.
+async fn store_external_streams(pool: &SqlitePool, item_id: &str, file_path: &str) {
+    let path = Path::new(file_path);
+    if strm::is_strm_file(path) {
+        return;
+    }
+    if let Err(e) = external_streams::refresh(pool, item_id, path).await {
+        tracing::warn!("Failed to store external streams for {}: {}", file_path, e);
+    }
+}
+
+/// Record the TMDB collection of a new movie, adding it to the collection's BoxSet
+async fn store_movie_collection(pool: &SqlitePool, item_id: &str, metadata: &UnifiedMetadata) {
+    if let Err(e) = box_sets::record_movie(pool, item_id, metadata.collection.as_ref()).await {
+        tracing::warn!(
+            "Failed to update the collection of movie {}: {}",
+            item_id,
+            e
+        );
+    }
+}
+
+/// Store the age rating the provider gave a series or movie (kept when it gave none)
+async fn store_official_rating(pool: &SqlitePool, item_id: &str, metadata: &UnifiedMetadata) {
+    let Some(ref rating) = metadata.official_rating else {
+        return;
+    };
+    if let Err(e) = parental_controls::set_item_rating(pool, item_id, rating).await {
+        tracing::warn!("Failed to store the rating of {}: {}", item_id, e);
+    }
+}
+
 /// Scan a library directory and add all media items to the database
 pub async fn scan_library(
     pool: &SqlitePool,
@@ -369,9 +802,60 @@ pub async fn scan_library_with_cache_dir(
     anime_db_enabled: Option<bool>,
     fetch_episode_metadata: Option<bool>,
 ) -> Result<ScanResult> {
+    if !availability::check_library(pool, library_id, path).await? {
+        return Ok(ScanResult::default());
+    }
+    let _progress = progress::start_scan(library_id, "Full");
+    let run = ScanRun::start(pool, library_id, "Full").await;
+    let result = run_full_scan(
+        pool,
+        library_id,
+        path,
+        library_type,
+        cache_dir,
+        anime_db_enabled,
+        fetch_episode_metadata,
+    )
+    .await;
+    group_episodes(pool, library_id, &result).await;
+    if let Some(run) = run {
+        run.finish(pool, &result).await;
+    }
+    result
+}
+
+/// Group episodes found in several files, and episodes into seasons, once a
+/// scan has succeeded
+async fn group_episodes<T>(pool: &SqlitePool, library_id: &str, outcome: &Result<T>) {
+    if outcome.is_err() {
+        return;
+    }
+    match duplicates::resolve_library(pool, library_id).await {
+        Ok(0) => {}
+        Ok(count) => tracing::info!("Updated duplicate episode grouping for {} files", count),
+        Err(e) => tracing::warn!("Failed to resolve duplicate episodes: {}", e),
+    }
+    if let Err(e) = seasons::sync_library(pool, Some(library_id)).await {
+        tracing::warn!("Failed to update seasons: {}", e);
+    }
+}
+
+/// Full scan with metadata providers set up from the cache directory (not recorded in history)
+async fn run_full_scan(
+    pool: &SqlitePool,
+    library_id: &str,
+    path: &str,
+    library_type: &str,
+    cache_dir: PathBuf,
+    anime_db_enabled: Option<bool>,
+    fetch_episode_metadata: Option<bool>,
+) -> Result<ScanResult> {
+    let settings = library_settings::get(library_id);
     let image_cache_dir = cache_dir.join("images");
-    let metadata_service = MetadataService::from_env(image_cache_dir, anime_db_enabled);
-    let fetch_ep_meta = fetch_episode_metadata.unwrap_or(false);
+    let metadata_service =
+        MetadataService::from_env(image_cache_dir, settings.anime_db(anime_db_enabled))
+            .with_language(settings.metadata_language.as_deref());
+    let fetch_ep_meta = settings.episode_metadata(fetch_episode_metadata.unwrap_or(false));
 
     if metadata_service.has_tmdb() {
         tracing::info!("Metadata providers: AniList + TMDB");
@@ -407,6 +891,21 @@ pub async fn scan_library_with_cache_dir(
     )
     .await;
 
+    // Fill in IDs from other providers while the anime database is loaded
+    if scan_result.is_ok() {
+        match crate::services::provider_ids::cross_populate_library(
+            pool,
+            &metadata_service,
+            library_id,
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Cross-populated provider IDs for {} items", count),
+            Err(e) => tracing::warn!("Failed to cross-populate provider IDs: {}", e),
+        }
+    }
+
     // Unload anime database after scan to free memory
     metadata_service.unload_anime_db().await;
 
@@ -457,20 +956,42 @@ pub async fn scan_library_with_metadata(
         "movies" | "movie" => {
             scan_movie_library(pool, library_id, path, &mut result, metadata).await?;
         }
+        "music" => {
+            result.tracks_added = music::scan_music_folder(
+                pool,
+                library_id,
+                path,
+                path,
+                &HashSet::new(),
+                metadata.map(|m| m.image_cache_dir()),
+            )
+            .await?;
+            music::remove_empty(pool, library_id).await?;
+        }
         _ => {
             tracing::warn!("Unknown library type: {}", library_type);
         }
     }
 
     tracing::info!(
-        "Scan complete: {} series added, {} series reused, {} episodes added, {} episodes in existing series, {} movies added",
+        "Scan complete: {} series added, {} series reused, {} episodes added, {} episodes in existing series, {} movies added, {} tracks added",
         result.series_added,
         result.series_reused,
         result.episodes_added,
         result.episodes_from_existing_series,
-        result.movies_added
+        result.movies_added,
+        result.tracks_added
     );
 
+    events::publish(ServerEvent::ScanCompleted {
+        library_id: library_id.to_string(),
+        items_added: result.series_added
+            + result.episodes_added
+            + result.movies_added
+            + result.tracks_added,
+        items_removed: 0,
+    });
+
     Ok(result)
 }
 
@@ -481,6 +1002,7 @@ pub struct ScanResult {
     pub movies_added: i32,
     pub series_reused: i32,
     pub episodes_from_existing_series: i32,
+    pub tracks_added: i32,
 }
 
 /// Type alias for series row data from database
@@ -543,9 +1065,7 @@ const SKIP_FOLDER_SUFFIXES: &[&str] = &[
     "_ncop",
     " creditless",
     " textless",
-    " - ova",
-    " - special",
-    " - specials",
+    // "Show - OVA" and "Show - Specials" are season 0 of the show
     " - extra",
     " - extras",
     " battle stage",
@@ -742,7 +1262,7 @@ async fn scan_tv_library_with_cache(
                 .unwrap_or_default();
 
             // Check for .ignore file (can skip entire subtrees)
-            if should_ignore_path(&entry_path).await {
+            if should_ignore_path(&entry_path).await || exclude::is_excluded(&entry_path) {
                 tracing::debug!("Skipping ignored folder: {}", folder_name);
                 continue;
             }
@@ -756,11 +1276,25 @@ async fn scan_tv_library_with_cache(
             // This is a show folder - create a series for it
             tracing::info!("Scanning show folder: {}", folder_name);
 
+            // "Show 2nd Season" holds season 2 of "Show", not a separate series
+            let series_name = match infer_season_from_folder_name(folder_name) {
+                Some((base, season)) => {
+                    tracing::debug!(
+                        "Folder '{}' looks like season {} of '{}'",
+                        folder_name,
+                        season,
+                        base
+                    );
+                    base
+                }
+                None => folder_name.to_string(),
+            };
+
             // Create the series using the folder name for metadata lookup
             let (series_id, series_metadata, is_new_series) = create_or_get_series_with_cache(
                 pool,
                 library_id,
-                folder_name,
+                &series_name,
                 folder_name, // Use folder name for anime detection too
                 metadata,
                 series_cache,
@@ -784,7 +1318,10 @@ async fn scan_tv_library_with_cache(
                 fetch_episode_metadata,
             )
             .await?;
-        } else if entry_path.is_file() && is_video_file(&entry_path) {
+        } else if entry_path.is_file()
+            && is_video_file(&entry_path)
+            && !samples::too_small(&entry_path).await
+        {
             // Video files directly in the library root are unusual for TV shows
             // but we'll handle them - try to parse and create as standalone series
             let filename = entry_path
@@ -806,7 +1343,7 @@ async fn scan_tv_library_with_cache(
                     result.series_added += 1;
                 }
 
-                create_episode(
+                let created = create_episode(
                     pool,
                     library_id,
                     &series_id,
@@ -817,7 +1354,9 @@ async fn scan_tv_library_with_cache(
                     fetch_episode_metadata,
                 )
                 .await?;
-                result.episodes_added += 1;
+                if created.is_some() {
+                    result.episodes_added += 1;
+                }
             }
         }
     }
@@ -856,8 +1395,7 @@ async fn scan_show_folder(
     let parseable_files: Vec<(PathBuf, ParsedEpisode)> = video_files
         .into_iter()
         .filter_map(|file_path| {
-            let filename = file_path.file_name()?.to_str()?;
-            let parsed = parse_episode_filename(filename)?;
+            let parsed = parse_episode_path(&file_path)?;
             Some((file_path, parsed))
         })
         .collect();
@@ -868,27 +1406,37 @@ async fn scan_show_folder(
     }
 
     // Phase 3: Extract media info in parallel (ffprobe is the bottleneck)
-    let episodes_with_info = parallel_extract_media_info(parseable_files).await;
+    let mut episodes_with_info = parallel_extract_media_info(library_id, parseable_files).await;
+    episodes_with_info.retain(|e| !samples::too_short(&e.path, e.runtime_ticks));
+
+    // Split-cour rules: metadata is looked up by the provider's numbering
+    progress::add_discovered(library_id, episodes_with_info.len());
+    let season_mappings = season_mapping::get_mappings(pool, series_id).await?;
+    let episodes_before = result.episodes_added;
 
     // Phase 4: Insert episodes into database
     // We process in batches for better memory management, but each episode
     // still needs individual metadata fetch (for episode-specific info) if enabled
     for episode_info in episodes_with_info {
+        progress::add_processed(library_id);
+        let (provider_season, provider_episode) = season_mapping::map_episode(
+            &season_mappings,
+            episode_info.parsed.season,
+            episode_info.parsed.episode,
+        )
+        .unwrap_or((episode_info.parsed.season, episode_info.parsed.episode));
+
         // Fetch episode metadata if available and enabled (e.g., from TMDB)
         let (episode_name, overview, premiere_date, rating) = if fetch_episode_metadata {
             if let Some(service) = metadata_service {
                 match service
-                    .get_episode_metadata(
-                        series_metadata,
-                        episode_info.parsed.season,
-                        episode_info.parsed.episode,
-                    )
+                    .get_episode_metadata(series_metadata, provider_season, provider_episode)
                     .await
                 {
                     Ok(Some(ep_meta)) => {
                         let name = ep_meta
                             .name
-                            .unwrap_or_else(|| format!("Episode {}", episode_info.parsed.episode));
+                            .unwrap_or_else(|| episode_info.parsed.default_name());
                         (
                             name,
                             ep_meta.overview,
@@ -896,41 +1444,57 @@ async fn scan_show_folder(
                             ep_meta.community_rating,
                         )
                     }
-                    _ => (
-                        format!("Episode {}", episode_info.parsed.episode),
-                        None,
-                        None,
-                        None,
-                    ),
+                    _ => (episode_info.parsed.default_name(), None, None, None),
                 }
             } else {
-                (
-                    format!("Episode {}", episode_info.parsed.episode),
-                    None,
-                    None,
-                    None,
-                )
+                (episode_info.parsed.default_name(), None, None, None)
             }
         } else {
-            (
-                format!("Episode {}", episode_info.parsed.episode),
-                None,
-                None,
-                None,
-            )
+            (episode_info.parsed.default_name(), None, None, None)
         };
 
         let id = Uuid::new_v4().to_string();
         let file_path = episode_info.path.to_str().unwrap_or_default();
 
         // Check if this episode already exists (by path) to avoid duplicates
-        let existing: Option<(String,)> =
-            sqlx::query_as("SELECT id FROM media_items WHERE path = ?")
-                .bind(file_path)
-                .fetch_optional(pool)
+        let existing: Option<(String, Option<i32>, Option<i32>)> = sqlx::query_as(
+            "SELECT id, parent_index_number, index_number_end FROM media_items WHERE path = ?",
+        )
+        .bind(file_path)
+        .fetch_optional(pool)
+        .await?;
+
+        if let Some((existing_id, existing_season, existing_end)) = existing {
+            // Earlier scans read multi-episode files as their first episode
+            if episode_info.parsed.episode_end.is_some()
+                && existing_end != episode_info.parsed.episode_end
+            {
+                sqlx::query("UPDATE media_items SET index_number_end = ? WHERE id = ?")
+                    .bind(episode_info.parsed.episode_end)
+                    .bind(&existing_id)
+                    .execute(pool)
+                    .await?;
+            }
+
+            // Earlier scans ignored season folders and put these in season 1;
+            // clearing season_id has the seasons sync file it under the right one
+            if episode_info.parsed.season_from_folder
+                && existing_season != Some(episode_info.parsed.season)
+            {
+                sqlx::query(
+                    "UPDATE media_items SET parent_index_number = ?, season_id = NULL WHERE id = ?",
+                )
+                .bind(episode_info.parsed.season)
+                .bind(&existing_id)
+                .execute(pool)
                 .await?;
+                tracing::info!(
+                    "Moved {} to season {} (from its season folder)",
+                    file_path,
+                    episode_info.parsed.season
+                );
+            }
 
-        if let Some((existing_id,)) = existing {
             // Episode exists, but make sure it has a thumbnail queued
             if !crate::db::has_thumbnail(pool, &existing_id)
                 .await
@@ -938,14 +1502,16 @@ async fn scan_show_folder(
             {
                 let _ = crate::db::queue_thumbnail(pool, &existing_id, file_path).await;
             }
+            store_stream_url(pool, &existing_id, file_path).await;
+            store_external_streams(pool, &existing_id, file_path).await;
             tracing::debug!("Skipping duplicate episode: {}", file_path);
             continue;
         }
 
         sqlx::query(
             r#"INSERT INTO media_items 
-               (id, library_id, parent_id, item_type, name, path, index_number, parent_index_number, runtime_ticks, overview, premiere_date, community_rating)
-               VALUES (?, ?, ?, 'Episode', ?, ?, ?, ?, ?, ?, ?, ?)"#,
+               (id, library_id, parent_id, item_type, name, path, index_number, index_number_end, parent_index_number, runtime_ticks, overview, premiere_date, community_rating, width, height)
+               VALUES (?, ?, ?, 'Episode', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
         )
         .bind(&id)
         .bind(library_id)
@@ -953,14 +1519,25 @@ async fn scan_show_folder(
         .bind(&episode_name)
         .bind(file_path)
         .bind(episode_info.parsed.episode)
+        .bind(episode_info.parsed.episode_end)
         .bind(episode_info.parsed.season)
         .bind(episode_info.runtime_ticks)
         .bind(&overview)
         .bind(&premiere_date)
         .bind(rating)
+        .bind(episode_info.width)
+        .bind(episode_info.height)
         .execute(pool)
         .await?;
 
+        let hints = language_hints_for_path(&episode_info.path);
+        if let Err(e) = store_language_hints(pool, &id, &hints).await {
+            tracing::warn!("Failed to store language hints for {}: {}", file_path, e);
+        }
+        store_stream_url(pool, &id, file_path).await;
+        store_external_streams(pool, &id, file_path).await;
+        publish_item_added(&id, "Episode", library_id);
+
         // Queue thumbnail generation
         if let Err(e) = crate::db::queue_thumbnail(pool, &id, file_path).await {
             tracing::warn!("Failed to queue thumbnail for episode {}: {}", id, e);
@@ -969,6 +1546,10 @@ async fn scan_show_folder(
         result.episodes_added += 1;
     }
 
+    if !season_mappings.is_empty() && result.episodes_added > episodes_before {
+        season_mapping::apply_season_mappings(pool, series_id).await?;
+    }
+
     Ok(())
 }
 
@@ -1014,10 +1595,13 @@ async fn scan_movie_library(
         .collect();
 
     // Phase 3: Extract media info in parallel
-    let movies_with_info = parallel_extract_movie_info(parseable_files).await;
+    let mut movies_with_info = parallel_extract_movie_info(library_id, parseable_files).await;
+    movies_with_info.retain(|m| !samples::too_short(&m.path, m.runtime_ticks));
+    progress::add_discovered(library_id, movies_with_info.len());
 
     // Phase 4: Fetch metadata and insert movies
     for movie_info in movies_with_info {
+        progress::add_processed(library_id);
         let file_path = movie_info.path.to_str().unwrap_or_default();
 
         // Check if this movie already exists (by path) to avoid duplicates
@@ -1035,6 +1619,8 @@ async fn scan_movie_library(
             {
                 let _ = crate::db::queue_thumbnail(pool, &existing_id, file_path).await;
             }
+            store_stream_url(pool, &existing_id, file_path).await;
+            store_external_streams(pool, &existing_id, file_path).await;
             tracing::debug!("Skipping duplicate movie: {}", file_path);
             continue;
         }
@@ -1068,7 +1654,6 @@ async fn scan_movie_library(
         };
 
         let id = Uuid::new_v4().to_string();
-        let sort_name = movie_info.parsed.title.to_lowercase();
 
         let (
             final_name,
@@ -1111,15 +1696,15 @@ async fn scan_movie_library(
 
         sqlx::query(
             r#"INSERT INTO media_items 
-               (id, library_id, item_type, name, path, year, sort_name, runtime_ticks, overview, premiere_date, community_rating, tmdb_id, imdb_id, anilist_id, mal_id)
-               VALUES (?, ?, 'Movie', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+               (id, library_id, item_type, name, path, year, sort_name, runtime_ticks, overview, premiere_date, community_rating, tmdb_id, imdb_id, anilist_id, mal_id, width, height)
+               VALUES (?, ?, 'Movie', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
         )
         .bind(&id)
         .bind(library_id)
         .bind(final_name)
         .bind(file_path)
         .bind(year)
-        .bind(&sort_name)
+        .bind(sort_name::sort_name(final_name))
         .bind(runtime_ticks)
         .bind(overview)
         .bind(premiere_date)
@@ -1128,17 +1713,30 @@ async fn scan_movie_library(
         .bind(imdb_id)
         .bind(anilist_id)
         .bind(mal_id)
+        .bind(movie_info.width)
+        .bind(movie_info.height)
         .execute(pool)
         .await?;
 
+        let hints = language_hints_for_path(&movie_info.path);
+        if let Err(e) = store_language_hints(pool, &id, &hints).await {
+            tracing::warn!("Failed to store language hints for {}: {}", file_path, e);
+        }
+        store_stream_url(pool, &id, file_path).await;
+        store_external_streams(pool, &id, file_path).await;
+        publish_item_added(&id, "Movie", library_id);
+
         // Queue images for background download
         if let Some(ref meta) = metadata {
+            store_movie_collection(pool, &id, meta).await;
+            store_official_rating(pool, &id, meta).await;
             if let Some(ref url) = meta.poster_url {
                 let _ = crate::db::queue_image(pool, &id, "Primary", url).await;
             }
             if let Some(ref url) = meta.backdrop_url {
                 let _ = crate::db::queue_image(pool, &id, "Backdrop", url).await;
             }
+            let _ = crate::db::queue_extra_backdrops(pool, &id, &meta.extra_backdrop_urls).await;
         }
 
         // Queue thumbnail generation
@@ -1504,6 +2102,7 @@ async fn update_series_metadata(
     .bind(series_id)
     .execute(pool)
     .await?;
+    store_official_rating(pool, series_id, metadata).await;
 
     // Queue images if available
     if let Some(ref url) = metadata.poster_url {
@@ -1512,6 +2111,7 @@ async fn update_series_metadata(
     if let Some(ref url) = metadata.backdrop_url {
         let _ = crate::db::queue_image(pool, series_id, "Backdrop", url).await;
     }
+    let _ = crate::db::queue_extra_backdrops(pool, series_id, &metadata.extra_backdrop_urls).await;
 
     // Update genres
     if let Some(ref genres) = metadata.genres {
@@ -1559,7 +2159,6 @@ async fn create_or_get_series_with_cache(
 ) -> Result<(String, Option<UnifiedMetadata>, bool)> {
     // Returns (series_id, metadata, is_new_series)
     // is_new_series is true if a new series was created, false if an existing one was reused
-    let sort_name = name.to_lowercase();
 
     // Extract year from folder name (e.g., "My Happy Marriage (2023)" -> 2023)
     let (clean_name, folder_year) = extract_year_from_name(name);
@@ -1725,7 +2324,7 @@ async fn create_or_get_series_with_cache(
     .bind(&id)
     .bind(library_id)
     .bind(final_name)
-    .bind(&sort_name)
+    .bind(sort_name::sort_name(final_name))
     .bind(overview)
     .bind(year)
     .bind(premiere_date)
@@ -1739,8 +2338,15 @@ async fn create_or_get_series_with_cache(
     .execute(pool)
     .await?;
 
+    let hints = parse_language_hints(name);
+    if let Err(e) = store_language_hints(pool, &id, &hints).await {
+        tracing::warn!("Failed to store language hints for series {}: {}", name, e);
+    }
+    publish_item_added(&id, "Series", library_id);
+
     // Queue images for background download instead of blocking
     if let Some(ref meta) = metadata {
+        store_official_rating(pool, &id, meta).await;
         if let Some(ref url) = meta.poster_url {
             if let Err(e) = crate::db::queue_image(pool, &id, "Primary", url).await {
                 tracing::warn!("Failed to queue poster image for {}: {}", name, e);
@@ -1751,6 +2357,10 @@ async fn create_or_get_series_with_cache(
                 tracing::warn!("Failed to queue backdrop image for {}: {}", name, e);
             }
         }
+        if let Err(e) = crate::db::queue_extra_backdrops(pool, &id, &meta.extra_backdrop_urls).await
+        {
+            tracing::warn!("Failed to queue backdrop images for {}: {}", name, e);
+        }
     }
 
     // Save genres to normalized tables
@@ -1829,6 +2439,11 @@ async fn create_or_get_series_with_cache(
 
     // Track if this series has no metadata
     if metadata.is_none() {
+        let failure_reason = if metadata_service.is_some_and(|s| s.has_provider_outage()) {
+            "Metadata provider unavailable"
+        } else {
+            "No metadata match found"
+        };
         if let Err(e) = mark_series_unmatched(
             pool,
             library_id,
@@ -1836,7 +2451,7 @@ async fn create_or_get_series_with_cache(
             name,
             name,
             extract_year_from_name(name).1,
-            "No metadata match found",
+            failure_reason,
         )
         .await
         {
@@ -1856,7 +2471,7 @@ async fn create_episode(
     series_metadata: Option<&UnifiedMetadata>,
     metadata_service: Option<&MetadataService>,
     fetch_episode_metadata: bool,
-) -> Result<String> {
+) -> Result<Option<String>> {
     // Check if this episode already exists (by path) to avoid duplicates
     let existing: Option<(String,)> = sqlx::query_as("SELECT id FROM media_items WHERE path = ?")
         .bind(file_path)
@@ -1871,12 +2486,40 @@ async fn create_episode(
         {
             let _ = crate::db::queue_thumbnail(pool, &existing_id, file_path).await;
         }
+        store_stream_url(pool, &existing_id, file_path).await;
+        store_external_streams(pool, &existing_id, file_path).await;
         tracing::debug!("Episode already exists, skipping: {}", file_path);
-        return Ok(existing_id);
+        return Ok(Some(existing_id));
     }
 
     let id = Uuid::new_v4().to_string();
 
+    // Extract media info (duration, etc.)
+    let (runtime_ticks, width, height) = match probe_video(library_id, Path::new(file_path)).await {
+        Ok(info) => {
+            tracing::debug!(
+                "Media info for {}: duration={:?}, resolution={:?}x{:?}",
+                file_path,
+                info.duration_ticks,
+                info.width,
+                info.height
+            );
+            (
+                info.duration_ticks,
+                info.width.map(|w| w as i32),
+                info.height.map(|h| h as i32),
+            )
+        }
+        Err(e) => {
+            tracing::warn!("Failed to extract media info for {}: {}", file_path, e);
+            (None, None, None)
+        }
+    };
+    // Samples are only recognisable once their duration is known
+    if samples::too_short(Path::new(file_path), runtime_ticks) {
+        return Ok(None);
+    }
+
     // Try to fetch episode metadata from TMDB if enabled and we have a TMDB ID for the series
     let (episode_name, overview, premiere_date, rating) = if fetch_episode_metadata {
         if let Some(service) = metadata_service {
@@ -1885,9 +2528,7 @@ async fn create_episode(
                 .await
             {
                 Ok(Some(ep_meta)) => {
-                    let name = ep_meta
-                        .name
-                        .unwrap_or_else(|| format!("Episode {}", parsed.episode));
+                    let name = ep_meta.name.unwrap_or_else(|| parsed.default_name());
                     tracing::debug!(
                         "Found episode metadata: S{:02}E{:02} - {}",
                         parsed.season,
@@ -1907,7 +2548,7 @@ async fn create_episode(
                         parsed.season,
                         parsed.episode
                     );
-                    (format!("Episode {}", parsed.episode), None, None, None)
+                    (parsed.default_name(), None, None, None)
                 }
                 Err(e) => {
                     tracing::warn!(
@@ -1916,36 +2557,20 @@ async fn create_episode(
                         parsed.episode,
                         e
                     );
-                    (format!("Episode {}", parsed.episode), None, None, None)
+                    (parsed.default_name(), None, None, None)
                 }
             }
         } else {
-            (format!("Episode {}", parsed.episode), None, None, None)
+            (parsed.default_name(), None, None, None)
         }
     } else {
-        (format!("Episode {}", parsed.episode), None, None, None)
-    };
-
-    // Extract media info (duration, etc.)
-    let runtime_ticks = match mediainfo::extract_media_info_async(Path::new(file_path)).await {
-        Ok(info) => {
-            tracing::debug!(
-                "Media info for {}: duration={:?}",
-                file_path,
-                info.duration_ticks
-            );
-            info.duration_ticks
-        }
-        Err(e) => {
-            tracing::warn!("Failed to extract media info for {}: {}", file_path, e);
-            None
-        }
+        (parsed.default_name(), None, None, None)
     };
 
     sqlx::query(
         r#"INSERT INTO media_items 
-           (id, library_id, parent_id, item_type, name, path, index_number, parent_index_number, runtime_ticks, overview, premiere_date, community_rating)
-           VALUES (?, ?, ?, 'Episode', ?, ?, ?, ?, ?, ?, ?, ?)"#,
+           (id, library_id, parent_id, item_type, name, path, index_number, index_number_end, parent_index_number, runtime_ticks, overview, premiere_date, community_rating, width, height)
+           VALUES (?, ?, ?, 'Episode', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
     )
     .bind(&id)
     .bind(library_id)
@@ -1953,14 +2578,25 @@ async fn create_episode(
     .bind(&episode_name)
     .bind(file_path)
     .bind(parsed.episode)
+    .bind(parsed.episode_end)
     .bind(parsed.season)
     .bind(runtime_ticks)
     .bind(&overview)
     .bind(&premiere_date)
     .bind(rating)
+    .bind(width)
+    .bind(height)
     .execute(pool)
     .await?;
 
+    let hints = language_hints_for_path(Path::new(file_path));
+    if let Err(e) = store_language_hints(pool, &id, &hints).await {
+        tracing::warn!("Failed to store language hints for {}: {}", file_path, e);
+    }
+    store_stream_url(pool, &id, file_path).await;
+    store_external_streams(pool, &id, file_path).await;
+    publish_item_added(&id, "Episode", library_id);
+
     tracing::debug!(
         "Created episode: S{:02}E{:02} - {}",
         parsed.season,
@@ -1973,7 +2609,7 @@ async fn create_episode(
         tracing::warn!("Failed to queue thumbnail for episode {}: {}", id, e);
     }
 
-    Ok(id)
+    Ok(Some(id))
 }
 
 async fn create_movie(
@@ -1982,7 +2618,7 @@ async fn create_movie(
     parsed: &ParsedMovie,
     file_path: &str,
     metadata_service: Option<&MetadataService>,
-) -> Result<String> {
+) -> Result<Option<String>> {
     // Check if this movie already exists (by path) to avoid duplicates
     let existing: Option<(String,)> = sqlx::query_as("SELECT id FROM media_items WHERE path = ?")
         .bind(file_path)
@@ -1997,12 +2633,39 @@ async fn create_movie(
         {
             let _ = crate::db::queue_thumbnail(pool, &existing_id, file_path).await;
         }
+        store_stream_url(pool, &existing_id, file_path).await;
+        store_external_streams(pool, &existing_id, file_path).await;
         tracing::debug!("Movie already exists, skipping: {}", file_path);
-        return Ok(existing_id);
+        return Ok(Some(existing_id));
     }
 
     let id = Uuid::new_v4().to_string();
-    let sort_name = parsed.title.to_lowercase();
+
+    // Extract media info (duration, etc.)
+    let (runtime_ticks, width, height) = match probe_video(library_id, Path::new(file_path)).await {
+        Ok(info) => {
+            tracing::debug!(
+                "Media info for {}: duration={:?}, resolution={:?}x{:?}",
+                file_path,
+                info.duration_ticks,
+                info.width,
+                info.height
+            );
+            (
+                info.duration_ticks,
+                info.width.map(|w| w as i32),
+                info.height.map(|h| h as i32),
+            )
+        }
+        Err(e) => {
+            tracing::warn!("Failed to extract media info for {}: {}", file_path, e);
+            (None, None, None)
+        }
+    };
+    // Samples are only recognisable once their duration is known
+    if samples::too_short(Path::new(file_path), runtime_ticks) {
+        return Ok(None);
+    }
 
     // Try to fetch metadata from unified service
     let metadata = if let Some(service) = metadata_service {
@@ -2056,33 +2719,17 @@ async fn create_movie(
             )
         };
 
-    // Extract media info (duration, etc.)
-    let runtime_ticks = match mediainfo::extract_media_info_async(Path::new(file_path)).await {
-        Ok(info) => {
-            tracing::debug!(
-                "Media info for {}: duration={:?}",
-                file_path,
-                info.duration_ticks
-            );
-            info.duration_ticks
-        }
-        Err(e) => {
-            tracing::warn!("Failed to extract media info for {}: {}", file_path, e);
-            None
-        }
-    };
-
     sqlx::query(
         r#"INSERT INTO media_items 
-           (id, library_id, item_type, name, path, year, sort_name, runtime_ticks, overview, premiere_date, community_rating, tmdb_id, imdb_id, anilist_id, mal_id)
-           VALUES (?, ?, 'Movie', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+           (id, library_id, item_type, name, path, year, sort_name, runtime_ticks, overview, premiere_date, community_rating, tmdb_id, imdb_id, anilist_id, mal_id, width, height)
+           VALUES (?, ?, 'Movie', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
     )
     .bind(&id)
     .bind(library_id)
     .bind(final_name)
     .bind(file_path)
     .bind(year)
-    .bind(&sort_name)
+    .bind(sort_name::sort_name(final_name))
     .bind(runtime_ticks)
     .bind(overview)
     .bind(premiere_date)
@@ -2091,11 +2738,23 @@ async fn create_movie(
     .bind(imdb_id)
     .bind(anilist_id)
     .bind(mal_id)
+    .bind(width)
+    .bind(height)
     .execute(pool)
     .await?;
 
+    let hints = language_hints_for_path(Path::new(file_path));
+    if let Err(e) = store_language_hints(pool, &id, &hints).await {
+        tracing::warn!("Failed to store language hints for {}: {}", file_path, e);
+    }
+    store_stream_url(pool, &id, file_path).await;
+    store_external_streams(pool, &id, file_path).await;
+    publish_item_added(&id, "Movie", library_id);
+
     // Queue images for background download instead of blocking
     if let Some(ref meta) = metadata {
+        store_movie_collection(pool, &id, meta).await;
+        store_official_rating(pool, &id, meta).await;
         if let Some(ref url) = meta.poster_url {
             if let Err(e) = crate::db::queue_image(pool, &id, "Primary", url).await {
                 tracing::warn!("Failed to queue poster image for {}: {}", parsed.title, e);
@@ -2106,6 +2765,14 @@ async fn create_movie(
                 tracing::warn!("Failed to queue backdrop image for {}: {}", parsed.title, e);
             }
         }
+        if let Err(e) = crate::db::queue_extra_backdrops(pool, &id, &meta.extra_backdrop_urls).await
+        {
+            tracing::warn!(
+                "Failed to queue backdrop images for {}: {}",
+                parsed.title,
+                e
+            );
+        }
     }
 
     // Save genres to normalized tables
@@ -2146,7 +2813,7 @@ async fn create_movie(
         tracing::warn!("Failed to queue thumbnail for movie {}: {}", id, e);
     }
 
-    Ok(id)
+    Ok(Some(id))
 }
 
 /// Refresh all libraries
@@ -2167,22 +2834,43 @@ pub async fn refresh_all_libraries_with_settings(
             .await?;
 
     for (library_id, path, library_type) in libraries {
-        // Clear existing items for this library
-        sqlx::query("DELETE FROM media_items WHERE library_id = ?")
-            .bind(&library_id)
-            .execute(pool)
-            .await?;
+        // Files of an offline library look deleted; leave them until it's back
+        if !availability::check_library(pool, &library_id, &path).await? {
+            continue;
+        }
 
-        scan_library_with_cache_dir(
-            pool,
-            &library_id,
-            &path,
-            &library_type,
-            cache_dir.clone(),
-            anime_db_enabled,
-            fetch_episode_metadata,
-        )
-        .await?;
+        // Reconciling and rescanning is one run in the history
+        let _progress = progress::start_scan(&library_id, "Refresh");
+        let run = ScanRun::start(pool, &library_id, "Refresh").await;
+        let result = async {
+            // Drop the items of deleted files and probe changed ones; items of
+            // files that are still there keep their IDs and user data
+            let reconciled =
+                reconcile::reconcile_library(pool, &library_id, &cache_dir.join("images")).await?;
+            tracing::info!(
+                "Refreshing library '{}': {} items removed, {} changed files updated",
+                library_id,
+                reconciled.removed,
+                reconciled.updated
+            );
+
+            run_full_scan(
+                pool,
+                &library_id,
+                &path,
+                &library_type,
+                cache_dir.clone(),
+                anime_db_enabled,
+                fetch_episode_metadata,
+            )
+            .await
+        }
+        .await;
+        group_episodes(pool, &library_id, &result).await;
+        if let Some(run) = run {
+            run.finish(pool, &result).await;
+        }
+        result?;
     }
 
     Ok(())
@@ -2196,32 +2884,29 @@ pub struct QuickScanResult {
     pub libraries_scanned: i32,
 }
 
-/// Quick scan all libraries - only adds new files and removes missing ones
-/// This is much faster than a full refresh as it doesn't re-fetch metadata for existing items
-pub async fn quick_scan_all_libraries(
+/// Quick scan a single library - only adds new files, removes missing ones
+pub async fn quick_scan_library(
     pool: &SqlitePool,
+    library_id: &str,
+    path: &str,
+    library_type: &str,
     cache_dir: PathBuf,
 ) -> Result<QuickScanResult> {
-    let libraries: Vec<(String, String, String)> =
-        sqlx::query_as("SELECT id, path, library_type FROM libraries")
-            .fetch_all(pool)
-            .await?;
-
-    let mut total_result = QuickScanResult::default();
-
-    for (library_id, path, library_type) in libraries {
-        let result =
-            quick_scan_library(pool, &library_id, &path, &library_type, cache_dir.clone()).await?;
-        total_result.files_added += result.files_added;
-        total_result.files_removed += result.files_removed;
-        total_result.libraries_scanned += 1;
+    // Files of an offline library look deleted; leave them until it's back
+    if !availability::check_library(pool, library_id, path).await? {
+        return Ok(QuickScanResult::default());
     }
-
-    Ok(total_result)
+    let _progress = progress::start_scan(library_id, "Quick");
+    let run = ScanRun::start(pool, library_id, "Quick").await;
+    let result = run_quick_scan(pool, library_id, path, library_type, cache_dir).await;
+    group_episodes(pool, library_id, &result).await;
+    if let Some(run) = run {
+        run.finish(pool, &result).await;
+    }
+    result
 }
 
-/// Quick scan a single library - only adds new files, removes missing ones
-pub async fn quick_scan_library(
+async fn run_quick_scan(
     pool: &SqlitePool,
     library_id: &str,
     path: &str,
@@ -2245,19 +2930,16 @@ pub async fn quick_scan_library(
 
     // Check for removed files (use async to avoid blocking)
     for (item_id, item_path) in &existing_paths {
-        if !fs::try_exists(Path::new(item_path)).await.unwrap_or(true) {
-            tracing::info!("Removing missing file from database: {}", item_path);
-            sqlx::query("DELETE FROM media_items WHERE id = ?")
-                .bind(item_id)
-                .execute(pool)
-                .await?;
+        if reconcile::remove_if_missing(pool, item_id, item_path).await? {
             result.files_removed += 1;
         }
     }
 
     // Create metadata service for new files
+    let settings = library_settings::get(library_id);
     let image_cache_dir = cache_dir.join("images");
-    let metadata_service = MetadataService::from_env(image_cache_dir, None);
+    let metadata_service = MetadataService::from_env(image_cache_dir, settings.anime_db(None))
+        .with_language(settings.metadata_language.as_deref());
 
     // Scan for new files (use async check to avoid blocking)
     let path = Path::new(path);
@@ -2290,6 +2972,19 @@ pub async fn quick_scan_library(
                 Some(&metadata_service),
             )
             .await?;
+            box_sets::remove_empty(pool).await?;
+        }
+        "music" => {
+            result.files_added += music::scan_music_folder(
+                pool,
+                library_id,
+                path,
+                path,
+                &existing_path_set,
+                Some(metadata_service.image_cache_dir()),
+            )
+            .await?;
+            music::remove_empty(pool, library_id).await?;
         }
         _ => {
             tracing::warn!("Unknown library type for quick scan: {}", library_type);
@@ -2307,6 +3002,12 @@ pub async fn quick_scan_library(
         tracing::debug!("Quick scan complete for '{}': no changes", library_id);
     }
 
+    events::publish(ServerEvent::ScanCompleted {
+        library_id: library_id.to_string(),
+        items_added: result.files_added,
+        items_removed: result.files_removed,
+    });
+
     Ok(result)
 }
 
@@ -2320,87 +3021,83 @@ async fn quick_scan_tv_library(
     metadata: Option<&MetadataService>,
     fetch_episode_metadata: bool,
 ) -> Result<()> {
-    let mut entries = fs::read_dir(path).await?;
+    let mut series_map = load_series_map(pool, library_id).await?;
+    quick_scan_tv_folder(
+        pool,
+        library_id,
+        path,
+        existing_paths,
+        &mut series_map,
+        result,
+        metadata,
+        fetch_episode_metadata,
+    )
+    .await
+}
 
-    // Track series we've created this scan: name -> (id, metadata)
-    let mut series_map: std::collections::HashMap<String, (String, Option<UnifiedMetadata>)> =
-        std::collections::HashMap::new();
+/// Series of a TV library by name, with the metadata of those found this scan
+type SeriesMap = std::collections::HashMap<String, (String, Option<UnifiedMetadata>)>;
 
-    // Pre-load existing series from DB
+async fn load_series_map(pool: &SqlitePool, library_id: &str) -> Result<SeriesMap> {
     let existing_series: Vec<(String, String)> = sqlx::query_as(
         "SELECT id, name FROM media_items WHERE library_id = ? AND item_type = 'Series'",
     )
     .bind(library_id)
     .fetch_all(pool)
     .await?;
+    Ok(existing_series
+        .into_iter()
+        .map(|(id, name)| (name, (id, None)))
+        .collect())
+}
 
-    for (id, name) in existing_series {
-        series_map.insert(name, (id, None));
-    }
-
+#[allow(clippy::too_many_arguments)]
+async fn quick_scan_tv_folder(
+    pool: &SqlitePool,
+    library_id: &str,
+    path: &Path,
+    existing_paths: &std::collections::HashSet<String>,
+    series_map: &mut SeriesMap,
+    result: &mut QuickScanResult,
+    metadata: Option<&MetadataService>,
+    fetch_episode_metadata: bool,
+) -> Result<()> {
+    let mut entries = fs::read_dir(path).await?;
     let mut items_processed = 0u32;
 
     while let Some(entry) = entries.next_entry().await? {
         let entry_path = entry.path();
+        if exclude::is_excluded(&entry_path) {
+            continue;
+        }
 
         if entry_path.is_file() && is_video_file(&entry_path) {
-            let path_str = entry_path.to_str().unwrap_or_default().to_string();
-
-            // Skip if already in database
-            if existing_paths.contains(&path_str) {
+            let path_str = entry_path.to_str().unwrap_or_default();
+            if existing_paths.contains(path_str) {
                 continue;
             }
-
-            let filename = entry_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or_default();
-
-            if let Some(parsed) = parse_episode_filename(filename) {
-                // Get or create series
-                let (series_id, series_metadata) =
-                    if let Some((id, meta)) = series_map.get(&parsed.show_name) {
-                        (id.clone(), meta.clone())
-                    } else {
-                        let (id, meta, _is_new) = create_or_get_series(
-                            pool,
-                            library_id,
-                            &parsed.show_name,
-                            filename,
-                            metadata,
-                        )
-                        .await?;
-                        series_map.insert(parsed.show_name.clone(), (id.clone(), meta.clone()));
-                        (id, meta)
-                    };
-
-                // Create episode
-                create_episode(
-                    pool,
-                    library_id,
-                    &series_id,
-                    &parsed,
-                    &path_str,
-                    series_metadata.as_ref(),
-                    metadata,
-                    fetch_episode_metadata,
-                )
-                .await?;
-                result.files_added += 1;
-
-                tracing::debug!("Added new episode: {}", filename);
-            }
+            quick_add_episode(
+                pool,
+                library_id,
+                &entry_path,
+                series_map,
+                result,
+                metadata,
+                fetch_episode_metadata,
+            )
+            .await?;
 
             items_processed += 1;
             if items_processed.is_multiple_of(10) {
                 tokio::task::yield_now().await;
             }
         } else if entry_path.is_dir() {
-            Box::pin(quick_scan_tv_library(
+            Box::pin(quick_scan_tv_folder(
                 pool,
                 library_id,
                 &entry_path,
                 existing_paths,
+                series_map,
                 result,
                 metadata,
                 fetch_episode_metadata,
@@ -2412,6 +3109,56 @@ async fn quick_scan_tv_library(
     Ok(())
 }
 
+/// Add an episode file that isn't in the library yet, unless it's a sample or stub
+async fn quick_add_episode(
+    pool: &SqlitePool,
+    library_id: &str,
+    file: &Path,
+    series_map: &mut SeriesMap,
+    result: &mut QuickScanResult,
+    metadata: Option<&MetadataService>,
+    fetch_episode_metadata: bool,
+) -> Result<()> {
+    if samples::skipped_before(file) || samples::too_small(file).await {
+        return Ok(());
+    }
+    let path_str = file.to_str().unwrap_or_default();
+    let filename = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+    let Some(parsed) = parse_episode_path(file) else {
+        return Ok(());
+    };
+
+    // Get or create series
+    let (series_id, series_metadata) = if let Some((id, meta)) = series_map.get(&parsed.show_name) {
+        (id.clone(), meta.clone())
+    } else {
+        let (id, meta, _is_new) =
+            create_or_get_series(pool, library_id, &parsed.show_name, filename, metadata).await?;
+        series_map.insert(parsed.show_name.clone(), (id.clone(), meta.clone()));
+        (id, meta)
+    };
+
+    let created = create_episode(
+        pool,
+        library_id,
+        &series_id,
+        &parsed,
+        path_str,
+        series_metadata.as_ref(),
+        metadata,
+        fetch_episode_metadata,
+    )
+    .await?;
+    if created.is_some() {
+        result.files_added += 1;
+        tracing::debug!("Added new episode: {}", filename);
+    }
+    Ok(())
+}
+
 /// Quick scan movie library - only process files not already in database
 async fn quick_scan_movie_library(
     pool: &SqlitePool,
@@ -2426,25 +3173,16 @@ async fn quick_scan_movie_library(
 
     while let Some(entry) = entries.next_entry().await? {
         let entry_path = entry.path();
+        if exclude::is_excluded(&entry_path) {
+            continue;
+        }
 
         if entry_path.is_file() && is_video_file(&entry_path) {
-            let path_str = entry_path.to_str().unwrap_or_default().to_string();
-
-            // Skip if already in database
-            if existing_paths.contains(&path_str) {
+            let path_str = entry_path.to_str().unwrap_or_default();
+            if existing_paths.contains(path_str) {
                 continue;
             }
-
-            let filename = entry_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or_default();
-
-            let parsed = parse_movie_filename(filename);
-            create_movie(pool, library_id, &parsed, &path_str, metadata).await?;
-            result.files_added += 1;
-
-            tracing::debug!("Added new movie: {}", filename);
+            quick_add_movie(pool, library_id, &entry_path, result, metadata).await?;
 
             items_processed += 1;
             if items_processed.is_multiple_of(10) {
@@ -2466,53 +3204,542 @@ async fn quick_scan_movie_library(
     Ok(())
 }
 
-/// Result of scanning for missing metadata
-#[derive(Debug, Default)]
-pub struct MissingMetadataResult {
-    pub series_scanned: i32,
-    pub series_updated: i32,
-    pub movies_scanned: i32,
-    pub movies_updated: i32,
+/// Add a movie file that isn't in the library yet, unless it's a sample or stub
+async fn quick_add_movie(
+    pool: &SqlitePool,
+    library_id: &str,
+    file: &Path,
+    result: &mut QuickScanResult,
+    metadata: Option<&MetadataService>,
+) -> Result<()> {
+    if samples::skipped_before(file) || samples::too_small(file).await {
+        return Ok(());
+    }
+    let path_str = file.to_str().unwrap_or_default();
+    let filename = file
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default();
+
+    let parsed = parse_movie_filename(filename);
+    if create_movie(pool, library_id, &parsed, path_str, metadata)
+        .await?
+        .is_some()
+    {
+        result.files_added += 1;
+        tracing::debug!("Added new movie: {}", filename);
+    }
+    Ok(())
 }
 
-/// Scan library for items missing metadata and fetch only for those
-/// This is more efficient than a full library scan when most items already have metadata
-pub async fn scan_missing_metadata(
+/// LIKE pattern matching every path under a folder
+fn like_prefix(dir: &str) -> String {
+    format!(
+        "{}/%",
+        dir.trim_end_matches('/')
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    )
+}
+
+/// Whether `target` lies inside `library_path`
+///
+/// Paths come from webhook payloads, so ".." is refused outright: starts_with
+/// compares components, and "/media/tv/../../etc" starts with "/media/tv".
+fn is_inside_library(target: &Path, library_path: &Path) -> bool {
+    !target.components().any(|c| c == Component::ParentDir) && target.starts_with(library_path)
+}
+
+/// The library holding a path (the most specific one): id, path and type
+async fn library_of(pool: &SqlitePool, target: &Path) -> Result<Option<(String, String, String)>> {
+    let libraries: Vec<(String, String, String)> =
+        sqlx::query_as("SELECT id, path, library_type FROM libraries")
+            .fetch_all(pool)
+            .await?;
+    Ok(libraries
+        .into_iter()
+        .filter(|(_, path, _)| is_inside_library(target, Path::new(path)))
+        .max_by_key(|(_, path, _)| path.len()))
+}
+
+/// Targeted scan of a single file or folder inside a library
+/// Used by import webhooks so new downloads show up without waiting for the periodic scan.
+/// Files are picked up from the containing folder, so sibling files imported in the
+/// same batch are added too; items under that folder whose files are gone are removed.
+pub async fn scan_path(
     pool: &SqlitePool,
-    library_id: &str,
+    target: &Path,
     cache_dir: PathBuf,
     anime_db_enabled: Option<bool>,
-) -> Result<MissingMetadataResult> {
-    let image_cache_dir = cache_dir.join("images");
-    let metadata_service = MetadataService::from_env(image_cache_dir, anime_db_enabled);
+    fetch_episode_metadata: bool,
+) -> Result<QuickScanResult> {
+    let Some((library_id, library_path, library_type)) = library_of(pool, target).await? else {
+        anyhow::bail!("Path is not inside any library: {}", target.display());
+    };
+    if !availability::check_library(pool, &library_id, &library_path).await? {
+        anyhow::bail!("Library of {} is offline", target.display());
+    }
 
-    // Preload anime database if enabled
-    if metadata_service.has_anime_db() {
-        let _ = metadata_service.preload_anime_db().await;
+    let _progress = progress::start_scan(&library_id, "Targeted");
+    let run = ScanRun::start(pool, &library_id, "Targeted").await;
+    let result = run_targeted_scan(
+        pool,
+        &library_id,
+        &library_type,
+        target,
+        cache_dir,
+        anime_db_enabled,
+        fetch_episode_metadata,
+    )
+    .await;
+    group_episodes(pool, &library_id, &result).await;
+    if let Some(run) = run {
+        run.finish(pool, &result).await;
     }
+    result
+}
 
-    let mut result = MissingMetadataResult::default();
+async fn run_targeted_scan(
+    pool: &SqlitePool,
+    library_id: &str,
+    library_type: &str,
+    target: &Path,
+    cache_dir: PathBuf,
+    anime_db_enabled: Option<bool>,
+    fetch_episode_metadata: bool,
+) -> Result<QuickScanResult> {
+    let mut result = QuickScanResult::default();
 
-    // Find series missing metadata (no overview AND no poster image)
-    let missing_series: Vec<(String, String, Option<i32>)> = sqlx::query_as(
-        r#"
-        SELECT m.id, m.name, m.year
-        FROM media_items m
-        WHERE m.library_id = ?
-          AND m.item_type = 'Series'
-          AND (
-            m.overview IS NULL 
-            OR m.overview = ''
-            OR NOT EXISTS (
-                SELECT 1 FROM images i WHERE i.item_id = m.id AND i.image_type = 'Primary'
-            )
-          )
-        ORDER BY m.name
-        "#,
-    )
-    .bind(library_id)
-    .fetch_all(pool)
-    .await?;
+    let scan_dir = if fs::metadata(target)
+        .await
+        .map(|m| m.is_dir())
+        .unwrap_or(false)
+    {
+        target.to_path_buf()
+    } else {
+        target
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|| target.to_path_buf())
+    };
+    let scan_dir_str = scan_dir.to_str().unwrap_or_default();
+
+    tracing::info!(
+        "Targeted scan of '{}' in library '{}'",
+        scan_dir.display(),
+        library_id
+    );
+
+    // Only consider items under the scanned folder
+    let like_pattern = like_prefix(scan_dir_str);
+    let existing_paths: Vec<(String, String)> = sqlx::query_as(
+        "SELECT id, path FROM media_items WHERE library_id = ? AND path LIKE ? ESCAPE '\\'",
+    )
+    .bind(library_id)
+    .bind(&like_pattern)
+    .fetch_all(pool)
+    .await?;
+
+    for (item_id, item_path) in &existing_paths {
+        let excluded = exclude::is_excluded(Path::new(item_path));
+        if excluded || !fs::try_exists(Path::new(item_path)).await.unwrap_or(true) {
+            tracing::info!(
+                "Removing {} file from database: {}",
+                if excluded { "excluded" } else { "missing" },
+                item_path
+            );
+            sqlx::query("DELETE FROM media_items WHERE id = ?")
+                .bind(item_id)
+                .execute(pool)
+                .await?;
+            events::publish(ServerEvent::ItemRemoved {
+                item_id: item_id.clone(),
+            });
+            result.files_removed += 1;
+        }
+    }
+
+    if !fs::try_exists(&scan_dir).await.unwrap_or(false) {
+        tracing::debug!("Scan target no longer exists: {}", scan_dir.display());
+        return Ok(result);
+    }
+
+    // Subtitles dropped beside a video that's already in the library only
+    // change the folder, so the watcher's targeted scans pick them up here
+    if library_type != "music" {
+        for (item_id, item_path) in &existing_paths {
+            if fs::try_exists(Path::new(item_path)).await.unwrap_or(false) {
+                store_external_streams(pool, item_id, item_path).await;
+            }
+        }
+    }
+
+    let existing_path_set: HashSet<String> = existing_paths.into_iter().map(|(_, p)| p).collect();
+
+    let settings = library_settings::get(library_id);
+    let fetch_episode_metadata = settings.episode_metadata(fetch_episode_metadata);
+    let image_cache_dir = cache_dir.join("images");
+    let metadata_service =
+        MetadataService::from_env(image_cache_dir, settings.anime_db(anime_db_enabled))
+            .with_language(settings.metadata_language.as_deref());
+
+    match library_type {
+        "tvshows" | "tvshow" => {
+            quick_scan_tv_library(
+                pool,
+                library_id,
+                &scan_dir,
+                &existing_path_set,
+                &mut result,
+                Some(&metadata_service),
+                fetch_episode_metadata,
+            )
+            .await?;
+        }
+        "movies" | "movie" => {
+            quick_scan_movie_library(
+                pool,
+                library_id,
+                &scan_dir,
+                &existing_path_set,
+                &mut result,
+                Some(&metadata_service),
+            )
+            .await?;
+            box_sets::remove_empty(pool).await?;
+        }
+        "music" => {
+            let root: String = sqlx::query_scalar("SELECT path FROM libraries WHERE id = ?")
+                .bind(library_id)
+                .fetch_one(pool)
+                .await?;
+            result.files_added += music::scan_music_folder(
+                pool,
+                library_id,
+                Path::new(&root),
+                &scan_dir,
+                &existing_path_set,
+                Some(metadata_service.image_cache_dir()),
+            )
+            .await?;
+            music::remove_empty(pool, library_id).await?;
+        }
+        _ => {
+            tracing::warn!("Unknown library type for targeted scan: {}", library_type);
+        }
+    }
+
+    metadata_service.unload_anime_db().await;
+    result.libraries_scanned = 1;
+
+    tracing::info!(
+        "Targeted scan of '{}' complete: {} added, {} removed",
+        scan_dir.display(),
+        result.files_added,
+        result.files_removed
+    );
+
+    events::publish(ServerEvent::ScanCompleted {
+        library_id: library_id.to_string(),
+        items_added: result.files_added,
+        items_removed: result.files_removed,
+    });
+
+    Ok(result)
+}
+
+/// Add and remove the items of changed paths inside a library (library watcher)
+///
+/// `added` are files and folders that were created, finished writing or moved
+/// in: a folder is scanned as a whole and a file on its own. Items at or under
+/// a path in `removed` are dropped once it's gone from disk. Paths outside the
+/// libraries are ignored; one scan is recorded per library touched.
+pub async fn scan_changes(
+    pool: &SqlitePool,
+    added: &[PathBuf],
+    removed: &[PathBuf],
+    cache_dir: PathBuf,
+    anime_db_enabled: Option<bool>,
+    fetch_episode_metadata: bool,
+) -> Result<QuickScanResult> {
+    // Added and removed paths of each library (id, path, type)
+    let mut by_library: std::collections::BTreeMap<_, (Vec<&Path>, Vec<&Path>)> =
+        std::collections::BTreeMap::new();
+    for (path, is_added) in added
+        .iter()
+        .map(|p| (p, true))
+        .chain(removed.iter().map(|p| (p, false)))
+    {
+        let Some(library) = library_of(pool, path).await? else {
+            tracing::debug!("Changed path is not inside any library: {}", path.display());
+            continue;
+        };
+        let entry = by_library.entry(library).or_default();
+        if is_added {
+            entry.0.push(path);
+        } else {
+            entry.1.push(path);
+        }
+    }
+
+    let mut result = QuickScanResult::default();
+    for ((library_id, library_path, library_type), (added, removed)) in by_library {
+        if !availability::check_library(pool, &library_id, &library_path).await? {
+            tracing::debug!("Ignoring changes in offline library '{}'", library_id);
+            continue;
+        }
+        let _progress = progress::start_scan(&library_id, "Targeted");
+        let run = ScanRun::start(pool, &library_id, "Targeted").await;
+        let outcome = run_change_scan(
+            pool,
+            &library_id,
+            &library_type,
+            &added,
+            &removed,
+            cache_dir.clone(),
+            anime_db_enabled,
+            fetch_episode_metadata,
+        )
+        .await;
+        group_episodes(pool, &library_id, &outcome).await;
+        if let Some(run) = run {
+            run.finish(pool, &outcome).await;
+        }
+        let outcome = outcome?;
+        result.files_added += outcome.files_added;
+        result.files_removed += outcome.files_removed;
+        result.libraries_scanned += 1;
+    }
+    Ok(result)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn run_change_scan(
+    pool: &SqlitePool,
+    library_id: &str,
+    library_type: &str,
+    added: &[&Path],
+    removed: &[&Path],
+    cache_dir: PathBuf,
+    anime_db_enabled: Option<bool>,
+    fetch_episode_metadata: bool,
+) -> Result<QuickScanResult> {
+    let mut result = QuickScanResult::default();
+
+    for path in removed {
+        let path_str = path.to_str().unwrap_or_default();
+        let items: Vec<(String, String)> = sqlx::query_as(
+            "SELECT id, path FROM media_items
+             WHERE library_id = ? AND (path = ? OR path LIKE ? ESCAPE '\\')",
+        )
+        .bind(library_id)
+        .bind(path_str)
+        .bind(like_prefix(path_str))
+        .fetch_all(pool)
+        .await?;
+        for (item_id, item_path) in &items {
+            if reconcile::remove_if_missing(pool, item_id, item_path).await? {
+                result.files_removed += 1;
+            }
+        }
+    }
+
+    // New videos, and folders whose other files changed (subtitles beside a video)
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut touched_dirs: Vec<&Path> = Vec::new();
+    for path in added {
+        let Ok(file_type) = fs::metadata(path).await.map(|m| m.file_type()) else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if library_type != "music" {
+                let mut visited = HashSet::new();
+                files.extend(collect_video_files(path, &mut visited).await?);
+            }
+            touched_dirs.push(path);
+        } else if library_type != "music" && is_video_file(path) {
+            if !exclude::is_excluded(path) {
+                files.push(path.to_path_buf());
+            }
+        } else if let Some(parent) = path.parent() {
+            touched_dirs.push(parent);
+        }
+    }
+    touched_dirs.sort();
+    touched_dirs.dedup();
+
+    let mut new_files = Vec::new();
+    for file in files {
+        let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM media_items WHERE path = ?")
+            .bind(file.to_str().unwrap_or_default())
+            .fetch_optional(pool)
+            .await?;
+        if exists.is_none() {
+            new_files.push(file);
+        }
+    }
+
+    if library_type != "music" {
+        // Subtitles dropped beside a video that's already in the library
+        for dir in &touched_dirs {
+            let dir_str = dir.to_str().unwrap_or_default();
+            let items: Vec<(String, String)> = sqlx::query_as(
+                "SELECT id, path FROM media_items WHERE library_id = ? AND path LIKE ? ESCAPE '\\'",
+            )
+            .bind(library_id)
+            .bind(like_prefix(dir_str))
+            .fetch_all(pool)
+            .await?;
+            for (item_id, item_path) in &items {
+                if Path::new(item_path).parent() == Some(*dir) {
+                    store_external_streams(pool, item_id, item_path).await;
+                }
+            }
+        }
+    }
+
+    let is_music = library_type == "music";
+    if !new_files.is_empty() || (is_music && !touched_dirs.is_empty()) {
+        let settings = library_settings::get(library_id);
+        let fetch_episode_metadata = settings.episode_metadata(fetch_episode_metadata);
+        let image_cache_dir = cache_dir.join("images");
+        let metadata_service =
+            MetadataService::from_env(image_cache_dir, settings.anime_db(anime_db_enabled))
+                .with_language(settings.metadata_language.as_deref());
+
+        match library_type {
+            "tvshows" | "tvshow" => {
+                let mut series_map = load_series_map(pool, library_id).await?;
+                for file in &new_files {
+                    quick_add_episode(
+                        pool,
+                        library_id,
+                        file,
+                        &mut series_map,
+                        &mut result,
+                        Some(&metadata_service),
+                        fetch_episode_metadata,
+                    )
+                    .await?;
+                }
+            }
+            "movies" | "movie" => {
+                for file in &new_files {
+                    quick_add_movie(pool, library_id, file, &mut result, Some(&metadata_service))
+                        .await?;
+                }
+            }
+            "music" => {
+                // Tracks are grouped into albums by folder, so the folder is read
+                let root: String = sqlx::query_scalar("SELECT path FROM libraries WHERE id = ?")
+                    .bind(library_id)
+                    .fetch_one(pool)
+                    .await?;
+                for dir in &touched_dirs {
+                    let existing: HashSet<String> = sqlx::query_scalar(
+                        "SELECT path FROM media_items WHERE library_id = ? AND path LIKE ? ESCAPE '\\'",
+                    )
+                    .bind(library_id)
+                    .bind(like_prefix(dir.to_str().unwrap_or_default()))
+                    .fetch_all(pool)
+                    .await?
+                    .into_iter()
+                    .collect();
+                    result.files_added += music::scan_music_folder(
+                        pool,
+                        library_id,
+                        Path::new(&root),
+                        dir,
+                        &existing,
+                        Some(metadata_service.image_cache_dir()),
+                    )
+                    .await?;
+                }
+            }
+            _ => {
+                tracing::warn!("Unknown library type for changed files: {}", library_type);
+            }
+        }
+
+        metadata_service.unload_anime_db().await;
+    }
+
+    match library_type {
+        "movies" | "movie" => {
+            box_sets::remove_empty(pool).await?;
+        }
+        "music" => {
+            music::remove_empty(pool, library_id).await?;
+        }
+        _ => {}
+    }
+
+    tracing::info!(
+        "Changed files in library '{}': {} added, {} removed",
+        library_id,
+        result.files_added,
+        result.files_removed
+    );
+    events::publish(ServerEvent::ScanCompleted {
+        library_id: library_id.to_string(),
+        items_added: result.files_added,
+        items_removed: result.files_removed,
+    });
+
+    Ok(result)
+}
+
+/// Result of scanning for missing metadata
+#[derive(Debug, Default)]
+pub struct MissingMetadataResult {
+    pub series_scanned: i32,
+    pub series_updated: i32,
+    pub movies_scanned: i32,
+    pub movies_updated: i32,
+}
+
+/// Scan library for items missing metadata and fetch only for those
+/// This is more efficient than a full library scan when most items already have metadata
+pub async fn scan_missing_metadata(
+    pool: &SqlitePool,
+    library_id: &str,
+    cache_dir: PathBuf,
+    anime_db_enabled: Option<bool>,
+) -> Result<MissingMetadataResult> {
+    let settings = library_settings::get(library_id);
+    let image_cache_dir = cache_dir.join("images");
+    let metadata_service =
+        MetadataService::from_env(image_cache_dir, settings.anime_db(anime_db_enabled))
+            .with_language(settings.metadata_language.as_deref());
+
+    // Preload anime database if enabled
+    if metadata_service.has_anime_db() {
+        let _ = metadata_service.preload_anime_db().await;
+    }
+
+    let mut result = MissingMetadataResult::default();
+
+    // Find series missing metadata (no overview AND no poster image)
+    let missing_series: Vec<(String, String, Option<i32>)> = sqlx::query_as(
+        r#"
+        SELECT m.id, m.name, m.year
+        FROM media_items m
+        WHERE m.library_id = ?
+          AND m.item_type = 'Series'
+          AND (
+            m.overview IS NULL 
+            OR m.overview = ''
+            OR NOT EXISTS (
+                SELECT 1 FROM images i WHERE i.item_id = m.id AND i.image_type = 'Primary'
+            )
+          )
+        ORDER BY m.name
+        "#,
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
 
     tracing::info!(
         "Found {} series missing metadata in library {}",
@@ -2618,6 +3845,8 @@ pub async fn scan_missing_metadata(
                 .bind(&movie_id)
                 .execute(pool)
                 .await?;
+                store_movie_collection(pool, &movie_id, &meta).await;
+                store_official_rating(pool, &movie_id, &meta).await;
 
                 // Queue images
                 if let Some(ref url) = meta.poster_url {
@@ -2626,6 +3855,9 @@ pub async fn scan_missing_metadata(
                 if let Some(ref url) = meta.backdrop_url {
                     let _ = crate::db::queue_image(pool, &movie_id, "Backdrop", url).await;
                 }
+                let _ =
+                    crate::db::queue_extra_backdrops(pool, &movie_id, &meta.extra_backdrop_urls)
+                        .await;
 
                 // Update genres
                 if let Some(ref genres) = meta.genres {
@@ -2658,10 +3890,147 @@ pub async fn scan_missing_metadata(
     Ok(result)
 }
 
-/// Update media info for items missing runtime_ticks
+/// Result of retrying unmatched series
+#[derive(Debug, Default)]
+pub struct UnmatchedRetryResult {
+    pub series_retried: i32,
+    pub series_matched: i32,
+}
+
+/// Base delay between unmatched series lookups
+const UNMATCHED_RETRY_BASE_DELAY_MS: u64 = 2000;
+
+/// Maximum random jitter added on top of the base delay
+const UNMATCHED_RETRY_JITTER_MS: u64 = 3000;
+
+/// Compute a jittered delay so retries don't hit providers in lockstep
+fn jittered_delay(base_ms: u64, jitter_ms: u64) -> std::time::Duration {
+    use rand_core::{OsRng, RngCore};
+
+    let jitter = if jitter_ms > 0 {
+        OsRng.next_u64() % (jitter_ms + 1)
+    } else {
+        0
+    };
+    std::time::Duration::from_millis(base_ms + jitter)
+}
+
+/// Re-attempt metadata lookup for series that previously failed to match
+///
+/// Series that match are updated and removed from tracking. Series that still
+/// fail get their attempt count bumped, so after 3 attempts they stop being retried.
+pub async fn retry_unmatched_series(
+    pool: &SqlitePool,
+    cache_dir: PathBuf,
+    anime_db_enabled: Option<bool>,
+) -> Result<UnmatchedRetryResult> {
+    let mut result = UnmatchedRetryResult::default();
+
+    let library_ids: Vec<(String,)> =
+        sqlx::query_as("SELECT DISTINCT library_id FROM unmatched_series WHERE attempt_count < 3")
+            .fetch_all(pool)
+            .await?;
+
+    if library_ids.is_empty() {
+        tracing::debug!("No unmatched series to retry");
+        return Ok(result);
+    }
+
+    let image_cache_dir = cache_dir.join("images");
+    let metadata_service = MetadataService::from_env(image_cache_dir, anime_db_enabled);
+
+    if metadata_service.has_anime_db() {
+        let _ = metadata_service.preload_anime_db().await;
+    }
+
+    for (library_id,) in library_ids {
+        let pending = get_unmatched_series_for_retry(pool, &library_id).await?;
+
+        for (series_id, folder_name, attempted_title, attempted_year) in pending {
+            if result.series_retried > 0 {
+                tokio::time::sleep(jittered_delay(
+                    UNMATCHED_RETRY_BASE_DELAY_MS,
+                    UNMATCHED_RETRY_JITTER_MS,
+                ))
+                .await;
+            }
+            result.series_retried += 1;
+
+            let (clean_name, folder_year) = extract_year_from_name(&folder_name);
+            let year = attempted_year.or(folder_year);
+            let is_anime = MetadataService::is_likely_anime(&folder_name);
+
+            let lookup = if is_anime {
+                metadata_service.get_anime_metadata(&clean_name, year).await
+            } else {
+                metadata_service
+                    .get_series_metadata(&clean_name, year)
+                    .await
+            };
+
+            let failure_reason = match lookup {
+                Ok(Some(meta)) => {
+                    tracing::info!(
+                        "Matched previously unmatched series '{}' via {}",
+                        folder_name,
+                        meta.provider
+                    );
+                    if let Err(e) = update_series_metadata(pool, &series_id, &meta).await {
+                        tracing::warn!("Failed to update series '{}': {}", folder_name, e);
+                        continue;
+                    }
+                    if let Err(e) = clear_unmatched_tracking(pool, &series_id).await {
+                        tracing::warn!("Failed to clear unmatched tracking: {}", e);
+                    }
+                    result.series_matched += 1;
+                    continue;
+                }
+                Ok(None) if metadata_service.has_provider_outage() => {
+                    // Don't burn a retry attempt on a provider outage
+                    tracing::debug!(
+                        "Skipping unmatched update for '{}' while a provider is unavailable",
+                        folder_name
+                    );
+                    continue;
+                }
+                Ok(None) => "No metadata match found".to_string(),
+                Err(e) => format!("Metadata lookup failed: {}", e),
+            };
+
+            if let Err(e) = mark_series_unmatched(
+                pool,
+                &library_id,
+                &series_id,
+                &folder_name,
+                &attempted_title,
+                year,
+                &failure_reason,
+            )
+            .await
+            {
+                tracing::warn!("Failed to update unmatched tracking: {}", e);
+            }
+        }
+    }
+
+    metadata_service.unload_anime_db().await;
+
+    tracing::info!(
+        "Unmatched series retry complete: {}/{} matched",
+        result.series_matched,
+        result.series_retried
+    );
+
+    Ok(result)
+}
+
+/// Update media info for items missing runtime_ticks or video resolution
 pub async fn update_missing_media_info(pool: &SqlitePool) -> Result<i32> {
     let items: Vec<(String, String)> = sqlx::query_as(
-        "SELECT id, path FROM media_items WHERE path IS NOT NULL AND runtime_ticks IS NULL",
+        r#"SELECT id, path FROM media_items
+           WHERE path IS NOT NULL AND stream_url IS NULL
+             AND (runtime_ticks IS NULL OR (item_type IN ('Movie', 'Episode') AND width IS NULL))
+             AND library_id NOT IN (SELECT id FROM libraries WHERE extract_media_info = 0)"#,
     )
     .fetch_all(pool)
     .await?;
@@ -2673,14 +4042,28 @@ pub async fn update_missing_media_info(pool: &SqlitePool) -> Result<i32> {
     for (id, path) in items {
         match mediainfo::extract_media_info_async(Path::new(&path)).await {
             Ok(info) => {
-                if let Some(ticks) = info.duration_ticks {
-                    sqlx::query("UPDATE media_items SET runtime_ticks = ? WHERE id = ?")
-                        .bind(ticks)
-                        .bind(&id)
-                        .execute(pool)
-                        .await?;
+                if info.duration_ticks.is_some() || info.width.is_some() {
+                    sqlx::query(
+                        r#"UPDATE media_items SET
+                           runtime_ticks = COALESCE(runtime_ticks, ?),
+                           width = COALESCE(width, ?),
+                           height = COALESCE(height, ?)
+                           WHERE id = ?"#,
+                    )
+                    .bind(info.duration_ticks)
+                    .bind(info.width.map(|w| w as i32))
+                    .bind(info.height.map(|h| h as i32))
+                    .bind(&id)
+                    .execute(pool)
+                    .await?;
                     updated += 1;
-                    tracing::debug!("Updated runtime for {}: {} ticks", path, ticks);
+                    tracing::debug!(
+                        "Updated media info for {}: {:?} ticks, {:?}x{:?}",
+                        path,
+                        info.duration_ticks,
+                        info.width,
+                        info.height
+                    );
                 }
             }
             Err(e) => {
@@ -2693,10 +4076,120 @@ pub async fn update_missing_media_info(pool: &SqlitePool) -> Result<i32> {
     Ok(updated)
 }
 
+/// What probing an item's file again found
+#[derive(Debug, Default)]
+pub struct RescanResult {
+    pub runtime_ticks: Option<i64>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    /// Bytes on disk now
+    pub file_size: u64,
+    /// Whether a new thumbnail was queued
+    pub thumbnail_queued: bool,
+}
+
+/// Probe one item's file again, after it was replaced in place (a better
+/// encode, a repaired download)
+///
+/// Runtime and resolution are overwritten rather than only filled in. A
+/// thumbnail generated from the old file is dropped and queued again; artwork
+/// from providers or from beside the media is kept.
+pub async fn rescan_item(
+    pool: &SqlitePool,
+    item_id: &str,
+    item_type: &str,
+    path: &Path,
+    image_cache_dir: &Path,
+) -> Result<RescanResult> {
+    let file_size = fs::metadata(path).await?.len();
+    let info = mediainfo::extract_media_info_async(path).await?;
+    let width = info.width.map(|w| w as i32);
+    let height = info.height.map(|h| h as i32);
+
+    sqlx::query(
+        "UPDATE media_items SET
+            runtime_ticks = COALESCE(?, runtime_ticks), width = ?, height = ?,
+            updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?",
+    )
+    .bind(info.duration_ticks)
+    .bind(width)
+    .bind(height)
+    .bind(item_id)
+    .execute(pool)
+    .await?;
+
+    let mut thumbnail_queued = false;
+    if matches!(item_type, "Episode" | "Movie") {
+        // Provider downloads record when they were fetched; generated
+        // thumbnails don't, and live at a fixed path in the cache
+        let primary: Option<(String, String, Option<String>)> = sqlx::query_as(
+            "SELECT id, path, downloaded_at FROM images WHERE item_id = ? AND image_type = 'Primary'",
+        )
+        .bind(item_id)
+        .fetch_optional(pool)
+        .await?;
+        let generated = image_cache_dir.join(item_id).join("Primary.jpg");
+        let regenerate = match primary {
+            None => true,
+            Some((image_id, image_path, None)) if Path::new(&image_path) == generated => {
+                sqlx::query("DELETE FROM images WHERE id = ?")
+                    .bind(image_id)
+                    .execute(pool)
+                    .await?;
+                true
+            }
+            Some(_) => false,
+        };
+        if regenerate {
+            crate::db::queue_thumbnail(pool, item_id, path.to_str().unwrap_or_default()).await?;
+            thumbnail_queued = true;
+        }
+    }
+
+    events::publish(ServerEvent::ItemUpdated {
+        item_id: item_id.to_string(),
+    });
+    Ok(RescanResult {
+        runtime_ticks: info.duration_ticks,
+        width,
+        height,
+        file_size,
+        thumbnail_queued,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_is_inside_library() {
+        let library = Path::new("/media/tv");
+        assert!(is_inside_library(
+            Path::new("/media/tv/Show/S01E01.mkv"),
+            library
+        ));
+        assert!(is_inside_library(Path::new("/media/tv"), library));
+        assert!(!is_inside_library(
+            Path::new("/media/tvshows/Show"),
+            library
+        ));
+        assert!(!is_inside_library(
+            Path::new("/media/movies/Film.mkv"),
+            library
+        ));
+        // Paths climbing out of the library are refused, even when they'd land back in it
+        assert!(!is_inside_library(
+            Path::new("/media/tv/../../etc/x"),
+            library
+        ));
+        assert!(!is_inside_library(
+            Path::new("/media/tv/Show/../Other"),
+            library
+        ));
+    }
+
     #[test]
     fn test_parse_anime_episode() {
         let filename =
@@ -2718,6 +4211,139 @@ mod tests {
         assert_eq!(parsed.episode, 5);
     }
 
+    #[test]
+    fn test_parse_multi_episode_files() {
+        let range = |name: &str| {
+            let parsed = parse_episode_filename(name).unwrap();
+            (parsed.season, parsed.episode, parsed.episode_end)
+        };
+
+        assert_eq!(range("Show S01E01E02.mkv"), (1, 1, Some(2)));
+        assert_eq!(range("Show S02E05-E07 1080p.mkv"), (2, 5, Some(7)));
+        assert_eq!(range("Show.S01E01-02.WEB.mkv"), (1, 1, Some(2)));
+        assert_eq!(range("[Group] Show - 01-02 [1080p].mkv"), (1, 1, Some(2)));
+        assert_eq!(
+            parse_episode_filename("Show S01E03E04.mkv")
+                .unwrap()
+                .default_name(),
+            "Episode 3-4"
+        );
+
+        // Resolutions, spaced dashes and backwards numbers aren't ranges
+        assert_eq!(range("Show S01E05-720p.mkv"), (1, 5, None));
+        assert_eq!(range("Show 2 - 05.mkv"), (1, 5, None));
+        assert_eq!(range("Show S01E05E03.mkv"), (1, 5, None));
+        assert_eq!(range("Show S01E05.mkv"), (1, 5, None));
+    }
+
+    #[test]
+    fn test_parse_season_folder() {
+        assert_eq!(parse_season_folder("Season 01"), Some(1));
+        assert_eq!(parse_season_folder("season 2"), Some(2));
+        assert_eq!(parse_season_folder("Season.3"), Some(3));
+        assert_eq!(parse_season_folder("S04"), Some(4));
+        assert_eq!(parse_season_folder("Season 2 [1080p]"), Some(2));
+        assert_eq!(parse_season_folder("Specials"), Some(0));
+        assert_eq!(parse_season_folder("Season 0"), Some(0));
+        assert_eq!(parse_season_folder("Some Show"), None);
+        assert_eq!(parse_season_folder("Seasons"), None);
+    }
+
+    #[test]
+    fn test_parse_episode_path_uses_season_folder() {
+        // Absolute-style name inside a season folder takes the folder's season
+        let parsed =
+            parse_episode_path(Path::new("/tv/Show/Season 2/[Group] Show - 05 [1080p].mkv"))
+                .unwrap();
+        assert_eq!(parsed.season, 2);
+        assert_eq!(parsed.episode, 5);
+        assert!(parsed.season_from_folder);
+
+        let parsed = parse_episode_path(Path::new("/tv/Show/Specials/Show - 01.mkv")).unwrap();
+        assert_eq!(parsed.season, 0);
+
+        // Explicit SxxEyy numbering wins over the folder
+        let parsed = parse_episode_path(Path::new("/tv/Show/Season 2/Show S03E01.mkv")).unwrap();
+        assert_eq!(parsed.season, 3);
+        assert!(!parsed.season_from_folder);
+
+        // No season folder keeps the season 1 default
+        let parsed = parse_episode_path(Path::new("/tv/Show/Show - 05.mkv")).unwrap();
+        assert_eq!(parsed.season, 1);
+        assert!(!parsed.season_from_folder);
+    }
+
+    #[test]
+    fn test_parse_specials() {
+        let parsed = parse_episode_filename("Show S00E03.mkv").unwrap();
+        assert_eq!((parsed.season, parsed.episode), (0, 3));
+
+        let parsed = parse_episode_filename("[Group] Show - SP02 [1080p].mkv").unwrap();
+        assert_eq!(
+            (parsed.show_name.as_str(), parsed.season, parsed.episode),
+            ("Show", 0, 2)
+        );
+        let parsed = parse_episode_filename("Show OVA 1.mkv").unwrap();
+        assert_eq!((parsed.season, parsed.episode), (0, 1));
+        let parsed = parse_episode_filename("Show - OVA.mkv").unwrap();
+        assert_eq!((parsed.season, parsed.episode), (0, 1));
+
+        // A special numbered in a season folder is still a special
+        let parsed = parse_episode_path(Path::new("/tv/Show/Season 1/Show - SP01.mkv")).unwrap();
+        assert_eq!(parsed.season, 0);
+        let parsed = parse_episode_path(Path::new("/tv/Show/OVA/Show - 02.mkv")).unwrap();
+        assert_eq!((parsed.season, parsed.episode), (0, 2));
+
+        // "Show - Specials" beside "Show" is its season 0, not skipped
+        assert!(!should_skip_folder("Show - Specials"));
+        assert_eq!(
+            infer_season_from_folder_name("Show - OVA"),
+            Some(("Show".to_string(), 0))
+        );
+        let parsed = parse_episode_path(Path::new("/tv/Show - Specials/Show - 01.mkv")).unwrap();
+        assert_eq!(parsed.season, 0);
+
+        // Regular episodes are unaffected
+        let parsed = parse_episode_filename("Spy x Family - 05.mkv").unwrap();
+        assert_eq!((parsed.season, parsed.episode), (1, 5));
+    }
+
+    #[test]
+    fn test_infer_season_from_folder_name() {
+        let infer = |name: &str| infer_season_from_folder_name(name);
+
+        assert_eq!(infer("Oshi no Ko S2"), Some(("Oshi no Ko".to_string(), 2)));
+        assert_eq!(
+            infer("Spy x Family Season 2 [1080p]"),
+            Some(("Spy x Family".to_string(), 2))
+        );
+        assert_eq!(
+            infer("Mushoku Tensei 2nd Season"),
+            Some(("Mushoku Tensei".to_string(), 2))
+        );
+        assert_eq!(
+            infer("Vinland Saga Second Season"),
+            Some(("Vinland Saga".to_string(), 2))
+        );
+        assert_eq!(infer("Show - Part 3"), Some(("Show".to_string(), 3)));
+        assert_eq!(
+            infer("Himouto.Umaru.chan.S02.1080p.BluRay.x265-smol"),
+            Some(("Himouto.Umaru.chan".to_string(), 2))
+        );
+
+        // Multi-season packs, bare season folders and plain names
+        assert_eq!(infer("Scissor.Seven.S01-S03.1080p.NF.WEB-DL"), None);
+        assert_eq!(infer("Season 2"), None);
+        assert_eq!(infer("Mob Psycho 100"), None);
+        assert_eq!(infer("Attack on Titan Final Season"), None);
+
+        // Files directly inside such a folder get its season
+        let parsed =
+            parse_episode_path(Path::new("/anime/Oshi no Ko S2/Oshi no Ko - 03.mkv")).unwrap();
+        assert_eq!(parsed.season, 2);
+        assert_eq!(parsed.episode, 3);
+    }
+
     #[test]
     fn test_parse_movie() {
         let parsed = parse_movie_filename("The Matrix (1999).mkv");
@@ -2725,6 +4351,30 @@ mod tests {
         assert_eq!(parsed.year, Some(1999));
     }
 
+    #[test]
+    fn test_parse_language_hints() {
+        let hints = parse_language_hints("[Group] Show Name - 01 [1080p][Dual Audio]");
+        assert!(hints.is_dual_audio);
+        assert!(!hints.is_dubbed);
+
+        let hints = parse_language_hints("Show.Name.S01E01.ENG.DUB.1080p");
+        assert!(hints.is_dubbed);
+        assert_eq!(hints.languages, vec!["eng"]);
+
+        let hints = parse_language_hints("Movie (2020) [Multi-Audio] Japanese Audio");
+        assert!(hints.is_dual_audio);
+        assert_eq!(hints.languages, vec!["jpn"]);
+
+        assert!(parse_language_hints("Breaking Bad S01E01").is_empty());
+        assert!(parse_language_hints("Dubai Heat (2019)").is_empty());
+
+        let hints = language_hints_for_path(Path::new(
+            "/media/anime/Show [English Dub]/Season 01/Show - 01.mkv",
+        ));
+        assert!(hints.is_dubbed);
+        assert_eq!(hints.languages, vec!["eng"]);
+    }
+
     #[test]
     fn test_extract_year_from_name() {
         // Standard case
@@ -2834,6 +4484,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_jittered_delay_bounds() {
+        for _ in 0..100 {
+            let delay = jittered_delay(2000, 3000);
+            assert!(delay >= std::time::Duration::from_millis(2000));
+            assert!(delay <= std::time::Duration::from_millis(5000));
+        }
+        assert_eq!(
+            jittered_delay(500, 0),
+            std::time::Duration::from_millis(500)
+        );
+    }
+
     #[test]
     fn test_folder_name_parsing() {
         // Test clean_folder_name and extract_year_from_name with real-world examples
@@ -0,0 +1,574 @@
+// Music libraries
+//
+// Audio files become Audio items under a MusicAlbum, which sits under the
+// album artist's MusicArtist item. Tags come from ffprobe; files without them
+// fall back to the usual layout of `Artist/Album (Year)/01 - Title.ext`.
+// Artist and album IDs are derived from their names, so rescans and other
+// tracks of the same album find the same items without a lookup, and a track's
+// artists can be linked to their items by name alone.
+//
+// Track artists (features, compilations) get MusicArtist items too, but only
+// album artists have albums under them. Albums and artists are removed once
+// their last track is gone.
+
+use anyhow::Result;
+use futures::{stream, StreamExt};
+use regex::Regex;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use tokio::fs;
+use uuid::Uuid;
+
+use super::{exclude, publish_item_added, should_ignore_path, sort_name, SCAN_CONCURRENCY};
+use crate::api::filters::{get_or_create_genre, link_item_genre};
+use crate::events::{self, ServerEvent};
+use crate::services::{mediainfo, progress};
+
+pub const AUDIO_EXTENSIONS: &[&str] = &[
+    "mp3", "flac", "m4a", "aac", "ogg", "oga", "opus", "wav", "wma", "aif", "aiff", "ape", "wv",
+    "mka",
+];
+
+/// Separator of the names in `media_items.artists`
+pub const ARTIST_SEPARATOR: char = ';';
+
+/// Cover images looked for in an album's folder, in order of preference
+const COVER_NAMES: &[&str] = &["cover", "folder", "front", "album"];
+const COVER_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+const UNKNOWN_ALBUM: &str = "Unknown Album";
+
+/// "01 - Title", "01. Title", "1-03 Title" (disc 1, track 3)
+static RE_TRACK_FILENAME: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:(\d{1,2})[-.])?(\d{1,3})(?:\s*[-._]\s*|\s+)(.+)$").unwrap());
+
+/// A year in an album folder name: "Album (2020)" or "2020 - Album"
+static RE_FOLDER_YEAR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:\((\d{4})\)|(\d{4}))\s*-?\s*|\s*[(\[](\d{4})[)\]]\s*$").unwrap()
+});
+
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// ID of an artist's item in a library
+pub fn artist_id(library_id: &str, name: &str) -> String {
+    let key = format!("music-artist:{}:{}", library_id, name.to_lowercase());
+    Uuid::new_v3(&Uuid::NAMESPACE_URL, key.as_bytes()).to_string()
+}
+
+/// ID of an album's item in a library
+pub fn album_id(library_id: &str, album_artist: &str, album: &str) -> String {
+    let key = format!(
+        "music-album:{}:{}:{}",
+        library_id,
+        album_artist.to_lowercase(),
+        album.to_lowercase()
+    );
+    Uuid::new_v3(&Uuid::NAMESPACE_URL, key.as_bytes()).to_string()
+}
+
+/// What a track is, from its tags or else its path
+#[derive(Debug, Default, PartialEq)]
+pub struct TrackTags {
+    pub title: String,
+    pub artists: Vec<String>,
+    pub album_artist: String,
+    pub album: String,
+    pub track: Option<i32>,
+    pub disc: Option<i32>,
+    pub year: Option<i32>,
+    pub genres: Vec<String>,
+}
+
+/// First non-empty tag of several names for the same thing
+fn tag<'a>(tags: &'a HashMap<String, String>, keys: &[&str]) -> Option<&'a str> {
+    keys.iter()
+        .filter_map(|key| tags.get(*key))
+        .map(|value| value.trim())
+        .find(|value| !value.is_empty())
+}
+
+/// "3" or "3/12"
+fn tag_number(value: &str) -> Option<i32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+/// Names of a multi-valued tag, joined with ';' (or NUL in some ID3 frames),
+/// and of `media_items.artists`
+pub fn split_names(value: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in value.split([ARTIST_SEPARATOR, '\0']).map(str::trim) {
+        if !name.is_empty() && !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// Folder name without its year, and the year
+fn split_folder_year(name: &str) -> (String, Option<i32>) {
+    match RE_FOLDER_YEAR.captures(name) {
+        Some(caps) => {
+            let year = caps
+                .iter()
+                .skip(1)
+                .flatten()
+                .find_map(|m| m.as_str().parse().ok());
+            let rest = RE_FOLDER_YEAR.replace(name, "").trim().to_string();
+            if rest.is_empty() {
+                (name.to_string(), year)
+            } else {
+                (rest, year)
+            }
+        }
+        None => (name.to_string(), None),
+    }
+}
+
+/// Tags of the track at `path` inside the library folder `root`
+pub fn track_tags(path: &Path, root: &Path, tags: &HashMap<String, String>) -> TrackTags {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    let from_name = RE_TRACK_FILENAME.captures(stem);
+    let name_number = |group: usize| {
+        from_name
+            .as_ref()
+            .and_then(|caps| caps.get(group))
+            .and_then(|m| m.as_str().parse::<i32>().ok())
+    };
+
+    // Folders below the library root: [.., artist, album]
+    let folders: Vec<&str> = path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(root).ok())
+        .map(|dir| dir.iter().filter_map(|c| c.to_str()).collect())
+        .unwrap_or_default();
+    let (folder_album, folder_year) = match folders.last() {
+        Some(name) => {
+            let (album, year) = split_folder_year(name);
+            (Some(album), year)
+        }
+        None => (None, None),
+    };
+    let folder_artist = folders.len().checked_sub(2).map(|i| folders[i]);
+
+    let title = tag(tags, &["title"])
+        .map(str::to_string)
+        .or_else(|| from_name.as_ref().map(|caps| caps[3].trim().to_string()))
+        .unwrap_or_else(|| stem.to_string());
+
+    let mut artists = tag(tags, &["artists"])
+        .or_else(|| tag(tags, &["artist"]))
+        .map(split_names)
+        .unwrap_or_default();
+    let album_artist = tag(tags, &["album_artist", "albumartist", "album artist"])
+        .and_then(|value| split_names(value).into_iter().next())
+        .or_else(|| artists.first().cloned())
+        .or_else(|| folder_artist.map(str::to_string))
+        .unwrap_or_else(|| UNKNOWN_ARTIST.to_string());
+    if artists.is_empty() {
+        artists.push(album_artist.clone());
+    }
+
+    TrackTags {
+        title,
+        artists,
+        album_artist,
+        album: tag(tags, &["album"])
+            .map(str::to_string)
+            .or(folder_album)
+            .unwrap_or_else(|| UNKNOWN_ALBUM.to_string()),
+        track: tag(tags, &["track", "tracknumber"])
+            .and_then(tag_number)
+            .or_else(|| name_number(2)),
+        disc: tag(tags, &["disc", "discnumber"])
+            .and_then(tag_number)
+            .or_else(|| name_number(1)),
+        year: tag(tags, &["date", "year", "originaldate"])
+            .and_then(|date| date.get(..4))
+            .and_then(|year| year.parse().ok())
+            .or(folder_year),
+        genres: tag(tags, &["genre"]).map(split_names).unwrap_or_default(),
+    }
+}
+
+/// Recursively collect audio files, with symlink loop protection
+async fn collect_audio_files(path: &Path, visited: &mut HashSet<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    let canonical = match fs::canonicalize(path).await {
+        Ok(p) => p,
+        Err(e) => {
+            tracing::warn!("Cannot canonicalize path {:?}: {}", path, e);
+            return Ok(files);
+        }
+    };
+    if !visited.insert(canonical) {
+        tracing::warn!("Symlink loop detected, skipping: {:?}", path);
+        return Ok(files);
+    }
+
+    let mut entries = match fs::read_dir(path).await {
+        Ok(e) => e,
+        Err(e) => {
+            tracing::warn!("Cannot read directory {:?}: {}", path, e);
+            return Ok(files);
+        }
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let entry_path = entry.path();
+        if exclude::is_excluded(&entry_path) {
+            continue;
+        }
+        if entry_path.is_file() && is_audio_file(&entry_path) {
+            files.push(entry_path);
+        } else if entry_path.is_dir() && !should_ignore_path(&entry_path).await {
+            files.append(&mut Box::pin(collect_audio_files(&entry_path, visited)).await?);
+        }
+    }
+    Ok(files)
+}
+
+/// Create an artist's item if it doesn't exist yet
+async fn ensure_artist(pool: &SqlitePool, library_id: &str, name: &str) -> Result<String> {
+    let id = artist_id(library_id, name);
+    let inserted = sqlx::query(
+        "INSERT OR IGNORE INTO media_items (id, library_id, item_type, name, sort_name)
+         VALUES (?, ?, 'MusicArtist', ?, ?)",
+    )
+    .bind(&id)
+    .bind(library_id)
+    .bind(name)
+    .bind(sort_name::sort_name(name))
+    .execute(pool)
+    .await?;
+    if inserted.rows_affected() > 0 {
+        publish_item_added(&id, "MusicArtist", library_id);
+    }
+    Ok(id)
+}
+
+/// Create an album's item if it doesn't exist yet; returns its ID and whether it's new
+async fn ensure_album(
+    pool: &SqlitePool,
+    library_id: &str,
+    tags: &TrackTags,
+) -> Result<(String, bool)> {
+    let artist_id = ensure_artist(pool, library_id, &tags.album_artist).await?;
+    let id = album_id(library_id, &tags.album_artist, &tags.album);
+    let inserted = sqlx::query(
+        "INSERT OR IGNORE INTO media_items
+            (id, library_id, parent_id, item_type, name, sort_name, year, album_artist, artists)
+         VALUES (?, ?, ?, 'MusicAlbum', ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(library_id)
+    .bind(&artist_id)
+    .bind(&tags.album)
+    .bind(sort_name::sort_name(&tags.album))
+    .bind(tags.year)
+    .bind(&tags.album_artist)
+    .bind(&tags.album_artist)
+    .execute(pool)
+    .await?;
+    let is_new = inserted.rows_affected() > 0;
+    if is_new {
+        publish_item_added(&id, "MusicAlbum", library_id);
+    } else if tags.year.is_some() {
+        sqlx::query("UPDATE media_items SET year = ? WHERE id = ? AND year IS NULL")
+            .bind(tags.year)
+            .bind(&id)
+            .execute(pool)
+            .await?;
+    }
+    Ok((id, is_new))
+}
+
+/// Insert a track, or update the one already stored for its file; returns
+/// whether it was inserted
+async fn save_track(
+    pool: &SqlitePool,
+    library_id: &str,
+    album_id: &str,
+    path: &str,
+    tags: &TrackTags,
+    runtime_ticks: Option<i64>,
+) -> Result<bool> {
+    let existing: Option<String> =
+        sqlx::query_scalar("SELECT id FROM media_items WHERE library_id = ? AND path = ?")
+            .bind(library_id)
+            .bind(path)
+            .fetch_optional(pool)
+            .await?;
+    let is_new = existing.is_none();
+    let id = existing.unwrap_or_else(|| Uuid::new_v4().to_string());
+    let artists = tags.artists.join(&ARTIST_SEPARATOR.to_string());
+
+    sqlx::query(
+        "INSERT INTO media_items
+            (id, library_id, parent_id, item_type, name, sort_name, path, year, runtime_ticks,
+             index_number, parent_index_number, album, album_artist, artists)
+         VALUES (?, ?, ?, 'Audio', ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET
+            parent_id = excluded.parent_id, name = excluded.name, sort_name = excluded.sort_name,
+            year = excluded.year, runtime_ticks = COALESCE(excluded.runtime_ticks, runtime_ticks),
+            index_number = excluded.index_number, parent_index_number = excluded.parent_index_number,
+            album = excluded.album, album_artist = excluded.album_artist, artists = excluded.artists,
+            updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(&id)
+    .bind(library_id)
+    .bind(album_id)
+    .bind(&tags.title)
+    .bind(sort_name::sort_name(&tags.title))
+    .bind(path)
+    .bind(tags.year)
+    .bind(runtime_ticks)
+    .bind(tags.track)
+    .bind(tags.disc)
+    .bind(&tags.album)
+    .bind(&tags.album_artist)
+    .bind(&artists)
+    .execute(pool)
+    .await?;
+
+    for artist in &tags.artists {
+        ensure_artist(pool, library_id, artist).await?;
+    }
+    for genre in &tags.genres {
+        let genre_id = get_or_create_genre(pool, genre).await?;
+        link_item_genre(pool, &id, &genre_id).await?;
+        link_item_genre(pool, album_id, &genre_id).await?;
+    }
+
+    if is_new {
+        publish_item_added(&id, "Audio", library_id);
+    }
+    Ok(is_new)
+}
+
+/// Cover image in an album's folder (cover.jpg, folder.png, ...)
+async fn find_folder_cover(album_dir: &Path) -> Option<PathBuf> {
+    let mut entries = fs::read_dir(album_dir).await.ok()?;
+    let mut images: Vec<(usize, PathBuf)> = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let (Some(stem), Some(ext)) = (
+            path.file_stem().and_then(|s| s.to_str()),
+            path.extension().and_then(|e| e.to_str()),
+        ) else {
+            continue;
+        };
+        if !COVER_EXTENSIONS.contains(&ext.to_lowercase().as_str()) {
+            continue;
+        }
+        if let Some(rank) = COVER_NAMES
+            .iter()
+            .position(|name| stem.eq_ignore_ascii_case(name))
+        {
+            images.push((rank, path));
+        }
+    }
+    images.into_iter().min().map(|(_, path)| path)
+}
+
+/// Give a new album a Primary image: the folder's cover, or else the art
+/// embedded in one of its tracks
+async fn add_album_art(
+    pool: &SqlitePool,
+    album_id: &str,
+    track_path: &Path,
+    image_cache_dir: Option<&Path>,
+) -> Result<()> {
+    let has_image: Option<i32> = sqlx::query_scalar(
+        "SELECT 1 FROM images WHERE item_id = ? AND image_type = 'Primary' LIMIT 1",
+    )
+    .bind(album_id)
+    .fetch_optional(pool)
+    .await?;
+    if has_image.is_some() {
+        return Ok(());
+    }
+
+    let cover = match track_path.parent() {
+        Some(dir) => find_folder_cover(dir).await,
+        None => None,
+    };
+    let cover = match (cover, image_cache_dir) {
+        (Some(cover), _) => cover,
+        (None, Some(cache_dir)) => {
+            let output = cache_dir.join(album_id).join("Primary.jpg");
+            if let Err(e) = mediainfo::extract_cover_art(track_path, &output).await {
+                tracing::debug!("No cover art for album {}: {}", album_id, e);
+                return Ok(());
+            }
+            output
+        }
+        (None, None) => return Ok(()),
+    };
+
+    sqlx::query(
+        "INSERT OR REPLACE INTO images (id, item_id, image_type, path) VALUES (?, ?, 'Primary', ?)",
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(album_id)
+    .bind(cover.to_string_lossy().as_ref())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Add the tracks under `folder` of the music library at `root`, with their
+/// albums and artists
+///
+/// Files in `existing` are skipped; other files already in the database get
+/// their tags read again. Returns the number of tracks added.
+pub async fn scan_music_folder(
+    pool: &SqlitePool,
+    library_id: &str,
+    root: &Path,
+    folder: &Path,
+    existing: &HashSet<String>,
+    image_cache_dir: Option<&Path>,
+) -> Result<i32> {
+    let mut visited = HashSet::new();
+    let files: Vec<PathBuf> = collect_audio_files(folder, &mut visited)
+        .await?
+        .into_iter()
+        .filter(|path| !existing.contains(path.to_str().unwrap_or_default()))
+        .collect();
+    if files.is_empty() {
+        return Ok(0);
+    }
+    tracing::info!("Reading tags of {} audio files", files.len());
+    progress::add_discovered(library_id, files.len());
+
+    let mut tracks: Vec<(PathBuf, TrackTags, Option<i64>)> = stream::iter(files)
+        .map(|path| async move {
+            let info = match mediainfo::extract_media_info_async(&path).await {
+                Ok(info) => info,
+                Err(e) => {
+                    tracing::debug!("Failed to read tags of {:?}: {}", path, e);
+                    mediainfo::MediaInfo::default()
+                }
+            };
+            let tags = track_tags(&path, root, &info.tags);
+            (path, tags, info.duration_ticks)
+        })
+        .buffer_unordered(SCAN_CONCURRENCY)
+        .collect()
+        .await;
+    // Albums are created in path order, so their first track's folder and art are used
+    tracks.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut added = 0;
+    let mut new_albums = HashSet::new();
+    for (path, tags, runtime_ticks) in &tracks {
+        progress::add_processed(library_id);
+        let (album_id, is_new) = ensure_album(pool, library_id, tags).await?;
+        if is_new {
+            new_albums.insert(album_id.clone());
+        }
+        let path_str = path.to_str().unwrap_or_default();
+        if save_track(pool, library_id, &album_id, path_str, tags, *runtime_ticks).await? {
+            added += 1;
+        }
+        if new_albums.remove(&album_id) {
+            if let Err(e) = add_album_art(pool, &album_id, path, image_cache_dir).await {
+                tracing::warn!("Failed to add album art for {:?}: {}", path, e);
+            }
+        }
+    }
+    Ok(added)
+}
+
+/// Remove albums without tracks and artists without albums or tracks
+pub async fn remove_empty(pool: &SqlitePool, library_id: &str) -> Result<usize> {
+    let mut removed: Vec<String> = sqlx::query_scalar(
+        "DELETE FROM media_items
+         WHERE library_id = ? AND item_type = 'MusicAlbum'
+           AND NOT EXISTS (SELECT 1 FROM media_items t WHERE t.parent_id = media_items.id)
+         RETURNING id",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+    removed.extend(
+        sqlx::query_scalar::<_, String>(
+            "DELETE FROM media_items
+             WHERE library_id = ?1 AND item_type = 'MusicArtist'
+               AND NOT EXISTS (SELECT 1 FROM media_items a WHERE a.parent_id = media_items.id)
+               AND NOT EXISTS (
+                   SELECT 1 FROM media_items t
+                   WHERE t.library_id = ?1 AND t.item_type = 'Audio'
+                     AND instr(LOWER(';' || t.artists || ';'), LOWER(';' || media_items.name || ';')) > 0)
+             RETURNING id",
+        )
+        .bind(library_id)
+        .fetch_all(pool)
+        .await?,
+    );
+    for item_id in &removed {
+        events::publish(ServerEvent::ItemRemoved {
+            item_id: item_id.clone(),
+        });
+    }
+    Ok(removed.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_track_tags_from_tags_and_path() {
+        let root = Path::new("/music");
+
+        let mut tags = HashMap::new();
+        tags.insert("title".to_string(), "Song".to_string());
+        tags.insert("artist".to_string(), "Singer; Guest".to_string());
+        tags.insert("album".to_string(), "Record".to_string());
+        tags.insert("track".to_string(), "3/12".to_string());
+        tags.insert("date".to_string(), "2019-05-01".to_string());
+        let track = track_tags(Path::new("/music/x/y/03 - Other.flac"), root, &tags);
+        assert_eq!(track.title, "Song");
+        assert_eq!(track.artists, ["Singer", "Guest"]);
+        assert_eq!(track.album_artist, "Singer");
+        assert_eq!(
+            (track.track, track.disc, track.year),
+            (Some(3), None, Some(2019))
+        );
+
+        // No tags: Artist/Album (Year)/Disc-Track Title
+        let path = Path::new("/music/Band/Debut (2001)/1-04 Opener.mp3");
+        let track = track_tags(path, root, &HashMap::new());
+        assert_eq!(track.title, "Opener");
+        assert_eq!(track.album, "Debut");
+        assert_eq!(track.album_artist, "Band");
+        assert_eq!(track.artists, ["Band"]);
+        assert_eq!(
+            (track.track, track.disc, track.year),
+            (Some(4), Some(1), Some(2001))
+        );
+
+        // Loose files in the library root
+        let track = track_tags(Path::new("/music/Untitled.ogg"), root, &HashMap::new());
+        assert_eq!(track.title, "Untitled");
+        assert_eq!(track.album_artist, UNKNOWN_ARTIST);
+        assert_eq!(track.album, UNKNOWN_ALBUM);
+
+        assert_eq!(artist_id("lib", "Band"), artist_id("lib", "BAND"));
+        assert_ne!(
+            album_id("lib", "Band", "Debut"),
+            album_id("lib", "Other", "Debut")
+        );
+    }
+}
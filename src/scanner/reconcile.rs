@@ -0,0 +1,187 @@
+// Library refresh reconciliation
+//
+// A refresh used to delete every item of a library and scan it from scratch,
+// which took everyone's played state, favorites and collection entries for
+// those items with it. It now brings the stored items in line with the files
+// on disk instead: items whose files are gone (or excluded) are removed, items
+// whose files changed size or modification time since they were last seen are
+// probed again in place, and the full scan that follows adds new files while
+// keeping the items it already has. An item is never deleted while its file
+// still exists. The size and time are stored on media_items; items from before
+// they were recorded get them on their first refresh.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tokio::fs;
+
+use super::{exclude, library_settings};
+use crate::events::{self, ServerEvent};
+
+/// What a refresh changed before scanning for new files
+#[derive(Debug, Default)]
+pub struct ReconcileResult {
+    pub removed: i32,
+    pub updated: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileState {
+    /// Never recorded (items from before refreshes recorded files)
+    Unrecorded,
+    Unchanged,
+    Changed,
+}
+
+/// Compare a file's stored size and modification time with the ones on disk
+fn file_state(stored: (Option<i64>, Option<i64>), current: (i64, i64)) -> FileState {
+    match stored {
+        (Some(size), Some(modified)) if (size, modified) == current => FileState::Unchanged,
+        (Some(_), Some(_)) => FileState::Changed,
+        _ => FileState::Unrecorded,
+    }
+}
+
+/// Remove an item whose file is missing or excluded; false if the file is still there
+pub async fn remove_if_missing(pool: &SqlitePool, item_id: &str, item_path: &str) -> Result<bool> {
+    let excluded = exclude::is_excluded(Path::new(item_path));
+    if !excluded && fs::try_exists(Path::new(item_path)).await.unwrap_or(true) {
+        return Ok(false);
+    }
+    tracing::info!(
+        "Removing {} file from database: {}",
+        if excluded { "excluded" } else { "missing" },
+        item_path
+    );
+    sqlx::query("DELETE FROM media_items WHERE id = ?")
+        .bind(item_id)
+        .execute(pool)
+        .await?;
+    events::publish(ServerEvent::ItemRemoved {
+        item_id: item_id.to_string(),
+    });
+    Ok(true)
+}
+
+#[derive(sqlx::FromRow)]
+struct FileItem {
+    id: String,
+    item_type: String,
+    path: String,
+    stream_url: Option<String>,
+    file_size: Option<i64>,
+    file_modified: Option<i64>,
+}
+
+/// Remove the items of missing files and probe changed files again
+pub async fn reconcile_library(
+    pool: &SqlitePool,
+    library_id: &str,
+    image_cache_dir: &Path,
+) -> Result<ReconcileResult> {
+    let mut result = ReconcileResult::default();
+    let items: Vec<FileItem> = sqlx::query_as(
+        "SELECT id, item_type, path, stream_url, file_size, file_modified
+         FROM media_items WHERE library_id = ? AND path IS NOT NULL",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+    let probes = library_settings::probes_media(library_id);
+
+    for (i, item) in items.iter().enumerate() {
+        if i.is_multiple_of(50) {
+            tokio::task::yield_now().await;
+        }
+        if remove_if_missing(pool, &item.id, &item.path).await? {
+            result.removed += 1;
+            continue;
+        }
+
+        let path = Path::new(&item.path);
+        let Ok(metadata) = fs::metadata(path).await else {
+            continue;
+        };
+        if !metadata.is_file() {
+            continue;
+        }
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs() as i64);
+        let current = (metadata.len() as i64, modified);
+
+        match file_state((item.file_size, item.file_modified), current) {
+            FileState::Unchanged => continue,
+            FileState::Unrecorded => {}
+            FileState::Changed => {
+                let probe = probes
+                    && item.stream_url.is_none()
+                    && matches!(item.item_type.as_str(), "Episode" | "Movie" | "Audio");
+                if probe {
+                    if let Err(e) =
+                        super::rescan_item(pool, &item.id, &item.item_type, path, image_cache_dir)
+                            .await
+                    {
+                        tracing::warn!("Failed to probe changed file {}: {:#}", item.path, e);
+                    }
+                }
+                tracing::info!("File changed since the last refresh: {}", item.path);
+                result.updated += 1;
+            }
+        }
+
+        sqlx::query("UPDATE media_items SET file_size = ?, file_modified = ? WHERE id = ?")
+            .bind(current.0)
+            .bind(current.1)
+            .bind(&item.id)
+            .execute(pool)
+            .await?;
+    }
+
+    // Series have no file of their own; drop the ones whose episodes all went
+    if result.removed > 0 {
+        let removed: Vec<String> = sqlx::query_scalar(
+            "DELETE FROM media_items
+             WHERE library_id = ? AND item_type = 'Series'
+               AND NOT EXISTS (SELECT 1 FROM media_items e
+                               WHERE e.parent_id = media_items.id AND e.item_type = 'Episode')
+             RETURNING id",
+        )
+        .bind(library_id)
+        .fetch_all(pool)
+        .await?;
+        result.removed += removed.len() as i32;
+        for item_id in removed {
+            events::publish(ServerEvent::ItemRemoved { item_id });
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_state_compares_size_and_time() {
+        let current = (1_000_000, 1_700_000_000);
+        assert_eq!(file_state((None, None), current), FileState::Unrecorded);
+        assert_eq!(
+            file_state((Some(1_000_000), Some(1_700_000_000)), current),
+            FileState::Unchanged
+        );
+        // A file replaced in place, whether or not the copy kept the old time
+        assert_eq!(
+            file_state((Some(900_000), Some(1_700_000_000)), current),
+            FileState::Changed
+        );
+        assert_eq!(
+            file_state((Some(1_000_000), Some(1_600_000_000)), current),
+            FileState::Changed
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// Sample and stub files
+//
+// Release folders often carry a 30-second sample next to the real file, and
+// failed or placeholder downloads leave zero-byte stubs. Both parse like any
+// episode or movie and would become broken items with no runtime, so video
+// files under `scanner.min_file_size_mb` are skipped when the folder is read,
+// and those shorter than `scanner.min_duration_seconds` once ffprobe has read
+// their duration. Files whose duration can't be read are kept, and `.strm`
+// files (a URL, not media) are never measured. Short files are remembered
+// until restart, so quick scans don't run ffprobe on them every time.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex, OnceLock};
+use tokio::fs;
+
+use crate::time::Ticks;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Thresholds {
+    min_file_size_bytes: u64,
+    min_duration_ticks: i64,
+}
+
+static THRESHOLDS: OnceLock<Thresholds> = OnceLock::new();
+
+/// Files skipped for being too short
+static SHORT_FILES: LazyLock<Mutex<HashSet<PathBuf>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Set the minimum size and duration of video files (0 disables a check)
+pub fn set_thresholds(min_file_size_mb: u64, min_duration_seconds: u64) {
+    let _ = THRESHOLDS.set(Thresholds {
+        min_file_size_bytes: min_file_size_mb * 1024 * 1024,
+        min_duration_ticks: Ticks::from_seconds(min_duration_seconds as i64).0,
+    });
+}
+
+fn thresholds() -> Thresholds {
+    THRESHOLDS.get().copied().unwrap_or_default()
+}
+
+fn is_strm(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("strm"))
+}
+
+/// Whether a video file is too small to be anything but a stub or sample
+pub async fn too_small(path: &Path) -> bool {
+    let min = thresholds().min_file_size_bytes;
+    if min == 0 || is_strm(path) {
+        return false;
+    }
+    match fs::metadata(path).await {
+        Ok(meta) if meta.len() < min => {
+            tracing::debug!(
+                "Skipping {}: {} bytes is below the minimum file size",
+                path.display(),
+                meta.len()
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Whether a probed video file is too short to be anything but a sample
+///
+/// Unknown durations pass, since ffprobe may just be missing.
+pub fn too_short(path: &Path, runtime_ticks: Option<i64>) -> bool {
+    let min = thresholds().min_duration_ticks;
+    match runtime_ticks {
+        Some(ticks) if min > 0 && ticks < min && !is_strm(path) => {
+            SHORT_FILES.lock().unwrap().insert(path.to_path_buf());
+            tracing::info!(
+                "Skipping {}: {} is below the minimum duration",
+                path.display(),
+                Ticks(ticks)
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Whether a file was already found too short since the server started
+pub fn skipped_before(path: &Path) -> bool {
+    SHORT_FILES.lock().unwrap().contains(path)
+}
@@ -0,0 +1,201 @@
+// Season items
+//
+// Seasons are media_items rows of type Season whose parent is their series, so
+// they have IDs of their own that images, user data and favorites can be
+// attached to. Episodes keep the series as their parent_id (listing a series'
+// episodes, blocks and the duplicate grouping all rely on it) and point at
+// their season through `season_id`.
+//
+// Seasons aren't found on disk but follow the episodes' displayed season
+// numbers, after season mappings and episode orderings: a season is created
+// with its first episode and removed with its last. They're synced after every
+// scan and whenever a series is renumbered.
+
+use anyhow::{Context, Result};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+/// Season number an episode is listed under (no number means season 1)
+const EPISODE_SEASON: &str = "COALESCE(display_parent_index_number, parent_index_number, 1)";
+
+/// Name of a season: "Specials" for season 0, otherwise "Season N"
+pub fn season_name(number: i32) -> String {
+    if number == 0 {
+        "Specials".to_string()
+    } else {
+        format!("Season {}", number)
+    }
+}
+
+/// Sort name of a season, with specials after the numbered seasons
+pub fn season_sort_name(number: i32) -> String {
+    if number == 0 {
+        "Season 999".to_string()
+    } else {
+        format!("Season {:03}", number)
+    }
+}
+
+/// Create, update and remove a series' seasons to match its episodes
+pub async fn sync_series(pool: &SqlitePool, series_id: &str) -> Result<()> {
+    let series: Option<(String, Option<i32>)> = sqlx::query_as(
+        "SELECT library_id, year FROM media_items WHERE id = ? AND item_type = 'Series'",
+    )
+    .bind(series_id)
+    .fetch_optional(pool)
+    .await?;
+    let Some((library_id, series_year)) = series else {
+        return Ok(());
+    };
+
+    // Each season's number and its first episode's air date
+    let numbers: Vec<(i32, Option<String>)> = sqlx::query_as(&format!(
+        "SELECT {0}, MIN(premiere_date) FROM media_items
+         WHERE parent_id = ? AND item_type = 'Episode' GROUP BY {0}",
+        EPISODE_SEASON
+    ))
+    .bind(series_id)
+    .fetch_all(pool)
+    .await?;
+    let existing: Vec<(String, Option<i32>)> = sqlx::query_as(
+        "SELECT id, index_number FROM media_items WHERE parent_id = ? AND item_type = 'Season'",
+    )
+    .bind(series_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut existing: HashMap<i32, String> = existing
+        .into_iter()
+        .filter_map(|(id, number)| Some((number?, id)))
+        .collect();
+
+    let mut tx = pool.begin().await?;
+    for (number, premiere_date) in &numbers {
+        let year = premiere_date
+            .as_deref()
+            .and_then(|d| d.get(..4))
+            .and_then(|y| y.parse::<i32>().ok())
+            .or(series_year);
+        let season_id = match existing.remove(number) {
+            Some(id) => {
+                sqlx::query("UPDATE media_items SET premiere_date = ?, year = ? WHERE id = ?")
+                    .bind(premiere_date)
+                    .bind(year)
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+                id
+            }
+            None => {
+                let id = uuid::Uuid::new_v4().to_string();
+                sqlx::query(
+                    "INSERT INTO media_items
+                        (id, library_id, parent_id, item_type, name, sort_name, index_number, year, premiere_date)
+                     VALUES (?, ?, ?, 'Season', ?, ?, ?, ?, ?)",
+                )
+                .bind(&id)
+                .bind(&library_id)
+                .bind(series_id)
+                .bind(season_name(*number))
+                .bind(season_sort_name(*number))
+                .bind(number)
+                .bind(year)
+                .bind(premiere_date)
+                .execute(&mut *tx)
+                .await?;
+                id
+            }
+        };
+
+        sqlx::query(&format!(
+            "UPDATE media_items SET season_id = ?
+             WHERE parent_id = ? AND item_type = 'Episode' AND {} = ?
+               AND season_id IS NOT ?",
+            EPISODE_SEASON
+        ))
+        .bind(&season_id)
+        .bind(series_id)
+        .bind(number)
+        .bind(&season_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    // Seasons left without episodes
+    for id in existing.values() {
+        sqlx::query("DELETE FROM media_items WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit()
+        .await
+        .with_context(|| format!("Failed to update seasons of series {}", series_id))?;
+    Ok(())
+}
+
+/// The season item for a season ID from before seasons were items
+/// ("{series_id}_season_{n}"), which clients may still have saved
+pub async fn find_legacy(pool: &SqlitePool, id: &str) -> Result<Option<String>> {
+    let Some((series_id, number)) = id
+        .rsplit_once("_season_")
+        .and_then(|(series, n)| Some((series, n.parse::<i32>().ok()?)))
+    else {
+        return Ok(None);
+    };
+    Ok(sqlx::query_scalar(
+        "SELECT id FROM media_items WHERE parent_id = ? AND item_type = 'Season' AND index_number = ?",
+    )
+    .bind(series_id)
+    .bind(number)
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// Sync the seasons of series with new or unassigned episodes, and remove
+/// seasons whose episodes are all gone
+///
+/// Covers one library, or all of them with None (at startup, which also
+/// creates the seasons of databases from before seasons were items). Returns
+/// the number of series synced.
+pub async fn sync_library(pool: &SqlitePool, library_id: Option<&str>) -> Result<usize> {
+    let series_ids: Vec<String> = sqlx::query_scalar(
+        "SELECT DISTINCT parent_id FROM media_items
+         WHERE item_type = 'Episode' AND season_id IS NULL AND parent_id IS NOT NULL
+           AND (?1 IS NULL OR library_id = ?1)",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+    for series_id in &series_ids {
+        sync_series(pool, series_id).await?;
+    }
+
+    let removed = sqlx::query(
+        "DELETE FROM media_items
+         WHERE item_type = 'Season' AND (?1 IS NULL OR library_id = ?1)
+           AND NOT EXISTS (SELECT 1 FROM media_items e WHERE e.season_id = media_items.id)",
+    )
+    .bind(library_id)
+    .execute(pool)
+    .await?;
+    if removed.rows_affected() > 0 {
+        tracing::debug!("Removed {} empty seasons", removed.rows_affected());
+    }
+    Ok(series_ids.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_season_names() {
+        assert_eq!(season_name(0), "Specials");
+        assert_eq!(season_name(2), "Season 2");
+        // Specials sort after every numbered season
+        let mut sort_names = [0, 10, 2].map(season_sort_name);
+        sort_names.sort();
+        assert_eq!(sort_names, ["Season 002", "Season 010", "Season 999"]);
+    }
+}
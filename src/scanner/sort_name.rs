@@ -0,0 +1,194 @@
+// Sort names
+//
+// Titles are browsed by `sort_name`, which is the name folded to lower case
+// without diacritics ("Amélie" sorts with "amelie"), with leading punctuation
+// dropped and a leading article moved to the end ("The Matrix" sorts as
+// "matrix, the"), so libraries are ordered the way people look for titles.
+// Which articles count depends on the languages in `scanner.ignore_articles`,
+// plus any in `scanner.extra_articles`. Sort names are generated when items
+// are added and regenerated at startup, so changing the settings (or a
+// metadata refresh renaming an item) reorders existing items too.
+//
+// The same folding backs the FOLD collation registered on every connection,
+// used where items are ordered by their display name.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::cmp::Ordering;
+use std::sync::OnceLock;
+
+/// Leading articles per language; those ending in an apostrophe are elided
+/// onto the next word ("L'Odyssée")
+const ARTICLES: &[(&str, &[&str])] = &[
+    ("en", &["the", "a", "an"]),
+    ("de", &["der", "die", "das", "ein", "eine"]),
+    ("fr", &["le", "la", "les", "l'", "un", "une"]),
+    ("es", &["el", "la", "los", "las", "un", "una"]),
+    (
+        "it",
+        &["il", "lo", "la", "i", "gli", "le", "l'", "un", "una"],
+    ),
+    ("nl", &["de", "het", "een"]),
+    ("pt", &["o", "a", "os", "as", "um", "uma"]),
+];
+
+/// Item types whose sort name is derived from their name (seasons have their
+/// own, episodes sort by number)
+const NAMED_TYPES: &[&str] = &["Movie", "Series", "MusicArtist", "MusicAlbum", "Audio"];
+
+static IGNORED_ARTICLES: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Set the articles ignored at the start of names: those of the given
+/// languages, plus extra ones
+///
+/// Unknown languages are logged and skipped.
+pub fn set_articles(languages: &[String], extra: &[String]) {
+    let mut articles: Vec<String> = Vec::new();
+    for language in languages {
+        match ARTICLES
+            .iter()
+            .find(|(code, _)| code.eq_ignore_ascii_case(language))
+        {
+            Some((_, list)) => articles.extend(list.iter().map(|a| a.to_string())),
+            None => tracing::warn!("No sort articles known for language '{}'", language),
+        }
+    }
+    articles.extend(extra.iter().map(|a| fold(a.trim())));
+    articles.sort();
+    articles.dedup();
+    let _ = IGNORED_ARTICLES.set(articles);
+}
+
+fn ignored_articles() -> &'static [String] {
+    IGNORED_ARTICLES
+        .get()
+        .map(|a| a.as_slice())
+        .unwrap_or_default()
+}
+
+/// Lower-case a string and strip diacritics from Latin letters
+pub fn fold(s: &str) -> String {
+    let mut folded = String::with_capacity(s.len());
+    for c in s.chars().flat_map(char::to_lowercase) {
+        let base = match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+            'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+            'ď' | 'đ' | 'ð' => "d",
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+            'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+            'ĥ' | 'ħ' => "h",
+            'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+            'ĵ' => "j",
+            'ķ' => "k",
+            'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+            'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+            'ŕ' | 'ŗ' | 'ř' => "r",
+            'ś' | 'ŝ' | 'ş' | 'š' => "s",
+            'ţ' | 'ť' | 'ŧ' => "t",
+            'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+            'ŵ' => "w",
+            'ý' | 'ÿ' | 'ŷ' => "y",
+            'ź' | 'ż' | 'ž' => "z",
+            'æ' => "ae",
+            'œ' => "oe",
+            'ß' => "ss",
+            'þ' => "th",
+            '’' => "'",
+            _ => {
+                folded.push(c);
+                continue;
+            }
+        };
+        folded.push_str(base);
+    }
+    folded
+}
+
+/// Compare two strings ignoring case and diacritics (the FOLD collation)
+pub fn compare(a: &str, b: &str) -> Ordering {
+    fold(a).cmp(&fold(b))
+}
+
+/// The sort name of a title
+pub fn sort_name(name: &str) -> String {
+    let folded = fold(name.trim());
+    // Leading punctuation ("[Oshi no Ko]", "...And Justice for All")
+    let title = match folded.trim_start_matches(|c: char| !c.is_alphanumeric()) {
+        "" => folded.as_str(),
+        rest => rest,
+    };
+
+    for article in ignored_articles() {
+        let Some(rest) = title.strip_prefix(article.as_str()) else {
+            continue;
+        };
+        let rest = if article.ends_with('\'') {
+            rest
+        } else if let Some(rest) = rest.strip_prefix(' ') {
+            rest
+        } else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        if !rest.is_empty() {
+            return format!("{}, {}", rest, article);
+        }
+    }
+    title.to_string()
+}
+
+/// Regenerate the sort names of existing items; returns how many changed
+pub async fn refresh_all(pool: &SqlitePool) -> Result<u64> {
+    let placeholders = vec!["?"; NAMED_TYPES.len()].join(", ");
+    let sql = format!(
+        "SELECT id, name, sort_name FROM media_items WHERE item_type IN ({})",
+        placeholders
+    );
+    let mut query = sqlx::query_as::<_, (String, String, Option<String>)>(&sql);
+    for item_type in NAMED_TYPES {
+        query = query.bind(*item_type);
+    }
+    let items = query.fetch_all(pool).await?;
+
+    let mut tx = pool.begin().await?;
+    let mut changed = 0;
+    for (id, name, current) in items {
+        let generated = sort_name(&name);
+        if current.as_deref() == Some(generated.as_str()) {
+            continue;
+        }
+        sqlx::query("UPDATE media_items SET sort_name = ? WHERE id = ?")
+            .bind(&generated)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+        changed += 1;
+    }
+    tx.commit().await?;
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_names() {
+        set_articles(&["en".to_string(), "fr".to_string()], &[]);
+
+        assert_eq!(sort_name("The Matrix"), "matrix, the");
+        assert_eq!(sort_name("A Silent Voice"), "silent voice, a");
+        assert_eq!(sort_name("L'Odyssée de Pi"), "odyssee de pi, l'");
+        assert_eq!(sort_name("Amélie"), "amelie");
+        assert_eq!(sort_name("[Oshi no Ko]"), "oshi no ko]");
+
+        // Articles only count as whole words with a title after them
+        assert_eq!(sort_name("Theodore Rex"), "theodore rex");
+        assert_eq!(sort_name("The"), "the");
+        assert_eq!(sort_name("Anastasia"), "anastasia");
+
+        assert_eq!(compare("Éclair", "eclair"), Ordering::Equal);
+        assert_eq!(compare("Zorro", "ÉCLAIR"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,362 @@
+// Library folder watcher
+//
+// Quick scans only notice new and deleted files every few minutes. With
+// `scanner.watch` (or a configured library's own `watch`) on, the library
+// folders are watched through `notify` (inotify on Linux, FSEvents on macOS,
+// ReadDirectoryChangesW on Windows) instead. Events are debounced: a path
+// counts once it has been quiet for `scanner.watch_debounce_seconds`, so a
+// file still being copied isn't added half-written. Each batch is then
+// applied path by path: new files are added on their own, new folders are
+// scanned as a whole, and the items of deleted or moved-away paths are
+// removed; a rename does both. Periodic quick scans keep running as a safety
+// net: the watcher doesn't see changes made by other machines on network
+// shares, and when events are lost the whole library is rescanned.
+//
+// On Linux each watched folder takes an inotify watch; large libraries may
+// need a higher fs.inotify.max_user_watches.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::event::{AccessKind, AccessMode, ModifyKind, RenameMode};
+use notify::{EventKind, RecursiveMode};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, DebouncedEvent};
+use sqlx::SqlitePool;
+use tokio_util::sync::CancellationToken;
+
+use super::exclude;
+use crate::config::AppConfig;
+
+type Debouncer = notify_debouncer_full::Debouncer<
+    notify::RecommendedWatcher,
+    notify_debouncer_full::RecommendedCache,
+>;
+
+/// How often the library list is re-read, so new libraries (and library
+/// folders that came back) are watched
+const LIBRARY_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Whether the folders of the library at `path` are watched
+pub fn is_watched(config: &AppConfig, path: &Path) -> bool {
+    config
+        .libraries
+        .iter()
+        .find(|lib| lib.path == path)
+        .and_then(|lib| lib.watch)
+        .unwrap_or(config.scanner.watch)
+}
+
+/// Reduce changed paths to the ones to handle: a path inside another changed
+/// folder is covered by that folder
+pub fn scan_targets(mut dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    dirs.sort();
+    dirs.dedup();
+    let mut targets: Vec<PathBuf> = Vec::new();
+    for dir in dirs {
+        // Sorted, so an ancestor comes right before its descendants
+        if targets.last().is_some_and(|last| dir.starts_with(last)) {
+            continue;
+        }
+        targets.push(dir);
+    }
+    targets
+}
+
+/// What a batch of debounced events changed
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    /// Files and folders created, written or moved in
+    pub added: Vec<PathBuf>,
+    /// Files and folders deleted or moved away
+    pub removed: Vec<PathBuf>,
+    /// Events were lost, so the watched libraries have to be rescanned
+    pub rescan: bool,
+}
+
+/// Hidden files (partial downloads, `.DS_Store`) never become items
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+}
+
+/// Sort a batch of events into added and removed paths
+///
+/// A rename removes its old path and adds the new one; a rename whose other
+/// side is unknown (moved in or out of the watched folders) is treated as
+/// both, since the scan checks the disk anyway.
+pub fn changes(events: &[DebouncedEvent]) -> Changes {
+    let mut result = Changes::default();
+    let mut added = Vec::new();
+    let mut removed = Vec::new();
+
+    for event in events {
+        if event.need_rescan() {
+            result.rescan = true;
+            continue;
+        }
+        match event.kind {
+            EventKind::Create(_)
+            | EventKind::Modify(ModifyKind::Data(_))
+            | EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                added.extend(event.paths.iter().cloned());
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                removed.extend(event.paths.iter().cloned());
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    removed.push(from.clone());
+                    added.push(to.clone());
+                }
+            }
+            EventKind::Modify(ModifyKind::Name(_)) => {
+                removed.extend(event.paths.iter().cloned());
+                added.extend(event.paths.iter().cloned());
+            }
+            _ => {}
+        }
+    }
+
+    added.retain(|path| !is_hidden(path) && !exclude::is_excluded(path));
+    removed.retain(|path| !is_hidden(path));
+    result.added = scan_targets(added);
+    result.removed = scan_targets(removed);
+    result
+}
+
+/// Watch library folders and apply their changes until cancelled
+pub async fn run(pool: SqlitePool, config: AppConfig, cancel: CancellationToken) {
+    let debounce = Duration::from_secs(config.scanner.watch_debounce_seconds.max(1));
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut debouncer = match new_debouncer(debounce, None, move |result: DebounceEventResult| {
+        let _ = tx.send(result);
+    }) {
+        Ok(debouncer) => debouncer,
+        Err(e) => {
+            tracing::warn!("Library folders can't be watched: {}", e);
+            return;
+        }
+    };
+
+    // Library roots being watched
+    let mut roots: HashSet<PathBuf> = HashSet::new();
+    let mut refresh = tokio::time::interval(LIBRARY_REFRESH_INTERVAL);
+
+    loop {
+        let result = tokio::select! {
+            _ = cancel.cancelled() => {
+                tracing::debug!("Library watcher received shutdown signal");
+                break;
+            }
+            _ = refresh.tick() => {
+                watch_libraries(&pool, &config, &mut debouncer, &mut roots).await;
+                continue;
+            }
+            result = rx.recv() => match result {
+                Some(result) => result,
+                None => break,
+            },
+        };
+
+        let events = match result {
+            Ok(events) => events,
+            Err(errors) => {
+                for e in errors {
+                    tracing::warn!("Library watcher error: {}", e);
+                }
+                continue;
+            }
+        };
+
+        let changes = changes(&events);
+
+        // A deleted or moved library folder is watched again once it's back
+        let gone: Vec<PathBuf> = roots
+            .iter()
+            .filter(|root| changes.removed.iter().any(|p| root.starts_with(p)))
+            .cloned()
+            .collect();
+        for root in gone {
+            let _ = debouncer.unwatch(&root);
+            roots.remove(&root);
+        }
+
+        if changes.rescan {
+            tracing::warn!("File change events were lost, rescanning watched libraries");
+            for root in &roots {
+                if let Err(e) = super::scan_path(
+                    &pool,
+                    root,
+                    config.paths.cache_dir.clone(),
+                    Some(config.anime_db_enabled),
+                    config.fetch_episode_metadata,
+                )
+                .await
+                {
+                    tracing::warn!("Rescan of '{}' failed: {}", root.display(), e);
+                }
+            }
+            continue;
+        }
+        if changes.added.is_empty() && changes.removed.is_empty() {
+            continue;
+        }
+
+        for path in &changes.added {
+            tracing::debug!("Path added or changed: {}", path.display());
+        }
+        for path in &changes.removed {
+            tracing::debug!("Path removed: {}", path.display());
+        }
+        if let Err(e) = super::scan_changes(
+            &pool,
+            &changes.added,
+            &changes.removed,
+            config.paths.cache_dir.clone(),
+            Some(config.anime_db_enabled),
+            config.fetch_episode_metadata,
+        )
+        .await
+        {
+            tracing::warn!("Applying file changes failed: {}", e);
+        }
+    }
+}
+
+/// Start watching libraries that should be and aren't yet
+async fn watch_libraries(
+    pool: &SqlitePool,
+    config: &AppConfig,
+    debouncer: &mut Debouncer,
+    roots: &mut HashSet<PathBuf>,
+) {
+    let libraries: Vec<(String, String)> =
+        match sqlx::query_as("SELECT name, path FROM libraries WHERE offline_since IS NULL")
+            .fetch_all(pool)
+            .await
+        {
+            Ok(libraries) => libraries,
+            Err(e) => {
+                tracing::warn!("Failed to list libraries to watch: {}", e);
+                return;
+            }
+        };
+
+    for (name, path) in libraries {
+        let root = PathBuf::from(path);
+        if roots.contains(&root) || !is_watched(config, &root) {
+            continue;
+        }
+        // Watching a large tree walks it, so keep it off the async workers
+        match tokio::task::block_in_place(|| debouncer.watch(&root, RecursiveMode::Recursive)) {
+            Ok(()) => {
+                tracing::info!("Watching library '{}'", name);
+                roots.insert(root);
+            }
+            Err(e) if matches!(e.kind, notify::ErrorKind::MaxFilesWatch) => {
+                tracing::warn!(
+                    "Out of watches for library '{}'; raise fs.inotify.max_user_watches",
+                    name
+                );
+                let _ = debouncer.unwatch(&root);
+            }
+            Err(e) => tracing::warn!("Can't watch library '{}': {}", name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{CreateKind, Flag, MetadataKind, RemoveKind};
+    use notify::Event;
+    use std::time::Instant;
+
+    #[test]
+    fn test_changes_from_debounced_batch() {
+        let event = |kind: EventKind, paths: &[&str]| {
+            let event = paths.iter().fold(Event::new(kind), |event, path| {
+                event.add_path(PathBuf::from(path))
+            });
+            DebouncedEvent::new(event, Instant::now())
+        };
+        let events = vec![
+            event(
+                EventKind::Create(CreateKind::File),
+                &["/tv/Show/S01E01.mkv"],
+            ),
+            event(
+                EventKind::Access(AccessKind::Close(AccessMode::Write)),
+                &["/tv/Show/S01E01.mkv"],
+            ),
+            event(
+                EventKind::Remove(RemoveKind::File),
+                &["/tv/Show/S01E02.mkv"],
+            ),
+            event(
+                EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+                &["/tv/Old/S01E03.mkv", "/tv/New/S01E03.mkv"],
+            ),
+            // A new folder covers the files created inside it
+            event(EventKind::Create(CreateKind::Folder), &["/tv/Other"]),
+            event(
+                EventKind::Create(CreateKind::File),
+                &["/tv/Other/S01E01.mkv"],
+            ),
+            // Hidden partial downloads and attribute changes are ignored
+            event(
+                EventKind::Create(CreateKind::File),
+                &["/tv/Show/.S01E04.mkv.part"],
+            ),
+            event(
+                EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions)),
+                &["/tv/Show/S01E05.mkv"],
+            ),
+        ];
+
+        let batch = changes(&events);
+        assert_eq!(
+            batch.added,
+            [
+                PathBuf::from("/tv/New/S01E03.mkv"),
+                PathBuf::from("/tv/Other"),
+                PathBuf::from("/tv/Show/S01E01.mkv"),
+            ]
+        );
+        assert_eq!(
+            batch.removed,
+            [
+                PathBuf::from("/tv/Old/S01E03.mkv"),
+                PathBuf::from("/tv/Show/S01E02.mkv"),
+            ]
+        );
+        assert!(!batch.rescan);
+
+        // Lost events ask for a rescan
+        let lost = event(EventKind::Other, &[]);
+        let lost = DebouncedEvent::new(lost.event.set_flag(Flag::Rescan), lost.time);
+        assert!(changes(&[lost]).rescan);
+    }
+
+    #[test]
+    fn test_scan_targets_skip_nested_folders() {
+        let targets = scan_targets(vec![
+            PathBuf::from("/tv/Show/Season 1"),
+            PathBuf::from("/tv/Show"),
+            PathBuf::from("/tv/Show/Season 2"),
+            PathBuf::from("/tv/Other"),
+            PathBuf::from("/tv/Show"),
+            PathBuf::from("/tv/Showcase"),
+        ]);
+        assert_eq!(
+            targets,
+            [
+                PathBuf::from("/tv/Other"),
+                PathBuf::from("/tv/Show"),
+                PathBuf::from("/tv/Showcase"),
+            ]
+        );
+    }
+}
@@ -5,12 +5,14 @@
 
 use anyhow::{Context, Result};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::{Duration, Instant};
 use tokio::fs;
 use tokio::sync::Mutex;
 
+use super::image_validation;
+
 const ANIDB_API_BASE: &str = "http://api.anidb.net:9001/httpapi";
 const ANIDB_IMAGE_BASE: &str = "https://cdn.anidb.net/images/main";
 // AniDB requires a client identifier
@@ -43,7 +45,7 @@ pub struct AniDBAnime {
 }
 
 /// AniDB episode data
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AniDBEpisode {
     pub eid: i64,
     pub epno: String,
@@ -55,7 +57,7 @@ pub struct AniDBEpisode {
 }
 
 /// Metadata result compatible with our system
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AniDBMetadata {
     pub anidb_id: Option<String>,
     pub name: Option<String>,
@@ -284,7 +286,14 @@ impl AniDBClient {
 
         // Skip if already cached (use async check to avoid blocking)
         if fs::try_exists(&local_path).await.unwrap_or(false) {
-            return Ok(local_path);
+            match image_validation::inspect_file(&local_path).await {
+                Ok(_) => return Ok(local_path),
+                Err(e) => tracing::warn!(
+                    "Re-downloading invalid cached image {:?}: {}",
+                    local_path,
+                    e
+                ),
+            }
         }
 
         // No rate limiting for image downloads (different server)
@@ -299,7 +308,10 @@ impl AniDBClient {
             anyhow::bail!("Image download failed: {}", response.status());
         }
 
+        let content_type = image_validation::content_type(&response);
         let bytes = response.bytes().await?;
+        image_validation::validate_download(&bytes, content_type.as_deref(), image_type)
+            .with_context(|| format!("Invalid image from {}", url))?;
         fs::write(&local_path, &bytes).await?;
 
         tracing::info!("Downloaded AniDB image to {:?}", local_path);
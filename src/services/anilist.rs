@@ -4,6 +4,8 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tokio::fs;
 
+use super::image_validation;
+
 const ANILIST_API_URL: &str = "https://graphql.anilist.co";
 
 /// AniList API client
@@ -154,7 +156,7 @@ pub struct StaffImage {
 }
 
 /// Metadata result compatible with our system
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AnimeMetadata {
     pub anilist_id: Option<String>,
     pub mal_id: Option<String>,
@@ -175,7 +177,7 @@ pub struct AnimeMetadata {
 }
 
 /// A cast member (voice actor + character)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CastMember {
     pub person_id: String,
     pub person_name: String,
@@ -609,6 +611,26 @@ impl AniListClient {
         url: &str,
         item_id: &str,
         image_type: &str,
+    ) -> Result<PathBuf> {
+        self.fetch_image(url, item_id, image_type, false).await
+    }
+
+    /// Download an image even if one is cached, replacing it once the download succeeds
+    pub async fn redownload_image(
+        &self,
+        url: &str,
+        item_id: &str,
+        image_type: &str,
+    ) -> Result<PathBuf> {
+        self.fetch_image(url, item_id, image_type, true).await
+    }
+
+    async fn fetch_image(
+        &self,
+        url: &str,
+        item_id: &str,
+        image_type: &str,
+        replace: bool,
     ) -> Result<PathBuf> {
         // Create cache directory structure
         let item_cache_dir = self.image_cache_dir.join(item_id);
@@ -625,9 +647,18 @@ impl AniListClient {
         let local_path = item_cache_dir.join(&local_filename);
 
         // Skip if already cached (use async check to avoid blocking)
-        if fs::try_exists(&local_path).await.unwrap_or(false) {
-            tracing::debug!("Image already cached: {:?}", local_path);
-            return Ok(local_path);
+        if !replace && fs::try_exists(&local_path).await.unwrap_or(false) {
+            match image_validation::inspect_file(&local_path).await {
+                Ok(_) => {
+                    tracing::debug!("Image already cached: {:?}", local_path);
+                    return Ok(local_path);
+                }
+                Err(e) => tracing::warn!(
+                    "Re-downloading invalid cached image {:?}: {}",
+                    local_path,
+                    e
+                ),
+            }
         }
 
         // Download
@@ -644,8 +675,14 @@ impl AniListClient {
             anyhow::bail!("Image download failed with status: {}", response.status());
         }
 
+        let content_type = image_validation::content_type(&response);
         let bytes = response.bytes().await?;
-        fs::write(&local_path, &bytes).await?;
+        image_validation::validate_download(&bytes, content_type.as_deref(), image_type)
+            .with_context(|| format!("Invalid image from {}", url))?;
+        // Written beside the target and renamed, so a replaced image is never half-written
+        let partial_path = item_cache_dir.join(format!("{}.part", local_filename));
+        fs::write(&partial_path, &bytes).await?;
+        fs::rename(&partial_path, &local_path).await?;
 
         tracing::info!("Downloaded image to {:?}", local_path);
         Ok(local_path)
@@ -332,6 +332,33 @@ impl AnimeOfflineDatabase {
         Ok(None)
     }
 
+    /// Find the entry for any of the given IDs (tried in AniList, MAL, AniDB, Kitsu order)
+    ///
+    /// Unlike the single-ID lookups this matches IDs exactly, so 12 won't match 123.
+    pub async fn find_by_provider_ids(&self, ids: &ProviderIds) -> Result<Option<AnimeEntry>> {
+        self.ensure_loaded().await?;
+
+        let db = self.database.read().await;
+        let entries = db.as_ref().unwrap();
+
+        let patterns = [
+            ids.anilist_id.map(|id| format!("anilist.co/anime/{}", id)),
+            ids.mal_id.map(|id| format!("myanimelist.net/anime/{}", id)),
+            ids.anidb_id.map(|id| format!("anidb.net/anime/{}", id)),
+            ids.kitsu_id.map(|id| format!("kitsu.app/anime/{}", id)),
+        ];
+        for pattern in patterns.iter().flatten() {
+            if let Some(entry) = entries
+                .iter()
+                .find(|e| e.sources.iter().any(|s| s.ends_with(pattern.as_str())))
+            {
+                return Ok(Some(entry.clone()));
+            }
+        }
+
+        Ok(None)
+    }
+
     pub async fn find_by_mal_id(&self, mal_id: i64) -> Result<Option<AnimeEntry>> {
         self.ensure_loaded().await?;
 
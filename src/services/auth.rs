@@ -4,11 +4,32 @@ use argon2::{
     Argon2,
 };
 use rand_core::OsRng;
+use regex::Regex;
 use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use crate::models::{Session, User};
 
+/// Check a provided API key against the configured one
+/// Always fails when no API key is configured
+pub fn verify_api_key(configured: Option<&str>, provided: Option<&str>) -> bool {
+    match (configured, provided) {
+        (Some(expected), Some(given)) if !expected.is_empty() => {
+            // Compare in constant time so the key can't be guessed byte by byte
+            expected.len() == given.len()
+                && expected
+                    .bytes()
+                    .zip(given.bytes())
+                    .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+        }
+        _ => false,
+    }
+}
+
 /// Hash a password using Argon2
 pub fn hash_password(password: &str) -> Result<String> {
     let salt = SaltString::generate(&mut OsRng);
@@ -55,6 +76,52 @@ pub async fn create_user(
     })
 }
 
+/// Whether an admin has disabled a user's account
+pub async fn is_disabled(pool: &SqlitePool, user_id: &str) -> Result<bool> {
+    let disabled: Option<bool> = sqlx::query_scalar("SELECT is_disabled FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(disabled.unwrap_or(false))
+}
+
+/// Disable or enable a user's account; disabling signs them out everywhere
+pub async fn set_disabled(pool: &SqlitePool, user_id: &str, disabled: bool) -> Result<()> {
+    sqlx::query("UPDATE users SET is_disabled = ? WHERE id = ?")
+        .bind(disabled)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    if disabled {
+        revoke_all_user_sessions(pool, user_id).await?;
+    }
+    Ok(())
+}
+
+/// Set a user's password and sign out their other sessions
+///
+/// The session making the change (`keep_token`) stays signed in.
+pub async fn change_password(
+    pool: &SqlitePool,
+    user_id: &str,
+    password: &str,
+    keep_token: Option<&str>,
+) -> Result<()> {
+    let password_hash = hash_password(password)?;
+    sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    sqlx::query("DELETE FROM sessions WHERE user_id = ? AND token IS NOT ?")
+        .bind(user_id)
+        .bind(keep_token)
+        .execute(pool)
+        .await?;
+    invalidate_cached_user(user_id);
+    Ok(())
+}
+
 /// Session lifetime in seconds (24 hours by default)
 const SESSION_LIFETIME_SECS: i64 = 24 * 60 * 60;
 
@@ -76,6 +143,9 @@ pub async fn authenticate(
     if !verify_password(password, &user.password_hash)? {
         return Err(anyhow!("Invalid password"));
     }
+    if is_disabled(pool, &user.id).await? {
+        return Err(anyhow!("User is disabled"));
+    }
 
     let token = Uuid::new_v4().to_string();
     let now = chrono::Utc::now();
@@ -108,14 +178,77 @@ pub async fn authenticate(
     Ok((user, session))
 }
 
+/// How long a validated session is trusted without going back to the database
+///
+/// Image-heavy screens fire dozens of requests per second with the same token;
+/// within this window they skip both the session and the user query. The
+/// sliding expiry is still extended whenever an entry is refreshed.
+const SESSION_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// Entries beyond this count trigger a sweep of stale entries
+const SESSION_CACHE_SWEEP_THRESHOLD: usize = 1024;
+
+struct CachedSession {
+    user: User,
+    validated_at: Instant,
+}
+
+static SESSION_CACHE: LazyLock<Mutex<HashMap<String, CachedSession>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn session_cache() -> std::sync::MutexGuard<'static, HashMap<String, CachedSession>> {
+    SESSION_CACHE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn cached_session_user(token: &str) -> Option<User> {
+    let mut cache = session_cache();
+    match cache.get(token) {
+        Some(entry) if entry.validated_at.elapsed() < SESSION_CACHE_TTL => Some(entry.user.clone()),
+        Some(_) => {
+            cache.remove(token);
+            None
+        }
+        None => None,
+    }
+}
+
+fn cache_session_user(token: &str, user: &User) {
+    let mut cache = session_cache();
+    if cache.len() >= SESSION_CACHE_SWEEP_THRESHOLD {
+        cache.retain(|_, entry| entry.validated_at.elapsed() < SESSION_CACHE_TTL);
+    }
+    cache.insert(
+        token.to_string(),
+        CachedSession {
+            user: user.clone(),
+            validated_at: Instant::now(),
+        },
+    );
+}
+
+/// Drop a token from the session cache (logout, revocation)
+pub fn invalidate_cached_session(token: &str) {
+    session_cache().remove(token);
+}
+
+/// Drop every cached session of a user (password change, deletion, permission change)
+pub fn invalidate_cached_user(user_id: &str) {
+    session_cache().retain(|_, entry| entry.user.id != user_id);
+}
+
 /// Validate session token and get user
 ///
-/// This function:
+/// Recently validated tokens are answered from an in-memory cache for
+/// SESSION_CACHE_TTL. Otherwise this function:
 /// 1. Checks if the session exists
 /// 2. Verifies the session hasn't expired
 /// 3. Updates the last_activity timestamp
 /// 4. Extends expiration on activity (sliding window)
 pub async fn validate_session(pool: &SqlitePool, token: &str) -> Result<User> {
+    if let Some(user) = cached_session_user(token) {
+        return Ok(user);
+    }
+
     let session: Session = sqlx::query_as("SELECT * FROM sessions WHERE token = ?")
         .bind(token)
         .fetch_optional(pool)
@@ -152,9 +285,86 @@ pub async fn validate_session(pool: &SqlitePool, token: &str) -> Result<User> {
         .fetch_one(pool)
         .await?;
 
+    cache_session_user(token, &user);
+
     Ok(user)
 }
 
+/// Lifetime of a read-as-user session opened by an admin
+const IMPERSONATION_LIFETIME_SECS: i64 = 60 * 60;
+
+/// Session an admin's requests run under while impersonating another user
+///
+/// The token is only ever used server-side: the impersonation middleware swaps
+/// it into requests that still carry the admin's own token, so it's never
+/// handed to a client. One session is kept per admin device and target user.
+pub async fn impersonation_session(
+    pool: &SqlitePool,
+    admin: &User,
+    admin_token: &str,
+    target: &User,
+) -> Result<String> {
+    let (device_id, device_name, client): (String, String, String) =
+        sqlx::query_as("SELECT device_id, device_name, client FROM sessions WHERE token = ?")
+            .bind(admin_token)
+            .fetch_optional(pool)
+            .await?
+            .ok_or_else(|| anyhow!("Invalid session"))?;
+    let now = chrono::Utc::now();
+
+    let existing: Option<(String,)> = sqlx::query_as(
+        "SELECT token FROM sessions
+         WHERE user_id = ? AND impersonated_by = ? AND device_id = ? AND expires_at > ?",
+    )
+    .bind(&target.id)
+    .bind(&admin.id)
+    .bind(&device_id)
+    .bind(now.to_rfc3339())
+    .fetch_optional(pool)
+    .await?;
+    if let Some((token,)) = existing {
+        return Ok(token);
+    }
+
+    let token = Uuid::new_v4().to_string();
+    let expires_at = now + chrono::Duration::seconds(IMPERSONATION_LIFETIME_SECS);
+    sqlx::query(
+        "INSERT INTO sessions (token, user_id, device_id, device_name, client, last_activity, expires_at, impersonated_by)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&token)
+    .bind(&target.id)
+    .bind(&device_id)
+    .bind(format!("{} (as {})", device_name, target.name))
+    .bind(client)
+    .bind(now.to_rfc3339())
+    .bind(expires_at.to_rfc3339())
+    .bind(&admin.id)
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+/// Token field of an X-Emby-Authorization header (quoted or not)
+static HEADER_TOKEN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(^|[\s,])Token="?[^",]*"?"#).unwrap());
+
+/// Replace the Token value of an X-Emby-Authorization header, keeping the
+/// client and device fields; a header without a token gets one appended
+pub fn replace_header_token(header: &str, token: &str) -> String {
+    let field = format!("Token=\"{}\"", token);
+    if HEADER_TOKEN.is_match(header) {
+        HEADER_TOKEN
+            .replace(header, |caps: &regex::Captures| {
+                format!("{}{}", &caps[1], field)
+            })
+            .into_owned()
+    } else {
+        format!("{}, {}", header, field)
+    }
+}
+
 /// Clean up expired sessions from the database
 /// Returns the number of sessions removed
 pub async fn cleanup_expired_sessions(pool: &SqlitePool) -> Result<i32> {
@@ -175,6 +385,7 @@ pub async fn revoke_session(pool: &SqlitePool, token: &str) -> Result<()> {
         .bind(token)
         .execute(pool)
         .await?;
+    invalidate_cached_session(token);
 
     Ok(())
 }
@@ -185,6 +396,78 @@ pub async fn revoke_all_user_sessions(pool: &SqlitePool, user_id: &str) -> Resul
         .bind(user_id)
         .execute(pool)
         .await?;
+    invalidate_cached_user(user_id);
+
+    Ok(result.rows_affected() as i32)
+}
+
+/// Revoke the sessions of one of a user's devices (admin "log out this session")
+pub async fn revoke_device_sessions(
+    pool: &SqlitePool,
+    user_id: &str,
+    device_id: &str,
+) -> Result<i32> {
+    let result = sqlx::query("DELETE FROM sessions WHERE user_id = ? AND device_id = ?")
+        .bind(user_id)
+        .bind(device_id)
+        .execute(pool)
+        .await?;
+    invalidate_cached_user(user_id);
 
     Ok(result.rows_affected() as i32)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_user(id: &str) -> User {
+        User {
+            id: id.to_string(),
+            name: "test".to_string(),
+            password_hash: String::new(),
+            is_admin: false,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_session_cache_invalidation() {
+        let user_id = Uuid::new_v4().to_string();
+        let (first, second) = (Uuid::new_v4().to_string(), Uuid::new_v4().to_string());
+        cache_session_user(&first, &test_user(&user_id));
+        cache_session_user(&second, &test_user(&user_id));
+
+        assert_eq!(
+            cached_session_user(&first).map(|u| u.id),
+            Some(user_id.clone())
+        );
+
+        invalidate_cached_session(&first);
+        assert!(cached_session_user(&first).is_none());
+        assert!(cached_session_user(&second).is_some());
+
+        invalidate_cached_user(&user_id);
+        assert!(cached_session_user(&second).is_none());
+    }
+
+    #[test]
+    fn test_replace_header_token() {
+        assert_eq!(
+            replace_header_token(
+                r#"MediaBrowser Client="Web", DeviceId="abc", Token="old", Version="10""#,
+                "new"
+            ),
+            r#"MediaBrowser Client="Web", DeviceId="abc", Token="new", Version="10""#
+        );
+        assert_eq!(
+            replace_header_token("MediaBrowser Token=old", "new"),
+            r#"MediaBrowser Token="new""#
+        );
+        // Other fields ending in "Token" are left alone
+        assert_eq!(
+            replace_header_token(r#"Emby Client="Web", XToken="x""#, "new"),
+            r#"Emby Client="Web", XToken="x", Token="new""#
+        );
+    }
+}
@@ -0,0 +1,189 @@
+// Automatic movie collections (TMDB)
+//
+// TMDB groups franchise movies into collections ("The Matrix Collection").
+// Movie scans and refreshes record the collection of each movie on its item
+// (media_items.tmdb_collection_id); once the server holds MIN_MOVIES movies of
+// one collection, a BoxSet is created for it in the collections table and its
+// movies are added in release order. Later movies of the collection are
+// appended as they are scanned or refreshed, so movies an admin removed from
+// the BoxSet stay out. The collection's TMDB poster and backdrop are
+// kept as URLs and served through the remote image cache (services::image_proxy).
+// Collections created by hand have no TMDB collection ID and are never touched.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use super::tmdb::CollectionRef;
+
+/// Movies of one TMDB collection needed before a BoxSet is created for it
+pub const MIN_MOVIES: usize = 2;
+
+/// TMDB image URL of a collection's poster or backdrop
+fn image_url(path: Option<&str>, size: &str) -> Option<String> {
+    path.map(|p| format!("https://image.tmdb.org/t/p/{}{}", size, p))
+}
+
+/// Whether a TMDB collection with `movies` movies on the server gets a BoxSet
+fn should_create(existing: bool, movies: usize) -> bool {
+    existing || movies >= MIN_MOVIES
+}
+
+/// Record a movie's TMDB collection (None if it belongs to none) and keep the
+/// collection's BoxSet in sync
+///
+/// Returns the ID of the BoxSet the movie was added to, if any.
+pub async fn record_movie(
+    pool: &SqlitePool,
+    item_id: &str,
+    collection: Option<&CollectionRef>,
+) -> Result<Option<String>> {
+    sqlx::query("UPDATE media_items SET tmdb_collection_id = ? WHERE id = ?")
+        .bind(collection.map(|c| c.id.to_string()))
+        .bind(item_id)
+        .execute(pool)
+        .await?;
+    match collection {
+        Some(collection) => sync_collection(pool, item_id, collection).await,
+        None => Ok(None),
+    }
+}
+
+/// Add a movie to the BoxSet of its TMDB collection, creating the BoxSet
+/// (with every movie of the collection) once there are enough movies
+async fn sync_collection(
+    pool: &SqlitePool,
+    item_id: &str,
+    collection: &CollectionRef,
+) -> Result<Option<String>> {
+    let tmdb_id = collection.id.to_string();
+    let movies: Vec<String> = sqlx::query_scalar(
+        "SELECT id FROM media_items WHERE item_type = 'Movie' AND tmdb_collection_id = ?
+         ORDER BY COALESCE(premiere_date, CAST(year AS TEXT)), sort_name",
+    )
+    .bind(&tmdb_id)
+    .fetch_all(pool)
+    .await?;
+
+    let existing: Option<String> =
+        sqlx::query_scalar("SELECT id FROM collections WHERE tmdb_collection_id = ?")
+            .bind(&tmdb_id)
+            .fetch_optional(pool)
+            .await?;
+    if !should_create(existing.is_some(), movies.len()) {
+        return Ok(None);
+    }
+
+    let poster_url = image_url(collection.poster_path.as_deref(), "w500");
+    let backdrop_url = image_url(collection.backdrop_path.as_deref(), "w1280");
+    let mut tx = pool.begin().await?;
+    let (collection_id, added) = match existing {
+        Some(id) => {
+            sqlx::query(
+                "UPDATE collections SET poster_url = COALESCE(?, poster_url),
+                    backdrop_url = COALESCE(?, backdrop_url) WHERE id = ?",
+            )
+            .bind(&poster_url)
+            .bind(&backdrop_url)
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+            // Only the movie at hand: movies an admin took out stay out
+            (id, vec![item_id.to_string()])
+        }
+        None => {
+            let id = uuid::Uuid::new_v4().to_string();
+            sqlx::query(
+                "INSERT INTO collections (id, name, sort_name, tmdb_collection_id, poster_url, backdrop_url)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&id)
+            .bind(&collection.name)
+            .bind(crate::scanner::sort_name::sort_name(&collection.name))
+            .bind(&tmdb_id)
+            .bind(&poster_url)
+            .bind(&backdrop_url)
+            .execute(&mut *tx)
+            .await?;
+            tracing::info!(
+                "Created collection '{}' for {} movies",
+                collection.name,
+                movies.len()
+            );
+            (id, movies)
+        }
+    };
+
+    // New movies go after the ones already there, which may have been reordered
+    for movie_id in &added {
+        sqlx::query(
+            "INSERT OR IGNORE INTO collection_items (collection_id, item_id, sort_order)
+             SELECT ?, ?, COALESCE(MAX(sort_order) + 1, 0) FROM collection_items WHERE collection_id = ?",
+        )
+        .bind(&collection_id)
+        .bind(movie_id)
+        .bind(&collection_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(Some(collection_id))
+}
+
+/// TMDB poster (Primary) or Backdrop URL of a collection, if it has one
+pub async fn artwork_url(
+    pool: &SqlitePool,
+    collection_id: &str,
+    image_type: &str,
+) -> Result<Option<String>> {
+    let column = match image_type {
+        "Primary" => "poster_url",
+        "Backdrop" => "backdrop_url",
+        _ => return Ok(None),
+    };
+    let url: Option<Option<String>> =
+        sqlx::query_scalar(&format!("SELECT {} FROM collections WHERE id = ?", column))
+            .bind(collection_id)
+            .fetch_optional(pool)
+            .await?;
+    Ok(url.flatten())
+}
+
+/// Image tag of a collection image, which changes with its URL
+pub fn artwork_tag(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// Delete TMDB collections whose movies are all gone; returns how many
+pub async fn remove_empty(pool: &SqlitePool) -> Result<u64> {
+    let removed = sqlx::query(
+        "DELETE FROM collections WHERE tmdb_collection_id IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM collection_items ci WHERE ci.collection_id = collections.id)",
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collection_creation_and_images() {
+        assert!(!should_create(false, 1));
+        assert!(should_create(false, MIN_MOVIES));
+        // An existing collection keeps getting movies, even a lone one
+        assert!(should_create(true, 1));
+
+        assert_eq!(
+            image_url(Some("/matrix.jpg"), "w500").as_deref(),
+            Some("https://image.tmdb.org/t/p/w500/matrix.jpg")
+        );
+        assert_eq!(image_url(None, "w500"), None);
+    }
+}
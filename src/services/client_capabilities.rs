@@ -0,0 +1,299 @@
+// Client capabilities
+//
+// Clients register what they can do with POST /Sessions/Capabilities(/Full):
+// the media types they play, the remote-control commands they handle, whether
+// they accept playstate commands, and a device profile describing the formats
+// they can direct play. They are stored on the client's active_sessions row.
+// A session that never registered keeps the old assumptions (video and audio,
+// the basic command set), so clients that skip the call still work. The kind
+// of device and the client's version are stored with the session too, so the
+// dashboard can show a device icon and version for each one.
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+/// Media types assumed for sessions that haven't registered capabilities
+pub const DEFAULT_MEDIA_TYPES: &[&str] = &["Video", "Audio"];
+
+/// Commands assumed for sessions that haven't registered capabilities
+pub const DEFAULT_COMMANDS: &[&str] = &[
+    "PlayState",
+    "Seek",
+    "PlayNext",
+    "PlayLast",
+    "DisplayMessage",
+];
+
+/// Jellyfin's ClientCapabilitiesDto
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct ClientCapabilities {
+    pub playable_media_types: Vec<String>,
+    pub supported_commands: Vec<String>,
+    pub supports_media_control: bool,
+    pub supports_persistent_identifier: bool,
+    pub device_profile: Option<Value>,
+    pub icon_url: Option<String>,
+}
+
+/// Words (lowercase) in client or device names that give away the kind of
+/// device, checked in order
+const DEVICE_TYPES: &[(&str, &[&str])] = &[
+    ("Roku", &["roku"]),
+    ("Kodi", &["kodi", "jellycon"]),
+    (
+        "TV",
+        &[
+            "android tv",
+            "androidtv",
+            "fire tv",
+            "firetv",
+            "apple tv",
+            "appletv",
+            "tvos",
+            "webos",
+            "tizen",
+            " tv ",
+        ],
+    ),
+    ("iOS", &["ios", "iphone", "ipad", "swiftfin", "infuse"]),
+    ("Android", &["android", "findroid"]),
+    ("Web", &["web", "browser", "chrome", "firefox", "safari"]),
+    (
+        "Desktop",
+        &[
+            "media player",
+            "jmp",
+            "desktop",
+            "windows",
+            "macos",
+            "linux",
+            "mpv",
+        ],
+    ),
+    ("Mobile", &["fladder", "finamp", "streamyfin"]),
+];
+
+/// Kind of device a session runs on (TV, iOS, Android, Web, ...), from its
+/// client and device names; "Unknown" when neither tells
+pub fn device_type(client: &str, device_name: &str) -> &'static str {
+    let names = format!(" {} {} ", client, device_name).to_lowercase();
+    DEVICE_TYPES
+        .iter()
+        .find(|(_, words)| words.iter().any(|w| names.contains(w)))
+        .map_or("Unknown", |(device_type, _)| device_type)
+}
+
+/// Split a comma-separated list as sent in query strings and stored in the database
+pub fn split_list(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Store a session's capabilities, replacing any registered before
+pub async fn store(pool: &SqlitePool, session_id: &str, caps: &ClientCapabilities) -> Result<()> {
+    let device_profile = match caps.device_profile {
+        Some(ref profile) if !profile.is_null() => Some(serde_json::to_string(profile)?),
+        _ => None,
+    };
+    sqlx::query(
+        "UPDATE active_sessions SET
+            playable_media_types = ?, supported_commands = ?, supports_media_control = ?,
+            supports_persistent_identifier = ?, device_profile = ?,
+            app_icon_url = COALESCE(?, app_icon_url), capabilities_updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(caps.playable_media_types.join(","))
+    .bind(caps.supported_commands.join(","))
+    .bind(caps.supports_media_control)
+    .bind(caps.supports_persistent_identifier)
+    .bind(device_profile)
+    .bind(caps.icon_url.as_deref().filter(|url| !url.is_empty()))
+    .bind(chrono::Utc::now().to_rfc3339())
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// The device profile registered by a user's device, if any
+pub async fn device_profile(pool: &SqlitePool, user_id: &str, device_id: &str) -> Option<Value> {
+    let row: Option<(Option<String>,)> = sqlx::query_as(
+        "SELECT device_profile FROM active_sessions WHERE user_id = ? AND device_id = ?",
+    )
+    .bind(user_id)
+    .bind(device_id)
+    .fetch_optional(pool)
+    .await
+    .ok()?;
+    serde_json::from_str(&row?.0?).ok()
+}
+
+/// Whether a comma-separated profile field allows a value (empty allows anything)
+pub(crate) fn list_allows(list: Option<&str>, value: Option<&str>) -> bool {
+    let allowed = split_list(list);
+    if allowed.is_empty() {
+        return true;
+    }
+    value.is_some_and(|v| allowed.iter().any(|a| a.eq_ignore_ascii_case(v)))
+}
+
+/// Whether a device profile's DirectPlayProfiles cover a file
+///
+/// None when the profile has no direct play profiles to judge by.
+pub fn can_direct_play(
+    profile: &Value,
+    media_type: &str,
+    container: Option<&str>,
+    video_codec: Option<&str>,
+    audio_codec: Option<&str>,
+) -> Option<bool> {
+    let profiles = profile.get("DirectPlayProfiles")?.as_array()?;
+    if profiles.is_empty() {
+        return None;
+    }
+    let field = |p: &Value, name: &str| p.get(name).and_then(Value::as_str).map(str::to_string);
+    Some(profiles.iter().any(|p| {
+        field(p, "Type").is_none_or(|t| t.eq_ignore_ascii_case(media_type))
+            && list_allows(field(p, "Container").as_deref(), container)
+            && (video_codec.is_none()
+                || list_allows(field(p, "VideoCodec").as_deref(), video_codec))
+            && (audio_codec.is_none()
+                || list_allows(field(p, "AudioCodec").as_deref(), audio_codec))
+    }))
+}
+
+/// Whether a device profile's SubtitleProfiles let the client load a subtitle
+/// format as a separate file
+///
+/// None when the profile has no subtitle profiles to judge by.
+pub fn can_load_external_subtitle(profile: &Value, format: &str) -> Option<bool> {
+    let profiles = profile.get("SubtitleProfiles")?.as_array()?;
+    if profiles.is_empty() {
+        return None;
+    }
+    let field = |p: &Value, name: &str| p.get(name).and_then(Value::as_str).map(str::to_string);
+    Some(profiles.iter().any(|p| {
+        field(p, "Method").is_some_and(|m| m.eq_ignore_ascii_case("External"))
+            && field(p, "Format").is_some_and(|f| f.eq_ignore_ascii_case(format))
+    }))
+}
+
+/// Whether a device profile's TranscodingProfiles take HLS for a media type
+///
+/// None when the profile has no transcoding profiles to judge by.
+pub fn can_play_hls(profile: &Value, media_type: &str) -> Option<bool> {
+    let profiles = profile.get("TranscodingProfiles")?.as_array()?;
+    if profiles.is_empty() {
+        return None;
+    }
+    let field = |p: &Value, name: &str| p.get(name).and_then(Value::as_str).map(str::to_string);
+    Some(profiles.iter().any(|p| {
+        field(p, "Type").is_none_or(|t| t.eq_ignore_ascii_case(media_type))
+            && field(p, "Protocol").is_some_and(|m| m.eq_ignore_ascii_case("hls"))
+    }))
+}
+
+/// A device profile's MaxStreamingBitrate, in bits/s
+pub fn max_streaming_bitrate(profile: &Value) -> Option<u32> {
+    profile
+        .get("MaxStreamingBitrate")
+        .and_then(Value::as_u64)
+        .filter(|b| *b > 0)
+        .map(|b| b.min(u32::MAX as u64) as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_type_from_client_and_device() {
+        assert_eq!(device_type("Jellyfin Android TV", "SHIELD"), "TV");
+        assert_eq!(device_type("Jellyfin Android", "Pixel 8"), "Android");
+        assert_eq!(device_type("Swiftfin", "Apple TV"), "TV");
+        assert_eq!(device_type("Swiftfin", "iPhone"), "iOS");
+        assert_eq!(device_type("Jellyfin Media Player", "DESKTOP-1"), "Desktop");
+        assert_eq!(device_type("Jellyfin Web", "Firefox"), "Web");
+        // The device name helps when the client name doesn't
+        assert_eq!(device_type("MyScript", "Chrome"), "Web");
+        assert_eq!(device_type("curl", "box"), "Unknown");
+    }
+
+    #[test]
+    fn test_can_direct_play_matches_profiles() {
+        let profile = serde_json::json!({
+            "DirectPlayProfiles": [
+                { "Type": "Video", "Container": "mp4,m4v", "VideoCodec": "h264", "AudioCodec": "aac,mp3" },
+                { "Type": "Video", "Container": "webm" },
+                { "Type": "Audio", "Container": "flac" }
+            ]
+        });
+
+        assert_eq!(
+            can_direct_play(&profile, "Video", Some("mp4"), Some("h264"), Some("aac")),
+            Some(true)
+        );
+        // Codec the mp4 profile doesn't list
+        assert_eq!(
+            can_direct_play(&profile, "Video", Some("mp4"), Some("hevc"), Some("aac")),
+            Some(false)
+        );
+        // Container no profile lists
+        assert_eq!(
+            can_direct_play(&profile, "Video", Some("mkv"), Some("h264"), Some("aac")),
+            Some(false)
+        );
+        // Profiles without codec lists accept any codec
+        assert_eq!(
+            can_direct_play(&profile, "Video", Some("webm"), Some("vp9"), Some("opus")),
+            Some(true)
+        );
+        assert_eq!(
+            can_direct_play(&serde_json::json!({}), "Video", Some("mkv"), None, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_can_load_external_subtitle() {
+        let profile = serde_json::json!({
+            "SubtitleProfiles": [
+                { "Format": "vtt", "Method": "External" },
+                { "Format": "srt", "Method": "Embed" }
+            ]
+        });
+
+        assert_eq!(can_load_external_subtitle(&profile, "VTT"), Some(true));
+        assert_eq!(can_load_external_subtitle(&profile, "srt"), Some(false));
+        assert_eq!(can_load_external_subtitle(&profile, "ass"), Some(false));
+        assert_eq!(
+            can_load_external_subtitle(&serde_json::json!({}), "srt"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_transcoding_profiles() {
+        let profile = serde_json::json!({
+            "MaxStreamingBitrate": 8000000,
+            "TranscodingProfiles": [
+                { "Type": "Audio", "Container": "mp3", "Protocol": "http" },
+                { "Type": "Video", "Container": "ts", "Protocol": "hls" }
+            ]
+        });
+
+        assert_eq!(can_play_hls(&profile, "Video"), Some(true));
+        assert_eq!(can_play_hls(&profile, "Audio"), Some(false));
+        assert_eq!(can_play_hls(&serde_json::json!({}), "Video"), None);
+        assert_eq!(max_streaming_bitrate(&profile), Some(8_000_000));
+        assert_eq!(max_streaming_bitrate(&serde_json::json!({})), None);
+    }
+}
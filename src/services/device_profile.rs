@@ -0,0 +1,432 @@
+// Device profile negotiation
+//
+// PlaybackInfo decides for each media source how the client plays it, much as
+// Jellyfin's StreamBuilder does. The device profile comes from the PlaybackInfo
+// body or, for clients that send none, the one registered with the session.
+// A source is direct played when a DirectPlayProfile covers its container and
+// codecs, every CodecProfile condition holds for the probed streams (level,
+// resolution, bit depth, channels, ...) and its bitrate is within the limit.
+// When only the container is the problem the streams are copied unchanged into
+// a container the client does list (direct stream); anything else is
+// transcoded. The reasons are Jellyfin's TranscodeReason names, so clients
+// and the dashboard can say why a file wasn't direct played.
+
+use serde_json::Value;
+
+use super::client_capabilities::{can_direct_play, list_allows, split_list};
+use super::external_streams::RemuxContainer;
+
+/// How a client plays a source (Jellyfin's PlayMethod)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayMethod {
+    DirectPlay,
+    /// Streams copied into another container
+    DirectStream,
+    Transcode,
+}
+
+/// The probed video stream of a source
+#[derive(Debug, Clone, Default)]
+pub struct VideoProperties {
+    pub codec: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub bitrate: Option<i64>,
+    pub level: Option<f64>,
+    pub profile: Option<String>,
+    pub bit_depth: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub is_interlaced: Option<bool>,
+}
+
+/// The probed audio stream a source plays with
+#[derive(Debug, Clone, Default)]
+pub struct AudioProperties {
+    pub codec: Option<String>,
+    pub channels: Option<i32>,
+    pub sample_rate: Option<i32>,
+    pub bitrate: Option<i64>,
+}
+
+/// What a decision is made from
+#[derive(Debug, Clone, Default)]
+pub struct SourceProperties {
+    /// "Video" or "Audio"
+    pub media_type: &'static str,
+    pub container: Option<String>,
+    pub bitrate: Option<i64>,
+    pub video: Option<VideoProperties>,
+    pub audio: Option<AudioProperties>,
+}
+
+/// Limits from the PlaybackInfo request, on top of the profile's own
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlaybackLimits {
+    /// Bits/s; the profile's MaxStreamingBitrate when the request sets none
+    pub max_bitrate: Option<u32>,
+    pub max_audio_channels: Option<i32>,
+    pub enable_direct_play: bool,
+    pub enable_direct_stream: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaybackDecision {
+    pub method: PlayMethod,
+    /// Container the streams are copied into for DirectStream
+    pub remux_container: Option<RemuxContainer>,
+    /// Why the source isn't direct played (Jellyfin's TranscodeReason names)
+    pub reasons: Vec<&'static str>,
+}
+
+fn field(value: &Value, name: &str) -> Option<String> {
+    value.get(name).and_then(Value::as_str).map(str::to_string)
+}
+
+/// A profile's entries in `key` that apply to a media type
+fn profiles_of<'a>(profile: &'a Value, key: &str, media_type: &str) -> Vec<&'a Value> {
+    profile
+        .get(key)
+        .and_then(Value::as_array)
+        .map(|profiles| {
+            profiles
+                .iter()
+                .filter(|p| field(p, "Type").is_none_or(|t| t.eq_ignore_ascii_case(media_type)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The value of a condition's property for a source, as text; None when the
+/// stream doesn't have it or the property isn't one we know
+fn property(source: &SourceProperties, name: &str) -> Option<String> {
+    let video = source.video.as_ref();
+    let audio = source.audio.as_ref();
+    let number = |n: Option<f64>| n.map(|n| n.to_string());
+    match name {
+        "Width" => number(video?.width.map(f64::from)),
+        "Height" => number(video?.height.map(f64::from)),
+        "VideoBitDepth" => number(video?.bit_depth.map(f64::from)),
+        "VideoLevel" => number(video?.level),
+        "VideoProfile" => video?.profile.clone(),
+        "VideoBitrate" => number(video?.bitrate.map(|b| b as f64)),
+        "VideoFramerate" => number(video?.frame_rate),
+        "IsInterlaced" => video?.is_interlaced.map(|i| i.to_string()),
+        "AudioChannels" => number(audio?.channels.map(f64::from)),
+        "AudioSampleRate" => number(audio?.sample_rate.map(f64::from)),
+        "AudioBitrate" => number(audio?.bitrate.map(|b| b as f64)),
+        _ => None,
+    }
+}
+
+/// The TranscodeReason for a failed condition on a property
+fn condition_reason(property: &str) -> Option<&'static str> {
+    Some(match property {
+        "Width" | "Height" => "VideoResolutionNotSupported",
+        "VideoBitDepth" => "VideoBitDepthNotSupported",
+        "VideoLevel" => "VideoLevelNotSupported",
+        "VideoProfile" => "VideoProfileNotSupported",
+        "VideoBitrate" => "VideoBitrateNotSupported",
+        "VideoFramerate" => "VideoFramerateNotSupported",
+        "IsInterlaced" => "InterlacedVideoNotSupported",
+        "AudioChannels" => "AudioChannelsNotSupported",
+        "AudioSampleRate" => "AudioSampleRateNotSupported",
+        "AudioBitrate" => "AudioBitrateNotSupported",
+        _ => return None,
+    })
+}
+
+/// Whether a ProfileCondition holds for a value
+///
+/// Numbers compare as numbers, anything else as case-insensitive text.
+/// EqualsAny takes a pipe-separated list. A value the stream doesn't have
+/// only fails conditions marked IsRequired.
+fn condition_holds(condition: &Value, value: Option<&str>) -> bool {
+    let Some(value) = value else {
+        return !condition
+            .get("IsRequired")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+    };
+    let expected = field(condition, "Value").unwrap_or_default();
+    let equals = |expected: &str| match (value.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => value.eq_ignore_ascii_case(expected),
+    };
+    let compare = || value.parse::<f64>().ok().zip(expected.parse::<f64>().ok());
+    match field(condition, "Condition").as_deref() {
+        Some("Equals") => equals(&expected),
+        Some("NotEquals") => !equals(&expected),
+        Some("EqualsAny") => expected.split('|').any(|e| equals(e.trim())),
+        Some("LessThanEqual") => compare().is_none_or(|(a, b)| a <= b),
+        Some("GreaterThanEqual") => compare().is_none_or(|(a, b)| a >= b),
+        _ => true,
+    }
+}
+
+/// Reasons the profile's CodecProfiles of `codec_type` ("Video", "VideoAudio"
+/// or "Audio") rule out a stream
+fn codec_profile_reasons(
+    profile: &Value,
+    codec_type: &str,
+    codec: Option<&str>,
+    source: &SourceProperties,
+) -> Vec<&'static str> {
+    let mut reasons = Vec::new();
+    for codec_profile in profiles_of(profile, "CodecProfiles", codec_type) {
+        // A profile without a type would apply to every stream; Jellyfin requires one
+        if field(codec_profile, "Type").is_none()
+            || !list_allows(field(codec_profile, "Codec").as_deref(), codec)
+            || !list_allows(
+                field(codec_profile, "Container").as_deref(),
+                source.container.as_deref(),
+            )
+        {
+            continue;
+        }
+        let conditions = codec_profile
+            .get("Conditions")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for condition in conditions {
+            let Some(name) = field(condition, "Property") else {
+                continue;
+            };
+            let Some(reason) = condition_reason(&name) else {
+                continue;
+            };
+            if !condition_holds(condition, property(source, &name).as_deref())
+                && !reasons.contains(&reason)
+            {
+                reasons.push(reason);
+            }
+        }
+    }
+    reasons
+}
+
+/// Decide how a client plays a source
+///
+/// Without a profile (or one without DirectPlayProfiles) only the bitrate
+/// limit and the request's switches can rule out direct play.
+pub fn decide(
+    profile: Option<&Value>,
+    source: &SourceProperties,
+    limits: &PlaybackLimits,
+) -> PlaybackDecision {
+    let video_codec = source.video.as_ref().and_then(|v| v.codec.as_deref());
+    let audio_codec = source.audio.as_ref().and_then(|a| a.codec.as_deref());
+    let mut reasons: Vec<&'static str> = Vec::new();
+    let mut direct_play_profiles = Vec::new();
+
+    if let Some(profile) = profile {
+        direct_play_profiles = profiles_of(profile, "DirectPlayProfiles", source.media_type);
+        if !direct_play_profiles.is_empty() {
+            let allows = |p: &Value, key: &str, value: Option<&str>| {
+                value.is_none() || list_allows(field(p, key).as_deref(), value)
+            };
+            let container_profiles: Vec<&Value> = direct_play_profiles
+                .iter()
+                .copied()
+                .filter(|p| allows(p, "Container", source.container.as_deref()))
+                .collect();
+            // Codecs are judged against the profiles of the file's container
+            // when there are any, so an unsupported container isn't also
+            // blamed on its codecs
+            let codec_profiles = if container_profiles.is_empty() {
+                reasons.push("ContainerNotSupported");
+                &direct_play_profiles
+            } else {
+                &container_profiles
+            };
+            if !codec_profiles
+                .iter()
+                .any(|p| allows(p, "VideoCodec", video_codec))
+            {
+                reasons.push("VideoCodecNotSupported");
+            }
+            if !codec_profiles
+                .iter()
+                .any(|p| allows(p, "AudioCodec", audio_codec))
+            {
+                reasons.push("AudioCodecNotSupported");
+            }
+            // Each is allowed by some profile, but no one profile allows all three
+            if reasons.is_empty()
+                && can_direct_play(
+                    profile,
+                    source.media_type,
+                    source.container.as_deref(),
+                    video_codec,
+                    audio_codec,
+                ) == Some(false)
+            {
+                reasons.push("DirectPlayError");
+            }
+        }
+
+        if source.video.is_some() {
+            reasons.extend(codec_profile_reasons(profile, "Video", video_codec, source));
+            reasons.extend(codec_profile_reasons(
+                profile,
+                "VideoAudio",
+                audio_codec,
+                source,
+            ));
+        } else {
+            reasons.extend(codec_profile_reasons(profile, "Audio", audio_codec, source));
+        }
+    }
+
+    let channels = source.audio.as_ref().and_then(|a| a.channels);
+    if limits
+        .max_audio_channels
+        .zip(channels)
+        .is_some_and(|(max, channels)| max > 0 && channels > max)
+        && !reasons.contains(&"AudioChannelsNotSupported")
+    {
+        reasons.push("AudioChannelsNotSupported");
+    }
+    let max_bitrate = limits
+        .max_bitrate
+        .or_else(|| profile.and_then(super::client_capabilities::max_streaming_bitrate));
+    if max_bitrate
+        .zip(source.bitrate)
+        .is_some_and(|(max, bitrate)| bitrate > max as i64)
+    {
+        reasons.push("ContainerBitrateExceedsLimit");
+    }
+
+    if reasons.is_empty() && limits.enable_direct_play {
+        return PlaybackDecision {
+            method: PlayMethod::DirectPlay,
+            remux_container: None,
+            reasons,
+        };
+    }
+
+    // Copying the streams only helps when nothing but the container is wrong
+    let container_only = reasons
+        .iter()
+        .all(|r| matches!(*r, "ContainerNotSupported" | "DirectPlayError"));
+    let remux_container = (limits.enable_direct_stream && container_only)
+        .then(|| remux_container(&direct_play_profiles, video_codec, audio_codec))
+        .flatten();
+    PlaybackDecision {
+        method: match remux_container {
+            Some(_) => PlayMethod::DirectStream,
+            None => PlayMethod::Transcode,
+        },
+        remux_container,
+        reasons,
+    }
+}
+
+/// The first container the client direct plays that the source's codecs can
+/// be copied into, in the profile's order
+fn remux_container(
+    direct_play_profiles: &[&Value],
+    video_codec: Option<&str>,
+    audio_codec: Option<&str>,
+) -> Option<RemuxContainer> {
+    direct_play_profiles.iter().find_map(|p| {
+        let codecs_allowed = (video_codec.is_none()
+            || list_allows(field(p, "VideoCodec").as_deref(), video_codec))
+            && (audio_codec.is_none()
+                || list_allows(field(p, "AudioCodec").as_deref(), audio_codec));
+        if !codecs_allowed {
+            return None;
+        }
+        split_list(field(p, "Container").as_deref())
+            .iter()
+            .filter_map(|c| RemuxContainer::from_name(c))
+            .find(|c| c.can_hold(video_codec, audio_codec))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide_play_method() {
+        let profile = serde_json::json!({
+            "DirectPlayProfiles": [
+                { "Type": "Video", "Container": "mp4,m4v", "VideoCodec": "h264", "AudioCodec": "aac,mp3" }
+            ],
+            "CodecProfiles": [
+                { "Type": "Video", "Codec": "h264", "Conditions": [
+                    { "Condition": "LessThanEqual", "Property": "VideoLevel", "Value": "41" },
+                    { "Condition": "LessThanEqual", "Property": "VideoBitDepth", "Value": "8", "IsRequired": false }
+                ]},
+                { "Type": "VideoAudio", "Conditions": [
+                    { "Condition": "LessThanEqual", "Property": "AudioChannels", "Value": "6" }
+                ]}
+            ]
+        });
+        let source = |container: &str, level: f64, channels: i32| SourceProperties {
+            media_type: "Video",
+            container: Some(container.to_string()),
+            bitrate: Some(4_000_000),
+            video: Some(VideoProperties {
+                codec: Some("h264".to_string()),
+                level: Some(level),
+                ..Default::default()
+            }),
+            audio: Some(AudioProperties {
+                codec: Some("aac".to_string()),
+                channels: Some(channels),
+                ..Default::default()
+            }),
+        };
+        let limits = PlaybackLimits {
+            enable_direct_play: true,
+            enable_direct_stream: true,
+            ..Default::default()
+        };
+        let decide = |source: &SourceProperties, limits: &PlaybackLimits| {
+            decide(Some(&profile), source, limits)
+        };
+
+        let decision = decide(&source("mp4", 41.0, 2), &limits);
+        assert_eq!(decision.method, PlayMethod::DirectPlay);
+        assert!(decision.reasons.is_empty());
+
+        // Only the container is wrong: the streams are copied into mp4
+        let decision = decide(&source("mkv", 41.0, 2), &limits);
+        assert_eq!(decision.method, PlayMethod::DirectStream);
+        assert_eq!(decision.remux_container, Some(RemuxContainer::Mp4));
+        assert_eq!(decision.reasons, ["ContainerNotSupported"]);
+
+        let decision = decide(&source("mkv", 51.0, 8), &limits);
+        assert_eq!(decision.method, PlayMethod::Transcode);
+        assert_eq!(
+            decision.reasons,
+            [
+                "ContainerNotSupported",
+                "VideoLevelNotSupported",
+                "AudioChannelsNotSupported"
+            ]
+        );
+
+        let capped = PlaybackLimits {
+            max_bitrate: Some(2_000_000),
+            ..limits
+        };
+        let decision = decide(&source("mp4", 41.0, 2), &capped);
+        assert_eq!(decision.method, PlayMethod::Transcode);
+        assert_eq!(decision.reasons, ["ContainerBitrateExceedsLimit"]);
+
+        // Without a profile only the request's switches and limits count
+        let decision = super::decide(None, &source("mkv", 51.0, 8), &limits);
+        assert_eq!(decision.method, PlayMethod::DirectPlay);
+        assert!(!condition_holds(
+            &serde_json::json!({ "Condition": "Equals", "Value": "High", "IsRequired": true }),
+            None
+        ));
+        assert!(condition_holds(
+            &serde_json::json!({ "Condition": "EqualsAny", "Value": "main|high" }),
+            Some("High")
+        ));
+    }
+}
@@ -0,0 +1,675 @@
+// DLNA media server
+//
+// Smart TVs, consoles and players without a Jellyfin app find media servers
+// with SSDP (UDP multicast on 239.255.255.250:1900) and browse them through
+// UPnP's ContentDirectory service. With [dlna] enabled, the server announces
+// itself as a MediaServer every few minutes, answers M-SEARCH queries and says
+// goodbye at shutdown; api::dlna serves the device description, the SOAP
+// control endpoints and the files under /dlna. This module holds the SSDP task
+// and the XML documents both sides exchange. DLNA clients can't sign in, so
+// they browse as one configured account, and only clients on the local
+// network are answered.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use super::xml;
+use crate::config::DlnaConfig;
+use crate::events::{self, ServerEvent};
+
+/// SSDP multicast group and port
+const SSDP_ADDR: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+
+pub const DEVICE_TYPE: &str = "urn:schemas-upnp-org:device:MediaServer:1";
+pub const CONTENT_DIRECTORY: &str = "urn:schemas-upnp-org:service:ContentDirectory:1";
+pub const CONNECTION_MANAGER: &str = "urn:schemas-upnp-org:service:ConnectionManager:1";
+
+/// DLNA flags of every served file: byte seeking, streaming transfer, DLNA 1.5
+pub const DLNA_FEATURES: &str =
+    "DLNA.ORG_OP=01;DLNA.ORG_CI=0;DLNA.ORG_FLAGS=01700000000000000000000000000000";
+
+const SERVER_HEADER: &str = concat!(
+    "Linux/1.0 UPnP/1.0 jellyfin-rust/",
+    env!("CARGO_PKG_VERSION")
+);
+
+// Bumped when library contents change, so clients that cache browse results reload them
+static SYSTEM_UPDATE_ID: AtomicU32 = AtomicU32::new(1);
+
+/// The ContentDirectory's SystemUpdateID
+pub fn system_update_id() -> u32 {
+    SYSTEM_UPDATE_ID.load(Ordering::Relaxed)
+}
+
+/// The device's UDN, the server ID as a UUID
+pub fn device_uuid() -> String {
+    let server_id = super::server_id::get();
+    uuid::Uuid::parse_str(server_id)
+        .map(|id| id.hyphenated().to_string())
+        .unwrap_or_else(|_| server_id.to_string())
+}
+
+/// Whether an address is on the local network (private, link-local or loopback)
+pub fn is_local_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_private() || ip.is_link_local() || ip.is_loopback(),
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_local_address(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            ip.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+    }
+}
+
+/// Account DLNA clients browse as: the configured one, or else the first admin
+///
+/// Disabled accounts browse nothing.
+pub async fn browse_user(pool: &SqlitePool, config: &DlnaConfig) -> Result<Option<String>> {
+    let user_id = match config.user {
+        Some(ref name) => {
+            sqlx::query_scalar("SELECT id FROM users WHERE name = ? AND is_disabled = 0")
+                .bind(name)
+                .fetch_optional(pool)
+                .await?
+        }
+        None => {
+            sqlx::query_scalar(
+                "SELECT id FROM users WHERE is_admin = 1 AND is_disabled = 0 ORDER BY created_at LIMIT 1",
+            )
+            .fetch_optional(pool)
+            .await?
+        }
+    };
+    Ok(user_id)
+}
+
+// --- SSDP ---
+
+/// Everything announced, as (NT or ST, USN): the root device, the device and its services
+fn advertisements(uuid: &str) -> Vec<(String, String)> {
+    let mut ads = vec![
+        (
+            "upnp:rootdevice".to_string(),
+            format!("uuid:{}::upnp:rootdevice", uuid),
+        ),
+        (format!("uuid:{}", uuid), format!("uuid:{}", uuid)),
+    ];
+    for target in [DEVICE_TYPE, CONTENT_DIRECTORY, CONNECTION_MANAGER] {
+        ads.push((target.to_string(), format!("uuid:{}::{}", uuid, target)));
+    }
+    ads
+}
+
+/// The search target of an M-SEARCH request, or None for any other datagram
+fn search_target(datagram: &str) -> Option<String> {
+    let mut lines = datagram.lines();
+    if !lines
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("M-SEARCH * HTTP/1.1")
+    {
+        return None;
+    }
+    lines
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("ST"))
+        .map(|(_, value)| value.trim().to_string())
+}
+
+/// Advertisements answering a search target
+fn matching_advertisements<'a>(
+    ads: &'a [(String, String)],
+    target: &str,
+) -> impl Iterator<Item = &'a (String, String)> {
+    let target = target.to_string();
+    ads.iter()
+        .filter(move |(nt, _)| target == "ssdp:all" || *nt == target)
+}
+
+fn notify_message(location: &str, nt: &str, usn: &str, max_age: u64, alive: bool) -> String {
+    if alive {
+        format!(
+            "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nCACHE-CONTROL: max-age={}\r\nLOCATION: {}\r\n\
+             NT: {}\r\nNTS: ssdp:alive\r\nSERVER: {}\r\nUSN: {}\r\n\r\n",
+            SSDP_ADDR, SSDP_PORT, max_age, location, nt, SERVER_HEADER, usn
+        )
+    } else {
+        format!(
+            "NOTIFY * HTTP/1.1\r\nHOST: {}:{}\r\nNT: {}\r\nNTS: ssdp:byebye\r\nUSN: {}\r\n\r\n",
+            SSDP_ADDR, SSDP_PORT, nt, usn
+        )
+    }
+}
+
+fn search_response(location: &str, st: &str, usn: &str, max_age: u64) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nCACHE-CONTROL: max-age={}\r\nDATE: {}\r\nEXT:\r\nLOCATION: {}\r\n\
+         SERVER: {}\r\nST: {}\r\nUSN: {}\r\n\r\n",
+        max_age,
+        chrono::Utc::now().format("%a, %d %b %Y %H:%M:%S GMT"),
+        location,
+        SERVER_HEADER,
+        st,
+        usn
+    )
+}
+
+/// Bind the SSDP port alongside any other UPnP software and join the multicast group
+fn bind_ssdp_socket() -> std::io::Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, SSDP_PORT)).into())?;
+    socket.join_multicast_v4(&SSDP_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    UdpSocket::from_std(socket.into())
+}
+
+/// Where the device description is reached from `peer`
+///
+/// The address is the one the server listens on, or else the one of the
+/// interface traffic to `peer` leaves through.
+fn description_url(bind_address: &str, port: u16, peer: SocketAddr) -> Option<String> {
+    let ip = match bind_address.parse::<IpAddr>() {
+        Ok(ip) if !ip.is_unspecified() => ip,
+        _ => {
+            let probe = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+            probe.connect(peer).ok()?;
+            probe.local_addr().ok()?.ip()
+        }
+    };
+    Some(format!("http://{}:{}/dlna/description.xml", ip, port))
+}
+
+/// Announce the server on the local network and answer searches until shutdown
+pub async fn run(config: DlnaConfig, port: u16, bind_address: String, cancel: CancellationToken) {
+    let socket = match bind_ssdp_socket() {
+        Ok(socket) => socket,
+        Err(e) => {
+            tracing::warn!(
+                "DLNA unavailable: can't listen on SSDP port {}: {}",
+                SSDP_PORT,
+                e
+            );
+            return;
+        }
+    };
+    let multicast = SocketAddr::from((SSDP_ADDR, SSDP_PORT));
+    let interval = Duration::from_secs(config.announce_interval_seconds.max(60));
+    // Clients forget the server if two announcements in a row get lost
+    let max_age = (interval.as_secs() * 2).max(1800);
+    let ads = advertisements(&device_uuid());
+    let mut announce = tokio::time::interval(interval);
+    let mut events = events::subscribe();
+    let mut buf = [0u8; 2048];
+
+    tracing::info!(
+        "DLNA server '{}' announced on the local network",
+        config.friendly_name
+    );
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                for (nt, usn) in &ads {
+                    let message = notify_message("", nt, usn, max_age, false);
+                    let _ = socket.send_to(message.as_bytes(), multicast).await;
+                }
+                break;
+            }
+            _ = announce.tick() => {
+                let Some(location) = description_url(&bind_address, port, multicast) else {
+                    tracing::debug!("No network route for DLNA announcements");
+                    continue;
+                };
+                for (nt, usn) in &ads {
+                    let message = notify_message(&location, nt, usn, max_age, true);
+                    if let Err(e) = socket.send_to(message.as_bytes(), multicast).await {
+                        tracing::debug!("Failed to send DLNA announcement: {}", e);
+                        break;
+                    }
+                }
+            }
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = match received {
+                    Ok(received) => received,
+                    Err(e) => {
+                        tracing::debug!("SSDP receive failed: {}", e);
+                        continue;
+                    }
+                };
+                if !is_local_address(peer.ip()) {
+                    continue;
+                }
+                let Some(target) = search_target(&String::from_utf8_lossy(&buf[..len])) else {
+                    continue;
+                };
+                let Some(location) = description_url(&bind_address, port, peer) else {
+                    continue;
+                };
+                for (st, usn) in matching_advertisements(&ads, &target) {
+                    let response = search_response(&location, st, usn, max_age);
+                    let _ = socket.send_to(response.as_bytes(), peer).await;
+                }
+            }
+            event = events.recv() => match event {
+                Ok(ServerEvent::ItemAdded { .. })
+                | Ok(ServerEvent::ItemUpdated { .. })
+                | Ok(ServerEvent::ItemRemoved { .. })
+                | Err(broadcast::error::RecvError::Lagged(_)) => {
+                    SYSTEM_UPDATE_ID.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(_) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        }
+    }
+}
+
+// --- Device and service descriptions ---
+
+/// Escape text for XML content and attribute values
+pub fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The root device description at /dlna/description.xml
+pub fn device_description(friendly_name: &str) -> String {
+    let service = |service_type: &str, name: &str| {
+        format!(
+            "<service><serviceType>{}</serviceType><serviceId>urn:upnp-org:serviceId:{}</serviceId>\
+             <SCPDURL>/dlna/{}.xml</SCPDURL><controlURL>/dlna/control/{}</controlURL>\
+             <eventSubURL>/dlna/events/{}</eventSubURL></service>",
+            service_type, name, name, name, name
+        )
+    };
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<root xmlns="urn:schemas-upnp-org:device-1-0" xmlns:dlna="urn:schemas-dlna-org:device-1-0">
+<specVersion><major>1</major><minor>0</minor></specVersion>
+<device>
+<deviceType>{}</deviceType>
+<dlna:X_DLNADOC>DMS-1.50</dlna:X_DLNADOC>
+<friendlyName>{}</friendlyName>
+<manufacturer>jellyfin-rust</manufacturer>
+<manufacturerURL>https://github.com/imaviso/jellyfin-rust</manufacturerURL>
+<modelName>jellyfin-rust</modelName>
+<modelNumber>{}</modelNumber>
+<UDN>uuid:{}</UDN>
+<presentationURL>/</presentationURL>
+<serviceList>{}{}</serviceList>
+</device>
+</root>"#,
+        DEVICE_TYPE,
+        xml_escape(friendly_name),
+        env!("CARGO_PKG_VERSION"),
+        device_uuid(),
+        service(CONTENT_DIRECTORY, "ContentDirectory"),
+        service(CONNECTION_MANAGER, "ConnectionManager"),
+    )
+}
+
+/// An action's arguments, as (name, direction, related state variable)
+type ActionArguments<'a> = &'a [(&'a str, &'a str, &'a str)];
+
+/// A service description (SCPD) from its actions and state variables
+fn service_description(
+    actions: &[(&str, ActionArguments)],
+    state_variables: &[(&str, &str, bool)],
+) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <scpd xmlns=\"urn:schemas-upnp-org:service-1-0\">\
+         <specVersion><major>1</major><minor>0</minor></specVersion><actionList>",
+    );
+    for (name, arguments) in actions {
+        xml.push_str(&format!("<action><name>{}</name><argumentList>", name));
+        for (argument, direction, variable) in arguments.iter() {
+            xml.push_str(&format!(
+                "<argument><name>{}</name><direction>{}</direction>\
+                 <relatedStateVariable>{}</relatedStateVariable></argument>",
+                argument, direction, variable
+            ));
+        }
+        xml.push_str("</argumentList></action>");
+    }
+    xml.push_str("</actionList><serviceStateTable>");
+    for (name, data_type, send_events) in state_variables {
+        xml.push_str(&format!(
+            "<stateVariable sendEvents=\"{}\"><name>{}</name><dataType>{}</dataType></stateVariable>",
+            if *send_events { "yes" } else { "no" },
+            name,
+            data_type
+        ));
+    }
+    xml.push_str("</serviceStateTable></scpd>");
+    xml
+}
+
+/// The ContentDirectory description at /dlna/ContentDirectory.xml
+pub fn content_directory_description() -> String {
+    service_description(
+        &[
+            (
+                "Browse",
+                &[
+                    ("ObjectID", "in", "A_ARG_TYPE_ObjectID"),
+                    ("BrowseFlag", "in", "A_ARG_TYPE_BrowseFlag"),
+                    ("Filter", "in", "A_ARG_TYPE_Filter"),
+                    ("StartingIndex", "in", "A_ARG_TYPE_Index"),
+                    ("RequestedCount", "in", "A_ARG_TYPE_Count"),
+                    ("SortCriteria", "in", "A_ARG_TYPE_SortCriteria"),
+                    ("Result", "out", "A_ARG_TYPE_Result"),
+                    ("NumberReturned", "out", "A_ARG_TYPE_Count"),
+                    ("TotalMatches", "out", "A_ARG_TYPE_Count"),
+                    ("UpdateID", "out", "A_ARG_TYPE_UpdateID"),
+                ],
+            ),
+            (
+                "GetSearchCapabilities",
+                &[("SearchCaps", "out", "SearchCapabilities")],
+            ),
+            (
+                "GetSortCapabilities",
+                &[("SortCaps", "out", "SortCapabilities")],
+            ),
+            ("GetSystemUpdateID", &[("Id", "out", "SystemUpdateID")]),
+        ],
+        &[
+            ("A_ARG_TYPE_ObjectID", "string", false),
+            ("A_ARG_TYPE_BrowseFlag", "string", false),
+            ("A_ARG_TYPE_Filter", "string", false),
+            ("A_ARG_TYPE_Index", "ui4", false),
+            ("A_ARG_TYPE_Count", "ui4", false),
+            ("A_ARG_TYPE_SortCriteria", "string", false),
+            ("A_ARG_TYPE_Result", "string", false),
+            ("A_ARG_TYPE_UpdateID", "ui4", false),
+            ("SearchCapabilities", "string", false),
+            ("SortCapabilities", "string", false),
+            ("SystemUpdateID", "ui4", true),
+        ],
+    )
+}
+
+/// The ConnectionManager description at /dlna/ConnectionManager.xml
+pub fn connection_manager_description() -> String {
+    service_description(
+        &[
+            (
+                "GetProtocolInfo",
+                &[
+                    ("Source", "out", "SourceProtocolInfo"),
+                    ("Sink", "out", "SinkProtocolInfo"),
+                ],
+            ),
+            (
+                "GetCurrentConnectionIDs",
+                &[("ConnectionIDs", "out", "CurrentConnectionIDs")],
+            ),
+            (
+                "GetCurrentConnectionInfo",
+                &[
+                    ("ConnectionID", "in", "A_ARG_TYPE_ConnectionID"),
+                    ("RcsID", "out", "A_ARG_TYPE_RcsID"),
+                    ("AVTransportID", "out", "A_ARG_TYPE_AVTransportID"),
+                    ("ProtocolInfo", "out", "A_ARG_TYPE_ProtocolInfo"),
+                    (
+                        "PeerConnectionManager",
+                        "out",
+                        "A_ARG_TYPE_ConnectionManager",
+                    ),
+                    ("PeerConnectionID", "out", "A_ARG_TYPE_ConnectionID"),
+                    ("Direction", "out", "A_ARG_TYPE_Direction"),
+                    ("Status", "out", "A_ARG_TYPE_ConnectionStatus"),
+                ],
+            ),
+        ],
+        &[
+            ("SourceProtocolInfo", "string", true),
+            ("SinkProtocolInfo", "string", true),
+            ("CurrentConnectionIDs", "string", true),
+            ("A_ARG_TYPE_ConnectionID", "i4", false),
+            ("A_ARG_TYPE_RcsID", "i4", false),
+            ("A_ARG_TYPE_AVTransportID", "i4", false),
+            ("A_ARG_TYPE_ProtocolInfo", "string", false),
+            ("A_ARG_TYPE_ConnectionManager", "string", false),
+            ("A_ARG_TYPE_Direction", "string", false),
+            ("A_ARG_TYPE_ConnectionStatus", "string", false),
+        ],
+    )
+}
+
+// --- SOAP ---
+
+/// The action named by a SOAPACTION header ("urn:...:ContentDirectory:1#Browse")
+pub fn soap_action(header: &str) -> Option<&str> {
+    let (_, action) = header.trim().trim_matches('"').rsplit_once('#')?;
+    Some(action)
+}
+
+/// The arguments of a SOAP request (Browse's ObjectID, ...) by name; none
+/// when the body isn't a SOAP envelope
+pub fn soap_arguments(body: &str) -> HashMap<String, String> {
+    let Ok(envelope) = xml::parse(body) else {
+        return HashMap::new();
+    };
+    let Some(action) = envelope.child("Body").and_then(|b| b.children.first()) else {
+        return HashMap::new();
+    };
+    action
+        .children
+        .iter()
+        .map(|argument| {
+            let value = argument.text().unwrap_or_default();
+            (argument.name.clone(), value.to_string())
+        })
+        .collect()
+}
+
+/// A SOAP response to `action` of `service`
+pub fn soap_response(service: &str, action: &str, arguments: &[(&str, String)]) -> String {
+    let mut body = String::new();
+    for (name, value) in arguments {
+        body.push_str(&format!("<{}>{}</{}>", name, xml_escape(value), name));
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body>\
+         <u:{action}Response xmlns:u=\"{service}\">{body}</u:{action}Response>\
+         </s:Body></s:Envelope>"
+    )
+}
+
+/// A UPnP error (401 invalid action, 402 invalid args, 701 no such object)
+pub fn soap_fault(code: u32, description: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><s:Fault>\
+         <faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail>\
+         <UPnPError xmlns=\"urn:schemas-upnp-org:control-1-0\"><errorCode>{}</errorCode>\
+         <errorDescription>{}</errorDescription></UPnPError></detail></s:Fault></s:Body></s:Envelope>",
+        code,
+        xml_escape(description)
+    )
+}
+
+// --- DIDL-Lite ---
+
+/// A file an item is played from
+#[derive(Debug, Clone)]
+pub struct DidlResource {
+    pub url: String,
+    pub mime_type: &'static str,
+    pub size: Option<u64>,
+    pub duration_ticks: Option<i64>,
+    pub resolution: Option<(i32, i32)>,
+}
+
+/// A container (library, series, album) or item (movie, episode, track) in a Browse result
+#[derive(Debug, Clone)]
+pub struct DidlObject {
+    pub id: String,
+    pub parent_id: String,
+    pub title: String,
+    /// UPnP class, such as object.item.videoItem.movie
+    pub class: &'static str,
+    /// Set for containers, which is what makes them one
+    pub child_count: Option<i32>,
+    pub resource: Option<DidlResource>,
+    pub album_art_url: Option<String>,
+}
+
+/// Duration as DIDL-Lite writes it (H:MM:SS.mmm)
+fn didl_duration(ticks: i64) -> String {
+    let millis = ticks / 10_000;
+    format!(
+        "{}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// A Browse result document
+pub fn didl_lite(objects: &[DidlObject]) -> String {
+    let mut xml = String::from(
+        "<DIDL-Lite xmlns=\"urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\" \
+         xmlns:upnp=\"urn:schemas-upnp-org:metadata-1-0/upnp/\" \
+         xmlns:dlna=\"urn:schemas-dlna-org:metadata-1-0/\">",
+    );
+    for object in objects {
+        let element = if object.child_count.is_some() {
+            "container"
+        } else {
+            "item"
+        };
+        xml.push_str(&format!(
+            "<{} id=\"{}\" parentID=\"{}\" restricted=\"1\"",
+            element,
+            xml_escape(&object.id),
+            xml_escape(&object.parent_id)
+        ));
+        if let Some(count) = object.child_count {
+            xml.push_str(&format!(" childCount=\"{}\" searchable=\"0\"", count));
+        }
+        xml.push_str(&format!(
+            "><dc:title>{}</dc:title><upnp:class>{}</upnp:class>",
+            xml_escape(&object.title),
+            object.class
+        ));
+        if let Some(ref url) = object.album_art_url {
+            xml.push_str(&format!(
+                "<upnp:albumArtURI dlna:profileID=\"JPEG_TN\">{}</upnp:albumArtURI>",
+                xml_escape(url)
+            ));
+        }
+        if let Some(ref resource) = object.resource {
+            xml.push_str(&format!(
+                "<res protocolInfo=\"http-get:*:{}:{}\"",
+                resource.mime_type, DLNA_FEATURES
+            ));
+            if let Some(size) = resource.size {
+                xml.push_str(&format!(" size=\"{}\"", size));
+            }
+            if let Some(ticks) = resource.duration_ticks {
+                xml.push_str(&format!(" duration=\"{}\"", didl_duration(ticks)));
+            }
+            if let Some((width, height)) = resource.resolution {
+                xml.push_str(&format!(" resolution=\"{}x{}\"", width, height));
+            }
+            xml.push_str(&format!(">{}</res>", xml_escape(&resource.url)));
+        }
+        xml.push_str(&format!("</{}>", element));
+    }
+    xml.push_str("</DIDL-Lite>");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_and_browse_requests() {
+        let ads = advertisements("abc");
+        let search = "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\n\
+                      MAN: \"ssdp:discover\"\r\nMX: 2\r\nst: urn:schemas-upnp-org:device:MediaServer:1\r\n\r\n";
+        let target = search_target(search).unwrap();
+        let matched: Vec<_> = matching_advertisements(&ads, &target).collect();
+        assert_eq!(matched.len(), 1);
+        assert_eq!(matched[0].1, format!("uuid:abc::{}", DEVICE_TYPE));
+        assert_eq!(matching_advertisements(&ads, "ssdp:all").count(), 5);
+        assert_eq!(
+            search_target("NOTIFY * HTTP/1.1\r\nNT: upnp:rootdevice\r\n"),
+            None
+        );
+
+        let body = r#"<?xml version="1.0" encoding="utf-8"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/" s:encodingStyle="http://schemas.xmlsoap.org/soap/encoding/">
+<s:Body><u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
+<ObjectID>lib&amp;1</ObjectID><BrowseFlag>BrowseDirectChildren</BrowseFlag>
+<Filter>*</Filter><StartingIndex>0</StartingIndex>
+<RequestedCount>10</RequestedCount><SortCriteria/></u:Browse></s:Body></s:Envelope>"#;
+        let arguments = soap_arguments(body);
+        assert_eq!(arguments["ObjectID"], "lib&1");
+        assert_eq!(arguments["RequestedCount"], "10");
+        assert_eq!(arguments["SortCriteria"], "");
+        assert!(!arguments.contains_key("Missing"));
+        assert!(soap_arguments("<u:Browse><ObjectID>0</ObjectID>").is_empty());
+        assert_eq!(
+            soap_action("\"urn:schemas-upnp-org:service:ContentDirectory:1#Browse\""),
+            Some("Browse")
+        );
+
+        let item = DidlObject {
+            id: "item".to_string(),
+            parent_id: "lib".to_string(),
+            title: "Tom & Jerry".to_string(),
+            class: "object.item.videoItem.movie",
+            child_count: None,
+            resource: Some(DidlResource {
+                url: "http://10.0.0.2:8096/dlna/media/item".to_string(),
+                mime_type: "video/mp4",
+                size: Some(1000),
+                duration_ticks: Some(54_321_000_000),
+                resolution: Some((1920, 1080)),
+            }),
+            album_art_url: None,
+        };
+        let didl = didl_lite(&[item]);
+        assert!(didl.contains("<dc:title>Tom &amp; Jerry</dc:title>"));
+        assert!(didl.contains("duration=\"1:30:32.100\""));
+        assert!(didl.contains("resolution=\"1920x1080\""));
+        assert!(didl.contains("<item id=\"item\" parentID=\"lib\" restricted=\"1\">"));
+
+        assert!(is_local_address("192.168.1.20".parse().unwrap()));
+        assert!(is_local_address("fe80::1".parse().unwrap()));
+        assert!(!is_local_address("8.8.8.8".parse().unwrap()));
+    }
+}
@@ -0,0 +1,183 @@
+// Alternate episode orderings (TMDB episode groups)
+//
+// Episodes keep their aired numbering in parent_index_number/index_number, since
+// that is what file names say and what rescans write. Choosing an episode group
+// for a series fills display_parent_index_number/display_index_number, which the
+// API shows and sorts by instead. Episodes missing from the group keep their
+// aired numbers.
+
+use anyhow::{anyhow, Context, Result};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+use super::tmdb::{EpisodeGroup, TmdbClient};
+
+/// Map aired (season, episode) numbers to the group's (season, episode) numbers
+///
+/// Groups become seasons in their `order`; a group named "Specials" becomes
+/// season 0. When a non-special group has order 0 the seasons are shifted so
+/// numbering starts at 1. Episodes are numbered from 1 within their group.
+pub fn build_number_map(groups: &[EpisodeGroup]) -> HashMap<(i32, i32), (i32, i32)> {
+    let is_specials = |g: &EpisodeGroup| g.name.to_lowercase().starts_with("special");
+    let offset = if groups.iter().any(|g| g.order == 0 && !is_specials(g)) {
+        1
+    } else {
+        0
+    };
+
+    let mut map = HashMap::new();
+    for group in groups {
+        let season = if is_specials(group) {
+            0
+        } else {
+            group.order + offset
+        };
+        for entry in &group.episodes {
+            map.entry((entry.season_number, entry.episode_number))
+                .or_insert((season, entry.order + 1));
+        }
+    }
+    map
+}
+
+/// Select (or clear, with None) the episode ordering of a series and renumber its episodes
+///
+/// Returns the number of episodes given alternate numbers.
+pub async fn set_episode_ordering(
+    pool: &SqlitePool,
+    tmdb: Option<&TmdbClient>,
+    series_id: &str,
+    group_id: Option<&str>,
+) -> Result<usize> {
+    let Some(group_id) = group_id.filter(|g| !g.is_empty()) else {
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE media_items SET episode_group_id = NULL WHERE id = ?")
+            .bind(series_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "UPDATE media_items SET display_parent_index_number = NULL, display_index_number = NULL WHERE parent_id = ?",
+        )
+        .bind(series_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        // Back to aired order, which may still have season mappings
+        super::season_mapping::apply_season_mappings(pool, series_id).await?;
+        return Ok(0);
+    };
+
+    let tmdb = tmdb.ok_or_else(|| anyhow!("TMDB is not configured"))?;
+    let group = tmdb.get_episode_group(group_id).await?;
+    let numbers = build_number_map(&group.groups);
+
+    let episodes: Vec<(String, Option<i32>, Option<i32>)> = sqlx::query_as(
+        "SELECT id, parent_index_number, index_number FROM media_items WHERE parent_id = ? AND item_type = 'Episode'",
+    )
+    .bind(series_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE media_items SET episode_group_id = ? WHERE id = ?")
+        .bind(group_id)
+        .bind(series_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut remapped = 0;
+    for (id, season, episode) in episodes {
+        // Episodes without a season number are treated as season 1, like the Seasons view
+        let display = episode.and_then(|e| numbers.get(&(season.unwrap_or(1), e)));
+        sqlx::query(
+            "UPDATE media_items SET display_parent_index_number = ?, display_index_number = ? WHERE id = ?",
+        )
+        .bind(display.map(|d| d.0))
+        .bind(display.map(|d| d.1))
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+        if display.is_some() {
+            remapped += 1;
+        }
+    }
+    tx.commit()
+        .await
+        .context("Failed to save episode ordering")?;
+    crate::scanner::seasons::sync_series(pool, series_id).await?;
+
+    tracing::info!(
+        "Applied episode ordering '{}' to series {} ({} episodes renumbered)",
+        group.name,
+        series_id,
+        remapped
+    );
+
+    Ok(remapped)
+}
+
+/// Re-apply a series' selected ordering, e.g. after new episodes were scanned
+pub async fn reapply_episode_ordering(
+    pool: &SqlitePool,
+    tmdb: Option<&TmdbClient>,
+    series_id: &str,
+) -> Result<()> {
+    let group_id: Option<String> =
+        sqlx::query_scalar("SELECT episode_group_id FROM media_items WHERE id = ?")
+            .bind(series_id)
+            .fetch_optional(pool)
+            .await?
+            .flatten();
+
+    if let Some(group_id) = group_id {
+        set_episode_ordering(pool, tmdb, series_id, Some(&group_id)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::tmdb::EpisodeGroupEntry;
+
+    fn group(name: &str, order: i32, episodes: &[(i32, i32)]) -> EpisodeGroup {
+        EpisodeGroup {
+            name: name.to_string(),
+            order,
+            episodes: episodes
+                .iter()
+                .enumerate()
+                .map(|(i, &(season_number, episode_number))| EpisodeGroupEntry {
+                    season_number,
+                    episode_number,
+                    order: i as i32,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_build_number_map_splits_aired_season() {
+        // One aired season of 4 episodes split into two "DVD" seasons, recap moved to specials
+        let groups = vec![
+            group("Specials", 0, &[(1, 3)]),
+            group("Season 1", 1, &[(1, 1), (1, 2)]),
+            group("Season 2", 2, &[(1, 4)]),
+        ];
+        let map = build_number_map(&groups);
+
+        assert_eq!(map.get(&(1, 1)), Some(&(1, 1)));
+        assert_eq!(map.get(&(1, 2)), Some(&(1, 2)));
+        assert_eq!(map.get(&(1, 3)), Some(&(0, 1)));
+        assert_eq!(map.get(&(1, 4)), Some(&(2, 1)));
+    }
+
+    #[test]
+    fn test_build_number_map_zero_based_groups() {
+        let groups = vec![group("Part 1", 0, &[(1, 1)]), group("Part 2", 1, &[(1, 2)])];
+        let map = build_number_map(&groups);
+
+        assert_eq!(map.get(&(1, 1)), Some(&(1, 1)));
+        assert_eq!(map.get(&(1, 2)), Some(&(2, 1)));
+    }
+}
@@ -0,0 +1,450 @@
+// External subtitle and audio files
+//
+// Subtitle and audio tracks often sit next to a video as separate files
+// ("Movie.mkv" with "Movie.en.srt", "Movie.en.forced.ass" or "Movie.ja.mka").
+// Scans store the ones beside each video in the external_streams table, with
+// the language, flags and title parsed from the file name, and they're offered
+// as external streams after the file's embedded ones. Items stored before
+// anything was found for them (or before this table existed) are looked up on
+// disk instead, so files added since the last scan still show up. Clients that can load subtitles separately fetch
+// them through the subtitle endpoint; for the rest, and for external audio,
+// which no client can play alongside the video, the video is remuxed on the fly
+// with ffmpeg: the selected files are copied into a Matroska stream next to the
+// original video and audio, without transcoding anything. The same remux copies
+// a file into another container for clients that play its codecs but not its
+// container.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+
+use super::mediainfo::{AudioStream, SubtitleStream};
+use crate::time::Ticks;
+
+/// Stream index of the first external file
+///
+/// Indexes of embedded streams come from ffprobe and stay well below this;
+/// subtitles downloaded from providers are numbered from 100.
+pub const FIRST_INDEX: i32 = 200;
+
+const SUBTITLE_EXTENSIONS: &[&str] = &["srt", "ass", "ssa", "vtt"];
+
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "mka", "ac3", "eac3", "dts", "aac", "m4a", "flac", "mp3", "opus",
+];
+
+/// A subtitle or audio file beside a video
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExternalFile {
+    pub path: PathBuf,
+    pub index: i32,
+    pub is_audio: bool,
+    /// ffmpeg codec name, None when the container doesn't tell (.mka)
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub is_default: bool,
+    pub is_forced: bool,
+}
+
+impl ExternalFile {
+    /// The file as an audio stream, for display titles
+    pub fn audio_stream(&self) -> AudioStream {
+        let is_commentary = self
+            .title
+            .as_deref()
+            .is_some_and(super::mediainfo::is_commentary_title);
+        AudioStream {
+            index: self.index,
+            codec: self.codec.clone().unwrap_or_else(|| "mka".to_string()),
+            language: self.language.clone(),
+            title: self.title.clone(),
+            channels: None,
+            sample_rate: None,
+            is_default: self.is_default && !is_commentary,
+            is_commentary,
+        }
+    }
+
+    /// The file as a subtitle stream, for display titles
+    pub fn subtitle_stream(&self) -> SubtitleStream {
+        SubtitleStream {
+            index: self.index,
+            codec: self.codec.clone().unwrap_or_default(),
+            language: self.language.clone(),
+            title: self.title.clone(),
+            is_default: self.is_default,
+            is_forced: self.is_forced,
+        }
+    }
+}
+
+fn codec_for_extension(ext: &str) -> Option<&'static str> {
+    match ext {
+        "srt" => Some("subrip"),
+        "ass" => Some("ass"),
+        "ssa" => Some("ssa"),
+        "vtt" => Some("webvtt"),
+        "ac3" => Some("ac3"),
+        "eac3" => Some("eac3"),
+        "dts" => Some("dts"),
+        "aac" | "m4a" => Some("aac"),
+        "flac" => Some("flac"),
+        "mp3" => Some("mp3"),
+        "opus" => Some("opus"),
+        _ => None,
+    }
+}
+
+/// Parse a file name against the video's stem ("Movie" and "Movie.en.forced.srt")
+///
+/// The dot-separated parts between the stem and the extension hold the
+/// language (a 2 or 3 letter code or an English name, normalized by
+/// services::localization), the "default" and "forced" flags, and
+/// anything else, which becomes the title ("Movie.en.Commentary.mka").
+fn parse_file_name(video_stem: &str, file_name: &str) -> Option<ExternalFile> {
+    let (rest, ext) = file_name.rsplit_once('.')?;
+    let ext = ext.to_lowercase();
+    let is_audio = AUDIO_EXTENSIONS.contains(&ext.as_str());
+    if !is_audio && !SUBTITLE_EXTENSIONS.contains(&ext.as_str()) {
+        return None;
+    }
+    if rest.len() < video_stem.len()
+        || !rest.is_char_boundary(video_stem.len())
+        || !rest[..video_stem.len()].eq_ignore_ascii_case(video_stem)
+    {
+        return None;
+    }
+    let tags = &rest[video_stem.len()..];
+    if !tags.is_empty() && !tags.starts_with('.') {
+        return None;
+    }
+
+    let mut file = ExternalFile {
+        path: PathBuf::from(file_name),
+        index: 0,
+        is_audio,
+        codec: codec_for_extension(&ext).map(str::to_string),
+        language: None,
+        title: None,
+        is_default: false,
+        is_forced: false,
+    };
+    let mut title = Vec::new();
+    for tag in tags.split('.').filter(|t| !t.is_empty()) {
+        match tag.to_lowercase().as_str() {
+            "default" => file.is_default = true,
+            "forced" => file.is_forced = true,
+            code if file.language.is_none()
+                && (super::localization::find(code).is_some()
+                    || (2..=3).contains(&code.len())
+                        && code.chars().all(|c| c.is_ascii_alphabetic())) =>
+            {
+                file.language = super::localization::normalize(code)
+            }
+            _ => title.push(tag),
+        }
+    }
+    if !title.is_empty() {
+        file.title = Some(title.join(" "));
+    }
+    Some(file)
+}
+
+/// External subtitle and audio files beside a video, in file name order and
+/// numbered from FIRST_INDEX
+pub async fn find(video_path: &Path) -> Vec<ExternalFile> {
+    let (Some(dir), Some(stem)) = (
+        video_path.parent(),
+        video_path.file_stem().and_then(|s| s.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    let Ok(mut entries) = tokio::fs::read_dir(dir).await else {
+        return Vec::new();
+    };
+
+    let mut files = Vec::new();
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let file_name = entry.file_name();
+        let Some(name) = file_name.to_str() else {
+            continue;
+        };
+        if let Some(mut file) = parse_file_name(stem, name) {
+            if entry.file_type().await.is_ok_and(|t| t.is_file()) {
+                file.path = entry.path();
+                files.push(file);
+            }
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    for (i, file) in files.iter_mut().enumerate() {
+        file.index = FIRST_INDEX + i as i32;
+    }
+    files
+}
+
+#[derive(sqlx::FromRow)]
+struct ExternalStreamRow {
+    stream_index: i32,
+    path: String,
+    is_audio: bool,
+    codec: Option<String>,
+    language: Option<String>,
+    title: Option<String>,
+    is_default: bool,
+    is_forced: bool,
+}
+
+impl From<ExternalStreamRow> for ExternalFile {
+    fn from(row: ExternalStreamRow) -> Self {
+        Self {
+            path: PathBuf::from(row.path),
+            index: row.stream_index,
+            is_audio: row.is_audio,
+            codec: row.codec,
+            language: row.language,
+            title: row.title,
+            is_default: row.is_default,
+            is_forced: row.is_forced,
+        }
+    }
+}
+
+/// Find the files beside an item's video and store them, replacing what was
+/// stored before; returns how many there are
+pub async fn refresh(pool: &SqlitePool, item_id: &str, video_path: &Path) -> Result<usize> {
+    let files = find(video_path).await;
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM external_streams WHERE item_id = ?")
+        .bind(item_id)
+        .execute(&mut *tx)
+        .await?;
+    for file in &files {
+        sqlx::query(
+            "INSERT INTO external_streams
+                (item_id, stream_index, path, is_audio, codec, language, title, is_default, is_forced)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(item_id)
+        .bind(file.index)
+        .bind(file.path.to_string_lossy())
+        .bind(file.is_audio)
+        .bind(&file.codec)
+        .bind(&file.language)
+        .bind(&file.title)
+        .bind(file.is_default)
+        .bind(file.is_forced)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(files.len())
+}
+
+/// An item's external files: the ones stored by the last scan, or the ones on
+/// disk when none are stored
+pub async fn for_item(pool: &SqlitePool, item_id: &str, video_path: &Path) -> Vec<ExternalFile> {
+    let rows: Vec<ExternalStreamRow> = match sqlx::query_as(
+        "SELECT stream_index, path, is_audio, codec, language, title, is_default, is_forced
+         FROM external_streams WHERE item_id = ? ORDER BY stream_index",
+    )
+    .bind(item_id)
+    .fetch_all(pool)
+    .await
+    {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::warn!("Failed to load external streams of {}: {}", item_id, e);
+            Vec::new()
+        }
+    };
+    if rows.is_empty() {
+        return find(video_path).await;
+    }
+    rows.into_iter().map(ExternalFile::from).collect()
+}
+
+/// An item's external file with a stream index, if any
+pub async fn find_index(
+    pool: &SqlitePool,
+    item_id: &str,
+    video_path: &Path,
+    index: i32,
+) -> Option<ExternalFile> {
+    if index < FIRST_INDEX {
+        return None;
+    }
+    for_item(pool, item_id, video_path)
+        .await
+        .into_iter()
+        .find(|f| f.index == index)
+}
+
+/// Containers a file's streams can be copied into without transcoding
+///
+/// Matroska for external files; the others for clients whose device profile
+/// covers a file's codecs but not its container (see services::device_profile).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemuxContainer {
+    Mkv,
+    Mp4,
+    Ts,
+    Webm,
+}
+
+impl RemuxContainer {
+    /// The container of a device profile name ("mkv", "matroska", "m4v", ...)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "mkv" | "matroska" => Some(Self::Mkv),
+            "mp4" | "m4v" => Some(Self::Mp4),
+            "ts" | "mpegts" => Some(Self::Ts),
+            "webm" => Some(Self::Webm),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Mkv => "mkv",
+            Self::Mp4 => "mp4",
+            Self::Ts => "ts",
+            Self::Webm => "webm",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Mkv => "video/x-matroska",
+            Self::Mp4 => "video/mp4",
+            Self::Ts => "video/mp2t",
+            Self::Webm => "video/webm",
+        }
+    }
+
+    /// Whether ffmpeg can copy streams of these codecs into the container
+    pub fn can_hold(self, video_codec: Option<&str>, audio_codec: Option<&str>) -> bool {
+        let (video, audio): (&[&str], &[&str]) = match self {
+            Self::Mkv => return true,
+            Self::Mp4 => (
+                &["h264", "hevc", "av1", "vp9", "mpeg4"],
+                &["aac", "mp3", "ac3", "eac3", "opus", "flac", "alac"],
+            ),
+            Self::Ts => (
+                &["h264", "hevc", "mpeg2video"],
+                &["aac", "mp3", "mp2", "ac3", "eac3"],
+            ),
+            Self::Webm => (&["vp8", "vp9", "av1"], &["opus", "vorbis"]),
+        };
+        video_codec.is_none_or(|c| video.contains(&c))
+            && audio_codec.is_none_or(|c| audio.contains(&c))
+    }
+
+    /// Output arguments; mp4 is fragmented, since a pipe can't be seeked back
+    /// to write the index at the start
+    fn format_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Mkv => &["-f", "matroska"],
+            Self::Mp4 => &[
+                "-f",
+                "mp4",
+                "-movflags",
+                "frag_keyframe+empty_moov+default_base_moof",
+            ],
+            Self::Ts => &["-f", "mpegts"],
+            Self::Webm => &["-f", "webm"],
+        }
+    }
+}
+
+/// ffmpeg arguments that remux a video, with any external files, into
+/// `container` on stdout
+///
+/// An external audio file replaces the video's own audio tracks, since it was
+/// picked over them; otherwise `audio_stream_index` picks one embedded track,
+/// or all are kept. Only the external subtitle is muxed: embedded subtitles
+/// are still delivered as before, and some (mov_text) can't be copied into
+/// Matroska anyway.
+pub fn remux_args(
+    video_path: &Path,
+    audio: Option<&Path>,
+    audio_stream_index: Option<i32>,
+    subtitle: Option<&Path>,
+    start: Option<Ticks>,
+    container: RemuxContainer,
+) -> Vec<String> {
+    let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error"]
+        .map(String::from)
+        .to_vec();
+    let inputs = std::iter::once(video_path).chain(audio).chain(subtitle);
+    for input in inputs {
+        if let Some(start) = start.filter(|s| s.0 > 0) {
+            args.extend(["-ss".to_string(), start.to_ffmpeg()]);
+        }
+        args.extend(["-i".to_string(), input.to_string_lossy().into_owned()]);
+    }
+
+    args.extend(["-map".to_string(), "0:v?".to_string()]);
+    let mut next_input = 1;
+    if audio.is_some() {
+        args.extend(["-map".to_string(), format!("{}:a:0", next_input)]);
+        args.extend(["-disposition:a:0".to_string(), "default".to_string()]);
+        next_input += 1;
+    } else if let Some(index) = audio_stream_index {
+        args.extend(["-map".to_string(), format!("0:{}", index)]);
+    } else {
+        args.extend(["-map".to_string(), "0:a?".to_string()]);
+    }
+    if subtitle.is_some() {
+        args.extend(["-map".to_string(), format!("{}:s:0", next_input)]);
+        args.extend(["-disposition:s:0".to_string(), "default".to_string()]);
+    }
+
+    args.extend(["-c".to_string(), "copy".to_string()]);
+    args.extend(container.format_args().iter().map(|a| a.to_string()));
+    args.push("pipe:1".to_string());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_external_files() {
+        let sub = parse_file_name("Show S01E01", "Show S01E01.en.forced.srt").unwrap();
+        assert!(!sub.is_audio);
+        assert_eq!(sub.language.as_deref(), Some("eng"));
+        assert!(sub.is_forced);
+        assert_eq!(sub.codec.as_deref(), Some("subrip"));
+
+        let audio = parse_file_name("Movie", "movie.JPN.Director Commentary.mka").unwrap();
+        assert!(audio.is_audio);
+        assert_eq!(audio.codec, None);
+        assert_eq!(audio.language.as_deref(), Some("jpn"));
+        assert_eq!(audio.title.as_deref(), Some("Director Commentary"));
+
+        assert!(parse_file_name("Movie", "Movie.ass").is_some());
+        // Other videos, other episodes and unrelated files
+        assert!(parse_file_name("Movie", "Movie.Part2.mkv").is_none());
+        assert!(parse_file_name("Show S01E01", "Show S01E010.srt").is_none());
+        assert!(parse_file_name("Movie", "Movie.nfo").is_none());
+
+        let args = remux_args(
+            Path::new("/m/Movie.mkv"),
+            Some(Path::new("/m/Movie.ja.mka")),
+            None,
+            Some(Path::new("/m/Movie.en.srt")),
+            Some(Ticks::from_seconds(90)),
+            RemuxContainer::Mkv,
+        )
+        .join(" ");
+        assert_eq!(
+            args,
+            "-hide_banner -loglevel error -ss 90.000 -i /m/Movie.mkv -ss 90.000 -i /m/Movie.ja.mka \
+             -ss 90.000 -i /m/Movie.en.srt -map 0:v? -map 1:a:0 -disposition:a:0 default \
+             -map 2:s:0 -disposition:s:0 default -c copy -f matroska pipe:1"
+        );
+    }
+}
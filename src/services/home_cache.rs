@@ -0,0 +1,136 @@
+// Home screen cache
+//
+// Latest (per library), Resume and Next Up are the first requests of every
+// client's home screen and among the most expensive: each walks the visibility
+// view and joins the user's playback state. Right after a scan nothing is in
+// SQLite's page cache either, so the first home screen used to take seconds.
+// The lists are now cached per user (the cache itself lives in api::home),
+// warmed for active users once a scan finishes, and dropped when the event bus
+// reports a write that could change them: any item or image change clears
+// every list, a user's playback or user data changes clear that user's.
+// Entries also expire after a while, which bounds staleness from writes that
+// publish no event (such as a new block or parental setting).
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long a cached list is served
+pub const TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Items cached per list, so requests for up to this many are answered from one entry
+pub const CACHED_LIMIT: i32 = 50;
+
+/// A cached home screen list
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HomeList {
+    /// Latest episodes and movies, of one library or all of them
+    Latest(Option<String>),
+    Resume,
+    NextUp,
+}
+
+struct Entry<T> {
+    items: Vec<T>,
+    cached_at: Instant,
+}
+
+/// Cached lists by user, with a generation bumped by every invalidation so a
+/// list computed before a write is never stored after it
+pub struct ListCache<T> {
+    entries: HashMap<(String, HomeList), Entry<T>>,
+    generation: u64,
+}
+
+impl<T> Default for ListCache<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            generation: 0,
+        }
+    }
+}
+
+impl<T: Clone> ListCache<T> {
+    /// The current generation; pass it to `store` with a list computed after reading it
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The first `limit` items of a user's list, if it is cached and younger than `ttl`
+    ///
+    /// A list is cached with up to CACHED_LIMIT items; larger requests go to
+    /// the database.
+    pub fn get(&self, user_id: &str, list: &HomeList, limit: i32, ttl: Duration) -> Option<Vec<T>> {
+        if limit > CACHED_LIMIT {
+            return None;
+        }
+        let entry = self.entries.get(&(user_id.to_string(), list.clone()))?;
+        if entry.cached_at.elapsed() > ttl {
+            return None;
+        }
+        Some(
+            entry
+                .items
+                .iter()
+                .take(limit.max(0) as usize)
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Cache a user's list of up to CACHED_LIMIT items, unless something changed since `generation`
+    pub fn store(&mut self, user_id: &str, list: HomeList, items: Vec<T>, generation: u64) {
+        if generation != self.generation {
+            return;
+        }
+        let entry = Entry {
+            items,
+            cached_at: Instant::now(),
+        };
+        self.entries.insert((user_id.to_string(), list), entry);
+    }
+
+    /// Drop a user's lists (their playback or user data changed), or everyone's
+    /// (items were added, changed or removed)
+    pub fn invalidate(&mut self, user_id: Option<&str>) {
+        self.generation += 1;
+        match user_id {
+            Some(user_id) => self.entries.retain(|(user, _), _| user != user_id),
+            None => self.entries.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cached_lists_and_invalidation() {
+        let mut cache = ListCache::default();
+        let latest = HomeList::Latest(Some("lib".to_string()));
+
+        let generation = cache.generation();
+        cache.store("alice", latest.clone(), vec!["a", "b"], generation);
+        cache.store("alice", HomeList::Resume, vec!["r"], generation);
+        assert_eq!(cache.get("alice", &latest, 1, TTL), Some(vec!["a"]));
+        assert_eq!(cache.get("alice", &latest, 16, TTL), Some(vec!["a", "b"]));
+        assert_eq!(cache.get("alice", &latest, CACHED_LIMIT + 1, TTL), None);
+        assert_eq!(cache.get("alice", &HomeList::Latest(None), 16, TTL), None);
+        assert_eq!(cache.get("bob", &latest, 16, TTL), None);
+        assert_eq!(cache.get("alice", &latest, 16, Duration::ZERO), None);
+
+        // Another user's changes leave the lists alone
+        cache.invalidate(Some("bob"));
+        assert!(cache.get("alice", &HomeList::Resume, 16, TTL).is_some());
+        cache.invalidate(Some("alice"));
+        assert_eq!(cache.get("alice", &HomeList::Resume, 16, TTL), None);
+
+        // A list computed before a write isn't stored after it
+        cache.store("alice", HomeList::NextUp, vec!["n"], generation);
+        assert_eq!(cache.get("alice", &HomeList::NextUp, 16, TTL), None);
+        cache.store("alice", HomeList::NextUp, vec!["n"], cache.generation());
+        cache.invalidate(None);
+        assert_eq!(cache.get("alice", &HomeList::NextUp, 16, TTL), None);
+    }
+}
@@ -0,0 +1,174 @@
+// Remote image proxy for metadata provider artwork
+//
+// /Items/:id/RemoteImages returns provider URLs that clients can't always load
+// directly (mixed content on HTTPS servers, hotlink/referer blocking). The
+// /Images/Remote endpoint fetches them server side instead. Only the image
+// hosts of our metadata providers are allowed, so it can't be used as an open
+// proxy; downloads are size-limited and cached as
+// {image_cache}/remote/{sha1(url)}.{ext}.
+
+use anyhow::{bail, Context, Result};
+use reqwest::Url;
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::image_validation;
+
+/// Image hosts of the metadata providers (TMDB, AniList, MyAnimeList, AniDB)
+const ALLOWED_HOSTS: &[&str] = &[
+    "image.tmdb.org",
+    "s4.anilist.co",
+    "cdn.myanimelist.net",
+    "cdn.anidb.net",
+    "cdn-eu.anidb.net",
+    "cdn-us.anidb.net",
+];
+
+/// Largest image the proxy will download
+pub const MAX_IMAGE_BYTES: usize = 10 * 1024 * 1024;
+
+/// Cached images older than this are fetched again
+const CACHE_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Extensions cached images are stored with, by content type
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("image/gif", "gif"),
+];
+
+/// Parse a URL and check it points at an allowed provider image host
+pub fn validate_url(url: &str) -> Result<Url> {
+    let parsed = Url::parse(url).context("Invalid image URL")?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        bail!("Unsupported URL scheme: {}", parsed.scheme());
+    }
+    if parsed.port().is_some() || !parsed.username().is_empty() || parsed.password().is_some() {
+        bail!("Image URL must not contain a port or credentials");
+    }
+    let host = parsed.host_str().unwrap_or_default().to_lowercase();
+    if !ALLOWED_HOSTS.contains(&host.as_str()) {
+        bail!("Image host not allowed: {}", host);
+    }
+    Ok(parsed)
+}
+
+fn cache_dir(image_dir: &Path) -> PathBuf {
+    image_dir.join("remote")
+}
+
+fn cache_key(url: &Url) -> String {
+    Sha1::digest(url.as_str().as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// A fresh cached copy of the image, if there is one
+async fn cached(dir: &Path, key: &str) -> Option<PathBuf> {
+    for (_, ext) in CONTENT_TYPES {
+        let path = dir.join(format!("{}.{}", key, ext));
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let fresh = metadata
+            .modified()
+            .ok()
+            .and_then(|m| m.elapsed().ok())
+            .is_some_and(|age| age < CACHE_MAX_AGE);
+        if fresh {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Fetch a provider image through the cache, returning the cached file
+///
+/// `url` must already have passed `validate_url`.
+pub async fn fetch(image_dir: &Path, url: &Url) -> Result<PathBuf> {
+    let dir = cache_dir(image_dir);
+    let key = cache_key(url);
+    if let Some(path) = cached(&dir, &key).await {
+        return Ok(path);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        // Redirects must stay on the allowed hosts too
+        .redirect(reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() < 5 && validate_url(attempt.url().as_str()).is_ok() {
+                attempt.follow()
+            } else {
+                attempt.stop()
+            }
+        }))
+        .build()?;
+    let mut response = client.get(url.clone()).send().await?;
+    if !response.status().is_success() {
+        bail!("Image request failed with status {}", response.status());
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_lowercase())
+        .unwrap_or_default();
+    let Some((_, ext)) = CONTENT_TYPES.iter().find(|(ct, _)| *ct == content_type) else {
+        bail!("Not a supported image type: {}", content_type);
+    };
+    if response
+        .content_length()
+        .is_some_and(|len| len as usize > MAX_IMAGE_BYTES)
+    {
+        bail!("Image exceeds {} bytes", MAX_IMAGE_BYTES);
+    }
+
+    // Content-Length may be missing or wrong, so enforce the limit while reading
+    let mut data = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if data.len() + chunk.len() > MAX_IMAGE_BYTES {
+            bail!("Image exceeds {} bytes", MAX_IMAGE_BYTES);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    image_validation::inspect(&data).context("Invalid image")?;
+
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.{}", key, ext));
+    // Write then rename so concurrent requests never serve a partial file
+    let temp = dir.join(format!("{}.{}.part", key, uuid::Uuid::new_v4()));
+    tokio::fs::write(&temp, &data)
+        .await
+        .with_context(|| format!("Failed to write {}", temp.display()))?;
+    tokio::fs::rename(&temp, &path)
+        .await
+        .with_context(|| format!("Failed to move {} into place", temp.display()))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_url_allows_only_provider_hosts() {
+        assert!(validate_url("https://image.tmdb.org/t/p/w300/abc.jpg").is_ok());
+        assert!(validate_url("https://S4.AniList.co/file/anilistcdn/media/cover.png").is_ok());
+
+        assert!(validate_url("https://example.com/image.jpg").is_err());
+        assert!(validate_url("https://image.tmdb.org.evil.com/a.jpg").is_err());
+        assert!(validate_url("https://image.tmdb.org:8443/a.jpg").is_err());
+        assert!(validate_url("https://user@image.tmdb.org/a.jpg").is_err());
+        assert!(validate_url("file:///etc/passwd").is_err());
+        assert!(validate_url("not a url").is_err());
+    }
+}
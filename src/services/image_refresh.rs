@@ -0,0 +1,192 @@
+// Bulk image refresh
+//
+// Providers replace artwork over time (higher-resolution posters, textless
+// backdrops), but a cached image is never downloaded again. A refresh looks up
+// the current poster and backdrop of every series and movie in a library by
+// the provider IDs it already has, and queues them as re-downloads: the
+// background image downloader fetches them at its usual pace and replaces the
+// cached files once each download succeeds. Lookups are spaced out and go
+// through the providers' circuit breakers, so refreshing a large library
+// doesn't trip rate limits. Only one refresh runs at a time.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use super::metadata::MetadataService;
+
+/// Pause between provider lookups
+const LOOKUP_DELAY: Duration = Duration::from_millis(250);
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// What a refresh did
+#[derive(Debug, Default)]
+pub struct ImageRefreshSummary {
+    /// Series and movies with provider IDs in scope
+    pub items_checked: usize,
+    /// Items skipped because their images are newer than the age limit
+    pub items_skipped: usize,
+    pub images_queued: usize,
+    /// Items no provider returned artwork for
+    pub items_not_found: usize,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct RefreshCandidate {
+    id: String,
+    item_type: String,
+    tmdb_id: Option<String>,
+    anilist_id: Option<String>,
+    /// Download time of the item's oldest provider image: NULL without images,
+    /// '' when one predates download times being recorded
+    oldest_image: Option<String>,
+}
+
+/// Whether a refresh is in progress
+pub fn is_running() -> bool {
+    RUNNING.load(Ordering::SeqCst)
+}
+
+/// Whether an item's images are old enough to refresh
+///
+/// Images without a recorded download time count as old.
+fn needs_refresh(oldest_image: Option<&str>, cutoff: Option<DateTime<Utc>>) -> bool {
+    let Some(cutoff) = cutoff else {
+        return true;
+    };
+    match oldest_image.and_then(|t| DateTime::parse_from_rfc3339(t).ok()) {
+        Some(downloaded) => downloaded < cutoff,
+        None => true,
+    }
+}
+
+/// Refresh the images of one library's items (all libraries when None),
+/// optionally only those downloaded more than `older_than_days` ago
+///
+/// Returns None when a refresh is already running.
+pub async fn refresh_images(
+    pool: &SqlitePool,
+    image_cache_dir: PathBuf,
+    library_id: Option<&str>,
+    older_than_days: Option<u32>,
+) -> Result<Option<ImageRefreshSummary>> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Ok(None);
+    }
+    let result = run(pool, image_cache_dir, library_id, older_than_days).await;
+    RUNNING.store(false, Ordering::SeqCst);
+    result.map(Some)
+}
+
+async fn run(
+    pool: &SqlitePool,
+    image_cache_dir: PathBuf,
+    library_id: Option<&str>,
+    older_than_days: Option<u32>,
+) -> Result<ImageRefreshSummary> {
+    let candidates: Vec<RefreshCandidate> = sqlx::query_as(
+        "SELECT m.id, m.item_type, m.tmdb_id, m.anilist_id,
+                (SELECT MIN(COALESCE(i.downloaded_at, '')) FROM images i
+                 WHERE i.item_id = m.id AND i.image_type IN ('Primary', 'Backdrop')) AS oldest_image
+         FROM media_items m
+         WHERE m.item_type IN ('Series', 'Movie')
+           AND (m.tmdb_id IS NOT NULL OR m.anilist_id IS NOT NULL)
+           AND (?1 IS NULL OR m.library_id = ?1)
+         ORDER BY m.sort_name, m.name",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+
+    let cutoff = older_than_days.map(|days| Utc::now() - chrono::Duration::days(days as i64));
+    // Only lookups by ID are made, so the anime offline database isn't needed
+    let metadata = MetadataService::from_env(image_cache_dir, Some(false));
+    let mut summary = ImageRefreshSummary::default();
+
+    for item in candidates {
+        summary.items_checked += 1;
+        if !needs_refresh(item.oldest_image.as_deref(), cutoff) {
+            summary.items_skipped += 1;
+            continue;
+        }
+
+        let artwork = match metadata
+            .artwork_urls(
+                item.item_type == "Movie",
+                item.tmdb_id.as_deref(),
+                item.anilist_id.as_deref(),
+            )
+            .await
+        {
+            Ok(artwork) => artwork,
+            Err(e) => {
+                tracing::debug!("Artwork lookup failed for {}: {}", item.id, e);
+                None
+            }
+        };
+
+        match artwork {
+            Some(artwork) => {
+                let backdrops = artwork
+                    .backdrop_url
+                    .into_iter()
+                    .chain(artwork.extra_backdrop_urls);
+                let images = artwork
+                    .poster_url
+                    .map(|url| ("Primary".to_string(), url))
+                    .into_iter()
+                    .chain(
+                        backdrops
+                            .enumerate()
+                            .map(|(i, url)| (crate::db::indexed_image_type("Backdrop", i), url)),
+                    );
+                for (image_type, url) in images {
+                    crate::db::queue_image_refresh(pool, &item.id, &image_type, &url).await?;
+                    summary.images_queued += 1;
+                }
+            }
+            None => summary.items_not_found += 1,
+        }
+
+        tokio::time::sleep(LOOKUP_DELAY).await;
+    }
+
+    tracing::info!(
+        "Image refresh: {} images queued for {} items ({} skipped as recent, {} without artwork)",
+        summary.images_queued,
+        summary.items_checked,
+        summary.items_skipped,
+        summary.items_not_found
+    );
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_refresh() {
+        let cutoff = DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert!(needs_refresh(
+            Some("2024-01-15T10:00:00+00:00"),
+            Some(cutoff)
+        ));
+        assert!(!needs_refresh(
+            Some("2024-07-01T10:00:00+00:00"),
+            Some(cutoff)
+        ));
+        // Unknown download time or no images at all
+        assert!(needs_refresh(Some(""), Some(cutoff)));
+        assert!(needs_refresh(None, Some(cutoff)));
+        // Without an age limit everything is refreshed
+        assert!(needs_refresh(Some("2024-07-01T10:00:00+00:00"), None));
+    }
+}
@@ -0,0 +1,275 @@
+// On-the-fly image resizing
+//
+// Clients ask for artwork at the size they show it
+// (/Items/{id}/Images/Primary?fillHeight=300&quality=90&format=webp), and
+// sending a 2000px poster to fill a 300px card slows down every library grid.
+// Images requested with size or format parameters are scaled down (never up)
+// keeping their aspect ratio and encoded again. Each variant is cached as
+// {image_cache}/resized/{sha1(source, size, mtime, parameters)}.{ext}, so it is
+// only made once and a replaced source image gets new variants. JPEG output
+// honours the quality; PNG and WebP output is lossless.
+
+use anyhow::{Context, Result};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageReader};
+use sha1::{Digest, Sha1};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// JPEG quality when the client doesn't ask for one
+const DEFAULT_QUALITY: u8 = 90;
+
+/// Image formats variants can be encoded as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl OutputFormat {
+    /// Parse a Format parameter (Jellyfin's ImageFormat names)
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "jpg" | "jpeg" => Some(Self::Jpeg),
+            "png" => Some(Self::Png),
+            "webp" => Some(Self::Webp),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Jpeg => "jpg",
+            Self::Png => "png",
+            Self::Webp => "webp",
+        }
+    }
+
+    fn of(format: image::ImageFormat) -> Option<Self> {
+        match format {
+            image::ImageFormat::Jpeg => Some(Self::Jpeg),
+            image::ImageFormat::Png => Some(Self::Png),
+            image::ImageFormat::WebP => Some(Self::Webp),
+            _ => None,
+        }
+    }
+}
+
+/// Size, quality and format a client asked an image in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResizeOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub max_width: Option<u32>,
+    pub max_height: Option<u32>,
+    pub fill_width: Option<u32>,
+    pub fill_height: Option<u32>,
+    pub quality: Option<u8>,
+    pub format: Option<OutputFormat>,
+}
+
+impl ResizeOptions {
+    /// Whether the original image can be served as it is
+    pub fn is_empty(&self) -> bool {
+        [
+            self.width,
+            self.height,
+            self.max_width,
+            self.max_height,
+            self.fill_width,
+            self.fill_height,
+        ]
+        .iter()
+        .all(|side| side.is_none_or(|side| side == 0))
+            && self.format.is_none()
+    }
+}
+
+/// Size an image of `original` size is scaled to
+///
+/// Width/Height ask for an exact size (the other side following the aspect
+/// ratio when only one is given), FillWidth/FillHeight for the smallest size
+/// covering that box, and MaxWidth/MaxHeight bound the result. Images are never
+/// scaled up.
+fn target_size(original: (u32, u32), options: &ResizeOptions) -> (u32, u32) {
+    let (width, height) = (original.0 as f64, original.1 as f64);
+    let side = |value: Option<u32>| value.filter(|v| *v > 0).map(f64::from);
+
+    let mut scale = match (side(options.width), side(options.height)) {
+        (Some(w), Some(h)) => (w / width).min(h / height),
+        (Some(w), None) => w / width,
+        (None, Some(h)) => h / height,
+        (None, None) => match (side(options.fill_width), side(options.fill_height)) {
+            (Some(w), Some(h)) => (w / width).max(h / height),
+            (Some(w), None) => w / width,
+            (None, Some(h)) => h / height,
+            (None, None) => 1.0,
+        },
+    };
+    if let Some(max) = side(options.max_width) {
+        scale = scale.min(max / width);
+    }
+    if let Some(max) = side(options.max_height) {
+        scale = scale.min(max / height);
+    }
+    let scale = scale.min(1.0);
+
+    let scaled = |side: f64| ((side * scale).round() as u32).max(1);
+    (scaled(width), scaled(height))
+}
+
+fn cache_dir(image_dir: &Path) -> PathBuf {
+    image_dir.join("resized")
+}
+
+/// Cache key of a variant: the source file as it is now plus the output
+fn cache_key(
+    source: &Path,
+    metadata: &std::fs::Metadata,
+    size: (u32, u32),
+    format: OutputFormat,
+    quality: u8,
+) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let key = format!(
+        "{}|{}|{}|{}x{}|{}|{}",
+        source.display(),
+        metadata.len(),
+        modified,
+        size.0,
+        size.1,
+        format.extension(),
+        quality
+    );
+    Sha1::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn encode(image: &DynamicImage, path: &Path, format: OutputFormat, quality: u8) -> Result<()> {
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    match format {
+        OutputFormat::Jpeg => {
+            // JPEG has no alpha channel
+            let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(file, quality);
+            image.to_rgb8().write_with_encoder(encoder)?;
+        }
+        OutputFormat::Png => {
+            image.write_with_encoder(image::codecs::png::PngEncoder::new(file))?;
+        }
+        OutputFormat::Webp => {
+            let encoder = image::codecs::webp::WebPEncoder::new_lossless(file);
+            image.to_rgba8().write_with_encoder(encoder)?;
+        }
+    }
+    Ok(())
+}
+
+fn resize_blocking(image_dir: &Path, source: &Path, options: &ResizeOptions) -> Result<PathBuf> {
+    let metadata = std::fs::metadata(source).context("Cannot read image")?;
+    let reader = ImageReader::open(source)?.with_guessed_format()?;
+    let source_format = reader.format().and_then(OutputFormat::of);
+    let original = reader.into_dimensions().context("Cannot read image size")?;
+
+    let size = target_size(original, options);
+    // GIFs and other formats are served as PNG once resized
+    let format = options
+        .format
+        .or(source_format)
+        .unwrap_or(OutputFormat::Png);
+    if size == original && (options.format.is_none() || Some(format) == source_format) {
+        return Ok(source.to_path_buf());
+    }
+
+    let quality = options.quality.unwrap_or(DEFAULT_QUALITY).clamp(1, 100);
+    let dir = cache_dir(image_dir);
+    let key = cache_key(source, &metadata, size, format, quality);
+    let path = dir.join(format!("{}.{}", key, format.extension()));
+    if path.exists() {
+        return Ok(path);
+    }
+
+    let image = ImageReader::open(source)?
+        .with_guessed_format()?
+        .decode()
+        .context("Cannot decode image")?;
+    let resized = if size == original {
+        image
+    } else {
+        image.resize_exact(size.0, size.1, FilterType::Lanczos3)
+    };
+
+    // Concurrent requests for the same variant each write their own file
+    std::fs::create_dir_all(&dir)?;
+    let tmp = dir.join(format!("{}.{}.tmp", key, uuid::Uuid::new_v4()));
+    if let Err(e) = encode(&resized, &tmp, format, quality) {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// The image at `source` in the requested size and format, through the cache
+///
+/// Returns `source` itself when no parameter changes the image.
+pub async fn resize(image_dir: &Path, source: &Path, options: &ResizeOptions) -> Result<PathBuf> {
+    if options.is_empty() {
+        return Ok(source.to_path_buf());
+    }
+    let (image_dir, source, options) = (image_dir.to_path_buf(), source.to_path_buf(), *options);
+    tokio::task::spawn_blocking(move || resize_blocking(&image_dir, &source, &options)).await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_size() {
+        let poster = (1000, 1500);
+        let opts = |f: fn(&mut ResizeOptions)| {
+            let mut options = ResizeOptions::default();
+            f(&mut options);
+            options
+        };
+
+        assert_eq!(
+            target_size(poster, &opts(|o| o.max_width = Some(300))),
+            (300, 450)
+        );
+        assert_eq!(
+            target_size(poster, &opts(|o| o.height = Some(300))),
+            (200, 300)
+        );
+        // Fill covers the box
+        let fill = opts(|o| {
+            o.fill_width = Some(400);
+            o.fill_height = Some(400);
+        });
+        assert_eq!(target_size(poster, &fill), (400, 600));
+        // Max bounds the exact size; nothing is scaled up
+        let bounded = opts(|o| {
+            o.width = Some(600);
+            o.max_height = Some(600);
+        });
+        assert_eq!(target_size(poster, &bounded), (400, 600));
+        assert_eq!(target_size(poster, &opts(|o| o.width = Some(4000))), poster);
+        assert_eq!(
+            target_size((10, 3000), &opts(|o| o.max_height = Some(100))),
+            (1, 100)
+        );
+
+        assert!(opts(|o| o.max_width = Some(0)).is_empty());
+        assert!(!opts(|o| o.format = Some(OutputFormat::Webp)).is_empty());
+        assert_eq!(OutputFormat::parse("Jpg"), Some(OutputFormat::Jpeg));
+        assert_eq!(OutputFormat::parse("Svg"), None);
+    }
+}
@@ -0,0 +1,241 @@
+// Downloaded image validation
+//
+// Providers and CDNs sometimes answer an image URL with an HTML error page, a
+// JSON error, or a connection that drops halfway, and all of those used to be
+// cached and served as artwork. Downloads are now checked before they are
+// written: the content type mustn't be text, the data must parse as a JPEG,
+// PNG, GIF or WebP with sensible dimensions and must not be cut off, and a
+// backdrop or banner must be wider than it is tall. A rejected download fails
+// like any other, so the image queue retries it. The dimensions are stored in
+// the images table for /Items/{id}/Images.
+
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+
+/// Smallest width or height accepted (placeholder pixels are smaller)
+const MIN_SIDE: u32 = 16;
+
+/// How far from the end an end-of-image marker may be (some encoders pad)
+const TRAILER_WINDOW: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Gif,
+    Webp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageInfo {
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn be16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le16(data: &[u8], at: usize) -> Option<u32> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as u32)
+}
+
+fn le24(data: &[u8], at: usize) -> Option<u32> {
+    let b = data.get(at..at + 3)?;
+    Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+}
+
+fn be32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// Whether `needle` appears near the end of the data
+fn ends_with_marker(data: &[u8], needle: &[u8]) -> bool {
+    let tail = &data[data.len().saturating_sub(TRAILER_WINDOW)..];
+    tail.windows(needle.len()).any(|w| w == needle)
+}
+
+/// Frame size from the first SOF segment of a JPEG
+fn jpeg_size(data: &[u8]) -> Option<(u32, u32)> {
+    let mut i = 2;
+    loop {
+        if *data.get(i)? != 0xFF {
+            return None;
+        }
+        // Markers may be preceded by fill bytes
+        while *data.get(i)? == 0xFF {
+            i += 1;
+        }
+        let marker = data[i];
+        i += 1;
+        match marker {
+            0x01 | 0xD0..=0xD7 => continue,
+            // End of image or start of scan before any frame header
+            0xD9 | 0xDA => return None,
+            0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                return Some((be16(data, i + 5)?, be16(data, i + 3)?));
+            }
+            _ => i += be16(data, i)? as usize,
+        }
+    }
+}
+
+/// Canvas size of a WebP
+fn webp_size(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        b"VP8 " => Some((le16(data, 26)? & 0x3FFF, le16(data, 28)? & 0x3FFF)),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+        }
+        b"VP8X" => Some((le24(data, 24)? + 1, le24(data, 27)? + 1)),
+        _ => None,
+    }
+}
+
+/// Identify an image and read its dimensions, checking it isn't truncated
+pub fn inspect(data: &[u8]) -> Result<ImageInfo> {
+    let (format, size, complete) = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        (
+            ImageFormat::Jpeg,
+            jpeg_size(data),
+            ends_with_marker(data, &[0xFF, 0xD9]),
+        )
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let size = (data.get(12..16) == Some(b"IHDR"))
+            .then(|| Some((be32(data, 16)?, be32(data, 20)?)))
+            .flatten();
+        (ImageFormat::Png, size, ends_with_marker(data, b"IEND"))
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        let size = le16(data, 6).zip(le16(data, 8));
+        (ImageFormat::Gif, size, data.ends_with(&[0x3B]))
+    } else if data.starts_with(b"RIFF") && data.get(8..12) == Some(b"WEBP") {
+        let riff_len = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        (
+            ImageFormat::Webp,
+            webp_size(data),
+            data.len() >= riff_len + 8,
+        )
+    } else {
+        let start = String::from_utf8_lossy(&data[..data.len().min(32)]).to_string();
+        bail!("Not an image (starts with {:?})", start.trim());
+    };
+
+    let Some((width, height)) = size else {
+        bail!("Unreadable {:?} header", format);
+    };
+    if !complete {
+        bail!("Truncated {:?} image", format);
+    }
+    if width < MIN_SIDE || height < MIN_SIDE {
+        bail!("{:?} image is only {}x{}", format, width, height);
+    }
+    Ok(ImageInfo {
+        format,
+        width,
+        height,
+    })
+}
+
+/// Check a downloaded image before it is cached as an item's `image_type`
+pub fn validate_download(
+    data: &[u8],
+    content_type: Option<&str>,
+    image_type: &str,
+) -> Result<ImageInfo> {
+    if let Some(content_type) = content_type {
+        let content_type = content_type.to_ascii_lowercase();
+        if content_type.starts_with("text/") || content_type.contains("json") {
+            bail!("Server returned {} instead of an image", content_type);
+        }
+    }
+
+    let info = inspect(data)?;
+    let landscape_ratio = match crate::db::split_image_index(image_type).0 {
+        "Backdrop" | "Thumb" => Some(1.0),
+        "Banner" => Some(2.0),
+        _ => None,
+    };
+    if let Some(ratio) = landscape_ratio {
+        if (info.width as f64) < info.height as f64 * ratio {
+            bail!(
+                "{}x{} image doesn't fit a {} (too narrow)",
+                info.width,
+                info.height,
+                image_type
+            );
+        }
+    }
+    Ok(info)
+}
+
+/// Inspect an image file on disk
+pub async fn inspect_file(path: &Path) -> Result<ImageInfo> {
+    let data = tokio::fs::read(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    inspect(&data)
+}
+
+/// Content type of a response, without parameters
+pub fn content_type(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR".to_vec();
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82");
+        data
+    }
+
+    fn jpeg(width: u16, height: u16) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        // APP0 segment, then a baseline frame header
+        data.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00]);
+        data.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 0x08]);
+        data.extend_from_slice(&height.to_be_bytes());
+        data.extend_from_slice(&width.to_be_bytes());
+        data.extend_from_slice(&[0x01, 0x01, 0x11, 0x00]);
+        data.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+        data
+    }
+
+    #[test]
+    fn test_inspect_and_validate_downloads() {
+        let info = inspect(&png(680, 1000)).unwrap();
+        assert_eq!(
+            (info.format, info.width, info.height),
+            (ImageFormat::Png, 680, 1000)
+        );
+        let info = inspect(&jpeg(1920, 1080)).unwrap();
+        assert_eq!(
+            (info.format, info.width, info.height),
+            (ImageFormat::Jpeg, 1920, 1080)
+        );
+
+        // Error pages, cut-off downloads and placeholder pixels
+        assert!(inspect(b"<!DOCTYPE html><html>502 Bad Gateway</html>").is_err());
+        let full = jpeg(1920, 1080);
+        assert!(inspect(&full[..full.len() - 2]).is_err());
+        assert!(inspect(&png(1, 1)).is_err());
+
+        // Content type and shape for the image type
+        assert!(validate_download(&png(680, 1000), Some("image/png"), "Primary").is_ok());
+        assert!(validate_download(&png(680, 1000), Some("text/html"), "Primary").is_err());
+        assert!(validate_download(&png(680, 1000), None, "Backdrop").is_err());
+        assert!(validate_download(&jpeg(1920, 1080), None, "Backdrop").is_ok());
+    }
+}
@@ -0,0 +1,212 @@
+// Registration invites
+//
+// On friends-and-family servers the admin would otherwise create every account
+// by hand and pass the password on. An invite is a code the admin shares
+// instead: anyone holding it can create their own account until it expires or
+// runs out of uses. The library access the admin picked when creating the
+// invite is applied to each account made with it.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+use super::auth;
+use crate::{
+    db,
+    events::{self, ServerEvent},
+    models::User,
+};
+
+/// Longest an invite may stay valid
+pub const MAX_LIFETIME_HOURS: i64 = 30 * 24;
+
+/// An invite code and the account template it carries
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Invite {
+    pub code: String,
+    pub created_by: String,
+    pub created_at: String,
+    pub expires_at: String,
+    pub max_uses: i64,
+    pub use_count: i64,
+    pub enable_all_folders: bool,
+    /// Comma-separated library IDs
+    pub enabled_folders: Option<String>,
+}
+
+impl Invite {
+    /// Whether the invite can still be redeemed
+    pub fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.use_count < self.max_uses
+            && DateTime::parse_from_rfc3339(&self.expires_at).is_ok_and(|expires| expires > now)
+    }
+
+    /// Libraries accounts made with the invite may see, None for all
+    pub fn library_access(&self) -> Option<Vec<String>> {
+        (!self.enable_all_folders)
+            .then(|| super::client_capabilities::split_list(self.enabled_folders.as_deref()))
+    }
+}
+
+/// Create an invite valid for `lifetime_hours` and `max_uses` accounts
+pub async fn create(
+    pool: &SqlitePool,
+    created_by: &str,
+    lifetime_hours: i64,
+    max_uses: i64,
+    library_access: Option<&[String]>,
+) -> Result<Invite> {
+    let now = Utc::now();
+    let invite = Invite {
+        code: uuid::Uuid::new_v4().simple().to_string(),
+        created_by: created_by.to_string(),
+        created_at: now.to_rfc3339(),
+        expires_at: (now + chrono::Duration::hours(lifetime_hours.clamp(1, MAX_LIFETIME_HOURS)))
+            .to_rfc3339(),
+        max_uses: max_uses.max(1),
+        use_count: 0,
+        enable_all_folders: library_access.is_none(),
+        enabled_folders: library_access.map(|ids| ids.join(",")),
+    };
+
+    sqlx::query(
+        "INSERT INTO user_invites
+            (code, created_by, created_at, expires_at, max_uses, use_count, enable_all_folders, enabled_folders)
+         VALUES (?, ?, ?, ?, ?, 0, ?, ?)",
+    )
+    .bind(&invite.code)
+    .bind(&invite.created_by)
+    .bind(&invite.created_at)
+    .bind(&invite.expires_at)
+    .bind(invite.max_uses)
+    .bind(invite.enable_all_folders)
+    .bind(&invite.enabled_folders)
+    .execute(pool)
+    .await?;
+    Ok(invite)
+}
+
+/// All invites, newest first
+pub async fn list(pool: &SqlitePool) -> Result<Vec<Invite>> {
+    Ok(
+        sqlx::query_as("SELECT * FROM user_invites ORDER BY created_at DESC")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+/// Look up an invite by code
+pub async fn get(pool: &SqlitePool, code: &str) -> Result<Option<Invite>> {
+    Ok(sqlx::query_as("SELECT * FROM user_invites WHERE code = ?")
+        .bind(code)
+        .fetch_optional(pool)
+        .await?)
+}
+
+/// Revoke an invite, returning whether it existed
+pub async fn revoke(pool: &SqlitePool, code: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM user_invites WHERE code = ?")
+        .bind(code)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Create an account with an invite
+///
+/// Returns None when the invite doesn't exist, has expired or is used up. The
+/// use is counted and the account created in one transaction, so an invite
+/// can't be redeemed more often than it allows.
+pub async fn redeem(
+    pool: &SqlitePool,
+    code: &str,
+    name: &str,
+    password: &str,
+) -> Result<Option<User>> {
+    let mut tx = pool.begin().await?;
+    let invite: Option<Invite> = sqlx::query_as("SELECT * FROM user_invites WHERE code = ?")
+        .bind(code)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let now = Utc::now();
+    let Some(invite) = invite.filter(|i| i.is_usable(now)) else {
+        return Ok(None);
+    };
+
+    let claimed = sqlx::query(
+        "UPDATE user_invites SET use_count = use_count + 1 WHERE code = ? AND use_count < max_uses",
+    )
+    .bind(code)
+    .execute(&mut *tx)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    let user = User {
+        id: uuid::Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        password_hash: auth::hash_password(password)?,
+        is_admin: false,
+        created_at: now.to_rfc3339(),
+    };
+    sqlx::query(
+        "INSERT INTO users (id, name, password_hash, is_admin, created_at) VALUES (?, ?, ?, 0, ?)",
+    )
+    .bind(&user.id)
+    .bind(&user.name)
+    .bind(&user.password_hash)
+    .bind(&user.created_at)
+    .execute(&mut *tx)
+    .await?;
+    db::set_library_access(&mut tx, &user.id, invite.library_access().as_deref()).await?;
+    tx.commit().await?;
+
+    tracing::info!(
+        "User '{}' created with an invite from {}",
+        user.name,
+        invite.created_by
+    );
+    events::publish(ServerEvent::UserCreated {
+        user_id: user.id.clone(),
+        user_name: user.name.clone(),
+    });
+    Ok(Some(user))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invite_usable_and_access() {
+        let now = DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut invite = Invite {
+            code: "abc".to_string(),
+            created_by: "admin".to_string(),
+            created_at: "2024-06-01T00:00:00+00:00".to_string(),
+            expires_at: "2024-06-02T00:00:00+00:00".to_string(),
+            max_uses: 2,
+            use_count: 1,
+            enable_all_folders: false,
+            enabled_folders: Some("lib1, lib2".to_string()),
+        };
+
+        assert!(invite.is_usable(now));
+        assert_eq!(
+            invite.library_access(),
+            Some(vec!["lib1".to_string(), "lib2".to_string()])
+        );
+
+        invite.use_count = 2;
+        assert!(!invite.is_usable(now));
+        invite.use_count = 0;
+        invite.expires_at = "2024-06-01T11:59:59+00:00".to_string();
+        assert!(!invite.is_usable(now));
+
+        invite.enable_all_folders = true;
+        assert_eq!(invite.library_access(), None);
+    }
+}
@@ -4,7 +4,7 @@
 
 use anyhow::{Context, Result};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
@@ -141,7 +141,7 @@ pub struct JikanStreaming {
 
 // === Unified metadata output ===
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JikanMetadata {
     pub mal_id: Option<String>,
     pub name: Option<String>,
@@ -0,0 +1,191 @@
+// Library (CollectionFolder) artwork
+//
+// Libraries are not media items, so their images live outside the images table:
+// an admin-uploaded image is stored as {image_cache}/libraries/{id}/{type}.{ext}.
+// Without an upload, the Primary image is a collage of the posters of the
+// library's newest top-level items, rebuilt when that set of posters changes.
+
+use anyhow::{Context, Result};
+use sqlx::SqlitePool;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use super::mediainfo;
+
+/// Extensions accepted for uploaded library images
+pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "png", "webp", "gif"];
+
+/// Number of posters tiled into the automatic collage
+const COLLAGE_SIZE: usize = 4;
+
+fn library_dir(image_dir: &Path, library_id: &str) -> PathBuf {
+    image_dir.join("libraries").join(library_id)
+}
+
+fn tag_for(input: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// The image an admin uploaded for a library, if any
+pub async fn user_image(image_dir: &Path, library_id: &str, image_type: &str) -> Option<PathBuf> {
+    let dir = library_dir(image_dir, library_id);
+    for ext in IMAGE_EXTENSIONS {
+        let path = dir.join(format!("{}.{}", image_type.to_lowercase(), ext));
+        if tokio::fs::metadata(&path).await.is_ok() {
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Replace a library's image with uploaded data
+pub async fn save_user_image(
+    image_dir: &Path,
+    library_id: &str,
+    image_type: &str,
+    extension: &str,
+    data: &[u8],
+) -> Result<PathBuf> {
+    delete_user_image(image_dir, library_id, image_type).await?;
+
+    let dir = library_dir(image_dir, library_id);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(format!("{}.{}", image_type.to_lowercase(), extension));
+    tokio::fs::write(&path, data)
+        .await
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(path)
+}
+
+/// Remove a library's uploaded image, falling back to the collage
+///
+/// Returns whether there was an image to remove.
+pub async fn delete_user_image(
+    image_dir: &Path,
+    library_id: &str,
+    image_type: &str,
+) -> Result<bool> {
+    let mut removed = false;
+    while let Some(path) = user_image(image_dir, library_id, image_type).await {
+        tokio::fs::remove_file(&path)
+            .await
+            .with_context(|| format!("Failed to remove {}", path.display()))?;
+        removed = true;
+    }
+    Ok(removed)
+}
+
+/// Posters of the newest top-level items in the library that exist on disk
+async fn collage_sources(pool: &SqlitePool, library_id: &str) -> Vec<String> {
+    let paths: Vec<(String,)> = sqlx::query_as(
+        "SELECT i.path FROM images i
+         JOIN media_items m ON m.id = i.item_id
+         WHERE m.library_id = ? AND m.parent_id IS NULL AND i.image_type = 'Primary'
+         ORDER BY m.created_at DESC, m.id
+         LIMIT 12",
+    )
+    .bind(library_id)
+    .fetch_all(pool)
+    .await
+    .unwrap_or_default();
+
+    let mut sources = Vec::new();
+    for (path,) in paths {
+        if sources.len() == COLLAGE_SIZE {
+            break;
+        }
+        if tokio::fs::metadata(&path).await.is_ok() {
+            sources.push(path);
+        }
+    }
+    sources
+}
+
+/// Image tag for a library image, changing whenever the served image would
+pub async fn image_tag(
+    pool: &SqlitePool,
+    image_dir: &Path,
+    library_id: &str,
+    image_type: &str,
+) -> Option<String> {
+    if let Some(path) = user_image(image_dir, library_id, image_type).await {
+        let modified = tokio::fs::metadata(&path)
+            .await
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        return Some(tag_for(&format!("{}:{}", path.display(), modified)));
+    }
+
+    if !image_type.eq_ignore_ascii_case("Primary") {
+        return None;
+    }
+    let sources = collage_sources(pool, library_id).await;
+    if sources.is_empty() {
+        None
+    } else {
+        Some(tag_for(&sources.join("\n")))
+    }
+}
+
+/// Path of the image to serve for a library, building the collage if needed
+pub async fn library_image(
+    pool: &SqlitePool,
+    image_dir: &Path,
+    library_id: &str,
+    image_type: &str,
+) -> Option<PathBuf> {
+    if let Some(path) = user_image(image_dir, library_id, image_type).await {
+        return Some(path);
+    }
+    if !image_type.eq_ignore_ascii_case("Primary") {
+        return None;
+    }
+
+    let sources = collage_sources(pool, library_id).await;
+    match sources.len() {
+        0 => return None,
+        1 => return Some(PathBuf::from(&sources[0])),
+        _ => {}
+    }
+
+    let dir = library_dir(image_dir, library_id);
+    let collage = dir.join("collage.jpg");
+    // Remembers which posters the collage was built from
+    let manifest = dir.join("collage.sources");
+    let wanted = sources.join("\n");
+    if tokio::fs::metadata(&collage).await.is_ok()
+        && tokio::fs::read_to_string(&manifest).await.ok().as_deref() == Some(wanted.as_str())
+    {
+        return Some(collage);
+    }
+
+    let output = collage.clone();
+    let built = tokio::task::spawn_blocking(move || {
+        let paths: Vec<&Path> = sources.iter().map(Path::new).collect();
+        mediainfo::create_collage(&paths, &output)
+    })
+    .await
+    .context("Task join error")
+    .and_then(|r| r);
+
+    match built {
+        Ok(()) => {
+            if let Err(e) = tokio::fs::write(&manifest, &wanted).await {
+                tracing::warn!("Failed to record collage sources for {}: {}", library_id, e);
+            }
+            Some(collage)
+        }
+        Err(e) => {
+            tracing::warn!("Failed to build collage for library {}: {}", library_id, e);
+            wanted.lines().next().map(PathBuf::from)
+        }
+    }
+}
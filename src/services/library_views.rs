@@ -0,0 +1,211 @@
+// Per-user library order and visibility
+//
+// Each user can put their libraries in their own order, hide libraries from
+// their views and drop libraries from the Latest sections of their home
+// screen, so a family sharing a server can each see the libraries they care
+// about first (and the kids' profile doesn't lead with the 4K remuxes).
+// These are Jellyfin's OrderedViews, MyMediaExcludes and LatestItemsExcludes
+// user settings. Clients read and write them through the "usersettings"
+// display preferences (api::display_preferences) and the user configuration;
+// /UserViews and the Latest lists apply them. Hiding a library is cosmetic:
+// library access is still governed by the user's policy.
+
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+
+use super::client_capabilities::split_list;
+
+/// CustomPrefs key of the user's library order
+pub const ORDERED_VIEWS_KEY: &str = "OrderedViews";
+
+/// CustomPrefs key of the libraries hidden from the user's views
+pub const HIDDEN_VIEWS_KEY: &str = "MyMediaExcludes";
+
+/// CustomPrefs key of the libraries left out of the user's Latest sections
+pub const LATEST_EXCLUDES_KEY: &str = "LatestItemsExcludes";
+
+/// A user's library order and visibility, as library IDs
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryViewSettings {
+    /// Libraries in the user's order; the rest follow by name
+    pub ordered: Vec<String>,
+    /// Libraries left out of /UserViews
+    pub hidden: Vec<String>,
+    /// Libraries without a Latest section
+    pub latest_excluded: Vec<String>,
+}
+
+impl LibraryViewSettings {
+    pub fn is_hidden(&self, library_id: &str) -> bool {
+        self.hidden.iter().any(|id| id == library_id)
+    }
+
+    pub fn is_latest_excluded(&self, library_id: &str) -> bool {
+        self.latest_excluded.iter().any(|id| id == library_id)
+    }
+
+    /// Apply the keys a CustomPrefs map sets (comma-separated library IDs)
+    ///
+    /// Returns whether the map set any; keys it leaves out keep their value.
+    pub fn merge_custom_prefs(&mut self, prefs: &BTreeMap<String, Option<String>>) -> bool {
+        let mut changed = false;
+        for (key, list) in [
+            (ORDERED_VIEWS_KEY, &mut self.ordered),
+            (HIDDEN_VIEWS_KEY, &mut self.hidden),
+            (LATEST_EXCLUDES_KEY, &mut self.latest_excluded),
+        ] {
+            if let Some(value) = prefs.get(key) {
+                *list = split_list(value.as_deref());
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Add the settings to a CustomPrefs map
+    pub fn write_custom_prefs(&self, prefs: &mut BTreeMap<String, Option<String>>) {
+        for (key, list) in [
+            (ORDERED_VIEWS_KEY, &self.ordered),
+            (HIDDEN_VIEWS_KEY, &self.hidden),
+            (LATEST_EXCLUDES_KEY, &self.latest_excluded),
+        ] {
+            prefs.insert(key.to_string(), Some(list.join(",")));
+        }
+    }
+
+    /// Sort libraries (listed by name) into the user's order
+    ///
+    /// Ordered libraries come first, in the user's order; libraries the
+    /// order doesn't mention (added since) keep their relative order after them.
+    pub fn sort<T>(&self, libraries: &mut [T], id: impl Fn(&T) -> &str) {
+        libraries.sort_by_key(|lib| {
+            self.ordered
+                .iter()
+                .position(|ordered| ordered == id(lib))
+                .unwrap_or(usize::MAX)
+        });
+    }
+}
+
+/// Row of user_library_views
+#[derive(Debug, sqlx::FromRow)]
+struct ViewRow {
+    library_id: String,
+    sort_index: Option<i64>,
+    hidden: bool,
+    hide_latest: bool,
+}
+
+/// A user's library order and visibility (all defaults if never set)
+pub async fn load(pool: &SqlitePool, user_id: &str) -> sqlx::Result<LibraryViewSettings> {
+    let rows: Vec<ViewRow> = sqlx::query_as(
+        "SELECT library_id, sort_index, hidden, hide_latest FROM user_library_views
+         WHERE user_id = ? ORDER BY sort_index IS NULL, sort_index, library_id",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut settings = LibraryViewSettings::default();
+    for row in rows {
+        if row.sort_index.is_some() {
+            settings.ordered.push(row.library_id.clone());
+        }
+        if row.hidden {
+            settings.hidden.push(row.library_id.clone());
+        }
+        if row.hide_latest {
+            settings.latest_excluded.push(row.library_id);
+        }
+    }
+    Ok(settings)
+}
+
+/// Replace a user's library order and visibility
+///
+/// IDs that aren't libraries (deleted since, or typos) are dropped.
+pub async fn save(
+    pool: &SqlitePool,
+    user_id: &str,
+    settings: &LibraryViewSettings,
+) -> sqlx::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM user_library_views WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let libraries = settings
+        .ordered
+        .iter()
+        .chain(&settings.hidden)
+        .chain(&settings.latest_excluded);
+    for library_id in libraries {
+        let sort_index = settings
+            .ordered
+            .iter()
+            .position(|id| id == library_id)
+            .map(|i| i as i64);
+        sqlx::query(
+            "INSERT OR IGNORE INTO user_library_views (user_id, library_id, sort_index, hidden, hide_latest)
+             SELECT ?, id, ?, ?, ? FROM libraries WHERE id = ?",
+        )
+        .bind(user_id)
+        .bind(sort_index)
+        .bind(settings.is_hidden(library_id))
+        .bind(settings.is_latest_excluded(library_id))
+        .bind(library_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_library_order_and_custom_prefs() {
+        let settings = LibraryViewSettings {
+            ordered: vec!["tv".to_string(), "movies".to_string(), "gone".to_string()],
+            hidden: vec!["music".to_string()],
+            latest_excluded: vec![],
+        };
+        let mut libraries = vec!["anime", "movies", "music", "tv"];
+        settings.sort(&mut libraries, |id| *id);
+        assert_eq!(libraries, vec!["tv", "movies", "anime", "music"]);
+
+        assert!(settings.is_hidden("music"));
+        assert!(!settings.is_hidden("tv"));
+        assert!(!settings.is_latest_excluded("music"));
+
+        let mut merged = settings.clone();
+        let prefs = BTreeMap::from([
+            (HIDDEN_VIEWS_KEY.to_string(), None),
+            (
+                LATEST_EXCLUDES_KEY.to_string(),
+                Some("tv, movies".to_string()),
+            ),
+            ("homesection0".to_string(), Some("latestmedia".to_string())),
+        ]);
+        assert!(merged.merge_custom_prefs(&prefs));
+        assert_eq!(merged.ordered, settings.ordered);
+        assert!(merged.hidden.is_empty());
+        assert_eq!(merged.latest_excluded, vec!["tv", "movies"]);
+        assert!(!merged.merge_custom_prefs(&BTreeMap::new()));
+
+        let mut written = BTreeMap::new();
+        merged.write_custom_prefs(&mut written);
+        assert_eq!(
+            written.get(ORDERED_VIEWS_KEY),
+            Some(&Some("tv,movies,gone".to_string()))
+        );
+        assert_eq!(written.get(HIDDEN_VIEWS_KEY), Some(&Some(String::new())));
+
+        let mut libraries = vec!["b", "a"];
+        LibraryViewSettings::default().sort(&mut libraries, |id| *id);
+        assert_eq!(libraries, vec!["b", "a"]);
+    }
+}
@@ -0,0 +1,762 @@
+// Live TV from an IPTV tuner
+//
+// Channels come from an M3U playlist (`live_tv.m3u`) and their guide from an
+// XMLTV file (`live_tv.xmltv`, or the playlist's url-tvg header), each a URL or
+// a local path; guides are often served gzipped. The "Refresh Guide" task
+// reads both into the channels and programs tables: channels keep their IDs
+// across refreshes (they come from the tvg-id, else the stream URL) so
+// favorites and clients' remembered channels survive, channels gone from the
+// playlist are removed, and the programs are replaced with the guide's entries
+// that haven't ended yet. Guide channels are matched to playlist channels by
+// tvg-id, then by display name. Channels are played by redirecting clients to
+// their stream URL from the usual /Videos/{id}/stream route.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use regex::Regex;
+use sqlx::SqlitePool;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::sync::LazyLock;
+use std::time::Duration;
+use uuid::Uuid;
+
+use super::xml::{self, Element};
+use crate::config::LiveTvConfig;
+
+/// How long fetching the playlist or the guide may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Programs further ahead than this are left out of the guide
+const MAX_GUIDE_DAYS: i64 = 14;
+
+static RE_ATTRIBUTE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"([\w-]+)=(?:"([^"]*)"|'([^']*)')"#).unwrap());
+
+/// A channel read from the M3U playlist
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct M3uChannel {
+    /// tvg-id
+    pub guide_id: Option<String>,
+    pub name: String,
+    /// tvg-chno
+    pub number: Option<String>,
+    pub group_title: Option<String>,
+    pub logo_url: Option<String>,
+    pub stream_url: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct M3uPlaylist {
+    pub channels: Vec<M3uChannel>,
+    /// The header's url-tvg (or x-tvg-url)
+    pub guide_url: Option<String>,
+}
+
+/// A channel of the XMLTV guide
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GuideChannel {
+    pub id: String,
+    pub display_names: Vec<String>,
+    pub icon: Option<String>,
+}
+
+/// A programme of the XMLTV guide
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuideProgram {
+    /// The guide channel's id
+    pub channel: String,
+    pub title: String,
+    pub episode_title: Option<String>,
+    pub overview: Option<String>,
+    pub category: Option<String>,
+    pub image_url: Option<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Guide {
+    pub channels: Vec<GuideChannel>,
+    pub programs: Vec<GuideProgram>,
+}
+
+/// A stored channel, as clients see it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Channel {
+    pub id: String,
+    pub name: String,
+    pub number: Option<String>,
+    pub logo_url: Option<String>,
+    pub stream_url: String,
+}
+
+/// A stored guide entry
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Program {
+    pub id: String,
+    pub channel_id: String,
+    pub title: String,
+    pub episode_title: Option<String>,
+    pub overview: Option<String>,
+    pub category: Option<String>,
+    pub image_url: Option<String>,
+    pub start_date: String,
+    pub end_date: String,
+}
+
+/// What a guide refresh stored
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RefreshSummary {
+    pub channels: usize,
+    pub programs: usize,
+}
+
+fn attributes(text: &str) -> HashMap<String, String> {
+    RE_ATTRIBUTE
+        .captures_iter(text)
+        .map(|c| {
+            let value = c.get(2).or(c.get(3)).map_or("", |v| v.as_str());
+            (c[1].to_lowercase(), value.trim().to_string())
+        })
+        .collect()
+}
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value.filter(|v| !v.is_empty()).cloned()
+}
+
+/// Parse an extended M3U playlist
+///
+/// Entries without an #EXTINF line are named after their URL.
+pub fn parse_m3u(text: &str) -> M3uPlaylist {
+    let mut playlist = M3uPlaylist::default();
+    let mut pending: Option<M3uChannel> = None;
+    let mut group: Option<String> = None;
+
+    for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(header) = line.strip_prefix("#EXTM3U") {
+            let attrs = attributes(header);
+            playlist.guide_url = non_empty(attrs.get("url-tvg").or(attrs.get("x-tvg-url")))
+                // Several guides may be listed; the first one is used
+                .and_then(|urls| urls.split(',').next().map(|u| u.trim().to_string()));
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // The name follows the first comma after the attributes, which
+            // may themselves contain commas
+            let attrs_end = RE_ATTRIBUTE.find_iter(info).last().map_or(0, |m| m.end());
+            let name = info[attrs_end..]
+                .split_once(',')
+                .map(|(_, name)| name.trim().to_string())
+                .unwrap_or_default();
+            let attrs = attributes(&info[..attrs_end]);
+            pending = Some(M3uChannel {
+                guide_id: non_empty(attrs.get("tvg-id")),
+                name: if name.is_empty() {
+                    non_empty(attrs.get("tvg-name")).unwrap_or_default()
+                } else {
+                    name
+                },
+                number: non_empty(attrs.get("tvg-chno").or(attrs.get("channel-number"))),
+                group_title: non_empty(attrs.get("group-title")),
+                logo_url: non_empty(attrs.get("tvg-logo")),
+                stream_url: String::new(),
+            });
+        } else if let Some(title) = line.strip_prefix("#EXTGRP:") {
+            group = Some(title.trim().to_string()).filter(|g| !g.is_empty());
+        } else if !line.starts_with('#') {
+            let mut channel = pending.take().unwrap_or_default();
+            if channel.name.is_empty() {
+                channel.name = line.to_string();
+            }
+            if channel.group_title.is_none() {
+                channel.group_title = group.take();
+            }
+            channel.stream_url = line.to_string();
+            playlist.channels.push(channel);
+            group = None;
+        }
+    }
+    playlist
+}
+
+/// Parse an XMLTV time ("20240501180000 +0200"; UTC without an offset)
+fn parse_xmltv_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_str(value, "%Y%m%d%H%M%S %z") {
+        return Some(time.with_timezone(&Utc));
+    }
+    let digits = value.get(..14)?;
+    NaiveDateTime::parse_from_str(digits, "%Y%m%d%H%M%S")
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Parse an XMLTV guide
+pub fn parse_xmltv(text: &str) -> Result<Guide> {
+    let tv = xml::parse(text).context("Invalid XMLTV guide")?;
+    let icon = |element: &Element| {
+        element
+            .child("icon")
+            .and_then(|icon| icon.attribute("src"))
+            .map(str::trim)
+            .filter(|src| !src.is_empty())
+            .map(String::from)
+    };
+
+    let channels = tv
+        .children("channel")
+        .filter_map(|channel| {
+            let id = channel
+                .attribute("id")
+                .map(str::trim)
+                .filter(|id| !id.is_empty())?;
+            Some(GuideChannel {
+                id: id.to_string(),
+                display_names: channel
+                    .children("display-name")
+                    .filter_map(Element::text)
+                    .map(String::from)
+                    .collect(),
+                icon: icon(channel),
+            })
+        })
+        .collect();
+
+    let programs = tv
+        .children("programme")
+        .filter_map(|programme| {
+            let start = parse_xmltv_time(programme.attribute("start")?)?;
+            let end = parse_xmltv_time(programme.attribute("stop")?)?;
+            if end <= start {
+                return None;
+            }
+            let text = |tag| programme.child_text(tag).map(String::from);
+            Some(GuideProgram {
+                channel: programme.attribute("channel")?.trim().to_string(),
+                title: text("title")?,
+                episode_title: text("sub-title"),
+                overview: text("desc"),
+                category: text("category"),
+                image_url: icon(programme),
+                start,
+                end,
+            })
+        })
+        .collect();
+
+    Ok(Guide { channels, programs })
+}
+
+/// A time as stored in the programs table
+pub fn guide_time(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// A client's date parameter in the stored form, so dates compare as text
+pub fn parse_guide_time(value: &str) -> Option<String> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|time| guide_time(time.with_timezone(&Utc)))
+}
+
+/// Read a playlist or guide from a URL or a local file, unpacking gzip
+async fn read_source(source: &str) -> Result<String> {
+    let bytes = if source.starts_with("http://") || source.starts_with("https://") {
+        reqwest::Client::builder()
+            .timeout(FETCH_TIMEOUT)
+            .user_agent(concat!("jellyfin-rust/", env!("CARGO_PKG_VERSION")))
+            .build()?
+            .get(source)
+            .send()
+            .await?
+            .error_for_status()?
+            .bytes()
+            .await?
+            .to_vec()
+    } else {
+        tokio::fs::read(source).await?
+    };
+
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut text)
+            .context("Invalid gzip data")?;
+        return Ok(text);
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// IDs of the playlist's channels, stable across refreshes
+///
+/// Channels sharing a tvg-id (an HD and an SD feed) are told apart by their
+/// order in the playlist.
+fn channel_ids(channels: &[M3uChannel]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    channels
+        .iter()
+        .map(|channel| {
+            let key = channel.guide_id.as_ref().unwrap_or(&channel.stream_url);
+            let count = seen.entry(key.clone()).or_default();
+            *count += 1;
+            let key = match *count {
+                1 => format!("live-tv-channel:{}", key),
+                n => format!("live-tv-channel:{}#{}", key, n),
+            };
+            Uuid::new_v3(&Uuid::NAMESPACE_URL, key.as_bytes()).to_string()
+        })
+        .collect()
+}
+
+/// Read the playlist and guide into the channels and programs tables
+pub async fn refresh(pool: &SqlitePool, config: &LiveTvConfig) -> Result<RefreshSummary> {
+    let Some(m3u) = config.m3u.as_deref().filter(|m| !m.is_empty()) else {
+        bail!("No M3U tuner is configured (live_tv.m3u)");
+    };
+    let playlist = parse_m3u(
+        &read_source(m3u)
+            .await
+            .context("Failed to read the M3U playlist")?,
+    );
+    // An error page instead of the playlist shouldn't remove every channel
+    if playlist.channels.is_empty() {
+        bail!("The M3U playlist has no channels");
+    }
+
+    let guide_source = config
+        .xmltv
+        .clone()
+        .filter(|x| !x.is_empty())
+        .or(playlist.guide_url.clone());
+    let guide = match guide_source {
+        Some(source) => Some(parse_xmltv(
+            &read_source(&source)
+                .await
+                .context("Failed to read the XMLTV guide")?,
+        )?),
+        None => None,
+    };
+
+    let ids = channel_ids(&playlist.channels);
+    let icons: HashMap<&str, &str> = guide
+        .iter()
+        .flat_map(|g| &g.channels)
+        .filter_map(|c| Some((c.id.as_str(), c.icon.as_deref()?)))
+        .collect();
+
+    let mut tx = pool.begin().await?;
+    for (index, (channel, id)) in playlist.channels.iter().zip(&ids).enumerate() {
+        let logo = channel.logo_url.clone().or_else(|| {
+            let guide_id = channel.guide_id.as_deref()?;
+            icons.get(guide_id).map(|icon| icon.to_string())
+        });
+        sqlx::query(
+            "INSERT INTO channels (id, guide_id, name, number, group_title, logo_url, stream_url, sort_index)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET guide_id = excluded.guide_id, name = excluded.name,
+                 number = excluded.number, group_title = excluded.group_title,
+                 logo_url = excluded.logo_url, stream_url = excluded.stream_url,
+                 sort_index = excluded.sort_index",
+        )
+        .bind(id)
+        .bind(&channel.guide_id)
+        .bind(&channel.name)
+        .bind(
+            channel
+                .number
+                .clone()
+                .unwrap_or_else(|| (index + 1).to_string()),
+        )
+        .bind(&channel.group_title)
+        .bind(logo)
+        .bind(&channel.stream_url)
+        .bind(index as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+    sqlx::query("DELETE FROM channels WHERE id NOT IN (SELECT value FROM json_each(?))")
+        .bind(serde_json::to_string(&ids)?)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut program_count = 0;
+    if let Some(guide) = &guide {
+        // Guide channel id -> our channels showing it: by tvg-id when the
+        // guide knows it (guides without <channel> elements still name it in
+        // their programmes), else by display name
+        let guide_ids: HashSet<&str> = guide
+            .channels
+            .iter()
+            .map(|c| c.id.as_str())
+            .chain(guide.programs.iter().map(|p| p.channel.as_str()))
+            .collect();
+        let mut targets: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (channel, id) in playlist.channels.iter().zip(&ids) {
+            let name = channel.name.to_lowercase();
+            let guide_id = channel
+                .guide_id
+                .as_deref()
+                .filter(|guide_id| guide_ids.contains(guide_id))
+                .or_else(|| {
+                    guide
+                        .channels
+                        .iter()
+                        .find(|c| c.display_names.iter().any(|n| n.to_lowercase() == name))
+                        .map(|c| c.id.as_str())
+                });
+            if let Some(guide_id) = guide_id {
+                targets.entry(guide_id).or_default().push(id.as_str());
+            }
+        }
+
+        sqlx::query("DELETE FROM programs")
+            .execute(&mut *tx)
+            .await?;
+        let now = Utc::now();
+        let horizon = now + chrono::Duration::days(MAX_GUIDE_DAYS);
+        for program in &guide.programs {
+            if program.end <= now || program.start > horizon {
+                continue;
+            }
+            let Some(channel_ids) = targets.get(program.channel.as_str()) else {
+                continue;
+            };
+            let start = guide_time(program.start);
+            for channel_id in channel_ids {
+                let key = format!("live-tv-program:{}:{}", channel_id, start);
+                sqlx::query(
+                    "INSERT OR REPLACE INTO programs
+                     (id, channel_id, title, episode_title, overview, category, image_url, start_date, end_date)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(Uuid::new_v3(&Uuid::NAMESPACE_URL, key.as_bytes()).to_string())
+                .bind(channel_id)
+                .bind(&program.title)
+                .bind(&program.episode_title)
+                .bind(&program.overview)
+                .bind(&program.category)
+                .bind(&program.image_url)
+                .bind(&start)
+                .bind(guide_time(program.end))
+                .execute(&mut *tx)
+                .await?;
+                program_count += 1;
+            }
+        }
+    }
+    tx.commit().await?;
+
+    Ok(RefreshSummary {
+        channels: ids.len(),
+        programs: program_count,
+    })
+}
+
+/// Every channel in playlist order
+pub async fn list_channels(pool: &SqlitePool) -> Result<Vec<Channel>> {
+    Ok(
+        sqlx::query_as("SELECT * FROM channels ORDER BY sort_index, name")
+            .fetch_all(pool)
+            .await?,
+    )
+}
+
+pub async fn find_channel(pool: &SqlitePool, id: &str) -> Result<Option<Channel>> {
+    Ok(sqlx::query_as("SELECT * FROM channels WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?)
+}
+
+pub async fn find_program(pool: &SqlitePool, id: &str) -> Result<Option<Program>> {
+    Ok(sqlx::query_as("SELECT * FROM programs WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?)
+}
+
+/// The logo of a channel or the image of a program
+pub async fn image_url(pool: &SqlitePool, id: &str) -> Result<Option<String>> {
+    Ok(sqlx::query_scalar(
+        "SELECT logo_url FROM channels WHERE id = ?
+         UNION ALL SELECT image_url FROM programs WHERE id = ?",
+    )
+    .bind(id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?
+    .flatten())
+}
+
+/// Image tag of a channel logo or program image, which changes with its URL
+pub fn image_tag(url: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    url.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}
+
+/// ID of the Live TV view among the user's views
+pub fn view_id() -> String {
+    Uuid::new_v3(&Uuid::NAMESPACE_URL, b"live-tv-view").to_string()
+}
+
+/// Whether any channel is known, for showing the Live TV view
+pub async fn has_channels(pool: &SqlitePool) -> Result<bool> {
+    Ok(sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM channels)")
+        .fetch_one(pool)
+        .await?)
+}
+
+/// The program airing now on each channel that has one
+pub async fn current_programs(pool: &SqlitePool) -> Result<HashMap<String, Program>> {
+    let now = guide_time(Utc::now());
+    let programs: Vec<Program> =
+        sqlx::query_as("SELECT * FROM programs WHERE start_date <= ? AND end_date > ?")
+            .bind(&now)
+            .bind(&now)
+            .fetch_all(pool)
+            .await?;
+    Ok(programs
+        .into_iter()
+        .map(|p| (p.channel_id.clone(), p))
+        .collect())
+}
+
+/// Kinds of program clients list separately, told from the XMLTV category
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgramKind {
+    Movie,
+    Sports,
+    News,
+    Kids,
+}
+
+impl ProgramKind {
+    /// Lowercase words a category of this kind contains
+    fn keywords(self) -> &'static [&'static str] {
+        match self {
+            Self::Movie => &["movie", "film"],
+            Self::Sports => &["sport"],
+            Self::News => &["news"],
+            Self::Kids => &["kids", "children", "animation"],
+        }
+    }
+
+    pub fn matches(self, category: Option<&str>) -> bool {
+        let category = category.unwrap_or_default().to_lowercase();
+        self.keywords().iter().any(|k| category.contains(k))
+    }
+}
+
+/// Filters of a program listing; dates are in guide_time form
+#[derive(Debug, Clone, Default)]
+pub struct ProgramFilter {
+    pub channel_ids: Vec<String>,
+    pub min_start_date: Option<String>,
+    pub max_start_date: Option<String>,
+    pub min_end_date: Option<String>,
+    pub max_end_date: Option<String>,
+    pub is_airing: Option<bool>,
+    pub has_aired: Option<bool>,
+    /// Kinds a program must (true) or must not (false) be
+    pub kinds: Vec<(ProgramKind, bool)>,
+}
+
+/// Programs matching `filter` by channel and start time, with the total count
+pub async fn list_programs(
+    pool: &SqlitePool,
+    filter: &ProgramFilter,
+    start_index: i64,
+    limit: Option<i64>,
+) -> Result<(Vec<Program>, i64)> {
+    let now = guide_time(Utc::now());
+    let build = |select: &str| {
+        let mut qb: sqlx::QueryBuilder<sqlx::Sqlite> = sqlx::QueryBuilder::new(select);
+        qb.push(" FROM programs p JOIN channels c ON c.id = p.channel_id WHERE 1 = 1");
+        if !filter.channel_ids.is_empty() {
+            qb.push(" AND p.channel_id IN (SELECT value FROM json_each(");
+            qb.push_bind(serde_json::to_string(&filter.channel_ids).unwrap_or_default());
+            qb.push("))");
+        }
+        let bounds = [
+            (" AND p.start_date >= ", &filter.min_start_date),
+            (" AND p.start_date <= ", &filter.max_start_date),
+            (" AND p.end_date >= ", &filter.min_end_date),
+            (" AND p.end_date <= ", &filter.max_end_date),
+        ];
+        for (sql, value) in bounds {
+            if let Some(value) = value {
+                qb.push(sql);
+                qb.push_bind(value.clone());
+            }
+        }
+        match filter.is_airing {
+            Some(true) => {
+                qb.push(" AND p.start_date <= ");
+                qb.push_bind(now.clone());
+                qb.push(" AND p.end_date > ");
+                qb.push_bind(now.clone());
+            }
+            Some(false) => {
+                qb.push(" AND (p.start_date > ");
+                qb.push_bind(now.clone());
+                qb.push(" OR p.end_date <= ");
+                qb.push_bind(now.clone());
+                qb.push(")");
+            }
+            None => {}
+        }
+        for (kind, wanted) in &filter.kinds {
+            qb.push(if *wanted { " AND (" } else { " AND NOT (" });
+            for (i, keyword) in kind.keywords().iter().enumerate() {
+                if i > 0 {
+                    qb.push(" OR ");
+                }
+                qb.push("LOWER(COALESCE(p.category, '')) LIKE ");
+                qb.push_bind(format!("%{}%", keyword));
+            }
+            qb.push(")");
+        }
+        match filter.has_aired {
+            Some(true) => {
+                qb.push(" AND p.end_date <= ");
+                qb.push_bind(now.clone());
+            }
+            Some(false) => {
+                qb.push(" AND p.end_date > ");
+                qb.push_bind(now.clone());
+            }
+            None => {}
+        }
+        qb
+    };
+
+    let total: i64 = build("SELECT COUNT(*)")
+        .build_query_scalar()
+        .fetch_one(pool)
+        .await?;
+    let mut qb = build("SELECT p.*");
+    qb.push(" ORDER BY p.start_date, c.sort_index LIMIT ");
+    qb.push_bind(limit.unwrap_or(-1));
+    qb.push(" OFFSET ");
+    qb.push_bind(start_index.max(0));
+    let programs = qb.build_query_as().fetch_all(pool).await?;
+    Ok((programs, total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_m3u_and_xmltv() {
+        let playlist = parse_m3u(
+            r#"#EXTM3U url-tvg="http://example.com/guide.xml.gz"
+#EXTINF:-1 tvg-id="news.uk" tvg-chno="101" tvg-logo="http://example.com/news.png" group-title="News, Weather",News 24, HD
+http://example.com/live/news.m3u8
+
+#EXTINF:-1,Music
+#EXTGRP:Radio
+http://example.com/live/music.ts
+http://example.com/live/bare.ts
+"#,
+        );
+        assert_eq!(
+            playlist.guide_url.as_deref(),
+            Some("http://example.com/guide.xml.gz")
+        );
+        assert_eq!(playlist.channels.len(), 3);
+        let news = &playlist.channels[0];
+        assert_eq!(news.guide_id.as_deref(), Some("news.uk"));
+        assert_eq!(news.name, "News 24, HD");
+        assert_eq!(news.number.as_deref(), Some("101"));
+        assert_eq!(news.group_title.as_deref(), Some("News, Weather"));
+        assert_eq!(news.stream_url, "http://example.com/live/news.m3u8");
+        assert_eq!(playlist.channels[1].group_title.as_deref(), Some("Radio"));
+        assert_eq!(playlist.channels[2].name, "http://example.com/live/bare.ts");
+
+        // Channels sharing a tvg-id still get their own stable IDs
+        let mut twins = playlist.channels.clone();
+        twins[1].guide_id = Some("news.uk".to_string());
+        let ids = channel_ids(&twins);
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[0], channel_ids(&playlist.channels)[0]);
+
+        let guide = parse_xmltv(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<tv>
+  <channel id="news.uk"><display-name>News 24</display-name><icon src="http://example.com/n.png"/></channel>
+  <programme start="20240501180000 +0200" stop="20240501190000 +0200" channel="news.uk">
+    <title lang="en">Evening &amp; Late News</title>
+    <sub-title>Part 1</sub-title>
+    <desc><![CDATA[Headlines <live>]]></desc>
+    <category>News</category>
+  </programme>
+  <programme start="20240501190000" stop="20240501180000" channel="news.uk"><title>Backwards</title></programme>
+</tv>"#,
+        )
+        .unwrap();
+        assert_eq!(guide.channels[0].display_names, vec!["News 24"]);
+        assert_eq!(
+            guide.channels[0].icon.as_deref(),
+            Some("http://example.com/n.png")
+        );
+        assert_eq!(guide.programs.len(), 1);
+        let program = &guide.programs[0];
+        assert_eq!(program.title, "Evening & Late News");
+        assert_eq!(program.episode_title.as_deref(), Some("Part 1"));
+        assert_eq!(program.overview.as_deref(), Some("Headlines <live>"));
+        assert_eq!(guide_time(program.start), "2024-05-01T16:00:00Z");
+        assert_eq!(
+            parse_guide_time("2024-05-01T18:00:00.000+02:00").as_deref(),
+            Some("2024-05-01T16:00:00Z")
+        );
+    }
+
+    #[test]
+    fn test_parse_xmltv_entities_and_cdata() {
+        let guide = parse_xmltv(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE tv SYSTEM "xmltv.dtd">
+<tv generator-info-name="test">
+  <channel id="kids&amp;family.uk">
+    <display-name lang="en">Kids &amp; Family</display-name>
+    <display-name>  </display-name>
+    <display-name>K&#38;F</display-name>
+  </channel>
+  <programme start="20240501060000 +0000" stop="20240501063000 +0000" channel="kids&amp;family.uk">
+    <title lang="en"></title>
+    <title lang="en">Bob&#39;s Burgers</title>
+    <sub-title>Tina&#x27;s &#8220;Friend&#8221;</sub-title>
+    <desc lang="en"><![CDATA[Louise & Gene <b>scheme</b>]]> &amp; more</desc>
+    <icon src="http://example.com/p.png?w=1&amp;h=2" />
+  </programme>
+</tv>"#,
+        )
+        .unwrap();
+
+        let channel = &guide.channels[0];
+        assert_eq!(channel.id, "kids&family.uk");
+        assert_eq!(channel.display_names, vec!["Kids & Family", "K&F"]);
+        let program = &guide.programs[0];
+        assert_eq!(program.channel, "kids&family.uk");
+        assert_eq!(program.title, "Bob's Burgers");
+        assert_eq!(
+            program.episode_title.as_deref(),
+            Some("Tina's \u{201c}Friend\u{201d}")
+        );
+        assert_eq!(
+            program.overview.as_deref(),
+            Some("Louise & Gene <b>scheme</b> & more")
+        );
+        assert_eq!(
+            program.image_url.as_deref(),
+            Some("http://example.com/p.png?w=1&h=2")
+        );
+
+        // A truncated download fails instead of emptying the guide
+        assert!(parse_xmltv("<tv><programme start=\"20240501060000\"").is_err());
+    }
+}
@@ -0,0 +1,197 @@
+// Language codes and names
+//
+// Stream languages come from ffprobe (Matroska stores ISO 639-2/B, MP4 often
+// 639-2/T, some muxers 639-1 or a name) and from external file names
+// ("Movie.en.srt", "Movie.english.srt"), so the same language used to show up
+// as eng, en and english. Clients match their language menus and preferred
+// audio and subtitle languages against the three-letter codes of
+// /Localization/Cultures, so every stream language is normalized to the
+// ISO 639-2/B code of the table below, which the Cultures endpoint is built
+// from too. "und" and empty tags mean no language.
+
+/// A language clients can pick
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Language {
+    pub name: &'static str,
+    /// ISO 639-1, empty when the language has none
+    pub two_letter: &'static str,
+    /// ISO 639-2/B, the code stream languages are normalized to
+    pub three_letter: &'static str,
+    /// ISO 639-2/T where it differs from the bibliographic code ("deu" for "ger")
+    pub terminology: Option<&'static str>,
+}
+
+const fn language(
+    name: &'static str,
+    two_letter: &'static str,
+    three_letter: &'static str,
+    terminology: Option<&'static str>,
+) -> Language {
+    Language {
+        name,
+        two_letter,
+        three_letter,
+        terminology,
+    }
+}
+
+/// Known languages, in the order Cultures lists them
+pub const LANGUAGES: &[Language] = &[
+    language("Albanian", "sq", "alb", Some("sqi")),
+    language("Arabic", "ar", "ara", None),
+    language("Armenian", "hy", "arm", Some("hye")),
+    language("Basque", "eu", "baq", Some("eus")),
+    language("Bengali", "bn", "ben", None),
+    language("Bulgarian", "bg", "bul", None),
+    language("Catalan", "ca", "cat", None),
+    language("Chinese", "zh", "chi", Some("zho")),
+    language("Croatian", "hr", "hrv", None),
+    language("Czech", "cs", "cze", Some("ces")),
+    language("Danish", "da", "dan", None),
+    language("Dutch", "nl", "dut", Some("nld")),
+    language("English", "en", "eng", None),
+    language("Estonian", "et", "est", None),
+    language("Filipino", "", "fil", None),
+    language("Finnish", "fi", "fin", None),
+    language("French", "fr", "fre", Some("fra")),
+    language("Galician", "gl", "glg", None),
+    language("Georgian", "ka", "geo", Some("kat")),
+    language("German", "de", "ger", Some("deu")),
+    language("Greek", "el", "gre", Some("ell")),
+    language("Hebrew", "he", "heb", None),
+    language("Hindi", "hi", "hin", None),
+    language("Hungarian", "hu", "hun", None),
+    language("Icelandic", "is", "ice", Some("isl")),
+    language("Indonesian", "id", "ind", None),
+    language("Irish", "ga", "gle", None),
+    language("Italian", "it", "ita", None),
+    language("Japanese", "ja", "jpn", None),
+    language("Kannada", "kn", "kan", None),
+    language("Kazakh", "kk", "kaz", None),
+    language("Korean", "ko", "kor", None),
+    language("Latin", "la", "lat", None),
+    language("Latvian", "lv", "lav", None),
+    language("Lithuanian", "lt", "lit", None),
+    language("Macedonian", "mk", "mac", Some("mkd")),
+    language("Malay", "ms", "may", Some("msa")),
+    language("Malayalam", "ml", "mal", None),
+    language("Marathi", "mr", "mar", None),
+    language("Mongolian", "mn", "mon", None),
+    language("Norwegian", "no", "nor", None),
+    language("Norwegian Bokmål", "nb", "nob", None),
+    language("Norwegian Nynorsk", "nn", "nno", None),
+    language("Persian", "fa", "per", Some("fas")),
+    language("Polish", "pl", "pol", None),
+    language("Portuguese", "pt", "por", None),
+    language("Punjabi", "pa", "pan", None),
+    language("Romanian", "ro", "rum", Some("ron")),
+    language("Russian", "ru", "rus", None),
+    language("Serbian", "sr", "srp", None),
+    language("Slovak", "sk", "slo", Some("slk")),
+    language("Slovenian", "sl", "slv", None),
+    language("Spanish", "es", "spa", None),
+    language("Swahili", "sw", "swa", None),
+    language("Swedish", "sv", "swe", None),
+    language("Tagalog", "tl", "tgl", None),
+    language("Tamil", "ta", "tam", None),
+    language("Telugu", "te", "tel", None),
+    language("Thai", "th", "tha", None),
+    language("Turkish", "tr", "tur", None),
+    language("Ukrainian", "uk", "ukr", None),
+    language("Urdu", "ur", "urd", None),
+    language("Vietnamese", "vi", "vie", None),
+    language("Welsh", "cy", "wel", Some("cym")),
+    language("Multiple languages", "", "mul", None),
+];
+
+/// Withdrawn or common wrong codes still found in files: (code, ISO 639-1)
+const ALIASES: &[(&str, &str)] = &[("jp", "ja"), ("iw", "he"), ("in", "id")];
+
+/// Tags that mean the language isn't known
+const UNDETERMINED: &[&str] = &["und", "unk", "unknown", "undetermined", "zxx", "none"];
+
+/// The language of a code or name in any of the forms files use: "en",
+/// "eng", "English", "en-US", "pt_BR", "deu"
+pub fn find(value: &str) -> Option<&'static Language> {
+    let value = value.trim();
+    // Region and script subtags don't change the language
+    let base = value
+        .split(['-', '_'])
+        .next()
+        .unwrap_or(value)
+        .to_ascii_lowercase();
+    let base = ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == base)
+        .map(|(_, code)| code.to_string())
+        .unwrap_or(base);
+    LANGUAGES.iter().find(|lang| {
+        (!lang.two_letter.is_empty() && lang.two_letter == base)
+            || lang.three_letter == base
+            || lang.terminology == Some(base.as_str())
+            || lang.name.eq_ignore_ascii_case(value)
+    })
+}
+
+/// A stream language as the ISO 639-2/B code clients match against, None when
+/// it's undetermined; codes not in the table are kept, lowercased
+pub fn normalize(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.is_empty()
+        || UNDETERMINED
+            .iter()
+            .any(|code| code.eq_ignore_ascii_case(value))
+    {
+        return None;
+    }
+    Some(match find(value) {
+        Some(lang) => lang.three_letter.to_string(),
+        None => value.to_lowercase(),
+    })
+}
+
+/// English name of a language code, or the code in capitals when it's unknown
+pub fn display_name(code: &str) -> String {
+    match find(code) {
+        Some(lang) => lang.name.to_string(),
+        None => code.to_uppercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_languages() {
+        for value in ["eng", "en", "English", "ENGLISH", "en-US", "en_GB", " eng "] {
+            assert_eq!(normalize(value).as_deref(), Some("eng"), "{}", value);
+        }
+        assert_eq!(normalize("deu").as_deref(), Some("ger"));
+        assert_eq!(normalize("de").as_deref(), Some("ger"));
+        assert_eq!(normalize("zh-Hans").as_deref(), Some("chi"));
+        assert_eq!(normalize("pt-BR").as_deref(), Some("por"));
+        assert_eq!(normalize("jp").as_deref(), Some("jpn"));
+        assert_eq!(normalize("und"), None);
+        assert_eq!(normalize(""), None);
+        assert_eq!(normalize("Klingon").as_deref(), Some("klingon"));
+
+        assert_eq!(display_name("fre"), "French");
+        assert_eq!(display_name("fra"), "French");
+        assert_eq!(display_name("tlh"), "TLH");
+        // Languages are told apart by every code they have
+        let mut codes: Vec<&str> = LANGUAGES
+            .iter()
+            .flat_map(|l| {
+                [l.two_letter, l.three_letter]
+                    .into_iter()
+                    .chain(l.terminology)
+            })
+            .filter(|c| !c.is_empty())
+            .collect();
+        let count = codes.len();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), count);
+    }
+}
@@ -0,0 +1,255 @@
+// Lyrics discovery and LRC parsing
+//
+// Lyrics come from a sidecar file next to the audio file (song.lrc, song.elrc,
+// song.txt) or from the file's embedded lyrics tag. LRC content is parsed into
+// timed lines; anything without timestamps is returned as plain unsynced text.
+
+use std::path::{Path, PathBuf};
+
+use super::mediainfo;
+use crate::time::Ticks;
+
+/// Sidecar extensions, in order of preference (synced formats first)
+const SIDECAR_EXTENSIONS: &[&str] = &["lrc", "elrc", "txt"];
+
+/// Where a set of lyrics was found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LyricsSource {
+    Sidecar,
+    Embedded,
+}
+
+impl LyricsSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sidecar => "sidecar",
+            Self::Embedded => "embedded",
+        }
+    }
+}
+
+/// A single line of lyrics
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+    pub text: String,
+    /// Start time in ticks, None for unsynced lyrics
+    pub start_ticks: Option<i64>,
+}
+
+/// Parsed lyrics with LRC header tags
+#[derive(Debug, Clone, Default)]
+pub struct Lyrics {
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub by: Option<String>,
+    pub length_ticks: Option<i64>,
+    /// [offset:] in ticks (already applied to line start times)
+    pub offset_ticks: Option<i64>,
+    pub is_synced: bool,
+    pub lines: Vec<LyricLine>,
+}
+
+/// Find a sidecar lyrics file for an audio file
+pub fn find_sidecar(audio_path: &Path) -> Option<PathBuf> {
+    SIDECAR_EXTENSIONS
+        .iter()
+        .map(|ext| audio_path.with_extension(ext))
+        .find(|p| p.is_file())
+}
+
+/// Find lyrics for an audio file, preferring a sidecar over embedded tags
+pub async fn discover_lyrics(audio_path: &Path) -> Option<(String, LyricsSource)> {
+    if let Some(sidecar) = find_sidecar(audio_path) {
+        match tokio::fs::read(&sidecar).await {
+            Ok(bytes) => {
+                let content = String::from_utf8_lossy(&bytes)
+                    .trim_start_matches('\u{feff}')
+                    .to_string();
+                if !content.trim().is_empty() {
+                    return Some((content, LyricsSource::Sidecar));
+                }
+            }
+            Err(e) => tracing::warn!("Failed to read lyrics file {:?}: {}", sidecar, e),
+        }
+    }
+
+    match mediainfo::extract_media_info_async(audio_path).await {
+        Ok(info) => info.lyrics.map(|l| (l, LyricsSource::Embedded)),
+        Err(e) => {
+            tracing::debug!("Failed to probe {:?} for lyrics: {}", audio_path, e);
+            None
+        }
+    }
+}
+
+/// Parse a timestamp like "01:23.45", "01:23.456" or "01:23" into ticks
+fn parse_timestamp(s: &str) -> Option<i64> {
+    let (minutes, seconds) = s.trim().split_once(':')?;
+    let minutes: i64 = minutes.parse().ok()?;
+    let (whole, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    let whole: i64 = whole.parse().ok()?;
+    if !(0..60).contains(&whole) {
+        return None;
+    }
+
+    // Fraction may be hundredths ("45") or milliseconds ("456")
+    let fraction_ticks = if fraction.is_empty() {
+        0
+    } else {
+        let digits: String = fraction.chars().take(7).collect();
+        let value: i64 = digits.parse().ok()?;
+        value * 10_i64.pow(7 - digits.len() as u32)
+    };
+
+    Some(Ticks::from_seconds(minutes * 60 + whole).0 + fraction_ticks)
+}
+
+/// Remove enhanced-LRC word timings like "<00:12.34>" from a line
+fn strip_word_timings(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        match rest[start..].find('>') {
+            Some(end) if parse_timestamp(&rest[start + 1..start + end]).is_some() => {
+                out.push_str(&rest[..start]);
+                rest = &rest[start + end + 1..];
+            }
+            _ => {
+                out.push_str(&rest[..=start]);
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse LRC (or plain text) lyrics
+pub fn parse_lyrics(content: &str) -> Lyrics {
+    let mut lyrics = Lyrics::default();
+    let mut timed: Vec<LyricLine> = Vec::new();
+    let mut plain: Vec<String> = Vec::new();
+
+    for raw_line in content.lines() {
+        let mut line = raw_line.trim();
+        let mut starts: Vec<i64> = Vec::new();
+        let mut is_tag = false;
+
+        // A line may carry several leading time tags: "[00:12.00][01:30.00]Chorus"
+        while line.starts_with('[') {
+            let Some(end) = line.find(']') else { break };
+            let tag = &line[1..end];
+
+            if let Some(ticks) = parse_timestamp(tag) {
+                starts.push(ticks);
+            } else if let Some((key, value)) = tag.split_once(':') {
+                let value = value.trim().to_string();
+                match key.trim().to_lowercase().as_str() {
+                    "ar" => lyrics.artist = Some(value),
+                    "al" => lyrics.album = Some(value),
+                    "ti" => lyrics.title = Some(value),
+                    "au" => lyrics.author = Some(value),
+                    "by" => lyrics.by = Some(value),
+                    "length" => lyrics.length_ticks = parse_timestamp(&value),
+                    "offset" => {
+                        lyrics.offset_ticks = value.parse().ok().map(|ms| Ticks::from_millis(ms).0)
+                    }
+                    _ => {}
+                }
+                is_tag = true;
+            } else {
+                break;
+            }
+            line = line[end + 1..].trim_start();
+        }
+
+        if !starts.is_empty() {
+            let text = strip_word_timings(line);
+            for start in starts {
+                timed.push(LyricLine {
+                    text: text.clone(),
+                    start_ticks: Some(start),
+                });
+            }
+        } else if !is_tag {
+            plain.push(line.to_string());
+        }
+    }
+
+    if timed.is_empty() {
+        // Trim leading/trailing blank lines but keep stanza breaks
+        let first = plain
+            .iter()
+            .position(|l| !l.is_empty())
+            .unwrap_or(plain.len());
+        let last = plain
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(first, |i| i + 1);
+        lyrics.lines = plain[first..last]
+            .iter()
+            .map(|text| LyricLine {
+                text: text.clone(),
+                start_ticks: None,
+            })
+            .collect();
+        return lyrics;
+    }
+
+    // A positive offset means lyrics should appear earlier
+    let offset = lyrics.offset_ticks.unwrap_or(0);
+    for line in &mut timed {
+        line.start_ticks = line.start_ticks.map(|t| (t - offset).max(0));
+    }
+    timed.sort_by_key(|l| l.start_ticks);
+
+    lyrics.is_synced = true;
+    lyrics.lines = timed;
+    lyrics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_synced_lrc() {
+        let lrc = "[ar:Some Artist]\n[ti:Song]\n[offset:500]\n\n\
+                   [00:12.00][01:30.50]Chorus line\n\
+                   [00:05.123]<00:05.123>First <00:06.000>line\n";
+        let lyrics = parse_lyrics(lrc);
+
+        assert!(lyrics.is_synced);
+        assert_eq!(lyrics.artist.as_deref(), Some("Some Artist"));
+        assert_eq!(lyrics.title.as_deref(), Some("Song"));
+        assert_eq!(lyrics.offset_ticks, Some(5_000_000));
+        assert_eq!(lyrics.lines.len(), 3);
+        assert_eq!(lyrics.lines[0].text, "First line");
+        assert_eq!(lyrics.lines[0].start_ticks, Some(51_230_000 - 5_000_000));
+        assert_eq!(lyrics.lines[1].text, "Chorus line");
+        assert_eq!(lyrics.lines[1].start_ticks, Some(115_000_000));
+        assert_eq!(lyrics.lines[2].start_ticks, Some(905_000_000 - 5_000_000));
+    }
+
+    #[test]
+    fn test_parse_plain_lyrics() {
+        let lyrics = parse_lyrics("\nVerse one\nstill verse one\n\nVerse two\n\n");
+
+        assert!(!lyrics.is_synced);
+        assert_eq!(lyrics.lines.len(), 4);
+        assert_eq!(lyrics.lines[0].text, "Verse one");
+        assert_eq!(lyrics.lines[2].text, "");
+        assert!(lyrics.lines.iter().all(|l| l.start_ticks.is_none()));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("00:01"), Some(10_000_000));
+        assert_eq!(parse_timestamp("01:02.5"), Some(625_000_000));
+        assert_eq!(parse_timestamp("00:00.01"), Some(100_000));
+        assert_eq!(parse_timestamp("ar"), None);
+        assert_eq!(parse_timestamp("00:75"), None);
+    }
+}
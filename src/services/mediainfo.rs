@@ -2,8 +2,13 @@
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::time::Ticks;
 
 /// Media information extracted from a file
 #[derive(Debug, Clone, Default)]
@@ -15,11 +20,30 @@ pub struct MediaInfo {
     pub video_codec: Option<String>,
     pub width: Option<u32>,
     pub height: Option<u32>,
+    /// Display aspect ratio ("16:9"), accounting for non-square pixels
+    pub aspect_ratio: Option<String>,
+    /// Frames per second, averaged over the stream
+    pub average_frame_rate: Option<f64>,
+    /// Base frame rate, the lowest rate all timestamps can be represented at
+    pub real_frame_rate: Option<f64>,
+    pub is_interlaced: bool,
+    /// Codec profile of the video ("High", "Main 10")
+    pub video_profile: Option<String>,
+    /// Codec level of the video (41 for H.264 level 4.1, 153 for HEVC 5.1)
+    pub video_level: Option<f64>,
+    pub pixel_format: Option<String>,
+    /// Bits per sample of the video, 8 unless the pixel format says more
+    pub video_bit_depth: Option<u32>,
     /// Container format (e.g., "matroska", "mp4")
     pub container: Option<String>,
     pub bitrate: Option<u64>,
     pub audio_streams: Vec<AudioStream>,
     pub subtitle_streams: Vec<SubtitleStream>,
+    /// Embedded lyrics from container tags (ID3 USLT, Vorbis LYRICS, etc.)
+    pub lyrics: Option<String>,
+    /// Container tags with lowercased keys ("artist", "album", "track", ...),
+    /// plus those of the first audio stream, where Ogg and Opus files keep them
+    pub tags: HashMap<String, String>,
 }
 
 /// Information about an audio stream
@@ -35,6 +59,24 @@ pub struct AudioStream {
     pub channels: Option<i32>,
     pub sample_rate: Option<i32>,
     pub is_default: bool,
+    /// Director or cast commentary, never the default track
+    pub is_commentary: bool,
+}
+
+/// Whether an audio track's title marks it as commentary
+pub fn is_commentary_title(title: &str) -> bool {
+    title.to_lowercase().contains("commentary")
+}
+
+/// The audio track players should start with: the default track, or the first
+/// one when none is marked, skipping commentary unless there's nothing else
+pub fn default_audio_index(streams: &[AudioStream]) -> Option<i32> {
+    let programme = || streams.iter().filter(|a| !a.is_commentary);
+    programme()
+        .find(|a| a.is_default)
+        .or_else(|| programme().next())
+        .or(streams.first())
+        .map(|a| a.index)
 }
 
 impl AudioStream {
@@ -43,7 +85,7 @@ impl AudioStream {
         let mut parts = Vec::new();
 
         if let Some(lang) = &self.language {
-            parts.push(language_name(lang));
+            parts.push(super::localization::display_name(lang));
         }
 
         if let Some(title) = &self.title {
@@ -51,6 +93,10 @@ impl AudioStream {
                 parts.push(title.clone());
             }
         }
+        // Flagged by disposition only, the title doesn't say so
+        if self.is_commentary && !self.title.as_deref().is_some_and(is_commentary_title) {
+            parts.push("Commentary".to_string());
+        }
 
         // Add codec name
         let codec_name = match self.codec.as_str() {
@@ -122,7 +168,7 @@ impl SubtitleStream {
         let mut parts = Vec::new();
 
         if let Some(lang) = &self.language {
-            parts.push(language_name(lang));
+            parts.push(super::localization::display_name(lang));
         }
 
         if let Some(title) = &self.title {
@@ -152,25 +198,6 @@ impl SubtitleStream {
     }
 }
 
-/// Convert language code to human-readable name
-fn language_name(code: &str) -> String {
-    match code {
-        "eng" | "en" => "English".to_string(),
-        "jpn" | "ja" => "Japanese".to_string(),
-        "spa" | "es" => "Spanish".to_string(),
-        "fre" | "fra" | "fr" => "French".to_string(),
-        "ger" | "deu" | "de" => "German".to_string(),
-        "ita" | "it" => "Italian".to_string(),
-        "por" | "pt" => "Portuguese".to_string(),
-        "rus" | "ru" => "Russian".to_string(),
-        "chi" | "zho" | "zh" => "Chinese".to_string(),
-        "kor" | "ko" => "Korean".to_string(),
-        "ara" | "ar" => "Arabic".to_string(),
-        "und" => "Unknown".to_string(),
-        _ => code.to_uppercase(),
-    }
-}
-
 /// ffprobe JSON output structure
 #[derive(Debug, Deserialize)]
 struct FfprobeOutput {
@@ -183,6 +210,7 @@ struct FfprobeFormat {
     duration: Option<String>,
     format_name: Option<String>,
     bit_rate: Option<String>,
+    tags: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -192,6 +220,14 @@ struct FfprobeStream {
     codec_name: Option<String>,
     width: Option<u32>,
     height: Option<u32>,
+    sample_aspect_ratio: Option<String>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    field_order: Option<String>,
+    profile: Option<String>,
+    level: Option<i32>,
+    pix_fmt: Option<String>,
+    bits_per_raw_sample: Option<String>,
     channels: Option<i32>,
     sample_rate: Option<String>, // ffprobe returns this as a string
     tags: Option<FfprobeStreamTags>,
@@ -202,12 +238,15 @@ struct FfprobeStream {
 struct FfprobeStreamTags {
     language: Option<String>,
     title: Option<String>,
+    #[serde(flatten)]
+    other: HashMap<String, String>,
 }
 
 #[derive(Debug, Deserialize)]
 struct FfprobeDisposition {
     default: Option<i32>,
     forced: Option<i32>,
+    comment: Option<i32>,
 }
 
 /// Find ffprobe binary - checks FFPROBE_PATH env var, then common locations
@@ -235,8 +274,8 @@ fn find_ffprobe() -> String {
     "ffprobe".to_string()
 }
 
-/// Extract media information from a file using ffprobe
-pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
+/// Run ffprobe on a file and return its JSON output
+fn run_ffprobe(path: &Path) -> Result<String> {
     let ffprobe = find_ffprobe();
 
     let output = Command::new(&ffprobe)
@@ -262,9 +301,13 @@ pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
         anyhow::bail!("ffprobe failed: {}", stderr);
     }
 
-    let json_output = String::from_utf8_lossy(&output.stdout);
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Media information from ffprobe's JSON output, as kept in probe_cache
+pub fn parse_probe_output(json_output: &str) -> Result<MediaInfo> {
     let probe: FfprobeOutput =
-        serde_json::from_str(&json_output).context("Failed to parse ffprobe output")?;
+        serde_json::from_str(json_output).context("Failed to parse ffprobe output")?;
 
     let mut info = MediaInfo::default();
 
@@ -273,11 +316,24 @@ pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
         if let Some(duration_str) = format.duration {
             if let Ok(duration) = duration_str.parse::<f64>() {
                 info.duration_seconds = Some(duration);
-                // Convert to ticks (1 second = 10,000,000 ticks)
-                info.duration_ticks = Some((duration * 10_000_000.0) as i64);
+                info.duration_ticks = Some(Ticks::from_secs_f64(duration).0);
             }
         }
         info.container = format.format_name;
+        info.tags = format
+            .tags
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key.to_lowercase(), value))
+            .collect();
+        // Lyrics tag names vary by format: "lyrics", "LYRICS", "lyrics-eng", "UNSYNCEDLYRICS"
+        info.lyrics = info
+            .tags
+            .iter()
+            .find(|(key, value)| {
+                (key.starts_with("lyrics") || *key == "unsyncedlyrics") && !value.trim().is_empty()
+            })
+            .map(|(_, value)| value.clone());
         if let Some(bitrate_str) = format.bit_rate {
             info.bitrate = bitrate_str.parse().ok();
         }
@@ -292,9 +348,42 @@ pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
                         info.video_codec = stream.codec_name;
                         info.width = stream.width;
                         info.height = stream.height;
+                        info.aspect_ratio = stream.width.zip(stream.height).and_then(|(w, h)| {
+                            display_aspect_ratio(w, h, stream.sample_aspect_ratio.as_deref())
+                        });
+                        info.average_frame_rate =
+                            stream.avg_frame_rate.as_deref().and_then(frame_rate);
+                        info.real_frame_rate = stream.r_frame_rate.as_deref().and_then(frame_rate);
+                        // "tt", "bb", "tb" and "bt" are the interlaced field orders
+                        info.is_interlaced = stream
+                            .field_order
+                            .as_deref()
+                            .is_some_and(|order| matches!(order, "tt" | "bb" | "tb" | "bt"));
+                        info.video_profile = stream.profile;
+                        // ffprobe gives -99 when the level is unknown
+                        info.video_level = stream.level.filter(|l| *l > 0).map(f64::from);
+                        info.video_bit_depth = stream
+                            .bits_per_raw_sample
+                            .as_deref()
+                            .and_then(|b| b.parse().ok())
+                            .or_else(|| stream.pix_fmt.as_deref().map(pixel_format_bit_depth));
+                        info.pixel_format = stream.pix_fmt;
                     }
                 }
                 Some("audio") => {
+                    if info.audio_streams.is_empty() {
+                        if let Some(ref tags) = stream.tags {
+                            let title = tags.title.iter().map(|t| ("title".to_string(), t));
+                            for (key, value) in tags
+                                .other
+                                .iter()
+                                .map(|(k, v)| (k.to_lowercase(), v))
+                                .chain(title)
+                            {
+                                info.tags.entry(key).or_insert_with(|| value.clone());
+                            }
+                        }
+                    }
                     if let (Some(index), Some(codec)) = (stream.index, stream.codec_name) {
                         let is_default = stream
                             .disposition
@@ -302,15 +391,29 @@ pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
                             .and_then(|d| d.default)
                             .map(|v| v == 1)
                             .unwrap_or(false);
+                        let title = stream.tags.as_ref().and_then(|t| t.title.clone());
+                        // Muxers set the comment disposition; otherwise only the title tells
+                        let is_commentary = stream
+                            .disposition
+                            .as_ref()
+                            .and_then(|d| d.comment)
+                            .is_some_and(|v| v == 1)
+                            || title.as_deref().is_some_and(is_commentary_title);
 
                         info.audio_streams.push(AudioStream {
                             index,
                             codec,
-                            language: stream.tags.as_ref().and_then(|t| t.language.clone()),
-                            title: stream.tags.as_ref().and_then(|t| t.title.clone()),
+                            language: stream
+                                .tags
+                                .as_ref()
+                                .and_then(|t| t.language.as_deref())
+                                .and_then(super::localization::normalize),
+                            title,
                             channels: stream.channels,
                             sample_rate: stream.sample_rate.as_ref().and_then(|s| s.parse().ok()),
-                            is_default,
+                            // Some releases flag the commentary as default by mistake
+                            is_default: is_default && !is_commentary,
+                            is_commentary,
                         });
                     }
                 }
@@ -332,7 +435,11 @@ pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
                         info.subtitle_streams.push(SubtitleStream {
                             index,
                             codec,
-                            language: stream.tags.as_ref().and_then(|t| t.language.clone()),
+                            language: stream
+                                .tags
+                                .as_ref()
+                                .and_then(|t| t.language.as_deref())
+                                .and_then(super::localization::normalize),
                             title: stream.tags.as_ref().and_then(|t| t.title.clone()),
                             is_default,
                             is_forced,
@@ -347,30 +454,123 @@ pub fn extract_media_info(path: &Path) -> Result<MediaInfo> {
     Ok(info)
 }
 
-/// Extract media info asynchronously (runs ffprobe in blocking task)
+/// Extract media information from a file using ffprobe (run in a blocking task)
+///
+/// .strm files point at remote streams, which aren't probed; they get empty
+/// info. Once the probe cache is set up, a file whose size and modification time
+/// match its cached probe isn't probed again.
 pub async fn extract_media_info_async(path: &Path) -> Result<MediaInfo> {
-    let path = path.to_path_buf();
-    tokio::task::spawn_blocking(move || extract_media_info(&path))
+    if super::strm::is_strm_file(path) {
+        return Ok(MediaInfo::default());
+    }
+    let cache = match (PROBE_CACHE.get(), file_stamp(path).await) {
+        (Some(pool), Some(stamp)) => Some((pool, stamp)),
+        _ => None,
+    };
+    let key = path.to_string_lossy().into_owned();
+
+    if let Some((pool, stamp)) = cache {
+        match cached_probe(pool, &key, stamp).await {
+            Ok(Some(output)) => match parse_probe_output(&output) {
+                Ok(info) => return Ok(info),
+                Err(e) => tracing::debug!("Ignoring unreadable cached probe of {}: {}", key, e),
+            },
+            Ok(None) => {}
+            Err(e) => tracing::debug!("Failed to read the probe cache: {}", e),
+        }
+    }
+
+    let probe_path = path.to_path_buf();
+    let output = tokio::task::spawn_blocking(move || run_ffprobe(&probe_path))
         .await
-        .context("Task join error")?
+        .context("Task join error")??;
+    let info = parse_probe_output(&output)?;
+
+    if let Some((pool, stamp)) = cache {
+        if let Err(e) = store_probe(pool, &key, stamp, &output).await {
+            tracing::debug!("Failed to cache the probe of {}: {}", key, e);
+        }
+    }
+    Ok(info)
 }
 
-/// Format duration ticks as human-readable string (HH:MM:SS)
-pub fn format_duration(ticks: i64) -> String {
-    let total_seconds = ticks / 10_000_000;
-    let hours = total_seconds / 3600;
-    let minutes = (total_seconds % 3600) / 60;
-    let seconds = total_seconds % 60;
+// Probe cache
+//
+// The same file is probed by the scan, thumbnail timing, item details and
+// PlaybackInfo, each spawning ffprobe, which for a file on a network share or
+// a sleeping disk can take seconds. ffprobe's output is now kept in the
+// probe_cache table by path, along with the file's size and modification
+// time; a file that changed in either is probed again and its row replaced.
 
-    if hours > 0 {
-        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
-    } else {
-        format!("{:02}:{:02}", minutes, seconds)
+static PROBE_CACHE: OnceLock<SqlitePool> = OnceLock::new();
+
+/// A file's size and modification time (Unix seconds), which a cached probe must match
+async fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    let modified = metadata
+        .modified()
+        .ok()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?;
+    Some((metadata.len() as i64, modified.as_secs() as i64))
+}
+
+async fn cached_probe(pool: &SqlitePool, path: &str, stamp: (i64, i64)) -> Result<Option<String>> {
+    let output = sqlx::query_scalar(
+        "SELECT output FROM probe_cache WHERE path = ? AND file_size = ? AND file_modified = ?",
+    )
+    .bind(path)
+    .bind(stamp.0)
+    .bind(stamp.1)
+    .fetch_optional(pool)
+    .await?;
+    Ok(output)
+}
+
+async fn store_probe(pool: &SqlitePool, path: &str, stamp: (i64, i64), output: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO probe_cache (path, file_size, file_modified, output, probed_at)
+        VALUES (?, ?, ?, ?, ?)
+        ON CONFLICT (path) DO UPDATE SET
+            file_size = excluded.file_size,
+            file_modified = excluded.file_modified,
+            output = excluded.output,
+            probed_at = excluded.probed_at
+        "#,
+    )
+    .bind(path)
+    .bind(stamp.0)
+    .bind(stamp.1)
+    .bind(output)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Cache probes in the database from now on, dropping those of files no item points at anymore
+pub async fn init_probe_cache(pool: &SqlitePool) -> Result<()> {
+    let removed = sqlx::query(
+        "DELETE FROM probe_cache WHERE path NOT IN (SELECT path FROM media_items WHERE path IS NOT NULL)",
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    if removed > 0 {
+        tracing::debug!("Dropped {} cached probe(s) of removed files", removed);
     }
+    let _ = PROBE_CACHE.set(pool.clone());
+    Ok(())
+}
+
+/// Format duration ticks as human-readable string (HH:MM:SS)
+pub fn format_duration(ticks: i64) -> String {
+    Ticks(ticks).to_string()
 }
 
 /// Find ffmpeg binary - checks FFMPEG_PATH env var, then common locations
-fn find_ffmpeg() -> String {
+pub fn find_ffmpeg() -> String {
     // Check environment variable first
     if let Ok(path) = std::env::var("FFMPEG_PATH") {
         return path;
@@ -394,13 +594,73 @@ fn find_ffmpeg() -> String {
     "ffmpeg".to_string()
 }
 
+/// How far past the target time scene detection looks for a detailed frame
+const SCENE_SEARCH_SECONDS: u32 = 120;
+
+/// Normalized luma entropy (0-1) a frame needs to be picked by scene
+/// detection; black frames and fades are near 0, credits rarely pass 0.4
+const MIN_FRAME_ENTROPY: f64 = 0.6;
+
+/// Filter chain keeping the first detailed frame, sampling one per second
+fn detailed_frame_filter(scale_filter: &str) -> String {
+    format!(
+        "fps=1,{},entropy,metadata=mode=select:key=lavfi.entropy.normalized_entropy.normal.Y:value={}:function=greater",
+        scale_filter, MIN_FRAME_ENTROPY
+    )
+}
+
+/// Write the first detailed frame after `timestamp`; returns false when the
+/// search window has none (or ffmpeg can't tell)
+fn extract_detailed_frame(
+    ffmpeg: &str,
+    video_path: &Path,
+    output_path: &Path,
+    timestamp: Ticks,
+    scale_filter: &str,
+    decode_args: &[String],
+) -> Result<bool> {
+    // Written beside the output first, so a failed search keeps the old image
+    let candidate = output_path.with_extension("scene.jpg");
+    let output = Command::new(ffmpeg)
+        .args([
+            "-hide_banner",
+            "-loglevel",
+            "error",
+            "-ss",
+            &timestamp.to_ffmpeg(),
+            "-t",
+            &SCENE_SEARCH_SECONDS.to_string(),
+        ])
+        .args(decode_args)
+        .arg("-i")
+        .arg(video_path)
+        .args(["-vf", &detailed_frame_filter(scale_filter)])
+        .args(["-frames:v", "1", "-q:v", "5", "-y"])
+        .arg(&candidate)
+        .output()
+        .with_context(|| format!("Failed to run ffmpeg at '{}'. Is ffmpeg installed?", ffmpeg))?;
+
+    let found = output.status.success() && std::fs::metadata(&candidate).is_ok_and(|m| m.len() > 0);
+    if found {
+        std::fs::rename(&candidate, output_path)?;
+    } else {
+        let _ = std::fs::remove_file(&candidate);
+    }
+    Ok(found)
+}
+
 /// Extract a thumbnail from a video file at the specified timestamp
 ///
 /// # Arguments
 /// * `video_path` - Path to the video file
 /// * `output_path` - Path where the thumbnail should be saved
-/// * `timestamp_seconds` - Position in video to extract frame (in seconds)
+/// * `timestamp` - Position in video to extract frame
 /// * `width` - Optional max width (maintains aspect ratio)
+/// * `decode_args` - Extra input options, such as a hardware decoder; the
+///   slow-seek retry always decodes in software
+/// * `scene_detection` - Take the first detailed frame from `timestamp` on,
+///   skipping black frames, fades and credits; the frame at `timestamp` is
+///   used when none is found
 ///
 /// # Returns
 /// * `Ok(())` if successful
@@ -408,8 +668,10 @@ fn find_ffmpeg() -> String {
 pub fn extract_thumbnail(
     video_path: &Path,
     output_path: &Path,
-    timestamp_seconds: f64,
+    timestamp: Ticks,
     width: Option<u32>,
+    decode_args: &[String],
+    scene_detection: bool,
 ) -> Result<()> {
     let ffmpeg = find_ffmpeg();
 
@@ -424,6 +686,25 @@ pub fn extract_thumbnail(
         None => "scale=320:-1".to_string(), // Default to 320px wide
     };
 
+    if scene_detection {
+        match extract_detailed_frame(
+            &ffmpeg,
+            video_path,
+            output_path,
+            timestamp,
+            &scale_filter,
+            decode_args,
+        ) {
+            Ok(true) => return Ok(()),
+            Ok(false) => tracing::debug!(
+                "No detailed frame in {} after {}, using the fixed position",
+                video_path.display(),
+                timestamp
+            ),
+            Err(e) => tracing::debug!("Scene detection for {} failed: {}", video_path.display(), e),
+        }
+    }
+
     // Try fast seeking first (-ss before -i)
     // This is much faster as it seeks by keyframes without decoding
     let output = Command::new(&ffmpeg)
@@ -432,9 +713,10 @@ pub fn extract_thumbnail(
             "-loglevel",
             "error",
             "-ss",
-            &format!("{:.3}", timestamp_seconds),
-            "-i",
+            &timestamp.to_ffmpeg(),
         ])
+        .args(decode_args)
+        .arg("-i")
         .arg(video_path)
         .args(["-vframes", "1", "-vf", &scale_filter, "-q:v", "5", "-y"])
         .arg(output_path)
@@ -448,7 +730,7 @@ pub fn extract_thumbnail(
             .arg(video_path)
             .args([
                 "-ss",
-                &format!("{:.3}", timestamp_seconds),
+                &timestamp.to_ffmpeg(),
                 "-vframes",
                 "1",
                 "-vf",
@@ -478,28 +760,188 @@ pub fn extract_thumbnail(
 pub async fn extract_thumbnail_async(
     video_path: &Path,
     output_path: &Path,
-    timestamp_seconds: f64,
+    timestamp: Ticks,
     width: Option<u32>,
+    decode_args: Vec<String>,
+    scene_detection: bool,
 ) -> Result<()> {
     let video_path = video_path.to_path_buf();
     let output_path = output_path.to_path_buf();
 
     tokio::task::spawn_blocking(move || {
-        extract_thumbnail(&video_path, &output_path, timestamp_seconds, width)
+        extract_thumbnail(
+            &video_path,
+            &output_path,
+            timestamp,
+            width,
+            &decode_args,
+            scene_detection,
+        )
     })
     .await
     .context("Task join error")?
 }
 
+/// Save the cover art embedded in an audio file as a JPEG
+pub async fn extract_cover_art(audio_path: &Path, output_path: &Path) -> Result<()> {
+    if let Some(parent) = output_path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let ffmpeg = find_ffmpeg();
+    let output = tokio::process::Command::new(&ffmpeg)
+        .args(["-hide_banner", "-loglevel", "error", "-i"])
+        .arg(audio_path)
+        .args(["-an", "-frames:v", "1", "-y"])
+        .arg(output_path)
+        .output()
+        .await
+        .with_context(|| format!("Failed to run ffmpeg at '{}'. Is ffmpeg installed?", ffmpeg))?;
+
+    // Files without a picture stream fail with "Output file does not contain any stream"
+    if !output.status.success() || !output_path.exists() {
+        anyhow::bail!(
+            "No cover art extracted: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+/// Tile poster images into one collage with ffmpeg
+///
+/// Four or more images make a 2x2 grid, fewer are placed side by side. Each tile is
+/// scaled and padded to 300x450 so posters with odd aspect ratios still line up.
+pub fn create_collage(images: &[&Path], output_path: &Path) -> Result<()> {
+    let images = if images.len() >= 4 {
+        &images[..4]
+    } else {
+        images
+    };
+    if images.is_empty() {
+        anyhow::bail!("No images for collage");
+    }
+
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut filter = String::new();
+    for i in 0..images.len() {
+        filter.push_str(&format!(
+            "[{i}:v]scale=300:450:force_original_aspect_ratio=decrease,pad=300:450:(ow-iw)/2:(oh-ih)/2,setsar=1[t{i}];"
+        ));
+    }
+    let tiles: String = (0..images.len()).map(|i| format!("[t{}]", i)).collect();
+    if images.len() == 4 {
+        filter.push_str(&format!(
+            "{}xstack=inputs=4:layout=0_0|w0_0|0_h0|w0_h0",
+            tiles
+        ));
+    } else if images.len() > 1 {
+        filter.push_str(&format!("{}hstack=inputs={}", tiles, images.len()));
+    } else {
+        filter.push_str(&format!("{}null", tiles));
+    }
+
+    let ffmpeg = find_ffmpeg();
+    let mut cmd = Command::new(&ffmpeg);
+    cmd.args(["-hide_banner", "-loglevel", "error"]);
+    for image in images {
+        cmd.arg("-i").arg(image);
+    }
+    let output = cmd
+        .args([
+            "-filter_complex",
+            &filter,
+            "-frames:v",
+            "1",
+            "-q:v",
+            "3",
+            "-y",
+        ])
+        .arg(output_path)
+        .output()
+        .with_context(|| format!("Failed to run ffmpeg at '{}'. Is ffmpeg installed?", ffmpeg))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("ffmpeg collage failed: {}", stderr);
+    }
+
+    Ok(())
+}
+
+/// Parse an ffprobe ratio ("16:9", "24000/1001"), None for 0 or "N/A" parts
+fn parse_ratio(ratio: &str) -> Option<(u64, u64)> {
+    let (num, den) = ratio.split_once([':', '/'])?;
+    let (num, den) = (num.trim().parse().ok()?, den.trim().parse().ok()?);
+    (num > 0 && den > 0).then_some((num, den))
+}
+
+/// Frames per second from an ffprobe rate ("24000/1001" is 23.976)
+fn frame_rate(rate: &str) -> Option<f64> {
+    let (num, den) = parse_ratio(rate)?;
+    Some((num as f64 / den as f64 * 1000.0).round() / 1000.0)
+}
+
+/// Bits per sample of an ffmpeg pixel format ("yuv420p10le" is 10)
+fn pixel_format_bit_depth(pix_fmt: &str) -> u32 {
+    let digits: String = pix_fmt
+        .trim_end_matches("le")
+        .trim_end_matches("be")
+        .chars()
+        .rev()
+        .take_while(char::is_ascii_digit)
+        .collect();
+    match digits.chars().rev().collect::<String>().parse() {
+        // "yuv420p" ends in the chroma subsampling, not a depth
+        Ok(depth @ 9..=16) if pix_fmt.contains('p') => depth,
+        _ => 8,
+    }
+}
+
+/// Display aspect ratio of a frame, stretched by its sample (pixel) aspect ratio
+///
+/// Anamorphic DVDs store 720x480 with wide pixels and display at 16:9. Ratios
+/// that don't reduce to small numbers are given as decimals ("1.85:1").
+fn display_aspect_ratio(
+    width: u32,
+    height: u32,
+    sample_aspect_ratio: Option<&str>,
+) -> Option<String> {
+    fn gcd(a: u64, b: u64) -> u64 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+
+    let (sar_num, sar_den) = sample_aspect_ratio.and_then(parse_ratio).unwrap_or((1, 1));
+    let num = width as u64 * sar_num;
+    let den = height as u64 * sar_den;
+    if num == 0 || den == 0 {
+        return None;
+    }
+    let divisor = gcd(num, den);
+    let (num, den) = (num / divisor, den / divisor);
+    if den <= 10 {
+        Some(format!("{}:{}", num, den))
+    } else {
+        Some(format!("{:.2}:1", num as f64 / den as f64))
+    }
+}
+
 /// Calculate a good timestamp for thumbnail extraction
 /// Uses ~10% into the video to avoid intros/black screens
-pub fn calculate_thumbnail_timestamp(duration_seconds: f64) -> f64 {
+pub fn calculate_thumbnail_timestamp(duration: Ticks) -> Ticks {
     // Use 10% into the video, but at least 5 seconds and at most 5 minutes
-    let timestamp = duration_seconds * 0.10;
+    let timestamp = Ticks(duration.0 / 10);
     timestamp
-        .clamp(5.0, 300.0)
-        .min(duration_seconds - 1.0)
-        .max(0.0)
+        .clamp(Ticks::from_seconds(5), Ticks::from_minutes(5))
+        .min(duration - Ticks::from_seconds(1))
+        .max(Ticks::ZERO)
 }
 
 #[cfg(test)]
@@ -517,15 +959,112 @@ mod tests {
         assert_eq!(format_duration(ticks), "05:30");
     }
 
+    #[test]
+    fn test_aspect_ratio_and_frame_rate() {
+        assert_eq!(
+            display_aspect_ratio(1920, 1080, Some("1:1")).as_deref(),
+            Some("16:9")
+        );
+        assert_eq!(
+            display_aspect_ratio(1920, 800, None).as_deref(),
+            Some("12:5")
+        );
+        assert_eq!(
+            display_aspect_ratio(1920, 1038, Some("0:1")).as_deref(),
+            Some("1.85:1")
+        );
+        // Anamorphic NTSC DVD and HDV
+        assert_eq!(
+            display_aspect_ratio(720, 480, Some("32:27")).as_deref(),
+            Some("16:9")
+        );
+        assert_eq!(
+            display_aspect_ratio(1440, 1080, Some("4:3")).as_deref(),
+            Some("16:9")
+        );
+        assert_eq!(display_aspect_ratio(0, 1080, None), None);
+
+        assert_eq!(frame_rate("24000/1001"), Some(23.976));
+        assert_eq!(frame_rate("25/1"), Some(25.0));
+        assert_eq!(frame_rate("0/0"), None);
+    }
+
+    #[test]
+    fn test_commentary_tracks() {
+        let track = |index, title: Option<&str>, is_default, is_commentary| AudioStream {
+            index,
+            codec: "aac".to_string(),
+            language: Some("eng".to_string()),
+            title: title.map(str::to_string),
+            channels: Some(2),
+            sample_rate: None,
+            is_default,
+            is_commentary,
+        };
+        assert!(is_commentary_title("Director's Commentary"));
+        assert!(!is_commentary_title("English 5.1"));
+
+        let streams = vec![
+            track(1, Some("Commentary with the cast"), false, true),
+            track(2, None, false, false),
+            track(3, None, true, false),
+        ];
+        assert_eq!(default_audio_index(&streams), Some(3));
+        assert_eq!(default_audio_index(&streams[..2]), Some(2));
+        assert_eq!(default_audio_index(&streams[..1]), Some(1));
+        assert_eq!(default_audio_index(&[]), None);
+
+        assert_eq!(
+            track(4, None, false, true).display_title(),
+            "English - Commentary - AAC - Stereo"
+        );
+    }
+
     #[test]
     fn test_calculate_thumbnail_timestamp() {
         // 24 minute episode -> ~2.4 minutes = 144 seconds
-        assert!((calculate_thumbnail_timestamp(1440.0) - 144.0).abs() < 0.1);
+        assert_eq!(
+            calculate_thumbnail_timestamp(Ticks::from_minutes(24)),
+            Ticks::from_seconds(144)
+        );
 
         // Very short video (30 sec) -> use near start
-        assert!(calculate_thumbnail_timestamp(30.0) < 30.0);
+        assert!(calculate_thumbnail_timestamp(Ticks::from_seconds(30)) < Ticks::from_seconds(30));
 
         // Very long video (2 hours) -> cap at 5 minutes
-        assert!((calculate_thumbnail_timestamp(7200.0) - 300.0).abs() < 0.1);
+        assert_eq!(
+            calculate_thumbnail_timestamp(Ticks::from_minutes(120)),
+            Ticks::from_minutes(5)
+        );
+    }
+
+    #[test]
+    fn test_parse_cached_probe_output() {
+        let output = r#"{
+            "format": {"duration": "1440.5", "format_name": "matroska,webm", "bit_rate": "4000000"},
+            "streams": [
+                {"index": 0, "codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080,
+                 "avg_frame_rate": "24000/1001", "r_frame_rate": "24000/1001",
+                 "profile": "High 10", "level": 41, "pix_fmt": "yuv420p10le"},
+                {"index": 1, "codec_type": "audio", "codec_name": "aac", "channels": 2,
+                 "sample_rate": "48000", "tags": {"language": "jpn"}, "disposition": {"default": 1}},
+                {"index": 2, "codec_type": "subtitle", "codec_name": "ass",
+                 "tags": {"language": "eng", "title": "Signs"}, "disposition": {"forced": 1}}
+            ]
+        }"#;
+        let info = parse_probe_output(output).unwrap();
+        assert_eq!(info.duration_seconds, Some(1440.5));
+        assert_eq!(info.video_codec.as_deref(), Some("h264"));
+        assert_eq!((info.width, info.height), (Some(1920), Some(1080)));
+        assert_eq!(info.video_level, Some(41.0));
+        assert_eq!(info.video_bit_depth, Some(10));
+        assert_eq!(pixel_format_bit_depth("yuv420p"), 8);
+        assert_eq!(info.audio_streams.len(), 1);
+        assert_eq!(info.audio_streams[0].sample_rate, Some(48000));
+        assert!(info.audio_streams[0].is_default);
+        assert_eq!(info.subtitle_streams.len(), 1);
+        assert!(info.subtitle_streams[0].is_forced);
+
+        assert!(parse_probe_output("not json").is_err());
     }
 }
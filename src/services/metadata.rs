@@ -1,11 +1,21 @@
 use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
 use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 use super::anidb::{AniDBClient, AniDBMetadata};
 use super::anilist::{AniListClient, AnimeMetadata, CastMember};
-use super::anime_db::AnimeOfflineDatabase;
+use super::anime_db::{AnimeOfflineDatabase, ProviderIds};
 use super::jikan::{JikanClient, JikanMetadata};
-use super::tmdb::{MediaMetadata, TmdbCastMember, TmdbClient};
+use super::tmdb::{
+    CollectionRef, ExternalIds, Image, Images, MediaMetadata, TmdbCastMember, TmdbClient,
+};
+use crate::config::ArtworkConfig;
 
 #[derive(Debug, Clone, Default)]
 pub struct UnifiedMetadata {
@@ -23,12 +33,18 @@ pub struct UnifiedMetadata {
     pub community_rating: Option<f64>,
     pub poster_url: Option<String>,
     pub backdrop_url: Option<String>,
+    /// Backdrops after `backdrop_url`, best first, for clients that rotate them
+    pub extra_backdrop_urls: Vec<String>,
     pub episode_count: Option<i32>,
     pub runtime_minutes: Option<i32>,
     pub genres: Option<Vec<String>>,
     pub studio: Option<String>,
     pub cast: Vec<CastMember>,
     pub provider: MetadataProvider,
+    /// TMDB collection of a movie (services::box_sets)
+    pub collection: Option<CollectionRef>,
+    /// Age rating from the provider (services::parental_controls)
+    pub official_rating: Option<String>,
 }
 
 #[derive(Debug, Clone, Default, PartialEq)]
@@ -64,6 +80,336 @@ pub struct EpisodeMetadata {
     pub still_url: Option<String>,
 }
 
+/// Provider artwork for an item
+#[derive(Debug, Clone, Default)]
+pub struct ArtworkUrls {
+    pub poster_url: Option<String>,
+    pub backdrop_url: Option<String>,
+    /// Backdrops after `backdrop_url`, best first
+    pub extra_backdrop_urls: Vec<String>,
+}
+
+impl ArtworkUrls {
+    /// The best of a TMDB show's or movie's images, falling back to its
+    /// default poster and backdrop when it lists none
+    fn from_tmdb(
+        poster_path: Option<String>,
+        backdrop_path: Option<String>,
+        images: Option<&Images>,
+    ) -> Self {
+        let preferences = artwork_preferences();
+        let poster = images
+            .and_then(|i| select_image(&i.posters, false, preferences))
+            .map(|i| i.file_path.clone())
+            .or(poster_path);
+        let mut backdrops: Vec<String> = images
+            .map(|i| rank_images(&i.backdrops, true, preferences))
+            .unwrap_or_default()
+            .into_iter()
+            .take(preferences.max_backdrops.max(1))
+            .map(|i| i.file_path.clone())
+            .collect();
+        if backdrops.is_empty() {
+            backdrops.extend(backdrop_path);
+        }
+        let mut backdrop_urls = backdrops
+            .into_iter()
+            .map(|p| format!("https://image.tmdb.org/t/p/w1280{}", p));
+        Self {
+            poster_url: poster.map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
+            backdrop_url: backdrop_urls.next(),
+            extra_backdrop_urls: backdrop_urls.collect(),
+        }
+    }
+}
+
+/// Convert an absolute episode number to a (season, episode) by provider season sizes
+///
+/// Anime releases number long runs continuously (`Show - 125.mkv`) while
+/// TMDB splits them into seasons; the absolute number counts through the
+/// regular seasons in order. `season_counts` maps seasons to their episode
+/// counts (specials are ignored). Returns None past the last known episode.
+pub fn absolute_to_season_episode(
+    season_counts: &BTreeMap<i32, i32>,
+    absolute: i32,
+) -> Option<(i32, i32)> {
+    let mut remaining = absolute;
+    for (&season, &count) in season_counts.range(1..) {
+        if remaining <= count {
+            return Some((season, remaining));
+        }
+        remaining -= count.max(0);
+    }
+    None
+}
+
+static ARTWORK_PREFERENCES: OnceLock<ArtworkConfig> = OnceLock::new();
+
+/// Set how provider posters and backdrops are chosen (call once at startup)
+pub fn set_artwork_preferences(config: ArtworkConfig) {
+    let _ = ARTWORK_PREFERENCES.set(config);
+}
+
+fn artwork_preferences() -> &'static ArtworkConfig {
+    ARTWORK_PREFERENCES.get_or_init(ArtworkConfig::default)
+}
+
+/// Pick the poster or backdrop to use from those a provider offers
+pub fn select_image<'a>(
+    images: &'a [Image],
+    backdrop: bool,
+    preferences: &ArtworkConfig,
+) -> Option<&'a Image> {
+    rank_images(images, backdrop, preferences)
+        .into_iter()
+        .next()
+}
+
+/// A provider's posters or backdrops, best first
+///
+/// Images in a preferred language win (textless ones rank after the
+/// preferred languages for posters, and before them for backdrops, which are
+/// shown behind text). Among those, images at least `min_width` wide with
+/// `min_vote_count` votes come first, by vote average.
+pub fn rank_images<'a>(
+    images: &'a [Image],
+    backdrop: bool,
+    preferences: &ArtworkConfig,
+) -> Vec<&'a Image> {
+    let preferred = preferences.languages.len();
+    let language_rank = |image: &Image| {
+        let language = image.iso_639_1.as_deref().filter(|l| *l != "xx");
+        let position = language.and_then(|l| {
+            preferences
+                .languages
+                .iter()
+                .position(|p| p.eq_ignore_ascii_case(l))
+        });
+        match (language, position) {
+            (None, _) if backdrop => 0,
+            (None, _) => preferred,
+            (Some(_), Some(i)) if backdrop => i + 1,
+            (Some(_), Some(i)) => i,
+            (Some(_), None) => preferred + 1,
+        }
+    };
+    let rank = |image: &Image| {
+        (
+            language_rank(image),
+            image.width < preferences.min_width,
+            image.vote_count < preferences.min_vote_count,
+        )
+    };
+
+    let mut ranked: Vec<&Image> = images.iter().collect();
+    ranked.sort_by(|a, b| {
+        rank(a)
+            .cmp(&rank(b))
+            .then(b.vote_average.total_cmp(&a.vote_average))
+            .then(b.vote_count.cmp(&a.vote_count))
+            .then(b.width.cmp(&a.width))
+    });
+    ranked
+}
+
+/// Consecutive failed requests before a provider's circuit opens
+const CIRCUIT_FAILURE_THRESHOLD: u32 = 5;
+
+/// How long an open circuit short-circuits requests before letting a probe through
+const CIRCUIT_COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Per-provider circuit breaker
+/// Trips after repeated request failures (timeouts, connection errors) so a provider
+/// outage doesn't stall a scan on one timeout per title
+struct CircuitBreaker {
+    provider: MetadataProvider,
+    state: Mutex<CircuitState>,
+}
+
+impl CircuitBreaker {
+    fn new(provider: MetadataProvider) -> Self {
+        Self {
+            provider,
+            state: Mutex::new(CircuitState::default()),
+        }
+    }
+
+    /// Whether the circuit is currently open (requests are being skipped)
+    fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        matches!(state.open_until, Some(until) if Instant::now() < until)
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.is_some() {
+            tracing::info!("{} is responding again, resuming lookups", self.provider);
+        }
+        *state = CircuitState::default();
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+
+        if state.consecutive_failures < CIRCUIT_FAILURE_THRESHOLD {
+            return;
+        }
+
+        if state.open_until.is_none() {
+            tracing::warn!(
+                "{} failed {} consecutive requests, skipping lookups for {}s",
+                self.provider,
+                state.consecutive_failures,
+                CIRCUIT_COOLDOWN.as_secs()
+            );
+        } else {
+            tracing::debug!("{} still failing, keeping circuit open", self.provider);
+        }
+        state.open_until = Some(Instant::now() + CIRCUIT_COOLDOWN);
+    }
+}
+
+/// Per-provider token bucket
+/// Spaces lookups out so a large scan stays under the provider's rate limit
+/// instead of getting 429s that leave titles unmatched. Buckets are shared by
+/// every MetadataService, since scans, refreshes and retries run side by side.
+struct TokenBucket {
+    /// Lookups added per second
+    rate: f64,
+    /// Lookups that may go out back to back after a quiet spell
+    burst: f64,
+    /// Tokens left (negative while lookups are queued) and when they were counted
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: f64) -> Self {
+        Self {
+            rate,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// Take a token, returning how long to wait before using it
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let (tokens, counted) = *state;
+        let refilled = now.saturating_duration_since(counted).as_secs_f64() * self.rate;
+        let tokens = (tokens + refilled).min(self.burst) - 1.0;
+        *state = (tokens, now.max(counted));
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / self.rate)
+        }
+    }
+
+    async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tracing::trace!("Rate limited, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// The rate limit lookups from a provider share
+fn rate_limit(provider: &MetadataProvider) -> Option<&'static TokenBucket> {
+    static ANILIST: LazyLock<TokenBucket> = LazyLock::new(|| TokenBucket::new(0.5, 5.0));
+    static ANIDB: LazyLock<TokenBucket> = LazyLock::new(|| TokenBucket::new(0.5, 1.0));
+    static JIKAN: LazyLock<TokenBucket> = LazyLock::new(|| TokenBucket::new(0.5, 3.0));
+    static TMDB: LazyLock<TokenBucket> = LazyLock::new(|| TokenBucket::new(10.0, 20.0));
+    match provider {
+        MetadataProvider::AniList => Some(&ANILIST),
+        MetadataProvider::AniDB => Some(&ANIDB),
+        MetadataProvider::Jikan => Some(&JIKAN),
+        MetadataProvider::Tmdb => Some(&TMDB),
+        MetadataProvider::None => None,
+    }
+}
+
+// Response cache
+//
+// Lookups that found a match are kept in the metadata_cache table by provider
+// and query (the search or ID and, for TMDB, the languages asked for), so
+// scanning the same titles again doesn't query the providers again. Misses
+// aren't kept: they may come from an outage or a rate limit, and unmatched
+// series are retried. Entries expire after RESPONSE_CACHE_TTL_DAYS and an
+// explicit metadata refresh always asks the provider.
+
+static RESPONSE_CACHE: OnceLock<SqlitePool> = OnceLock::new();
+
+/// How long a cached provider response is used
+const RESPONSE_CACHE_TTL_DAYS: i64 = 7;
+
+async fn cached_response(pool: &SqlitePool, provider: &str, query: &str) -> Result<Option<String>> {
+    let response = sqlx::query_scalar(
+        "SELECT response FROM metadata_cache
+         WHERE provider = ? AND query = ? AND fetched_at > datetime('now', ?)",
+    )
+    .bind(provider)
+    .bind(query)
+    .bind(format!("-{} days", RESPONSE_CACHE_TTL_DAYS))
+    .fetch_optional(pool)
+    .await?;
+    Ok(response)
+}
+
+async fn store_response(
+    pool: &SqlitePool,
+    provider: &str,
+    query: &str,
+    response: &str,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO metadata_cache (provider, query, response, fetched_at)
+        VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+        ON CONFLICT (provider, query) DO UPDATE SET
+            response = excluded.response,
+            fetched_at = excluded.fetched_at
+        "#,
+    )
+    .bind(provider)
+    .bind(query)
+    .bind(response)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Cache provider responses in the database from now on, dropping expired ones
+pub async fn init_response_cache(pool: &SqlitePool) -> Result<()> {
+    let removed = sqlx::query("DELETE FROM metadata_cache WHERE fetched_at <= datetime('now', ?)")
+        .bind(format!("-{} days", RESPONSE_CACHE_TTL_DAYS))
+        .execute(pool)
+        .await?
+        .rows_affected();
+    if removed > 0 {
+        tracing::debug!("Dropped {} expired provider response(s)", removed);
+    }
+    let _ = RESPONSE_CACHE.set(pool.clone());
+    Ok(())
+}
+
+/// Cache key of a title search
+fn search_key(kind: &str, name: &str, year: Option<i32>) -> String {
+    format!(
+        "{}:{}:{}",
+        kind,
+        name.trim().to_lowercase(),
+        year.map(|y| y.to_string()).unwrap_or_default()
+    )
+}
+
 pub struct MetadataService {
     anilist: AniListClient,
     anidb: AniDBClient,
@@ -71,11 +417,20 @@ pub struct MetadataService {
     anime_db: AnimeOfflineDatabase,
     tmdb: Option<TmdbClient>,
     image_cache_dir: PathBuf,
+    anilist_circuit: CircuitBreaker,
+    anidb_circuit: CircuitBreaker,
+    jikan_circuit: CircuitBreaker,
+    tmdb_circuit: CircuitBreaker,
+    /// Episode counts of TMDB shows' seasons, for absolute numbering
+    season_counts: Mutex<HashMap<i64, BTreeMap<i32, i32>>>,
+    /// Whether cached provider responses may be used
+    read_cache: bool,
 }
 
 impl MetadataService {
     pub fn new(image_cache_dir: PathBuf, anime_db_enabled: Option<bool>) -> Self {
-        let tmdb = TmdbClient::from_env(image_cache_dir.clone());
+        let tmdb = TmdbClient::from_env(image_cache_dir.clone())
+            .map(|tmdb| tmdb.with_image_languages(&artwork_preferences().languages));
         let cache_dir = image_cache_dir
             .parent()
             .unwrap_or(&image_cache_dir)
@@ -88,6 +443,12 @@ impl MetadataService {
             anime_db: AnimeOfflineDatabase::new(cache_dir, anime_db_enabled),
             tmdb,
             image_cache_dir,
+            anilist_circuit: CircuitBreaker::new(MetadataProvider::AniList),
+            anidb_circuit: CircuitBreaker::new(MetadataProvider::AniDB),
+            jikan_circuit: CircuitBreaker::new(MetadataProvider::Jikan),
+            tmdb_circuit: CircuitBreaker::new(MetadataProvider::Tmdb),
+            season_counts: Mutex::new(HashMap::new()),
+            read_cache: true,
         }
     }
 
@@ -98,6 +459,25 @@ impl MetadataService {
         Self::new(image_cache_dir, anime_db_enabled)
     }
 
+    /// Request names and overviews in this language from providers that
+    /// support it (TMDB); AniList and the anime database are language-neutral
+    pub fn with_language(mut self, language: Option<&str>) -> Self {
+        self.tmdb = self.tmdb.map(|tmdb| tmdb.with_language(language));
+        self
+    }
+
+    /// Always ask the providers rather than using cached responses (their
+    /// answers still replace the cached ones)
+    pub fn with_fresh_lookups(mut self) -> Self {
+        self.read_cache = false;
+        self
+    }
+
+    /// Where downloaded and generated images are kept
+    pub fn image_cache_dir(&self) -> &std::path::Path {
+        &self.image_cache_dir
+    }
+
     pub fn is_available(&self) -> bool {
         true
     }
@@ -110,6 +490,80 @@ impl MetadataService {
         self.anime_db.is_enabled()
     }
 
+    /// Whether any remote provider is currently short-circuited after repeated failures
+    /// A miss while this is true may be an outage rather than a genuine no-match
+    pub fn has_provider_outage(&self) -> bool {
+        self.anilist_circuit.is_open()
+            || self.anidb_circuit.is_open()
+            || self.jikan_circuit.is_open()
+            || (self.tmdb.is_some() && self.tmdb_circuit.is_open())
+    }
+
+    /// Run a provider request through its circuit breaker and rate limit
+    /// While the circuit is open the request is skipped and treated as no match
+    async fn guarded<T>(
+        circuit: &CircuitBreaker,
+        request: impl Future<Output = Result<Option<T>>>,
+    ) -> Result<Option<T>> {
+        if circuit.is_open() {
+            return Ok(None);
+        }
+        if let Some(bucket) = rate_limit(&circuit.provider) {
+            bucket.acquire().await;
+        }
+
+        match request.await {
+            Ok(result) => {
+                circuit.record_success();
+                Ok(result)
+            }
+            Err(e) => {
+                circuit.record_failure();
+                Err(e)
+            }
+        }
+    }
+
+    /// Answer a provider request from the response cache, or run it through
+    /// `guarded` and cache what it found
+    async fn cached<T: Serialize + DeserializeOwned>(
+        &self,
+        circuit: &CircuitBreaker,
+        query: &str,
+        request: impl Future<Output = Result<Option<T>>>,
+    ) -> Result<Option<T>> {
+        let Some(pool) = RESPONSE_CACHE.get() else {
+            return Self::guarded(circuit, request).await;
+        };
+        let provider = circuit.provider.to_string();
+
+        if self.read_cache {
+            match cached_response(pool, &provider, query).await {
+                Ok(Some(response)) => match serde_json::from_str(&response) {
+                    Ok(value) => {
+                        tracing::debug!("Using cached {} response for {}", provider, query);
+                        return Ok(Some(value));
+                    }
+                    Err(e) => tracing::debug!("Ignoring cached {} response: {}", provider, e),
+                },
+                Ok(None) => {}
+                Err(e) => tracing::debug!("Response cache unavailable: {}", e),
+            }
+        }
+
+        let result = Self::guarded(circuit, request).await?;
+        if let Some(ref value) = result {
+            let stored = match serde_json::to_string(value) {
+                Ok(response) => store_response(pool, &provider, query, &response).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = stored {
+                tracing::debug!("Failed to cache {} response for {}: {}", provider, query, e);
+            }
+        }
+        Ok(result)
+    }
+
     /// Preload the anime offline database (downloads if needed)
     /// Call this before scanning to ensure the database is ready
     pub async fn preload_anime_db(&self) -> Result<()> {
@@ -122,6 +576,34 @@ impl MetadataService {
         self.anime_db.unload().await
     }
 
+    /// Look up an anime's other provider IDs in the anime offline database
+    /// Returns None when the database is disabled or has no entry for the IDs
+    pub async fn anime_db_provider_ids(&self, known: &ProviderIds) -> Result<Option<ProviderIds>> {
+        if !self.anime_db.is_enabled() {
+            return Ok(None);
+        }
+        Ok(self
+            .anime_db
+            .find_by_provider_ids(known)
+            .await?
+            .map(|entry| entry.provider_ids()))
+    }
+
+    /// IMDb/TVDB IDs for a TMDB show or movie (None without a TMDB key)
+    pub async fn tmdb_external_ids(
+        &self,
+        tmdb_id: i64,
+        is_movie: bool,
+    ) -> Result<Option<ExternalIds>> {
+        let Some(ref tmdb) = self.tmdb else {
+            return Ok(None);
+        };
+        Self::guarded(&self.tmdb_circuit, async {
+            tmdb.get_external_ids(tmdb_id, is_movie).await.map(Some)
+        })
+        .await
+    }
+
     /// Get metadata for an anime series
     /// Priority: anime-offline-database -> AniList -> AniDB -> TMDB
     pub async fn get_anime_metadata(
@@ -175,8 +657,13 @@ impl MetadataService {
                             );
 
                             if let Some(anilist_id) = provider_ids.anilist_id {
-                                if let Ok(Some(meta)) =
-                                    self.anilist.get_anime_by_id(anilist_id).await
+                                if let Ok(Some(meta)) = self
+                                    .cached(
+                                        &self.anilist_circuit,
+                                        &format!("id:{}", anilist_id),
+                                        self.anilist.get_anime_by_id(anilist_id),
+                                    )
+                                    .await
                                 {
                                     tracing::info!(
                                         "Found anime on AniList (via local DB): {} -> {}",
@@ -201,7 +688,14 @@ impl MetadataService {
                             }
 
                             if let Some(anidb_id) = provider_ids.anidb_id {
-                                if let Ok(Some(meta)) = self.anidb.get_anime_by_id(anidb_id).await {
+                                if let Ok(Some(meta)) = self
+                                    .cached(
+                                        &self.anidb_circuit,
+                                        &format!("id:{}", anidb_id),
+                                        self.anidb.get_anime_by_id(anidb_id),
+                                    )
+                                    .await
+                                {
                                     tracing::info!(
                                         "Found anime on AniDB (via local DB): {} -> {}",
                                         name,
@@ -217,7 +711,14 @@ impl MetadataService {
 
                             // Try Jikan (MAL) if we have a MAL ID
                             if let Some(mal_id) = provider_ids.mal_id {
-                                if let Ok(Some(meta)) = self.jikan.get_anime_by_id(mal_id).await {
+                                if let Ok(Some(meta)) = self
+                                    .cached(
+                                        &self.jikan_circuit,
+                                        &format!("id:{}", mal_id),
+                                        self.jikan.get_anime_by_id(mal_id),
+                                    )
+                                    .await
+                                {
                                     tracing::info!(
                                         "Found anime on Jikan/MAL (via local DB): {} -> {}",
                                         name,
@@ -245,7 +746,14 @@ impl MetadataService {
             }
         }
 
-        match self.anilist.get_anime_metadata(name, year).await {
+        match self
+            .cached(
+                &self.anilist_circuit,
+                &search_key("search", name, year),
+                self.anilist.get_anime_metadata(name, year),
+            )
+            .await
+        {
             Ok(Some(meta)) => {
                 tracing::info!(
                     "Found anime on AniList: {} -> {}",
@@ -285,7 +793,14 @@ impl MetadataService {
         }
 
         // Try Jikan (MAL) as fallback
-        match self.jikan.search_anime_best_match(name, year).await {
+        match self
+            .cached(
+                &self.jikan_circuit,
+                &search_key("search", name, year),
+                self.jikan.search_anime_best_match(name, year),
+            )
+            .await
+        {
             Ok(Some(meta)) => {
                 tracing::info!(
                     "Found anime on Jikan/MAL: {} -> {}",
@@ -303,7 +818,15 @@ impl MetadataService {
         }
 
         if let Some(ref tmdb) = self.tmdb {
-            match tmdb.get_series_metadata(name, year).await {
+            let query = format!("{}:{}", search_key("tv", name, year), tmdb.cache_locale());
+            match self
+                .cached(
+                    &self.tmdb_circuit,
+                    &query,
+                    tmdb.get_series_metadata(name, year),
+                )
+                .await
+            {
                 Ok(Some(meta)) => {
                     tracing::info!(
                         "Found anime on TMDB: {} -> {}",
@@ -378,8 +901,13 @@ impl MetadataService {
                             );
 
                             if let Some(anilist_id) = provider_ids.anilist_id {
-                                if let Ok(Some(meta)) =
-                                    self.anilist.get_anime_by_id(anilist_id).await
+                                if let Ok(Some(meta)) = self
+                                    .cached(
+                                        &self.anilist_circuit,
+                                        &format!("id:{}", anilist_id),
+                                        self.anilist.get_anime_by_id(anilist_id),
+                                    )
+                                    .await
                                 {
                                     tracing::info!(
                                         "Found series on AniList (via anime-offline-database): {} -> {}",
@@ -404,7 +932,14 @@ impl MetadataService {
                             }
 
                             if let Some(anidb_id) = provider_ids.anidb_id {
-                                if let Ok(Some(meta)) = self.anidb.get_anime_by_id(anidb_id).await {
+                                if let Ok(Some(meta)) = self
+                                    .cached(
+                                        &self.anidb_circuit,
+                                        &format!("id:{}", anidb_id),
+                                        self.anidb.get_anime_by_id(anidb_id),
+                                    )
+                                    .await
+                                {
                                     tracing::info!(
                                         "Found series on AniDB (via anime-offline-database): {} -> {}",
                                         name,
@@ -420,7 +955,14 @@ impl MetadataService {
 
                             // Try Jikan (MAL) if we have a MAL ID
                             if let Some(mal_id) = provider_ids.mal_id {
-                                if let Ok(Some(meta)) = self.jikan.get_anime_by_id(mal_id).await {
+                                if let Ok(Some(meta)) = self
+                                    .cached(
+                                        &self.jikan_circuit,
+                                        &format!("id:{}", mal_id),
+                                        self.jikan.get_anime_by_id(mal_id),
+                                    )
+                                    .await
+                                {
                                     tracing::info!(
                                         "Found series on Jikan/MAL (via local DB): {} -> {}",
                                         name,
@@ -449,7 +991,15 @@ impl MetadataService {
         }
 
         if let Some(ref tmdb) = self.tmdb {
-            match tmdb.get_series_metadata(name, year).await {
+            let query = format!("{}:{}", search_key("tv", name, year), tmdb.cache_locale());
+            match self
+                .cached(
+                    &self.tmdb_circuit,
+                    &query,
+                    tmdb.get_series_metadata(name, year),
+                )
+                .await
+            {
                 Ok(Some(meta)) => {
                     tracing::info!(
                         "Found series on TMDB: {} -> {}",
@@ -467,7 +1017,14 @@ impl MetadataService {
             }
         }
 
-        match self.anilist.get_anime_metadata(name, year).await {
+        match self
+            .cached(
+                &self.anilist_circuit,
+                &search_key("search", name, year),
+                self.anilist.get_anime_metadata(name, year),
+            )
+            .await
+        {
             Ok(Some(meta)) => {
                 tracing::info!(
                     "Found series on AniList: {} -> {}",
@@ -513,7 +1070,14 @@ impl MetadataService {
         }
 
         // Try Jikan (MAL) as final fallback for anime
-        match self.jikan.search_anime_best_match(name, year).await {
+        match self
+            .cached(
+                &self.jikan_circuit,
+                &search_key("search", name, year),
+                self.jikan.search_anime_best_match(name, year),
+            )
+            .await
+        {
             Ok(Some(meta)) => {
                 tracing::info!(
                     "Found series on Jikan/MAL: {} -> {}",
@@ -543,7 +1107,19 @@ impl MetadataService {
         tracing::debug!("Searching for movie metadata: {} ({:?})", title, year);
 
         if let Some(ref tmdb) = self.tmdb {
-            match tmdb.get_movie_metadata(title, year).await {
+            let query = format!(
+                "{}:{}",
+                search_key("movie", title, year),
+                tmdb.cache_locale()
+            );
+            match self
+                .cached(
+                    &self.tmdb_circuit,
+                    &query,
+                    tmdb.get_movie_metadata(title, year),
+                )
+                .await
+            {
                 Ok(Some(meta)) => {
                     tracing::info!(
                         "Found movie on TMDB: {} -> {}",
@@ -562,7 +1138,14 @@ impl MetadataService {
         }
 
         // Try Jikan for anime movies
-        match self.jikan.search_anime_best_match(title, year).await {
+        match self
+            .cached(
+                &self.jikan_circuit,
+                &search_key("search", title, year),
+                self.jikan.search_anime_best_match(title, year),
+            )
+            .await
+        {
             Ok(Some(meta)) => {
                 tracing::info!(
                     "Found movie on Jikan/MAL: {} -> {}",
@@ -711,8 +1294,22 @@ impl MetadataService {
             if let Some(series_meta) = series_metadata {
                 if let Some(ref tmdb_id_str) = series_meta.tmdb_id {
                     if let Ok(tmdb_id) = tmdb_id_str.parse::<i64>() {
-                        match tmdb
-                            .get_episode_metadata(tmdb_id, season_number, episode_number)
+                        let (season_number, episode_number) = self
+                            .provider_episode_number(tmdb, tmdb_id, season_number, episode_number)
+                            .await;
+                        let query = format!(
+                            "episode:{}:{}:{}:{}",
+                            tmdb_id,
+                            season_number,
+                            episode_number,
+                            tmdb.cache_locale()
+                        );
+                        match self
+                            .cached(
+                                &self.tmdb_circuit,
+                                &query,
+                                tmdb.get_episode_metadata(tmdb_id, season_number, episode_number),
+                            )
                             .await
                         {
                             Ok(Some(meta)) => {
@@ -757,6 +1354,68 @@ impl MetadataService {
         Ok(None)
     }
 
+    /// TMDB (season, episode) for an episode numbered absolutely in season 1
+    ///
+    /// Numbers within season 1's episode count are left alone; anything past it
+    /// counts on through the show's later seasons. If the season sizes can't
+    /// be fetched the numbers are used as they are.
+    async fn provider_episode_number(
+        &self,
+        tmdb: &TmdbClient,
+        tmdb_id: i64,
+        season_number: i32,
+        episode_number: i32,
+    ) -> (i32, i32) {
+        if season_number != 1 {
+            return (season_number, episode_number);
+        }
+        let cached = self.season_counts.lock().unwrap().get(&tmdb_id).cloned();
+        let counts = match cached {
+            Some(counts) => counts,
+            None => {
+                let details = Self::guarded(&self.tmdb_circuit, async {
+                    tmdb.get_tv_details(tmdb_id).await.map(Some)
+                })
+                .await;
+                let counts: BTreeMap<i32, i32> = match details {
+                    Ok(Some(details)) => details
+                        .seasons
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|s| Some((s.season_number, s.episode_count?)))
+                        .collect(),
+                    Ok(None) => return (season_number, episode_number),
+                    Err(e) => {
+                        tracing::debug!("TMDB season sizes unavailable for {}: {}", tmdb_id, e);
+                        return (season_number, episode_number);
+                    }
+                };
+                self.season_counts
+                    .lock()
+                    .unwrap()
+                    .insert(tmdb_id, counts.clone());
+                counts
+            }
+        };
+
+        if counts.get(&1).is_none_or(|&count| episode_number <= count) {
+            return (season_number, episode_number);
+        }
+        match absolute_to_season_episode(&counts, episode_number) {
+            Some((season, episode)) => {
+                tracing::debug!(
+                    "Absolute episode {} of TMDB show {} is S{:02}E{:02}",
+                    episode_number,
+                    tmdb_id,
+                    season,
+                    episode
+                );
+                (season, episode)
+            }
+            None => (season_number, episode_number),
+        }
+    }
+
     pub async fn cache_images(
         &self,
         item_id: &str,
@@ -796,6 +1455,74 @@ impl MetadataService {
         self.anilist.download_image(url, item_id, image_type).await
     }
 
+    /// Download an image to the cache, replacing any cached copy (image refresh)
+    pub async fn redownload_image_to_cache(
+        &self,
+        url: &str,
+        item_id: &str,
+        image_type: &str,
+    ) -> Result<PathBuf> {
+        self.anilist
+            .redownload_image(url, item_id, image_type)
+            .await
+    }
+
+    /// Current poster and backdrop URLs of a series or movie, looked up by the
+    /// provider IDs it already has (TMDB, then AniList) rather than by title
+    ///
+    /// None when no provider knows the item or the providers are unavailable.
+    pub async fn artwork_urls(
+        &self,
+        is_movie: bool,
+        tmdb_id: Option<&str>,
+        anilist_id: Option<&str>,
+    ) -> Result<Option<ArtworkUrls>> {
+        if let (Some(tmdb), Some(id)) = (
+            self.tmdb.as_ref(),
+            tmdb_id.and_then(|id| id.parse::<i64>().ok()),
+        ) {
+            let paths = Self::guarded(&self.tmdb_circuit, async {
+                if is_movie {
+                    let details = tmdb.get_movie_details(id).await?;
+                    Ok(Some((
+                        details.poster_path,
+                        details.backdrop_path,
+                        details.images,
+                    )))
+                } else {
+                    let details = tmdb.get_tv_details(id).await?;
+                    Ok(Some((
+                        details.poster_path,
+                        details.backdrop_path,
+                        details.images,
+                    )))
+                }
+            })
+            .await?;
+            if let Some((poster, backdrop, images)) = paths {
+                return Ok(Some(ArtworkUrls::from_tmdb(
+                    poster,
+                    backdrop,
+                    images.as_ref(),
+                )));
+            }
+        }
+
+        if let Some(id) = anilist_id.and_then(|id| id.parse::<i64>().ok()) {
+            let meta =
+                Self::guarded(&self.anilist_circuit, self.anilist.get_anime_by_id(id)).await?;
+            if let Some(meta) = meta {
+                return Ok(Some(ArtworkUrls {
+                    poster_url: meta.poster_url,
+                    backdrop_url: meta.backdrop_url,
+                    extra_backdrop_urls: Vec::new(),
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
     fn anilist_to_unified(&self, meta: AnimeMetadata) -> UnifiedMetadata {
         UnifiedMetadata {
             anilist_id: meta.anilist_id,
@@ -812,12 +1539,15 @@ impl MetadataService {
             community_rating: meta.community_rating,
             poster_url: meta.poster_url,
             backdrop_url: meta.backdrop_url,
+            extra_backdrop_urls: Vec::new(),
             episode_count: meta.episode_count,
             runtime_minutes: meta.episode_duration_minutes,
             genres: meta.genres,
             studio: meta.studio,
             cast: meta.cast,
             provider: MetadataProvider::AniList,
+            collection: None,
+            official_rating: None,
         }
     }
 
@@ -837,12 +1567,15 @@ impl MetadataService {
             community_rating: meta.community_rating,
             poster_url: meta.poster_url,
             backdrop_url: None,
+            extra_backdrop_urls: Vec::new(),
             episode_count: meta.episode_count,
             runtime_minutes: None,
             genres: None,
             studio: None,
             cast: Vec::new(),
             provider: MetadataProvider::AniDB,
+            collection: None,
+            official_rating: None,
         }
     }
 
@@ -862,16 +1595,21 @@ impl MetadataService {
             community_rating: meta.community_rating,
             poster_url: meta.poster_url,
             backdrop_url: meta.backdrop_url,
+            extra_backdrop_urls: Vec::new(),
             episode_count: meta.episode_count,
             runtime_minutes: None,
             genres: meta.genres,
             studio: meta.studio,
             cast: Vec::new(),
             provider: MetadataProvider::Jikan,
+            collection: None,
+            official_rating: None,
         }
     }
 
     fn tmdb_series_to_unified(&self, meta: MediaMetadata) -> UnifiedMetadata {
+        let artwork =
+            ArtworkUrls::from_tmdb(meta.poster_path, meta.backdrop_path, meta.images.as_ref());
         UnifiedMetadata {
             anilist_id: None,
             mal_id: None,
@@ -885,22 +1623,23 @@ impl MetadataService {
             year: meta.year,
             premiere_date: meta.premiere_date,
             community_rating: meta.community_rating,
-            poster_url: meta
-                .poster_path
-                .map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
-            backdrop_url: meta
-                .backdrop_path
-                .map(|p| format!("https://image.tmdb.org/t/p/w1280{}", p)),
+            poster_url: artwork.poster_url,
+            backdrop_url: artwork.backdrop_url,
+            extra_backdrop_urls: artwork.extra_backdrop_urls,
             episode_count: None,
             runtime_minutes: meta.runtime_minutes,
             genres: meta.genres,
             studio: None,
             cast: Self::convert_tmdb_cast(meta.cast),
             provider: MetadataProvider::Tmdb,
+            collection: None,
+            official_rating: meta.official_rating,
         }
     }
 
     fn tmdb_movie_to_unified(&self, meta: MediaMetadata) -> UnifiedMetadata {
+        let artwork =
+            ArtworkUrls::from_tmdb(meta.poster_path, meta.backdrop_path, meta.images.as_ref());
         UnifiedMetadata {
             anilist_id: None,
             mal_id: None,
@@ -914,18 +1653,17 @@ impl MetadataService {
             year: meta.year,
             premiere_date: meta.premiere_date,
             community_rating: meta.community_rating,
-            poster_url: meta
-                .poster_path
-                .map(|p| format!("https://image.tmdb.org/t/p/w500{}", p)),
-            backdrop_url: meta
-                .backdrop_path
-                .map(|p| format!("https://image.tmdb.org/t/p/w1280{}", p)),
+            poster_url: artwork.poster_url,
+            backdrop_url: artwork.backdrop_url,
+            extra_backdrop_urls: artwork.extra_backdrop_urls,
             episode_count: None,
             runtime_minutes: meta.runtime_minutes,
             genres: meta.genres,
             studio: None,
             cast: Self::convert_tmdb_cast(meta.cast),
             provider: MetadataProvider::Tmdb,
+            collection: meta.collection,
+            official_rating: meta.official_rating,
         }
     }
 
@@ -948,6 +1686,18 @@ impl MetadataService {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_absolute_to_season_episode() {
+        let counts = BTreeMap::from([(0, 5), (1, 61), (2, 16), (3, 14)]);
+        assert_eq!(absolute_to_season_episode(&counts, 12), Some((1, 12)));
+        assert_eq!(absolute_to_season_episode(&counts, 61), Some((1, 61)));
+        assert_eq!(absolute_to_season_episode(&counts, 62), Some((2, 1)));
+        assert_eq!(absolute_to_season_episode(&counts, 78), Some((3, 1)));
+        assert_eq!(absolute_to_season_episode(&counts, 91), Some((3, 14)));
+        assert_eq!(absolute_to_season_episode(&counts, 92), None);
+        assert_eq!(absolute_to_season_episode(&BTreeMap::new(), 1), None);
+    }
+
     #[test]
     fn test_is_likely_anime() {
         assert!(MetadataService::is_likely_anime(
@@ -992,4 +1742,121 @@ mod tests {
         assert!(!MetadataService::is_likely_anime("San Andreas (2015)"));
         assert!(!MetadataService::is_likely_anime("The Mandalorian"));
     }
+
+    #[test]
+    fn test_select_image_by_language_size_and_votes() {
+        let image = |path: &str, language: Option<&str>, width, votes, average| Image {
+            file_path: path.to_string(),
+            width,
+            iso_639_1: language.map(str::to_string),
+            vote_average: average,
+            vote_count: votes,
+        };
+        let preferences = ArtworkConfig {
+            languages: vec!["de".to_string(), "en".to_string()],
+            ..ArtworkConfig::default()
+        };
+        let images = vec![
+            image("/first.jpg", Some("en"), 500, 1, 5.0),
+            image("/textless.jpg", None, 2000, 20, 5.5),
+            image("/small.jpg", Some("en"), 600, 40, 6.0),
+            image("/large.jpg", Some("en"), 2000, 10, 5.3),
+            image("/best.jpg", Some("en"), 2000, 12, 5.4),
+            image("/french.jpg", Some("fr"), 2000, 50, 6.5),
+        ];
+        let selected = |backdrop| {
+            select_image(&images, backdrop, &preferences)
+                .map(|i| i.file_path.as_str())
+                .unwrap()
+        };
+
+        // English over textless for posters, and large well-voted ones first
+        assert_eq!(selected(false), "/best.jpg");
+        // Textless first for backdrops
+        assert_eq!(selected(true), "/textless.jpg");
+        // German is preferred but not offered; anything beats nothing
+        assert_eq!(
+            select_image(&images[5..], false, &preferences).map(|i| i.file_path.as_str()),
+            Some("/french.jpg")
+        );
+        assert!(select_image(&[], false, &preferences).is_none());
+    }
+
+    #[test]
+    fn test_tmdb_backdrops_in_rank_order() {
+        let backdrop = |path: &str, votes| Image {
+            file_path: path.to_string(),
+            width: 1920,
+            iso_639_1: None,
+            vote_average: 5.0,
+            vote_count: votes,
+        };
+        let images = Images {
+            posters: Vec::new(),
+            backdrops: (1..=7)
+                .map(|i| backdrop(&format!("/b{}.jpg", i), i))
+                .collect(),
+        };
+
+        let artwork = ArtworkUrls::from_tmdb(None, Some("/default.jpg".to_string()), Some(&images));
+        assert_eq!(
+            artwork.backdrop_url.as_deref(),
+            Some("https://image.tmdb.org/t/p/w1280/b7.jpg")
+        );
+        // Up to max_backdrops in all, best first
+        assert_eq!(artwork.extra_backdrop_urls.len(), 4);
+        assert!(artwork.extra_backdrop_urls[0].ends_with("/b6.jpg"));
+        assert!(artwork.extra_backdrop_urls[3].ends_with("/b3.jpg"));
+
+        // Without a list of images there is only the default backdrop
+        let artwork = ArtworkUrls::from_tmdb(None, Some("/default.jpg".to_string()), None);
+        assert!(artwork.backdrop_url.unwrap().ends_with("/default.jpg"));
+        assert!(artwork.extra_backdrop_urls.is_empty());
+
+        assert_eq!(crate::db::split_image_index("Backdrop3"), ("Backdrop", 3));
+        assert_eq!(crate::db::split_image_index("Backdrop"), ("Backdrop", 0));
+        assert_eq!(crate::db::indexed_image_type("Backdrop", 2), "Backdrop2");
+    }
+
+    #[test]
+    fn test_circuit_breaker_trips_and_resets() {
+        let circuit = CircuitBreaker::new(MetadataProvider::AniList);
+
+        for _ in 0..CIRCUIT_FAILURE_THRESHOLD - 1 {
+            circuit.record_failure();
+        }
+        assert!(!circuit.is_open());
+
+        circuit.record_failure();
+        assert!(circuit.is_open());
+
+        circuit.record_success();
+        assert!(!circuit.is_open());
+
+        // A success resets the consecutive failure count
+        circuit.record_failure();
+        assert!(!circuit.is_open());
+    }
+
+    #[test]
+    fn test_token_bucket_spaces_out_lookups() {
+        let bucket = TokenBucket::new(1.0, 2.0);
+        let start = Instant::now();
+
+        // A full bucket lets a burst through, then lookups queue up a second apart
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::ZERO);
+        assert_eq!(bucket.reserve(start), Duration::from_secs(1));
+        assert_eq!(bucket.reserve(start), Duration::from_secs(2));
+
+        // Waiting refills the bucket, but no further than the burst
+        assert_eq!(
+            bucket.reserve(start + Duration::from_secs(3)),
+            Duration::ZERO
+        );
+        let later = start + Duration::from_secs(60);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::ZERO);
+        assert_eq!(bucket.reserve(later), Duration::from_secs(1));
+    }
 }
@@ -1,12 +1,46 @@
 // Services module - business logic layer
 
 pub mod auth;
+pub mod box_sets;
+pub mod client_capabilities;
+pub mod device_profile;
+pub mod dlna;
+pub mod episode_order;
+pub mod external_streams;
+pub mod home_cache;
+pub mod image_proxy;
+pub mod image_refresh;
+pub mod image_resize;
+pub mod image_validation;
+pub mod invites;
+pub mod library_images;
+pub mod library_views;
+pub mod localization;
+pub mod lyrics;
 pub mod mediainfo;
+pub mod parental_controls;
+pub mod playback_stats;
+pub mod playstate;
+pub mod progress;
+pub mod provider_ids;
+pub mod scheduled_tasks;
+pub mod season_mapping;
+pub mod server_id;
+pub mod share_links;
+pub mod strm;
+pub mod suggestions;
+pub mod thumbnails;
+pub mod transcoding;
+pub mod updates;
+pub mod watch_import;
+pub mod webhooks;
+pub mod xml;
 
 // Metadata providers
 pub mod anidb;
 pub mod anilist;
 pub mod anime_db;
 pub mod jikan;
+pub mod live_tv;
 pub mod metadata;
 pub mod tmdb;
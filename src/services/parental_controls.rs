@@ -0,0 +1,165 @@
+// Parental controls
+//
+// Scans and refreshes store the age rating the provider gives an item
+// (official_rating: "PG-13", "TV-MA", "FSK 16") together with its score in
+// years (parental_rating), so ratings from different countries compare. An
+// admin can give a user the highest score they may see and tags (genre or
+// studio names) blocked for them. Both live in user_policies and are applied
+// through the user_hidden_items view, so browse, search, home rows and the
+// Shows endpoints leave out the same items; a limited series hides its seasons
+// and episodes. Items without a rating stay visible.
+
+use anyhow::Result;
+use regex::Regex;
+use sqlx::SqlitePool;
+use std::sync::LazyLock;
+
+/// Ratings offered when choosing a user's limit, with their scores
+pub const RATINGS: &[(&str, i32)] = &[
+    ("G", 0),
+    ("PG", 10),
+    ("PG-13", 13),
+    ("R", 17),
+    ("NC-17", 18),
+    ("TV-Y", 0),
+    ("TV-Y7", 7),
+    ("TV-G", 0),
+    ("TV-PG", 10),
+    ("TV-14", 14),
+    ("TV-MA", 17),
+];
+
+/// Other countries' ratings that aren't an age ("U" in the UK, "L" in Brazil)
+const OTHER_RATINGS: &[(&str, i32)] = &[
+    ("U", 0),
+    ("L", 0),
+    ("AL", 0),
+    ("ALL", 0),
+    ("TP", 0),
+    ("TV-Y7-FV", 7),
+    ("M", 15),
+    ("R18", 18),
+    ("X", 18),
+    ("XXX", 18),
+];
+
+/// Ratings that say an item wasn't rated
+const UNRATED: &[&str] = &["NR", "UR", "NOT RATED", "UNRATED"];
+
+/// Age in ratings such as "FSK 16", "12A", "MA 15+" or "14A"
+static RE_AGE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(\d{1,2})").unwrap());
+
+/// Score in years of a rating; None for unknown and unrated ones
+pub fn rating_score(rating: &str) -> Option<i32> {
+    let rating = rating.trim().to_uppercase();
+    if rating.is_empty() || UNRATED.contains(&rating.as_str()) {
+        return None;
+    }
+    if let Some((_, score)) = RATINGS
+        .iter()
+        .chain(OTHER_RATINGS)
+        .find(|(name, _)| *name == rating)
+    {
+        return Some(*score);
+    }
+    RE_AGE
+        .captures(&rating)
+        .and_then(|c| c[1].parse::<i32>().ok())
+        .filter(|age| *age <= 21)
+}
+
+/// Store an item's rating from its provider
+pub async fn set_item_rating(pool: &SqlitePool, item_id: &str, rating: &str) -> Result<()> {
+    sqlx::query("UPDATE media_items SET official_rating = ?, parental_rating = ? WHERE id = ?")
+        .bind(rating.trim())
+        .bind(rating_score(rating))
+        .bind(item_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// What an admin limited a user to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParentalPolicy {
+    /// Highest score shown; None for no limit
+    pub max_parental_rating: Option<i32>,
+    /// Genre and studio names hidden from the user
+    pub blocked_tags: Vec<String>,
+}
+
+/// A user's parental controls (the default when none are set)
+pub async fn load(pool: &SqlitePool, user_id: &str) -> Result<ParentalPolicy> {
+    let row: Option<(Option<i32>, String)> = sqlx::query_as(
+        "SELECT max_parental_rating, blocked_tags FROM user_policies WHERE user_id = ?",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(match row {
+        Some((max_parental_rating, blocked_tags)) => ParentalPolicy {
+            max_parental_rating,
+            blocked_tags: serde_json::from_str(&blocked_tags).unwrap_or_default(),
+        },
+        None => ParentalPolicy::default(),
+    })
+}
+
+/// Replace a user's parental controls; tags are trimmed and deduplicated
+pub async fn save(
+    conn: &mut sqlx::SqliteConnection,
+    user_id: &str,
+    policy: &ParentalPolicy,
+) -> Result<()> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in policy.blocked_tags.iter().map(|t| t.trim()) {
+        if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+
+    if policy.max_parental_rating.is_none() && tags.is_empty() {
+        sqlx::query("DELETE FROM user_policies WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO user_policies (user_id, max_parental_rating, blocked_tags, updated_at)
+         VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+         ON CONFLICT(user_id) DO UPDATE SET
+            max_parental_rating = excluded.max_parental_rating,
+            blocked_tags = excluded.blocked_tags,
+            updated_at = excluded.updated_at",
+    )
+    .bind(user_id)
+    .bind(policy.max_parental_rating)
+    .bind(serde_json::to_string(&tags)?)
+    .execute(&mut *conn)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rating_score() {
+        assert_eq!(rating_score("PG-13"), Some(13));
+        assert_eq!(rating_score("tv-ma"), Some(17));
+        assert_eq!(rating_score(" G "), Some(0));
+        assert_eq!(rating_score("U"), Some(0));
+        // Ages in other countries' ratings
+        assert_eq!(rating_score("FSK 16"), Some(16));
+        assert_eq!(rating_score("12A"), Some(12));
+        assert_eq!(rating_score("MA 15+"), Some(15));
+        assert_eq!(rating_score("16"), Some(16));
+        // Unrated and unknown
+        assert_eq!(rating_score("NR"), None);
+        assert_eq!(rating_score("Not Rated"), None);
+        assert_eq!(rating_score("Approved"), None);
+        assert_eq!(rating_score(""), None);
+    }
+}
@@ -0,0 +1,112 @@
+// Per-item playback analytics
+//
+// Every finished playback (a client's stop report, or a dead session finalized
+// by the reaper) is folded into rollups: how often an item was played, how far
+// viewers got on average, and the minutes at which unfinished playbacks were
+// abandoned. Admins use these to spot items nobody finishes (a bad encode, a
+// dull pilot). Only the rollups are kept, not individual playbacks.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+
+use crate::time::TICKS_PER_MINUTE;
+
+/// Drop-off positions are grouped into buckets of this many ticks (one minute)
+pub const DROP_OFF_BUCKET_TICKS: i64 = TICKS_PER_MINUTE;
+
+/// How far through an item a playback got, in percent
+///
+/// None when the runtime is unknown, unless the item was marked played.
+fn completion_percent(
+    position_ticks: i64,
+    runtime_ticks: Option<i64>,
+    played: bool,
+) -> Option<f64> {
+    if played {
+        return Some(100.0);
+    }
+    let runtime = runtime_ticks.filter(|&r| r > 0)?;
+    Some((position_ticks.max(0) as f64 / runtime as f64 * 100.0).min(100.0))
+}
+
+/// Drop-off bucket of an unfinished playback; None for finished ones and for
+/// playbacks stopped before they began
+fn drop_off_bucket(position_ticks: i64, played: bool) -> Option<i64> {
+    (!played && position_ticks > 0).then_some(position_ticks / DROP_OFF_BUCKET_TICKS)
+}
+
+/// Fold a finished playback into the item's rollups
+pub async fn record_playback(
+    pool: &SqlitePool,
+    item_id: &str,
+    position_ticks: i64,
+    played: bool,
+) -> Result<()> {
+    let runtime: Option<(Option<i64>,)> =
+        sqlx::query_as("SELECT runtime_ticks FROM media_items WHERE id = ?")
+            .bind(item_id)
+            .fetch_optional(pool)
+            .await?;
+    // The item was removed while it played
+    let Some((runtime_ticks,)) = runtime else {
+        return Ok(());
+    };
+    let completion = completion_percent(position_ticks, runtime_ticks, played);
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "INSERT INTO playback_stats
+            (item_id, play_count, completed_count, completion_sum, completion_samples, last_played_at)
+         VALUES (?1, 1, ?2, COALESCE(?3, 0), ?4, ?5)
+         ON CONFLICT(item_id) DO UPDATE SET
+            play_count = play_count + 1,
+            completed_count = completed_count + ?2,
+            completion_sum = completion_sum + COALESCE(?3, 0),
+            completion_samples = completion_samples + ?4,
+            last_played_at = ?5",
+    )
+    .bind(item_id)
+    .bind(played as i64)
+    .bind(completion)
+    .bind(completion.is_some() as i64)
+    .bind(chrono::Utc::now().to_rfc3339())
+    .execute(&mut *tx)
+    .await?;
+
+    if let Some(bucket) = drop_off_bucket(position_ticks, played) {
+        sqlx::query(
+            "INSERT INTO playback_drop_offs (item_id, minute, count) VALUES (?, ?, 1)
+             ON CONFLICT(item_id, minute) DO UPDATE SET count = count + 1",
+        )
+        .bind(item_id)
+        .bind(bucket)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_and_drop_off() {
+        let hour = 60 * DROP_OFF_BUCKET_TICKS;
+
+        assert_eq!(completion_percent(hour / 4, Some(hour), false), Some(25.0));
+        // Positions past the runtime (bad metadata) are capped
+        assert_eq!(completion_percent(2 * hour, Some(hour), false), Some(100.0));
+        assert_eq!(completion_percent(hour / 2, None, false), None);
+        assert_eq!(completion_percent(hour / 2, None, true), Some(100.0));
+
+        assert_eq!(
+            drop_off_bucket(12 * DROP_OFF_BUCKET_TICKS + 5, false),
+            Some(12)
+        );
+        assert_eq!(drop_off_bucket(hour, true), None);
+        assert_eq!(drop_off_bucket(0, false), None);
+    }
+}
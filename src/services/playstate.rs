@@ -0,0 +1,156 @@
+// Write-behind playback progress
+//
+// Clients report the position of whatever is playing every few seconds, and
+// each report used to be its own write to playback_progress, which contends
+// with scans and everything else writing to SQLite. Reports are now kept in
+// memory, one per user and item, and written together every few seconds; a
+// stop (or a reaped dead session) writes the final state at once and drops
+// what was pending. When the same user plays an item on two devices, the most
+// recent report wins, also against state written since it was made: a flush
+// never replaces a row whose last_played is newer than the report.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+/// How often pending positions are written
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A reported position not yet written
+#[derive(Debug, Clone, PartialEq)]
+struct PendingProgress {
+    position_ticks: i64,
+    reported_at: DateTime<Utc>,
+}
+
+// Pending positions by (user, item)
+static PENDING: LazyLock<Mutex<HashMap<(String, String), PendingProgress>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Keep the newer of a pending report and a new one
+fn merge(
+    pending: &mut HashMap<(String, String), PendingProgress>,
+    key: (String, String),
+    report: PendingProgress,
+) {
+    match pending.get(&key) {
+        Some(existing) if existing.reported_at > report.reported_at => {}
+        _ => {
+            pending.insert(key, report);
+        }
+    }
+}
+
+/// Record a reported playback position, to be written with the next flush
+pub fn record_progress(user_id: &str, item_id: &str, position_ticks: i64) {
+    let report = PendingProgress {
+        position_ticks,
+        reported_at: Utc::now(),
+    };
+    merge(
+        &mut PENDING.lock().unwrap(),
+        (user_id.to_string(), item_id.to_string()),
+        report,
+    );
+}
+
+/// Drop a pending position (the playback's final state is being written)
+pub fn discard(user_id: &str, item_id: &str) {
+    PENDING
+        .lock()
+        .unwrap()
+        .remove(&(user_id.to_string(), item_id.to_string()));
+}
+
+/// Write all pending positions in one transaction; returns how many were pending
+pub async fn flush(pool: &SqlitePool) -> Result<usize> {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    if pending.is_empty() {
+        return Ok(0);
+    }
+    if let Err(e) = write(pool, &pending).await {
+        // Put the reports back for the next flush, unless newer ones came in meanwhile
+        let mut current = PENDING.lock().unwrap();
+        for (key, progress) in pending {
+            merge(&mut current, key, progress);
+        }
+        return Err(e);
+    }
+    Ok(pending.len())
+}
+
+async fn write(
+    pool: &SqlitePool,
+    pending: &HashMap<(String, String), PendingProgress>,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    // Users and items deleted since their report are skipped
+    for ((user_id, item_id), progress) in pending {
+        sqlx::query(
+            r#"
+            INSERT INTO playback_progress (user_id, item_id, position_ticks, last_played)
+            SELECT u.id, m.id, ?, ? FROM users u, media_items m WHERE u.id = ? AND m.id = ?
+            ON CONFLICT (user_id, item_id) DO UPDATE SET
+                position_ticks = excluded.position_ticks,
+                last_played = excluded.last_played
+            WHERE playback_progress.last_played IS NULL
+               OR julianday(playback_progress.last_played) <= julianday(excluded.last_played)
+            "#,
+        )
+        .bind(progress.position_ticks)
+        .bind(progress.reported_at.to_rfc3339())
+        .bind(user_id)
+        .bind(item_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Flush pending positions every few seconds, and once more at shutdown
+pub async fn run_flusher(pool: SqlitePool, cancel: CancellationToken) {
+    loop {
+        let stopping = tokio::select! {
+            _ = cancel.cancelled() => true,
+            _ = tokio::time::sleep(FLUSH_INTERVAL) => false,
+        };
+        match flush(&pool).await {
+            Ok(0) => {}
+            Ok(count) => tracing::debug!("Wrote {} playback position(s)", count),
+            Err(e) => tracing::warn!("Failed to write playback positions: {}", e),
+        }
+        if stopping {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newest_report_wins() {
+        let at = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        let report = |position_ticks, secs| PendingProgress {
+            position_ticks,
+            reported_at: at(secs),
+        };
+        let key = || ("user".to_string(), "item".to_string());
+        let mut pending = HashMap::new();
+
+        merge(&mut pending, key(), report(100, 10));
+        merge(&mut pending, key(), report(200, 20));
+        assert_eq!(pending[&key()].position_ticks, 200);
+
+        // Reports put back after a failed write don't replace newer ones
+        merge(&mut pending, key(), report(150, 15));
+        assert_eq!(pending[&key()], report(200, 20));
+        assert_eq!(pending.len(), 1);
+    }
+}
@@ -0,0 +1,205 @@
+// In-memory progress for the admin dashboard
+//
+// Scans and the server's background loops report here as they run, so
+// /System/Status can show what the server is busy with without a database
+// round trip per scan. Nothing is persisted: a restart starts empty, and
+// scan_history keeps the record of finished scans.
+
+use futures::FutureExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+static NEXT_SCAN: AtomicU64 = AtomicU64::new(1);
+
+static SCANS: LazyLock<Mutex<HashMap<u64, ScanProgress>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// Libraries with a scan requested through the API, from the request until the scan ends
+static REQUESTED: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+static BACKGROUND: LazyLock<Mutex<BTreeMap<&'static str, BackgroundTask>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+/// A scan that is running
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ScanProgress {
+    pub library_id: String,
+    pub scan_type: &'static str,
+    pub started_at: String,
+    /// Media files found so far (grows as folders are walked)
+    pub items_discovered: u64,
+    /// Files added, updated or skipped as unchanged
+    pub items_processed: u64,
+}
+
+/// Removes its scan from the running scans when dropped, however the scan ends
+pub struct ScanGuard {
+    id: u64,
+}
+
+impl Drop for ScanGuard {
+    fn drop(&mut self) {
+        SCANS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Track a scan (Full, Quick, Targeted or Refresh) of a library until the guard is dropped
+pub fn start_scan(library_id: &str, scan_type: &'static str) -> ScanGuard {
+    let id = NEXT_SCAN.fetch_add(1, Ordering::Relaxed);
+    SCANS.lock().unwrap().insert(
+        id,
+        ScanProgress {
+            library_id: library_id.to_string(),
+            scan_type,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            items_discovered: 0,
+            items_processed: 0,
+        },
+    );
+    ScanGuard { id }
+}
+
+/// Count files found by the running scans of a library
+pub fn add_discovered(library_id: &str, count: usize) {
+    update(library_id, |scan| scan.items_discovered += count as u64);
+}
+
+/// Count a file handled by the running scans of a library
+pub fn add_processed(library_id: &str) {
+    update(library_id, |scan| scan.items_processed += 1);
+}
+
+fn update(library_id: &str, f: impl Fn(&mut ScanProgress)) {
+    let mut scans = SCANS.lock().unwrap();
+    scans
+        .values_mut()
+        .filter(|scan| scan.library_id == library_id)
+        .for_each(f);
+}
+
+/// Holds a library's requested scan until dropped
+pub struct ScanRequest {
+    library_id: String,
+}
+
+impl Drop for ScanRequest {
+    fn drop(&mut self) {
+        REQUESTED.lock().unwrap().remove(&self.library_id);
+    }
+}
+
+/// Claim a library for an on-demand scan; None while it is already being scanned
+pub fn request_scan(library_id: &str) -> Option<ScanRequest> {
+    let mut requested = REQUESTED.lock().unwrap();
+    let scanning = SCANS
+        .lock()
+        .unwrap()
+        .values()
+        .any(|scan| scan.library_id == library_id);
+    if scanning || !requested.insert(library_id.to_string()) {
+        return None;
+    }
+    Some(ScanRequest {
+        library_id: library_id.to_string(),
+    })
+}
+
+/// Running scans, oldest first
+pub fn scans() -> Vec<ScanProgress> {
+    let mut scans: Vec<ScanProgress> = SCANS.lock().unwrap().values().cloned().collect();
+    scans.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    scans
+}
+
+/// A background loop started by main
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BackgroundTask {
+    pub name: &'static str,
+    /// Running, Finished or Panicked
+    pub state: &'static str,
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stopped_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn set_background_state(name: &'static str, state: &'static str, error: Option<String>) {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tasks = BACKGROUND.lock().unwrap();
+    let task = tasks.entry(name).or_insert_with(|| BackgroundTask {
+        name,
+        state,
+        started_at: now.clone(),
+        stopped_at: None,
+        error: None,
+    });
+    task.state = state;
+    if state != "Running" {
+        task.stopped_at = Some(now);
+    }
+    task.error = error;
+}
+
+/// Wrap a background loop so its state shows up in `background_tasks`
+///
+/// A panic is recorded and then resumed, so the task's JoinHandle still
+/// reports it.
+pub async fn supervise<F>(name: &'static str, future: F)
+where
+    F: Future<Output = ()>,
+{
+    set_background_state(name, "Running", None);
+    match AssertUnwindSafe(future).catch_unwind().await {
+        Ok(()) => set_background_state(name, "Finished", None),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_background_state(name, "Panicked", Some(message));
+            std::panic::resume_unwind(payload);
+        }
+    }
+}
+
+/// Background loops by name
+pub fn background_tasks() -> Vec<BackgroundTask> {
+    BACKGROUND.lock().unwrap().values().cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_progress_ends_with_guard() {
+        let guard = start_scan("progress-test-lib", "Quick");
+        add_discovered("progress-test-lib", 3);
+        add_processed("progress-test-lib");
+        add_processed("other-lib");
+
+        let scan = scans()
+            .into_iter()
+            .find(|s| s.library_id == "progress-test-lib")
+            .unwrap();
+        assert_eq!((scan.items_discovered, scan.items_processed), (3, 1));
+
+        // A library being scanned can't be claimed for another scan
+        assert!(request_scan("progress-test-lib").is_none());
+        drop(guard);
+        assert!(!scans().iter().any(|s| s.library_id == "progress-test-lib"));
+
+        let request = request_scan("progress-test-lib").unwrap();
+        assert!(request_scan("progress-test-lib").is_none());
+        drop(request);
+        assert!(request_scan("progress-test-lib").is_some());
+    }
+}
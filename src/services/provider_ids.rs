@@ -0,0 +1,231 @@
+// Provider ID cross-population
+//
+// Items are usually matched through a single provider (often AniList), which
+// leaves the other ID columns empty. This fills them in from cross-references:
+// AniList/MAL/AniDB/Kitsu IDs from the anime offline database (when enabled),
+// and IMDb/TVDB IDs from TMDB's external IDs for items with a TMDB ID. Only
+// empty columns are written. Each series and movie is checked once after a
+// scan, and again whenever its metadata is updated.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+
+use super::anime_db::ProviderIds;
+use super::metadata::MetadataService;
+use super::tmdb::ExternalIds;
+
+#[derive(Debug, Default, sqlx::FromRow)]
+struct ItemIds {
+    id: String,
+    item_type: String,
+    tmdb_id: Option<String>,
+    imdb_id: Option<String>,
+    tvdb_id: Option<String>,
+    anilist_id: Option<String>,
+    mal_id: Option<String>,
+    anidb_id: Option<String>,
+    kitsu_id: Option<String>,
+}
+
+const ITEM_IDS_COLUMNS: &str =
+    "id, item_type, tmdb_id, imdb_id, tvdb_id, anilist_id, mal_id, anidb_id, kitsu_id";
+
+/// IDs found for columns that were empty
+#[derive(Debug, Default, PartialEq)]
+struct ResolvedIds {
+    anilist_id: Option<String>,
+    mal_id: Option<String>,
+    anidb_id: Option<String>,
+    kitsu_id: Option<String>,
+    imdb_id: Option<String>,
+    tvdb_id: Option<String>,
+}
+
+impl ResolvedIds {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+fn parse_id(id: &Option<String>) -> Option<i64> {
+    id.as_deref().and_then(|s| s.trim().parse().ok())
+}
+
+/// Value to store for a column: only when it is empty and a new ID was found
+fn fill(current: &Option<String>, found: Option<String>) -> Option<String> {
+    if current.is_some() {
+        None
+    } else {
+        found.filter(|id| !id.trim().is_empty())
+    }
+}
+
+fn resolve(
+    item: &ItemIds,
+    anime: Option<&ProviderIds>,
+    external: Option<&ExternalIds>,
+) -> ResolvedIds {
+    let anime_id = |f: fn(&ProviderIds) -> Option<i64>| anime.and_then(f).map(|id| id.to_string());
+    ResolvedIds {
+        anilist_id: fill(&item.anilist_id, anime_id(|ids| ids.anilist_id)),
+        mal_id: fill(&item.mal_id, anime_id(|ids| ids.mal_id)),
+        anidb_id: fill(&item.anidb_id, anime_id(|ids| ids.anidb_id)),
+        kitsu_id: fill(&item.kitsu_id, anime_id(|ids| ids.kitsu_id)),
+        imdb_id: fill(&item.imdb_id, external.and_then(|e| e.imdb_id.clone())),
+        tvdb_id: fill(
+            &item.tvdb_id,
+            external.and_then(|e| e.tvdb_id).map(|id| id.to_string()),
+        ),
+    }
+}
+
+/// Look up and store the missing IDs of one item; returns whether any were added
+async fn cross_populate(
+    pool: &SqlitePool,
+    metadata: &MetadataService,
+    item: &ItemIds,
+) -> Result<bool> {
+    let known = ProviderIds {
+        anilist_id: parse_id(&item.anilist_id),
+        mal_id: parse_id(&item.mal_id),
+        anidb_id: parse_id(&item.anidb_id),
+        kitsu_id: parse_id(&item.kitsu_id),
+    };
+    let has_anime_id = known.anilist_id.is_some()
+        || known.mal_id.is_some()
+        || known.anidb_id.is_some()
+        || known.kitsu_id.is_some();
+    let anime = if has_anime_id {
+        metadata.anime_db_provider_ids(&known).await?
+    } else {
+        None
+    };
+
+    let is_movie = item.item_type == "Movie";
+    // Movies have no TVDB ID, so only a missing IMDb ID is worth a request
+    let wants_external = item.imdb_id.is_none() || (!is_movie && item.tvdb_id.is_none());
+    let external = match parse_id(&item.tmdb_id) {
+        Some(tmdb_id) if wants_external => metadata.tmdb_external_ids(tmdb_id, is_movie).await?,
+        _ => None,
+    };
+
+    let resolved = resolve(item, anime.as_ref(), external.as_ref());
+    sqlx::query(
+        "UPDATE media_items SET
+            anilist_id = COALESCE(anilist_id, ?),
+            mal_id = COALESCE(mal_id, ?),
+            anidb_id = COALESCE(anidb_id, ?),
+            kitsu_id = COALESCE(kitsu_id, ?),
+            imdb_id = COALESCE(imdb_id, ?),
+            tvdb_id = COALESCE(tvdb_id, ?),
+            provider_ids_checked_at = CURRENT_TIMESTAMP
+         WHERE id = ?",
+    )
+    .bind(resolved.anilist_id.as_deref())
+    .bind(resolved.mal_id.as_deref())
+    .bind(resolved.anidb_id.as_deref())
+    .bind(resolved.kitsu_id.as_deref())
+    .bind(resolved.imdb_id.as_deref())
+    .bind(resolved.tvdb_id.as_deref())
+    .bind(&item.id)
+    .execute(pool)
+    .await?;
+
+    if !resolved.is_empty() {
+        tracing::debug!(
+            "Cross-populated provider IDs for {}: {:?}",
+            item.id,
+            resolved
+        );
+    }
+    Ok(!resolved.is_empty())
+}
+
+/// Fill missing provider IDs for a library's series and movies not checked since
+/// their last update; returns how many items gained IDs
+pub async fn cross_populate_library(
+    pool: &SqlitePool,
+    metadata: &MetadataService,
+    library_id: &str,
+) -> Result<usize> {
+    let items: Vec<ItemIds> = sqlx::query_as(&format!(
+        "SELECT {} FROM media_items
+         WHERE library_id = ? AND parent_id IS NULL AND item_type IN ('Series', 'Movie')
+           AND (provider_ids_checked_at IS NULL OR updated_at > provider_ids_checked_at)
+           AND COALESCE(anilist_id, mal_id, anidb_id, kitsu_id, tmdb_id) IS NOT NULL",
+        ITEM_IDS_COLUMNS
+    ))
+    .bind(library_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut updated = 0;
+    for item in &items {
+        match cross_populate(pool, metadata, item).await {
+            Ok(true) => updated += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Failed to resolve provider IDs for {}: {}", item.id, e),
+        }
+    }
+    Ok(updated)
+}
+
+/// Fill missing provider IDs for a single item (e.g. after a metadata refresh)
+pub async fn cross_populate_item(
+    pool: &SqlitePool,
+    metadata: &MetadataService,
+    item_id: &str,
+) -> Result<bool> {
+    let item: Option<ItemIds> = sqlx::query_as(&format!(
+        "SELECT {} FROM media_items WHERE id = ?",
+        ITEM_IDS_COLUMNS
+    ))
+    .bind(item_id)
+    .fetch_optional(pool)
+    .await?;
+
+    match item {
+        Some(item) => cross_populate(pool, metadata, &item).await,
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_only_fills_empty_columns() {
+        let item = ItemIds {
+            id: "s1".to_string(),
+            item_type: "Series".to_string(),
+            anilist_id: Some("21".to_string()),
+            mal_id: Some("999".to_string()),
+            imdb_id: Some("tt0388629".to_string()),
+            ..Default::default()
+        };
+        let anime = ProviderIds {
+            anilist_id: Some(21),
+            mal_id: Some(21),
+            anidb_id: Some(69),
+            kitsu_id: Some(12),
+        };
+        let external = ExternalIds {
+            imdb_id: Some("tt9999999".to_string()),
+            tvdb_id: Some(81797),
+        };
+
+        let resolved = resolve(&item, Some(&anime), Some(&external));
+        assert_eq!(
+            resolved,
+            ResolvedIds {
+                anidb_id: Some("69".to_string()),
+                kitsu_id: Some("12".to_string()),
+                tvdb_id: Some("81797".to_string()),
+                ..Default::default()
+            }
+        );
+
+        assert!(resolve(&item, None, None).is_empty());
+    }
+}
@@ -0,0 +1,856 @@
+// Scheduled tasks
+//
+// The maintenance jobs admins see under Scheduled Tasks: scanning libraries,
+// looking up missing metadata, queueing missing thumbnails, refreshing
+// artwork, optimizing the database, rebuilding the search index, checking
+// the database's consistency, cleaning up sessions and refreshing the Live TV
+// guide. Each has triggers in Jellyfin's shape (daily, weekly, interval,
+// startup); until an admin changes them they come from the config
+// (`scanner.*` intervals), and changed ones are kept in the scheduled_tasks
+// table with each task's last result. The scheduler checks the triggers every
+// 30 seconds and a task runs once at a time, whether started by a trigger or
+// through the API. Tasks report progress as they work through their steps (a
+// library, a cleanup) and stop between steps when cancelled or past a
+// trigger's maximum runtime. Library tasks aren't triggered while there are no
+// libraries, so a fresh install doesn't log a scan of nothing every few
+// minutes.
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Local, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::AppConfig;
+use crate::db;
+use crate::scanner;
+use crate::services::{auth, image_refresh, live_tv, share_links};
+use crate::time::{Ticks, TICKS_PER_MILLISECOND};
+
+/// How often the scheduler checks the triggers
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A task the server can run
+pub struct TaskDefinition {
+    pub id: &'static str,
+    pub key: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub category: &'static str,
+}
+
+pub const TASKS: &[TaskDefinition] = &[
+    TaskDefinition {
+        id: "library-scan",
+        key: "RefreshLibrary",
+        name: "Scan Media Library",
+        description: "Adds new files and removes missing ones in every library",
+        category: "Library",
+    },
+    TaskDefinition {
+        id: "missing-metadata",
+        key: "RefreshMissingMetadata",
+        name: "Scan for Missing Metadata",
+        description: "Looks up metadata for items without any, and retries unmatched series",
+        category: "Library",
+    },
+    TaskDefinition {
+        id: "thumbnail-gen",
+        key: "RefreshThumbnails",
+        name: "Generate Missing Thumbnails",
+        description: "Queues thumbnails for episodes and movies without artwork",
+        category: "Library",
+    },
+    TaskDefinition {
+        id: "image-refresh",
+        key: "ImageRefresh",
+        name: "Refresh All Images",
+        description:
+            "Downloads every series and movie poster and backdrop again from the metadata providers",
+        category: "Library",
+    },
+    TaskDefinition {
+        id: "db-optimize",
+        key: "OptimizeDatabase",
+        name: "Optimize Database",
+        description: "Compacts the database and search index and refreshes query statistics",
+        category: "Maintenance",
+    },
+    TaskDefinition {
+        id: "search-index",
+        key: "RebuildSearchIndex",
+        name: "Rebuild Search Index",
+        description:
+            "Indexes every item for search again, for when search misses or shows removed items",
+        category: "Maintenance",
+    },
+    TaskDefinition {
+        id: "consistency-check",
+        key: "CheckDatabaseConsistency",
+        name: "Check Database Consistency",
+        description: "Looks for rows pointing at deleted items, missing image files and a stale search index, repairing them if enabled",
+        category: "Maintenance",
+    },
+    TaskDefinition {
+        id: "session-cleanup",
+        key: "SessionCleanup",
+        name: "Clean Up Session Data",
+        description: "Removes expired logins, stale sessions and expired share links",
+        category: "Maintenance",
+    },
+    TaskDefinition {
+        id: "live-tv-guide",
+        key: "RefreshGuide",
+        name: "Refresh Guide",
+        description: "Reads the channels and program guide of the Live TV tuner again",
+        category: "Live TV",
+    },
+];
+
+pub fn find(id: &str) -> Option<&'static TaskDefinition> {
+    TASKS
+        .iter()
+        .find(|t| t.id == id || t.key.eq_ignore_ascii_case(id))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TriggerType {
+    #[serde(rename = "DailyTrigger")]
+    Daily,
+    #[serde(rename = "WeeklyTrigger")]
+    Weekly,
+    #[serde(rename = "IntervalTrigger")]
+    Interval,
+    #[serde(rename = "StartupTrigger")]
+    Startup,
+}
+
+/// When a task runs (Jellyfin's TaskTriggerInfo)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskTrigger {
+    #[serde(rename = "Type")]
+    pub trigger_type: TriggerType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_of_day_ticks: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_ticks: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day_of_week: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_runtime_ticks: Option<i64>,
+}
+
+impl TaskTrigger {
+    fn interval(minutes: u64) -> Self {
+        Self {
+            trigger_type: TriggerType::Interval,
+            time_of_day_ticks: None,
+            interval_ticks: Some(Ticks::from_minutes(minutes as i64).0),
+            day_of_week: None,
+            max_runtime_ticks: None,
+        }
+    }
+
+    fn startup() -> Self {
+        Self {
+            trigger_type: TriggerType::Startup,
+            time_of_day_ticks: None,
+            interval_ticks: None,
+            day_of_week: None,
+            max_runtime_ticks: None,
+        }
+    }
+
+    /// Check the fields its type needs
+    pub fn validate(&self) -> Result<(), String> {
+        match self.trigger_type {
+            TriggerType::Interval if self.interval_ticks.unwrap_or(0) <= 0 => {
+                Err("IntervalTrigger needs a positive IntervalTicks".to_string())
+            }
+            TriggerType::Daily | TriggerType::Weekly if self.time_of_day().is_none() => {
+                Err(format!(
+                    "{:?}Trigger needs TimeOfDayTicks within a day",
+                    self.trigger_type
+                ))
+            }
+            TriggerType::Weekly if self.weekday().is_none() => {
+                Err("WeeklyTrigger needs a DayOfWeek".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn time_of_day(&self) -> Option<NaiveTime> {
+        let millis = self.time_of_day_ticks? / TICKS_PER_MILLISECOND;
+        let seconds = u32::try_from(millis / 1000).ok()?;
+        NaiveTime::from_num_seconds_from_midnight_opt(seconds, (millis % 1000) as u32 * 1_000_000)
+    }
+
+    fn weekday(&self) -> Option<Weekday> {
+        self.day_of_week.as_deref()?.parse().ok()
+    }
+
+    fn max_runtime(&self) -> Option<Duration> {
+        let ticks = self.max_runtime_ticks.filter(|t| *t > 0)?;
+        Some(Duration::from_millis(
+            (ticks / TICKS_PER_MILLISECOND) as u64,
+        ))
+    }
+
+    /// Whether the trigger fires between the scheduler's previous check and
+    /// `now`; interval triggers count from `interval_base` (the end of the
+    /// task's last run, or the scheduler's start when later)
+    fn fires(
+        &self,
+        previous_check: Option<DateTime<Local>>,
+        now: DateTime<Local>,
+        interval_base: DateTime<Utc>,
+    ) -> bool {
+        let Some(previous_check) = previous_check else {
+            return self.trigger_type == TriggerType::Startup;
+        };
+        match self.trigger_type {
+            TriggerType::Startup => false,
+            TriggerType::Interval => {
+                let ticks = self.interval_ticks.unwrap_or(0);
+                ticks > 0
+                    && now.with_timezone(&Utc)
+                        >= interval_base
+                            + chrono::Duration::milliseconds(ticks / TICKS_PER_MILLISECOND)
+            }
+            TriggerType::Daily | TriggerType::Weekly => {
+                let Some(time) = self.time_of_day() else {
+                    return false;
+                };
+                // Checks may straddle midnight
+                [previous_check.date_naive(), now.date_naive()]
+                    .into_iter()
+                    .filter(|date| {
+                        self.trigger_type == TriggerType::Daily
+                            || Some(date.weekday()) == self.weekday()
+                    })
+                    .filter_map(|date| date.and_time(time).and_local_timezone(Local).earliest())
+                    .any(|at| previous_check < at && at <= now)
+            }
+        }
+    }
+}
+
+/// Triggers a task has until an admin changes them
+fn default_triggers(task: &TaskDefinition, config: &AppConfig) -> Vec<TaskTrigger> {
+    let scanner = &config.scanner;
+    let mut triggers = Vec::new();
+    match task.id {
+        "library-scan" if scanner.enabled => {
+            if scanner.quick_scan_interval_minutes > 0 {
+                triggers.push(TaskTrigger::interval(scanner.quick_scan_interval_minutes));
+            }
+            if scanner.scan_on_startup {
+                triggers.push(TaskTrigger::startup());
+            }
+        }
+        "missing-metadata" if scanner.unmatched_retry_interval_hours > 0 => {
+            triggers.push(TaskTrigger::interval(
+                scanner.unmatched_retry_interval_hours * 60,
+            ));
+        }
+        "thumbnail-gen" if scanner.missing_thumbnail_check_minutes > 0 => {
+            triggers.push(TaskTrigger::interval(
+                scanner.missing_thumbnail_check_minutes,
+            ));
+        }
+        "db-optimize" => triggers.push(TaskTrigger::interval(24 * 60)),
+        "consistency-check" if scanner.consistency_check_on_startup => {
+            triggers.push(TaskTrigger::startup());
+        }
+        "session-cleanup" => triggers.push(TaskTrigger::interval(5)),
+        "live-tv-guide" if config.live_tv.is_enabled() => {
+            triggers.push(TaskTrigger::startup());
+            if config.live_tv.guide_refresh_hours > 0 {
+                triggers.push(TaskTrigger::interval(
+                    config.live_tv.guide_refresh_hours * 60,
+                ));
+            }
+        }
+        _ => {}
+    }
+    triggers
+}
+
+/// How a run ended (Jellyfin's TaskCompletionStatus)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum TaskStatus {
+    Completed,
+    Failed,
+    /// Stopped by an admin
+    Cancelled,
+    /// Stopped for running past a trigger's MaxRuntimeTicks
+    Aborted,
+}
+
+impl TaskStatus {
+    fn parse(status: &str) -> Option<Self> {
+        match status {
+            "Completed" => Some(Self::Completed),
+            "Failed" => Some(Self::Failed),
+            "Cancelled" => Some(Self::Cancelled),
+            "Aborted" => Some(Self::Aborted),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskResult {
+    pub start_time_utc: String,
+    pub end_time_utc: String,
+    pub status: TaskStatus,
+    pub name: String,
+    pub key: String,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_message: Option<String>,
+}
+
+/// A task with its state (Jellyfin's TaskInfo)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskInfo {
+    pub name: String,
+    /// Idle, Running or Cancelling
+    pub state: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub current_progress_percentage: Option<f64>,
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_execution_result: Option<TaskResult>,
+    pub triggers: Vec<TaskTrigger>,
+    pub description: String,
+    pub category: String,
+    pub is_hidden: bool,
+    pub key: String,
+}
+
+/// Handed to a running task to report progress and see cancellation
+#[derive(Clone)]
+pub struct TaskContext {
+    progress: Arc<Mutex<f64>>,
+    cancel: CancellationToken,
+}
+
+impl TaskContext {
+    fn new() -> Self {
+        Self {
+            progress: Arc::new(Mutex::new(0.0)),
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// Record that `done` of `total` steps are finished
+    pub fn report(&self, done: usize, total: usize) {
+        let percent = if total == 0 {
+            100.0
+        } else {
+            done as f64 * 100.0 / total as f64
+        };
+        *self.progress.lock().unwrap() = percent.min(100.0);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Report progress before step `done` of `total`; false when the task
+    /// should stop instead
+    pub fn step(&self, done: usize, total: usize) -> bool {
+        if self.is_cancelled() {
+            return false;
+        }
+        self.report(done, total);
+        true
+    }
+}
+
+/// Tasks running now, by id
+static RUNNING: LazyLock<Mutex<HashMap<&'static str, TaskContext>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn is_running(task_id: &str) -> bool {
+    RUNNING.lock().unwrap().contains_key(task_id)
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TaskRow {
+    id: String,
+    triggers: Option<String>,
+    last_status: Option<String>,
+    last_started_at: Option<String>,
+    last_finished_at: Option<String>,
+    last_error: Option<String>,
+}
+
+async fn load_rows(pool: &SqlitePool) -> Result<HashMap<String, TaskRow>> {
+    let rows: Vec<TaskRow> = sqlx::query_as("SELECT * FROM scheduled_tasks")
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|row| (row.id.clone(), row)).collect())
+}
+
+fn triggers_of(
+    task: &TaskDefinition,
+    row: Option<&TaskRow>,
+    config: &AppConfig,
+) -> Vec<TaskTrigger> {
+    row.and_then(|row| row.triggers.as_deref())
+        .and_then(|json| match serde_json::from_str(json) {
+            Ok(triggers) => Some(triggers),
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable triggers of task {}: {}", task.id, e);
+                None
+            }
+        })
+        .unwrap_or_else(|| default_triggers(task, config))
+}
+
+fn info(task: &TaskDefinition, row: Option<&TaskRow>, config: &AppConfig) -> TaskInfo {
+    let running = RUNNING.lock().unwrap().get(task.id).cloned();
+    let (state, progress) = match running {
+        Some(ctx) if ctx.is_cancelled() => ("Cancelling", Some(*ctx.progress.lock().unwrap())),
+        Some(ctx) => ("Running", Some(*ctx.progress.lock().unwrap())),
+        None => ("Idle", None),
+    };
+    let last_execution_result = row.and_then(|row| {
+        Some(TaskResult {
+            start_time_utc: row.last_started_at.clone()?,
+            end_time_utc: row.last_finished_at.clone()?,
+            status: TaskStatus::parse(row.last_status.as_deref()?)?,
+            name: task.name.to_string(),
+            key: task.key.to_string(),
+            id: task.id.to_string(),
+            error_message: row.last_error.clone(),
+        })
+    });
+
+    TaskInfo {
+        name: task.name.to_string(),
+        state,
+        current_progress_percentage: progress,
+        id: task.id.to_string(),
+        last_execution_result,
+        triggers: triggers_of(task, row, config),
+        description: task.description.to_string(),
+        category: task.category.to_string(),
+        is_hidden: false,
+        key: task.key.to_string(),
+    }
+}
+
+/// Every task with its triggers, progress and last result
+pub async fn list(pool: &SqlitePool, config: &AppConfig) -> Result<Vec<TaskInfo>> {
+    let rows = load_rows(pool).await?;
+    Ok(TASKS
+        .iter()
+        .map(|task| info(task, rows.get(task.id), config))
+        .collect())
+}
+
+/// One task by id or key
+pub async fn get(pool: &SqlitePool, config: &AppConfig, id: &str) -> Result<Option<TaskInfo>> {
+    let Some(task) = find(id) else {
+        return Ok(None);
+    };
+    let rows = load_rows(pool).await?;
+    Ok(Some(info(task, rows.get(task.id), config)))
+}
+
+/// Replace a task's triggers
+pub async fn set_triggers(
+    pool: &SqlitePool,
+    task: &TaskDefinition,
+    triggers: &[TaskTrigger],
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO scheduled_tasks (id, triggers) VALUES (?, ?)
+         ON CONFLICT(id) DO UPDATE SET triggers = excluded.triggers",
+    )
+    .bind(task.id)
+    .bind(serde_json::to_string(triggers)?)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn record_result(
+    pool: &SqlitePool,
+    task: &TaskDefinition,
+    started: DateTime<Utc>,
+    status: TaskStatus,
+    error: Option<String>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO scheduled_tasks (id, last_status, last_started_at, last_finished_at, last_error)
+         VALUES (?, ?, ?, ?, ?)
+         ON CONFLICT(id) DO UPDATE SET last_status = excluded.last_status,
+             last_started_at = excluded.last_started_at,
+             last_finished_at = excluded.last_finished_at,
+             last_error = excluded.last_error",
+    )
+    .bind(task.id)
+    .bind(format!("{:?}", status))
+    .bind(started.to_rfc3339())
+    .bind(Utc::now().to_rfc3339())
+    .bind(error)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Start a task in the background; false when it is already running
+pub fn start(
+    pool: &SqlitePool,
+    config: &AppConfig,
+    task: &'static TaskDefinition,
+    max_runtime: Option<Duration>,
+) -> bool {
+    let ctx = {
+        let mut running = RUNNING.lock().unwrap();
+        if running.contains_key(task.id) {
+            return false;
+        }
+        let ctx = TaskContext::new();
+        running.insert(task.id, ctx.clone());
+        ctx
+    };
+
+    let (pool, config) = (pool.clone(), config.clone());
+    tokio::spawn(async move {
+        tracing::info!("Task '{}' started", task.name);
+        let started = Utc::now();
+        let body = execute(task, &pool, &config, &ctx);
+        tokio::pin!(body);
+        let mut aborted = false;
+        let result = match max_runtime {
+            Some(limit) => tokio::select! {
+                result = &mut body => result,
+                _ = tokio::time::sleep(limit) => {
+                    aborted = true;
+                    ctx.cancel.cancel();
+                    body.await
+                }
+            },
+            None => body.await,
+        };
+
+        let (status, error) = match result {
+            Err(e) => (TaskStatus::Failed, Some(e.to_string())),
+            Ok(()) if aborted => (TaskStatus::Aborted, None),
+            Ok(()) if ctx.is_cancelled() => (TaskStatus::Cancelled, None),
+            Ok(()) => (TaskStatus::Completed, None),
+        };
+        match &error {
+            Some(e) => tracing::warn!("Task '{}' failed: {}", task.name, e),
+            None => tracing::info!("Task '{}' finished: {:?}", task.name, status),
+        }
+        if let Err(e) = record_result(&pool, task, started, status, error).await {
+            tracing::warn!("Failed to record result of task '{}': {}", task.name, e);
+        }
+        RUNNING.lock().unwrap().remove(task.id);
+    });
+    true
+}
+
+/// Ask a running task to stop after its current step; false when it isn't running
+pub fn cancel(task: &TaskDefinition) -> bool {
+    match RUNNING.lock().unwrap().get(task.id) {
+        Some(ctx) => {
+            ctx.cancel.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+async fn execute(
+    task: &TaskDefinition,
+    pool: &SqlitePool,
+    config: &AppConfig,
+    ctx: &TaskContext,
+) -> Result<()> {
+    let cache_dir = &config.paths.cache_dir;
+    match task.id {
+        "library-scan" | "missing-metadata" => {
+            let libraries: Vec<(String, String, String)> =
+                sqlx::query_as("SELECT id, path, library_type FROM libraries ORDER BY name")
+                    .fetch_all(pool)
+                    .await?;
+            let quick = task.id == "library-scan";
+            // The missing metadata scan ends by retrying unmatched series
+            let total = libraries.len() + usize::from(!quick);
+            for (done, (library_id, path, library_type)) in libraries.iter().enumerate() {
+                if !ctx.step(done, total) {
+                    return Ok(());
+                }
+                if quick {
+                    let result = scanner::quick_scan_library(
+                        pool,
+                        library_id,
+                        path,
+                        library_type,
+                        cache_dir.clone(),
+                    )
+                    .await?;
+                    if result.files_added > 0 || result.files_removed > 0 {
+                        tracing::info!(
+                            "Quick scan of {}: {} added, {} removed",
+                            path,
+                            result.files_added,
+                            result.files_removed
+                        );
+                    }
+                } else if library_type != "music" {
+                    scanner::scan_missing_metadata(
+                        pool,
+                        library_id,
+                        cache_dir.clone(),
+                        Some(config.anime_db_enabled),
+                    )
+                    .await?;
+                }
+            }
+            if !quick && ctx.step(libraries.len(), total) {
+                let result = scanner::retry_unmatched_series(
+                    pool,
+                    cache_dir.clone(),
+                    Some(config.anime_db_enabled),
+                )
+                .await?;
+                if result.series_retried > 0 {
+                    tracing::info!(
+                        "Retried {} unmatched series, {} matched",
+                        result.series_retried,
+                        result.series_matched
+                    );
+                }
+            }
+        }
+        "thumbnail-gen" => {
+            if !ctx.step(0, 2) {
+                return Ok(());
+            }
+            let queued = db::queue_missing_thumbnails(pool).await?;
+            if queued > 0 {
+                tracing::info!("Queued {} missing thumbnails for generation", queued);
+            }
+            if config.scanner.retry_failed_thumbnails && ctx.step(1, 2) {
+                let reset = db::reset_failed_thumbnails(pool).await?;
+                if reset > 0 {
+                    tracing::info!("Reset {} failed thumbnails for retry", reset);
+                }
+            }
+        }
+        "image-refresh" => {
+            match image_refresh::refresh_images(pool, config.paths.image_cache_dir(), None, None)
+                .await?
+            {
+                Some(summary) => tracing::info!(
+                    "Image refresh queued {} images for {} items",
+                    summary.images_queued,
+                    summary.items_checked
+                ),
+                None => anyhow::bail!("An image refresh is already running"),
+            }
+        }
+        "db-optimize" => {
+            let statements = [
+                "INSERT INTO media_items_fts(media_items_fts) VALUES('optimize')",
+                "PRAGMA optimize",
+                "VACUUM",
+                "PRAGMA wal_checkpoint(TRUNCATE)",
+            ];
+            for (done, sql) in statements.iter().enumerate() {
+                if !ctx.step(done, statements.len()) {
+                    return Ok(());
+                }
+                sqlx::query(sql).execute(pool).await?;
+            }
+        }
+        "search-index" => {
+            // Stopping halfway would leave search missing items, so the
+            // rebuild runs to the end once started
+            db::rebuild_fts_index_with_progress(pool, |done, total| ctx.report(done, total))
+                .await?;
+        }
+        "consistency-check" => {
+            let report = db::check_consistency(pool, config.scanner.consistency_repair).await?;
+            if !report.has_issues() {
+                tracing::info!("Database consistency check passed");
+            } else if report.repaired {
+                tracing::warn!(
+                    "Database consistency check: {} problems repaired, {} orphaned child items, {} items without a library",
+                    report.repairable(),
+                    report.dangling_children,
+                    report.dangling_library_items
+                );
+            } else {
+                tracing::warn!(
+                    "Database consistency check: {} repairable problems (set scanner.consistency_repair to fix them), {} orphaned child items, {} items without a library",
+                    report.repairable(),
+                    report.dangling_children,
+                    report.dangling_library_items
+                );
+            }
+        }
+        "session-cleanup" => {
+            if !ctx.step(0, 3) {
+                return Ok(());
+            }
+            let removed = auth::cleanup_expired_sessions(pool).await?;
+            if removed > 0 {
+                tracing::info!("Cleaned up {} expired sessions", removed);
+            }
+            if !ctx.step(1, 3) {
+                return Ok(());
+            }
+            let removed = crate::api::sessions::cleanup_stale_sessions(pool, 3600).await?;
+            if removed > 0 {
+                tracing::info!("Cleaned up {} stale active sessions", removed);
+            }
+            if !ctx.step(2, 3) {
+                return Ok(());
+            }
+            let removed = share_links::delete_expired(pool).await?;
+            if removed > 0 {
+                tracing::info!("Cleaned up {} expired share links", removed);
+            }
+        }
+        "live-tv-guide" => {
+            let summary = live_tv::refresh(pool, &config.live_tv).await?;
+            tracing::info!(
+                "Live TV guide refreshed: {} channels, {} programs",
+                summary.channels,
+                summary.programs
+            );
+        }
+        _ => anyhow::bail!("Unknown task {}", task.id),
+    }
+    ctx.report(1, 1);
+    Ok(())
+}
+
+/// Start tasks as their triggers fire, until cancelled
+pub async fn run(pool: SqlitePool, config: AppConfig, cancel: CancellationToken) {
+    let scheduler_started = Utc::now();
+    let mut previous_check: Option<DateTime<Local>> = None;
+    tracing::info!("Task scheduler started");
+
+    loop {
+        let now = Local::now();
+        match load_rows(&pool).await {
+            Ok(rows) => {
+                let no_libraries = matches!(
+                    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM libraries")
+                        .fetch_one(&pool)
+                        .await,
+                    Ok(0)
+                );
+                for task in TASKS {
+                    if is_running(task.id) || (no_libraries && task.category == "Library") {
+                        continue;
+                    }
+                    let row = rows.get(task.id);
+                    let last_finished = row
+                        .and_then(|row| row.last_finished_at.as_deref())
+                        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                        .map(|t| t.with_timezone(&Utc));
+                    // Intervals count from startup at the earliest, so a
+                    // restart doesn't set off every task at once
+                    let interval_base =
+                        last_finished.map_or(scheduler_started, |t| t.max(scheduler_started));
+                    let fired = triggers_of(task, row, &config)
+                        .into_iter()
+                        .find(|trigger| trigger.fires(previous_check, now, interval_base));
+                    if let Some(trigger) = fired {
+                        tracing::debug!("Task '{}' triggered by {:?}", task.name, trigger);
+                        start(&pool, &config, task, trigger.max_runtime());
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Failed to load scheduled tasks: {}", e),
+        }
+        previous_check = Some(now);
+
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                tracing::debug!("Task scheduler received shutdown signal");
+                // Running tasks stop after their current step
+                for ctx in RUNNING.lock().unwrap().values() {
+                    ctx.cancel.cancel();
+                }
+                break;
+            }
+            _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_triggers_fire_between_checks() {
+        let at = |day, hour, minute| {
+            Local
+                .with_ymd_and_hms(2024, 5, day, hour, minute, 0)
+                .unwrap()
+        };
+        let base = at(1, 0, 0).with_timezone(&Utc);
+        let daily = TaskTrigger {
+            trigger_type: TriggerType::Daily,
+            time_of_day_ticks: Some(Ticks::from_minutes(3 * 60).0),
+            interval_ticks: None,
+            day_of_week: None,
+            max_runtime_ticks: None,
+        };
+
+        // 2024-05-06 was a Monday
+        assert!(daily.fires(Some(at(6, 2, 59)), at(6, 3, 0), base));
+        assert!(!daily.fires(Some(at(6, 3, 0)), at(6, 3, 1), base));
+        assert!(!daily.fires(None, at(6, 3, 0), base));
+        // A check across midnight still sees the time
+        let late = TaskTrigger {
+            time_of_day_ticks: Some(Ticks::from_minutes(23 * 60 + 59).0),
+            ..daily.clone()
+        };
+        assert!(late.fires(Some(at(5, 23, 58)), at(6, 0, 1), base));
+
+        let weekly = TaskTrigger {
+            trigger_type: TriggerType::Weekly,
+            day_of_week: Some("Monday".to_string()),
+            ..daily.clone()
+        };
+        assert!(weekly.validate().is_ok());
+        assert!(weekly.fires(Some(at(6, 2, 59)), at(6, 3, 0), base));
+        assert!(!weekly.fires(Some(at(7, 2, 59)), at(7, 3, 0), base));
+
+        let interval = TaskTrigger::interval(60);
+        assert!(!interval.fires(Some(at(1, 0, 30)), at(1, 0, 59), base));
+        assert!(interval.fires(Some(at(1, 0, 59)), at(1, 1, 0), base));
+
+        assert!(TaskTrigger::startup().fires(None, at(1, 0, 0), base));
+        assert!(!TaskTrigger::startup().fires(Some(at(1, 0, 0)), at(1, 0, 1), base));
+
+        assert!(TaskTrigger {
+            interval_ticks: None,
+            ..interval
+        }
+        .validate()
+        .is_err());
+    }
+}
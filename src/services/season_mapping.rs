@@ -0,0 +1,343 @@
+// Season mappings between disk numbering and provider seasons
+//
+// Split-cour anime is often one continuous run on disk (episodes 1-24 in one
+// folder) while providers list two seasons of 12, or the other way round:
+// "Part 2" folders numbered 13-24 that a provider calls season 2 episodes 1-12.
+// A mapping rule moves a range of disk episodes to a provider season with an
+// episode offset. Mapped numbers are stored in the display_* columns (shown and
+// sorted by the API) and used for episode metadata lookups.
+//
+// Rules are either set by an admin (Manual) or suggested from TMDB season
+// episode counts and applied automatically when confidence is high (Auto).
+// Auto rules are recomputed on refresh; manual rules are never touched.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+
+use super::tmdb::TmdbClient;
+
+/// Suggestions at or above this confidence are applied without an admin
+pub const AUTO_APPLY_CONFIDENCE: f64 = 0.9;
+
+/// One rule: disk episodes `first_episode..=last_episode` of `disk_season`
+/// become provider season `provider_season`, episode number minus `episode_offset`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, sqlx::FromRow)]
+#[serde(rename_all = "PascalCase")]
+pub struct SeasonMapping {
+    pub disk_season: i32,
+    pub first_episode: i32,
+    /// None means "to the end of the disk season"
+    #[serde(default)]
+    pub last_episode: Option<i32>,
+    pub provider_season: i32,
+    #[serde(default)]
+    pub episode_offset: i32,
+}
+
+impl SeasonMapping {
+    fn covers(&self, season: i32, episode: i32) -> bool {
+        season == self.disk_season
+            && episode >= self.first_episode
+            && self.last_episode.is_none_or(|last| episode <= last)
+    }
+}
+
+/// A rule proposed from provider episode counts
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SuggestedMapping {
+    #[serde(flatten)]
+    pub mapping: SeasonMapping,
+    /// 0.0 - 1.0, how well the disk numbering fits the provider seasons
+    pub confidence: f64,
+}
+
+/// Provider (season, episode) for a disk episode, if a rule covers it
+pub fn map_episode(mappings: &[SeasonMapping], season: i32, episode: i32) -> Option<(i32, i32)> {
+    mappings
+        .iter()
+        .find(|m| m.covers(season, episode))
+        .map(|m| (m.provider_season, episode - m.episode_offset))
+}
+
+/// Propose rules where disk numbering and provider season sizes disagree
+///
+/// `disk` maps each disk season to its (lowest, highest) episode number,
+/// `provider_counts` maps provider seasons to their episode counts.
+pub fn suggest_mappings(
+    disk: &BTreeMap<i32, (i32, i32)>,
+    provider_counts: &HashMap<i32, i32>,
+) -> Vec<SuggestedMapping> {
+    let mut suggestions = Vec::new();
+
+    for (&season, &(first, last)) in disk {
+        if season == 0 {
+            continue;
+        }
+        let Some(&count) = provider_counts.get(&season).filter(|c| **c > 0) else {
+            continue;
+        };
+
+        if first > count {
+            // Numbering continues from the previous season ("Part 2" holding 13-24)
+            let offset = first - 1;
+            if last - offset > count {
+                continue;
+            }
+            let continues_previous = provider_counts.get(&(season - 1)) == Some(&offset);
+            suggestions.push(SuggestedMapping {
+                mapping: SeasonMapping {
+                    disk_season: season,
+                    first_episode: first,
+                    last_episode: None,
+                    provider_season: season,
+                    episode_offset: offset,
+                },
+                confidence: if continues_previous { 0.95 } else { 0.6 },
+            });
+        } else if last > count {
+            // One disk season spans this provider season and the next (1-24 vs 12 + 12)
+            let Some(&next_count) = provider_counts.get(&(season + 1)) else {
+                continue;
+            };
+            if disk.contains_key(&(season + 1)) || last - count > next_count {
+                continue;
+            }
+            suggestions.push(SuggestedMapping {
+                mapping: SeasonMapping {
+                    disk_season: season,
+                    first_episode: count + 1,
+                    last_episode: None,
+                    provider_season: season + 1,
+                    episode_offset: count,
+                },
+                confidence: if last == count + next_count {
+                    0.95
+                } else {
+                    0.6
+                },
+            });
+        }
+    }
+
+    suggestions
+}
+
+/// Rules stored for a series, in match order
+pub async fn get_mappings(pool: &SqlitePool, series_id: &str) -> Result<Vec<SeasonMapping>> {
+    let mappings = sqlx::query_as(
+        "SELECT disk_season, first_episode, last_episode, provider_season, episode_offset
+         FROM series_season_mappings WHERE item_id = ? ORDER BY disk_season, first_episode",
+    )
+    .bind(series_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(mappings)
+}
+
+/// Whether the series has rules an admin set (which suppress automatic ones)
+pub async fn has_manual_mappings(pool: &SqlitePool, series_id: &str) -> Result<bool> {
+    let count: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM series_season_mappings WHERE item_id = ? AND source = 'Manual'",
+    )
+    .bind(series_id)
+    .fetch_one(pool)
+    .await?;
+    Ok(count > 0)
+}
+
+/// Replace all rules of a series
+pub async fn set_mappings(
+    pool: &SqlitePool,
+    series_id: &str,
+    mappings: &[SeasonMapping],
+    source: &str,
+) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM series_season_mappings WHERE item_id = ?")
+        .bind(series_id)
+        .execute(&mut *tx)
+        .await?;
+    for m in mappings {
+        sqlx::query(
+            "INSERT OR REPLACE INTO series_season_mappings
+             (item_id, disk_season, first_episode, last_episode, provider_season, episode_offset, source)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(series_id)
+        .bind(m.disk_season)
+        .bind(m.first_episode)
+        .bind(m.last_episode)
+        .bind(m.provider_season)
+        .bind(m.episode_offset)
+        .bind(source)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit()
+        .await
+        .context("Failed to save season mappings")?;
+    Ok(())
+}
+
+/// Renumber a series' episodes from its rules
+///
+/// A selected TMDB episode group owns the display numbers, so series with one
+/// are left alone. Returns the number of renumbered episodes.
+pub async fn apply_season_mappings(pool: &SqlitePool, series_id: &str) -> Result<usize> {
+    let group_id: Option<String> =
+        sqlx::query_scalar("SELECT episode_group_id FROM media_items WHERE id = ?")
+            .bind(series_id)
+            .fetch_optional(pool)
+            .await?
+            .flatten();
+    if group_id.is_some() {
+        return Ok(0);
+    }
+
+    let mappings = get_mappings(pool, series_id).await?;
+    let episodes: Vec<(String, Option<i32>, Option<i32>)> = sqlx::query_as(
+        "SELECT id, parent_index_number, index_number FROM media_items WHERE parent_id = ? AND item_type = 'Episode'",
+    )
+    .bind(series_id)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tx = pool.begin().await?;
+    let mut renumbered = 0;
+    for (id, season, episode) in episodes {
+        let mapped = episode.and_then(|e| map_episode(&mappings, season.unwrap_or(1), e));
+        sqlx::query(
+            "UPDATE media_items SET display_parent_index_number = ?, display_index_number = ? WHERE id = ?",
+        )
+        .bind(mapped.map(|m| m.0))
+        .bind(mapped.map(|m| m.1))
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+        if mapped.is_some() {
+            renumbered += 1;
+        }
+    }
+    tx.commit().await.context("Failed to renumber episodes")?;
+    crate::scanner::seasons::sync_series(pool, series_id).await?;
+
+    Ok(renumbered)
+}
+
+/// Lowest and highest disk episode number per season of a series
+async fn disk_season_ranges(
+    pool: &SqlitePool,
+    series_id: &str,
+) -> Result<BTreeMap<i32, (i32, i32)>> {
+    let rows: Vec<(i32, i32, i32)> = sqlx::query_as(
+        "SELECT COALESCE(parent_index_number, 1), MIN(index_number), MAX(index_number)
+         FROM media_items WHERE parent_id = ? AND item_type = 'Episode' AND index_number IS NOT NULL
+         GROUP BY COALESCE(parent_index_number, 1)",
+    )
+    .bind(series_id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|(s, min, max)| (s, (min, max)))
+        .collect())
+}
+
+/// Suggestions for a series from its TMDB season sizes (empty without a TMDB ID)
+pub async fn suggest_for_series(
+    pool: &SqlitePool,
+    tmdb: &TmdbClient,
+    series_id: &str,
+) -> Result<Vec<SuggestedMapping>> {
+    let tmdb_id: Option<String> =
+        sqlx::query_scalar("SELECT tmdb_id FROM media_items WHERE id = ?")
+            .bind(series_id)
+            .fetch_optional(pool)
+            .await?
+            .flatten();
+    let Some(tmdb_id) = tmdb_id.and_then(|t| t.parse::<i64>().ok()) else {
+        return Ok(Vec::new());
+    };
+
+    let details = tmdb.get_tv_details(tmdb_id).await?;
+    let provider_counts: HashMap<i32, i32> = details
+        .seasons
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|s| Some((s.season_number, s.episode_count?)))
+        .collect();
+
+    let disk = disk_season_ranges(pool, series_id).await?;
+    Ok(suggest_mappings(&disk, &provider_counts))
+}
+
+/// Recompute automatic rules for a series and renumber (skipped when an admin set rules)
+pub async fn auto_map_series(
+    pool: &SqlitePool,
+    tmdb: Option<&TmdbClient>,
+    series_id: &str,
+) -> Result<()> {
+    if let (Some(tmdb), false) = (tmdb, has_manual_mappings(pool, series_id).await?) {
+        let confident: Vec<SeasonMapping> = suggest_for_series(pool, tmdb, series_id)
+            .await?
+            .into_iter()
+            .filter(|s| s.confidence >= AUTO_APPLY_CONFIDENCE)
+            .map(|s| s.mapping)
+            .collect();
+        if !confident.is_empty() {
+            tracing::info!(
+                "Applying {} automatic season mapping(s) to series {}",
+                confident.len(),
+                series_id
+            );
+        }
+        set_mappings(pool, series_id, &confident, "Auto").await?;
+    }
+
+    apply_season_mappings(pool, series_id).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_split_of_continuous_season() {
+        // 24 episodes on disk in season 1, provider has 12 + 12
+        let disk = BTreeMap::from([(1, (1, 24))]);
+        let provider = HashMap::from([(1, 12), (2, 12)]);
+        let suggestions = suggest_mappings(&disk, &provider);
+
+        assert_eq!(suggestions.len(), 1);
+        assert!(suggestions[0].confidence >= AUTO_APPLY_CONFIDENCE);
+        let mappings: Vec<SeasonMapping> = suggestions.into_iter().map(|s| s.mapping).collect();
+        assert_eq!(map_episode(&mappings, 1, 12), None);
+        assert_eq!(map_episode(&mappings, 1, 13), Some((2, 1)));
+        assert_eq!(map_episode(&mappings, 1, 24), Some((2, 12)));
+    }
+
+    #[test]
+    fn test_suggest_offset_for_continued_numbering() {
+        // "Part 2" folder holds episodes 13-24, provider numbers season 2 from 1
+        let disk = BTreeMap::from([(1, (1, 12)), (2, (13, 24))]);
+        let provider = HashMap::from([(1, 12), (2, 12)]);
+        let suggestions = suggest_mappings(&disk, &provider);
+
+        assert_eq!(suggestions.len(), 1);
+        assert!(suggestions[0].confidence >= AUTO_APPLY_CONFIDENCE);
+        let mappings: Vec<SeasonMapping> = suggestions.into_iter().map(|s| s.mapping).collect();
+        assert_eq!(map_episode(&mappings, 2, 12), None);
+        assert_eq!(map_episode(&mappings, 2, 13), Some((2, 1)));
+    }
+
+    #[test]
+    fn test_no_suggestion_when_numbering_matches() {
+        let disk = BTreeMap::from([(1, (1, 12)), (2, (1, 12))]);
+        let provider = HashMap::from([(1, 12), (2, 12)]);
+        assert!(suggest_mappings(&disk, &provider).is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+// Server ID
+//
+// Clients key what they cache (saved logins, downloads, offline libraries) by
+// the ServerId of /System/Info, the authentication result and every item, so
+// it has to stay the same across restarts and differ between servers. It used
+// to be a fixed string shared by every installation. An ID is now generated
+// the first time a database is used, stored in the server_info table, and
+// loaded at startup; a restored database keeps the ID clients know it by.
+
+use anyhow::Result;
+use sqlx::SqlitePool;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+static SERVER_ID: OnceLock<String> = OnceLock::new();
+
+/// A new ID in Jellyfin's format (32 lowercase hex digits, no dashes)
+fn new_id() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Load the server's ID, generating and storing it on first run
+pub async fn load(pool: &SqlitePool) -> Result<()> {
+    sqlx::query("INSERT OR IGNORE INTO server_info (id, server_id, created_at) VALUES (1, ?, ?)")
+        .bind(new_id())
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+    let id: String = sqlx::query_scalar("SELECT server_id FROM server_info WHERE id = 1")
+        .fetch_one(pool)
+        .await?;
+    tracing::debug!("Server ID: {}", id);
+    let _ = SERVER_ID.set(id);
+    Ok(())
+}
+
+/// The server's ID
+///
+/// Only a process that never loaded one (tests) gets a random ID of its own.
+pub fn get() -> &'static str {
+    SERVER_ID.get_or_init(new_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_server_id_format_and_stability() {
+        let id = new_id();
+        assert_eq!(id.len(), 32);
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)));
+        assert_ne!(id, new_id());
+        assert_eq!(get(), get());
+    }
+}
@@ -0,0 +1,220 @@
+// Share links
+//
+// A share link lets someone without an account stream one movie or episode
+// until the link expires or its owner (or an admin) revokes it. The token in
+// the link is the only credential, so it's random and links are checked on
+// every request. Links can cap the bitrate, and the server policy can cap all
+// of them; sources above the cap are transcoded to H.264/AAC with ffmpeg
+// instead of being served as is.
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use std::path::Path;
+
+use crate::config::SharingConfig;
+
+/// Audio bitrate of transcoded shared streams
+const TRANSCODE_AUDIO_BITRATE: u32 = 128_000;
+
+/// A share link
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ShareLink {
+    pub token: String,
+    pub item_id: String,
+    pub user_id: String,
+    pub created_at: String,
+    pub expires_at: String,
+    /// Bitrate cap in bits/s chosen by the link's owner
+    pub max_bitrate: Option<i64>,
+    pub view_count: i64,
+    pub last_viewed_at: Option<String>,
+}
+
+impl ShareLink {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        DateTime::parse_from_rfc3339(&self.expires_at).map_or(true, |expires| expires <= now)
+    }
+}
+
+/// The bitrate shared streams of a link are held to: the lower of the link's
+/// cap and the policy's, None when neither caps it
+pub fn effective_bitrate(link_max: Option<i64>, policy: &SharingConfig) -> Option<u32> {
+    let link_max = link_max
+        .filter(|b| *b > 0)
+        .map(|b| b.min(u32::MAX as i64) as u32);
+    let policy_max = Some(policy.max_bitrate).filter(|b| *b > 0);
+    match (link_max, policy_max) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Create a link to an item, valid for `lifetime_hours` (capped by the policy)
+pub async fn create(
+    pool: &SqlitePool,
+    policy: &SharingConfig,
+    item_id: &str,
+    user_id: &str,
+    lifetime_hours: u64,
+    max_bitrate: Option<i64>,
+) -> Result<ShareLink> {
+    let now = Utc::now();
+    let hours = lifetime_hours.clamp(1, policy.max_lifetime_hours.max(1));
+    let link = ShareLink {
+        token: uuid::Uuid::new_v4().simple().to_string(),
+        item_id: item_id.to_string(),
+        user_id: user_id.to_string(),
+        created_at: now.to_rfc3339(),
+        expires_at: (now + chrono::Duration::hours(hours as i64)).to_rfc3339(),
+        max_bitrate: max_bitrate.filter(|b| *b > 0),
+        view_count: 0,
+        last_viewed_at: None,
+    };
+
+    sqlx::query(
+        "INSERT INTO share_links (token, item_id, user_id, created_at, expires_at, max_bitrate)
+         VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&link.token)
+    .bind(&link.item_id)
+    .bind(&link.user_id)
+    .bind(&link.created_at)
+    .bind(&link.expires_at)
+    .bind(link.max_bitrate)
+    .execute(pool)
+    .await?;
+    Ok(link)
+}
+
+/// A user's links (all links for None), newest first
+pub async fn list(pool: &SqlitePool, user_id: Option<&str>) -> Result<Vec<ShareLink>> {
+    Ok(sqlx::query_as(
+        "SELECT * FROM share_links WHERE ?1 IS NULL OR user_id = ?1 ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Look up a link by token, expired or not
+pub async fn get(pool: &SqlitePool, token: &str) -> Result<Option<ShareLink>> {
+    Ok(sqlx::query_as("SELECT * FROM share_links WHERE token = ?")
+        .bind(token)
+        .fetch_optional(pool)
+        .await?)
+}
+
+/// Count a view of a shared item
+pub async fn record_view(pool: &SqlitePool, token: &str) -> Result<()> {
+    sqlx::query(
+        "UPDATE share_links SET view_count = view_count + 1, last_viewed_at = ? WHERE token = ?",
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(token)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Revoke a link, returning whether it existed
+pub async fn revoke(pool: &SqlitePool, token: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM share_links WHERE token = ?")
+        .bind(token)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Delete links that have expired
+pub async fn delete_expired(pool: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM share_links WHERE expires_at <= ?")
+        .bind(Utc::now().to_rfc3339())
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+/// ffmpeg arguments that transcode a video to fragmented MP4 (H.264/AAC) at a
+/// total bitrate, streamed to stdout so browsers can play it as it arrives
+pub fn transcode_args(video_path: &Path, bitrate: u32) -> Vec<String> {
+    let video_bitrate = bitrate
+        .saturating_sub(TRANSCODE_AUDIO_BITRATE)
+        .max(bitrate / 2);
+    let mut args: Vec<String> = vec![
+        "-hide_banner".into(),
+        "-loglevel".into(),
+        "error".into(),
+        "-i".into(),
+        video_path.to_string_lossy().into_owned(),
+    ];
+    args.extend(
+        [
+            "-map", "0:v:0", "-map", "0:a:0?", "-sn", "-dn", "-c:v", "libx264", "-preset",
+            "veryfast",
+        ]
+        .map(String::from),
+    );
+    args.extend([
+        "-b:v".to_string(),
+        video_bitrate.to_string(),
+        "-maxrate".to_string(),
+        video_bitrate.to_string(),
+        "-bufsize".to_string(),
+        (video_bitrate * 2).to_string(),
+        "-c:a".to_string(),
+        "aac".to_string(),
+        "-ac".to_string(),
+        "2".to_string(),
+        "-b:a".to_string(),
+        TRANSCODE_AUDIO_BITRATE.min(bitrate / 2).to_string(),
+    ]);
+    args.extend(
+        [
+            "-movflags",
+            "frag_keyframe+empty_moov+default_base_moof",
+            "-f",
+            "mp4",
+            "pipe:1",
+        ]
+        .map(String::from),
+    );
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitrate_caps_and_transcode_args() {
+        let mut policy = SharingConfig::default();
+        assert_eq!(effective_bitrate(None, &policy), None);
+        assert_eq!(effective_bitrate(Some(4_000_000), &policy), Some(4_000_000));
+
+        policy.max_bitrate = 2_000_000;
+        assert_eq!(effective_bitrate(None, &policy), Some(2_000_000));
+        assert_eq!(effective_bitrate(Some(8_000_000), &policy), Some(2_000_000));
+        assert_eq!(effective_bitrate(Some(1_000_000), &policy), Some(1_000_000));
+        assert_eq!(effective_bitrate(Some(0), &policy), Some(2_000_000));
+
+        let args = transcode_args(Path::new("/m/Movie.mkv"), 2_000_000).join(" ");
+        assert!(args.contains("-i /m/Movie.mkv"));
+        assert!(args.contains("-b:v 1872000 -maxrate 1872000 -bufsize 3744000"));
+        assert!(args.ends_with("-f mp4 pipe:1"));
+
+        let link = ShareLink {
+            token: "t".to_string(),
+            item_id: "i".to_string(),
+            user_id: "u".to_string(),
+            created_at: "2024-06-01T00:00:00+00:00".to_string(),
+            expires_at: "2024-06-02T00:00:00+00:00".to_string(),
+            max_bitrate: None,
+            view_count: 0,
+            last_viewed_at: None,
+        };
+        let at = |t: &str| DateTime::parse_from_rfc3339(t).unwrap().with_timezone(&Utc);
+        assert!(!link.is_expired(at("2024-06-01T23:59:59Z")));
+        assert!(link.is_expired(at("2024-06-02T00:00:00Z")));
+    }
+}
@@ -0,0 +1,80 @@
+// .strm stream files
+//
+// A .strm file is a text file holding the URL of a remote stream (an HTTP
+// server, an RTSP camera, ...). The scanner adds it like any other video file
+// and stores the URL on the item; playback then points clients at the URL
+// instead of a local file. Remote streams aren't probed with ffprobe or used
+// for thumbnails, so scans of hybrid local/remote libraries stay fast.
+
+use anyhow::{Context, Result};
+use reqwest::Url;
+use std::path::Path;
+
+/// Largest .strm file read; they only hold a URL
+const MAX_STRM_BYTES: u64 = 64 * 1024;
+
+/// Whether a path is a .strm file
+pub fn is_strm_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("strm"))
+}
+
+/// The stream URL in a .strm file's contents: the first line that isn't blank
+/// or a comment
+pub fn parse_url(contents: &str) -> Option<String> {
+    let line = contents
+        .lines()
+        .map(|l| l.trim().trim_start_matches('\u{feff}'))
+        .find(|l| !l.is_empty() && !l.starts_with('#'))?;
+    let url = Url::parse(line).ok()?;
+    protocol(&url).map(|_| url.to_string())
+}
+
+/// Jellyfin MediaProtocol of a stream URL, None for unsupported schemes
+pub fn protocol(url: &Url) -> Option<&'static str> {
+    match url.scheme() {
+        "http" | "https" => Some("Http"),
+        "rtsp" => Some("Rtsp"),
+        "rtmp" => Some("Rtmp"),
+        "udp" => Some("Udp"),
+        "ftp" => Some("Ftp"),
+        _ => None,
+    }
+}
+
+/// Read the stream URL of a .strm file
+pub async fn read_url(path: &Path) -> Result<String> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    if metadata.len() > MAX_STRM_BYTES {
+        anyhow::bail!("{} is too large for a .strm file", path.display());
+    }
+    let contents = tokio::fs::read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_url(&contents).with_context(|| format!("No supported stream URL in {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            parse_url("\u{feff}# Channel 1\n\n  https://example.com/live/1.m3u8  \n").as_deref(),
+            Some("https://example.com/live/1.m3u8")
+        );
+        assert_eq!(
+            parse_url("rtsp://camera.local:554/stream").as_deref(),
+            Some("rtsp://camera.local:554/stream")
+        );
+        assert_eq!(parse_url("file:///etc/passwd"), None);
+        assert_eq!(parse_url("not a url"), None);
+        assert_eq!(parse_url(""), None);
+        assert!(is_strm_file(Path::new("/tv/Show/S01E01.STRM")));
+        assert!(!is_strm_file(Path::new("/tv/Show/S01E01.mkv")));
+    }
+}
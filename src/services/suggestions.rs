@@ -0,0 +1,265 @@
+// Personalized suggestions from watch history
+//
+// A user's taste is the genres and studios of the titles they watched (episodes
+// count for their series) and favorited. Unwatched movies and series are ranked
+// by how well they match that taste, plus their community rating, so users
+// without any history still get the best-rated titles. Rankings are cached per
+// user for the rest of the (UTC) day.
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use sqlx::SqlitePool;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::db::item_query::ItemQuery;
+
+const GENRE_WEIGHT: f64 = 0.5;
+const STUDIO_WEIGHT: f64 = 0.2;
+const RATING_WEIGHT: f64 = 0.3;
+
+/// Rating assumed for items without a community rating
+const DEFAULT_RATING: f64 = 5.0;
+
+/// Ranked suggestions kept per user
+const MAX_SUGGESTIONS: usize = 200;
+
+/// A suggested title, best match first
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub item_id: String,
+    pub item_type: String,
+}
+
+type CachedRanking = (NaiveDate, Arc<Vec<Suggestion>>);
+
+static CACHE: LazyLock<Mutex<HashMap<String, CachedRanking>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// How much a user likes each genre and studio, scaled so the favorite is 1.0
+#[derive(Debug, Default)]
+pub struct TasteProfile {
+    genres: HashMap<String, f64>,
+    studios: HashMap<String, f64>,
+}
+
+fn normalize(counts: Vec<(String, i64)>) -> HashMap<String, f64> {
+    let max = counts.iter().map(|(_, c)| *c).max().unwrap_or(0);
+    if max <= 0 {
+        return HashMap::new();
+    }
+    counts
+        .into_iter()
+        .map(|(name, count)| (name, count as f64 / max as f64))
+        .collect()
+}
+
+impl TasteProfile {
+    /// Build from how many watched/favorited titles had each genre and studio
+    pub fn from_counts(genres: Vec<(String, i64)>, studios: Vec<(String, i64)>) -> Self {
+        Self {
+            genres: normalize(genres),
+            studios: normalize(studios),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.genres.is_empty() && self.studios.is_empty()
+    }
+
+    /// Score a candidate between 0 and 1
+    ///
+    /// The genre match averages the item's two best-liked genres, so a title
+    /// sharing one favorite genre ranks below one sharing two. Without any
+    /// history only the rating counts.
+    pub fn score(&self, genres: &[String], studios: &[String], rating: Option<f64>) -> f64 {
+        let rating = (rating.unwrap_or(DEFAULT_RATING) / 10.0).clamp(0.0, 1.0);
+        if self.is_empty() {
+            return rating;
+        }
+
+        let mut genre_matches: Vec<f64> = genres
+            .iter()
+            .map(|g| self.genres.get(g).copied().unwrap_or(0.0))
+            .collect();
+        genre_matches.sort_by(|a, b| b.total_cmp(a));
+        let genre = genre_matches.iter().take(2).sum::<f64>() / 2.0;
+
+        let studio = studios
+            .iter()
+            .filter_map(|s| self.studios.get(s).copied())
+            .fold(0.0, f64::max);
+
+        GENRE_WEIGHT * genre + STUDIO_WEIGHT * studio + RATING_WEIGHT * rating
+    }
+}
+
+/// Titles (movies, and series through their episodes) the user watched or favorited
+const TASTE_TITLES_SQL: &str = "
+    SELECT COALESCE(m.parent_id, m.id) AS title_id
+    FROM playback_progress p JOIN media_items m ON m.id = p.item_id
+    WHERE p.user_id = ? AND (p.played = 1 OR p.play_count > 0)
+    UNION ALL
+    SELECT COALESCE(m.parent_id, m.id)
+    FROM user_favorites f JOIN media_items m ON m.id = f.item_id
+    WHERE f.user_id = ?";
+
+/// Build a user's taste profile; favorites that were also watched count twice
+pub async fn build_profile(pool: &SqlitePool, user_id: &str) -> Result<TasteProfile> {
+    let genres: Vec<(String, i64)> = sqlx::query_as(&format!(
+        "SELECT g.name, COUNT(*) FROM ({}) t
+         JOIN item_genres ig ON ig.item_id = t.title_id
+         JOIN genres g ON g.id = ig.genre_id
+         GROUP BY g.name",
+        TASTE_TITLES_SQL
+    ))
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    let studios: Vec<(String, i64)> = sqlx::query_as(&format!(
+        "SELECT s.name, COUNT(*) FROM ({}) t
+         JOIN item_studios its ON its.item_id = t.title_id
+         JOIN studios s ON s.id = its.studio_id
+         GROUP BY s.name",
+        TASTE_TITLES_SQL
+    ))
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(TasteProfile::from_counts(genres, studios))
+}
+
+/// Names linked to top-level movies and series, by item ID
+async fn names_by_item(pool: &SqlitePool, sql: &str) -> Result<HashMap<String, Vec<String>>> {
+    let rows: Vec<(String, String)> = sqlx::query_as(sql).fetch_all(pool).await?;
+    let mut map: HashMap<String, Vec<String>> = HashMap::new();
+    for (item_id, name) in rows {
+        map.entry(item_id).or_default().push(name);
+    }
+    Ok(map)
+}
+
+async fn rank(pool: &SqlitePool, user_id: &str) -> Result<Vec<Suggestion>> {
+    let profile = build_profile(pool, user_id).await?;
+
+    let candidates = ItemQuery::new()
+        .include_types(&["Movie", "Series"])
+        .top_level()
+        .visible_to(user_id)
+        .fetch_all(pool)
+        .await?;
+
+    // Anything started counts as seen: series in progress belong in Next Up
+    let seen: HashSet<String> = sqlx::query_scalar(
+        "SELECT DISTINCT COALESCE(m.parent_id, m.id)
+         FROM playback_progress p JOIN media_items m ON m.id = p.item_id
+         WHERE p.user_id = ? AND (p.played = 1 OR p.position_ticks > 0)",
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    let genres = names_by_item(
+        pool,
+        "SELECT ig.item_id, g.name FROM item_genres ig
+         JOIN genres g ON g.id = ig.genre_id
+         JOIN media_items m ON m.id = ig.item_id
+         WHERE m.parent_id IS NULL",
+    )
+    .await?;
+    let studios = names_by_item(
+        pool,
+        "SELECT its.item_id, s.name FROM item_studios its
+         JOIN studios s ON s.id = its.studio_id
+         JOIN media_items m ON m.id = its.item_id
+         WHERE m.parent_id IS NULL",
+    )
+    .await?;
+
+    let mut scored: Vec<(f64, Suggestion)> = candidates
+        .into_iter()
+        .filter(|item| !seen.contains(&item.id))
+        .map(|item| {
+            let score = profile.score(
+                genres.get(&item.id).map(Vec::as_slice).unwrap_or_default(),
+                studios.get(&item.id).map(Vec::as_slice).unwrap_or_default(),
+                item.community_rating,
+            );
+            (
+                score,
+                Suggestion {
+                    item_id: item.id,
+                    item_type: item.item_type,
+                },
+            )
+        })
+        .collect();
+
+    scored.sort_by(|a, b| {
+        b.0.total_cmp(&a.0)
+            .then_with(|| a.1.item_id.cmp(&b.1.item_id))
+    });
+    scored.truncate(MAX_SUGGESTIONS);
+    Ok(scored.into_iter().map(|(_, s)| s).collect())
+}
+
+/// Today's ranked suggestions for a user, computed on first use each day
+pub async fn suggestions_for_user(
+    pool: &SqlitePool,
+    user_id: &str,
+) -> Result<Arc<Vec<Suggestion>>> {
+    let today = chrono::Utc::now().date_naive();
+    if let Some((date, ranking)) = CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(user_id) {
+        if *date == today {
+            return Ok(ranking.clone());
+        }
+    }
+
+    let ranking = Arc::new(rank(pool, user_id).await?);
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.retain(|_, (date, _)| *date == today);
+    cache.insert(user_id.to_string(), (today, ranking.clone()));
+    Ok(ranking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_score_prefers_watched_genres_over_rating() {
+        let profile = TasteProfile::from_counts(
+            vec![("Anime".to_string(), 4), ("Comedy".to_string(), 2)],
+            vec![("MAPPA".to_string(), 1)],
+        );
+
+        let matching = profile.score(&names(&["Anime", "Comedy"]), &names(&["MAPPA"]), Some(7.0));
+        let one_genre = profile.score(&names(&["Anime", "Horror"]), &[], Some(7.0));
+        let unrelated = profile.score(&names(&["Horror"]), &[], Some(9.5));
+
+        assert!(matching > one_genre);
+        assert!(one_genre > unrelated);
+    }
+
+    #[test]
+    fn test_score_without_history_ranks_by_rating() {
+        let profile = TasteProfile::default();
+        assert!(profile.is_empty());
+
+        let high = profile.score(&names(&["Drama"]), &[], Some(8.8));
+        let unrated = profile.score(&names(&["Drama"]), &[], None);
+        let low = profile.score(&[], &[], Some(3.0));
+
+        assert!(high > unrated && unrated > low);
+    }
+}
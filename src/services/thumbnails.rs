@@ -0,0 +1,347 @@
+// Background thumbnail generation
+//
+// Episodes and movies without artwork are queued in thumbnail_queue (by the
+// scanner and the missing thumbnail checker), and a pool of workers grabs a
+// frame from each with ffmpeg. `thumbnails.workers` bounds how many run at
+// once, and `thumbnails.hwaccel` has ffmpeg decode on a GPU (VAAPI, NVDEC or
+// Quick Sync); a file the hardware can't decode is retried in software.
+// Progress since startup is kept in memory for GET /Library/Thumbnails.
+//
+// Thumbnails go to the image cache, or with `thumbnails.location = "media"`
+// beside the video (`<name>-thumb.jpg`, `<name>-poster.jpg` for movies) so
+// they travel and are backed up with the files. A folder the server can't
+// write to (a read-only mount) gets its thumbnails in the cache instead, and
+// an image already beside the video under that name is used as it is.
+
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::config::{HwAccel, ThumbnailConfig, ThumbnailLocation};
+use crate::db;
+use crate::events::{self, ServerEvent};
+use crate::services::mediainfo;
+use crate::time::Ticks;
+
+/// Width of generated thumbnails
+const THUMBNAIL_WIDTH: u32 = 480;
+
+/// Queue entries fetched per worker in each round
+const BATCH_PER_WORKER: usize = 4;
+
+/// Counters since the server started
+#[derive(Debug, Default)]
+struct Counters {
+    in_progress: AtomicU64,
+    completed: AtomicU64,
+    failed: AtomicU64,
+}
+
+static COUNTERS: LazyLock<Counters> = LazyLock::new(Counters::default);
+
+/// When the current run of work started, for the rate
+static BUSY_SINCE: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Thumbnail generation progress
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Progress {
+    /// Queue entries waiting for a worker
+    pub pending: i64,
+    /// Thumbnails being extracted right now
+    pub in_progress: u64,
+    /// Thumbnails generated since startup
+    pub completed: u64,
+    /// Failed attempts since startup (entries are retried twice)
+    pub failed: u64,
+    /// Queue entries given up on
+    pub given_up: i64,
+    /// Thumbnails per minute while the queue has been busy
+    pub per_minute: Option<f64>,
+    pub workers: usize,
+    pub hardware_acceleration: HwAccel,
+}
+
+/// Number of workers for the config (0 = half the CPU cores, at most 4)
+pub fn worker_count(config: &ThumbnailConfig) -> usize {
+    if config.workers > 0 {
+        return config.workers;
+    }
+    let cores = std::thread::available_parallelism()
+        .map(|p| p.get())
+        .unwrap_or(4);
+    (cores / 2).clamp(1, 4)
+}
+
+/// ffmpeg input options for the configured hardware decoder
+pub fn decode_args(config: &ThumbnailConfig) -> Vec<String> {
+    let args: &[&str] = match config.hwaccel {
+        HwAccel::None => &[],
+        HwAccel::Vaapi => &["-hwaccel", "vaapi", "-hwaccel_device", &config.vaapi_device],
+        HwAccel::Nvenc => &["-hwaccel", "cuda"],
+        HwAccel::Qsv => &["-hwaccel", "qsv"],
+    };
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// Current progress, with queue counts read from the database
+pub async fn progress(pool: &SqlitePool, config: &ThumbnailConfig) -> anyhow::Result<Progress> {
+    let (pending, given_up): (i64, i64) = sqlx::query_as(
+        "SELECT COALESCE(SUM(status = 'pending'), 0), COALESCE(SUM(status = 'failed'), 0)
+         FROM thumbnail_queue",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let completed = COUNTERS.completed.load(Ordering::Relaxed);
+    let per_minute = BUSY_SINCE.lock().unwrap().map(|since| {
+        let minutes = since.elapsed().as_secs_f64() / 60.0;
+        if minutes > 0.0 {
+            completed as f64 / minutes
+        } else {
+            0.0
+        }
+    });
+
+    Ok(Progress {
+        pending,
+        in_progress: COUNTERS.in_progress.load(Ordering::Relaxed),
+        completed,
+        failed: COUNTERS.failed.load(Ordering::Relaxed),
+        given_up,
+        per_minute,
+        workers: worker_count(config),
+        hardware_acceleration: config.hwaccel,
+    })
+}
+
+/// Work through the thumbnail queue until cancelled
+pub async fn run(
+    pool: SqlitePool,
+    image_cache_dir: PathBuf,
+    config: ThumbnailConfig,
+    cancel: CancellationToken,
+) {
+    let workers = worker_count(&config);
+    let decode_args = decode_args(&config);
+    tracing::info!(
+        "Background thumbnail generator started ({} workers, hwaccel: {:?})",
+        workers,
+        config.hwaccel
+    );
+
+    loop {
+        if cancel.is_cancelled() {
+            tracing::debug!("Thumbnail generator received shutdown signal");
+            break;
+        }
+
+        let pending = db::get_pending_thumbnails(&pool, (workers * BATCH_PER_WORKER) as i32)
+            .await
+            .unwrap_or_default();
+        if pending.is_empty() {
+            *BUSY_SINCE.lock().unwrap() = None;
+            tokio::select! {
+                _ = cancel.cancelled() => {}
+                _ = tokio::time::sleep(Duration::from_secs(10)) => {}
+            }
+            continue;
+        }
+        BUSY_SINCE.lock().unwrap().get_or_insert_with(Instant::now);
+
+        // A batch is finished before the next is fetched, so no entry is
+        // picked up by two workers
+        stream::iter(pending)
+            .for_each_concurrent(workers, |thumb| {
+                let (pool, image_cache_dir, decode_args, cancel) =
+                    (&pool, &image_cache_dir, &decode_args, &cancel);
+                let (location, scene_detection) = (config.location, config.scene_detection);
+                async move {
+                    if cancel.is_cancelled() {
+                        return;
+                    }
+                    COUNTERS.in_progress.fetch_add(1, Ordering::Relaxed);
+                    let generated = generate(
+                        pool,
+                        image_cache_dir,
+                        &thumb.item_id,
+                        Path::new(&thumb.video_path),
+                        decode_args,
+                        location,
+                        scene_detection,
+                    )
+                    .await;
+                    COUNTERS.in_progress.fetch_sub(1, Ordering::Relaxed);
+
+                    if generated {
+                        COUNTERS.completed.fetch_add(1, Ordering::Relaxed);
+                        let _ = db::mark_thumbnail_complete(pool, thumb.id).await;
+                        events::publish(ServerEvent::ImageUpdated {
+                            item_id: thumb.item_id.clone(),
+                            image_type: "Primary".to_string(),
+                        });
+                    } else {
+                        COUNTERS.failed.fetch_add(1, Ordering::Relaxed);
+                        let _ = db::mark_thumbnail_failed(pool, thumb.id).await;
+                    }
+                }
+            })
+            .await;
+    }
+}
+
+/// Where a video's thumbnail is written beside it
+pub fn beside_media_path(video_path: &Path, item_type: &str) -> Option<PathBuf> {
+    let stem = video_path.file_stem()?.to_str()?;
+    let suffix = if item_type == "Movie" {
+        "poster"
+    } else {
+        "thumb"
+    };
+    Some(video_path.with_file_name(format!("{}-{}.jpg", stem, suffix)))
+}
+
+/// Where to write an item's thumbnail beside its video; None when the folder
+/// isn't writable
+async fn beside_media_output(
+    pool: &SqlitePool,
+    item_id: &str,
+    video_path: &Path,
+) -> Option<PathBuf> {
+    let item_type: String = sqlx::query_scalar("SELECT item_type FROM media_items WHERE id = ?")
+        .bind(item_id)
+        .fetch_optional(pool)
+        .await
+        .ok()??;
+    let path = beside_media_path(video_path, &item_type)?;
+    if tokio::fs::metadata(&path).await.is_ok_and(|m| m.len() > 0) {
+        return Some(path);
+    }
+    // Creating the file is the permission check; ffmpeg writes it afterwards
+    match tokio::fs::File::create_new(&path).await {
+        Ok(_) => {
+            let _ = tokio::fs::remove_file(&path).await;
+            Some(path)
+        }
+        Err(e) => {
+            tracing::debug!(
+                "Can't write a thumbnail beside {} ({}), using the cache",
+                video_path.display(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Extract and store one item's thumbnail; returns whether it was generated
+async fn generate(
+    pool: &SqlitePool,
+    image_cache_dir: &Path,
+    item_id: &str,
+    video_path: &Path,
+    decode_args: &[String],
+    location: ThumbnailLocation,
+    scene_detection: bool,
+) -> bool {
+    let beside_media = match location {
+        ThumbnailLocation::Media => beside_media_output(pool, item_id, video_path).await,
+        ThumbnailLocation::Cache => None,
+    };
+    let output_path = match beside_media {
+        // An image already there is used rather than replaced
+        Some(path) if path.exists() => {
+            store(pool, item_id, &path).await;
+            return true;
+        }
+        Some(path) => path,
+        None => image_cache_dir.join(item_id).join("Primary.jpg"),
+    };
+
+    let timestamp = mediainfo::extract_media_info_async(video_path)
+        .await
+        .ok()
+        .and_then(|i| i.duration_ticks)
+        .map(|ticks| mediainfo::calculate_thumbnail_timestamp(Ticks(ticks)))
+        .unwrap_or(Ticks::from_seconds(30));
+
+    if let Err(e) = mediainfo::extract_thumbnail_async(
+        video_path,
+        &output_path,
+        timestamp,
+        Some(THUMBNAIL_WIDTH),
+        decode_args.to_vec(),
+        scene_detection,
+    )
+    .await
+    {
+        tracing::debug!("Thumbnail for {} failed: {}", video_path.display(), e);
+        return false;
+    }
+
+    store(pool, item_id, &output_path).await;
+    true
+}
+
+/// Record an item's thumbnail as its primary image
+async fn store(pool: &SqlitePool, item_id: &str, path: &Path) {
+    let _ = sqlx::query(
+        "INSERT OR REPLACE INTO images (id, item_id, image_type, path) VALUES (?, ?, ?, ?)",
+    )
+    .bind(uuid::Uuid::new_v4().to_string())
+    .bind(item_id)
+    .bind("Primary")
+    .bind(path.to_str().unwrap_or_default())
+    .execute(pool)
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_args_per_hwaccel() {
+        let config = |hwaccel| ThumbnailConfig {
+            hwaccel,
+            ..ThumbnailConfig::default()
+        };
+        assert!(decode_args(&config(HwAccel::None)).is_empty());
+        assert_eq!(
+            decode_args(&config(HwAccel::Vaapi)),
+            [
+                "-hwaccel",
+                "vaapi",
+                "-hwaccel_device",
+                "/dev/dri/renderD128"
+            ]
+        );
+        assert_eq!(decode_args(&config(HwAccel::Nvenc)), ["-hwaccel", "cuda"]);
+
+        assert_eq!(
+            worker_count(&ThumbnailConfig {
+                workers: 6,
+                ..ThumbnailConfig::default()
+            }),
+            6
+        );
+        assert!((1..=4).contains(&worker_count(&ThumbnailConfig::default())));
+    }
+
+    #[test]
+    fn test_beside_media_path() {
+        assert_eq!(
+            beside_media_path(Path::new("/tv/Show/Season 01/S01E01.mkv"), "Episode"),
+            Some(PathBuf::from("/tv/Show/Season 01/S01E01-thumb.jpg"))
+        );
+        assert_eq!(
+            beside_media_path(Path::new("/movies/Heat (1995)/Heat (1995).mkv"), "Movie"),
+            Some(PathBuf::from("/movies/Heat (1995)/Heat (1995)-poster.jpg"))
+        );
+    }
+}
@@ -7,6 +7,8 @@ use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use tokio::fs;
 
+use super::image_validation;
+
 const TMDB_API_BASE: &str = "https://api.themoviedb.org/3";
 const TMDB_IMAGE_BASE: &str = "https://image.tmdb.org/t/p";
 
@@ -15,6 +17,10 @@ pub struct TmdbClient {
     client: Client,
     api_key: String,
     image_cache_dir: PathBuf,
+    /// Languages of the posters and backdrops listed with details
+    image_languages: Vec<String>,
+    /// Language of names and overviews (TMDB's default, English, when None)
+    language: Option<String>,
 }
 
 /// Search result for TV shows
@@ -75,6 +81,16 @@ pub struct TvDetails {
     pub genres: Option<Vec<Genre>>,
     pub external_ids: Option<ExternalIds>,
     pub credits: Option<Credits>,
+    pub seasons: Option<Vec<SeasonSummary>>,
+    pub images: Option<Images>,
+    pub content_ratings: Option<ContentRatings>,
+}
+
+/// Season entry in TV show details
+#[derive(Debug, Deserialize)]
+pub struct SeasonSummary {
+    pub season_number: i32,
+    pub episode_count: Option<i32>,
 }
 
 /// Detailed movie info
@@ -93,6 +109,18 @@ pub struct MovieDetails {
     pub genres: Option<Vec<Genre>>,
     pub imdb_id: Option<String>,
     pub credits: Option<Credits>,
+    pub images: Option<Images>,
+    pub belongs_to_collection: Option<CollectionRef>,
+    pub release_dates: Option<ReleaseDates>,
+}
+
+/// The collection (franchise) a movie belongs to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionRef {
+    pub id: i64,
+    pub name: String,
+    pub poster_path: Option<String>,
+    pub backdrop_path: Option<String>,
 }
 
 /// Season details
@@ -121,13 +149,119 @@ pub struct EpisodeInfo {
     pub runtime: Option<i32>,
 }
 
+/// Alternate episode orderings offered for a TV show
+#[derive(Debug, Deserialize)]
+pub struct EpisodeGroupList {
+    pub results: Vec<EpisodeGroupSummary>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EpisodeGroupSummary {
+    pub id: String,
+    pub name: String,
+    pub episode_count: Option<i32>,
+    #[serde(rename = "type")]
+    pub group_type: Option<i32>,
+}
+
+impl EpisodeGroupSummary {
+    /// Human readable kind of ordering, from TMDB's numeric group type
+    pub fn type_name(&self) -> Option<&'static str> {
+        match self.group_type? {
+            1 => Some("Original air date"),
+            2 => Some("Absolute"),
+            3 => Some("DVD"),
+            4 => Some("Digital"),
+            5 => Some("Story arc"),
+            6 => Some("Production"),
+            7 => Some("TV"),
+            _ => None,
+        }
+    }
+}
+
+/// An episode group with its groups ("seasons") and episodes
+#[derive(Debug, Deserialize)]
+pub struct EpisodeGroupDetails {
+    pub name: String,
+    pub groups: Vec<EpisodeGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EpisodeGroup {
+    pub name: String,
+    pub order: i32,
+    pub episodes: Vec<EpisodeGroupEntry>,
+}
+
+/// An episode inside a group, with its original (aired) numbering
+#[derive(Debug, Deserialize)]
+pub struct EpisodeGroupEntry {
+    pub season_number: i32,
+    pub episode_number: i32,
+    /// Position within the group, starting at 0
+    pub order: i32,
+}
+
+/// A show's or movie's posters and backdrops
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Images {
+    #[serde(default)]
+    pub posters: Vec<Image>,
+    #[serde(default)]
+    pub backdrops: Vec<Image>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Image {
+    pub file_path: String,
+    pub width: u32,
+    /// Language of any text on the image (None or "xx" when textless)
+    pub iso_639_1: Option<String>,
+    #[serde(default)]
+    pub vote_average: f64,
+    #[serde(default)]
+    pub vote_count: i64,
+}
+
+/// A show's age rating in each country
+#[derive(Debug, Deserialize)]
+pub struct ContentRatings {
+    pub results: Vec<ContentRating>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentRating {
+    pub iso_3166_1: String,
+    pub rating: String,
+}
+
+/// A movie's releases in each country, with their certifications
+#[derive(Debug, Deserialize)]
+pub struct ReleaseDates {
+    pub results: Vec<CountryReleaseDates>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CountryReleaseDates {
+    pub iso_3166_1: String,
+    pub release_dates: Vec<ReleaseDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseDate {
+    /// Empty when the release wasn't rated
+    #[serde(default)]
+    pub certification: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct Genre {
     pub id: i64,
     pub name: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ExternalIds {
     pub imdb_id: Option<String>,
     pub tvdb_id: Option<i64>,
@@ -161,7 +295,7 @@ pub struct CrewMember {
 }
 
 /// Metadata result that can be applied to a media item
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct MediaMetadata {
     pub tmdb_id: Option<String>,
     pub imdb_id: Option<String>,
@@ -175,10 +309,16 @@ pub struct MediaMetadata {
     pub runtime_minutes: Option<i32>,
     pub genres: Option<Vec<String>>,
     pub cast: Vec<TmdbCastMember>,
+    /// Every poster and backdrop, to choose from (series and movies)
+    pub images: Option<Images>,
+    /// The collection a movie belongs to
+    pub collection: Option<CollectionRef>,
+    /// Age rating ("PG-13", "TV-MA", "16") for the certification country
+    pub official_rating: Option<String>,
 }
 
 /// Cast member info for unified metadata
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TmdbCastMember {
     pub person_id: String,
     pub person_name: String,
@@ -227,9 +367,76 @@ impl TmdbClient {
             client: Client::new(),
             api_key,
             image_cache_dir,
+            image_languages: vec!["en".to_string()],
+            language: None,
         }
     }
 
+    /// List posters and backdrops in these languages (and textless ones) with details
+    pub fn with_image_languages(mut self, languages: &[String]) -> Self {
+        self.image_languages = languages.to_vec();
+        self
+    }
+
+    /// Request names and overviews in this language ("ja", "pt-BR")
+    pub fn with_language(mut self, language: Option<&str>) -> Self {
+        self.language = language.map(|l| l.to_string());
+        self
+    }
+
+    /// The languages asked for, which cached responses are kept by
+    pub fn cache_locale(&self) -> String {
+        format!(
+            "{}/{}",
+            self.language.as_deref().unwrap_or_default(),
+            self.image_languages.join(",")
+        )
+    }
+
+    /// `language` parameter, if a language was set
+    fn language_param(&self) -> String {
+        self.language
+            .as_deref()
+            .map(|l| format!("&language={}", urlencoding::encode(l)))
+            .unwrap_or_default()
+    }
+
+    /// `include_image_language` parameter for the configured languages
+    fn image_language_param(&self) -> String {
+        let mut languages = self.image_languages.clone();
+        languages.push("null".to_string());
+        languages.join(",")
+    }
+
+    /// Countries whose age ratings are used, best first: the metadata
+    /// language's region ("pt-BR" gives BR), then the US
+    fn certification_countries(&self) -> Vec<String> {
+        let region = self
+            .language
+            .as_deref()
+            .and_then(|l| l.split_once('-'))
+            .map(|(_, region)| region.to_uppercase());
+        region
+            .into_iter()
+            .chain(std::iter::once("US".to_string()))
+            .collect()
+    }
+
+    /// First rating given for the certification countries, from (country, rating) pairs
+    fn certification<'a>(
+        &self,
+        ratings: impl Iterator<Item = (&'a str, &'a str)> + Clone,
+    ) -> Option<String> {
+        self.certification_countries().iter().find_map(|country| {
+            ratings
+                .clone()
+                .filter(|(c, _)| c.eq_ignore_ascii_case(country))
+                .map(|(_, rating)| rating.trim())
+                .find(|rating| !rating.is_empty())
+                .map(str::to_string)
+        })
+    }
+
     /// Create client from environment variable
     pub fn from_env(image_cache_dir: PathBuf) -> Option<Self> {
         std::env::var("TMDB_API_KEY")
@@ -240,10 +447,11 @@ impl TmdbClient {
     /// Search for TV shows by name
     pub async fn search_tv(&self, query: &str, year: Option<i32>) -> Result<Vec<TvSearchResult>> {
         let mut url = format!(
-            "{}/search/tv?api_key={}&query={}&include_adult=false",
+            "{}/search/tv?api_key={}&query={}&include_adult=false{}",
             TMDB_API_BASE,
             self.api_key,
-            urlencoding::encode(query)
+            urlencoding::encode(query),
+            self.language_param()
         );
 
         if let Some(y) = year {
@@ -270,10 +478,11 @@ impl TmdbClient {
         year: Option<i32>,
     ) -> Result<Vec<MovieSearchResult>> {
         let mut url = format!(
-            "{}/search/movie?api_key={}&query={}&include_adult=false",
+            "{}/search/movie?api_key={}&query={}&include_adult=false{}",
             TMDB_API_BASE,
             self.api_key,
-            urlencoding::encode(query)
+            urlencoding::encode(query),
+            self.language_param()
         );
 
         if let Some(y) = year {
@@ -296,8 +505,12 @@ impl TmdbClient {
     /// Get detailed TV show info
     pub async fn get_tv_details(&self, tmdb_id: i64) -> Result<TvDetails> {
         let url = format!(
-            "{}/tv/{}?api_key={}&append_to_response=external_ids,credits",
-            TMDB_API_BASE, tmdb_id, self.api_key
+            "{}/tv/{}?api_key={}&append_to_response=external_ids,credits,images,content_ratings&include_image_language={}{}",
+            TMDB_API_BASE,
+            tmdb_id,
+            self.api_key,
+            self.image_language_param(),
+            self.language_param()
         );
 
         let response: TvDetails = self
@@ -316,8 +529,12 @@ impl TmdbClient {
     /// Get detailed movie info
     pub async fn get_movie_details(&self, tmdb_id: i64) -> Result<MovieDetails> {
         let url = format!(
-            "{}/movie/{}?api_key={}&append_to_response=credits",
-            TMDB_API_BASE, tmdb_id, self.api_key
+            "{}/movie/{}?api_key={}&append_to_response=credits,images,release_dates&include_image_language={}{}",
+            TMDB_API_BASE,
+            tmdb_id,
+            self.api_key,
+            self.image_language_param(),
+            self.language_param()
         );
 
         let response: MovieDetails = self
@@ -333,6 +550,29 @@ impl TmdbClient {
         Ok(response)
     }
 
+    /// Get a show's or movie's IDs on other sites (IMDb, TVDB)
+    pub async fn get_external_ids(&self, tmdb_id: i64, is_movie: bool) -> Result<ExternalIds> {
+        let url = format!(
+            "{}/{}/{}/external_ids?api_key={}",
+            TMDB_API_BASE,
+            if is_movie { "movie" } else { "tv" },
+            tmdb_id,
+            self.api_key
+        );
+
+        let response: ExternalIds = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to get TMDB external IDs")?
+            .json()
+            .await
+            .context("Failed to parse TMDB external IDs response")?;
+
+        Ok(response)
+    }
+
     /// Get season details including episode list
     pub async fn get_season_details(
         &self,
@@ -340,8 +580,12 @@ impl TmdbClient {
         season_number: i32,
     ) -> Result<SeasonDetails> {
         let url = format!(
-            "{}/tv/{}/season/{}?api_key={}",
-            TMDB_API_BASE, tv_id, season_number, self.api_key
+            "{}/tv/{}/season/{}?api_key={}{}",
+            TMDB_API_BASE,
+            tv_id,
+            season_number,
+            self.api_key,
+            self.language_param()
         );
 
         let response: SeasonDetails = self
@@ -357,6 +601,48 @@ impl TmdbClient {
         Ok(response)
     }
 
+    /// List the alternate episode orderings (episode groups) of a TV show
+    pub async fn get_episode_groups(&self, tv_id: i64) -> Result<Vec<EpisodeGroupSummary>> {
+        let url = format!(
+            "{}/tv/{}/episode_groups?api_key={}",
+            TMDB_API_BASE, tv_id, self.api_key
+        );
+
+        let response: EpisodeGroupList = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to get TMDB episode groups")?
+            .json()
+            .await
+            .context("Failed to parse TMDB episode groups response")?;
+
+        Ok(response.results)
+    }
+
+    /// Get an episode group with the episodes in each of its groups
+    pub async fn get_episode_group(&self, group_id: &str) -> Result<EpisodeGroupDetails> {
+        let url = format!(
+            "{}/tv/episode_group/{}?api_key={}",
+            TMDB_API_BASE, group_id, self.api_key
+        );
+
+        let response: EpisodeGroupDetails = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to get TMDB episode group")?
+            .error_for_status()
+            .context("TMDB episode group not found")?
+            .json()
+            .await
+            .context("Failed to parse TMDB episode group response")?;
+
+        Ok(response)
+    }
+
     /// Download and cache an image, returns the local path
     pub async fn download_image(
         &self,
@@ -380,8 +666,17 @@ impl TmdbClient {
 
         // Skip if already cached (use async check to avoid blocking)
         if fs::try_exists(&local_path).await.unwrap_or(false) {
-            tracing::debug!("Image already cached: {:?}", local_path);
-            return Ok(local_path);
+            match image_validation::inspect_file(&local_path).await {
+                Ok(_) => {
+                    tracing::debug!("Image already cached: {:?}", local_path);
+                    return Ok(local_path);
+                }
+                Err(e) => tracing::warn!(
+                    "Re-downloading invalid cached image {:?}: {}",
+                    local_path,
+                    e
+                ),
+            }
         }
 
         // Download from TMDB
@@ -402,7 +697,10 @@ impl TmdbClient {
             );
         }
 
+        let content_type = image_validation::content_type(&response);
         let bytes = response.bytes().await?;
+        image_validation::validate_download(&bytes, content_type.as_deref(), image_type)
+            .with_context(|| format!("Invalid image from {}", url))?;
         fs::write(&local_path, &bytes).await?;
 
         tracing::info!("Downloaded image to {:?}", local_path);
@@ -486,6 +784,13 @@ impl TmdbClient {
 
             // Extract cast (limit to top 20 to keep it manageable)
             let cast = Self::extract_cast(&details.credits, 20);
+            let official_rating = details.content_ratings.as_ref().and_then(|r| {
+                self.certification(
+                    r.results
+                        .iter()
+                        .map(|r| (r.iso_3166_1.as_str(), r.rating.as_str())),
+                )
+            });
 
             Ok(Some(MediaMetadata {
                 tmdb_id: Some(details.id.to_string()),
@@ -502,6 +807,9 @@ impl TmdbClient {
                     .genres
                     .map(|g| g.into_iter().map(|genre| genre.name).collect()),
                 cast,
+                images: details.images,
+                collection: None,
+                official_rating,
             }))
         } else {
             tracing::debug!(
@@ -610,6 +918,14 @@ impl TmdbClient {
 
             // Extract cast (limit to top 20)
             let cast = Self::extract_cast(&details.credits, 20);
+            let official_rating = details.release_dates.as_ref().and_then(|r| {
+                self.certification(r.results.iter().flat_map(|country| {
+                    country
+                        .release_dates
+                        .iter()
+                        .map(|d| (country.iso_3166_1.as_str(), d.certification.as_str()))
+                }))
+            });
 
             Ok(Some(MediaMetadata {
                 tmdb_id: Some(details.id.to_string()),
@@ -626,6 +942,9 @@ impl TmdbClient {
                     .genres
                     .map(|g| g.into_iter().map(|genre| genre.name).collect()),
                 cast,
+                images: details.images,
+                collection: details.belongs_to_collection,
+                official_rating,
             }))
         } else {
             tracing::debug!(
@@ -660,6 +979,9 @@ impl TmdbClient {
                     runtime_minutes: episode.runtime,
                     genres: None,     // Episodes don't have genres
                     cast: Vec::new(), // Episodes don't have cast data here
+                    images: None,
+                    collection: None,
+                    official_rating: None,
                 }));
             }
         }
@@ -0,0 +1,571 @@
+// HLS transcoding
+//
+// Clients that can't direct play a file (HEVC or AC3 in a browser, MKV on a TV
+// that only takes MP4) get it as HLS instead: H.264/AAC in MPEG-TS segments,
+// transcoded by ffmpeg as they're requested. The master playlist offers a
+// ladder of renditions up to the source's resolution and the client's bitrate
+// limit. Each rendition's playlist lists every segment of the file up front,
+// so clients can seek anywhere; a request far past what ffmpeg has written
+// restarts it at that segment instead of waiting for it to get there.
+//
+// A play session has at most one transcode. Switching rendition or audio track
+// replaces it, and it's stopped when the client says playback ended
+// (DELETE /Videos/ActiveEncodings) or when nothing was requested from it for a
+// minute. Segments live in the cache directory only while their transcode runs.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::process::{Child, Command};
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use super::mediainfo;
+use crate::config::TranscodingConfig;
+use crate::time::Ticks;
+
+/// Bitrate of the stereo AAC audio in every rendition
+pub const AUDIO_BITRATE: u32 = 128_000;
+
+/// Rendition heights and their video bitrates, largest first
+const LADDER: &[(u32, u32)] = &[
+    (2160, 16_000_000),
+    (1440, 10_000_000),
+    (1080, 6_000_000),
+    (720, 3_000_000),
+    (480, 1_200_000),
+    (360, 600_000),
+];
+
+/// Lowest video bitrate offered, however low the client's limit
+const MIN_VIDEO_BITRATE: u32 = 200_000;
+
+/// Transcodes nothing was requested from for this long are stopped
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How far past the last written segment a request may be before the
+/// transcode restarts there rather than the request waiting for it
+const MAX_SEGMENTS_AHEAD: u32 = 3;
+
+/// Longest a request waits for its segment to be written
+const SEGMENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One resolution and bitrate a file is offered at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rendition {
+    pub height: u32,
+    pub video_bitrate: u32,
+}
+
+impl Rendition {
+    /// Total bitrate for the master playlist's BANDWIDTH
+    pub fn bandwidth(&self) -> u32 {
+        self.video_bitrate + AUDIO_BITRATE
+    }
+
+    /// Path component identifying the rendition ("720-3000000")
+    pub fn id(&self) -> String {
+        format!("{}-{}", self.height, self.video_bitrate)
+    }
+
+    /// Parse a rendition ID, refusing sizes no ladder produces
+    pub fn parse(id: &str) -> Option<Self> {
+        let (height, bitrate) = id.split_once('-')?;
+        let rendition = Self {
+            height: height.parse().ok()?,
+            video_bitrate: bitrate.parse().ok()?,
+        };
+        let valid = (2..=4320).contains(&rendition.height)
+            && rendition.height.is_multiple_of(2)
+            && (MIN_VIDEO_BITRATE..=100_000_000).contains(&rendition.video_bitrate);
+        valid.then_some(rendition)
+    }
+}
+
+/// Renditions for a source, largest first
+///
+/// The first is the source's own height (at the bitrate of the ladder rung it
+/// falls under), followed by the smaller rungs. Renditions above the client's
+/// bitrate limit are left out; when that leaves none, the smallest one is
+/// offered at the limit.
+pub fn ladder(source_height: Option<u32>, max_bitrate: Option<u32>) -> Vec<Rendition> {
+    // Scalers need even heights
+    let source_height = source_height.filter(|h| *h >= 2).unwrap_or(1080) & !1;
+    let top_bitrate = LADDER
+        .iter()
+        .rev()
+        .find(|(height, _)| *height >= source_height)
+        .unwrap_or(&LADDER[0])
+        .1;
+
+    let renditions = std::iter::once(Rendition {
+        height: source_height,
+        video_bitrate: top_bitrate,
+    })
+    .chain(
+        LADDER
+            .iter()
+            .filter(|(height, _)| *height < source_height)
+            .map(|&(height, video_bitrate)| Rendition {
+                height,
+                video_bitrate,
+            }),
+    );
+
+    let Some(max_bitrate) = max_bitrate.filter(|b| *b > 0) else {
+        return renditions.collect();
+    };
+    let mut renditions: Vec<Rendition> = renditions.collect();
+    let smallest = *renditions.last().unwrap();
+    renditions.retain(|r| r.bandwidth() <= max_bitrate);
+    if renditions.is_empty() {
+        renditions.push(Rendition {
+            height: smallest.height,
+            video_bitrate: max_bitrate
+                .saturating_sub(AUDIO_BITRATE)
+                .max(MIN_VIDEO_BITRATE),
+        });
+    }
+    renditions
+}
+
+/// Master playlist listing the renditions; `query` is appended to their URLs
+pub fn master_playlist(
+    renditions: &[Rendition],
+    source_size: Option<(u32, u32)>,
+    query: &str,
+) -> String {
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:3\n");
+    for rendition in renditions {
+        let width = match source_size {
+            Some((w, h)) if h > 0 => (w as u64 * rendition.height as u64 / h as u64) as u32,
+            _ => rendition.height * 16 / 9,
+        };
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={},RESOLUTION={}x{},CODECS=\"avc1.640028,mp4a.40.2\"\n\
+             hls1/{}/main.m3u8?{}\n",
+            rendition.bandwidth(),
+            (width + 1) & !1,
+            rendition.height,
+            rendition.id(),
+            query
+        ));
+    }
+    playlist
+}
+
+/// Number of segments a file of `duration` is split into
+pub fn segment_count(duration: Ticks, segment_seconds: u32) -> u32 {
+    let segment = Ticks::from_seconds(segment_seconds.max(1) as i64);
+    ((duration.0.max(1) + segment.0 - 1) / segment.0) as u32
+}
+
+/// A rendition's playlist, listing every segment of the file
+pub fn media_playlist(duration: Ticks, segment_seconds: u32, query: &str) -> String {
+    let count = segment_count(duration, segment_seconds);
+    let mut playlist = format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{}\n#EXT-X-MEDIA-SEQUENCE:0\n\
+         #EXT-X-PLAYLIST-TYPE:VOD\n",
+        segment_seconds
+    );
+    let total = duration.as_secs_f64();
+    for index in 0..count {
+        let length = (total - (index * segment_seconds) as f64).min(segment_seconds as f64);
+        playlist.push_str(&format!(
+            "#EXTINF:{:.3},\n{}.ts?{}\n",
+            length.max(0.001),
+            index,
+            query
+        ));
+    }
+    playlist.push_str("#EXT-X-ENDLIST\n");
+    playlist
+}
+
+/// What a transcode produces: a file's rendition with one audio track
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscodeSpec {
+    pub input: PathBuf,
+    /// External audio file picked instead of the file's own tracks
+    pub audio_file: Option<PathBuf>,
+    /// ffprobe index of the embedded audio track, None for the first
+    pub audio_stream_index: Option<i32>,
+    pub rendition: Rendition,
+}
+
+/// ffmpeg arguments writing a spec's segments from `start_segment` into `dir`
+pub fn ffmpeg_args(
+    spec: &TranscodeSpec,
+    config: &TranscodingConfig,
+    start_segment: u32,
+    dir: &Path,
+) -> Vec<String> {
+    let segment_seconds = config.segment_seconds.max(1);
+    let start = Ticks::from_seconds((start_segment * segment_seconds) as i64);
+    let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error"]
+        .map(String::from)
+        .to_vec();
+    for input in std::iter::once(&spec.input).chain(spec.audio_file.as_ref()) {
+        if start.0 > 0 {
+            args.extend(["-ss".to_string(), start.to_ffmpeg()]);
+        }
+        args.extend(["-i".to_string(), input.to_string_lossy().into_owned()]);
+    }
+
+    let audio_map = match (&spec.audio_file, spec.audio_stream_index) {
+        (Some(_), _) => "1:a:0".to_string(),
+        (None, Some(index)) => format!("0:{}", index),
+        (None, None) => "0:a:0?".to_string(),
+    };
+    args.extend(["-map".to_string(), "0:v:0".to_string()]);
+    args.extend(["-map".to_string(), audio_map]);
+
+    let bitrate = spec.rendition.video_bitrate.to_string();
+    args.extend(
+        [
+            "-sn",
+            "-dn",
+            "-c:v",
+            "libx264",
+            "-preset",
+            &config.preset,
+            "-profile:v",
+            "high",
+            "-pix_fmt",
+            "yuv420p",
+            "-vf",
+            &format!("scale=-2:{}", spec.rendition.height),
+            "-b:v",
+            &bitrate,
+            "-maxrate",
+            &bitrate,
+            "-bufsize",
+            &(spec.rendition.video_bitrate * 2).to_string(),
+            // Keyframes at every segment boundary, so segments can be cut exactly
+            "-force_key_frames",
+            &format!("expr:gte(t,n_forced*{})", segment_seconds),
+            "-c:a",
+            "aac",
+            "-ac",
+            "2",
+            "-b:a",
+            &AUDIO_BITRATE.to_string(),
+            "-f",
+            "hls",
+            "-hls_time",
+            &segment_seconds.to_string(),
+            "-hls_list_size",
+            "0",
+            "-hls_segment_type",
+            "mpegts",
+            // Segments only appear under their name once complete
+            "-hls_flags",
+            "temp_file",
+            "-start_number",
+            &start_segment.to_string(),
+            "-output_ts_offset",
+            &start.to_ffmpeg(),
+        ]
+        .map(String::from),
+    );
+    args.extend([
+        "-hls_segment_filename".to_string(),
+        dir.join("%d.ts").to_string_lossy().into_owned(),
+        dir.join("ffmpeg.m3u8").to_string_lossy().into_owned(),
+    ]);
+    args
+}
+
+/// A running transcode
+struct Job {
+    spec: TranscodeSpec,
+    device_id: Option<String>,
+    dir: PathBuf,
+    child: Child,
+    start_segment: u32,
+    /// Last segment seen on disk, to find how far ffmpeg got without
+    /// checking every file from the start
+    last_written: Option<u32>,
+    last_used: Instant,
+}
+
+impl Job {
+    /// Last segment ffmpeg has finished
+    fn last_written(&mut self) -> Option<u32> {
+        let mut next = self.last_written.map_or(self.start_segment, |n| n + 1);
+        while segment_path(&self.dir, next).exists() {
+            self.last_written = Some(next);
+            next += 1;
+        }
+        self.last_written
+    }
+
+    /// Whether a segment will be written soon without restarting
+    fn will_reach(&mut self, segment: u32) -> bool {
+        if segment < self.start_segment || self.child.try_wait().ok().flatten().is_some() {
+            return false;
+        }
+        let written = self
+            .last_written()
+            .unwrap_or(self.start_segment.saturating_sub(1));
+        segment <= written + MAX_SEGMENTS_AHEAD
+    }
+}
+
+/// Transcodes by play session
+static JOBS: LazyLock<Mutex<HashMap<String, Job>>> = LazyLock::new(Default::default);
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("{}.ts", segment))
+}
+
+/// Stop a transcode and delete its segments
+async fn stop_job(mut job: Job) {
+    let _ = job.child.kill().await;
+    if let Err(e) = tokio::fs::remove_dir_all(&job.dir).await {
+        tracing::debug!("Failed to remove {}: {}", job.dir.display(), e);
+    }
+}
+
+/// Start (or restart) a session's transcode at a segment
+async fn start_job(
+    config: &TranscodingConfig,
+    root: &Path,
+    spec: &TranscodeSpec,
+    device_id: Option<&str>,
+    segment: u32,
+    previous: Option<Job>,
+) -> Result<Job> {
+    // A restart of the same spec keeps the segments written so far
+    let dir = match previous {
+        Some(job) if job.spec == *spec => {
+            let mut job = job;
+            let _ = job.child.kill().await;
+            job.dir
+        }
+        Some(job) => {
+            stop_job(job).await;
+            root.join(uuid::Uuid::new_v4().simple().to_string())
+        }
+        None => root.join(uuid::Uuid::new_v4().simple().to_string()),
+    };
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+
+    let args = ffmpeg_args(spec, config, segment, &dir);
+    let child = Command::new(mediainfo::find_ffmpeg())
+        .args(&args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to run ffmpeg")?;
+
+    tracing::info!(
+        "Transcoding {} at {}p from segment {}",
+        spec.input.display(),
+        spec.rendition.height,
+        segment
+    );
+    Ok(Job {
+        spec: spec.clone(),
+        device_id: device_id.map(str::to_string),
+        dir,
+        child,
+        start_segment: segment,
+        last_written: None,
+        last_used: Instant::now(),
+    })
+}
+
+/// A segment of a session's transcode, waiting for ffmpeg to write it
+///
+/// Starts the transcode if the session has none (or one of another spec) and
+/// restarts it when the segment is behind it or far ahead. Ok(None) when
+/// starting it would exceed the configured number of transcodes.
+pub async fn segment(
+    config: &TranscodingConfig,
+    root: &Path,
+    session: &str,
+    device_id: Option<&str>,
+    spec: &TranscodeSpec,
+    segment: u32,
+) -> Result<Option<PathBuf>> {
+    let path = {
+        let mut jobs = JOBS.lock().await;
+        let reusable = match jobs.get_mut(session) {
+            Some(job) if job.spec == *spec => {
+                job.last_used = Instant::now();
+                segment_path(&job.dir, segment).exists() || job.will_reach(segment)
+            }
+            Some(_) => false,
+            None => {
+                if config.max_sessions > 0 && jobs.len() >= config.max_sessions {
+                    return Ok(None);
+                }
+                false
+            }
+        };
+        if !reusable {
+            let previous = jobs.remove(session);
+            let job = start_job(config, root, spec, device_id, segment, previous).await?;
+            jobs.insert(session.to_string(), job);
+        }
+        segment_path(&jobs[session].dir, segment)
+    };
+
+    let deadline = Instant::now() + SEGMENT_TIMEOUT;
+    loop {
+        if tokio::fs::try_exists(&path).await.unwrap_or(false) {
+            return Ok(Some(path));
+        }
+        {
+            let mut jobs = JOBS.lock().await;
+            let Some(job) = jobs.get_mut(session).filter(|job| job.spec == *spec) else {
+                anyhow::bail!("Transcode was stopped");
+            };
+            if let Ok(Some(status)) = job.child.try_wait() {
+                // ffmpeg may finish between the check above and this one
+                if !path.exists() {
+                    anyhow::bail!("ffmpeg exited ({}) before segment {}", status, segment);
+                }
+            }
+        }
+        if Instant::now() > deadline {
+            anyhow::bail!("Timed out waiting for segment {}", segment);
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
+
+/// Stop the transcodes of a play session or a device
+pub async fn stop(play_session_id: Option<&str>, device_id: Option<&str>) -> usize {
+    let stopped: Vec<Job> = {
+        let mut jobs = JOBS.lock().await;
+        let sessions: Vec<String> = jobs
+            .iter()
+            .filter(|(session, job)| {
+                play_session_id.is_some_and(|id| id == session.as_str())
+                    || device_id.is_some_and(|id| job.device_id.as_deref() == Some(id))
+            })
+            .map(|(session, _)| session.clone())
+            .collect();
+        sessions
+            .iter()
+            .filter_map(|session| jobs.remove(session))
+            .collect()
+    };
+    let count = stopped.len();
+    for job in stopped {
+        stop_job(job).await;
+    }
+    count
+}
+
+/// Stop transcodes nobody requests segments from, until shutdown
+///
+/// Segments left behind by a previous run are deleted at startup, and all
+/// transcodes are stopped at shutdown.
+pub async fn run_cleanup(root: PathBuf, cancel: CancellationToken) {
+    if tokio::fs::try_exists(&root).await.unwrap_or(false) {
+        if let Err(e) = tokio::fs::remove_dir_all(&root).await {
+            tracing::warn!("Failed to clear {}: {}", root.display(), e);
+        }
+    }
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = tokio::time::sleep(IDLE_TIMEOUT / 4) => {}
+        }
+
+        let idle: Vec<Job> = {
+            let mut jobs = JOBS.lock().await;
+            let sessions: Vec<String> = jobs
+                .iter()
+                .filter(|(_, job)| job.last_used.elapsed() > IDLE_TIMEOUT)
+                .map(|(session, _)| session.clone())
+                .collect();
+            sessions
+                .iter()
+                .filter_map(|session| jobs.remove(session))
+                .collect()
+        };
+        for job in idle {
+            tracing::info!("Stopping idle transcode of {}", job.spec.input.display());
+            stop_job(job).await;
+        }
+    }
+
+    let jobs: Vec<Job> = JOBS.lock().await.drain().map(|(_, job)| job).collect();
+    for job in jobs {
+        stop_job(job).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ladder_and_playlists() {
+        let heights = |renditions: Vec<Rendition>| -> Vec<u32> {
+            renditions.iter().map(|r| r.height).collect()
+        };
+        assert_eq!(heights(ladder(Some(1080), None)), [1080, 720, 480, 360]);
+        // Cropped sources keep their own height at the rung above's bitrate
+        let cropped = ladder(Some(803), Some(7_000_000));
+        assert_eq!(heights(cropped.clone()), [802, 720, 480, 360]);
+        assert_eq!(cropped[0].video_bitrate, 6_000_000);
+        assert_eq!(
+            heights(ladder(Some(2160), Some(5_000_000))),
+            [720, 480, 360]
+        );
+        // A limit below every rung still gets the smallest one
+        assert_eq!(
+            ladder(Some(1080), Some(400_000)),
+            [Rendition {
+                height: 360,
+                video_bitrate: 272_000
+            }]
+        );
+
+        let rendition = ladder(Some(720), None)[0];
+        assert_eq!(Rendition::parse(&rendition.id()), Some(rendition));
+        assert_eq!(Rendition::parse("721-3000000"), None);
+        assert_eq!(Rendition::parse("720-1"), None);
+
+        let master = master_playlist(&[rendition], Some((1920, 800)), "api_key=k");
+        assert!(master.contains("BANDWIDTH=3128000,RESOLUTION=1728x720"));
+        assert!(master.contains("hls1/720-3000000/main.m3u8?api_key=k"));
+
+        let media = media_playlist(Ticks::from_seconds(13), 6, "api_key=k");
+        assert_eq!(segment_count(Ticks::from_seconds(13), 6), 3);
+        assert!(media.contains("#EXTINF:6.000,\n0.ts?api_key=k\n"));
+        assert!(media.contains("#EXTINF:1.000,\n2.ts?api_key=k\n#EXT-X-ENDLIST"));
+
+        let spec = TranscodeSpec {
+            input: PathBuf::from("/m/Movie.mkv"),
+            audio_file: None,
+            audio_stream_index: Some(2),
+            rendition,
+        };
+        let args = ffmpeg_args(
+            &spec,
+            &TranscodingConfig::default(),
+            10,
+            Path::new("/t/job"),
+        )
+        .join(" ");
+        assert!(args.starts_with("-hide_banner -loglevel error -ss 60.000 -i /m/Movie.mkv"));
+        assert!(args.contains("-map 0:v:0 -map 0:2"));
+        assert!(args.contains("-vf scale=-2:720 -b:v 3000000"));
+        assert!(args.contains("-start_number 10 -output_ts_offset 60.000"));
+        assert!(args.ends_with("-hls_segment_filename /t/job/%d.ts /t/job/ffmpeg.m3u8"));
+    }
+}
@@ -0,0 +1,466 @@
+// Release update checks
+//
+// The release feed (GitHub's latest-release API by default) is checked every
+// `updates.check_interval_hours`. A release newer than the running version sets
+// HasUpdateAvailable in /System/Info and is announced once on the event bus,
+// which puts it in the activity log and webhooks. With
+// `updates.allow_self_update` an admin can install it: the release asset built
+// for this OS and architecture (`jellyfin-rust-linux-x86_64`,
+// `jellyfin-rust-linux-aarch64`, ...) is downloaded next to the running binary,
+// checked against the SHA-256 published with the release (`<asset>.sha256` or
+// a `SHA256SUMS` file), swapped in, and the server re-executed. Releases
+// without a checksum are never installed, and containers are left to update
+// their image.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::UpdateConfig;
+use crate::events::{self, ServerEvent};
+use crate::version::Version;
+
+/// Version of the running server
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Delay before the first check, so startup isn't slowed by it
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(60);
+
+const FEED_TIMEOUT: Duration = Duration::from_secs(30);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Files a release may list checksums for all its assets in
+const CHECKSUM_FILES: &[&str] = &["SHA256SUMS", "sha256sums.txt", "checksums.txt"];
+
+#[derive(Debug, Clone, Deserialize)]
+struct Release {
+    tag_name: String,
+    html_url: String,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    published_at: Option<String>,
+    #[serde(default)]
+    assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ReleaseAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+impl Release {
+    fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Option<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+/// Result of the last check
+#[derive(Debug)]
+struct Checked {
+    release: Option<Release>,
+    checked_at: Option<String>,
+    error: Option<String>,
+    /// Last version announced on the event bus
+    announced: Option<String>,
+}
+
+static CHECKED: Mutex<Checked> = Mutex::new(Checked {
+    release: None,
+    checked_at: None,
+    error: None,
+    announced: None,
+});
+
+/// What the last check found
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct UpdateStatus {
+    pub current_version: String,
+    pub latest_version: Option<String>,
+    pub update_available: bool,
+    pub release_url: Option<String>,
+    pub release_notes: Option<String>,
+    pub published_at: Option<String>,
+    /// Release asset for this OS and architecture, if the release has one
+    pub asset_name: Option<String>,
+    pub last_checked: Option<String>,
+    pub last_error: Option<String>,
+    pub can_self_update: bool,
+    /// Why the update can't be installed from here
+    pub self_update_unavailable_reason: Option<String>,
+}
+
+/// Whether `candidate` is a later version than `current`
+///
+/// A release is later than pre-releases of the same version.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (Version::parse(candidate), Version::parse(current)) {
+        (Some(new), Some(old)) => new > old,
+        _ => false,
+    }
+}
+
+/// Name of the release asset built for this OS and architecture
+pub fn asset_name() -> String {
+    format!(
+        "jellyfin-rust-{}-{}{}",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// Find an asset's SHA-256 in a checksum file ("<hash>  <name>" lines, or
+/// just the hash for a single-file `.sha256`)
+pub fn checksum_for(sums: &str, asset: &str) -> Option<String> {
+    let is_hash = |s: &str| s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit());
+    let lines: Vec<&str> = sums
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let hash = match lines.as_slice() {
+        [single] if is_hash(single) => *single,
+        _ => lines.iter().find_map(|line| {
+            let (hash, name) = line.split_once(char::is_whitespace)?;
+            // "*name" marks binary mode in sha256sum output
+            let name = name.trim().trim_start_matches('*');
+            (name == asset && is_hash(hash)).then_some(hash)
+        })?,
+    };
+    Some(hash.to_ascii_lowercase())
+}
+
+/// Why updates can't be installed from here, if they can't
+fn self_update_unavailable(config: &UpdateConfig) -> Option<String> {
+    if !config.allow_self_update {
+        return Some("Self-update is disabled (updates.allow_self_update)".to_string());
+    }
+    if !cfg!(unix) {
+        return Some("Self-update is only supported on Linux and macOS".to_string());
+    }
+    if ["/.dockerenv", "/run/.containerenv"]
+        .iter()
+        .any(|p| std::path::Path::new(p).exists())
+    {
+        return Some("Running in a container; pull the new image instead".to_string());
+    }
+    None
+}
+
+/// Whether the last check found a newer version
+pub fn update_available() -> bool {
+    CHECKED
+        .lock()
+        .unwrap()
+        .release
+        .as_ref()
+        .is_some_and(|r| is_newer(r.version(), CURRENT_VERSION))
+}
+
+/// What the last check found
+pub fn status(config: &UpdateConfig) -> UpdateStatus {
+    let checked = CHECKED.lock().unwrap();
+    let release = checked.release.as_ref();
+    let update_available = release.is_some_and(|r| is_newer(r.version(), CURRENT_VERSION));
+    let asset = asset_name();
+    let unavailable = self_update_unavailable(config);
+
+    UpdateStatus {
+        current_version: CURRENT_VERSION.to_string(),
+        latest_version: release.map(|r| r.version().to_string()),
+        update_available,
+        release_url: release.map(|r| r.html_url.clone()),
+        release_notes: release.and_then(|r| r.body.clone()),
+        published_at: release.and_then(|r| r.published_at.clone()),
+        asset_name: release
+            .and_then(|r| r.asset(&asset))
+            .map(|a| a.name.clone()),
+        last_checked: checked.checked_at.clone(),
+        last_error: checked.error.clone(),
+        can_self_update: update_available && unavailable.is_none(),
+        self_update_unavailable_reason: unavailable,
+    }
+}
+
+fn http_client(timeout: Duration) -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(timeout)
+        .user_agent(concat!("jellyfin-rust/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+async fn fetch_release(config: &UpdateConfig) -> Result<Release> {
+    let response = http_client(FEED_TIMEOUT)?
+        .get(&config.feed_url)
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .context("Failed to reach the release feed")?
+        .error_for_status()
+        .context("Release feed returned an error")?;
+    response
+        .json()
+        .await
+        .context("Failed to read the release feed")
+}
+
+/// Check the release feed now
+pub async fn check(config: &UpdateConfig) -> Result<UpdateStatus> {
+    let result = fetch_release(config).await;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let announce = {
+        let mut checked = CHECKED.lock().unwrap();
+        checked.checked_at = Some(now);
+        match result {
+            Ok(release) => {
+                checked.error = None;
+                let version = release.version().to_string();
+                let announce = is_newer(&version, CURRENT_VERSION)
+                    && checked.announced.as_deref() != Some(version.as_str());
+                if announce {
+                    checked.announced = Some(version.clone());
+                }
+                let url = release.html_url.clone();
+                checked.release = Some(release);
+                announce.then_some((version, url))
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                checked.error = Some(message.clone());
+                drop(checked);
+                bail!(message);
+            }
+        }
+    };
+
+    if let Some((version, url)) = announce {
+        events::publish(ServerEvent::UpdateAvailable { version, url });
+    }
+    Ok(status(config))
+}
+
+/// Check for updates periodically until cancelled
+pub async fn run(config: UpdateConfig, cancel: CancellationToken) {
+    let interval = Duration::from_secs(config.check_interval_hours.max(1) * 3600);
+    let mut delay = FIRST_CHECK_DELAY;
+
+    loop {
+        tokio::select! {
+            _ = cancel.cancelled() => {
+                tracing::debug!("Update checker received shutdown signal");
+                break;
+            }
+            _ = tokio::time::sleep(delay) => {}
+        }
+        delay = interval;
+
+        match check(&config).await {
+            Ok(status) if status.update_available => tracing::info!(
+                "Version {} is available (running {})",
+                status.latest_version.unwrap_or_default(),
+                CURRENT_VERSION
+            ),
+            Ok(_) => tracing::debug!("No update available (running {})", CURRENT_VERSION),
+            Err(e) => tracing::warn!("Update check failed: {}", e),
+        }
+    }
+}
+
+/// Why an update can't be installed
+#[derive(Debug)]
+pub enum InstallError {
+    /// Self-update is off or unsupported here
+    Unavailable(String),
+    /// No newer release is known, or it has nothing to install for this platform
+    NothingToInstall(String),
+    Failed(anyhow::Error),
+}
+
+impl std::fmt::Display for InstallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallError::Unavailable(reason) | InstallError::NothingToInstall(reason) => {
+                f.write_str(reason)
+            }
+            InstallError::Failed(e) => write!(f, "{:#}", e),
+        }
+    }
+}
+
+impl From<anyhow::Error> for InstallError {
+    fn from(e: anyhow::Error) -> Self {
+        InstallError::Failed(e)
+    }
+}
+
+/// A downloaded and verified update, in place of the old binary
+pub struct Installed {
+    pub version: String,
+    /// Path of the server binary (now the new version)
+    pub executable: PathBuf,
+}
+
+/// Download, verify and swap in the latest release's binary
+///
+/// The server keeps running the old binary until it is restarted.
+pub async fn install(config: &UpdateConfig) -> Result<Installed, InstallError> {
+    if let Some(reason) = self_update_unavailable(config) {
+        return Err(InstallError::Unavailable(reason));
+    }
+    let release = CHECKED
+        .lock()
+        .unwrap()
+        .release
+        .clone()
+        .filter(|r| is_newer(r.version(), CURRENT_VERSION))
+        .ok_or_else(|| InstallError::NothingToInstall("No newer version is known".to_string()))?;
+
+    let name = asset_name();
+    let asset = release.asset(&name).ok_or_else(|| {
+        InstallError::NothingToInstall(format!("Release {} has no {}", release.tag_name, name))
+    })?;
+
+    let client = http_client(DOWNLOAD_TIMEOUT)?;
+    let download = |url: String| {
+        let client = client.clone();
+        async move {
+            let response = client.get(&url).send().await?.error_for_status()?;
+            anyhow::Ok(response.bytes().await?)
+        }
+    };
+
+    // The checksum comes with the release, so a truncated or tampered download
+    // isn't installed
+    let sums_asset = release
+        .asset(&format!("{}.sha256", name))
+        .or_else(|| CHECKSUM_FILES.iter().find_map(|f| release.asset(f)))
+        .ok_or_else(|| {
+            InstallError::NothingToInstall(format!(
+                "Release {} has no checksum for {}",
+                release.tag_name, name
+            ))
+        })?;
+    let sums = download(sums_asset.browser_download_url.clone())
+        .await
+        .context("Failed to download the checksum")?;
+    let expected = checksum_for(&String::from_utf8_lossy(&sums), &name)
+        .ok_or_else(|| anyhow!("{} doesn't list {}", sums_asset.name, name))?;
+
+    tracing::info!("Downloading {} from release {}", name, release.tag_name);
+    let binary = download(asset.browser_download_url.clone())
+        .await
+        .context("Failed to download the update")?;
+    let actual: String = Sha256::digest(&binary)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if actual != expected {
+        return Err(anyhow!(
+            "Checksum mismatch for {} (got {}, expected {})",
+            name,
+            actual,
+            expected
+        )
+        .into());
+    }
+
+    let executable = std::env::current_exe().context("Failed to locate the server binary")?;
+    let staged = executable.with_file_name(format!(
+        ".{}.update",
+        executable
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    ));
+    tokio::fs::write(&staged, &binary)
+        .await
+        .with_context(|| format!("Failed to write {}", staged.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        tokio::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .await
+            .context("Failed to make the update executable")?;
+    }
+    // A rename in the same folder replaces the binary atomically; the running
+    // process keeps the old file open
+    if let Err(e) = tokio::fs::rename(&staged, &executable).await {
+        let _ = tokio::fs::remove_file(&staged).await;
+        return Err(anyhow!("Failed to replace {}: {}", executable.display(), e).into());
+    }
+
+    tracing::info!(
+        "Installed version {} to {}",
+        release.version(),
+        executable.display()
+    );
+    Ok(Installed {
+        version: release.version().to_string(),
+        executable,
+    })
+}
+
+/// Replace this process with the given binary, keeping the arguments
+///
+/// Falls back to exiting, for a process manager to restart the server.
+pub fn restart_into(executable: PathBuf) {
+    tokio::spawn(async move {
+        // Let the response go out first
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        tracing::info!("Restarting into the new version...");
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let error = std::process::Command::new(&executable)
+                .args(std::env::args_os().skip(1))
+                .exec();
+            tracing::error!("Failed to restart into {}: {}", executable.display(), error);
+        }
+        std::process::exit(0);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_and_checksums() {
+        assert!(is_newer("v0.2.0", "0.1.0"));
+        assert!(is_newer("0.1.10", "0.1.9"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(is_newer("0.2.0", "0.2.0-rc1"));
+        assert!(!is_newer("0.2.0-rc1", "0.2.0"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+
+        let hash = "a".repeat(64);
+        let sums = format!(
+            "{}  jellyfin-rust-linux-aarch64\n{} *jellyfin-rust-linux-x86_64\n",
+            "b".repeat(64),
+            hash
+        );
+        assert_eq!(
+            checksum_for(&sums, "jellyfin-rust-linux-x86_64").as_deref(),
+            Some(hash.as_str())
+        );
+        assert_eq!(checksum_for(&sums, "jellyfin-rust-macos-aarch64"), None);
+        // A lone .sha256 file may hold just the hash
+        assert_eq!(
+            checksum_for(&format!("{}\n", hash.to_uppercase()), "anything").as_deref(),
+            Some(hash.as_str())
+        );
+    }
+}
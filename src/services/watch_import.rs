@@ -0,0 +1,646 @@
+// Watch state import from Plex and Kodi
+//
+// Reads played/resume state (and optionally favorites) from another media
+// server's database or export, matches the entries to our items and writes
+// them into playback_progress/user_favorites. Supported sources:
+// - Plex: com.plexapp.plugins.library.db (or a copy of it)
+// - Kodi: MyVideosNN.db, a videodb.xml library export, or favourites.xml
+//
+// Items are matched by file path (after optional prefix rewriting, since the
+// other server usually sees the media under a different mount), then by a
+// unique file name, then by IMDb/TMDB ID for movies. Imported state never
+// lowers what is already recorded here.
+
+use anyhow::{anyhow, bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, SqlitePool};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::LazyLock;
+
+use super::xml;
+use crate::time::Ticks;
+
+/// Unmatched entries listed in the summary (the rest are only counted)
+const MAX_UNMATCHED_SAMPLES: usize = 20;
+
+static RE_PLEX_AGENT_GUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^com\.plexapp\.agents\.(imdb|themoviedb|thetvdb)://([^/?]+)").unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WatchStateSource {
+    Plex,
+    Kodi,
+}
+
+/// One watched/resumable/rated entry read from the other server
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WatchRecord {
+    pub path: Option<String>,
+    /// Provider name ("imdb", "tmdb", "tvdb") and ID
+    pub provider_ids: Vec<(String, String)>,
+    pub play_count: i32,
+    pub position_seconds: f64,
+    /// RFC 3339
+    pub last_played: Option<String>,
+    /// User rating on a 0-10 scale
+    pub rating: Option<f64>,
+    /// Explicit favorite (Kodi favourites.xml)
+    pub favorite: bool,
+}
+
+/// Rewrites the other server's path prefix to ours
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PathMapping {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    pub path_mappings: Vec<PathMapping>,
+    /// Ratings at or above this (0-10) become favorites; None imports no rating favorites
+    pub favorite_min_rating: Option<f64>,
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ImportSummary {
+    pub total: usize,
+    pub matched: usize,
+    pub played: usize,
+    pub in_progress: usize,
+    pub favorites: usize,
+    pub unmatched: usize,
+    pub unmatched_samples: Vec<String>,
+    /// Items whose user data was written (empty on a dry run)
+    #[serde(skip)]
+    pub changed_item_ids: Vec<String>,
+}
+
+/// Read watch state from a Plex or Kodi file, detecting the source if not given
+pub async fn read_watch_records(
+    file: &Path,
+    source: Option<WatchStateSource>,
+    plex_account_id: Option<i64>,
+) -> Result<Vec<WatchRecord>> {
+    let is_xml = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("xml"));
+
+    if is_xml {
+        if source == Some(WatchStateSource::Plex) {
+            bail!("Plex imports need the library database, not an XML file");
+        }
+        let text = tokio::fs::read_to_string(file)
+            .await
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        return parse_kodi_xml(&text);
+    }
+
+    let mut conn = SqliteConnectOptions::new()
+        .filename(file)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| format!("Failed to open {}", file.display()))?;
+
+    let tables: HashSet<String> =
+        sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .fetch_all(&mut conn)
+            .await?
+            .into_iter()
+            .collect();
+
+    let source = match source {
+        Some(source) => source,
+        None if tables.contains("metadata_item_settings") => WatchStateSource::Plex,
+        None if tables.contains("files") && tables.contains("bookmark") => WatchStateSource::Kodi,
+        None => bail!("Not a Plex or Kodi video database"),
+    };
+
+    match source {
+        WatchStateSource::Plex => read_plex_db(&mut conn, plex_account_id.unwrap_or(1)).await,
+        WatchStateSource::Kodi => read_kodi_db(&mut conn).await,
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PlexRow {
+    file: Option<String>,
+    guid: String,
+    view_count: Option<i64>,
+    /// Milliseconds
+    view_offset: Option<i64>,
+    /// Unix time
+    last_viewed_at: Option<i64>,
+    rating: Option<f64>,
+    /// Space separated "imdb://tt..." style tags
+    external_ids: Option<String>,
+}
+
+async fn read_plex_db(
+    conn: &mut sqlx::SqliteConnection,
+    account_id: i64,
+) -> Result<Vec<WatchRecord>> {
+    // metadata_type 1 = movie, 4 = episode. New-style plex:// guids keep
+    // external IDs as tags (tag_type 314, e.g. "imdb://tt0111161").
+    let rows: Vec<PlexRow> = sqlx::query_as(
+        r#"SELECT mp.file, mi.guid, s.view_count, s.view_offset, s.last_viewed_at, CAST(s.rating AS REAL) AS rating,
+                  (SELECT group_concat(t.tag, ' ') FROM taggings tg JOIN tags t ON t.id = tg.tag_id
+                   WHERE tg.metadata_item_id = mi.id AND t.tag_type = 314) AS external_ids
+           FROM metadata_item_settings s
+           JOIN metadata_items mi ON mi.guid = s.guid
+           LEFT JOIN media_items m ON m.metadata_item_id = mi.id
+           LEFT JOIN media_parts mp ON mp.media_item_id = m.id
+           WHERE s.account_id = ? AND mi.metadata_type IN (1, 4)"#,
+    )
+    .bind(account_id)
+    .fetch_all(&mut *conn)
+    .await
+    .context("Failed to read Plex watch state")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let mut provider_ids = parse_plex_guid(&row.guid).into_iter().collect::<Vec<_>>();
+            for tag in row
+                .external_ids
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+            {
+                provider_ids.extend(parse_plex_guid(tag));
+            }
+            WatchRecord {
+                path: row.file,
+                provider_ids,
+                play_count: row.view_count.unwrap_or(0) as i32,
+                position_seconds: row.view_offset.unwrap_or(0) as f64 / 1000.0,
+                last_played: row
+                    .last_viewed_at
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    .map(|t| t.to_rfc3339()),
+                rating: row.rating,
+                favorite: false,
+            }
+        })
+        .collect())
+}
+
+/// Provider ID from a Plex guid or external ID tag
+fn parse_plex_guid(guid: &str) -> Option<(String, String)> {
+    if let Some(caps) = RE_PLEX_AGENT_GUID.captures(guid) {
+        let provider = match &caps[1] {
+            "themoviedb" => "tmdb",
+            "thetvdb" => "tvdb",
+            other => other,
+        };
+        return Some((provider.to_string(), caps[2].to_string()));
+    }
+    let (provider, id) = guid.split_once("://")?;
+    matches!(provider, "imdb" | "tmdb" | "tvdb").then(|| (provider.to_string(), id.to_string()))
+}
+
+#[derive(sqlx::FromRow)]
+struct KodiRow {
+    path: String,
+    play_count: Option<i64>,
+    last_played: Option<String>,
+    /// Seconds
+    resume_position: Option<f64>,
+    rating: Option<f64>,
+    /// Space separated "imdb://tt..." style IDs
+    unique_ids: Option<String>,
+}
+
+async fn read_kodi_db(conn: &mut sqlx::SqliteConnection) -> Result<Vec<WatchRecord>> {
+    // Bookmark type 1 is the resume point
+    let rows: Vec<KodiRow> = sqlx::query_as(
+        r#"SELECT p.strPath || f.strFilename AS path, f.playCount AS play_count, f.lastPlayed AS last_played,
+                  (SELECT CAST(MAX(b.timeInSeconds) AS REAL) FROM bookmark b WHERE b.idFile = f.idFile AND b.type = 1) AS resume_position,
+                  CAST(COALESCE(mv.userrating, ep.userrating) AS REAL) AS rating,
+                  (SELECT group_concat(u.type || '://' || u.value, ' ') FROM uniqueid u
+                   WHERE (u.media_type = 'movie' AND u.media_id = mv.idMovie)
+                      OR (u.media_type = 'episode' AND u.media_id = ep.idEpisode)) AS unique_ids
+           FROM files f
+           JOIN path p ON p.idPath = f.idPath
+           LEFT JOIN movie mv ON mv.idFile = f.idFile
+           LEFT JOIN episode ep ON ep.idFile = f.idFile
+           WHERE mv.idMovie IS NOT NULL OR ep.idEpisode IS NOT NULL"#,
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .context("Failed to read Kodi watch state")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| WatchRecord {
+            path: Some(row.path),
+            provider_ids: row
+                .unique_ids
+                .as_deref()
+                .unwrap_or_default()
+                .split_whitespace()
+                .filter_map(|id| id.split_once("://"))
+                .map(|(provider, id)| (provider.to_string(), id.to_string()))
+                .collect(),
+            play_count: row.play_count.unwrap_or(0) as i32,
+            position_seconds: row.resume_position.unwrap_or(0.0),
+            last_played: row.last_played.as_deref().and_then(kodi_time_to_rfc3339),
+            rating: row.rating.filter(|r| *r > 0.0),
+            favorite: false,
+        })
+        .collect())
+}
+
+/// Kodi stores "YYYY-MM-DD HH:MM:SS"
+fn kodi_time_to_rfc3339(value: &str) -> Option<String> {
+    chrono::NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.and_utc().to_rfc3339())
+}
+
+/// Parse a Kodi videodb.xml export or a favourites.xml file
+pub fn parse_kodi_xml(text: &str) -> Result<Vec<WatchRecord>> {
+    let root = xml::parse(text).context("Invalid Kodi XML file")?;
+    if root.name == "favourites" {
+        return Ok(root
+            .children("favourite")
+            .filter_map(|favourite| {
+                // PlayMedia("smb://nas/movies/Film.mkv")
+                let path = favourite
+                    .text()?
+                    .strip_prefix("PlayMedia(")?
+                    .strip_suffix(')')?
+                    .trim_matches('"');
+                Some(WatchRecord {
+                    path: Some(path.to_string()).filter(|p| !p.is_empty()),
+                    favorite: true,
+                    ..Default::default()
+                })
+            })
+            .collect());
+    }
+
+    let mut entries = root.descendants("movie");
+    entries.extend(root.descendants("episodedetails"));
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let tag = |name| entry.child_text(name);
+            let resume = |name| entry.child("resume").and_then(|r| r.child_text(name));
+            WatchRecord {
+                path: tag("filenameandpath").map(String::from),
+                provider_ids: entry
+                    .children("uniqueid")
+                    .filter_map(|id| {
+                        Some((id.attribute("type")?.to_string(), id.text()?.to_string()))
+                    })
+                    .collect(),
+                play_count: tag("playcount").and_then(|v| v.parse().ok()).unwrap_or(0),
+                position_seconds: resume("position")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0.0),
+                last_played: tag("lastplayed").and_then(kodi_time_to_rfc3339),
+                rating: tag("userrating")
+                    .and_then(|v| v.parse().ok())
+                    .filter(|r: &f64| *r > 0.0),
+                favorite: false,
+            }
+        })
+        .collect())
+}
+
+/// Our item IDs indexed the ways imported records can be matched
+struct ItemIndex {
+    by_path: HashMap<String, String>,
+    /// Lowercased file name -> item ID, or None when the name is ambiguous
+    by_file_name: HashMap<String, Option<String>>,
+    by_provider: HashMap<(String, String), String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct IndexedItem {
+    id: String,
+    path: Option<String>,
+    imdb_id: Option<String>,
+    tmdb_id: Option<String>,
+    item_type: String,
+}
+
+impl ItemIndex {
+    async fn load(pool: &SqlitePool) -> Result<Self> {
+        let items: Vec<IndexedItem> = sqlx::query_as(
+            "SELECT id, path, imdb_id, tmdb_id, item_type FROM media_items WHERE item_type IN ('Movie', 'Episode')",
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut index = Self {
+            by_path: HashMap::new(),
+            by_file_name: HashMap::new(),
+            by_provider: HashMap::new(),
+        };
+        for IndexedItem {
+            id,
+            path,
+            imdb_id,
+            tmdb_id,
+            item_type,
+        } in items
+        {
+            if let Some(path) = path {
+                if let Some(name) = file_name_key(&path) {
+                    index
+                        .by_file_name
+                        .entry(name)
+                        .and_modify(|existing| *existing = None)
+                        .or_insert_with(|| Some(id.clone()));
+                }
+                index.by_path.insert(path, id.clone());
+            }
+            // Series-level IDs are not stored on episodes, so only movies match by ID
+            if item_type == "Movie" {
+                if let Some(imdb_id) = imdb_id {
+                    index
+                        .by_provider
+                        .insert(("imdb".to_string(), imdb_id), id.clone());
+                }
+                if let Some(tmdb_id) = tmdb_id {
+                    index.by_provider.insert(("tmdb".to_string(), tmdb_id), id);
+                }
+            }
+        }
+        Ok(index)
+    }
+
+    fn find(&self, record: &WatchRecord, mappings: &[PathMapping]) -> Option<&String> {
+        if let Some(path) = record.path.as_deref() {
+            let path = map_path(path, mappings);
+            if let Some(id) = self.by_path.get(&path) {
+                return Some(id);
+            }
+            if let Some(Some(id)) = file_name_key(&path).and_then(|n| self.by_file_name.get(&n)) {
+                return Some(id);
+            }
+        }
+        record
+            .provider_ids
+            .iter()
+            .find_map(|key| self.by_provider.get(key))
+    }
+}
+
+fn file_name_key(path: &str) -> Option<String> {
+    path.rsplit(['/', '\\'])
+        .next()
+        .filter(|n| !n.is_empty())
+        .map(|n| n.to_lowercase())
+}
+
+/// Apply the first matching prefix rewrite and normalize Windows separators
+pub fn map_path(path: &str, mappings: &[PathMapping]) -> String {
+    let mapped = mappings
+        .iter()
+        .find(|m| !m.from.is_empty() && path.starts_with(&m.from))
+        .map(|m| format!("{}{}", m.to, &path[m.from.len()..]))
+        .unwrap_or_else(|| path.to_string());
+    if mapped.contains('\\') && !mapped.starts_with('/') {
+        mapped.replace('\\', "/")
+    } else {
+        mapped
+    }
+}
+
+/// Match records to items and write the user's watch state
+pub async fn import_watch_state(
+    pool: &SqlitePool,
+    user_id: &str,
+    records: &[WatchRecord],
+    options: &ImportOptions,
+) -> Result<ImportSummary> {
+    let index = ItemIndex::load(pool).await?;
+    let mut summary = ImportSummary {
+        total: records.len(),
+        ..Default::default()
+    };
+
+    // Plex lists an item once per file part, keep the strongest state per item
+    let mut matched: HashMap<String, WatchRecord> = HashMap::new();
+    for record in records {
+        let Some(item_id) = index.find(record, &options.path_mappings) else {
+            summary.unmatched += 1;
+            if summary.unmatched_samples.len() < MAX_UNMATCHED_SAMPLES {
+                summary.unmatched_samples.push(
+                    record
+                        .path
+                        .clone()
+                        .or_else(|| {
+                            record
+                                .provider_ids
+                                .first()
+                                .map(|(p, id)| format!("{}://{}", p, id))
+                        })
+                        .unwrap_or_else(|| "(no path or ID)".to_string()),
+                );
+            }
+            continue;
+        };
+        let entry = matched.entry(item_id.clone()).or_default();
+        entry.play_count = entry.play_count.max(record.play_count);
+        entry.position_seconds = entry.position_seconds.max(record.position_seconds);
+        entry.last_played = entry.last_played.clone().max(record.last_played.clone());
+        entry.rating = match (entry.rating, record.rating) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        entry.favorite |= record.favorite;
+    }
+    summary.matched = matched.len();
+
+    let mut tx = pool.begin().await?;
+    for (item_id, state) in &matched {
+        let played = state.play_count > 0;
+        let position_ticks = Ticks::from_secs_f64(state.position_seconds).0;
+        let favorite = state.favorite
+            || options
+                .favorite_min_rating
+                .zip(state.rating)
+                .is_some_and(|(min, rating)| rating >= min);
+
+        if played {
+            summary.played += 1;
+        } else if position_ticks > 0 {
+            summary.in_progress += 1;
+        }
+        if favorite {
+            summary.favorites += 1;
+        }
+        if options.dry_run || (!played && position_ticks == 0 && !favorite) {
+            continue;
+        }
+
+        if played || position_ticks > 0 {
+            // Never lower existing state: keep our resume point unless the item is
+            // now played, and keep the higher play count and later date
+            sqlx::query(
+                r#"
+                INSERT INTO playback_progress (user_id, item_id, position_ticks, played, play_count, last_played)
+                VALUES (?, ?, ?, ?, ?, ?)
+                ON CONFLICT (user_id, item_id) DO UPDATE SET
+                    position_ticks = CASE
+                        WHEN excluded.played = 1 AND playback_progress.played = 0 THEN 0
+                        WHEN playback_progress.position_ticks = 0 AND playback_progress.played = 0 THEN excluded.position_ticks
+                        ELSE playback_progress.position_ticks END,
+                    played = MAX(playback_progress.played, excluded.played),
+                    play_count = MAX(playback_progress.play_count, excluded.play_count),
+                    last_played = NULLIF(MAX(COALESCE(playback_progress.last_played, ''), COALESCE(excluded.last_played, '')), '')
+                "#,
+            )
+            .bind(user_id)
+            .bind(item_id)
+            .bind(if played { 0 } else { position_ticks })
+            .bind(played)
+            .bind(state.play_count)
+            .bind(&state.last_played)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        if favorite {
+            sqlx::query("INSERT OR IGNORE INTO user_favorites (user_id, item_id) VALUES (?, ?)")
+                .bind(user_id)
+                .bind(item_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        summary.changed_item_ids.push(item_id.clone());
+    }
+    tx.commit()
+        .await
+        .map_err(|e| anyhow!("Failed to save imported watch state: {}", e))?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kodi_xml_export() {
+        let xml = r#"<videodb>
+            <movie>
+                <title>The Matrix</title>
+                <userrating>10</userrating>
+                <playcount>2</playcount>
+                <lastplayed>2024-03-01 20:15:00</lastplayed>
+                <uniqueid type="imdb" default="true">tt0133093</uniqueid>
+                <uniqueid type="tmdb">603</uniqueid>
+                <filenameandpath>smb://nas/movies/The Matrix (1999).mkv</filenameandpath>
+                <resume><position>0.000000</position><total>0.000000</total></resume>
+            </movie>
+            <tvshow><title>Show</title>
+                <episodedetails>
+                    <playcount>0</playcount>
+                    <filenameandpath>smb://nas/tv/Show/S01E02 &amp; more.mkv</filenameandpath>
+                    <resume><position>612.5</position><total>1400</total></resume>
+                </episodedetails>
+            </tvshow>
+        </videodb>"#;
+        let records = parse_kodi_xml(xml).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].play_count, 2);
+        assert_eq!(records[0].rating, Some(10.0));
+        assert_eq!(
+            records[0].last_played.as_deref(),
+            Some("2024-03-01T20:15:00+00:00")
+        );
+        assert!(records[0]
+            .provider_ids
+            .contains(&("imdb".to_string(), "tt0133093".to_string())));
+        assert_eq!(
+            records[1].path.as_deref(),
+            Some("smb://nas/tv/Show/S01E02 & more.mkv")
+        );
+        assert_eq!(records[1].position_seconds, 612.5);
+
+        let favourites = parse_kodi_xml(
+            r#"<favourites><favourite name="Film">PlayMedia(&quot;/media/film.mkv&quot;)</favourite></favourites>"#,
+        )
+        .unwrap();
+        assert_eq!(favourites.len(), 1);
+        assert!(favourites[0].favorite);
+        assert_eq!(favourites[0].path.as_deref(), Some("/media/film.mkv"));
+    }
+
+    #[test]
+    fn test_parse_kodi_xml_numeric_entities() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes" ?>
+<videodb version="1">
+    <movie>
+        <title>Am&#233;lie</title>
+        <playcount>1</playcount>
+        <uniqueid type="imdb">tt0211915</uniqueid>
+        <filenameandpath>/media/movies/Am&#233;lie (2001)/Am&#xE9;lie&#39;s World.mkv</filenameandpath>
+    </movie>
+</videodb>"#;
+        let records = parse_kodi_xml(xml).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].path.as_deref(),
+            Some("/media/movies/Am\u{e9}lie (2001)/Am\u{e9}lie's World.mkv")
+        );
+
+        let favourites = parse_kodi_xml(
+            r#"<favourites>
+    <favourite name="Caf&#233;" thumb="">PlayMedia(&quot;/media/Caf&#xE9; &amp; Bar.mkv&quot;)</favourite>
+    <favourite name="Addon">ActivateWindow(10025,&quot;plugin://x&quot;)</favourite>
+</favourites>"#,
+        )
+        .unwrap();
+        assert_eq!(favourites.len(), 1);
+        assert_eq!(
+            favourites[0].path.as_deref(),
+            Some("/media/Caf\u{e9} & Bar.mkv")
+        );
+
+        assert!(parse_kodi_xml("<videodb><movie>").is_err());
+    }
+
+    #[test]
+    fn test_plex_guids_and_path_mapping() {
+        assert_eq!(
+            parse_plex_guid("com.plexapp.agents.imdb://tt0111161?lang=en"),
+            Some(("imdb".to_string(), "tt0111161".to_string()))
+        );
+        assert_eq!(
+            parse_plex_guid("com.plexapp.agents.thetvdb://81189/1/1?lang=en"),
+            Some(("tvdb".to_string(), "81189".to_string()))
+        );
+        assert_eq!(
+            parse_plex_guid("tmdb://603"),
+            Some(("tmdb".to_string(), "603".to_string()))
+        );
+        assert_eq!(parse_plex_guid("plex://movie/5d7768"), None);
+
+        let mappings = vec![PathMapping {
+            from: "smb://nas/".to_string(),
+            to: "/mnt/media/".to_string(),
+        }];
+        assert_eq!(
+            map_path("smb://nas/movies/a.mkv", &mappings),
+            "/mnt/media/movies/a.mkv"
+        );
+        assert_eq!(map_path(r"D:\Movies\a.mkv", &[]), "D:/Movies/a.mkv");
+    }
+}
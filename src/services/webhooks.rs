@@ -0,0 +1,356 @@
+// Outgoing webhooks
+//
+// Each [[webhooks]] entry in the config is an HTTP endpoint that gets a POST
+// when something happens on the server: an item is added, a scan finishes,
+// playback starts or stops, an account is created. Events come from the event
+// bus and are delivered one at a time in the order they happened. The body is
+// either the event's values as a JSON object or a template with {{Placeholder}}
+// values filled in, so services like Discord and ntfy can be used directly.
+// Failed deliveries are logged and not retried.
+
+use anyhow::Result;
+use regex::Regex;
+use serde_json::{Map, Value};
+use sqlx::SqlitePool;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+
+use super::mediainfo;
+use crate::config::WebhookConfig;
+use crate::events::{self, ServerEvent};
+
+/// Events webhooks can subscribe to
+pub const EVENTS: &[&str] = &[
+    "ItemAdded",
+    "ScanCompleted",
+    "PlaybackStarted",
+    "PlaybackStopped",
+    "UserCreated",
+    "UpdateAvailable",
+];
+
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+static RE_PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap());
+
+/// Name of an event webhooks deliver, None for the rest
+fn event_name(event: &ServerEvent) -> Option<&'static str> {
+    match event {
+        ServerEvent::ItemAdded { .. } => Some("ItemAdded"),
+        ServerEvent::ScanCompleted { .. } => Some("ScanCompleted"),
+        ServerEvent::PlaybackStarted { .. } => Some("PlaybackStarted"),
+        ServerEvent::PlaybackStopped { .. } => Some("PlaybackStopped"),
+        ServerEvent::UserCreated { .. } => Some("UserCreated"),
+        ServerEvent::UpdateAvailable { .. } => Some("UpdateAvailable"),
+        _ => None,
+    }
+}
+
+/// Whether a webhook wants an event
+fn wants(hook: &WebhookConfig, event_name: &str) -> bool {
+    hook.events.is_empty()
+        || hook
+            .events
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(event_name))
+}
+
+/// An item with its parent's name (the series, for episodes)
+#[derive(sqlx::FromRow)]
+struct ItemRow {
+    name: String,
+    item_type: String,
+    year: Option<i32>,
+    parent_index_number: Option<i32>,
+    index_number: Option<i32>,
+    parent_name: Option<String>,
+}
+
+/// Add an item's name and numbering to the values
+async fn add_item_values(pool: &SqlitePool, item_id: &str, values: &mut Map<String, Value>) {
+    let item: Option<ItemRow> = sqlx::query_as(
+        "SELECT i.name, i.item_type, i.year, i.parent_index_number, i.index_number,
+                p.name AS parent_name
+         FROM media_items i LEFT JOIN media_items p ON p.id = i.parent_id
+         WHERE i.id = ?",
+    )
+    .bind(item_id)
+    .fetch_optional(pool)
+    .await
+    .ok()
+    .flatten();
+    let Some(ItemRow {
+        name,
+        item_type,
+        year,
+        parent_index_number: season,
+        index_number: episode,
+        parent_name,
+    }) = item
+    else {
+        return;
+    };
+
+    // "Show - S01E02 - Title" for episodes, "Title (2024)" for the rest
+    let display_name = match (item_type.as_str(), &parent_name, season, episode) {
+        ("Episode", Some(series), Some(s), Some(e)) => {
+            format!("{} - S{:02}E{:02} - {}", series, s, e, name)
+        }
+        _ => match year {
+            Some(year) => format!("{} ({})", name, year),
+            None => name.clone(),
+        },
+    };
+    values.insert("ItemName".into(), name.into());
+    values.insert("ItemType".into(), item_type.clone().into());
+    values.insert("ItemDisplayName".into(), display_name.into());
+    if let Some(year) = year {
+        values.insert("Year".into(), year.into());
+    }
+    if item_type == "Episode" {
+        if let Some(series) = parent_name {
+            values.insert("SeriesName".into(), series.into());
+        }
+        if let Some(season) = season {
+            values.insert("SeasonNumber".into(), season.into());
+        }
+        if let Some(episode) = episode {
+            values.insert("EpisodeNumber".into(), episode.into());
+        }
+    }
+}
+
+async fn add_name(
+    pool: &SqlitePool,
+    query: &str,
+    id: &str,
+    key: &str,
+    values: &mut Map<String, Value>,
+) {
+    let name: Option<(String,)> = sqlx::query_as(query)
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten();
+    if let Some((name,)) = name {
+        values.insert(key.into(), name.into());
+    }
+}
+
+async fn add_user_name(pool: &SqlitePool, user_id: &str, values: &mut Map<String, Value>) {
+    add_name(
+        pool,
+        "SELECT name FROM users WHERE id = ?",
+        user_id,
+        "UserName",
+        values,
+    )
+    .await
+}
+
+async fn add_library_name(pool: &SqlitePool, library_id: &str, values: &mut Map<String, Value>) {
+    add_name(
+        pool,
+        "SELECT name FROM libraries WHERE id = ?",
+        library_id,
+        "LibraryName",
+        values,
+    )
+    .await
+}
+
+/// Values of an event for templates: the event's own fields plus the names of
+/// the item, user and library they refer to
+async fn event_values(pool: &SqlitePool, name: &str, event: &ServerEvent) -> Map<String, Value> {
+    let mut values = match serde_json::to_value(event) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+    values.remove("Type");
+    values.insert("Event".into(), name.into());
+    values.insert("Date".into(), chrono::Utc::now().to_rfc3339().into());
+
+    match event {
+        ServerEvent::ItemAdded {
+            item_id,
+            library_id,
+            ..
+        } => {
+            add_item_values(pool, item_id, &mut values).await;
+            add_library_name(pool, library_id, &mut values).await;
+        }
+        ServerEvent::ScanCompleted { library_id, .. } => {
+            add_library_name(pool, library_id, &mut values).await;
+        }
+        ServerEvent::PlaybackStarted {
+            user_id, item_id, ..
+        } => {
+            add_item_values(pool, item_id, &mut values).await;
+            add_user_name(pool, user_id, &mut values).await;
+        }
+        ServerEvent::PlaybackStopped {
+            user_id,
+            item_id,
+            position_ticks,
+            ..
+        } => {
+            add_item_values(pool, item_id, &mut values).await;
+            add_user_name(pool, user_id, &mut values).await;
+            values.insert(
+                "Position".into(),
+                mediainfo::format_duration(*position_ticks).into(),
+            );
+        }
+        _ => {}
+    }
+    values
+}
+
+/// Fill a template's {{Placeholder}}s from the values
+///
+/// Unknown placeholders become empty. With `json` set, values are escaped for
+/// use inside JSON strings.
+pub fn render(template: &str, values: &Map<String, Value>, json: bool) -> String {
+    RE_PLACEHOLDER
+        .replace_all(template, |caps: &regex::Captures| {
+            let text = match values.get(&caps[1]) {
+                Some(Value::String(s)) => s.clone(),
+                Some(Value::Null) | None => String::new(),
+                Some(other) => other.to_string(),
+            };
+            if json {
+                let quoted = Value::String(text).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            } else {
+                text
+            }
+        })
+        .into_owned()
+}
+
+/// POST an event to a webhook
+async fn deliver(
+    client: &reqwest::Client,
+    hook: &WebhookConfig,
+    values: &Map<String, Value>,
+) -> Result<()> {
+    let body = match &hook.template {
+        Some(template) => render(template, values, hook.content_type.contains("json")),
+        None => serde_json::to_string(values)?,
+    };
+
+    let mut request = client
+        .post(&hook.url)
+        .header(reqwest::header::CONTENT_TYPE, &hook.content_type)
+        .body(body);
+    for (name, value) in &hook.headers {
+        request = request.header(name, render(value, values, false));
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        anyhow::bail!("HTTP {}", response.status());
+    }
+    Ok(())
+}
+
+/// Deliver events to the configured webhooks until shutdown
+pub async fn run(pool: SqlitePool, hooks: Vec<WebhookConfig>, cancel: CancellationToken) {
+    let hooks: Vec<WebhookConfig> = hooks
+        .into_iter()
+        .filter(|hook| {
+            if hook.url.is_empty() {
+                tracing::warn!("Ignoring webhook without a url");
+            }
+            for event in &hook.events {
+                if !EVENTS.iter().any(|e| e.eq_ignore_ascii_case(event)) {
+                    tracing::warn!("Webhook {} lists unknown event '{}'", hook.url, event);
+                }
+            }
+            !hook.url.is_empty()
+        })
+        .collect();
+    if hooks.is_empty() {
+        return;
+    }
+    tracing::info!("Delivering server events to {} webhook(s)", hooks.len());
+
+    let client = match reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .user_agent(concat!("jellyfin-rust/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!("Webhooks disabled, failed to create HTTP client: {}", e);
+            return;
+        }
+    };
+
+    let mut events = events::subscribe();
+    loop {
+        let event = tokio::select! {
+            _ = cancel.cancelled() => break,
+            event = events.recv() => event,
+        };
+
+        let event = match event {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!("Webhooks missed {} events", skipped);
+                continue;
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+        let Some(name) = event_name(&event) else {
+            continue;
+        };
+        if !hooks.iter().any(|hook| wants(hook, name)) {
+            continue;
+        }
+
+        let values = event_values(&pool, name, &event).await;
+        for hook in hooks.iter().filter(|hook| wants(hook, name)) {
+            if let Err(e) = deliver(&client, hook, &values).await {
+                tracing::warn!("Webhook {} failed for {}: {}", hook.url, name, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_and_filter() {
+        let mut values = Map::new();
+        values.insert("Event".into(), "ItemAdded".into());
+        values.insert("ItemDisplayName".into(), "Say \"Hi\" (2024)".into());
+        values.insert("Year".into(), 2024.into());
+
+        let template = r#"{"content": "{{ItemDisplayName}} added ({{ Year }}){{Missing}}"}"#;
+        let body = render(template, &values, true);
+        assert_eq!(body, r#"{"content": "Say \"Hi\" (2024) added (2024)"}"#);
+        assert!(serde_json::from_str::<Value>(&body).is_ok());
+        assert_eq!(
+            render("{{Event}}: {{ItemDisplayName}}", &values, false),
+            "ItemAdded: Say \"Hi\" (2024)"
+        );
+
+        let mut hook = WebhookConfig::default();
+        assert!(wants(&hook, "PlaybackStarted"));
+        hook.events = vec!["scancompleted".to_string()];
+        assert!(wants(&hook, "ScanCompleted"));
+        assert!(!wants(&hook, "ItemAdded"));
+
+        let event = ServerEvent::ItemUpdated {
+            item_id: "i".to_string(),
+        };
+        assert_eq!(event_name(&event), None);
+    }
+}
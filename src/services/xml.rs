@@ -0,0 +1,180 @@
+// XML documents read into a tree
+//
+// XMLTV guides, DLNA SOAP requests and Kodi exports are parsed with quick-xml
+// into a small element tree. Entities (named and numeric) and CDATA sections
+// are resolved while reading, so callers only look elements up by name.
+// Namespace prefixes are dropped (`<u:Browse>` is `Browse`); none of these
+// documents mixes vocabularies that share names.
+
+use anyhow::{bail, Context, Result};
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Reader, XmlVersion};
+
+/// An element with its attributes, text and child elements
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    attributes: Vec<(String, String)>,
+    /// Text directly inside the element, CDATA included
+    text: String,
+    pub children: Vec<Element>,
+}
+
+impl Element {
+    fn open(start: &BytesStart) -> Self {
+        let attributes = start
+            .attributes()
+            .flatten()
+            .map(|attr| {
+                let value = attr
+                    .normalized_value(XmlVersion::Implicit1_0)
+                    .map(|v| v.into_owned())
+                    // An unknown entity keeps the value as written
+                    .unwrap_or_else(|_| attr.value.to_string());
+                (attr.key.local_name().as_ref().to_string(), value)
+            })
+            .collect();
+        Element {
+            name: start.local_name().as_ref().to_string(),
+            attributes,
+            ..Default::default()
+        }
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The element's text, trimmed; None when there is none
+    pub fn text(&self) -> Option<&str> {
+        Some(self.text.trim()).filter(|text| !text.is_empty())
+    }
+
+    /// Child elements named `name`
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Text of the first `name` child that has any
+    pub fn child_text(&self, name: &str) -> Option<&str> {
+        self.children
+            .iter()
+            .filter(|child| child.name == name)
+            .find_map(Element::text)
+    }
+
+    /// Elements named `name` at any depth below this one, in document order;
+    /// a match's own children aren't searched
+    pub fn descendants<'a>(&'a self, name: &str) -> Vec<&'a Element> {
+        let mut found = Vec::new();
+        for child in &self.children {
+            if child.name == name {
+                found.push(child);
+            } else {
+                found.extend(child.descendants(name));
+            }
+        }
+        found
+    }
+}
+
+/// Parse a document into its root element
+pub fn parse(text: &str) -> Result<Element> {
+    let mut reader = Reader::from_str(text);
+    // Open elements, innermost last
+    let mut open: Vec<Element> = Vec::new();
+
+    loop {
+        let event = reader
+            .read_event()
+            .with_context(|| format!("Invalid XML at byte {}", reader.error_position()))?;
+        let closed = match event {
+            Event::Start(start) => {
+                open.push(Element::open(&start));
+                continue;
+            }
+            Event::Empty(start) => Element::open(&start),
+            Event::End(_) => match open.pop() {
+                Some(element) => element,
+                None => bail!("Unexpected closing tag"),
+            },
+            Event::Text(text) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&text.xml10_content());
+                }
+                continue;
+            }
+            Event::CData(cdata) => {
+                if let Some(element) = open.last_mut() {
+                    element.text.push_str(&cdata.xml10_content());
+                }
+                continue;
+            }
+            Event::GeneralRef(reference) => {
+                if let Some(element) = open.last_mut() {
+                    match reference.resolve_char_ref() {
+                        Ok(Some(c)) => element.text.push(c),
+                        _ => match resolve_predefined_entity(&reference) {
+                            Some(value) => element.text.push_str(value),
+                            // Entities declared in a DTD aren't expanded
+                            None => {
+                                element.text.push('&');
+                                element.text.push_str(&reference);
+                                element.text.push(';');
+                            }
+                        },
+                    }
+                }
+                continue;
+            }
+            Event::Eof => bail!("The document has no root element or isn't complete"),
+            _ => continue,
+        };
+        match open.last_mut() {
+            Some(parent) => parent.children.push(closed),
+            // Anything after the root element is ignored
+            None => return Ok(closed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_resolves_entities_and_namespaces() {
+        let root = parse(
+            r#"<?xml version="1.0"?>
+<s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/">
+  <s:Body><u:Browse xmlns:u="urn:schemas-upnp-org:service:ContentDirectory:1">
+    <ObjectID>Tom &amp; Jerry&#39;s &#xE9;t&#233; <![CDATA[<raw> &amp;]]></ObjectID>
+    <Filter title="A &quot;B&quot; &#x27;C&#x27;"/>
+  </u:Browse></s:Body>
+</s:Envelope>"#,
+        )
+        .unwrap();
+
+        assert_eq!(root.name, "Envelope");
+        let browse = root.child("Body").and_then(|b| b.child("Browse")).unwrap();
+        assert_eq!(
+            browse.child_text("ObjectID"),
+            Some("Tom & Jerry's été <raw> &amp;")
+        );
+        let filter = browse.child("Filter").unwrap();
+        assert_eq!(filter.text(), None);
+        assert_eq!(filter.attribute("title"), Some("A \"B\" 'C'"));
+
+        assert!(parse("<tv><channel></tv>").is_err());
+        assert!(parse("<tv>").is_err());
+        assert!(parse("not xml").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+// Media time units
+//
+// Jellyfin measures positions and runtimes in ticks of 100 nanoseconds
+// (RunTimeTicks, PositionTicks, StartTimeTicks), ffprobe reports seconds as
+// floats, ffmpeg takes "-ss" in seconds, and LRC/EDL/Plex files use seconds or
+// milliseconds. Converting through `Ticks` instead of multiplying by literals at
+// each call site keeps those units from being mixed up. The database and DTOs
+// keep plain i64 tick columns; wrap them with `Ticks(value)` where they're used.
+
+use std::fmt;
+
+/// Ticks in one millisecond
+pub const TICKS_PER_MILLISECOND: i64 = 10_000;
+
+/// Ticks in one second
+pub const TICKS_PER_SECOND: i64 = 1000 * TICKS_PER_MILLISECOND;
+
+/// Ticks in one minute
+pub const TICKS_PER_MINUTE: i64 = 60 * TICKS_PER_SECOND;
+
+/// A position or duration in Jellyfin ticks (100 ns)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ticks(pub i64);
+
+impl Ticks {
+    pub const ZERO: Ticks = Ticks(0);
+
+    pub const fn from_millis(millis: i64) -> Self {
+        Ticks(millis * TICKS_PER_MILLISECOND)
+    }
+
+    pub const fn from_seconds(seconds: i64) -> Self {
+        Ticks(seconds * TICKS_PER_SECOND)
+    }
+
+    pub const fn from_minutes(minutes: i64) -> Self {
+        Ticks(minutes * TICKS_PER_MINUTE)
+    }
+
+    /// Fractional seconds (ffprobe durations, EDL and Kodi positions), rounded
+    /// to the nearest tick; NaN and negative values become zero
+    pub fn from_secs_f64(seconds: f64) -> Self {
+        if seconds.is_finite() && seconds > 0.0 {
+            Ticks((seconds * TICKS_PER_SECOND as f64).round() as i64)
+        } else {
+            Ticks::ZERO
+        }
+    }
+
+    pub fn as_secs_f64(self) -> f64 {
+        self.0 as f64 / TICKS_PER_SECOND as f64
+    }
+
+    /// Whole seconds, rounded down
+    pub const fn whole_seconds(self) -> i64 {
+        self.0 / TICKS_PER_SECOND
+    }
+
+    /// Whole minutes, rounded down
+    pub const fn whole_minutes(self) -> i64 {
+        self.0 / TICKS_PER_MINUTE
+    }
+
+    /// Value for ffmpeg's "-ss" option (seconds with millisecond precision)
+    pub fn to_ffmpeg(self) -> String {
+        format!("{:.3}", self.0.max(0) as f64 / TICKS_PER_SECOND as f64)
+    }
+}
+
+impl From<Ticks> for i64 {
+    fn from(ticks: Ticks) -> i64 {
+        ticks.0
+    }
+}
+
+impl std::ops::Add for Ticks {
+    type Output = Ticks;
+
+    fn add(self, other: Ticks) -> Ticks {
+        Ticks(self.0 + other.0)
+    }
+}
+
+impl std::ops::Sub for Ticks {
+    type Output = Ticks;
+
+    fn sub(self, other: Ticks) -> Ticks {
+        Ticks(self.0 - other.0)
+    }
+}
+
+/// Clock format: "MM:SS", or "HH:MM:SS" from an hour up
+impl fmt::Display for Ticks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total_seconds = self.whole_seconds();
+        let (hours, minutes, seconds) = (
+            total_seconds / 3600,
+            (total_seconds % 3600) / 60,
+            total_seconds % 60,
+        );
+        if hours > 0 {
+            write!(f, "{:02}:{:02}:{:02}", hours, minutes, seconds)
+        } else {
+            write!(f, "{:02}:{:02}", minutes, seconds)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tick_conversions() {
+        assert_eq!(
+            Ticks::from_seconds(90),
+            Ticks::from_minutes(1) + Ticks::from_millis(30_000)
+        );
+        // Rounded rather than truncated: 0.57 * 1e7 is 5699999.999...
+        assert_eq!(Ticks::from_secs_f64(0.57), Ticks(5_700_000));
+        assert_eq!(Ticks::from_secs_f64(f64::NAN), Ticks::ZERO);
+        assert_eq!(Ticks::from_secs_f64(-3.0), Ticks::ZERO);
+        assert_eq!(Ticks::from_seconds(150).as_secs_f64(), 150.0);
+        assert_eq!(Ticks(Ticks::from_minutes(2).0 - 1).whole_minutes(), 1);
+        assert_eq!(Ticks(12_345_678).to_ffmpeg(), "1.235");
+        assert_eq!(Ticks::from_seconds(5 * 60 + 30).to_string(), "05:30");
+        assert_eq!(
+            Ticks::from_seconds(3600 + 30 * 60 + 45).to_string(),
+            "01:30:45"
+        );
+    }
+}
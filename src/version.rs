@@ -0,0 +1,91 @@
+// Release version numbers
+//
+// Versions are compared in two places: the update check (is a published release
+// newer than this build?) and the schema's downgrade protection (is this build
+// at least the database's minimum?). Both read the same forms: "1.2.3", a
+// leading "v" as in release tags, missing parts counting as zero ("1.2" is
+// 1.2.0), a pre-release suffix ("-rc1") and build metadata ("+abc", ignored).
+
+use std::cmp::Ordering;
+
+/// A parsed version; orders by number, then a release after its pre-releases
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre_release: bool,
+}
+
+impl Version {
+    /// Parse "v1.2.3-rc1+abc"; None for anything that isn't up to three numbers
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+        let version = version.split('+').next().unwrap_or_default();
+        let (numbers, pre_release) = match version.split_once('-') {
+            Some((numbers, _)) => (numbers, true),
+            None => (version, false),
+        };
+
+        let mut parts = numbers.split('.').map(|p| p.parse::<u64>());
+        let major = parts.next()?.ok()?;
+        let minor = parts.next().transpose().ok()?.unwrap_or(0);
+        let patch = parts.next().transpose().ok()?.unwrap_or(0);
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Version {
+            major,
+            minor,
+            patch,
+            pre_release,
+        })
+    }
+
+    /// The version numbers without the pre-release marker
+    pub fn numbers(&self) -> (u64, u64, u64) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numbers()
+            .cmp(&other.numbers())
+            .then(other.pre_release.cmp(&self.pre_release))
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_order() {
+        assert_eq!(version("0.10.2-beta+abc").numbers(), (0, 10, 2));
+        assert!(version("0.10.2-beta+abc").pre_release);
+        assert_eq!(version("v2").numbers(), (2, 0, 0));
+        assert_eq!(version(" V1.4 ").numbers(), (1, 4, 0));
+        assert!(!version("1.0.0+build.5").pre_release);
+        for invalid in ["", "x.1", "nightly", "1.2.3.4", "1..2", "v"] {
+            assert_eq!(Version::parse(invalid), None, "{}", invalid);
+        }
+
+        assert!(version("0.10.0") > version("0.9.9"));
+        assert!(version("1.0") > version("0.9.9"));
+        assert!(version("0.2.0") > version("0.2.0-rc1"));
+        assert!(version("0.2.1-rc1") > version("0.2.0"));
+        assert_eq!(version("v0.2.0"), version("0.2.0+abc"));
+    }
+}